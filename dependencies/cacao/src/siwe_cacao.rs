@@ -10,7 +10,7 @@ pub use siwe;
 use siwe::{encode_eip55, Message, TimeStamp, VerificationError as SVE, Version as SVersion};
 use std::{fmt::Debug, ops::Not};
 use thiserror::Error;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 
 pub type SiweCacao = CACAO<Eip191, Eip4361>;
 
@@ -68,8 +68,21 @@ impl Payload {
     }
 
     pub fn valid_at(&self, t: &OffsetDateTime) -> bool {
-        self.nbf.as_ref().map(|nbf| nbf < t).unwrap_or(true)
-            && self.exp.as_ref().map(|exp| exp >= t).unwrap_or(true)
+        self.valid_at_with_tolerance(t, Duration::ZERO)
+    }
+
+    /// Whether the payload is valid at some time within `tolerance` of `t`,
+    /// for issuers whose clocks are slightly off.
+    pub fn valid_at_with_tolerance(&self, t: &OffsetDateTime, tolerance: Duration) -> bool {
+        self.nbf
+            .as_ref()
+            .map(|nbf| nbf < &(*t + tolerance))
+            .unwrap_or(true)
+            && self
+                .exp
+                .as_ref()
+                .map(|exp| exp >= &(*t - tolerance))
+                .unwrap_or(true)
     }
 
     pub fn valid_now(&self) -> bool {
@@ -1,11 +1,11 @@
 // @generated by scripts/gen-capabilities.mjs — DO NOT EDIT.
-// Source: capabilities.json (registry version 1, sha256 daecd38d908d05d622684b580501dc38c5945b0499ce7eb3a18d77663b93c73a).
+// Source: capabilities.json (registry version 1, sha256 cc50956d2e6ce23b80f4207b1f0b4dcc8570bdc47102a73735ef4d43d2efbaf2).
 //
 // Canonical single source of truth for TinyCloud capability action URNs (TC-112).
 // Regenerate with: node scripts/gen-capabilities.mjs
 
 export const REGISTRY_VERSION = 1 as const;
-export const REGISTRY_SOURCE_SHA256 = "daecd38d908d05d622684b580501dc38c5945b0499ce7eb3a18d77663b93c73a" as const;
+export const REGISTRY_SOURCE_SHA256 = "cc50956d2e6ce23b80f4207b1f0b4dcc8570bdc47102a73735ef4d43d2efbaf2" as const;
 
 /** GitHub repository the registry lives in (TC-121; js-sdk sync anchor). */
 export const REGISTRY_SOURCE_REPO = "TinyCloudLabs/tinycloud-node" as const;
@@ -14,7 +14,7 @@ export const REGISTRY_SOURCE_REPO = "TinyCloudLabs/tinycloud-node" as const;
  * in CI (GITHUB_SHA); approximate when generated locally, where it names
  * the parent of the commit that will contain this artifact.
  */
-export const REGISTRY_SOURCE_GIT_SHA = "390253aca30628f2ac2be28e64d8e3830da07aaa" as const;
+export const REGISTRY_SOURCE_GIT_SHA = "e97ea9f325320d54a6f4339e58ef63d7dc509874" as const;
 
 export type CapabilityStatus = "active" | "deprecated-alias" | "reserved";
 
@@ -27,11 +27,15 @@ export interface CapabilityEntry {
 }
 
 export const CAPABILITIES: readonly CapabilityEntry[] = [
-  { urn: "tinycloud.kv/get", service: "tinycloud.kv", status: "active" },
+  { urn: "tinycloud.kv/get", service: "tinycloud.kv", status: "active", implies: ["tinycloud.kv/exists"] },
   { urn: "tinycloud.kv/list", service: "tinycloud.kv", status: "active" },
-  { urn: "tinycloud.kv/metadata", service: "tinycloud.kv", status: "active" },
-  { urn: "tinycloud.kv/put", service: "tinycloud.kv", status: "active" },
+  { urn: "tinycloud.kv/metadata", service: "tinycloud.kv", status: "active", implies: ["tinycloud.kv/exists"] },
+  { urn: "tinycloud.kv/exists", service: "tinycloud.kv", status: "active" },
+  { urn: "tinycloud.kv/put", service: "tinycloud.kv", status: "active", implies: ["tinycloud.kv/set-metadata"] },
+  { urn: "tinycloud.kv/set-metadata", service: "tinycloud.kv", status: "active" },
   { urn: "tinycloud.kv/del", service: "tinycloud.kv", status: "active" },
+  { urn: "tinycloud.kv/copy", service: "tinycloud.kv", status: "active" },
+  { urn: "tinycloud.kv/move", service: "tinycloud.kv", status: "active" },
   { urn: "tinycloud.kv/delete", service: "tinycloud.kv", status: "deprecated-alias", aliasOf: "tinycloud.kv/del" },
   { urn: "tinycloud.sql/read", service: "tinycloud.sql", status: "active" },
   { urn: "tinycloud.sql/select", service: "tinycloud.sql", status: "deprecated-alias", aliasOf: "tinycloud.sql/read" },
@@ -47,6 +51,9 @@ export const CAPABILITIES: readonly CapabilityEntry[] = [
   { urn: "tinycloud.duckdb/select", service: "tinycloud.duckdb", status: "deprecated-alias", aliasOf: "tinycloud.duckdb/read" },
   { urn: "tinycloud.duckdb/*", service: "tinycloud.duckdb", status: "active", implies: ["tinycloud.duckdb/read", "tinycloud.duckdb/write", "tinycloud.duckdb/admin", "tinycloud.duckdb/import", "tinycloud.duckdb/export"] },
   { urn: "tinycloud.capabilities/read", service: "tinycloud.capabilities", status: "active" },
+  { urn: "tinycloud.capabilities/effective", service: "tinycloud.capabilities", status: "active" },
+  { urn: "tinycloud.capabilities/head", service: "tinycloud.capabilities", status: "active" },
+  { urn: "tinycloud.capabilities/usage", service: "tinycloud.capabilities", status: "active" },
   { urn: "tinycloud.delegation/status", service: "tinycloud.delegation", status: "active" },
   { urn: "tinycloud.delegation/list", service: "tinycloud.delegation", status: "active" },
   { urn: "tinycloud.hooks/subscribe", service: "tinycloud.hooks", status: "active" },
@@ -70,12 +77,12 @@ export const CAPABILITIES: readonly CapabilityEntry[] = [
 /// Every action URN accepted at the policy boundary for a service
 /// (active, deprecated-alias, and reserved), sorted.
 export const ACCEPTED_ACTIONS: Readonly<Record<string, readonly string[]>> = {
-  "tinycloud.capabilities": ["tinycloud.capabilities/read"],
+  "tinycloud.capabilities": ["tinycloud.capabilities/effective", "tinycloud.capabilities/head", "tinycloud.capabilities/read", "tinycloud.capabilities/usage"],
   "tinycloud.delegation": ["tinycloud.delegation/list", "tinycloud.delegation/status"],
   "tinycloud.duckdb": ["tinycloud.duckdb/*", "tinycloud.duckdb/admin", "tinycloud.duckdb/export", "tinycloud.duckdb/import", "tinycloud.duckdb/read", "tinycloud.duckdb/select", "tinycloud.duckdb/write"],
   "tinycloud.encryption": ["tinycloud.encryption/decrypt", "tinycloud.encryption/network.create", "tinycloud.encryption/network.revoke"],
   "tinycloud.hooks": ["tinycloud.hooks/list", "tinycloud.hooks/register", "tinycloud.hooks/subscribe", "tinycloud.hooks/unregister"],
-  "tinycloud.kv": ["tinycloud.kv/del", "tinycloud.kv/delete", "tinycloud.kv/get", "tinycloud.kv/list", "tinycloud.kv/metadata", "tinycloud.kv/put"],
+  "tinycloud.kv": ["tinycloud.kv/copy", "tinycloud.kv/del", "tinycloud.kv/delete", "tinycloud.kv/exists", "tinycloud.kv/get", "tinycloud.kv/list", "tinycloud.kv/metadata", "tinycloud.kv/move", "tinycloud.kv/put", "tinycloud.kv/set-metadata"],
   "tinycloud.space": ["tinycloud.space/create", "tinycloud.space/host", "tinycloud.space/info", "tinycloud.space/list"],
   "tinycloud.sql": ["tinycloud.sql/*", "tinycloud.sql/admin", "tinycloud.sql/read", "tinycloud.sql/schema", "tinycloud.sql/select", "tinycloud.sql/write"],
   "tinycloud.vfs": ["tinycloud.vfs/delete", "tinycloud.vfs/get", "tinycloud.vfs/list", "tinycloud.vfs/metadata", "tinycloud.vfs/put"],
@@ -91,6 +98,9 @@ export const ALIASES: Readonly<Record<string, string>> = {
 /// urn -> directly implied URNs.
 export const IMPLICATIONS: Readonly<Record<string, readonly string[]>> = {
   "tinycloud.duckdb/*": ["tinycloud.duckdb/admin", "tinycloud.duckdb/export", "tinycloud.duckdb/import", "tinycloud.duckdb/read", "tinycloud.duckdb/write"],
+  "tinycloud.kv/get": ["tinycloud.kv/exists"],
+  "tinycloud.kv/metadata": ["tinycloud.kv/exists"],
+  "tinycloud.kv/put": ["tinycloud.kv/set-metadata"],
   "tinycloud.sql/*": ["tinycloud.sql/admin", "tinycloud.sql/read", "tinycloud.sql/schema", "tinycloud.sql/write"],
   "tinycloud.sql/admin": ["tinycloud.sql/schema"],
 };
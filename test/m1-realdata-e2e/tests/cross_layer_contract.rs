@@ -244,6 +244,7 @@ secret = "{}"
     let space_id = SpaceId::new(owner_did.parse::<DIDBuf>()?, SPACE_NAME.parse()?);
     space::ActiveModel {
         id: Set(SpaceIdWrap(space_id.clone())),
+        ..Default::default()
     }
     .insert(&conn)
     .await?;
@@ -1236,6 +1237,7 @@ async fn observe_grant_output_on_real_node(
     let space_id = SpaceId::new(owner_did.parse::<DIDBuf>()?, "default".parse()?);
     space::ActiveModel {
         id: Set(SpaceIdWrap(space_id.clone())),
+        ..Default::default()
     }
     .insert(conn)
     .await?;
@@ -1869,6 +1871,7 @@ async fn exercise_engine_sql_kv_mix(context: EngineSqlKvContext<'_>) -> Result<(
     let space_id = SpaceId::new(owner_did.parse::<DIDBuf>()?, "default".parse()?);
     space::ActiveModel {
         id: Set(SpaceIdWrap(space_id.clone())),
+        ..Default::default()
     }
     .insert(context.conn)
     .await?;
@@ -171,6 +171,7 @@ secret = "{}"
     let owner_did = space_id.did().to_string();
     space::ActiveModel {
         id: Set(SpaceIdWrap(space_id.clone())),
+        ..Default::default()
     }
     .insert(&conn)
     .await?;
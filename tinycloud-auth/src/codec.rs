@@ -0,0 +1,37 @@
+//! The multicodecs of the CIDs TinyCloud hands out.
+//!
+//! Clients and the node must agree on these for a delegation CID computed by
+//! one to name the delegation stored by the other.
+use ipld_core::cid::Cid;
+use multihash_codetable::{Code, MultihashDigest};
+
+/// Raw bytes: events, stored values and anything else addressed by the hash
+/// of its bytes.
+pub const RAW: u64 = 0x55;
+/// DAG-CBOR: the links inside an epoch, to its parents and to the operations
+/// and values its invocations recorded.
+pub const DAG_CBOR: u64 = 0x71;
+
+/// The multihash every CID TinyCloud hands out is taken with: of events, of
+/// stored values and of epochs. Space and resource IDs are not content, and
+/// keep their own.
+pub const CONTENT_HASH: Code = Code::Blake3_256;
+
+/// The CID of a delegation, invocation or revocation, from the bytes it was
+/// serialized to: the JWT of a UCAN or the DAG-CBOR of a CACAO.
+pub fn to_event_cid(serialization: &[u8]) -> Cid {
+    Cid::new_v1(RAW, CONTENT_HASH.digest(serialization))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_cids_are_raw_blake3() {
+        let cid = to_event_cid(b"header.payload.signature");
+        assert_eq!(cid.codec(), RAW);
+        assert_eq!(cid.hash().code(), u64::from(Code::Blake3_256));
+        assert_ne!(cid, to_event_cid(b"header.payload.other"));
+    }
+}
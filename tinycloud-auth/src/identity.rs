@@ -21,6 +21,8 @@ pub enum IdentityError {
     InvalidPkhDid,
     #[error("invalid DID")]
     InvalidDid,
+    #[error("invalid did:web DID")]
+    InvalidWebDid,
 }
 
 fn hex_nibble(index: usize, c: char) -> Result<u8, IdentityError> {
@@ -74,10 +76,56 @@ pub fn parse_pkh_did(did: &str) -> Result<Option<PkhDid>, IdentityError> {
     }))
 }
 
+/// Canonicalize the method-specific id of a did:web DID.
+///
+/// The id is a host (with its port colon percent-encoded, e.g.
+/// `example.com%3A8443`) optionally followed by colon-separated path segments.
+/// Hosts are case-insensitive and lowercased; percent-escapes are uppercased so
+/// that equivalent spellings compare equal.
+fn canonicalize_web_did(id: &str) -> Result<String, IdentityError> {
+    let mut segments = id.split(':');
+    let host = segments.next().unwrap_or_default();
+    if host.is_empty() {
+        return Err(IdentityError::InvalidWebDid);
+    }
+    let mut canonical = format!(
+        "did:web:{}",
+        uppercase_percent_escapes(&host.to_lowercase())?
+    );
+    for segment in segments {
+        if segment.is_empty() {
+            return Err(IdentityError::InvalidWebDid);
+        }
+        canonical.push(':');
+        canonical.push_str(&uppercase_percent_escapes(segment)?);
+    }
+    Ok(canonical)
+}
+
+pub(crate) fn uppercase_percent_escapes(s: &str) -> Result<String, IdentityError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        out.push(c);
+        if c == '%' {
+            for _ in 0..2 {
+                match chars.next() {
+                    Some(h) if h.is_ascii_hexdigit() => out.push(h.to_ascii_uppercase()),
+                    _ => return Err(IdentityError::InvalidWebDid),
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
 pub fn canonicalize_did(did: &str) -> Result<String, IdentityError> {
     if let Some(pkh) = parse_pkh_did(did)? {
         return Ok(format!("did:pkh:eip155:{}:{}", pkh.chain_id, pkh.address));
     }
+    if let Some(id) = did.strip_prefix("did:web:") {
+        return canonicalize_web_did(id);
+    }
     if did
         .strip_prefix("did:")
         .and_then(|rest| rest.split_once(':'))
@@ -172,6 +220,26 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn canonicalizes_web_dids() {
+        assert_eq!(
+            canonicalize_did("did:web:Example.COM%3a8443:user:alice").unwrap(),
+            "did:web:example.com%3A8443:user:alice"
+        );
+        assert!(did_principal_matches(
+            "did:web:example.com%3a8443#key-1",
+            "did:web:example.com%3A8443"
+        ));
+        assert_eq!(
+            canonicalize_did("did:web:example.com::alice").unwrap_err(),
+            IdentityError::InvalidWebDid
+        );
+        assert_eq!(
+            canonicalize_did("did:web:example.com%3").unwrap_err(),
+            IdentityError::InvalidWebDid
+        );
+    }
+
     #[test]
     fn rejects_invalid_supported_pkh_dids() {
         assert_eq!(
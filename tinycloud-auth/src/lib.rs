@@ -1,4 +1,5 @@
 pub mod authorization;
+pub mod codec;
 pub mod identity;
 pub mod policy_capability;
 pub mod protocol;
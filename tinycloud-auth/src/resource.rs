@@ -6,10 +6,13 @@ use serde::Serialize;
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use ssi::dids::{DIDBuf, DID};
 
-use crate::identity::canonicalize_did;
+use crate::identity::{canonicalize_did, uppercase_percent_escapes};
 use std::{convert::TryFrom, fmt, str::FromStr};
 use thiserror::Error;
 
+/// Longest space name accepted.
+pub const MAX_NAME_LENGTH: usize = 64;
+
 #[derive(Clone, Hash, PartialEq, Debug, Eq, Serialize, DeserializeFromStr, PartialOrd, Ord)]
 pub struct Name(String);
 
@@ -17,6 +20,28 @@ impl Name {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Space names end up in URIs and storage paths, so they are limited to
+    /// ASCII alphanumerics, `-`, `_` and `.`, and may not be `.` or `..`.
+    fn validate(name: &str) -> Result<(), KRIParseError> {
+        let invalid = |reason: String| Err(KRIParseError::InvalidName(reason));
+        if name.is_empty() {
+            invalid("space name is empty".to_string())
+        } else if name.len() > MAX_NAME_LENGTH {
+            invalid(format!(
+                "space name {name:?} is longer than {MAX_NAME_LENGTH} characters"
+            ))
+        } else if let Some(c) = name
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        {
+            invalid(format!("space name {name:?} contains {c:?}"))
+        } else if name == "." || name == ".." {
+            invalid(format!("space name {name:?} is reserved"))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl fmt::Display for Name {
@@ -28,8 +53,8 @@ impl fmt::Display for Name {
 impl TryFrom<String> for Name {
     type Error = KRIParseError;
 
-    // TODO finish this by doing validation
     fn try_from(n: String) -> Result<Self, Self::Error> {
+        Self::validate(&n)?;
         Ok(Self(n))
     }
 }
@@ -37,8 +62,8 @@ impl TryFrom<String> for Name {
 impl FromStr for Name {
     type Err = KRIParseError;
 
-    // TODO finish this by doing validation
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::validate(s)?;
         Ok(Self(s.to_string()))
     }
 }
@@ -70,7 +95,7 @@ impl SpaceId {
 
     pub fn get_cid(&self) -> Cid {
         Cid::new_v1(
-            0x55, // raw codec
+            crate::codec::RAW,
             Code::Blake2b256.digest(self.to_string().as_bytes()),
         )
     }
@@ -201,12 +226,7 @@ impl ResourceId {
             self.path().map(|p| p.as_str()),
             base.path().map(|p| p.as_str()),
         ) {
-            (Some(s), Some(b)) => {
-                !s.starts_with(b)
-                    || !(b.ends_with('/')
-                        || s.len() == b.len()
-                        || s.as_bytes().get(b.len()) == Some(&b'/'))
-            }
+            (Some(s), Some(b)) => !path_extends(s, b),
             (Some(_), None) | (None, None) => false,
             (None, Some(_)) => true,
         } {
@@ -236,7 +256,7 @@ impl ResourceId {
 
     pub fn get_cid(&self) -> Cid {
         Cid::new_v1(
-            0x55, // raw codec
+            crate::codec::RAW,
             Code::Blake2b256.digest(self.to_string().as_bytes()),
         )
     }
@@ -247,6 +267,23 @@ impl ResourceId {
     }
 }
 
+/// Whether path `s` lies within base path `b`.
+///
+/// An empty base (or `*`) covers every path, mirroring how a resource without
+/// a path covers the whole service. A trailing `/*` segment covers everything
+/// below its prefix. Otherwise `s` must equal `b` or continue it at a segment
+/// boundary, so `foo` covers `foo/bar` but not `foobar`.
+fn path_extends(s: &str, b: &str) -> bool {
+    if b.is_empty() || b == "*" {
+        return true;
+    }
+    if let Some(prefix) = b.strip_suffix('*').filter(|p| p.ends_with('/')) {
+        return s.starts_with(prefix);
+    }
+    s.starts_with(b)
+        && (b.ends_with('/') || s.len() == b.len() || s.as_bytes().get(b.len()) == Some(&b'/'))
+}
+
 #[derive(Error, Debug)]
 pub enum ResourceCheckError {
     #[error("Base and Extension Spaces do not match")]
@@ -285,8 +322,8 @@ impl fmt::Display for ResourceId {
 pub enum KRIParseError {
     #[error("Incorrect Structure")]
     IncorrectForm,
-    #[error("Invalid Name")]
-    InvalidName,
+    #[error("Invalid Name: {0}")]
+    InvalidName(String),
     #[error("Invalid Service")]
     InvalidService,
     #[error("Invalid Path")]
@@ -322,17 +359,20 @@ impl TryFrom<&UriStr> for SpaceId {
                 Some((suf, name))
             }
         }) {
-            Ok(Self::new(did_from_suffix(suf)?, Name(name.to_string())))
+            Ok(Self::new(did_from_suffix(suf)?, name.parse()?))
         } else {
             Err(KRIParseError::IncorrectForm)
         }
     }
 }
 
+// Percent-escapes are case-insensitive but only the uppercase form is
+// normalized, so accept e.g. a did:web port written as `%3a` by uppercasing
+// escapes before the normalization check in `TryFrom<&UriStr>`.
 impl FromStr for SpaceId {
     type Err = KRIParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        UriStr::new(s)?.try_into()
+        UriStr::new(&uppercase_percent_escapes(s).unwrap_or_else(|_| s.into()))?.try_into()
     }
 }
 
@@ -361,7 +401,7 @@ impl TryFrom<&UriStr> for ResourceId {
             })
         {
             Ok(
-                SpaceId::new(did_from_suffix(suf)?, Name(name.to_string())).to_resource(
+                SpaceId::new(did_from_suffix(suf)?, name.parse()?).to_resource(
                     Service(service.to_string()),
                     path.map(|p| Path(p.to_string())),
                     uri.query().map(|q| q.into()),
@@ -377,7 +417,7 @@ impl TryFrom<&UriStr> for ResourceId {
 impl FromStr for ResourceId {
     type Err = KRIParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        UriStr::new(s)?.try_into()
+        UriStr::new(&uppercase_percent_escapes(s).unwrap_or_else(|_| s.into()))?.try_into()
     }
 }
 
@@ -442,6 +482,47 @@ mod tests {
         assert!(invalid_name.is_err());
     }
 
+    #[test]
+    fn space_names_are_validated() {
+        let longest = "a".repeat(MAX_NAME_LENGTH);
+        for name in ["default", "ns0", "my-space_2.v1", "..a", longest.as_str()] {
+            assert_eq!(name.parse::<Name>().unwrap().as_str(), name);
+        }
+        for name in [
+            "",
+            ".",
+            "..",
+            "../etc",
+            "a/b",
+            "a:b",
+            "my space",
+            "tab\t",
+            "nul\0",
+            "caf\u{e9}",
+            &format!("{longest}a"),
+        ] {
+            assert!(
+                matches!(
+                    Name::try_from(name.to_string()),
+                    Err(KRIParseError::InvalidName(_))
+                ),
+                "{name:?} was accepted"
+            );
+        }
+        assert_eq!(
+            "a:b".parse::<Name>().unwrap_err().to_string(),
+            "Invalid Name: space name \"a:b\" contains ':'"
+        );
+
+        // names parsed from space and resource URIs are validated too
+        assert!("tinycloud:ens:example.eth:my%20space"
+            .parse::<SpaceId>()
+            .is_err());
+        assert!("tinycloud:ens:example.eth:my%20space/kv/path"
+            .parse::<ResourceId>()
+            .is_err());
+    }
+
     #[test]
     fn little_test() {
         let _: SpaceId = "tinycloud:pkh:eth:0xb1fef8ed913821b941a76de9fc7c41b90de3d37f:default"
@@ -471,6 +552,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn round_trips_non_eip155_pkh_spaces() {
+        for space in [
+            "tinycloud:pkh:solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:7S3P4HxJpyyigGzodYwHtCxZyUQe9JiBMHyRWXArAaKv:default",
+            "tinycloud:pkh:tezos:NetXdQprcVkpaWU:tz1TzrmTBSuiVHV2VfMnGRMYvTEPCP42oSM8:default",
+        ] {
+            let parsed: SpaceId = space.parse().unwrap();
+            assert_eq!(parsed.to_string(), space);
+            assert_eq!(parsed.name().as_str(), "default");
+            assert_eq!(
+                parsed.did().as_str(),
+                format!("did:{}", &space["tinycloud:".len()..space.len() - ":default".len()])
+            );
+
+            let resource: ResourceId = format!("{space}/kv/path").parse().unwrap();
+            assert_eq!(resource.space(), &parsed);
+            assert_eq!(resource.to_string(), format!("{space}/kv/path"));
+        }
+    }
+
+    #[test]
+    fn wildcard_paths_extend() {
+        let kv = |path: &str| -> ResourceId {
+            format!("tinycloud:ens:example.eth:ns0/kv/{path}")
+                .parse()
+                .unwrap()
+        };
+        let extends = |child: &str, base: &str| kv(child).extends(&kv(base)).is_ok();
+
+        // an empty base path and a bare `*` cover every path
+        assert!(extends("docs/a.txt", ""));
+        assert!(extends("", ""));
+        assert!(extends("docs/a.txt", "*"));
+
+        // a trailing wildcard segment covers everything below its prefix
+        assert!(extends("docs/a.txt", "docs/*"));
+        assert!(extends("docs/nested/a.txt", "docs/*"));
+        assert!(extends("docs/*", "docs/*"));
+        assert!(!extends("docs", "docs/*"));
+        assert!(!extends("docsx/a.txt", "docs/*"));
+
+        // a wildcard child cannot widen a specific parent
+        assert!(!extends("*", "docs/"));
+        assert!(!extends("", "docs"));
+        assert!(extends("docs/*", "docs"));
+
+        // prefix collisions are still rejected
+        assert!(!extends("foobar", "foo"));
+        assert!(extends("foo/bar", "foo"));
+    }
+
+    #[test]
+    fn web_dids_with_ports_and_paths() {
+        let uri = "tinycloud:web:example.com%3A8443:myspace/kv/path";
+        let res: ResourceId = uri.parse().unwrap();
+        assert_eq!("web:example.com%3A8443", res.space().suffix());
+        assert_eq!("did:web:example.com%3A8443", res.space().did().as_str());
+        assert_eq!("web", res.space().did().method_name());
+        assert_eq!("myspace", res.space().name().as_str());
+        assert_eq!(Some("path"), res.path().map(|p| p.as_str()));
+        assert_eq!(uri, res.to_string());
+
+        // path segments of the did:web id are colon-separated, and only the
+        // last colon separates the space name
+        let space: SpaceId = "tinycloud:web:example.com%3A8443:user:alice:myspace"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "did:web:example.com%3A8443:user:alice",
+            space.did().as_str()
+        );
+        assert_eq!("myspace", space.name().as_str());
+        assert_eq!(space, space.to_string().parse().unwrap());
+
+        // lowercase escapes and host case parse to the same space
+        let lower: ResourceId = "tinycloud:web:Example.com%3a8443:myspace/kv/path"
+            .parse()
+            .unwrap();
+        assert_eq!(lower, res);
+
+        let empty_segment: Result<SpaceId, _> = "tinycloud:web:example.com::myspace".parse();
+        assert!(empty_segment.is_err());
+    }
+
     #[test]
     fn roundtrip() {
         let resource_uri: String = "tinycloud:ens:example.eth:ns0/kv/prefix#list".into();
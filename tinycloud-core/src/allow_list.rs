@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+use std::{error::Error as StdError, fmt::Debug};
+use tinycloud_auth::resource::SpaceId;
+
+/// Decides whether a space may be created on this node.
+///
+/// Consulted only when a `tinycloud.space/host` delegation would create a
+/// space that does not exist yet; spaces already hosted are never re-checked.
+#[async_trait]
+pub trait SpaceAllowList: Debug + Send + Sync {
+    async fn is_allowed(&self, space: &SpaceId) -> Result<bool, AllowListError>;
+}
+
+/// The allowlist could not be consulted, as opposed to denying the space.
+#[derive(Debug, thiserror::Error)]
+#[error("space allowlist check failed: {0}")]
+pub struct AllowListError(#[from] pub Box<dyn StdError + Send + Sync>);
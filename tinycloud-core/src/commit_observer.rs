@@ -0,0 +1,32 @@
+use crate::db::Commit;
+use serde::Serialize;
+use std::fmt::Debug;
+use tinycloud_auth::resource::SpaceId;
+
+/// What a commit to a space recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitKind {
+    /// An invocation that wrote to or deleted from the space's KV store.
+    Write,
+    Delegation,
+    Revocation,
+}
+
+impl CommitKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Write => "write",
+            Self::Delegation => "delegation",
+            Self::Revocation => "revocation",
+        }
+    }
+}
+
+/// Told about every commit to a space once its transaction has committed.
+///
+/// Invocations that only read are not reported. Called on the task that made
+/// the commit, so implementations hand the commit off instead of doing I/O.
+pub trait CommitObserver: Debug + Send + Sync {
+    fn committed(&self, kind: CommitKind, space: &SpaceId, commit: &Commit);
+}
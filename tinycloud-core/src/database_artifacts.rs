@@ -92,7 +92,7 @@ impl DatabaseArtifactRepository for SeaOrmDatabaseArtifactRepository {
     ) -> Result<DatabaseArtifact, DatabaseArtifactError> {
         let size_bytes = i64::try_from(payload.len())
             .map_err(|_| DatabaseArtifactError::PayloadTooLarge(payload.len() as u64))?;
-        let content_hash = hash(&payload).to_cid(0x55).to_string();
+        let content_hash = hash(&payload).to_block_cid().to_string();
         let now = OffsetDateTime::now_utc()
             .format(&Rfc3339)
             .expect("current timestamps should format as RFC3339");
@@ -1,26 +1,33 @@
+use crate::allow_list::{AllowListError, SpaceAllowList};
+use crate::commit_observer::{CommitKind, CommitObserver};
 use crate::encryption::ColumnEncryption;
+use crate::event_log::{LogEntry, LogError};
 use crate::events::{epoch_hash, Delegation, Event, HashError, Invocation, Operation, Revocation};
 use crate::hash::Hash;
 use crate::keys::{get_did_key, Secrets};
 use crate::migrations::Migrator;
-use crate::models::*;
+use crate::models::{delegation::DelegationLimits, *};
+use crate::policy::{AllowAll, AuthContext, Policy, PolicyError};
 use crate::relationships::*;
+use crate::resolver::DidResolver;
 use crate::sql_sizes::SqlSizes;
 use crate::storage::{
-    either::EitherError, Content, HashBuffer, ImmutableReadStore, ImmutableStaging,
-    ImmutableWriteStore, StorageSetup, StoreSize,
+    either::EitherError, Content, HashBuffer, ImmutableDeleteStore, ImmutableListStore,
+    ImmutableReadStore, ImmutableStaging, ImmutableWriteStore, KeyedWriteError, Ranged,
+    StorageHealth, StorageSetup, StoreSize,
 };
 use crate::types::{
-    AccountDelegationRecord, CapabilitiesReadParams, DelegationQuery, DelegationQueryDirection,
-    DelegationQueryPage, DelegationQueryStatus, DelegationResource, ListFilters, Metadata,
-    Resource, SpaceIdWrap,
+    AccountDelegationRecord, CapabilitiesAbility, CapabilitiesReadParams, DelegationQuery,
+    DelegationQueryDirection, DelegationQueryPage, DelegationQueryStatus, DelegationResource,
+    KvAbility, ListFilters, Metadata, Resource, SpaceIdWrap, TinyCloudAbility,
 };
 use crate::util::{Capability, DelegationInfo, DelegationMode};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use sea_orm::{
     entity::prelude::*,
     error::{DbErr, RuntimeErr, SqlxError},
     query::*,
-    sea_query::{Alias, Expr, LikeExpr, OnConflict, Query},
+    sea_query::{Alias, Asterisk, Expr, Func, LikeExpr, OnConflict, Query},
     ActiveValue::Set,
     ConnectionTrait, DatabaseTransaction, IntoActiveModel, TransactionTrait,
 };
@@ -33,6 +40,7 @@ use tinycloud_auth::{
     identity::{canonicalize_did, did_principal_matches},
     resource::{Path, SpaceId},
 };
+use tokio::time::Instant;
 
 pub const HOOK_DELIVERY_STATUS_PENDING: &str = "pending";
 pub const HOOK_DELIVERY_STATUS_RETRYING: &str = "retrying";
@@ -64,12 +72,36 @@ pub enum AccountDelegationQueryError {
     Unauthorized,
 }
 
+/// Failure to delete a space with [`SpaceDatabase::delete_space`].
+#[derive(Debug, thiserror::Error)]
+pub enum SpaceDeleteError<L, R> {
+    #[error(transparent)]
+    Db(#[from] DbErr),
+    #[error("failed to list the space's blocks: {0}")]
+    ListBlocks(#[source] L),
+    #[error("failed to remove a block: {0}")]
+    RemoveBlock(#[source] R),
+}
+
+/// Staged values of one invocation written to the block store at once, unless
+/// set with [`SpaceDatabase::with_persist_concurrency`].
+pub const DEFAULT_PERSIST_CONCURRENCY: usize = 8;
+
 #[derive(Debug, Clone)]
 pub struct SpaceDatabase<C, B, S> {
     conn: C,
+    // serves reads made outside an invocation; `conn` when unset
+    replica: Option<C>,
     storage: B,
     secrets: S,
     encryption: Option<ColumnEncryption>,
+    allow_list: Option<Arc<dyn SpaceAllowList>>,
+    policy: Arc<dyn Policy>,
+    commit_observers: Vec<Arc<dyn CommitObserver>>,
+    delegation_limits: DelegationLimits,
+    // staged values of one invocation written to the store at once
+    persist_concurrency: usize,
+    resolver: DidResolver,
     sql_sizes: SqlSizes,
     revocation_chain_locks: Arc<tokio::sync::Mutex<HashMap<Hash, Weak<tokio::sync::Mutex<()>>>>>,
     kv_object_locks: KvObjectLockRegistry,
@@ -96,6 +128,22 @@ pub struct KvInvokeOptions {
     pub preconditions: HashMap<(SpaceId, Path), KvPrecondition>,
     pub max_response_bytes: Option<u64>,
     pub list_limit: Option<usize>,
+    /// Return metadata and content hashes alongside listed keys.
+    pub list_detailed: bool,
+    /// Verify and authorize the invocation, then roll back without touching
+    /// the store. The only outcome is [`InvocationOutcome::DryRun`].
+    pub dry_run: bool,
+    /// Hashes the client expects put values to have. A value that hashes
+    /// differently fails the invocation without being written.
+    pub content_hashes: HashMap<(SpaceId, Path), Hash>,
+    /// Metadata merged into the current metadata of keys invoked with
+    /// `tinycloud.kv/set-metadata`.
+    pub metadata_updates: HashMap<(SpaceId, Path), Metadata>,
+    /// Storage limits of spaces, reported by `tinycloud.capabilities/usage`.
+    pub quota_limits: HashMap<SpaceId, u64>,
+    /// Give up with [`TxError::TimedOut`] if the invocation has not started
+    /// committing by then. A commit, once started, always runs to completion.
+    pub deadline: Option<Instant>,
 }
 
 #[derive(Debug, Clone)]
@@ -148,6 +196,14 @@ pub enum TxError<S: StorageSetup, K: Secrets> {
     Secrets(K::Error),
     #[error("Space not found")]
     SpaceNotFound,
+    #[error("Space not allowed: {0}")]
+    SpaceNotAllowed(SpaceId),
+    #[error("Space is frozen: {0}")]
+    SpaceFrozen(SpaceId),
+    #[error(transparent)]
+    AllowList(#[from] AllowListError),
+    #[error(transparent)]
+    Policy(#[from] PolicyError),
     #[error("epoch insert failed: {0}")]
     EpochInsert(DbErr),
     #[error("Invalid delegation CID: {0}")]
@@ -156,6 +212,16 @@ pub enum TxError<S: StorageSetup, K: Secrets> {
     Encryption(#[from] crate::encryption::EncryptionError),
     #[error("delegation-chain-traversal-limit-exceeded")]
     ChainTraversalLimitExceeded,
+    #[error("{kind} has {count} capabilities, more than the {max} allowed")]
+    TooManyCapabilities {
+        kind: &'static str,
+        count: usize,
+        max: usize,
+    },
+    #[error(transparent)]
+    EventLog(#[from] LogError),
+    #[error("timed out before committing")]
+    TimedOut,
 }
 
 #[non_exhaustive]
@@ -183,6 +249,16 @@ where
     KvSerializationConflict,
     #[error("KV response is {size} bytes, exceeding the requested limit of {limit} bytes")]
     KvResponseTooLarge { size: u64, limit: u64 },
+    #[error("No Such Key: {0}")]
+    KvSourceNotFound(Path),
+    #[error("No Such Key: {0}")]
+    KvKeyNotFound(Path),
+    #[error("content written to {0} does not match its expected hash")]
+    KvContentHashMismatch(Path),
+    #[error("invalid kvVersions fact: {0}")]
+    KvInvalidVersions(String),
+    #[error("a copy cannot delete its source {0}; move it instead")]
+    KvCopyDeletesSource(Path),
 }
 
 impl<B, S, K> From<DbErr> for TxStoreError<B, S, K>
@@ -202,9 +278,16 @@ impl<B, K> SpaceDatabase<DatabaseConnection, B, K> {
         Migrator::up(&conn, None).await?;
         Ok(Self {
             conn,
+            replica: None,
             storage,
             secrets,
             encryption: None,
+            allow_list: None,
+            policy: Arc::new(AllowAll),
+            commit_observers: Vec::new(),
+            delegation_limits: DelegationLimits::default(),
+            persist_concurrency: DEFAULT_PERSIST_CONCURRENCY,
+            resolver: DidResolver::default(),
             sql_sizes: SqlSizes::default(),
             revocation_chain_locks: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
             kv_object_locks: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
@@ -216,18 +299,129 @@ impl<B, K> SpaceDatabase<DatabaseConnection, B, K> {
         self
     }
 
+    /// Restrict which new spaces may be created by host delegations.
+    pub fn with_allow_list(mut self, allow_list: Option<Arc<dyn SpaceAllowList>>) -> Self {
+        self.allow_list = allow_list;
+        self
+    }
+
+    /// Consult `policy` before committing any invocation, in addition to
+    /// checking its capabilities.
+    pub fn with_policy(mut self, policy: Arc<dyn Policy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Also report every write, delegation and revocation committed from now
+    /// on to `observer`.
+    pub fn with_commit_observer(mut self, observer: Option<Arc<dyn CommitObserver>>) -> Self {
+        self.commit_observers.extend(observer);
+        self
+    }
+
+    /// Bound the depth and lifetime of delegations registered from now on.
+    pub fn with_delegation_limits(mut self, limits: DelegationLimits) -> Self {
+        self.delegation_limits = limits;
+        self
+    }
+
+    /// Write up to `limit` of the values put by one invocation to the block
+    /// store at once. A limit of 0 is treated as 1.
+    pub fn with_persist_concurrency(mut self, limit: usize) -> Self {
+        self.persist_concurrency = limit.max(1);
+        self
+    }
+
+    /// Resolve the DIDs of delegation issuers with `resolver`.
+    pub fn with_resolver(mut self, resolver: DidResolver) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
     pub fn with_sql_sizes(mut self, sql_sizes: SqlSizes) -> Self {
         self.sql_sizes = sql_sizes;
         self
     }
+
+    /// Serve reads made outside an invocation (`readable`, KV gets, lists and
+    /// metadata) from a read replica. Invocations, including the delegation
+    /// lookups they make, always use the primary, so their reads are
+    /// consistent with their writes; standalone reads may lag the primary by
+    /// the replica's replication delay.
+    pub fn with_replica(mut self, replica: Option<DatabaseConnection>) -> Self {
+        self.replica = replica;
+        self
+    }
+}
+
+impl<C, B, K> SpaceDatabase<C, B, K> {
+    fn reader(&self) -> &C {
+        self.replica.as_ref().unwrap_or(&self.conn)
+    }
+
+    /// The resolver DIDs of delegation issuers are resolved with.
+    pub fn resolver(&self) -> &DidResolver {
+        &self.resolver
+    }
 }
 
 impl<C, B, K> SpaceDatabase<C, B, K>
 where
+    C: ConnectionTrait,
     K: Secrets,
 {
-    pub async fn stage_key(&self, space_id: &SpaceId) -> Result<String, K::Error> {
-        self.secrets.stage_keypair(space_id).await.map(get_did_key)
+    /// Stage the host key for `space_id` and return its did:key.
+    ///
+    /// A space already hosted under a key from an earlier secret rotation
+    /// keeps that key, so existing host delegations stay valid; otherwise the
+    /// current key is staged.
+    pub async fn stage_key(
+        &self,
+        space_id: &SpaceId,
+    ) -> Result<String, EitherError<DbErr, K::Error>> {
+        let hosts = abilities::Entity::find()
+            .filter(abilities::Column::Resource.eq(Resource::TinyCloud(
+                space_id.clone().to_resource(
+                    "space".parse().expect("space is a valid service name"),
+                    None,
+                    None,
+                    None,
+                ),
+            )))
+            .filter(
+                abilities::Column::Ability.eq(crate::types::Ability::try_from(
+                    "tinycloud.space/host".to_string(),
+                )
+                .expect("tinycloud.space/host is a valid ability string")),
+            )
+            .find_also_related(delegation::Entity)
+            .all(&self.conn)
+            .await
+            .map_err(EitherError::A)?
+            .into_iter()
+            .filter_map(|(_, delegation)| delegation.map(|d| d.delegatee))
+            .collect::<HashSet<String>>();
+
+        if !hosts.is_empty() {
+            let candidates = self
+                .secrets
+                .candidate_keypairs(space_id)
+                .await
+                .map_err(EitherError::B)?;
+            if let Some(did) = candidates
+                .into_iter()
+                .map(|keypair| get_did_key(keypair.public()))
+                .find(|did| hosts.contains(did))
+            {
+                return Ok(did);
+            }
+        }
+
+        self.secrets
+            .stage_keypair(space_id)
+            .await
+            .map(get_did_key)
+            .map_err(EitherError::B)
     }
 }
 
@@ -237,16 +431,120 @@ where
 {
     // to allow users to make custom read queries
     pub async fn readable(&self) -> Result<DatabaseTransaction, DbErr> {
-        self.conn
+        self.reader()
             .begin_with_config(None, Some(sea_orm::AccessMode::ReadOnly))
             .await
     }
+
+    /// Prune delegations that expired before `before`, less the configured
+    /// clock skew, returning how many were pruned.
+    ///
+    /// Pruning removes what lets a delegation authorize anything: its
+    /// abilities and its links to its parents. The delegation itself and its
+    /// places in the event log are kept, as the log serves it and the epochs
+    /// holding it are hashed over it, so `/log` and replicas verifying epochs
+    /// see the same history as before. A pruned delegation is one with no
+    /// abilities left, and is not pruned again.
+    ///
+    /// Revoked delegations are kept so their revocations stay resolvable, and
+    /// an expired delegation is kept for as long as any delegation naming it
+    /// as a parent is kept. Invocations citing it do not keep it, as nothing
+    /// checks their proofs again once they are committed. A delegation within
+    /// the clock skew of its expiry is still accepted, so it is not pruned.
+    pub async fn prune_expired_delegations(&self, before: OffsetDateTime) -> Result<u64, DbErr> {
+        let before = before - self.delegation_limits.clock_skew;
+        let tx = self.conn.begin().await?;
+
+        let mut prunable: HashSet<Hash> = delegation::Entity::find()
+            .left_join(revocation::Entity)
+            .filter(revocation::Column::Id.is_null())
+            .filter(delegation::Column::Expiry.lt(before))
+            .filter(delegation::Column::Id.in_subquery(delegations_granting(Condition::all())))
+            .all(&tx)
+            .await?
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        if prunable.is_empty() {
+            return Ok(0);
+        }
+
+        let edges = parent_delegations::Entity::find()
+            .filter(parent_delegations::Column::Parent.is_in(prunable.iter().copied()))
+            .all(&tx)
+            .await?;
+        let child_delegations: HashSet<Hash> = delegation::Entity::find()
+            .select_only()
+            .column(delegation::Column::Id)
+            .filter(delegation::Column::Id.is_in(edges.iter().map(|edge| edge.child)))
+            .into_tuple::<Hash>()
+            .all(&tx)
+            .await?
+            .into_iter()
+            .collect();
+        let edges: Vec<_> = edges
+            .into_iter()
+            .filter(|edge| child_delegations.contains(&edge.child))
+            .collect();
+        // keeping a child keeps its parents, which may in turn keep theirs
+        loop {
+            let referenced: HashSet<Hash> = edges
+                .iter()
+                .filter(|edge| !prunable.contains(&edge.child))
+                .map(|edge| edge.parent)
+                .collect();
+            let remaining = prunable.len();
+            prunable.retain(|id| !referenced.contains(id));
+            if prunable.len() == remaining {
+                break;
+            }
+        }
+        if prunable.is_empty() {
+            return Ok(0);
+        }
+
+        abilities::Entity::delete_many()
+            .filter(abilities::Column::Delegation.is_in(prunable.iter().copied()))
+            .exec(&tx)
+            .await?;
+        parent_delegations::Entity::delete_many()
+            .filter(
+                Condition::any()
+                    .add(parent_delegations::Column::Child.is_in(prunable.iter().copied()))
+                    .add(parent_delegations::Column::Parent.is_in(prunable.iter().copied())),
+            )
+            .exec(&tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(prunable.len() as u64)
+    }
 }
 
 impl<C, B, K> SpaceDatabase<C, B, K>
 where
     C: ConnectionTrait,
 {
+    /// Freeze or unfreeze `space`. A frozen space rejects new delegations and
+    /// writes with [`TxError::SpaceFrozen`] while still serving reads.
+    /// Returns `false` if the node does not host the space.
+    pub async fn set_space_frozen(&self, space: &SpaceId, frozen: bool) -> Result<bool, DbErr> {
+        let updated = space::Entity::update_many()
+            .col_expr(space::Column::Frozen, Expr::value(frozen))
+            .filter(space::Column::Id.eq(SpaceIdWrap(space.clone())))
+            .exec(&self.conn)
+            .await?;
+        Ok(updated.rows_affected > 0)
+    }
+
+    /// Whether `space` is frozen, or `None` if the node does not host it.
+    pub async fn is_space_frozen(&self, space: &SpaceId) -> Result<Option<bool>, DbErr> {
+        Ok(space::Entity::find_by_id(SpaceIdWrap(space.clone()))
+            .one(&self.conn)
+            .await?
+            .map(|s| s.frozen))
+    }
+
     /// List every space id known to this node (the full `space` table).
     /// Used by the admin usage endpoint to enumerate spaces without touching
     /// SQL directly.
@@ -259,6 +557,36 @@ where
             .collect())
     }
 
+    /// Check a delegation's signature and its chain of parents, as
+    /// [`delegate`](Self::delegate) would, without storing it.
+    pub async fn authorize_delegation(
+        &self,
+        delegation: &Delegation,
+    ) -> Result<(), delegation::Error> {
+        delegation::verify_and_authorize(
+            &self.conn,
+            &delegation.0,
+            &self.resolver,
+            &self.delegation_limits,
+        )
+        .await
+    }
+
+    /// Check an invocation's signature and that its capabilities are
+    /// delegated to its invoker, without recording it.
+    pub async fn authorize_invocation(
+        &self,
+        invocation: &crate::util::InvocationInfo,
+    ) -> Result<(), invocation::Error> {
+        invocation::verify_and_authorize(
+            &self.conn,
+            invocation,
+            &self.resolver,
+            OffsetDateTime::now_utc(),
+        )
+        .await
+    }
+
     /// Return lifecycle-complete delegations related to the authenticated account.
     ///
     /// The account is derived from the verified invocation signer and its one
@@ -269,7 +597,7 @@ where
         query: &DelegationQuery,
     ) -> Result<DelegationQueryPage, AccountDelegationQueryError> {
         let now = OffsetDateTime::now_utc();
-        invocation::verify_and_authorize(&self.conn, invocation, now)
+        invocation::verify_and_authorize(&self.conn, invocation, &self.resolver, now)
             .await
             .map_err(|_| AccountDelegationQueryError::Unauthorized)?;
         let principal = account_query_principal(&self.conn, invocation)
@@ -362,7 +690,7 @@ where
                 continue;
             }
 
-            let cid = delegation.id.to_cid(0x55).to_string();
+            let cid = delegation.id.to_event_cid().to_string();
             let mut parents = ancestor_state
                 .parents
                 .get(&delegation.id)
@@ -378,7 +706,7 @@ where
                 resources,
                 parents: parents
                     .into_iter()
-                    .map(|parent| parent.to_cid(0x55).to_string())
+                    .map(|parent| parent.to_event_cid().to_string())
                     .collect(),
                 issued_at: delegation.issued_at,
                 not_before: delegation.not_before,
@@ -647,6 +975,41 @@ where
     }
 }
 
+impl<C, B, K> SpaceDatabase<C, B, K>
+where
+    C: ConnectionTrait,
+    B: StoreSize,
+{
+    /// Every space on this node with its latest event sequence number (`None`
+    /// if it has no events yet) and its [`store_size`](Self::store_size).
+    /// Sorted by space id.
+    pub async fn list_spaces(
+        &self,
+    ) -> Result<Vec<(SpaceId, Option<i64>, Option<u64>)>, EitherError<DbErr, B::Error>> {
+        let mut latest_seqs: HashMap<SpaceIdWrap, i64> = event_order::Entity::find()
+            .select_only()
+            .column(event_order::Column::Space)
+            .column_as(event_order::Column::Seq.max(), "max_seq")
+            .group_by(event_order::Column::Space)
+            .into_tuple::<(SpaceIdWrap, i64)>()
+            .all(&self.conn)
+            .await
+            .map_err(EitherError::A)?
+            .into_iter()
+            .collect();
+        let mut space_ids = self.list_space_ids().await.map_err(EitherError::A)?;
+        space_ids.sort();
+
+        let mut spaces = Vec::with_capacity(space_ids.len());
+        for space in space_ids {
+            let size = self.store_size(&space).await.map_err(EitherError::B)?;
+            let seq = latest_seqs.remove(&SpaceIdWrap(space.clone()));
+            spaces.push((space, seq, size));
+        }
+        Ok(spaces)
+    }
+}
+
 fn normalize_hook_prefix(prefix: &str) -> Option<&str> {
     let trimmed = prefix.trim_matches('/');
     if trimmed.is_empty() {
@@ -674,6 +1037,218 @@ where
     }
 }
 
+impl<C, B, K> SpaceDatabase<C, B, K>
+where
+    C: TransactionTrait,
+    B: ImmutableListStore + ImmutableDeleteStore,
+{
+    /// Delete everything this node holds for `space`, returning how many
+    /// blocks were removed.
+    ///
+    /// In one transaction this removes the space's KV entries, epochs and
+    /// event log, its tickets, hooks and database artifacts, the space record,
+    /// and the delegations, invocations and revocations no other space's log
+    /// orders. A delegation is kept while a kept delegation names it as a
+    /// parent or a kept revocation names it, as in
+    /// [`prune_expired_delegations`](Self::prune_expired_delegations). Once
+    /// the rows are gone every block the store lists for the space is
+    /// removed, including blocks no KV write refers to, so a call that fails
+    /// part way leaves at most unreferenced blocks for the next call to
+    /// remove. Deleting a space that does not exist succeeds.
+    pub async fn delete_space(
+        &self,
+        space: &SpaceId,
+    ) -> Result<
+        u64,
+        SpaceDeleteError<<B as ImmutableListStore>::Error, <B as ImmutableDeleteStore>::Error>,
+    > {
+        let tx = self.conn.begin().await?;
+        let wrapped = SpaceIdWrap(space.clone());
+        let space_string = space.to_string();
+
+        let events: HashSet<Hash> = event_order::Entity::find()
+            .filter(event_order::Column::Space.eq(wrapped.clone()))
+            .all(&tx)
+            .await?
+            .into_iter()
+            .map(|order| order.event)
+            .collect();
+
+        kv_delete::Entity::delete_many()
+            .filter(kv_delete::Column::Space.eq(wrapped.clone()))
+            .exec(&tx)
+            .await?;
+        kv_write::Entity::delete_many()
+            .filter(kv_write::Column::Space.eq(wrapped.clone()))
+            .exec(&tx)
+            .await?;
+        event_order::Entity::delete_many()
+            .filter(event_order::Column::Space.eq(wrapped.clone()))
+            .exec(&tx)
+            .await?;
+        epoch_order::Entity::delete_many()
+            .filter(epoch_order::Column::Space.eq(wrapped.clone()))
+            .exec(&tx)
+            .await?;
+        epoch::Entity::delete_many()
+            .filter(epoch::Column::Space.eq(wrapped.clone()))
+            .exec(&tx)
+            .await?;
+
+        if !events.is_empty() {
+            // an event naming several spaces stays ordered in the others' logs
+            let shared: HashSet<Hash> = event_order::Entity::find()
+                .filter(event_order::Column::Event.is_in(events.iter().copied()))
+                .all(&tx)
+                .await?
+                .into_iter()
+                .map(|order| order.event)
+                .collect();
+            let orphaned: Vec<Hash> = events.difference(&shared).copied().collect();
+            delete_orphaned_events(&tx, &orphaned).await?;
+        }
+
+        signed_kv_ticket::Entity::delete_many()
+            .filter(signed_kv_ticket::Column::SpaceId.eq(space_string.clone()))
+            .exec(&tx)
+            .await?;
+        let subscriptions: Vec<String> = hook_subscription::Entity::find()
+            .filter(hook_subscription::Column::SpaceId.eq(space_string.clone()))
+            .all(&tx)
+            .await?
+            .into_iter()
+            .map(|subscription| subscription.id)
+            .collect();
+        if !subscriptions.is_empty() {
+            hook_delivery::Entity::delete_many()
+                .filter(hook_delivery::Column::SubscriptionId.is_in(subscriptions))
+                .exec(&tx)
+                .await?;
+        }
+        hook_subscription::Entity::delete_many()
+            .filter(hook_subscription::Column::SpaceId.eq(space_string.clone()))
+            .exec(&tx)
+            .await?;
+        database_artifact::Entity::delete_many()
+            .filter(database_artifact::Column::Space.eq(space_string))
+            .exec(&tx)
+            .await?;
+        space::Entity::delete_by_id(wrapped).exec(&tx).await?;
+        tx.commit().await?;
+
+        let blocks = self
+            .storage
+            .list(space)
+            .await
+            .map_err(SpaceDeleteError::ListBlocks)?;
+        let mut removed = 0;
+        for block in blocks {
+            if ImmutableDeleteStore::remove(&self.storage, space, &block)
+                .await
+                .map_err(SpaceDeleteError::RemoveBlock)?
+                .is_some()
+            {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Remove the rows of events no space's log orders any more.
+async fn delete_orphaned_events<C: ConnectionTrait>(
+    db: &C,
+    orphaned: &[Hash],
+) -> Result<(), DbErr> {
+    if orphaned.is_empty() {
+        return Ok(());
+    }
+
+    let invocations: Vec<Hash> = invocation::Entity::find()
+        .filter(invocation::Column::Id.is_in(orphaned.iter().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|i| i.id)
+        .collect();
+    if !invocations.is_empty() {
+        invoked_abilities::Entity::delete_many()
+            .filter(invoked_abilities::Column::Invocation.is_in(invocations.iter().copied()))
+            .exec(db)
+            .await?;
+        // invocations record their proofs as parent links too
+        parent_delegations::Entity::delete_many()
+            .filter(parent_delegations::Column::Child.is_in(invocations.iter().copied()))
+            .exec(db)
+            .await?;
+        invocation::Entity::delete_many()
+            .filter(invocation::Column::Id.is_in(invocations))
+            .exec(db)
+            .await?;
+    }
+    revocation::Entity::delete_many()
+        .filter(revocation::Column::Id.is_in(orphaned.iter().copied()))
+        .exec(db)
+        .await?;
+
+    let mut removable: HashSet<Hash> = delegation::Entity::find()
+        .filter(delegation::Column::Id.is_in(orphaned.iter().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|d| d.id)
+        .collect();
+    if removable.is_empty() {
+        return Ok(());
+    }
+    // revocations recorded in other spaces still name their delegation
+    let revoked: HashSet<Hash> = revocation::Entity::find()
+        .filter(revocation::Column::Revoked.is_in(removable.iter().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| r.revoked)
+        .collect();
+    removable.retain(|id| !revoked.contains(id));
+    let edges = parent_delegations::Entity::find()
+        .filter(parent_delegations::Column::Parent.is_in(removable.iter().copied()))
+        .all(db)
+        .await?;
+    loop {
+        let referenced: HashSet<Hash> = edges
+            .iter()
+            .filter(|edge| !removable.contains(&edge.child))
+            .map(|edge| edge.parent)
+            .collect();
+        let remaining = removable.len();
+        removable.retain(|id| !referenced.contains(id));
+        if removable.len() == remaining {
+            break;
+        }
+    }
+    if removable.is_empty() {
+        return Ok(());
+    }
+
+    abilities::Entity::delete_many()
+        .filter(abilities::Column::Delegation.is_in(removable.iter().copied()))
+        .exec(db)
+        .await?;
+    parent_delegations::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(parent_delegations::Column::Child.is_in(removable.iter().copied()))
+                .add(parent_delegations::Column::Parent.is_in(removable.iter().copied())),
+        )
+        .exec(db)
+        .await?;
+    delegation::Entity::delete_many()
+        .filter(delegation::Column::Id.is_in(removable.iter().copied()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
 impl<C, B, K> SpaceDatabase<C, B, K>
 where
     C: ConnectionTrait,
@@ -692,7 +1267,31 @@ where
         space_id: &SpaceId,
         key: &Path,
     ) -> Result<Option<(Metadata, Hash, Content<B::Readable>)>, EitherError<DbErr, B::Error>> {
-        get_kv(&self.conn, &self.storage, space_id, key).await
+        get_kv(self.reader(), &self.storage, space_id, key).await
+    }
+
+    /// Read bytes `start..end` of a public key's value; see
+    /// [`ImmutableReadStore::read_range`].
+    pub async fn public_kv_get_range(
+        &self,
+        space_id: &SpaceId,
+        key: &Path,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<(Metadata, Hash, Content<Ranged<B::Readable>>)>, EitherError<DbErr, B::Error>>
+    {
+        let Some(entry) = get_kv_entity(self.reader(), space_id, key)
+            .await
+            .map_err(EitherError::A)?
+        else {
+            return Ok(None);
+        };
+        Ok(self
+            .storage
+            .read_range(space_id, &entry.value, start, end)
+            .await
+            .map_err(EitherError::B)?
+            .map(|content| (entry.metadata, entry.value, content)))
     }
 
     pub async fn public_kv_metadata(
@@ -700,7 +1299,7 @@ where
         space_id: &SpaceId,
         key: &Path,
     ) -> Result<Option<Metadata>, DbErr> {
-        metadata(&self.conn, space_id, key).await
+        metadata(self.reader(), space_id, key).await
     }
 
     pub async fn public_kv_list(
@@ -708,7 +1307,7 @@ where
         space_id: &SpaceId,
         prefix: &Path,
     ) -> Result<Vec<Path>, DbErr> {
-        list(&self.conn, space_id, prefix).await
+        list(self.reader(), space_id, prefix).await
     }
 }
 
@@ -723,6 +1322,15 @@ where
     }
 }
 
+impl<C, B, K> SpaceDatabase<C, B, K>
+where
+    B: StorageHealth,
+{
+    pub async fn check_storage(&self) -> Result<(), B::Error> {
+        self.storage.health().await
+    }
+}
+
 pub type InvocationInputs<W> = HashMap<(SpaceId, Path), (Metadata, HashBuffer<W>)>;
 
 impl<C, B, K> SpaceDatabase<C, B, K>
@@ -731,6 +1339,14 @@ where
     B: StorageSetup,
     K: Secrets,
 {
+    fn notify_commits(&self, kind: CommitKind, result: &TransactResult) {
+        for observer in &self.commit_observers {
+            for (space, commit) in &result.commits {
+                observer.committed(kind, space, commit);
+            }
+        }
+    }
+
     async fn acquire_chain_guards(
         &self,
         roots: &[Hash],
@@ -805,45 +1421,267 @@ where
         guards
     }
 
-    async fn transact(&self, events: Vec<Event>) -> Result<TransactResult, TxError<B, K>> {
-        let tx = self
-            .conn
-            .begin_with_config(chain_isolation_level(&self.conn), None)
-            .await?;
+    /// The spaces `events` would create that the allow list admits, or `None`
+    /// without an allow list. The list is consulted here, before a commit's
+    /// transaction begins, so a slow list never holds one open.
+    ///
+    /// The delegations among `events` must already have been verified, and
+    /// they are authorized before the list is consulted, so a caller who
+    /// could not host a space is refused as they would be without a list:
+    /// they neither make the node query it nor learn what it holds.
+    async fn allowed_spaces(
+        &self,
+        events: &[Event],
+    ) -> Result<Option<HashSet<SpaceId>>, TxError<B, K>> {
+        let Some(allow_list) = self.allow_list.as_deref() else {
+            return Ok(None);
+        };
+        let new_spaces = new_spaces(&self.conn, events).await?;
+        if !new_spaces.is_empty() {
+            for event in events {
+                if let Event::Delegation(d) = event {
+                    delegation::authorize(&self.conn, &d.0, &self.delegation_limits).await?;
+                }
+            }
+        }
+        let mut allowed = HashSet::new();
+        for SpaceIdWrap(space) in new_spaces {
+            if !allow_list.is_allowed(&space).await? {
+                return Err(TxError::SpaceNotAllowed(space));
+            }
+            allowed.insert(space);
+        }
+        Ok(Some(allowed))
+    }
 
-        let result = transact(
-            &tx,
-            &self.storage,
-            &self.secrets,
-            events,
-            self.encryption.as_ref(),
-        )
-        .await?;
+    /// `fut`, the work leading up to a commit, given up with
+    /// [`TxError::TimedOut`] if it is still running at `deadline`. Only this
+    /// work is ever given up: a commit is never cut short, so a write is never
+    /// both made and reported as timed out, and its observers always hear of
+    /// it.
+    async fn before_deadline<T, E>(
+        deadline: Option<Instant>,
+        fut: impl std::future::Future<Output = Result<T, E>>,
+    ) -> Result<T, E>
+    where
+        E: From<TxError<B, K>>,
+    {
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, fut)
+                .await
+                .unwrap_or_else(|_| Err(TxError::TimedOut.into())),
+            None => fut.await,
+        }
+    }
 
+    /// Commit `events`, telling the commit observers about it as a `kind`
+    /// commit unless it is `None`. Gives up if the events have not started
+    /// committing by `deadline`.
+    async fn transact(
+        &self,
+        kind: Option<CommitKind>,
+        events: Vec<Event>,
+        deadline: Option<Instant>,
+    ) -> Result<TransactResult, TxError<B, K>> {
+        let (tx, result) = Self::before_deadline(deadline, self.prepare_events(events)).await?;
         tx.commit().await?;
-
+        if let Some(kind) = kind {
+            self.notify_commits(kind, &result);
+        }
         Ok(result)
     }
 
-    pub async fn delegate(&self, delegation: Delegation) -> Result<TransactResult, TxError<B, K>> {
-        let roots: Vec<Hash> = delegation
-            .0
+    /// Verify `events` and apply them in a transaction, which is returned for
+    /// the caller to commit.
+    async fn prepare_events(
+        &self,
+        events: Vec<Event>,
+    ) -> Result<(DatabaseTransaction, TransactResult), TxError<B, K>> {
+        check_capability_counts::<B, K>(&events, &self.delegation_limits)?;
+        // signatures are checked before the allow list is consulted
+        delegation::verify_all(
+            events.iter().filter_map(|e| match e {
+                Event::Delegation(d) => Some(&d.0.delegation),
+                _ => None,
+            }),
+            &self.resolver,
+            self.delegation_limits.clock_skew,
+        )
+        .await?;
+        let allowed_spaces = self.allowed_spaces(&events).await?;
+        let mut attempt = 1;
+        loop {
+            let tx = self
+                .conn
+                .begin_with_config(chain_isolation_level(&self.conn), None)
+                .await?;
+
+            let result = match transact(
+                &tx,
+                &self.storage,
+                &self.secrets,
+                events.clone(),
+                self.encryption.as_ref(),
+                allowed_spaces.as_ref(),
+                self.policy.as_ref(),
+                &self.delegation_limits,
+                &self.resolver,
+            )
+            .await
+            {
+                Err(error) if attempt < MAX_SEQUENCE_ATTEMPTS && is_sequence_race(&error) => {
+                    tx.rollback().await?;
+                    attempt += 1;
+                    continue;
+                }
+                result => result?,
+            };
+
+            return Ok((tx, result));
+        }
+    }
+
+    pub async fn delegate(&self, delegation: Delegation) -> Result<TransactResult, TxError<B, K>> {
+        self.delegate_before(delegation, None).await
+    }
+
+    /// [`Self::delegate`], giving up with [`TxError::TimedOut`] if the
+    /// delegation has not started committing by `deadline`.
+    pub async fn delegate_before(
+        &self,
+        delegation: Delegation,
+        deadline: Option<Instant>,
+    ) -> Result<TransactResult, TxError<B, K>> {
+        let roots: Vec<Hash> = delegation
+            .0
             .parents
             .iter()
             .copied()
             .map(Hash::from)
             .collect();
-        let _chain_guards = self.acquire_chain_guards(&roots).await?;
-        self.transact(vec![Event::Delegation(Box::new(delegation))])
-            .await
+        let _chain_guards =
+            Self::before_deadline(deadline, self.acquire_chain_guards(&roots)).await?;
+        self.transact(
+            Some(CommitKind::Delegation),
+            vec![Event::Delegation(Box::new(delegation))],
+            deadline,
+        )
+        .await
     }
 
     pub async fn revoke(&self, revocation: Revocation) -> Result<TransactResult, TxError<B, K>> {
         let mut roots = vec![Hash::from(revocation.0.revoked)];
         roots.extend(revocation.0.parents.iter().copied().map(Hash::from));
         let _chain_guards = self.acquire_chain_guards(&roots).await?;
-        self.transact(vec![Event::Revocation(Box::new(revocation))])
+        self.transact(
+            Some(CommitKind::Revocation),
+            vec![Event::Revocation(Box::new(revocation))],
+            None,
+        )
+        .await
+    }
+
+    /// Provision `space` without a host delegation: record it, set up its
+    /// block storage and save its staged keypair. Returns `false`, changing
+    /// nothing, if the space already exists. The space allow list is not
+    /// consulted; callers provisioning explicitly apply their own policy.
+    pub async fn create_space(&self, space: &SpaceId) -> Result<bool, TxError<B, K>> {
+        let tx = self.conn.begin().await?;
+        match space::Entity::insert(space::ActiveModel::from(space::Model {
+            id: SpaceIdWrap(space.clone()),
+            frozen: false,
+        }))
+        .on_conflict(
+            OnConflict::column(space::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(&tx)
+        .await
+        {
+            Err(DbErr::RecordNotInserted) => {
+                tx.rollback().await?;
+                return Ok(false);
+            }
+            r => {
+                r?;
+            }
+        };
+        self.storage
+            .create(space)
             .await
+            .map_err(TxError::StoreSetup)?;
+        self.secrets
+            .save_keypair(space)
+            .await
+            .map_err(TxError::Secrets)?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// The events committed to `space` in the `epochs` epochs after sequence
+    /// number `since`, in commit order, for another node to ingest.
+    pub async fn event_log(
+        &self,
+        space: &SpaceId,
+        since: i64,
+        epochs: u32,
+    ) -> Result<Vec<LogEntry>, LogError> {
+        crate::event_log::read(&self.conn, space, since, epochs, self.encryption.as_ref()).await
+    }
+
+    /// Commit events read from another node's [`event_log`](Self::event_log),
+    /// in order, returning how many were new to this node.
+    ///
+    /// Every event is verified again as though it had been submitted here,
+    /// now, so an event log need not be trusted: an invocation that has
+    /// expired since it was committed is refused, as is a write or delete its
+    /// capabilities do not name. Events this node already has are skipped, so
+    /// ingesting the same log twice is harmless. Written values are not part
+    /// of the log; they must be copied into this node's block store first,
+    /// and a write whose value is missing fails with
+    /// [`LogError::MissingValue`].
+    pub async fn ingest_events(&self, entries: Vec<LogEntry>) -> Result<usize, TxError<B, K>>
+    where
+        B: ImmutableReadStore,
+    {
+        let mut ingested = 0;
+        for entry in entries {
+            let event = match crate::event_log::decode(&self.conn, entry.event).await? {
+                Some(Event::Delegation(delegation)) => {
+                    self.delegate(*delegation).await?;
+                    ingested += 1;
+                    continue;
+                }
+                Some(Event::Revocation(revocation)) => {
+                    self.revoke(*revocation).await?;
+                    ingested += 1;
+                    continue;
+                }
+                Some(event) => event,
+                None => continue,
+            };
+            let Event::Invocation(_, ops) = &event else {
+                unreachable!("delegations and revocations are ingested above")
+            };
+            for op in ops {
+                if let Operation::KvWrite { space, value, .. } = op {
+                    if !self
+                        .storage
+                        .contains(space, value)
+                        .await
+                        .map_err(|e| LogError::Store(e.to_string()))?
+                    {
+                        return Err(LogError::MissingValue(value.to_block_cid()).into());
+                    }
+                }
+            }
+            let writes = !ops.is_empty();
+            self.transact(writes.then_some(CommitKind::Write), vec![event], None)
+                .await?;
+            ingested += 1;
+        }
+        Ok(ingested)
     }
 
     pub async fn delegation_status(
@@ -932,13 +1770,31 @@ where
         Ok(Some(DelegationStatus::Active))
     }
 
+    /// Whether any of `delegations`, or any delegation they derive from, has
+    /// been revoked.
+    pub async fn chain_revoked(&self, delegations: &[Hash]) -> Result<bool, TxError<B, K>> {
+        let chain = revocation::ancestor_chain_ids_for_roots(&self.conn, delegations)
+            .await
+            .map_err(|error| match error {
+                revocation::ChainTraversalError::Db(error) => TxError::Db(error),
+                revocation::ChainTraversalError::LimitExceeded => {
+                    TxError::ChainTraversalLimitExceeded
+                }
+            })?;
+        Ok(revocation::Entity::find()
+            .filter(revocation::Column::Revoked.is_in(chain))
+            .count(&self.conn)
+            .await?
+            > 0)
+    }
+
     pub async fn invoke<S>(
         &self,
         invocation: Invocation,
         inputs: InvocationInputs<S::Writable>,
     ) -> Result<(TransactResult, Vec<InvocationOutcome<B::Readable>>), TxStoreError<B, S, K>>
     where
-        B: ImmutableWriteStore<S> + ImmutableReadStore,
+        B: ImmutableWriteStore<S> + ImmutableReadStore + StoreSize,
         S: ImmutableStaging,
         S::Writable: 'static + Unpin,
     {
@@ -949,14 +1805,54 @@ where
     pub async fn invoke_with_options<S>(
         &self,
         invocation: Invocation,
-        mut inputs: InvocationInputs<S::Writable>,
+        inputs: InvocationInputs<S::Writable>,
         options: KvInvokeOptions,
     ) -> Result<(TransactResult, Vec<InvocationOutcome<B::Readable>>), TxStoreError<B, S, K>>
     where
-        B: ImmutableWriteStore<S> + ImmutableReadStore,
+        B: ImmutableWriteStore<S> + ImmutableReadStore + StoreSize,
+        S: ImmutableStaging,
+        S::Writable: 'static + Unpin,
+    {
+        let prepared = Self::before_deadline(
+            options.deadline,
+            self.prepare_invocation::<S>(invocation, inputs, options),
+        )
+        .await?;
+        let Some(tx) = prepared.tx else {
+            return Ok((prepared.commit, prepared.results));
+        };
+        // commit tx if all side effects worked
+        tx.commit().await.map_err(|error| {
+            if prepared.has_preconditions && is_serialization_db_error(&error) {
+                TxStoreError::KvSerializationConflict
+            } else {
+                TxStoreError::Tx(error.into())
+            }
+        })?;
+        if prepared.writes {
+            self.notify_commits(CommitKind::Write, &prepared.commit);
+        }
+        Ok((prepared.commit, prepared.results))
+    }
+
+    /// Everything [`Self::invoke_with_options`] does short of committing.
+    async fn prepare_invocation<S>(
+        &self,
+        invocation: Invocation,
+        mut inputs: InvocationInputs<S::Writable>,
+        options: KvInvokeOptions,
+    ) -> Result<PreparedInvocation<B::Readable>, TxStoreError<B, S, K>>
+    where
+        B: ImmutableWriteStore<S> + ImmutableReadStore + StoreSize,
         S: ImmutableStaging,
         S::Writable: 'static + Unpin,
     {
+        // before taking any locks or reading anything for it
+        check_capability_count::<B, K>(
+            "invocation",
+            &invocation.0.capabilities,
+            self.delegation_limits.max_invocation_capabilities,
+        )?;
         let roots: Vec<Hash> = invocation
             .0
             .parents
@@ -964,50 +1860,136 @@ where
             .copied()
             .map(Hash::from)
             .collect();
-        let _chain_guards = self.acquire_chain_guards(&roots).await?;
+        let chain_guards = self.acquire_chain_guards(&roots).await?;
         let mutation_keys = invocation
             .0
             .capabilities
             .iter()
             .filter_map(|cap| {
                 let resource = cap.resource.tinycloud_resource()?;
-                let ability =
-                    crate::policy_capability::resolve_alias(cap.ability.as_ref().as_ref());
+                let ability = cap.ability.tinycloud()?.kv()?;
+                if resource.service().as_str() != "kv" || !ability.is_mutation() {
+                    return None;
+                }
+                Some((resource.space().clone(), resource.path()?.clone()))
+            })
+            .collect::<Vec<_>>();
+        let kv_source = kv_source_from_facts(&invocation.0);
+        let delete_versions =
+            kv_delete_versions(&invocation.0).map_err(TxStoreError::KvInvalidVersions)?;
+        let list_content_type = kv_list_content_type(&invocation.0);
+        let mut copies = Vec::new();
+        for cap in invocation.0.capabilities.iter() {
+            let Some((space, dest, ability)) = cap
+                .resource
+                .tinycloud_resource()
+                .and_then(|r| Some((r.space(), r.path()?, cap.ability.tinycloud()?)))
+            else {
+                continue;
+            };
+            let is_move = match ability {
+                TinyCloudAbility::Kv(KvAbility::Copy) => false,
+                TinyCloudAbility::Kv(KvAbility::Move) => true,
+                _ => continue,
+            };
+            let source = kv_source.clone().ok_or(TxStoreError::MissingInput)?;
+            // a delete of the source would otherwise quietly turn the copy
+            // into a move
+            if !is_move
+                && kv_capability_present(&invocation.0.capabilities, space, &source, KvAbility::Del)
+            {
+                return Err(TxStoreError::KvCopyDeletesSource(source));
+            }
+            // The copy capability only authorizes the destination. The source
+            // must be readable (and, for a move, deletable) by the invoker
+            // through capabilities carried on the same invocation, which are
+            // validated with the rest of the invocation in `transact`.
+            for required in [KvAbility::Get]
+                .into_iter()
+                .chain(is_move.then_some(KvAbility::Del))
+            {
+                if !kv_capability_present(&invocation.0.capabilities, space, &source, required) {
+                    return Err(TxStoreError::Tx(TxError::InvalidInvocation(
+                        invocation::InvocationError::UnauthorizedAction(
+                            Resource::TinyCloud(space.clone().to_resource(
+                                "kv".parse().expect("kv is a valid service name"),
+                                Some(source.clone()),
+                                None,
+                                None,
+                            )),
+                            TinyCloudAbility::Kv(required).into(),
+                        ),
+                    )));
+                }
+            }
+            copies.push((space.clone(), dest.clone(), source));
+        }
+        let copy_sources = copies
+            .iter()
+            .map(|(space, _, source)| (space.clone(), source.clone()))
+            .collect::<HashSet<_>>();
+        let metadata_updates = invocation
+            .0
+            .capabilities
+            .iter()
+            .filter_map(|cap| {
+                let resource = cap.resource.tinycloud_resource()?;
                 if resource.service().as_str() != "kv"
-                    || !matches!(ability, "tinycloud.kv/put" | "tinycloud.kv/del")
+                    || cap.ability.tinycloud()? != KvAbility::SetMetadata.into()
                 {
                     return None;
                 }
-                Some((resource.space().clone(), resource.path()?.clone()))
+                let key = (resource.space().clone(), resource.path()?.clone());
+                let update = options
+                    .metadata_updates
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_else(|| Metadata(Default::default()));
+                Some((key, update))
             })
             .collect::<Vec<_>>();
-        let _kv_object_guards = self.acquire_kv_object_guards(&mutation_keys).await;
+        let kv_object_guards = self.acquire_kv_object_guards(&mutation_keys).await;
         let mut stages = HashMap::new();
         let mut ops = Vec::new();
         let mut write_hashes = HashMap::new();
+        let mut staged_puts = Vec::new();
         // for each capability being invoked
         for cap in invocation.0.capabilities.iter() {
             match cap.resource.tinycloud_resource().and_then(|r| {
                 Some((
                     r.space(),
                     r.service().as_str(),
-                    // TC-119: resolve deprecated aliases to canonical so an
-                    // invocation using `kv/delete` dispatches identically to
-                    // `kv/del`. Identity for canonical URNs, so dispatch for
-                    // every non-alias action is byte-for-byte unchanged.
-                    crate::policy_capability::resolve_alias(cap.ability.as_ref().as_ref()),
+                    // TC-119: `tinycloud()` resolves deprecated aliases to
+                    // canonical so an invocation using `kv/delete` dispatches
+                    // identically to `kv/del`.
+                    cap.ability.tinycloud()?,
                     r.path()?,
                 ))
             }) {
                 // stage inputs for content writes
-                Some((space, "kv", "tinycloud.kv/put", path)) => {
-                    let (metadata, mut stage) = inputs
-                        .remove(&(space.clone(), path.clone()))
-                        .ok_or(TxStoreError::MissingInput)?;
-
-                    let value = stage.hash();
+                Some((space, "kv", TinyCloudAbility::Kv(KvAbility::Put), path)) => {
+                    let (metadata, value) = match inputs.remove(&(space.clone(), path.clone())) {
+                        Some((metadata, mut stage)) => {
+                            let value = stage.hash();
+                            let content_type = metadata.content_type().map(str::to_string);
+                            staged_puts.push((
+                                space.clone(),
+                                path.clone(),
+                                stage.hasher().count(),
+                                content_type,
+                            ));
+                            stages.insert((space.clone(), path.clone()), stage);
+                            (metadata, value)
+                        }
+                        // a dry run only needs the write to be authorized, so
+                        // the client need not upload the content; the row it
+                        // records is rolled back with the transaction
+                        None if options.dry_run => {
+                            (Metadata(Default::default()), crate::hash::hash(&[]))
+                        }
+                        None => return Err(TxStoreError::MissingInput),
+                    };
 
-                    stages.insert((space.clone(), path.clone()), stage);
                     write_hashes.insert((space.clone(), path.clone()), value);
                     // add write for tx
                     ops.push(Operation::KvWrite {
@@ -1018,38 +2000,38 @@ where
                     });
                 }
                 // add delete for tx
-                Some((space, "kv", "tinycloud.kv/del", path)) => {
+                Some((space, "kv", TinyCloudAbility::Kv(KvAbility::Del), path)) => {
                     ops.push(Operation::KvDelete {
                         space: space.clone(),
                         key: path.clone(),
-                        version: None,
+                        version: delete_versions.get(&(space.clone(), path.clone())).copied(),
                     });
                 }
                 _ => {}
             }
         }
 
-        let has_preconditions = !options.preconditions.is_empty();
+        // the kv caveats of the delegations authorizing a put limit what it
+        // may write, so they are checked before anything is written
+        let puts = staged_puts
+            .iter()
+            .map(|(space, path, size, content_type)| invocation::KvPut {
+                space,
+                path,
+                size: *size,
+                content_type: content_type.as_deref(),
+            })
+            .collect::<Vec<_>>();
+        invocation::check_kv_caveats(&self.conn, &invocation.0, &puts)
+            .await
+            .map_err(|e| TxStoreError::Tx(e.into()))?;
+
+        let has_preconditions = !options.preconditions.is_empty() || !delete_versions.is_empty();
         let isolation_level = if has_preconditions {
             conditional_kv_isolation_level(&self.conn)
         } else {
             chain_isolation_level(&self.conn)
         };
-        let tx = self.conn.begin_with_config(isolation_level, None).await?;
-        let mut deleted_hashes = HashMap::new();
-        for key @ (space, path) in &mutation_keys {
-            let current = get_kv_entity(&tx, space, path)
-                .await?
-                .map(|entry| entry.value);
-            if let Some(precondition) = options.preconditions.get(key) {
-                if !kv_precondition_matches(*precondition, current) {
-                    return Err(TxStoreError::KvPreconditionFailed);
-                }
-            }
-            if let Some(hash) = current {
-                deleted_hashes.insert(key.clone(), hash);
-            }
-        }
         let caps = invocation.0.capabilities.clone();
         let invoker = invocation.0.invoker.clone();
         // Extract capabilities read params from UCAN facts field
@@ -1067,39 +2049,183 @@ where
                         .and_then(|v| serde_json::from_value(v.clone()).ok())
                 })
             });
-        //  verify and commit invocation and kv operations
-        let commit = transact(
-            &tx,
-            &self.storage,
-            &self.secrets,
-            vec![Event::Invocation(Box::new(invocation), ops)],
-            self.encryption.as_ref(),
-        )
-        .await
-        .map_err(|error| {
-            if has_preconditions && is_serialization_failure(&error) {
-                TxStoreError::KvSerializationConflict
-            } else {
-                TxStoreError::Tx(error)
+        let event = Event::Invocation(Box::new(invocation), ops);
+        let allowed_spaces = self.allowed_spaces(std::slice::from_ref(&event)).await?;
+        // Everything read inside the transaction is read again when an epoch
+        // append loses a sequence race, since the winner may have changed it.
+        // Nothing has left the transaction until `transact` succeeds.
+        let mut attempt = 1;
+        let (tx, deleted_hashes, commit) = loop {
+            let tx = self.conn.begin_with_config(isolation_level, None).await?;
+            let mut event = event.clone();
+            let Event::Invocation(_, ops) = &mut event else {
+                unreachable!("invocations are transacted as invocation events")
+            };
+            let mut deleted_hashes = HashMap::new();
+            for key @ (space, path) in &mutation_keys {
+                let current = get_kv_entity(&tx, space, path).await?;
+                if let Some(version) = delete_versions.get(key) {
+                    if current
+                        .as_ref()
+                        .map(|entry| (entry.seq, entry.epoch, entry.epoch_seq))
+                        != Some(*version)
+                    {
+                        return Err(TxStoreError::KvPreconditionFailed);
+                    }
+                }
+                let current = current.map(|entry| entry.value);
+                if let Some(precondition) = options.preconditions.get(key) {
+                    if !kv_precondition_matches(*precondition, current) {
+                        return Err(TxStoreError::KvPreconditionFailed);
+                    }
+                }
+                if let Some(hash) = current {
+                    deleted_hashes.insert(key.clone(), hash);
+                }
             }
-        })?;
+            // Content is addressed by hash within a space, so a copy only needs a
+            // new `kv_write` row pointing at the source's existing blob.
+            for (space, dest, source) in &copies {
+                let entry = get_kv_entity(&tx, space, source)
+                    .await?
+                    .ok_or_else(|| TxStoreError::KvSourceNotFound(source.clone()))?;
+                write_hashes.insert((space.clone(), dest.clone()), entry.value);
+                ops.push(Operation::KvWrite {
+                    space: space.clone(),
+                    key: dest.clone(),
+                    metadata: entry.metadata,
+                    value: entry.value,
+                });
+            }
+            // Likewise a metadata update rewrites the row around the same blob.
+            for ((space, path), update) in &metadata_updates {
+                let mut entry = get_kv_entity(&tx, space, path)
+                    .await?
+                    .ok_or_else(|| TxStoreError::KvKeyNotFound(path.clone()))?;
+                entry.metadata.0.extend(update.0.clone());
+                write_hashes.insert((space.clone(), path.clone()), entry.value);
+                ops.push(Operation::KvWrite {
+                    space: space.clone(),
+                    key: path.clone(),
+                    metadata: entry.metadata,
+                    value: entry.value,
+                });
+            }
+            //  verify and commit invocation and kv operations
+            match transact(
+                &tx,
+                &self.storage,
+                &self.secrets,
+                vec![event],
+                self.encryption.as_ref(),
+                allowed_spaces.as_ref(),
+                self.policy.as_ref(),
+                &self.delegation_limits,
+                &self.resolver,
+            )
+            .await
+            {
+                Ok(commit) => break (tx, deleted_hashes, commit),
+                Err(error) if attempt < MAX_SEQUENCE_ATTEMPTS && is_sequence_race(&error) => {
+                    tx.rollback()
+                        .await
+                        .map_err(|e| TxStoreError::Tx(e.into()))?;
+                    attempt += 1;
+                }
+                Err(error) if has_preconditions && is_serialization_failure(&error) => {
+                    return Err(TxStoreError::KvSerializationConflict)
+                }
+                Err(error) => return Err(TxStoreError::Tx(error)),
+            }
+        };
+
+        if options.dry_run {
+            // dropping the staged inputs discards them without persisting
+            tx.rollback()
+                .await
+                .map_err(|e| TxStoreError::Tx(e.into()))?;
+            return Ok(PreparedInvocation {
+                tx: None,
+                commit,
+                results: vec![InvocationOutcome::DryRun(caps)],
+                has_preconditions,
+                writes: false,
+                _guards: [chain_guards, kv_object_guards],
+            });
+        }
+
+        // write the staged values to the store, several at a time for
+        // invocations putting many keys. A failed write drops the transaction
+        // before anything is committed.
+        let persists = caps
+            .iter()
+            .filter_map(|c| {
+                let r = c.resource.tinycloud_resource()?;
+                match (r.service().as_str(), c.ability.tinycloud()?, r.path()) {
+                    ("kv", TinyCloudAbility::Kv(KvAbility::Put), Some(path)) => {
+                        let key = (r.space().clone(), path.clone());
+                        let stage = stages.remove(&key)?;
+                        Some((key, stage))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let persisted = stream::iter(persists)
+            .map(|((space, path), stage)| {
+                let expected = options.content_hashes.get(&(space.clone(), path.clone()));
+                async move {
+                    match expected {
+                        Some(expected) => self
+                            .storage
+                            .persist_keyed(&space, stage, expected)
+                            .await
+                            .map_err(|e| match e {
+                                KeyedWriteError::IncorrectHash => {
+                                    TxStoreError::KvContentHashMismatch(path.clone())
+                                }
+                                KeyedWriteError::Store(e) => TxStoreError::StoreWrite(e),
+                            })?,
+                        None => {
+                            self.storage
+                                .persist(&space, stage)
+                                .await
+                                .map_err(TxStoreError::StoreWrite)?;
+                        }
+                    }
+                    Ok::<_, TxStoreError<B, S, K>>((space, path))
+                }
+            })
+            .buffer_unordered(self.persist_concurrency)
+            .try_collect::<HashSet<_>>()
+            .await?;
 
         let mut results = Vec::new();
-        // perform and record side effects
+        // perform and record side effects. `transact` validated every
+        // capability of the invocation or failed as a whole, so nothing has
+        // touched the store yet and all of `caps` are authorized from here on.
         for cap in caps.iter().filter_map(|c| {
             c.resource.tinycloud_resource().and_then(|r| {
                 Some((
                     r.space(),
                     r.service().as_str(),
-                    // TC-119: resolve deprecated aliases to canonical (see the
-                    // staging loop above) — identity for canonical URNs.
-                    crate::policy_capability::resolve_alias(c.ability.as_ref().as_ref()),
+                    // TC-119: aliases resolve to canonical (see the staging
+                    // loop above)
+                    c.ability.tinycloud()?,
                     r.path()?,
                 ))
             })
         }) {
             match cap {
-                (space, "kv", "tinycloud.kv/get", path) => {
+                // source capabilities of a copy or move authorize the copy and
+                // do not produce outcomes of their own
+                (
+                    space,
+                    "kv",
+                    TinyCloudAbility::Kv(KvAbility::Get) | TinyCloudAbility::Kv(KvAbility::Del),
+                    path,
+                ) if copy_sources.contains(&(space.clone(), path.clone())) => {}
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::Get), path) => {
                     let data =
                         get_kv(&tx, &self.storage, space, path)
                             .await
@@ -1119,24 +2245,43 @@ where
                     }
                     results.push(InvocationOutcome::KvRead(data));
                 }
-                (space, "kv", "tinycloud.kv/list", path) => {
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::List), path)
+                    if list_content_type.is_some() =>
+                {
+                    let content_type = list_content_type.as_deref().unwrap_or_default();
+                    let (list, truncated) =
+                        list_by_content_type(&tx, space, path, content_type, options.list_limit)
+                            .await?;
+                    results.push(if options.list_detailed {
+                        InvocationOutcome::KvListDetailed(list, truncated)
+                    } else {
+                        InvocationOutcome::KvList(
+                            list.into_iter().map(|(key, _, _)| key).collect(),
+                            truncated,
+                        )
+                    })
+                }
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::List), path)
+                    if options.list_detailed =>
+                {
+                    let (list, truncated) =
+                        list_detailed_bounded(&tx, space, path, options.list_limit).await?;
+                    results.push(InvocationOutcome::KvListDetailed(list, truncated))
+                }
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::List), path) => {
                     let (list, truncated) =
                         list_bounded(&tx, space, path, options.list_limit).await?;
                     results.push(InvocationOutcome::KvList(list, truncated))
                 }
-                (space, "kv", "tinycloud.kv/del", path) => {
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::Del), path) => {
                     // KV deletion is logical. Blobs are content-addressed and may be
                     // shared by live sibling keys or retained version history.
                     results.push(InvocationOutcome::KvDelete(
                         deleted_hashes.get(&(space.clone(), path.clone())).copied(),
                     ))
                 }
-                (space, "kv", "tinycloud.kv/put", path) => {
-                    if let Some(stage) = stages.remove(&(space.clone(), path.clone())) {
-                        self.storage
-                            .persist(space, stage)
-                            .await
-                            .map_err(TxStoreError::StoreWrite)?;
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::Put), path) => {
+                    if persisted.contains(&(space.clone(), path.clone())) {
                         let hash = write_hashes
                             .get(&(space.clone(), path.clone()))
                             .copied()
@@ -1144,31 +2289,49 @@ where
                         results.push(InvocationOutcome::KvWrite(hash))
                     }
                 }
-                (space, "kv", "tinycloud.kv/metadata", path) => results.push(
+                (
+                    space,
+                    "kv",
+                    TinyCloudAbility::Kv(KvAbility::SetMetadata)
+                    | TinyCloudAbility::Kv(KvAbility::Copy)
+                    | TinyCloudAbility::Kv(KvAbility::Move),
+                    path,
+                ) => {
+                    if let Some(hash) = write_hashes.get(&(space.clone(), path.clone())) {
+                        results.push(InvocationOutcome::KvWrite(*hash))
+                    }
+                }
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::Exists), path) => results.push(
+                    InvocationOutcome::KvExists(get_kv_entity(&tx, space, path).await?.is_some()),
+                ),
+                (space, "kv", TinyCloudAbility::Kv(KvAbility::Metadata), path) => results.push(
                     InvocationOutcome::KvMetadata(metadata_with_hash(&tx, space, path).await?),
                 ),
-                (space, "capabilities", "tinycloud.capabilities/read", path)
-                    if path.as_str() == "all" =>
-                {
+                (
+                    space,
+                    "capabilities",
+                    TinyCloudAbility::Capabilities(CapabilitiesAbility::Read),
+                    path,
+                ) if path.as_str() == "all" => {
                     match &caps_read_params {
                         None => {
                             // Backward compatible: no params means return all valid delegations
                             results.push(InvocationOutcome::OpenSessions(
                                 get_valid_delegations(&tx, space, self.encryption.as_ref()).await?,
+                                None,
                             ))
                         }
                         Some(CapabilitiesReadParams::List { filters }) => {
-                            // List with optional filters
-                            results.push(InvocationOutcome::OpenSessions(
-                                get_filtered_delegations(
-                                    &tx,
-                                    space,
-                                    &invoker,
-                                    filters.as_ref(),
-                                    self.encryption.as_ref(),
-                                )
-                                .await?,
-                            ))
+                            // List with optional filters, a page at a time
+                            let (sessions, next) = get_filtered_delegations(
+                                &tx,
+                                space,
+                                &invoker,
+                                filters.as_ref(),
+                                self.encryption.as_ref(),
+                            )
+                            .await?;
+                            results.push(InvocationOutcome::OpenSessions(sessions, next))
                         }
                         Some(CapabilitiesReadParams::Chain { delegation_cid }) => {
                             // Get the delegation chain for a specific delegation
@@ -1184,60 +2347,277 @@ where
                         }
                     }
                 }
+                (
+                    space,
+                    "capabilities",
+                    TinyCloudAbility::Capabilities(CapabilitiesAbility::Usage),
+                    _,
+                ) => {
+                    let bytes = self
+                        .store_size(space)
+                        .await
+                        .map_err(|e| TxStoreError::Io(std::io::Error::other(e.to_string())))?
+                        .unwrap_or(0);
+                    results.push(InvocationOutcome::Usage {
+                        bytes,
+                        limit: options.quota_limits.get(space).copied(),
+                        objects: count_live_kv(&tx, space).await?,
+                        delegations: count_current_delegations(&tx, space).await?,
+                    })
+                }
+                (
+                    space,
+                    "capabilities",
+                    TinyCloudAbility::Capabilities(CapabilitiesAbility::Head),
+                    _,
+                ) => {
+                    let heads = epoch_heads(&tx, [SpaceIdWrap(space.clone())])
+                        .await?
+                        .remove(&SpaceIdWrap(space.clone()))
+                        .unwrap_or_default();
+                    // this invocation's own epoch is always among the heads
+                    let seq = commit.commits.get(space).map(|c| c.seq).unwrap_or_default();
+                    results.push(InvocationOutcome::EpochHeads(
+                        heads.iter().map(|head| head.to_block_cid()).collect(),
+                        seq,
+                    ))
+                }
+                (
+                    space,
+                    "capabilities",
+                    TinyCloudAbility::Capabilities(CapabilitiesAbility::Effective),
+                    path,
+                ) if path.as_str() == "all" => {
+                    results.push(InvocationOutcome::EffectivePermissions(
+                        get_effective_permissions(&tx, space, &invoker, self.encryption.as_ref())
+                            .await?,
+                    ))
+                }
                 _ => {}
             };
         }
 
-        // commit tx if all side effects worked
-        tx.commit().await.map_err(|error| {
-            if has_preconditions && is_serialization_db_error(&error) {
-                TxStoreError::KvSerializationConflict
-            } else {
-                TxStoreError::Tx(error.into())
-            }
-        })?;
-        Ok((commit, results))
+        Ok(PreparedInvocation {
+            tx: Some(tx),
+            commit,
+            results,
+            has_preconditions,
+            writes: !mutation_keys.is_empty(),
+            _guards: [chain_guards, kv_object_guards],
+        })
     }
 }
 
-fn chain_isolation_level<C: ConnectionTrait>(db: &C) -> Option<sea_orm::IsolationLevel> {
-    match db.get_database_backend() {
-        // SQLite's default transaction mode is serializable; sqlx rejects an
-        // explicit SET TRANSACTION isolation statement for SQLite.
-        sea_orm::DatabaseBackend::Sqlite => None,
-        sea_orm::DatabaseBackend::Postgres | sea_orm::DatabaseBackend::MySql => {
-            // Revocation ordering is enforced by the chain-scoped guards held
-            // through commit. SERIALIZABLE also made unrelated chains contend
-            // on the shared epoch-tip read/write path, producing routine 40001
-            // aborts for every authenticated operation class.
-            Some(sea_orm::IsolationLevel::ReadCommitted)
-        }
-    }
+/// An invocation applied in a transaction that has yet to be committed.
+struct PreparedInvocation<R> {
+    /// `None` for a dry run, which has nothing to commit.
+    tx: Option<DatabaseTransaction>,
+    commit: TransactResult,
+    results: Vec<InvocationOutcome<R>>,
+    has_preconditions: bool,
+    /// Whether the invocation writes to the KV store.
+    writes: bool,
+    /// The chain and KV object locks, held until the commit is made.
+    _guards: [Vec<tokio::sync::OwnedMutexGuard<()>>; 2],
 }
 
-fn conditional_kv_isolation_level<C: ConnectionTrait>(db: &C) -> Option<sea_orm::IsolationLevel> {
-    conditional_kv_isolation_for_backend(db.get_database_backend())
+/// The source path of a `kv/copy` or `kv/move`, named by a `kvSource` fact.
+fn kv_source_from_facts(invocation: &crate::util::InvocationInfo) -> Option<Path> {
+    invocation
+        .invocation
+        .payload()
+        .facts
+        .as_ref()?
+        .iter()
+        .find_map(|fact| {
+            fact.as_object()
+                .and_then(|obj| obj.get("kvSource"))
+                .and_then(|v| v.as_str())
+                .and_then(|v| v.parse().ok())
+        })
 }
 
-fn conditional_kv_isolation_for_backend(
-    backend: sea_orm::DatabaseBackend,
-) -> Option<sea_orm::IsolationLevel> {
-    match backend {
-        sea_orm::DatabaseBackend::Sqlite => None,
-        sea_orm::DatabaseBackend::Postgres | sea_orm::DatabaseBackend::MySql => {
-            Some(sea_orm::IsolationLevel::Serializable)
-        }
-    }
+/// The content type prefix a `contentTypePrefix` fact of `invocation`
+/// restricts its `kv/list` capabilities to, e.g. `image/`.
+fn kv_list_content_type(invocation: &crate::util::InvocationInfo) -> Option<String> {
+    invocation
+        .invocation
+        .payload()
+        .facts
+        .as_ref()?
+        .iter()
+        .find_map(|fact| fact.as_object()?.get("contentTypePrefix")?.as_str())
+        .map(str::to_string)
 }
 
-fn is_serialization_failure<S: StorageSetup, K: Secrets>(error: &TxError<S, K>) -> bool {
-    match error {
-        TxError::Db(error) | TxError::EpochInsert(error) => is_serialization_db_error(error),
-        _ => false,
+/// The versions the `kv/del` capabilities of `invocation` expect to delete,
+/// named by a `kvVersions` fact mapping each key to the `seq`, `epoch` CID
+/// and `epochSeq` of its current write. A delete whose key has moved on
+/// fails with [`TxStoreError::KvPreconditionFailed`], and a fact which does
+/// not name a version for every key it maps is refused rather than ignored.
+pub(crate) fn kv_delete_versions(
+    invocation: &crate::util::InvocationInfo,
+) -> Result<HashMap<(SpaceId, Path), (i64, Hash, i64)>, String> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct KvVersion {
+        seq: i64,
+        epoch: String,
+        epoch_seq: i64,
     }
-}
 
-fn is_serialization_db_error(error: &DbErr) -> bool {
+    let Some(fact) = invocation
+        .invocation
+        .payload()
+        .facts
+        .as_ref()
+        .and_then(|facts| {
+            facts
+                .iter()
+                .find_map(|fact| fact.as_object()?.get("kvVersions"))
+        })
+    else {
+        return Ok(HashMap::new());
+    };
+    let versions = serde_json::from_value::<HashMap<String, KvVersion>>(fact.clone())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(key, version)| {
+            let epoch = version
+                .epoch
+                .parse::<tinycloud_auth::ipld_core::cid::Cid>()
+                .map_err(|_| format!("{key}: invalid epoch CID {}", version.epoch))?;
+            Ok((key, (version.seq, Hash::from(epoch), version.epoch_seq)))
+        })
+        .collect::<Result<HashMap<_, _>, String>>()?;
+    Ok(invocation
+        .capabilities
+        .iter()
+        .filter_map(|cap| {
+            let resource = cap.resource.tinycloud_resource()?;
+            if resource.service().as_str() != "kv"
+                || cap.ability.tinycloud()? != KvAbility::Del.into()
+            {
+                return None;
+            }
+            let path = resource.path()?;
+            let version = versions.get(path.as_str())?;
+            Some(((resource.space().clone(), path.clone()), *version))
+        })
+        .collect())
+}
+
+/// The keys of the [`InvocationOutcome::KvRead`] outcomes `invoke` returns
+/// for `invocation`, in the same order. Reads that only authorize the source
+/// of a copy or move produce no outcome and are skipped.
+pub fn kv_read_keys(invocation: &crate::util::InvocationInfo) -> Vec<Path> {
+    let kv_caps = || {
+        invocation.capabilities.iter().filter_map(|c| {
+            let r = c.resource.tinycloud_resource()?;
+            (r.service().as_str() == "kv").then_some(())?;
+            Some((r.space(), r.path()?, c.ability.tinycloud()?.kv()?))
+        })
+    };
+    let source = kv_source_from_facts(invocation);
+    let copy_spaces = kv_caps()
+        .filter(|(_, _, ability)| matches!(ability, KvAbility::Copy | KvAbility::Move))
+        .map(|(space, _, _)| space)
+        .collect::<HashSet<_>>();
+    kv_caps()
+        .filter(|(space, path, ability)| {
+            *ability == KvAbility::Get
+                && !(copy_spaces.contains(space) && source.as_ref() == Some(*path))
+        })
+        .map(|(_, path, _)| path.clone())
+        .collect()
+}
+
+fn kv_capability_present(
+    capabilities: &[Capability],
+    space: &SpaceId,
+    path: &Path,
+    ability: KvAbility,
+) -> bool {
+    capabilities.iter().any(|cap| {
+        cap.resource
+            .tinycloud_resource()
+            .map(|r| {
+                r.space() == space
+                    && r.service().as_str() == "kv"
+                    && r.path() == Some(path)
+                    && cap.ability.tinycloud() == Some(ability.into())
+            })
+            .unwrap_or(false)
+    })
+}
+
+fn chain_isolation_level<C: ConnectionTrait>(db: &C) -> Option<sea_orm::IsolationLevel> {
+    match db.get_database_backend() {
+        // SQLite's default transaction mode is serializable; sqlx rejects an
+        // explicit SET TRANSACTION isolation statement for SQLite.
+        sea_orm::DatabaseBackend::Sqlite => None,
+        sea_orm::DatabaseBackend::Postgres | sea_orm::DatabaseBackend::MySql => {
+            // Revocation ordering is enforced by the chain-scoped guards held
+            // through commit. SERIALIZABLE also made unrelated chains contend
+            // on the shared epoch-tip read/write path, producing routine 40001
+            // aborts for every authenticated operation class.
+            Some(sea_orm::IsolationLevel::ReadCommitted)
+        }
+    }
+}
+
+fn conditional_kv_isolation_level<C: ConnectionTrait>(db: &C) -> Option<sea_orm::IsolationLevel> {
+    conditional_kv_isolation_for_backend(db.get_database_backend())
+}
+
+fn conditional_kv_isolation_for_backend(
+    backend: sea_orm::DatabaseBackend,
+) -> Option<sea_orm::IsolationLevel> {
+    match backend {
+        sea_orm::DatabaseBackend::Sqlite => None,
+        sea_orm::DatabaseBackend::Postgres | sea_orm::DatabaseBackend::MySql => {
+            Some(sea_orm::IsolationLevel::Serializable)
+        }
+    }
+}
+
+/// Attempts an append makes at claiming a space's next sequence number
+/// before losing the race to concurrent appends is reported as an error.
+const MAX_SEQUENCE_ATTEMPTS: usize = 5;
+
+/// Whether `transact` failed because a concurrent transaction appended an
+/// epoch to the same space first, taking the sequence number this one read.
+/// Storage and secrets are only touched after the epoch insert, so nothing
+/// outside the database transaction has happened yet and it can be retried
+/// from the top.
+fn is_sequence_race<S: StorageSetup, K: Secrets>(error: &TxError<S, K>) -> bool {
+    matches!(
+        error,
+        TxError::EpochInsert(error)
+            if is_unique_violation(error) || is_serialization_db_error(error)
+    )
+}
+
+fn is_unique_violation(error: &DbErr) -> bool {
+    matches!(
+        error,
+        DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(database_error)))
+        | DbErr::Query(RuntimeErr::SqlxError(SqlxError::Database(database_error)))
+            if matches!(
+                database_error.kind(),
+                sea_orm::sqlx::error::ErrorKind::UniqueViolation
+            )
+    )
+}
+
+fn is_serialization_failure<S: StorageSetup, K: Secrets>(error: &TxError<S, K>) -> bool {
+    match error {
+        TxError::Db(error) | TxError::EpochInsert(error) => is_serialization_db_error(error),
+        _ => false,
+    }
+}
+
+fn is_serialization_db_error(error: &DbErr) -> bool {
     matches!(
         error,
         DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(database_error)))
@@ -1252,19 +2632,45 @@ fn is_serialization_db_error(error: &DbErr) -> bool {
 #[derive(Debug)]
 pub enum InvocationOutcome<R> {
     KvList(Vec<Path>, bool),
+    /// Live keys under a prefix with the metadata and content hash of their
+    /// latest write, plus whether the listing was truncated.
+    KvListDetailed(Vec<(Path, Metadata, Hash)>, bool),
     KvDelete(Option<Hash>),
     KvMetadata(Option<(Metadata, Hash)>),
+    /// Whether a live value exists at the key.
+    KvExists(bool),
     KvWrite(Hash),
     KvBatchWrite(Vec<Path>),
     KvRead(Option<(Metadata, Hash, Content<R>)>),
-    OpenSessions(HashMap<Hash, DelegationInfo>),
+    /// Valid delegations in a space, and when a page limit cut the listing
+    /// short, the id of the last one to continue after.
+    OpenSessions(HashMap<Hash, DelegationInfo>, Option<Hash>),
     /// Ordered delegation chain from leaf to root
     DelegationChain(Vec<DelegationInfo>),
+    /// Everything the invoker was delegated in a space, with capabilities
+    /// covered by a broader one removed.
+    EffectivePermissions(Vec<Capability>),
+    /// The space's current epoch heads, sorted, and its latest sequence
+    /// number.
+    EpochHeads(Vec<tinycloud_auth::authorization::Cid>, i64),
+    /// What a space stores: the bytes of its blocks and databases, its
+    /// storage limit if it has one, its live keys and its unrevoked
+    /// delegations valid now. Deleting a key leaves its blocks in place, so it
+    /// lowers `objects` but not `bytes`.
+    Usage {
+        bytes: u64,
+        limit: Option<u64>,
+        objects: u64,
+        delegations: u64,
+    },
     SqlResult(serde_json::Value),
     SqlExport(Vec<u8>),
     DuckDbResult(serde_json::Value),
     DuckDbExport(Vec<u8>),
     DuckDbArrow(Vec<u8>),
+    /// The capabilities a dry-run invocation would have exercised, all of
+    /// which were authorized.
+    DryRun(Vec<Capability>),
 }
 
 impl<S: StorageSetup, K: Secrets> From<delegation::Error> for TxError<S, K> {
@@ -1343,22 +2749,168 @@ async fn event_spaces<'a, C: ConnectionTrait>(
     Ok(spaces)
 }
 
-pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
+/// The epochs of each of `spaces` that no later epoch builds on yet, sorted
+/// by hash. A space has more than one head only when epochs were appended to
+/// it concurrently; its next epoch merges them.
+async fn epoch_heads<C: ConnectionTrait>(
     db: &C,
-    store_setup: &S,
-    secrets: &K,
-    events: Vec<Event>,
-    encryption: Option<&ColumnEncryption>,
-) -> Result<TransactResult, TxError<S, K>> {
-    // for each event, get the hash and the relevent space(s)
-    let event_hashes = events
+    spaces: impl IntoIterator<Item = SpaceIdWrap>,
+) -> Result<HashMap<SpaceIdWrap, Vec<Hash>>, DbErr> {
+    Ok(epoch::Entity::find()
+        .select_only()
+        .left_join(epoch_order::Entity)
+        .filter(
+            Condition::all()
+                .add(epoch::Column::Space.is_in(spaces))
+                .add(epoch_order::Column::Child.is_null()),
+        )
+        .column(epoch::Column::Space)
+        .column(epoch::Column::Id)
+        .into_tuple::<(SpaceIdWrap, Hash)>()
+        .all(db)
+        .await?
         .into_iter()
-        .map(|e| (e.hash(), e))
-        .collect::<Vec<(Hash, Event)>>();
-    let event_spaces = event_spaces(db, &event_hashes).await?;
-    let mut new_spaces = event_hashes
+        .fold(
+            HashMap::new(),
+            |mut m: HashMap<SpaceIdWrap, Vec<Hash>>, (space, epoch)| {
+                let heads = m.entry(space).or_default();
+                let at = heads.partition_point(|head| head < &epoch);
+                heads.insert(at, epoch);
+                m
+            },
+        ))
+}
+
+/// Refuse delegations and invocations with more capabilities than `limits`
+/// allow, before verifying or storing any of them.
+fn check_capability_counts<S: StorageSetup, K: Secrets>(
+    events: &[Event],
+    limits: &DelegationLimits,
+) -> Result<(), TxError<S, K>> {
+    for event in events {
+        match event {
+            Event::Delegation(d) => check_capability_count::<S, K>(
+                "delegation",
+                &d.0.capabilities,
+                limits.max_capabilities,
+            )?,
+            Event::Invocation(i, _) => check_capability_count::<S, K>(
+                "invocation",
+                &i.0.capabilities,
+                limits.max_invocation_capabilities,
+            )?,
+            Event::Revocation(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Refuse invocations carrying kv operations their own capabilities do not
+/// name: a write needs a put, copy, move or metadata update of its key, and a
+/// delete a `kv/del` of it. Invocations submitted to this node derive their
+/// operations from their capabilities, but those read from another node's
+/// log arrive alongside them and are not trusted.
+fn check_operations<S: StorageSetup, K: Secrets>(events: &[Event]) -> Result<(), TxError<S, K>> {
+    for (invocation, ops) in events.iter().filter_map(|e| match e {
+        Event::Invocation(i, ops) => Some((i, ops)),
+        _ => None,
+    }) {
+        for op in ops {
+            let (space, key, abilities): (_, _, &[KvAbility]) = match op {
+                Operation::KvWrite { space, key, .. } => (
+                    space,
+                    key,
+                    &[
+                        KvAbility::Put,
+                        KvAbility::Copy,
+                        KvAbility::Move,
+                        KvAbility::SetMetadata,
+                    ],
+                ),
+                Operation::KvDelete { space, key, .. } => (space, key, &[KvAbility::Del]),
+            };
+            if !abilities.iter().any(|ability| {
+                kv_capability_present(&invocation.0.capabilities, space, key, *ability)
+            }) {
+                return Err(unauthorized_operation(op));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The error for a kv operation its invocation is not authorized to make.
+fn unauthorized_operation<S: StorageSetup, K: Secrets>(op: &Operation) -> TxError<S, K> {
+    let (space, key, ability) = match op {
+        Operation::KvWrite { space, key, .. } => (space, key, KvAbility::Put),
+        Operation::KvDelete { space, key, .. } => (space, key, KvAbility::Del),
+    };
+    TxError::InvalidInvocation(invocation::InvocationError::UnauthorizedAction(
+        Resource::TinyCloud(space.clone().to_resource(
+            "kv".parse().expect("kv is a valid service name"),
+            Some(key.clone()),
+            None,
+            None,
+        )),
+        TinyCloudAbility::Kv(ability).into(),
+    ))
+}
+
+fn check_capability_count<S: StorageSetup, K: Secrets>(
+    kind: &'static str,
+    capabilities: &[Capability],
+    max: Option<usize>,
+) -> Result<(), TxError<S, K>> {
+    match max {
+        Some(max) if capabilities.len() > max => Err(TxError::TooManyCapabilities {
+            kind,
+            count: capabilities.len(),
+            max,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Consult `policy` about `invoker` invoking `capabilities`, once for each
+/// space they are invoked on.
+async fn authorize_with_policy(
+    policy: &dyn Policy,
+    invoker: &str,
+    capabilities: &[Capability],
+) -> Result<(), PolicyError> {
+    let mut spaces = capabilities
         .iter()
-        .filter_map(|(_, e)| match e {
+        .filter_map(|c| c.resource.space())
+        .collect::<Vec<_>>();
+    spaces.sort();
+    spaces.dedup();
+    for space in spaces {
+        let capabilities = capabilities
+            .iter()
+            .filter(|c| c.resource.space() == Some(space))
+            .collect::<Vec<_>>();
+        policy
+            .authorize(&AuthContext {
+                invoker,
+                space,
+                capabilities: &capabilities,
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// The spaces that `events` host and that do not exist yet.
+///
+/// Hosting a space that already exists, whether created by an earlier host
+/// delegation or provisioned with `create_space`, leaves it as it is.
+async fn new_spaces<'a, C: ConnectionTrait>(
+    db: &C,
+    events: impl IntoIterator<Item = &'a Event>,
+) -> Result<Vec<SpaceIdWrap>, DbErr> {
+    let mut new_spaces = events
+        .into_iter()
+        .filter_map(|e| match e {
             Event::Delegation(d) => Some(d.0.capabilities.iter().filter_map(|c| {
                 match (&c.resource, c.ability.as_ref().as_ref()) {
                     (Resource::TinyCloud(r), "tinycloud.space/host")
@@ -1379,11 +2931,56 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
     new_spaces.dedup();
 
     if !new_spaces.is_empty() {
+        let existing: HashSet<SpaceId> = space::Entity::find()
+            .filter(space::Column::Id.is_in(new_spaces.clone()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|s| s.id.0)
+            .collect();
+        new_spaces.retain(|s| !existing.contains(&s.0));
+    }
+    Ok(new_spaces)
+}
+
+/// Record `events` in `db`. The delegations among them must already have
+/// been verified with [`delegation::verify_all`]; invocations and revocations
+/// are verified here.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
+    db: &C,
+    store_setup: &S,
+    secrets: &K,
+    events: Vec<Event>,
+    encryption: Option<&ColumnEncryption>,
+    allowed_spaces: Option<&HashSet<SpaceId>>,
+    policy: &dyn Policy,
+    delegation_limits: &DelegationLimits,
+    resolver: &DidResolver,
+) -> Result<TransactResult, TxError<S, K>> {
+    check_capability_counts::<S, K>(&events, delegation_limits)?;
+    check_operations::<S, K>(&events)?;
+    // for each event, get the hash and the relevent space(s)
+    let event_hashes = events
+        .into_iter()
+        .map(|e| (e.hash(), e))
+        .collect::<Vec<(Hash, Event)>>();
+    let event_spaces = event_spaces(db, &event_hashes).await?;
+    let new_spaces = new_spaces(db, event_hashes.iter().map(|(_, e)| e)).await?;
+
+    if !new_spaces.is_empty() {
+        if let Some(allowed) = allowed_spaces {
+            // a space that did not exist when the list was consulted, so was
+            // never checked against it, is refused rather than created
+            if let Some(SpaceIdWrap(space)) = new_spaces.iter().find(|s| !allowed.contains(&s.0)) {
+                return Err(TxError::SpaceNotAllowed(space.clone()));
+            }
+        }
         match space::Entity::insert_many(
             new_spaces
                 .iter()
                 .cloned()
-                .map(|id| space::Model { id })
+                .map(|id| space::Model { id, frozen: false })
                 .map(space::ActiveModel::from),
         )
         .on_conflict(
@@ -1475,16 +3072,41 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
         (event_spaces, vec![])
     };
 
+    // frozen spaces take no delegations or writes; read invocations and
+    // revocations are still recorded
+    let mutated: Vec<SpaceIdWrap> = event_spaces
+        .iter()
+        .filter(|(_, events)| {
+            events.iter().any(|(_, e)| match e {
+                Event::Delegation(_) => true,
+                Event::Invocation(_, ops) => !ops.is_empty(),
+                Event::Revocation(_) => false,
+            })
+        })
+        .map(|(space, _)| SpaceIdWrap(space.clone()))
+        .collect();
+    if !mutated.is_empty() {
+        if let Some(frozen) = space::Entity::find()
+            .filter(space::Column::Id.is_in(mutated))
+            .filter(space::Column::Frozen.eq(true))
+            .one(db)
+            .await?
+        {
+            return Err(TxError::SpaceFrozen(frozen.id.0));
+        }
+    }
+
     // If all spaces were filtered out, we still process delegations below
     // but skip epoch/event ordering creation
     if !event_spaces.is_empty() {
-        // get max sequence for each of the spaces
-        let mut max_seqs = event_order::Entity::find()
-            .filter(event_order::Column::Space.is_in(event_spaces.keys().cloned().map(SpaceIdWrap)))
+        // get max sequence for each of the spaces, from its epochs, which
+        // stay when the events they held are pruned
+        let mut max_seqs = epoch::Entity::find()
+            .filter(epoch::Column::Space.is_in(event_spaces.keys().cloned().map(SpaceIdWrap)))
             .select_only()
-            .column(event_order::Column::Space)
-            .column_as(event_order::Column::Seq.max(), "max_seq")
-            .group_by(event_order::Column::Space)
+            .column(epoch::Column::Space)
+            .column_as(epoch::Column::Seq.max(), "max_seq")
+            .group_by(epoch::Column::Space)
             .into_tuple::<(SpaceIdWrap, i64)>()
             .all(db)
             .await?
@@ -1495,27 +3117,8 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
             });
 
         // get 'most recent' epochs for each of the spaces
-        let mut most_recent = epoch::Entity::find()
-            .select_only()
-            .left_join(epoch_order::Entity)
-            .filter(
-                Condition::all()
-                    .add(epoch::Column::Space.is_in(event_spaces.keys().cloned().map(SpaceIdWrap)))
-                    .add(epoch_order::Column::Child.is_null()),
-            )
-            .column(epoch::Column::Space)
-            .column(epoch::Column::Id)
-            .into_tuple::<(SpaceIdWrap, Hash)>()
-            .all(db)
-            .await?
-            .into_iter()
-            .fold(
-                HashMap::new(),
-                |mut m: HashMap<SpaceIdWrap, Vec<Hash>>, (space, epoch)| {
-                    m.entry(space).or_default().push(epoch);
-                    m
-                },
-            );
+        let mut most_recent =
+            epoch_heads(db, event_spaces.keys().cloned().map(SpaceIdWrap)).await?;
 
         // get all the orderings and associated data
         let (epoch_order, space_order, event_order, epochs) = event_spaces
@@ -1592,7 +3195,9 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
             .exec(db)
             .await
             .map_err(|e| {
-                if let DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(db_err))) = &e {
+                if is_unique_violation(&e) {
+                    tracing::debug!(error = %e, "epoch sequence claimed by a concurrent append");
+                } else if let DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(db_err))) = &e {
                     tracing::error!(
                         error = %e,
                         db_error = %db_err,
@@ -1623,28 +3228,27 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
         for (hash, event) in event_hashes {
             match event {
                 Event::Delegation(d) => {
-                    let cid = delegation::process(db, *d, encryption).await?;
+                    let cid = delegation::process(db, *d, encryption, delegation_limits).await?;
                     delegation_cids.push(cid);
                 }
                 Event::Invocation(i, ops) => {
-                    invocation::process(
-                        db,
-                        *i,
-                        ops.into_iter()
-                            .map(|op| {
-                                let v = space_order
-                                    .get(op.space())
-                                    .and_then(|(s, e, _, h)| Some((s, e, h.get(&hash)?)))
-                                    .unwrap();
-                                op.version(*v.0, *v.1, *v.2)
-                            })
-                            .collect(),
-                        encryption,
-                    )
-                    .await?;
+                    let invoker = i.0.invoker.clone();
+                    let capabilities = i.0.capabilities.clone();
+                    let ops = ops
+                        .into_iter()
+                        .map(|op| {
+                            let (seq, epoch, epoch_seq) = space_order
+                                .get(op.space())
+                                .and_then(|(s, e, _, h)| Some((*s, *e, *h.get(&hash)?)))
+                                .ok_or_else(|| unauthorized_operation(&op))?;
+                            Ok(op.version(seq, epoch, epoch_seq))
+                        })
+                        .collect::<Result<_, TxError<S, K>>>()?;
+                    invocation::process(db, *i, ops, resolver, encryption).await?;
+                    authorize_with_policy(policy, &invoker, &capabilities).await?;
                 }
                 Event::Revocation(r) => {
-                    revocation::process(db, *r).await?;
+                    revocation::process(db, *r, resolver).await?;
                 }
             };
         }
@@ -1685,14 +3289,14 @@ pub(crate) async fn transact<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
         for (_, event) in event_hashes {
             match event {
                 Event::Delegation(d) => {
-                    let cid = delegation::process(db, *d, encryption).await?;
+                    let cid = delegation::process(db, *d, encryption, delegation_limits).await?;
                     delegation_cids.push(cid);
                 }
                 Event::Invocation(i, _ops) => {
-                    invocation::process(db, *i, Vec::new(), encryption).await?;
+                    invocation::process(db, *i, Vec::new(), resolver, encryption).await?;
                 }
                 Event::Revocation(r) => {
-                    revocation::process(db, *r).await?;
+                    revocation::process(db, *r, resolver).await?;
                 }
             };
         }
@@ -1726,12 +3330,59 @@ async fn list<C: ConnectionTrait>(
         .map(|(paths, _)| paths)
 }
 
-async fn list_bounded<C: ConnectionTrait>(
+/// How many live keys `space_id` holds.
+async fn count_live_kv<C: ConnectionTrait>(db: &C, space_id: &SpaceId) -> Result<u64, DbErr> {
+    let mut live = live_kv_writes_query(space_id, &"".parse().expect("paths always parse"));
+    live.column((kv_write::Entity, kv_write::Column::Key));
+    let count = Query::select()
+        .expr_as(Expr::col(Asterisk).count(), Alias::new("count"))
+        .from_subquery(live, Alias::new("live_kv"))
+        .to_owned();
+    Ok(db
+        .query_one(db.get_database_backend().build(&count))
+        .await?
+        .map(|row| row.try_get::<i64>("", "count"))
+        .transpose()?
+        .unwrap_or(0) as u64)
+}
+
+/// How many unrevoked delegations grant something in the space and are
+/// within their validity period, counted in the database. Their proof
+/// chains are not walked, so one whose ancestor was revoked still counts.
+async fn count_current_delegations<C: ConnectionTrait>(
     db: &C,
     space_id: &SpaceId,
-    prefix: &Path,
-    limit: Option<usize>,
-) -> Result<(Vec<Path>, bool), DbErr> {
+) -> Result<u64, DbErr> {
+    let now = OffsetDateTime::now_utc();
+    let space = escape_like(&space_id.to_string());
+    delegation::Entity::find()
+        .left_join(revocation::Entity)
+        .filter(revocation::Column::Id.is_null())
+        .filter(
+            delegation::Column::Id.in_subquery(delegations_granting(
+                Condition::all().add(
+                    Expr::col(abilities::Column::Resource)
+                        .like(LikeExpr::new(format!("{space}/%")).escape('!')),
+                ),
+            )),
+        )
+        .filter(
+            Condition::any()
+                .add(delegation::Column::Expiry.is_null())
+                .add(delegation::Column::Expiry.gt(now)),
+        )
+        .filter(
+            Condition::any()
+                .add(delegation::Column::NotBefore.is_null())
+                .add(delegation::Column::NotBefore.lte(now)),
+        )
+        .count(db)
+        .await
+}
+
+/// Select the live (latest, non-tombstoned) `kv_write` rows under `prefix`,
+/// ordered by key. Callers choose the projected columns and any limit.
+fn live_kv_writes_query(space_id: &SpaceId, prefix: &Path) -> sea_orm::sea_query::SelectStatement {
     let newer = Alias::new("newer_kv_write");
     let newer_order = Condition::any()
         .add(
@@ -1780,14 +3431,9 @@ async fn list_bounded<C: ConnectionTrait>(
                 .add(newer_order),
         )
         .to_owned();
-    let escaped_prefix = prefix
-        .as_str()
-        .replace('!', "!!")
-        .replace('%', "!%")
-        .replace('_', "!_");
+    let escaped_prefix = escape_like(prefix.as_str());
     let mut query = Query::select();
     query
-        .column((kv_write::Entity, kv_write::Column::Key))
         .from(kv_write::Entity)
         .left_join(
             kv_delete::Entity,
@@ -1819,6 +3465,25 @@ async fn list_bounded<C: ConnectionTrait>(
                 .add(Condition::all().not().add(Expr::exists(newer_write))),
         )
         .order_by((kv_write::Entity, kv_write::Column::Key), Order::Asc);
+    query
+}
+
+/// `value` with the wildcards of a `LIKE` pattern escaped by `!`.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('!', "!!")
+        .replace('%', "!%")
+        .replace('_', "!_")
+}
+
+async fn list_bounded<C: ConnectionTrait>(
+    db: &C,
+    space_id: &SpaceId,
+    prefix: &Path,
+    limit: Option<usize>,
+) -> Result<(Vec<Path>, bool), DbErr> {
+    let mut query = live_kv_writes_query(space_id, prefix);
+    query.column((kv_write::Entity, kv_write::Column::Key));
     if let Some(limit) = limit {
         query.limit(limit.saturating_add(1) as u64);
     }
@@ -1839,6 +3504,75 @@ async fn list_bounded<C: ConnectionTrait>(
     Ok((list, truncated))
 }
 
+async fn list_detailed_bounded<C: ConnectionTrait>(
+    db: &C,
+    space_id: &SpaceId,
+    prefix: &Path,
+    limit: Option<usize>,
+) -> Result<(Vec<(Path, Metadata, Hash)>, bool), DbErr> {
+    let mut query = live_kv_writes_query(space_id, prefix);
+    query.columns([
+        (kv_write::Entity, kv_write::Column::Key),
+        (kv_write::Entity, kv_write::Column::Value),
+        (kv_write::Entity, kv_write::Column::Metadata),
+    ]);
+    if let Some(limit) = limit {
+        query.limit(limit.saturating_add(1) as u64);
+    }
+    let mut list = db
+        .query_all(db.get_database_backend().build(&query))
+        .await?
+        .into_iter()
+        .map(|row| {
+            let key = row
+                .try_get::<String>("", kv_write::Column::Key.as_str())?
+                .parse::<Path>()
+                .map_err(|error| DbErr::Custom(format!("invalid persisted KV path: {error}")))?;
+            Ok((
+                key,
+                row.try_get::<Metadata>("", kv_write::Column::Metadata.as_str())?,
+                row.try_get::<Hash>("", kv_write::Column::Value.as_str())?,
+            ))
+        })
+        .collect::<Result<Vec<_>, DbErr>>()?;
+    let truncated = limit.map(|limit| list.len() > limit).unwrap_or(false);
+    if let Some(limit) = limit {
+        list.truncate(limit);
+    }
+    Ok((list, truncated))
+}
+
+/// Like [`list_detailed_bounded`], keeping only the keys whose content type
+/// starts with `content_type`, ignoring case.
+///
+/// Metadata is stored as a serialized map, which no index covers and whose
+/// JSON operators differ between backends, so the filter is applied to the
+/// listed rows rather than in the query: every live key under `prefix` is
+/// read, however few match, and `limit` bounds only what is returned.
+async fn list_by_content_type<C: ConnectionTrait>(
+    db: &C,
+    space_id: &SpaceId,
+    prefix: &Path,
+    content_type: &str,
+    limit: Option<usize>,
+) -> Result<(Vec<(Path, Metadata, Hash)>, bool), DbErr> {
+    let content_type = content_type.to_ascii_lowercase();
+    let (list, _) = list_detailed_bounded(db, space_id, prefix, None).await?;
+    let mut list = list
+        .into_iter()
+        .filter(|(_, metadata, _)| {
+            metadata
+                .content_type()
+                .is_some_and(|t| t.to_ascii_lowercase().starts_with(&content_type))
+        })
+        .collect::<Vec<_>>();
+    let truncated = limit.map(|limit| list.len() > limit).unwrap_or(false);
+    if let Some(limit) = limit {
+        list.truncate(limit);
+    }
+    Ok((list, truncated))
+}
+
 async fn metadata<C: ConnectionTrait>(
     db: &C,
     space_id: &SpaceId,
@@ -1929,15 +3663,87 @@ async fn get_kv_entity<C: ConnectionTrait>(
     )
 }
 
-async fn get_valid_delegations<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
-    db: &C,
-    space_id: &SpaceId,
-    encryption: Option<&ColumnEncryption>,
-) -> Result<HashMap<Hash, DelegationInfo>, TxError<S, K>> {
-    let (dels, abilities): (Vec<delegation::Model>, Vec<Vec<abilities::Model>>) =
-        delegation::Entity::find()
-            .left_join(revocation::Entity)
+/// The ids among `dels`, which must exclude revoked delegations, that are
+/// valid at `now` along with every delegation in their proof chains.
+/// Delegations citing a parent outside the set are dropped, so a revoked or
+/// expired delegation invalidates everything delegated from it.
+///
+/// Validity flows from parents to children in one topological walk over the
+/// `parents` edges, so each delegation and edge is visited once.
+fn live_delegations(
+    dels: &[delegation::Model],
+    parents: &[Vec<parent_delegations::Model>],
+    now: OffsetDateTime,
+) -> HashSet<Hash> {
+    let index: HashMap<Hash, usize> = dels
+        .iter()
+        .enumerate()
+        .map(|(i, del)| (del.id, i))
+        .collect();
+    let mut live: Vec<bool> = dels
+        .iter()
+        .map(|del| {
+            del.expiry.map(|e| e > now).unwrap_or(true)
+                && del.not_before.map(|n| n <= now).unwrap_or(true)
+        })
+        .collect();
+    // parents in the set each delegation still waits on, and its children
+    let mut waiting = vec![0usize; dels.len()];
+    let mut children = vec![Vec::new(); dels.len()];
+    for (child, parents) in parents.iter().enumerate() {
+        for parent in parents {
+            match index.get(&parent.parent) {
+                Some(&parent) => {
+                    waiting[child] += 1;
+                    children[parent].push(child);
+                }
+                // revoked, or never stored
+                None => live[child] = false,
+            }
+        }
+    }
+
+    let mut ready: Vec<usize> = (0..dels.len()).filter(|&i| waiting[i] == 0).collect();
+    while let Some(parent) = ready.pop() {
+        for &child in &children[parent] {
+            live[child] &= live[parent];
+            waiting[child] -= 1;
+            if waiting[child] == 0 {
+                ready.push(child);
+            }
+        }
+    }
+    // content addressing rules out cycles, but a delegation still waiting on
+    // a parent was never reached from a root, so is not live
+    dels.iter()
+        .zip(live)
+        .zip(waiting)
+        .filter(|((_, live), waiting)| *live && *waiting == 0)
+        .map(|((del, _), _)| del.id)
+        .collect()
+}
+
+/// The delegations granting something in the space, each valid along with
+/// its whole proof chain. Only the space's delegations and the ancestors
+/// they cite are read.
+async fn get_valid_delegations<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
+    db: &C,
+    space_id: &SpaceId,
+    encryption: Option<&ColumnEncryption>,
+) -> Result<HashMap<Hash, DelegationInfo>, TxError<S, K>> {
+    let space = escape_like(&space_id.to_string());
+    let (dels, abilities): (Vec<delegation::Model>, Vec<Vec<abilities::Model>>) =
+        delegation::Entity::find()
+            .left_join(revocation::Entity)
             .filter(revocation::Column::Id.is_null())
+            .filter(
+                delegation::Column::Id.in_subquery(delegations_granting(
+                    Condition::all().add(
+                        Expr::col(abilities::Column::Resource)
+                            .like(LikeExpr::new(format!("{space}/%")).escape('!')),
+                    ),
+                )),
+            )
             .find_with_related(abilities::Entity)
             .all(db)
             .await?
@@ -1945,48 +3751,97 @@ async fn get_valid_delegations<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
             .unzip();
     let parents = dels.load_many(parent_delegations::Entity, db).await?;
     let now = time::OffsetDateTime::now_utc();
+
+    let live = live_with_ancestors(db, &dels, &parents, now).await?;
     dels.into_iter()
         .zip(abilities)
         .zip(parents)
-        .filter_map(|((del, ability), parents)| {
-            if del.expiry.map(|e| e > now).unwrap_or(true)
-                && del.not_before.map(|n| n <= now).unwrap_or(true)
-                && ability.iter().any(|a| a.resource.space() == Some(space_id))
-            {
-                let serialization =
-                    match crate::encryption::maybe_decrypt(encryption, &del.serialization) {
-                        Ok(s) => s,
-                        Err(e) => return Some(Err(TxError::Encryption(e))),
-                    };
-                Some(match TinyCloudDelegation::from_bytes(&serialization) {
-                    Ok(delegation) => Ok((
-                        del.id,
-                        DelegationInfo {
-                            delegator: del.delegator,
-                            delegate: del.delegatee,
-                            parents: parents.into_iter().map(|p| p.parent.to_cid(0x55)).collect(),
-                            expiry: del.expiry,
-                            not_before: del.not_before,
-                            issued_at: del.issued_at,
-                            delegation_mode: mode_from_facts(&del.facts),
-                            capabilities: ability
-                                .into_iter()
-                                .map(|a| Capability {
-                                    resource: a.resource,
-                                    ability: a.ability,
-                                    caveats: a.caveats,
-                                })
-                                .collect(),
-                            delegation,
-                        },
-                    )),
-                    Err(e) => Err(TxError::Encoding(e)),
-                })
-            } else {
-                None
-            }
+        .filter(|((del, ability), _)| {
+            live.contains(&del.id) && ability.iter().any(|a| a.resource.space() == Some(space_id))
+        })
+        .map(|((del, ability), parents)| {
+            let id = del.id;
+            delegation_info(del, ability, parents, encryption).map(|info| (id, info))
+        })
+        .collect()
+}
+
+/// The listing form of a stored delegation, its abilities and its parents.
+fn delegation_info<S: StorageSetup, K: Secrets>(
+    del: delegation::Model,
+    ability: Vec<abilities::Model>,
+    parents: Vec<parent_delegations::Model>,
+    encryption: Option<&ColumnEncryption>,
+) -> Result<DelegationInfo, TxError<S, K>> {
+    let serialization = crate::encryption::maybe_decrypt(encryption, &del.serialization)?;
+    Ok(DelegationInfo {
+        delegation: TinyCloudDelegation::from_bytes(&serialization)?,
+        delegator: del.delegator,
+        delegate: del.delegatee,
+        parents: parents
+            .into_iter()
+            .map(|p| p.parent.to_event_cid())
+            .collect(),
+        expiry: del.expiry,
+        not_before: del.not_before,
+        issued_at: del.issued_at,
+        delegation_mode: mode_from_facts(&del.facts),
+        capabilities: ability
+            .into_iter()
+            .map(|a| Capability {
+                resource: a.resource,
+                ability: a.ability,
+                caveats: a.caveats,
+            })
+            .collect(),
+    })
+}
+
+/// The union of capabilities in `space_id` granted to `invoker` by unrevoked,
+/// unexpired delegations with valid proof chains, collapsed by
+/// [`collapse_capabilities`].
+async fn get_effective_permissions<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
+    db: &C,
+    space_id: &SpaceId,
+    invoker: &str,
+    encryption: Option<&ColumnEncryption>,
+) -> Result<Vec<Capability>, TxError<S, K>> {
+    Ok(collapse_capabilities(
+        get_valid_delegations(db, space_id, encryption)
+            .await?
+            .into_values()
+            .filter(|del| did_principal_matches(&del.delegate, invoker))
+            .flat_map(|del| del.capabilities)
+            .filter(|cap| cap.resource.space() == Some(space_id))
+            .collect(),
+    ))
+}
+
+/// Sort and dedupe capabilities, dropping any that an uncaveated capability
+/// on the same or a parent resource already grants (directly or through the
+/// registry's `implies`).
+fn collapse_capabilities(mut caps: Vec<Capability>) -> Vec<Capability> {
+    caps.sort_by_cached_key(|cap| (cap.resource.to_string(), cap.ability.to_string()));
+    caps.dedup();
+    let covers = |held: &Capability, cap: &Capability| {
+        held.caveats.0.is_empty()
+            && cap.resource.extends(&held.resource)
+            && crate::policy_capability::ability_matches(
+                held.ability.as_ref().as_ref(),
+                cap.ability.as_ref().as_ref(),
+            )
+    };
+    caps.iter()
+        .enumerate()
+        .filter(|(i, cap)| {
+            // of two capabilities covering each other, keep the first
+            !caps
+                .iter()
+                .enumerate()
+                .any(|(j, held)| j != *i && covers(held, cap) && (j < *i || !covers(cap, held)))
         })
-        .collect::<Result<HashMap<Hash, DelegationInfo>, TxError<S, K>>>()
+        .map(|(_, cap)| cap.clone())
+        .collect()
 }
 
 /// Decode the persisted `xyz.tinycloud.policy/delegationMode` marker from
@@ -2217,7 +4072,7 @@ impl AccountAncestorState {
                     return Ok(AccountLifecycle {
                         status: "ancestor_revoked",
                         direct_revocation: None,
-                        revoked_ancestor_cid: Some(current.to_cid(0x55).to_string()),
+                        revoked_ancestor_cid: Some(current.to_event_cid().to_string()),
                     });
                 }
                 effective_ids.push(current);
@@ -2329,121 +4184,191 @@ async fn load_account_ancestor_state<C: ConnectionTrait>(
     })
 }
 
-/// Get delegations with optional filters applied.
-/// Filters by direction (created/received relative to invoker), path prefix, and actions.
+/// Most delegations one page of a `capabilities/read` listing holds.
+const MAX_DELEGATION_PAGE: u64 = 1000;
+
+/// Get a page of the space's live delegations with optional filters applied,
+/// and the id to continue the listing after if it was cut short.
+///
+/// The space, service, action and principal filters are part of the query.
+/// The path prefix and the validity of each proof chain are checked as rows
+/// are read, so a page may take several reads to fill.
 async fn get_filtered_delegations<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
     db: &C,
     space_id: &SpaceId,
     invoker: &str,
     filters: Option<&ListFilters>,
     encryption: Option<&ColumnEncryption>,
-) -> Result<HashMap<Hash, DelegationInfo>, TxError<S, K>> {
+) -> Result<(HashMap<Hash, DelegationInfo>, Option<Hash>), TxError<S, K>> {
+    use tinycloud_auth::ipld_core::cid::Cid;
+
     // Resolve session key DID to PKH DID for direction filtering
     let pkh_did = resolve_pkh_did(db, invoker)
         .await
         .unwrap_or_else(|_| invoker.to_string());
+    let no_filters = ListFilters::default();
+    let filters = filters.unwrap_or(&no_filters);
+    let direction = filters.direction.as_deref();
+
+    let space = escape_like(&space_id.to_string());
+    let mut query = delegation::Entity::find()
+        .left_join(revocation::Entity)
+        .filter(revocation::Column::Id.is_null())
+        .filter(
+            delegation::Column::Id.in_subquery(delegations_granting(
+                Condition::all().add(
+                    Expr::col(abilities::Column::Resource)
+                        .like(LikeExpr::new(format!("{space}/%")).escape('!')),
+                ),
+            )),
+        );
+    if let Some(service) = &filters.service {
+        let resource = format!("{space}/{}", escape_like(service));
+        query = query.filter(delegation::Column::Id.in_subquery(
+            delegations_granting(["", "/%", "?%", "#%"].into_iter().fold(
+                Condition::any(),
+                |any, rest| {
+                    any.add(
+                        Expr::col(abilities::Column::Resource)
+                            .like(LikeExpr::new(format!("{resource}{rest}")).escape('!')),
+                    )
+                },
+            )),
+        ));
+    }
+    if let Some(actions) = &filters.actions {
+        query = query.filter(delegation::Column::Id.in_subquery(delegations_granting(
+            Condition::all().add(abilities::Column::Ability.is_in(actions.iter().cloned())),
+        )));
+    }
+    match direction {
+        Some("created") => {
+            query = query.filter(principal_condition(delegation::Column::Delegator, &pkh_did))
+        }
+        Some("received") => {
+            query = query.filter(principal_condition(delegation::Column::Delegatee, &pkh_did))
+        }
+        _ => {}
+    }
+    if let Some(delegate) = &filters.delegate {
+        query = query.filter(principal_condition(delegation::Column::Delegatee, delegate));
+    }
 
-    let (dels, abilities): (Vec<delegation::Model>, Vec<Vec<abilities::Model>>) =
-        delegation::Entity::find()
-            .left_join(revocation::Entity)
-            .filter(revocation::Column::Id.is_null())
-            .find_with_related(abilities::Entity)
-            .all(db)
-            .await?
-            .into_iter()
-            .unzip();
-    let parents = dels.load_many(parent_delegations::Entity, db).await?;
+    let limit = filters
+        .limit
+        .map(|limit| limit.clamp(1, MAX_DELEGATION_PAGE));
+    let mut after = filters
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<Cid>()
+                .map(Hash::from)
+                .map_err(|_| TxError::<S, K>::InvalidCid(cursor.to_string()))
+        })
+        .transpose()?;
     let now = time::OffsetDateTime::now_utc();
-
-    // Extract filter values
-    let direction = filters.and_then(|f| f.direction.as_deref());
-    let path_prefix = filters.and_then(|f| f.path.as_deref());
-    let actions = filters.and_then(|f| f.actions.as_ref());
-
-    dels.into_iter()
-        .zip(abilities)
-        .zip(parents)
-        .filter_map(|((del, ability), parents)| {
-            // Time validity check
-            if !(del.expiry.map(|e| e > now).unwrap_or(true)
-                && del.not_before.map(|n| n <= now).unwrap_or(true))
-            {
-                return None;
-            }
-
-            // Space membership check
-            if !ability.iter().any(|a| a.resource.space() == Some(space_id)) {
-                return None;
-            }
-
-            // Direction filter (using resolved PKH DID, not session key DID)
-            match direction {
-                Some("created") if !did_principal_matches(&del.delegator, &pkh_did) => {
-                    return None;
-                }
-                Some("received") if !did_principal_matches(&del.delegatee, &pkh_did) => {
-                    return None;
-                }
-                _ => {}
-            }
-
-            // Path prefix filter
-            if let Some(prefix) = path_prefix {
-                let has_matching_path = ability.iter().any(|a| {
+    let mut page = HashMap::new();
+    loop {
+        let mut batch = query.clone().order_by_asc(delegation::Column::Id);
+        if let Some(after) = after {
+            batch = batch.filter(delegation::Column::Id.gt(after));
+        }
+        if let Some(limit) = limit {
+            batch = batch.limit(limit + 1);
+        }
+        let dels = batch.all(db).await?;
+        let more = limit.is_some_and(|limit| dels.len() as u64 > limit);
+        let abilities = dels.load_many(abilities::Entity, db).await?;
+        let parents = dels.load_many(parent_delegations::Entity, db).await?;
+        let live = live_with_ancestors(db, &dels, &parents, now).await?;
+
+        let mut rows = dels.into_iter().zip(abilities).zip(parents).peekable();
+        while let Some(((del, ability), parents)) = rows.next() {
+            let id = del.id;
+            after = Some(id);
+            // the query matches principals case-insensitively; check them exactly
+            let principals_match = match direction {
+                Some("created") => did_principal_matches(&del.delegator, &pkh_did),
+                Some("received") => did_principal_matches(&del.delegatee, &pkh_did),
+                _ => true,
+            } && filters
+                .delegate
+                .as_ref()
+                .is_none_or(|delegate| did_principal_matches(&del.delegatee, delegate));
+            let path_matches = filters.path.as_deref().is_none_or(|prefix| {
+                ability.iter().any(|a| {
                     a.resource
                         .tinycloud_resource()
                         .and_then(|r| r.path())
-                        .map(|p| p.as_str().starts_with(prefix))
-                        .unwrap_or(false)
-                });
-                if !has_matching_path {
-                    return None;
-                }
+                        .is_some_and(|p| p.as_str().starts_with(prefix))
+                })
+            });
+            if !live.contains(&id) || !principals_match || !path_matches {
+                continue;
             }
-
-            // Actions filter
-            if let Some(action_list) = actions {
-                let has_matching_action = ability.iter().any(|a| {
-                    action_list
-                        .iter()
-                        .any(|action| a.ability.as_ref().as_ref() == action.as_str())
-                });
-                if !has_matching_action {
-                    return None;
-                }
+            page.insert(id, delegation_info(del, ability, parents, encryption)?);
+            if limit.is_some_and(|limit| page.len() as u64 >= limit) {
+                return Ok((page, (more || rows.peek().is_some()).then_some(id)));
             }
+        }
+        if !more {
+            return Ok((page, None));
+        }
+    }
+}
 
-            let serialization =
-                match crate::encryption::maybe_decrypt(encryption, &del.serialization) {
-                    Ok(s) => s,
-                    Err(e) => return Some(Err(TxError::Encryption(e))),
-                };
-            Some(match TinyCloudDelegation::from_bytes(&serialization) {
-                Ok(delegation) => Ok((
-                    del.id,
-                    DelegationInfo {
-                        delegator: del.delegator,
-                        delegate: del.delegatee,
-                        parents: parents.into_iter().map(|p| p.parent.to_cid(0x55)).collect(),
-                        expiry: del.expiry,
-                        not_before: del.not_before,
-                        issued_at: del.issued_at,
-                        delegation_mode: mode_from_facts(&del.facts),
-                        capabilities: ability
-                            .into_iter()
-                            .map(|a| Capability {
-                                resource: a.resource,
-                                ability: a.ability,
-                                caveats: a.caveats,
-                            })
-                            .collect(),
-                        delegation,
-                    },
-                )),
-                Err(e) => Err(TxError::Encoding(e)),
-            })
-        })
-        .collect::<Result<HashMap<Hash, DelegationInfo>, TxError<S, K>>>()
+/// Ids of the delegations with an ability row matching `condition`.
+fn delegations_granting(condition: Condition) -> sea_orm::sea_query::SelectStatement {
+    Query::select()
+        .column(abilities::Column::Delegation)
+        .from(abilities::Entity)
+        .cond_where(condition)
+        .to_owned()
+}
+
+/// Rows whose `column` is `did`, or a DID URL of it, ignoring case. This is a
+/// superset of [`did_principal_matches`], which rows are checked with after.
+fn principal_condition(column: delegation::Column, did: &str) -> Condition {
+    let did = did.split('#').next().unwrap_or(did).to_lowercase();
+    let lower = || Expr::expr(Func::lower(Expr::col((delegation::Entity, column))));
+    Condition::any()
+        .add(lower().eq(did.clone()))
+        .add(lower().like(LikeExpr::new(format!("{}#%", escape_like(&did))).escape('!')))
+}
+
+/// The ids among `dels`, which must exclude revoked delegations, valid at
+/// `now` along with their whole proof chains, reading the ancestors `dels`
+/// cite from `db` as [`live_delegations`] needs them.
+async fn live_with_ancestors<C: ConnectionTrait>(
+    db: &C,
+    dels: &[delegation::Model],
+    parents: &[Vec<parent_delegations::Model>],
+    now: OffsetDateTime,
+) -> Result<HashSet<Hash>, DbErr> {
+    let mut chain = dels.to_vec();
+    let mut chain_parents = parents.to_vec();
+    let mut read: HashSet<Hash> = dels.iter().map(|del| del.id).collect();
+    loop {
+        let missing: HashSet<Hash> = chain_parents
+            .iter()
+            .flatten()
+            .map(|p| p.parent)
+            .filter(|parent| !read.contains(parent))
+            .collect();
+        if missing.is_empty() {
+            return Ok(live_delegations(&chain, &chain_parents, now));
+        }
+        read.extend(missing.iter().copied());
+        let ancestors = delegation::Entity::find()
+            .left_join(revocation::Entity)
+            .filter(revocation::Column::Id.is_null())
+            .filter(delegation::Column::Id.is_in(missing))
+            .all(db)
+            .await?;
+        chain_parents.extend(ancestors.load_many(parent_delegations::Entity, db).await?);
+        chain.extend(ancestors);
+    }
 }
 
 /// Get the delegation chain for a specific delegation, ordered from leaf to root.
@@ -2500,7 +4425,7 @@ async fn get_delegation_chain<C: ConnectionTrait, S: StorageSetup, K: Secrets>(
             .all(db)
             .await?;
 
-        let parent_cids: Vec<Cid> = parents.iter().map(|p| p.parent.to_cid(0x55)).collect();
+        let parent_cids: Vec<Cid> = parents.iter().map(|p| p.parent.to_event_cid()).collect();
 
         // Create DelegationInfo
         let serialization = crate::encryption::maybe_decrypt(encryption, &del.serialization)?;
@@ -2565,9 +4490,2517 @@ mod test {
         SpaceId::new(did, name.parse().unwrap())
     }
 
+    /// A space whose DID is controlled by the returned key, so invocations it
+    /// signs are root-authorized and need no delegation chain.
+    async fn owned_test_space<B>(
+        db: &SpaceDatabase<sea_orm::DbConn, B, StaticSecret>,
+        name: &str,
+    ) -> (JWK, SpaceId) {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, name.parse().unwrap());
+        space::ActiveModel {
+            id: Set(SpaceIdWrap(space.clone())),
+            ..Default::default()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        (jwk, space)
+    }
+
+    /// An invocation of kv capabilities in `space` signed by `jwk`, which is
+    /// the space owner unless a test is exercising authorization.
+    fn owner_invocation(
+        jwk: &JWK,
+        space: &SpaceId,
+        caps: &[(&str, &str)],
+        facts: Vec<serde_json::Value>,
+    ) -> Invocation {
+        owner_service_invocation(jwk, space, "kv", caps, facts)
+    }
+
+    /// Like [`owner_invocation`], for `service` instead of `kv`.
+    fn owner_service_invocation(
+        jwk: &JWK,
+        space: &SpaceId,
+        service: &str,
+        caps: &[(&str, &str)],
+        facts: Vec<serde_json::Value>,
+    ) -> Invocation {
+        key_invocation(jwk, space, service, caps, facts, &[])
+    }
+
+    /// An invocation of `service` capabilities in `space` signed by `jwk`,
+    /// citing the delegations hashed in `parents` as proofs.
+    fn key_invocation(
+        jwk: &JWK,
+        space: &SpaceId,
+        service: &str,
+        caps: &[(&str, &str)],
+        facts: Vec<serde_json::Value>,
+        parents: &[Hash],
+    ) -> Invocation {
+        key_invocation_expiring(jwk, space, service, caps, facts, parents, 4_102_444_800.0)
+    }
+
+    /// Like [`key_invocation`], expiring at `expiration` seconds since the
+    /// epoch.
+    fn key_invocation_expiring(
+        jwk: &JWK,
+        space: &SpaceId,
+        service: &str,
+        caps: &[(&str, &str)],
+        facts: Vec<serde_json::Value>,
+        parents: &[Hash],
+        expiration: f64,
+    ) -> Invocation {
+        use tinycloud_auth::{
+            authorization::{HeaderEncode, TinyCloudInvocation},
+            ssi::{claims::jwt::NumericDate, dids::DIDURLBuf, ucan::Payload},
+            ucan_capabilities_object::Capabilities,
+        };
+
+        let did = DID_METHODS.generate(jwk, "key").unwrap().to_string();
+        let fragment = did.rsplit_once(':').unwrap().1;
+        let mut attenuation = Capabilities::new();
+        for (path, ability) in caps {
+            let resource = space.clone().to_resource(
+                service.parse().unwrap(),
+                Some(path.parse().unwrap()),
+                None,
+                None,
+            );
+            attenuation.with_actions(
+                resource.as_uri(),
+                std::iter::once((ability.parse().unwrap(), [])),
+            );
+        }
+        let invocation: TinyCloudInvocation = Payload {
+            issuer: format!("{did}#{fragment}").parse::<DIDURLBuf>().unwrap(),
+            audience: space.did().to_string().parse::<DIDBuf>().unwrap(),
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(expiration).unwrap(),
+            nonce: Some(format!("test-{caps:?}-{facts:?}")),
+            facts: Some(facts),
+            proof: parents.iter().map(|p| p.to_event_cid()).collect(),
+            attenuation,
+        }
+        .sign(tinycloud_auth::ssi::jwk::Algorithm::EdDSA, jwk)
+        .unwrap();
+        Invocation::from_header_ser::<TinyCloudInvocation>(&invocation.encode().unwrap()).unwrap()
+    }
+
+    /// An owner-signed `tinycloud.space/host` delegation of `space` to `host`.
+    fn owner_host_delegation(jwk: &JWK, space: &SpaceId, host: &str) -> Delegation {
+        owner_delegation(jwk, space, host, &[("space", None, "tinycloud.space/host")])
+    }
+
+    /// An owner-signed delegation of `(service, path, ability)` capabilities
+    /// in `space` to `audience`.
+    fn owner_delegation(
+        jwk: &JWK,
+        space: &SpaceId,
+        audience: &str,
+        caps: &[(&str, Option<&str>, &str)],
+    ) -> Delegation {
+        key_delegation(jwk, space, audience, caps, &[])
+    }
+
+    /// A delegation of `(service, path, ability)` capabilities in `space`
+    /// signed by `jwk`, citing the delegations hashed in `parents` as proofs.
+    fn key_delegation(
+        jwk: &JWK,
+        space: &SpaceId,
+        audience: &str,
+        caps: &[(&str, Option<&str>, &str)],
+        parents: &[Hash],
+    ) -> Delegation {
+        caveated_delegation(jwk, space, audience, caps, parents, None)
+    }
+
+    /// Like [`key_delegation`], with `nota_bene` as the caveats of each
+    /// capability.
+    fn caveated_delegation(
+        jwk: &JWK,
+        space: &SpaceId,
+        audience: &str,
+        caps: &[(&str, Option<&str>, &str)],
+        parents: &[Hash],
+        nota_bene: Option<serde_json::Value>,
+    ) -> Delegation {
+        use tinycloud_auth::{
+            authorization::{HeaderEncode, TinyCloudDelegation},
+            ssi::{claims::jwt::NumericDate, dids::DIDURLBuf, ucan::Payload},
+            ucan_capabilities_object::Capabilities,
+        };
+
+        let did = DID_METHODS.generate(jwk, "key").unwrap().to_string();
+        let fragment = did.rsplit_once(':').unwrap().1;
+        let mut attenuation = Capabilities::new();
+        for (service, path, ability) in caps {
+            let resource = space.clone().to_resource(
+                service.parse().unwrap(),
+                path.map(|p| p.parse().unwrap()),
+                None,
+                None,
+            );
+            let nota_bene = nota_bene.as_ref().map(|nb| {
+                serde_json::from_value::<std::collections::BTreeMap<String, serde_json::Value>>(
+                    nb.clone(),
+                )
+                .unwrap()
+            });
+            attenuation.with_actions(
+                resource.as_uri(),
+                std::iter::once((ability.parse().unwrap(), nota_bene)),
+            );
+        }
+        let ucan = Payload {
+            issuer: format!("{did}#{fragment}").parse::<DIDURLBuf>().unwrap(),
+            audience: audience.parse::<DIDBuf>().unwrap(),
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(4_102_444_800.0).unwrap(),
+            nonce: Some(format!("delegation-{audience}-{caps:?}-{nota_bene:?}")),
+            facts: None,
+            proof: parents.iter().map(|p| p.to_event_cid()).collect(),
+            attenuation,
+        }
+        .sign(tinycloud_auth::ssi::jwk::Algorithm::EdDSA, jwk)
+        .unwrap();
+        Delegation::from_header_ser::<TinyCloudDelegation>(
+            &TinyCloudDelegation::Ucan(Box::new(ucan)).encode().unwrap(),
+        )
+        .unwrap()
+    }
+
+    async fn put_value(
+        db: &SpaceDatabase<sea_orm::DbConn, MemoryStore, StaticSecret>,
+        jwk: &JWK,
+        space: &SpaceId,
+        path: &str,
+        value: &[u8],
+    ) -> Hash {
+        put_typed_value(db, jwk, space, path, value, "text/plain").await
+    }
+
+    /// Like [`put_value`], declaring `content_type`.
+    async fn put_typed_value(
+        db: &SpaceDatabase<sea_orm::DbConn, MemoryStore, StaticSecret>,
+        jwk: &JWK,
+        space: &SpaceId,
+        path: &str,
+        value: &[u8],
+        content_type: &str,
+    ) -> Hash {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let mut stage = MemoryStaging.stage(space).await.unwrap();
+        stage.write_all(value).await.unwrap();
+        let inputs = HashMap::from([(
+            (space.clone(), path.parse().unwrap()),
+            (
+                Metadata(std::collections::BTreeMap::from([(
+                    "content-type".to_string(),
+                    content_type.to_string(),
+                )])),
+                stage,
+            ),
+        )]);
+        match db
+            .invoke::<MemoryStaging>(
+                owner_invocation(jwk, space, &[(path, "tinycloud.kv/put")], vec![]),
+                inputs,
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap()
+            .1
+            .pop()
+        {
+            Some(InvocationOutcome::KvWrite(hash)) => hash,
+            _ => panic!("expected a kv write outcome"),
+        }
+    }
+
+    #[tokio::test]
+    async fn basic() {
+        let _db = get_db().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stage_key_keeps_hosted_spaces_on_their_rotated_key() {
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+        let old_secret = [1u8; 32].to_vec();
+        let old = StaticSecret::new(old_secret.clone()).unwrap();
+        let db = SpaceDatabase::new(
+            Database::connect(ConnectOptions::new("sqlite::memory:".to_string()))
+                .await
+                .unwrap(),
+            MemoryStore::default(),
+            StaticSecret::new([2u8; 32].to_vec())
+                .unwrap()
+                .with_previous(vec![old_secret])
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let hosted = test_space_id("hosted");
+        let old_did = get_did_key(old.get_pubkey(&hosted).await.unwrap());
+        for actor_id in [hosted.did().to_string(), old_did.clone()] {
+            actor::ActiveModel { id: Set(actor_id) }
+                .insert(&db.conn)
+                .await
+                .unwrap();
+        }
+        let host_delegation = crate::hash::hash(b"host-under-old-key");
+        delegation::ActiveModel {
+            id: Set(host_delegation),
+            delegator: Set(hosted.did().to_string()),
+            delegatee: Set(old_did.clone()),
+            expiry: Set(None),
+            issued_at: Set(None),
+            not_before: Set(None),
+            facts: Set(None),
+            serialization: Set(b"host-under-old-key".to_vec()),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        abilities::ActiveModel {
+            resource: Set(Resource::TinyCloud(hosted.clone().to_resource(
+                "space".parse().unwrap(),
+                None,
+                None,
+                None,
+            ))),
+            ability: Set(
+                crate::types::Ability::try_from("tinycloud.space/host".to_string()).unwrap(),
+            ),
+            delegation: Set(host_delegation),
+            caveats: Set(Default::default()),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        assert_eq!(db.stage_key(&hosted).await.unwrap(), old_did);
+
+        let fresh = test_space_id("fresh");
+        let new_did = db.stage_key(&fresh).await.unwrap();
+        assert_eq!(
+            new_did,
+            get_did_key(db.secrets.get_pubkey(&fresh).await.unwrap())
+        );
+        assert_ne!(new_did, get_did_key(old.get_pubkey(&fresh).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn prune_keeps_expired_parents_of_live_delegations() {
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+        let db = get_db().await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let expired = Some(now - time::Duration::hours(1));
+        let live = Some(now + time::Duration::hours(1));
+        actor::ActiveModel {
+            id: Set("did:key:owner".to_string()),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        // (name, expiry, parent)
+        let rows = [
+            ("lone-expired", expired, None),
+            ("live", live, None),
+            ("parent-of-live", expired, None),
+            ("live-child", live, Some("parent-of-live")),
+            ("grandparent", expired, None),
+            ("expired-child", expired, Some("grandparent")),
+            ("live-grandchild", None, Some("expired-child")),
+            ("expired-chain-root", expired, None),
+            ("expired-chain-leaf", expired, Some("expired-chain-root")),
+            ("revoked", expired, None),
+        ];
+        for (name, expiry, parent) in rows {
+            delegation::ActiveModel {
+                id: Set(crate::hash::hash(name.as_bytes())),
+                delegator: Set("did:key:owner".to_string()),
+                delegatee: Set("did:key:owner".to_string()),
+                expiry: Set(expiry),
+                issued_at: Set(None),
+                not_before: Set(None),
+                facts: Set(None),
+                serialization: Set(name.as_bytes().to_vec()),
+            }
+            .insert(&db.conn)
+            .await
+            .unwrap();
+            abilities::ActiveModel {
+                resource: Set(Resource::TinyCloud(test_space_id("pruned").to_resource(
+                    "kv".parse().unwrap(),
+                    Some(name.parse().unwrap()),
+                    None,
+                    None,
+                ))),
+                ability: Set("tinycloud.kv/get".to_string().try_into().unwrap()),
+                delegation: Set(crate::hash::hash(name.as_bytes())),
+                caveats: Set(Default::default()),
+            }
+            .insert(&db.conn)
+            .await
+            .unwrap();
+            if let Some(parent) = parent {
+                parent_delegations::ActiveModel {
+                    parent: Set(crate::hash::hash(parent.as_bytes())),
+                    child: Set(crate::hash::hash(name.as_bytes())),
+                }
+                .insert(&db.conn)
+                .await
+                .unwrap();
+            }
+        }
+        revocation::ActiveModel {
+            id: Set(crate::hash::hash(b"revocation")),
+            revoker: Set("did:key:owner".to_string()),
+            revoked: Set(crate::hash::hash(b"revoked")),
+            serialization: Set(b"revocation".to_vec()),
+            revoked_at: Set(None),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        assert_eq!(db.prune_expired_delegations(now).await.unwrap(), 3);
+
+        for (name, _, _) in rows {
+            let id = crate::hash::hash(name.as_bytes());
+            // pruned delegations keep their rows but lose their abilities
+            assert!(delegation::Entity::find_by_id(id)
+                .one(&db.conn)
+                .await
+                .unwrap()
+                .is_some());
+            let granting = abilities::Entity::find()
+                .filter(abilities::Column::Delegation.eq(id))
+                .count(&db.conn)
+                .await
+                .unwrap()
+                > 0;
+            let pruned = matches!(
+                name,
+                "lone-expired" | "expired-chain-root" | "expired-chain-leaf"
+            );
+            assert_eq!(granting, !pruned, "unexpected prune result for {name}");
+        }
+        assert_eq!(
+            parent_delegations::Entity::find()
+                .all(&db.conn)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(db.prune_expired_delegations(now).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn pruned_delegations_leave_the_event_log_readable() {
+        use crate::storage::memory::MemoryStaging;
+        use sea_orm::ActiveValue::Set;
+
+        let mut owner = JWK::generate_ed25519().unwrap();
+        owner.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let space = SpaceId::new(
+            DID_METHODS.generate(&owner, "key").unwrap(),
+            "pruned".parse().unwrap(),
+        );
+        let mut reader = JWK::generate_ed25519().unwrap();
+        reader.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let reader_did = DID_METHODS.generate(&reader, "key").unwrap().to_string();
+        let host = test_space_id("host").did().to_string();
+
+        let db = get_db().await.unwrap();
+        db.delegate(owner_host_delegation(&owner, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&db, &owner, &space, "a", b"1").await;
+        let grant = owner_delegation(
+            &owner,
+            &space,
+            &reader_did,
+            &[("kv", Some("a"), "tinycloud.kv/get")],
+        );
+        let grant_id = grant.content_hash();
+        db.delegate(grant).await.map_err(|e| e.to_string()).unwrap();
+        // the invocation names the grant as its parent
+        db.invoke::<MemoryStaging>(
+            key_invocation(
+                &reader,
+                &space,
+                "kv",
+                &[("a", "tinycloud.kv/get")],
+                vec![],
+                &[grant_id],
+            ),
+            HashMap::new(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+        let logged = db.event_log(&space, -1, 100).await.unwrap().len();
+
+        let now = OffsetDateTime::now_utc();
+        delegation::ActiveModel {
+            id: Set(grant_id),
+            expiry: Set(Some(now - time::Duration::hours(1))),
+            ..Default::default()
+        }
+        .update(&db.conn)
+        .await
+        .unwrap();
+        assert_eq!(db.prune_expired_delegations(now).await.unwrap(), 1);
+        assert_eq!(db.prune_expired_delegations(now).await.unwrap(), 0);
+
+        // the log still holds the pruned delegation
+        assert_eq!(db.event_log(&space, -1, 100).await.unwrap().len(), logged);
+        // the space's sequence carries on past the pruned delegation
+        put_value(&db, &owner, &space, "b", b"2").await;
+        let log = db.event_log(&space, -1, 100).await.unwrap();
+        assert_eq!(log.len(), logged + 1);
+        assert!(log.windows(2).all(|pair| pair[0].seq <= pair[1].seq));
+
+        // and a replica reading it arrives at the same epochs
+        let replica = get_db().await.unwrap();
+        copy_values(&db, &replica, &log).await;
+        assert_eq!(replica.ingest_events(log).await.unwrap(), logged + 1);
+        assert_eq!(
+            epoch_heads(&replica.conn, [SpaceIdWrap(space.clone())])
+                .await
+                .unwrap(),
+            epoch_heads(&db.conn, [SpaceIdWrap(space.clone())])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn delegations_within_the_clock_skew_are_not_pruned() {
+        let db = get_db()
+            .await
+            .unwrap()
+            .with_delegation_limits(DelegationLimits {
+                clock_skew: time::Duration::minutes(5),
+                ..Default::default()
+            });
+        let mut owner = JWK::generate_ed25519().unwrap();
+        owner.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let space = SpaceId::new(
+            DID_METHODS.generate(&owner, "key").unwrap(),
+            "skewed".parse().unwrap(),
+        );
+        let host = test_space_id("host").did().to_string();
+        let host_delegation = owner_host_delegation(&owner, &space, &host);
+        let id = host_delegation.content_hash();
+        db.delegate(host_delegation)
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        delegation::ActiveModel {
+            id: sea_orm::ActiveValue::Set(id),
+            expiry: sea_orm::ActiveValue::Set(Some(now - time::Duration::minutes(1))),
+            ..Default::default()
+        }
+        .update(&db.conn)
+        .await
+        .unwrap();
+        assert_eq!(db.prune_expired_delegations(now).await.unwrap(), 0);
+        assert_eq!(
+            db.prune_expired_delegations(now + time::Duration::minutes(5))
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    /// Allows one space, counting how often it is consulted.
+    #[derive(Debug)]
+    struct OnlySpace(SpaceId, std::sync::atomic::AtomicUsize);
+
+    impl OnlySpace {
+        fn new(space: SpaceId) -> Self {
+            Self(space, Default::default())
+        }
+
+        fn lookups(&self) -> usize {
+            self.1.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SpaceAllowList for OnlySpace {
+        async fn is_allowed(&self, space: &SpaceId) -> Result<bool, AllowListError> {
+            self.1.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(space == &self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn allow_list_gates_creation_of_new_spaces() {
+        let mut keys = std::iter::repeat_with(|| {
+            let mut jwk = JWK::generate_ed25519().unwrap();
+            jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+            let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+            (jwk, SpaceId::new(did, "default".parse().unwrap()))
+        });
+        let (allowed_key, allowed) = keys.next().unwrap();
+        let (denied_key, denied) = keys.next().unwrap();
+        let host = test_space_id("host").did().to_string();
+
+        let list = Arc::new(OnlySpace::new(allowed.clone()));
+        let db = get_db().await.unwrap().with_allow_list(Some(list.clone()));
+        // spaces that already exist are hosted without consulting the list
+        let (existing_key, existing) = owned_test_space(&db, "existing").await;
+
+        // delegations that could not host the space are refused as they
+        // would be without a list, which is never consulted for them
+        let jwt = |d: Delegation| String::from_utf8(d.serialized_bytes().to_vec()).unwrap();
+        let genuine = jwt(owner_host_delegation(&denied_key, &denied, &host));
+        let other = jwt(owner_host_delegation(&allowed_key, &denied, &host));
+        let forged = Delegation::from_header_ser::<
+            tinycloud_auth::authorization::TinyCloudDelegation,
+        >(&format!(
+            "{}.{}",
+            genuine.rsplit_once('.').unwrap().0,
+            other.rsplit_once('.').unwrap().1
+        ))
+        .unwrap();
+        for unauthorized in [forged, owner_host_delegation(&allowed_key, &denied, &host)] {
+            match db.delegate(unauthorized).await {
+                Err(TxError::InvalidDelegation(_)) => {}
+                Err(error) => panic!("expected InvalidDelegation, got {error}"),
+                Ok(_) => panic!("unauthorized delegation was hosted"),
+            }
+        }
+        assert_eq!(list.lookups(), 0);
+
+        let result = db
+            .delegate(owner_host_delegation(&allowed_key, &allowed, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(result.commits.contains_key(&allowed));
+
+        match db
+            .delegate(owner_host_delegation(&denied_key, &denied, &host))
+            .await
+        {
+            Err(TxError::SpaceNotAllowed(space)) => assert_eq!(space, denied),
+            Err(error) => panic!("expected SpaceNotAllowed, got {error}"),
+            Ok(_) => panic!("denied space was hosted"),
+        }
+        assert!(space::Entity::find_by_id(SpaceIdWrap(denied))
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .is_none());
+
+        db.delegate(owner_host_delegation(&existing_key, &existing, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn explicitly_created_spaces_can_still_be_hosted() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "default".parse().unwrap());
+        let host = test_space_id("host").did().to_string();
+        let db = get_db().await.unwrap();
+
+        assert!(db.create_space(&space).await.unwrap());
+        assert!(!db.create_space(&space).await.unwrap());
+        assert_eq!(db.is_space_frozen(&space).await.unwrap(), Some(false));
+
+        let result = db
+            .delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(result.commits.contains_key(&space));
+        assert!(result.skipped_spaces.is_empty());
+        assert_eq!(
+            space::Entity::find()
+                .filter(space::Column::Id.eq(SpaceIdWrap(space.clone())))
+                .count(&db.conn)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(!db.create_space(&space).await.unwrap());
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordCommits(std::sync::Mutex<Vec<(CommitKind, SpaceId, Commit)>>);
+
+    impl CommitObserver for RecordCommits {
+        fn committed(&self, kind: CommitKind, space: &SpaceId, commit: &Commit) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((kind, space.clone(), commit.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn commit_observer_sees_writes_and_delegations_but_not_reads() {
+        use crate::storage::memory::MemoryStaging;
+
+        let observer = Arc::new(RecordCommits::default());
+        let db = get_db()
+            .await
+            .unwrap()
+            .with_commit_observer(Some(observer.clone()));
+        let (jwk, space) = owned_test_space(&db, "observed").await;
+        let host = test_space_id("host").did().to_string();
+
+        db.delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&db, &jwk, &space, "key", b"value").await;
+        db.invoke::<MemoryStaging>(
+            owner_invocation(&jwk, &space, &[("key", "tinycloud.kv/get")], vec![]),
+            HashMap::new(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+
+        let seen = observer.0.lock().unwrap();
+        assert_eq!(
+            seen.iter()
+                .map(|(kind, space, _)| (*kind, space.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (CommitKind::Delegation, space.clone()),
+                (CommitKind::Write, space.clone())
+            ]
+        );
+        let write = &seen[1].2;
+        assert_eq!(write.seq, seen[0].2.seq + 1);
+        assert_eq!(write.committed_events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn effective_permissions_collapse_overlapping_delegations() {
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "effective").await;
+        let delegate = test_space_id("delegate").did().to_string();
+        let other = test_space_id("other").did().to_string();
+
+        for (audience, caps) in [
+            (
+                &delegate,
+                vec![
+                    ("kv", Some("docs/"), "tinycloud.kv/get"),
+                    ("kv", Some("docs/a.txt"), "tinycloud.kv/get"),
+                    ("kv", Some("photos/"), "tinycloud.kv/put"),
+                ],
+            ),
+            (
+                &delegate,
+                vec![
+                    ("kv", Some("docs/"), "tinycloud.kv/get"),
+                    ("kv", Some("docs/b.txt"), "tinycloud.kv/exists"),
+                    ("kv", Some("docs/b.txt"), "tinycloud.kv/put"),
+                ],
+            ),
+            (&other, vec![("kv", Some("private/"), "tinycloud.kv/get")]),
+        ] {
+            db.delegate(owner_delegation(&jwk, &space, audience, &caps))
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+        }
+
+        let effective = get_effective_permissions::<_, MemoryStore, StaticSecret>(
+            &db.conn, &space, &delegate, None,
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap()
+        .into_iter()
+        .map(|cap| (cap.resource.to_string(), cap.ability.to_string()))
+        .collect::<Vec<_>>();
+        let kv = |path: &str| {
+            space
+                .clone()
+                .to_resource(
+                    "kv".parse().unwrap(),
+                    Some(path.parse().unwrap()),
+                    None,
+                    None,
+                )
+                .to_string()
+        };
+        assert_eq!(
+            effective,
+            vec![
+                (kv("docs/"), "tinycloud.kv/get".to_string()),
+                (kv("docs/b.txt"), "tinycloud.kv/put".to_string()),
+                (kv("photos/"), "tinycloud.kv/put".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn epoch_head_is_the_latest_commit() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "head").await;
+        for (path, value) in [("a", b"1"), ("b", b"2"), ("a", b"3")] {
+            put_value(&db, &jwk, &space, path, value).await;
+        }
+
+        let (commit, outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_service_invocation(
+                    &jwk,
+                    &space,
+                    "capabilities",
+                    &[("all", "tinycloud.capabilities/head")],
+                    vec![],
+                ),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        let latest = &commit.commits[&space];
+        match outcomes.as_slice() {
+            [InvocationOutcome::EpochHeads(heads, seq)] => {
+                assert_eq!(heads, &vec![latest.rev.to_block_cid()]);
+                assert_eq!(*seq, latest.seq);
+            }
+            _ => panic!("expected the space's epoch heads"),
+        }
+    }
+
+    #[tokio::test]
+    async fn event_logs_replicate_spaces_between_nodes() {
+        use crate::storage::memory::MemoryStaging;
+
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "replicated".parse().unwrap());
+        let host = test_space_id("host").did().to_string();
+        let delegate = test_space_id("delegate").did().to_string();
+
+        let source = get_db().await.unwrap();
+        source
+            .delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&source, &jwk, &space, "a", b"1").await;
+        put_value(&source, &jwk, &space, "b", b"2").await;
+        source
+            .delegate(owner_delegation(
+                &jwk,
+                &space,
+                &delegate,
+                &[("kv", Some("b"), "tinycloud.kv/get")],
+            ))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        source
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/del")], vec![]),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        // pull the log in two pages, the second overlapping the first
+        let replica = get_db().await.unwrap();
+        let first = source.event_log(&space, -1, 2).await.unwrap();
+        assert_eq!(first.iter().map(|e| e.seq).max(), Some(1));
+        copy_values(&source, &replica, &first).await;
+        assert_eq!(replica.ingest_events(first).await.unwrap(), 2);
+        let rest = source.event_log(&space, 0, 100).await.unwrap();
+        assert_eq!(rest.len(), 4);
+        copy_values(&source, &replica, &rest).await;
+        assert_eq!(replica.ingest_events(rest.clone()).await.unwrap(), 3);
+        assert_eq!(replica.ingest_events(rest).await.unwrap(), 0);
+
+        // identical events in identical order produce identical epochs
+        assert_eq!(
+            epoch_heads(&replica.conn, [SpaceIdWrap(space.clone())])
+                .await
+                .unwrap(),
+            epoch_heads(&source.conn, [SpaceIdWrap(space.clone())])
+                .await
+                .unwrap()
+        );
+        let root: Path = "".parse().unwrap();
+        assert_eq!(
+            list(&replica.conn, &space, &root).await.unwrap(),
+            vec!["b".parse::<Path>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn event_cids_match_the_client_side_computation() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "cids".parse().unwrap());
+        let host = test_space_id("host").did().to_string();
+
+        let delegation = owner_host_delegation(&jwk, &space, &host);
+        let invocation = owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/get")], vec![]);
+        for (hash, serialization) in [
+            (delegation.content_hash(), delegation.serialized_bytes()),
+            (invocation.content_hash(), invocation.serialized_bytes()),
+        ] {
+            assert_eq!(
+                hash.to_event_cid(),
+                tinycloud_auth::codec::to_event_cid(serialization)
+            );
+        }
+    }
+
+    /// Copy the values written in `log` from `source`'s block store to
+    /// `replica`'s, as a node must before ingesting the log.
+    async fn copy_values(
+        source: &SpaceDatabase<sea_orm::DbConn, MemoryStore, StaticSecret>,
+        replica: &SpaceDatabase<sea_orm::DbConn, MemoryStore, StaticSecret>,
+        log: &[LogEntry],
+    ) {
+        use crate::{event_log::LoggedEvent, storage::memory::MemoryStaging};
+        use futures::io::AsyncWriteExt;
+
+        for entry in log {
+            let LoggedEvent::Invocation { writes, .. } = &entry.event else {
+                continue;
+            };
+            for write in writes {
+                let hash = Hash::from(
+                    write
+                        .value
+                        .parse::<tinycloud_auth::ipld_core::cid::Cid>()
+                        .unwrap(),
+                );
+                let value = source
+                    .storage
+                    .read_to_vec(&write.space, &hash)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let mut stage = MemoryStaging.stage(&write.space).await.unwrap();
+                stage.write_all(&value).await.unwrap();
+                ImmutableWriteStore::<MemoryStaging>::persist(
+                    &replica.storage,
+                    &write.space,
+                    stage,
+                )
+                .await
+                .unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_invocations_are_refused_on_ingest() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "expiring".parse().unwrap());
+        let host = test_space_id("host").did().to_string();
+
+        let source = get_db().await.unwrap();
+        source
+            .delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&source, &jwk, &space, "a", b"1").await;
+        let expiration = OffsetDateTime::now_utc().unix_timestamp_nanos() as f64 / 1e9 + 1.0;
+        source
+            .invoke::<crate::storage::memory::MemoryStaging>(
+                key_invocation_expiring(
+                    &jwk,
+                    &space,
+                    "kv",
+                    &[("a", "tinycloud.kv/del")],
+                    vec![],
+                    &[],
+                    expiration,
+                ),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+        // the delete is checked against this node's clock, whenever the
+        // source says it was committed
+        let log = source.event_log(&space, -1, 100).await.unwrap();
+        let replica = get_db().await.unwrap();
+        copy_values(&source, &replica, &log).await;
+        assert!(matches!(
+            replica.ingest_events(log).await,
+            Err(TxError::InvalidInvocation(
+                invocation::InvocationError::InvalidTime
+            ))
+        ));
+        let root: Path = "".parse().unwrap();
+        assert_eq!(
+            list(&replica.conn, &space, &root).await.unwrap(),
+            vec!["a".parse::<Path>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn ingested_operations_must_be_authorized_and_stored() {
+        use crate::event_log::{LoggedDelete, LoggedEvent, LoggedWrite};
+
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "forged".parse().unwrap());
+        let other = test_space_id("other");
+        let host = test_space_id("host").did().to_string();
+
+        let source = get_db().await.unwrap();
+        source
+            .delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&source, &jwk, &space, "a", b"1").await;
+        let log = source.event_log(&space, -1, 100).await.unwrap();
+        let [host_delegation, put] = <[LogEntry; 2]>::try_from(log).unwrap();
+        let LoggedEvent::Invocation { event, writes, .. } = put.event.clone() else {
+            panic!("expected the put");
+        };
+        let with_ops = |writes: Vec<LoggedWrite>, deletes: Vec<LoggedDelete>| LogEntry {
+            seq: put.seq,
+            event: LoggedEvent::Invocation {
+                event: event.clone(),
+                writes,
+                deletes,
+            },
+        };
+        let unauthorized = |result: Result<usize, TxError<MemoryStore, StaticSecret>>| {
+            matches!(
+                result,
+                Err(TxError::InvalidInvocation(
+                    invocation::InvocationError::UnauthorizedAction(..)
+                ))
+            )
+        };
+
+        let replica = get_db().await.unwrap();
+        copy_values(&source, &replica, std::slice::from_ref(&put)).await;
+        assert_eq!(
+            replica.ingest_events(vec![host_delegation]).await.unwrap(),
+            1
+        );
+
+        // a write or delete the put's capabilities do not name
+        let mut forged = writes.clone();
+        forged.push(LoggedWrite {
+            key: "b".parse().unwrap(),
+            ..writes[0].clone()
+        });
+        assert!(unauthorized(
+            replica.ingest_events(vec![with_ops(forged, vec![])]).await
+        ));
+        let delete = LoggedDelete {
+            space: space.clone(),
+            key: "a".parse().unwrap(),
+        };
+        assert!(unauthorized(
+            replica
+                .ingest_events(vec![with_ops(writes.clone(), vec![delete])])
+                .await
+        ));
+        // one in a space the put does not cover
+        let foreign = LoggedWrite {
+            space: other.clone(),
+            ..writes[0].clone()
+        };
+        assert!(unauthorized(
+            replica
+                .ingest_events(vec![with_ops(vec![foreign], vec![])])
+                .await
+        ));
+        // a write whose value this node does not have
+        let missing = LoggedWrite {
+            value: crate::hash::hash(b"2").to_block_cid().to_string(),
+            ..writes[0].clone()
+        };
+        assert!(matches!(
+            replica
+                .ingest_events(vec![with_ops(vec![missing], vec![])])
+                .await,
+            Err(TxError::EventLog(LogError::MissingValue(_)))
+        ));
+
+        let root: Path = "".parse().unwrap();
+        assert!(list(&replica.conn, &space, &root).await.unwrap().is_empty());
+        assert_eq!(replica.ingest_events(vec![put]).await.unwrap(), 1);
+        assert_eq!(
+            list(&replica.conn, &space, &root).await.unwrap(),
+            vec!["a".parse::<Path>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_epochs_merge_identically_on_every_node() {
+        use crate::migrations::m20261016_000000_epoch_seq_unique::Migration;
+        use sea_orm_migration::{MigrationTrait, SchemaManager};
+
+        async fn heads(db: &sea_orm::DbConn, space: &SpaceId) -> Vec<Hash> {
+            epoch_heads(db, [SpaceIdWrap(space.clone())])
+                .await
+                .unwrap()
+                .remove(&SpaceIdWrap(space.clone()))
+                .unwrap_or_default()
+        }
+
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did, "merged".parse().unwrap());
+        let host = test_space_id("host").did().to_string();
+
+        let source = get_db().await.unwrap();
+        source
+            .delegate(owner_host_delegation(&jwk, &space, &host))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        put_value(&source, &jwk, &space, "a", b"1").await;
+        let history = source.event_log(&space, -1, 100).await.unwrap();
+        put_value(&source, &jwk, &space, "d", b"4").await;
+        let next = source.event_log(&space, 1, 100).await.unwrap();
+
+        // two nodes with the same history each commit a different write,
+        // branching from the same epoch with the same sequence number
+        let mut branches = vec![];
+        for (path, value) in [("b", b"2"), ("c", b"3")] {
+            let node = get_db().await.unwrap();
+            copy_values(&source, &node, &history).await;
+            node.ingest_events(history.clone()).await.unwrap();
+            put_value(&node, &jwk, &space, path, value).await;
+            let [head] = <[Hash; 1]>::try_from(heads(&node.conn, &space).await).unwrap();
+            branches.push((
+                epoch::Entity::find()
+                    .filter(epoch::Column::Id.eq(head))
+                    .all(&node.conn)
+                    .await
+                    .unwrap(),
+                epoch_order::Entity::find()
+                    .filter(epoch_order::Column::Child.eq(head))
+                    .all(&node.conn)
+                    .await
+                    .unwrap(),
+                event_order::Entity::find()
+                    .filter(event_order::Column::Epoch.eq(head))
+                    .all(&node.conn)
+                    .await
+                    .unwrap(),
+            ));
+        }
+        let mut forks = branches
+            .iter()
+            .map(|(epochs, _, _)| epochs[0].id)
+            .collect::<Vec<_>>();
+        forks.sort();
+        assert_ne!(forks[0], forks[1]);
+        assert_eq!(branches[0].0[0].seq, branches[1].0[0].seq);
+
+        // a node holding both branches, recorded in either order, merges
+        // them with the next event into the same epoch
+        let mut merges = vec![];
+        for order in [[0, 1], [1, 0]] {
+            let node = get_db().await.unwrap();
+            copy_values(&source, &node, &history).await;
+            copy_values(&source, &node, &next).await;
+            node.ingest_events(history.clone()).await.unwrap();
+            // as in databases from before sequence numbers were unique
+            Migration
+                .down(&SchemaManager::new(&node.conn))
+                .await
+                .unwrap();
+            for i in order {
+                let (epochs, epoch_orders, event_orders) = branches[i].clone();
+                epoch::Entity::insert_many(epochs.into_iter().map(epoch::ActiveModel::from))
+                    .exec(&node.conn)
+                    .await
+                    .unwrap();
+                epoch_order::Entity::insert_many(
+                    epoch_orders.into_iter().map(epoch_order::ActiveModel::from),
+                )
+                .exec(&node.conn)
+                .await
+                .unwrap();
+                event_order::Entity::insert_many(
+                    event_orders.into_iter().map(event_order::ActiveModel::from),
+                )
+                .exec(&node.conn)
+                .await
+                .unwrap();
+            }
+            assert_eq!(heads(&node.conn, &space).await, forks);
+
+            node.ingest_events(next.clone()).await.unwrap();
+            let [merge] = <[Hash; 1]>::try_from(heads(&node.conn, &space).await).unwrap();
+            let mut parents = epoch_order::Entity::find()
+                .filter(epoch_order::Column::Child.eq(merge))
+                .all(&node.conn)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|order| order.parent)
+                .collect::<Vec<_>>();
+            parents.sort();
+            assert_eq!(parents, forks);
+            merges.push(merge);
+        }
+        assert_eq!(merges[0], merges[1]);
+    }
+
+    #[tokio::test]
+    async fn usage_counts_stored_bytes_keys_and_delegations() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "usage").await;
+        let usage = || async {
+            let (_, outcomes) = db
+                .invoke_with_options::<MemoryStaging>(
+                    owner_service_invocation(
+                        &jwk,
+                        &space,
+                        "capabilities",
+                        &[("all", "tinycloud.capabilities/usage")],
+                        vec![],
+                    ),
+                    HashMap::new(),
+                    KvInvokeOptions {
+                        quota_limits: HashMap::from([(space.clone(), 1024)]),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+            match outcomes.as_slice() {
+                [InvocationOutcome::Usage {
+                    bytes,
+                    limit,
+                    objects,
+                    delegations,
+                }] => (*bytes, *limit, *objects, *delegations),
+                _ => panic!("expected the space's usage"),
+            }
+        };
+
+        assert_eq!(usage().await, (0, Some(1024), 0, 0));
+        put_value(&db, &jwk, &space, "a", b"12345").await;
+        put_value(&db, &jwk, &space, "b", b"678").await;
+        db.delegate(owner_delegation(
+            &jwk,
+            &space,
+            &test_space_id("reader").did().to_string(),
+            &[("kv", Some("a"), "tinycloud.kv/get")],
+        ))
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+        assert_eq!(usage().await, (8, Some(1024), 2, 1));
+
+        // deletes are logical: the key goes, its block stays
+        db.invoke::<MemoryStaging>(
+            owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/del")], vec![]),
+            HashMap::new(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+        assert_eq!(usage().await, (8, Some(1024), 1, 1));
+    }
+
+    #[tokio::test]
+    async fn wildcard_parents_authorize_specific_children() {
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "wildcard").await;
+        let mut holder = JWK::generate_ed25519().unwrap();
+        holder.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let holder_did = DID_METHODS.generate(&holder, "key").unwrap().to_string();
+        let recipient = test_space_id("recipient").did().to_string();
+
+        let any_path = owner_delegation(
+            &owner,
+            &space,
+            &holder_did,
+            &[("kv", Some(""), "tinycloud.kv/get")],
+        );
+        let docs = owner_delegation(
+            &owner,
+            &space,
+            &holder_did,
+            &[("kv", Some("docs/*"), "tinycloud.kv/put")],
+        );
+        let (any_path_hash, docs_hash) = (any_path.content_hash(), docs.content_hash());
+        for parent in [any_path, docs] {
+            db.delegate(parent)
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+        }
+
+        let narrowed = |caps: &[(&str, Option<&str>, &str)], parent: Hash| {
+            key_delegation(&holder, &space, &recipient, caps, &[parent])
+        };
+        db.delegate(narrowed(
+            &[("kv", Some("photos/cat.jpg"), "tinycloud.kv/get")],
+            any_path_hash,
+        ))
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+        db.delegate(narrowed(
+            &[("kv", Some("docs/reports/q1.txt"), "tinycloud.kv/put")],
+            docs_hash,
+        ))
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+
+        // the wildcard does not reach outside its prefix
+        assert!(db
+            .delegate(narrowed(
+                &[("kv", Some("photos/cat.jpg"), "tinycloud.kv/put")],
+                docs_hash,
+            ))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn kv_lists_filter_by_content_type() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "content-types").await;
+        put_typed_value(&db, &jwk, &space, "photos/cat.png", b"png", "image/png").await;
+        put_typed_value(&db, &jwk, &space, "photos/dog.JPG", b"jpg", "Image/JPEG").await;
+        put_value(&db, &jwk, &space, "photos/notes.txt", b"text").await;
+
+        let list = |facts: Vec<serde_json::Value>| {
+            let (db, jwk, space) = (&db, &jwk, &space);
+            async move {
+                match db
+                    .invoke::<MemoryStaging>(
+                        owner_invocation(jwk, space, &[("photos", "tinycloud.kv/list")], facts),
+                        HashMap::new(),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())
+                    .unwrap()
+                    .1
+                    .pop()
+                {
+                    Some(InvocationOutcome::KvList(paths, _)) => paths
+                        .iter()
+                        .map(|path| path.to_string())
+                        .collect::<Vec<_>>(),
+                    _ => panic!("expected a kv list outcome"),
+                }
+            }
+        };
+
+        assert_eq!(
+            list(vec![]).await,
+            ["photos/cat.png", "photos/dog.JPG", "photos/notes.txt"]
+        );
+        assert_eq!(
+            list(vec![serde_json::json!({"contentTypePrefix": "image/"})]).await,
+            ["photos/cat.png", "photos/dog.JPG"]
+        );
+    }
+
+    #[tokio::test]
+    async fn kv_caveats_limit_delegated_puts() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "caveats").await;
+        let mut holder = JWK::generate_ed25519().unwrap();
+        holder.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let holder_did = DID_METHODS.generate(&holder, "key").unwrap().to_string();
+
+        let capped = caveated_delegation(
+            &owner,
+            &space,
+            &holder_did,
+            &[("kv", Some("notes/*"), "tinycloud.kv/put")],
+            &[],
+            Some(serde_json::json!({"maxSize": 4})),
+        );
+        let capped_hash = capped.content_hash();
+        db.delegate(capped)
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        let (db, holder, space) = (&db, &holder, &space);
+        let put = move |path: &'static str, value: &'static [u8]| async move {
+            let mut stage = MemoryStaging.stage(space).await.unwrap();
+            stage.write_all(value).await.unwrap();
+            let inputs = HashMap::from([(
+                (space.clone(), path.parse().unwrap()),
+                (Metadata(Default::default()), stage),
+            )]);
+            db.invoke::<MemoryStaging>(
+                key_invocation(
+                    holder,
+                    space,
+                    "kv",
+                    &[(path, "tinycloud.kv/put")],
+                    vec![],
+                    &[capped_hash],
+                ),
+                inputs,
+            )
+            .await
+        };
+
+        match put("notes/long.txt", b"hello").await {
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(
+                invocation::InvocationError::KvCaveatViolated(
+                    crate::types::KvCaveatError::TooLarge { size: 5, max: 4 },
+                ),
+            ))) => {}
+            Err(e) => panic!("expected the put to exceed the delegated size, got {e}"),
+            Ok(_) => panic!("expected the put to exceed the delegated size"),
+        }
+        assert!(
+            get_kv_entity(&db.conn, space, &"notes/long.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        put("notes/ok.txt", b"hey!")
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn invocations_reject_proof_chains_outside_their_validity_window() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+        let key = || {
+            let mut jwk = JWK::generate_ed25519().unwrap();
+            jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+            let did = DID_METHODS.generate(&jwk, "key").unwrap().to_string();
+            (jwk, did)
+        };
+        let caps = [("kv", Some("docs/"), "tinycloud.kv/put")];
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "validity").await;
+        let ((first, first_did), (second, second_did)) = (key(), key());
+
+        let root = owner_delegation(&owner, &space, &first_did, &caps);
+        let root_hash = root.content_hash();
+        db.delegate(root).await.map_err(|e| e.to_string()).unwrap();
+        let leaf = key_delegation(&first, &space, &second_did, &caps, &[root_hash]);
+        let leaf_hash = leaf.content_hash();
+        db.delegate(leaf).await.map_err(|e| e.to_string()).unwrap();
+
+        let (db, second, space) = (&db, &second, &space);
+        let put = move |path: &'static str| async move {
+            let mut stage = MemoryStaging.stage(space).await.unwrap();
+            stage.write_all(b"value").await.unwrap();
+            let inputs = HashMap::from([(
+                (space.clone(), path.parse().unwrap()),
+                (Metadata(Default::default()), stage),
+            )]);
+            db.invoke::<MemoryStaging>(
+                key_invocation(
+                    second,
+                    space,
+                    "kv",
+                    &[(path, "tinycloud.kv/put")],
+                    vec![],
+                    &[leaf_hash],
+                ),
+                inputs,
+            )
+            .await
+        };
+        // stands in for time passing since the delegations were accepted
+        let set_window = |id: Hash,
+                          expiry: Option<OffsetDateTime>,
+                          not_before: Option<OffsetDateTime>| async move {
+            delegation::ActiveModel {
+                id: Set(id),
+                expiry: Set(expiry),
+                not_before: Set(not_before),
+                ..Default::default()
+            }
+            .update(&db.conn)
+            .await
+            .unwrap();
+        };
+        let now = OffsetDateTime::now_utc();
+
+        put("docs/before.txt")
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        // an expired parent of the cited delegation
+        set_window(root_hash, Some(now - time::Duration::minutes(1)), None).await;
+        assert!(matches!(
+            put("docs/expired-parent.txt").await,
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(
+                invocation::InvocationError::UnauthorizedAction(..)
+            )))
+        ));
+
+        // the cited delegation itself not yet valid
+        set_window(root_hash, None, None).await;
+        set_window(leaf_hash, None, Some(now + time::Duration::hours(1))).await;
+        assert!(matches!(
+            put("docs/early-leaf.txt").await,
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(
+                invocation::InvocationError::UnauthorizedAction(..)
+            )))
+        ));
+
+        set_window(leaf_hash, None, Some(now - time::Duration::hours(1))).await;
+        put("docs/after.txt")
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        for path in ["docs/expired-parent.txt", "docs/early-leaf.txt"] {
+            assert!(get_kv_entity(&db.conn, space, &path.parse().unwrap())
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn liveness_flows_down_chains_listed_in_any_order() {
+        let now = OffsetDateTime::now_utc();
+        let del = |name: &str, expiry: Option<OffsetDateTime>| delegation::Model {
+            id: crate::hash::hash(name.as_bytes()),
+            delegator: "did:key:delegator".to_string(),
+            delegatee: "did:key:delegatee".to_string(),
+            expiry,
+            issued_at: None,
+            not_before: None,
+            facts: None,
+            serialization: name.as_bytes().to_vec(),
+        };
+        let edge = |parent: &delegation::Model, child: &delegation::Model| {
+            vec![parent_delegations::Model {
+                parent: parent.id,
+                child: child.id,
+            }]
+        };
+        // root -> a -> b -> c, and root -> expired -> d, and an orphan
+        // whose parent is missing, listed leaves first
+        let root = del("root", None);
+        let a = del("a", None);
+        let b = del("b", None);
+        let c = del("c", None);
+        let expired = del("expired", Some(now - time::Duration::hours(1)));
+        let d = del("d", None);
+        let orphan = del("orphan", None);
+        let parents = vec![
+            edge(&b, &c),
+            edge(&expired, &d),
+            edge(&a, &b),
+            vec![parent_delegations::Model {
+                parent: crate::hash::hash(b"missing"),
+                child: orphan.id,
+            }],
+            edge(&root, &a),
+            edge(&root, &expired),
+            vec![],
+        ];
+        let dels = vec![
+            c.clone(),
+            d.clone(),
+            b.clone(),
+            orphan.clone(),
+            a.clone(),
+            expired.clone(),
+            root.clone(),
+        ];
+
+        assert_eq!(
+            live_delegations(&dels, &parents, now),
+            HashSet::from([root.id, a.id, b.id, c.id])
+        );
+    }
+
+    #[tokio::test]
+    async fn revoking_a_delegation_invalidates_its_descendants() {
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+        let key = || {
+            let mut jwk = JWK::generate_ed25519().unwrap();
+            jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+            let did = DID_METHODS.generate(&jwk, "key").unwrap().to_string();
+            (jwk, did)
+        };
+        let caps = [("kv", Some("docs/"), "tinycloud.kv/get")];
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "revoked-chain").await;
+        let ((a, a_did), (b, b_did), (_, c_did)) = (key(), key(), key());
+
+        let to_a = owner_delegation(&owner, &space, &a_did, &caps);
+        let to_a_hash = to_a.content_hash();
+        db.delegate(to_a).await.map_err(|e| e.to_string()).unwrap();
+        let to_b = key_delegation(&a, &space, &b_did, &caps, &[to_a_hash]);
+        let to_b_hash = to_b.content_hash();
+        db.delegate(to_b).await.map_err(|e| e.to_string()).unwrap();
+        let to_c = key_delegation(&b, &space, &c_did, &caps, &[to_b_hash]);
+        let to_c_hash = to_c.content_hash();
+        db.delegate(to_c).await.map_err(|e| e.to_string()).unwrap();
+
+        let (conn, space_ref) = (&db.conn, &space);
+        let valid = move || async move {
+            get_valid_delegations::<_, MemoryStore, StaticSecret>(conn, space_ref, None)
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap()
+        };
+        let chain = [to_a_hash, to_b_hash, to_c_hash];
+        let before = valid().await;
+        assert!(chain.iter().all(|id| before.contains_key(id)));
+
+        revocation::ActiveModel {
+            id: Set(crate::hash::hash(b"revoke-a")),
+            revoker: Set(DID_METHODS.generate(&owner, "key").unwrap().to_string()),
+            revoked: Set(to_a_hash),
+            serialization: Set(b"revoke-a".to_vec()),
+            revoked_at: Set(Some(OffsetDateTime::now_utc())),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let after = valid().await;
+        assert!(chain.iter().all(|id| !after.contains_key(id)));
+        // delegations outside the revoked subtree are unaffected
+        assert_eq!(after.len(), before.len() - chain.len());
+        assert!(get_effective_permissions::<_, MemoryStore, StaticSecret>(
+            &db.conn, &space, &c_did, None
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap()
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn delegation_listings_filter_and_page_in_the_query() {
+        let key = || {
+            let mut jwk = JWK::generate_ed25519().unwrap();
+            jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+            let did = DID_METHODS.generate(&jwk, "key").unwrap().to_string();
+            (jwk, did)
+        };
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "paged").await;
+        let owner_did = DID_METHODS.generate(&owner, "key").unwrap().to_string();
+        let ((alice, alice_did), (_, bob_did), (_, carol_did)) = (key(), key(), key());
+
+        let mut alice_kv = Vec::new();
+        for i in 0..6 {
+            let docs = format!("docs{i}/");
+            for did in [&alice_did, &bob_did] {
+                let del = owner_delegation(
+                    &owner,
+                    &space,
+                    did,
+                    &[("kv", Some(&docs), "tinycloud.kv/get")],
+                );
+                if did == &alice_did {
+                    alice_kv.push(del.content_hash());
+                }
+                db.delegate(del).await.map_err(|e| e.to_string()).unwrap();
+            }
+        }
+        for i in 0..3 {
+            let database = format!("main{i}.db");
+            db.delegate(owner_delegation(
+                &owner,
+                &space,
+                &alice_did,
+                &[("sql", Some(&database), "tinycloud.sql/read")],
+            ))
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        }
+        let to_carol = key_delegation(
+            &alice,
+            &space,
+            &carol_did,
+            &[("kv", Some("docs0/"), "tinycloud.kv/get")],
+            &[alice_kv[0]],
+        );
+        let to_carol_hash = to_carol.content_hash();
+        db.delegate(to_carol)
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        let (conn, space_ref, owner_ref) = (&db.conn, &space, &owner_did);
+        let list = move |filters: ListFilters| async move {
+            get_filtered_delegations::<_, MemoryStore, StaticSecret>(
+                conn,
+                space_ref,
+                owner_ref,
+                Some(&filters),
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())
+        };
+        let delegate = |did: &str| ListFilters {
+            delegate: Some(did.to_string()),
+            ..Default::default()
+        };
+
+        let (to_alice, next) = list(delegate(&alice_did)).await.unwrap();
+        assert_eq!((to_alice.len(), next), (9, None));
+        assert!(to_alice
+            .values()
+            .all(|del| did_principal_matches(&del.delegate, &alice_did)));
+        let (alice_sql, _) = list(ListFilters {
+            service: Some("sql".into()),
+            ..delegate(&alice_did)
+        })
+        .await
+        .unwrap();
+        assert_eq!(alice_sql.len(), 3);
+        let (kv, _) = list(ListFilters {
+            service: Some("kv".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        // alice's, bob's and carol's
+        assert_eq!(kv.len(), 13);
+        // carol's proof chain is read although alice's delegations are not listed
+        let (to_carol, _) = list(delegate(&carol_did)).await.unwrap();
+        assert_eq!(to_carol.keys().collect::<Vec<_>>(), [&to_carol_hash]);
+
+        let mut paged = HashMap::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let (page, next) = list(ListFilters {
+                limit: Some(4),
+                cursor: cursor.map(|id: Hash| id.to_event_cid().to_string()),
+                ..delegate(&alice_did)
+            })
+            .await
+            .unwrap();
+            pages += 1;
+            assert!(page.len() <= 4);
+            assert!(page.keys().all(|id| !paged.contains_key(id)));
+            paged.extend(page);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(
+            paged.keys().collect::<HashSet<_>>(),
+            to_alice.keys().collect::<HashSet<_>>()
+        );
+
+        assert!(list(ListFilters {
+            cursor: Some("not-a-cid".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err()
+        .contains("Invalid delegation CID"));
+    }
+
+    #[tokio::test]
+    async fn delegation_limits_bound_depth_and_lifetime() {
+        let key = || {
+            let mut jwk = JWK::generate_ed25519().unwrap();
+            jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+            let did = DID_METHODS.generate(&jwk, "key").unwrap().to_string();
+            (jwk, did)
+        };
+        let caps = [("kv", Some("docs/"), "tinycloud.kv/get")];
+
+        let db = get_db()
+            .await
+            .unwrap()
+            .with_delegation_limits(DelegationLimits {
+                max_depth: Some(2),
+                ..Default::default()
+            });
+        let (owner, space) = owned_test_space(&db, "depth").await;
+        let ((first, first_did), (second, second_did), (_, third_did)) = (key(), key(), key());
+
+        let root = owner_delegation(&owner, &space, &first_did, &caps);
+        let root_hash = root.content_hash();
+        db.delegate(root).await.map_err(|e| e.to_string()).unwrap();
+        let child = key_delegation(&first, &space, &second_did, &caps, &[root_hash]);
+        let child_hash = child.content_hash();
+        db.delegate(child).await.map_err(|e| e.to_string()).unwrap();
+        match db
+            .delegate(key_delegation(
+                &second,
+                &space,
+                &third_did,
+                &caps,
+                &[child_hash],
+            ))
+            .await
+        {
+            Err(TxError::InvalidDelegation(delegation::DelegationError::ChainTooDeep(2))) => {}
+            Err(e) => panic!("expected the chain to be too deep, got {e}"),
+            Ok(_) => panic!("expected the chain to be too deep"),
+        }
+
+        // test delegations expire in 2100
+        let lifetime_db = |days| async move {
+            let db = get_db()
+                .await
+                .unwrap()
+                .with_delegation_limits(DelegationLimits {
+                    max_ttl: Some(time::Duration::days(days)),
+                    ..Default::default()
+                });
+            let (owner, space) = owned_test_space(&db, "lifetime").await;
+            db.delegate(owner_delegation(&owner, &space, &first_did, &caps))
+                .await
+        };
+        assert!(lifetime_db(365 * 100).await.is_ok());
+        match lifetime_db(1).await {
+            Err(TxError::InvalidDelegation(delegation::DelegationError::LifetimeTooLong(
+                86_400,
+            ))) => {}
+            Err(e) => panic!("expected the lifetime to be too long, got {e}"),
+            Ok(_) => panic!("expected the lifetime to be too long"),
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_authorizations_are_refused() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db()
+            .await
+            .unwrap()
+            .with_delegation_limits(DelegationLimits {
+                max_capabilities: Some(2),
+                max_invocation_capabilities: Some(1),
+                ..Default::default()
+            });
+        let (owner, space) = owned_test_space(&db, "capabilities").await;
+        let delegate = test_space_id("delegate").did().to_string();
+
+        let oversized = owner_delegation(
+            &owner,
+            &space,
+            &delegate,
+            &[
+                ("kv", Some("a"), "tinycloud.kv/get"),
+                ("kv", Some("b"), "tinycloud.kv/get"),
+                ("kv", Some("c"), "tinycloud.kv/get"),
+            ],
+        );
+        let hash = oversized.content_hash();
+        match db.delegate(oversized).await {
+            Err(TxError::TooManyCapabilities {
+                kind: "delegation",
+                count: 3,
+                max: 2,
+            }) => {}
+            Err(e) => panic!("expected too many capabilities, got {e}"),
+            Ok(_) => panic!("expected too many capabilities"),
+        }
+        assert!(delegation::Entity::find_by_id(hash)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .is_none());
+        db.delegate(owner_delegation(
+            &owner,
+            &space,
+            &delegate,
+            &[
+                ("kv", Some("a"), "tinycloud.kv/get"),
+                ("kv", Some("b"), "tinycloud.kv/get"),
+            ],
+        ))
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+
+        let invocation = owner_invocation(
+            &owner,
+            &space,
+            &[("a", "tinycloud.kv/get"), ("b", "tinycloud.kv/get")],
+            vec![],
+        );
+        let hash = invocation.content_hash();
+        assert!(matches!(
+            db.invoke::<MemoryStaging>(invocation, HashMap::new()).await,
+            Err(TxStoreError::Tx(TxError::TooManyCapabilities {
+                kind: "invocation",
+                count: 2,
+                max: 1,
+            }))
+        ));
+        assert!(invocation::Entity::find_by_id(hash)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn list_spaces_reports_sequence_and_size() {
+        let db = get_db().await.unwrap();
+        let (active_key, active) = owned_test_space(&db, "active").await;
+        let (_, idle) = owned_test_space(&db, "idle").await;
+        put_value(&db, &active_key, &active, "a.txt", b"hello").await;
+        put_value(&db, &active_key, &active, "b.txt", b"world!").await;
+
+        let mut expected = vec![(active, Some(1), Some(11)), (idle, None, None)];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            db.list_spaces().await.map_err(|e| e.to_string()).unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_invocations_have_no_store_side_effects() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "unauthorized").await;
+        let kept = put_value(&db, &owner, &space, "keep.txt", b"keep me").await;
+
+        // a key with no delegation from the owner deletes one key and writes
+        // another in the same invocation
+        let mut intruder = JWK::generate_ed25519().unwrap();
+        intruder.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let mut stage = MemoryStaging.stage(&space).await.unwrap();
+        stage.write_all(b"intruder").await.unwrap();
+        let inputs = HashMap::from([(
+            (space.clone(), "new.txt".parse().unwrap()),
+            (Metadata(Default::default()), stage),
+        )]);
+        let result = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(
+                    &intruder,
+                    &space,
+                    &[
+                        ("keep.txt", "tinycloud.kv/del"),
+                        ("new.txt", "tinycloud.kv/put"),
+                    ],
+                    vec![],
+                ),
+                inputs,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(_)))
+        ));
+
+        assert!(!db
+            .storage
+            .contains(&space, &crate::hash::hash(b"intruder"))
+            .await
+            .unwrap());
+        assert_eq!(
+            get_kv_entity(&db.conn, &space, &"keep.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .map(|entry| entry.value),
+            Some(kept)
+        );
+        assert!(get_kv_entity(&db.conn, &space, &"new.txt".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn invocations_not_committing_by_their_deadline_time_out() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "deadline").await;
+        let key: Path = "new.txt".parse().unwrap();
+        let (db, owner, space, key) = (&db, &owner, &space, &key);
+        let put = |deadline| async move {
+            let mut stage = MemoryStaging.stage(&space).await.unwrap();
+            stage.write_all(b"deadline").await.unwrap();
+            db.invoke_with_options::<MemoryStaging>(
+                owner_invocation(&owner, &space, &[("new.txt", "tinycloud.kv/put")], vec![]),
+                HashMap::from([(
+                    (space.clone(), key.clone()),
+                    (Metadata(Default::default()), stage),
+                )]),
+                KvInvokeOptions {
+                    deadline: Some(deadline),
+                    ..Default::default()
+                },
+            )
+            .await
+        };
+
+        // another writer holds the key past the deadline
+        let held = db
+            .acquire_kv_object_guards(&[(space.clone(), key.clone())])
+            .await;
+        let deadline = Instant::now() + std::time::Duration::from_millis(50);
+        assert!(matches!(
+            put(deadline).await,
+            Err(TxStoreError::Tx(TxError::TimedOut))
+        ));
+        drop(held);
+        assert!(get_kv_entity(&db.conn, space, key).await.unwrap().is_none());
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        assert!(put(deadline).await.is_ok());
+        assert!(get_kv_entity(&db.conn, space, key).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn dry_run_put_authorizes_without_writing() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "dry-run").await;
+        let mut stage = MemoryStaging.stage(&space).await.unwrap();
+        stage.write_all(b"preflight").await.unwrap();
+        let inputs = HashMap::from([(
+            (space.clone(), "new.txt".parse().unwrap()),
+            (Metadata(Default::default()), stage),
+        )]);
+        let (_, outcomes) = db
+            .invoke_with_options::<MemoryStaging>(
+                owner_invocation(&owner, &space, &[("new.txt", "tinycloud.kv/put")], vec![]),
+                inputs,
+                KvInvokeOptions {
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [InvocationOutcome::DryRun(caps)] if caps.len() == 1
+        ));
+        assert!(!db
+            .storage
+            .contains(&space, &crate::hash::hash(b"preflight"))
+            .await
+            .unwrap());
+        assert!(get_kv_entity(&db.conn, &space, &"new.txt".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+
+        // the content is optional, and an unauthorized invoker still fails
+        let mut intruder = JWK::generate_ed25519().unwrap();
+        intruder.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let dry_run = || KvInvokeOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert!(db
+            .invoke_with_options::<MemoryStaging>(
+                owner_invocation(&owner, &space, &[("new.txt", "tinycloud.kv/put")], vec![]),
+                HashMap::new(),
+                dry_run(),
+            )
+            .await
+            .is_ok());
+        assert!(matches!(
+            db.invoke_with_options::<MemoryStaging>(
+                owner_invocation(
+                    &intruder,
+                    &space,
+                    &[("new.txt", "tinycloud.kv/put")],
+                    vec![]
+                ),
+                HashMap::new(),
+                dry_run(),
+            )
+            .await,
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn standalone_reads_are_served_by_the_replica() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("primary.db");
+        let db = SpaceDatabase::new(
+            Database::connect(format!("sqlite://{}?mode=rwc", file.display()))
+                .await
+                .unwrap(),
+            MemoryStore::default(),
+            StaticSecret::new([0u8; 32].to_vec()).unwrap(),
+        )
+        .await
+        .unwrap();
+        let (jwk, space) = owned_test_space(&db, "replica").await;
+        let key: Path = "notes/a".parse().unwrap();
+        let hash = put_value(&db, &jwk, &space, "notes/a", b"hello").await;
+
+        // a read-only connection to the same file stands in for a replica
+        let replica = Database::connect(format!("sqlite://{}?mode=ro", file.display()))
+            .await
+            .unwrap();
+        let db = db.with_replica(Some(replica));
+        assert_eq!(
+            db.kv_get(&space, &key)
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap()
+                .map(|(_, hash, _)| hash),
+            Some(hash),
+            "the replica sees the primary's write"
+        );
+        assert_eq!(
+            db.public_kv_list(&space, &"notes".parse().unwrap())
+                .await
+                .unwrap(),
+            vec![key.clone()]
+        );
+
+        // reads never fall through to the primary, while writes still go there
+        let empty = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&empty, None).await.unwrap();
+        let db = db.with_replica(Some(empty));
+        assert!(db
+            .kv_get(&space, &key)
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap()
+            .is_none());
+        put_value(&db, &jwk, &space, "notes/b", b"world").await;
+        assert!(db
+            .with_replica(None)
+            .public_kv_metadata(&space, &"notes/b".parse().unwrap())
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn expected_content_hashes_gate_puts() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "content-hash").await;
+        let put = |expected: Hash| {
+            let (db, owner, space) = (&db, &owner, &space);
+            async move {
+                let mut stage = MemoryStaging.stage(space).await.unwrap();
+                stage.write_all(b"payload").await.unwrap();
+                let key = (space.clone(), "file.txt".parse::<Path>().unwrap());
+                db.invoke_with_options::<MemoryStaging>(
+                    owner_invocation(owner, space, &[("file.txt", "tinycloud.kv/put")], vec![]),
+                    HashMap::from([(key.clone(), (Metadata(Default::default()), stage))]),
+                    KvInvokeOptions {
+                        content_hashes: HashMap::from([(key, expected)]),
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+        };
+
+        let wrong = crate::hash::hash(b"something else");
+        assert!(matches!(
+            put(wrong).await,
+            Err(TxStoreError::KvContentHashMismatch(_))
+        ));
+        assert!(
+            get_kv_entity(&db.conn, &space, &"file.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let right = crate::hash::hash(b"payload");
+        put(right).await.map_err(|e| e.to_string()).unwrap();
+        assert_eq!(
+            get_kv_entity(&db.conn, &space, &"file.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .map(|entry| entry.value),
+            Some(right)
+        );
+        assert!(db.storage.contains(&space, &right).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn kv_exists_reports_key_presence() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "kv-exists").await;
+        put_value(&db, &jwk, &space, "present.txt", b"here").await;
+
+        for (path, expected) in [("present.txt", true), ("absent.txt", false)] {
+            let (_, outcomes) = db
+                .invoke::<MemoryStaging>(
+                    owner_invocation(&jwk, &space, &[(path, "tinycloud.kv/exists")], vec![]),
+                    HashMap::new(),
+                )
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+            assert!(
+                matches!(outcomes.as_slice(), [InvocationOutcome::KvExists(e)] if *e == expected),
+                "unexpected exists outcome for {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn kv_copy_and_move_reuse_the_source_content_hash() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "kv-copy-move").await;
+        let hash = put_value(&db, &jwk, &space, "docs/original.txt", b"copy me").await;
+        let source = serde_json::json!({ "kvSource": "docs/original.txt" });
+
+        let (_, mut outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(
+                    &jwk,
+                    &space,
+                    &[
+                        ("docs/copy.txt", "tinycloud.kv/copy"),
+                        ("docs/original.txt", "tinycloud.kv/get"),
+                    ],
+                    vec![source.clone()],
+                ),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(outcomes.pop(), Some(InvocationOutcome::KvWrite(h)) if h == hash));
+        let (metadata, copied) =
+            metadata_with_hash(&db.conn, &space, &"docs/copy.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(copied, hash);
+        assert_eq!(
+            metadata.0.get("content-type").map(String::as_str),
+            Some("text/plain")
+        );
+
+        let (_, mut outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(
+                    &jwk,
+                    &space,
+                    &[
+                        ("docs/moved.txt", "tinycloud.kv/move"),
+                        ("docs/original.txt", "tinycloud.kv/get"),
+                        ("docs/original.txt", "tinycloud.kv/del"),
+                    ],
+                    vec![source.clone()],
+                ),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(outcomes.pop(), Some(InvocationOutcome::KvWrite(h)) if h == hash));
+        assert!(
+            get_kv_entity(&db.conn, &space, &"docs/original.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            db.kv_get(&space, &"docs/moved.txt".parse().unwrap())
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap()
+                .map(|(_, hash, _)| hash),
+            Some(hash)
+        );
+
+        // the source has been moved away
+        assert!(matches!(
+            db.invoke::<MemoryStaging>(
+                owner_invocation(
+                    &jwk,
+                    &space,
+                    &[
+                        ("docs/again.txt", "tinycloud.kv/copy"),
+                        ("docs/original.txt", "tinycloud.kv/get"),
+                    ],
+                    vec![source.clone()],
+                ),
+                HashMap::new(),
+            )
+            .await,
+            Err(TxStoreError::KvSourceNotFound(path)) if path.as_str() == "docs/original.txt"
+        ));
+
+        // a copy deleting its source is refused rather than made a move
+        assert!(matches!(
+            db.invoke::<MemoryStaging>(
+                owner_invocation(
+                    &jwk,
+                    &space,
+                    &[
+                        ("docs/again.txt", "tinycloud.kv/copy"),
+                        ("docs/moved.txt", "tinycloud.kv/get"),
+                        ("docs/moved.txt", "tinycloud.kv/del"),
+                    ],
+                    vec![serde_json::json!({ "kvSource": "docs/moved.txt" })],
+                ),
+                HashMap::new(),
+            )
+            .await,
+            Err(TxStoreError::KvCopyDeletesSource(path)) if path.as_str() == "docs/moved.txt"
+        ));
+        assert!(
+            get_kv_entity(&db.conn, &space, &"docs/moved.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // a copy must carry a read capability for its source
+        assert!(matches!(
+            db.invoke::<MemoryStaging>(
+                owner_invocation(
+                    &jwk,
+                    &space,
+                    &[("docs/again.txt", "tinycloud.kv/copy")],
+                    vec![serde_json::json!({ "kvSource": "docs/moved.txt" })],
+                ),
+                HashMap::new(),
+            )
+            .await,
+            Err(TxStoreError::Tx(TxError::InvalidInvocation(
+                invocation::InvocationError::UnauthorizedAction(..)
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn kv_set_metadata_keeps_the_content_hash() {
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "set-metadata").await;
+        let hash = put_value(&db, &owner, &space, "notes.txt", b"{}").await;
+        let update = |path: &str| KvInvokeOptions {
+            metadata_updates: HashMap::from([(
+                (space.clone(), path.parse().unwrap()),
+                Metadata(std::collections::BTreeMap::from([(
+                    "content-type".to_string(),
+                    "application/json".to_string(),
+                )])),
+            )]),
+            ..Default::default()
+        };
+
+        let (_, mut outcomes) = db
+            .invoke_with_options::<crate::storage::memory::MemoryStaging>(
+                owner_invocation(
+                    &owner,
+                    &space,
+                    &[("notes.txt", "tinycloud.kv/set-metadata")],
+                    vec![],
+                ),
+                HashMap::new(),
+                update("notes.txt"),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(outcomes.pop(), Some(InvocationOutcome::KvWrite(h)) if h == hash));
+        let (metadata, stored) =
+            metadata_with_hash(&db.conn, &space, &"notes.txt".parse().unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(stored, hash);
+        assert_eq!(
+            metadata.0.get("content-type").map(String::as_str),
+            Some("application/json")
+        );
+        assert_eq!(db.storage.list(&space).await.unwrap(), vec![hash]);
+
+        assert!(matches!(
+            db.invoke_with_options::<crate::storage::memory::MemoryStaging>(
+                owner_invocation(
+                    &owner,
+                    &space,
+                    &[("missing.txt", "tinycloud.kv/set-metadata")],
+                    vec![],
+                ),
+                HashMap::new(),
+                update("missing.txt"),
+            )
+            .await,
+            Err(TxStoreError::KvKeyNotFound(path)) if path.as_str() == "missing.txt"
+        ));
+    }
+
+    #[tokio::test]
+    async fn versioned_deletes_require_the_current_write() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "versioned-delete").await;
+        put_value(&db, &owner, &space, "a", b"first").await;
+        let first = get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        let hash = put_value(&db, &owner, &space, "a", b"second").await;
+        let second = get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        let delete = |version: &kv_write::Model| {
+            owner_invocation(
+                &owner,
+                &space,
+                &[("a", "tinycloud.kv/del")],
+                vec![serde_json::json!({ "kvVersions": { "a": {
+                    "seq": version.seq,
+                    "epoch": version.epoch.to_block_cid().to_string(),
+                    "epochSeq": version.epoch_seq,
+                } } })],
+            )
+        };
+
+        // the key changed since the first write was read
+        assert!(matches!(
+            db.invoke::<MemoryStaging>(delete(&first), HashMap::new())
+                .await,
+            Err(TxStoreError::KvPreconditionFailed)
+        ));
+        assert!(get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .is_some());
+
+        let (_, mut outcomes) = db
+            .invoke::<MemoryStaging>(delete(&second), HashMap::new())
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(
+            outcomes.pop(),
+            Some(InvocationOutcome::KvDelete(Some(h))) if h == hash
+        ));
+        assert!(get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
-    async fn basic() {
-        let _db = get_db().await.unwrap();
+    async fn malformed_delete_versions_are_refused() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (owner, space) = owned_test_space(&db, "malformed-versions").await;
+        put_value(&db, &owner, &space, "a", b"kept").await;
+        let written = get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        for versions in [
+            serde_json::json!({ "a": { "seq": written.seq } }),
+            serde_json::json!({ "a": {
+                "seq": written.seq,
+                "epoch": "not a cid",
+                "epochSeq": written.epoch_seq,
+            } }),
+            serde_json::json!(["a"]),
+        ] {
+            let delete = owner_invocation(
+                &owner,
+                &space,
+                &[("a", "tinycloud.kv/del")],
+                vec![serde_json::json!({ "kvVersions": versions })],
+            );
+            assert!(matches!(
+                db.invoke::<MemoryStaging>(delete, HashMap::new()).await,
+                Err(TxStoreError::KvInvalidVersions(_))
+            ));
+        }
+        // none of them deleted the key
+        assert!(get_kv_entity(&db.conn, &space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .is_some());
     }
 
     #[test]
@@ -2655,6 +7088,7 @@ mod test {
         .unwrap();
         space::ActiveModel {
             id: Set(SpaceIdWrap(space.clone())),
+            ..Default::default()
         }
         .insert(&db.conn)
         .await
@@ -2787,6 +7221,19 @@ mod test {
             vec!["b", "c", "literal%key", "literalXkey"]
         );
         assert!(!truncated);
+
+        let (entries, truncated) =
+            list_detailed_bounded(&db.conn, &space, &"".parse().unwrap(), Some(2))
+                .await
+                .unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|(path, metadata, hash)| (path.as_str(), metadata.0.len(), *hash))
+                .collect::<Vec<_>>(),
+            vec![("b", 0, shared_value), ("c", 0, shared_value)]
+        );
+        assert!(truncated);
     }
 
     #[tokio::test]
@@ -2869,6 +7316,11 @@ mod test {
         tokio::task::yield_now().await;
         assert!(!issue.is_finished());
         assert!(!use_existing.is_finished());
+        assert!(!db
+            .chain_revoked(&[child_id])
+            .await
+            .ok()
+            .expect("chain lookup"));
 
         revocation::ActiveModel {
             id: Set(crate::hash::hash(b"race-revocation")),
@@ -2887,6 +7339,11 @@ mod test {
             use_existing.await.unwrap(),
             "existing child use check must observe revoke"
         );
+        assert!(db
+            .chain_revoked(&[child_id])
+            .await
+            .ok()
+            .expect("chain lookup"));
     }
 
     #[tokio::test]
@@ -2968,7 +7425,7 @@ mod test {
         assert_eq!(child.status, "ancestor_revoked");
         assert_eq!(
             child.revoked_ancestor_cid,
-            Some(parent_id.to_cid(0x55).to_string())
+            Some(parent_id.to_event_cid().to_string())
         );
 
         revocation::Entity::delete_by_id(crate::hash::hash(b"history-revocation"))
@@ -3046,6 +7503,330 @@ mod test {
         drop(first_guard);
     }
 
+    /// Behaviour the transaction code relies on that differs in how each
+    /// backend reports it: conflict-ignoring inserts, foreign-key violations
+    /// and a put/get round trip through the migrated schema.
+    async fn assert_backend_semantics(conn: DatabaseConnection) {
+        let db = SpaceDatabase::new(
+            conn,
+            MemoryStore::default(),
+            StaticSecret::new([0u8; 32].to_vec()).unwrap(),
+        )
+        .await
+        .expect("migrations apply");
+        let (jwk, space) = owned_test_space(&db, "backend").await;
+
+        let hash = put_value(&db, &jwk, &space, "notes/a", b"hello").await;
+        assert_eq!(
+            db.kv_get(&space, &"notes/a".parse().unwrap())
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap()
+                .map(|(_, hash, _)| hash),
+            Some(hash)
+        );
+
+        // an ignored conflict is reported as RecordNotInserted, not an error
+        let reinsert = space::Entity::insert(space::ActiveModel::from(space::Model {
+            id: SpaceIdWrap(space.clone()),
+            frozen: false,
+        }))
+        .on_conflict(
+            OnConflict::column(space::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(&db.conn)
+        .await;
+        assert!(
+            matches!(reinsert, Err(DbErr::RecordNotInserted)),
+            "{reinsert:?}"
+        );
+
+        let err = epoch::Entity::insert(epoch::ActiveModel::from(epoch::Model {
+            seq: 0,
+            id: crate::hash::hash(b"ghost-epoch"),
+            space: SpaceIdWrap(test_space_id("ghost")),
+        }))
+        .exec(&db.conn)
+        .await
+        .unwrap_err();
+        match err {
+            DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(db_err))) => assert_eq!(
+                db_err.kind(),
+                sea_orm::sqlx::error::ErrorKind::ForeignKeyViolation,
+                "code {:?}",
+                db_err.code()
+            ),
+            other => panic!("expected FK database error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_puts_to_one_space_commit_with_distinct_sequences() {
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "parallel-puts").await;
+
+        let puts = (0..8).map(|i| {
+            let (db, jwk, space) = (db.clone(), jwk.clone(), space.clone());
+            tokio::spawn(async move {
+                put_value(&db, &jwk, &space, &format!("key-{i}"), b"value").await
+            })
+        });
+        for put in futures::future::join_all(puts).await {
+            put.unwrap();
+        }
+
+        let seqs = epoch::Entity::find()
+            .filter(epoch::Column::Space.eq(SpaceIdWrap(space.clone())))
+            .all(&db.conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|epoch| epoch.seq)
+            .collect::<HashSet<_>>();
+        assert_eq!(seqs, (0..8).collect());
+
+        // a second epoch can never claim a sequence number already taken
+        let err = epoch::Entity::insert(epoch::ActiveModel::from(epoch::Model {
+            seq: 0,
+            id: crate::hash::hash(b"racing-epoch"),
+            space: SpaceIdWrap(space),
+        }))
+        .exec(&db.conn)
+        .await
+        .unwrap_err();
+        assert!(is_unique_violation(&err), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn duplicate_sequences_stop_the_unique_index_migration() {
+        use crate::migrations::m20261016_000000_epoch_seq_unique::Migration;
+        use sea_orm_migration::{MigrationTrait, SchemaManager};
+
+        let db = get_db().await.unwrap();
+        let (_, space) = owned_test_space(&db, "duplicate-seqs").await;
+        let manager = SchemaManager::new(&db.conn);
+        Migration.down(&manager).await.unwrap();
+        for id in [b"first-epoch".as_slice(), b"second-epoch"] {
+            epoch::Entity::insert(epoch::ActiveModel::from(epoch::Model {
+                seq: 0,
+                id: crate::hash::hash(id),
+                space: SpaceIdWrap(space.clone()),
+            }))
+            .exec(&db.conn)
+            .await
+            .unwrap();
+        }
+
+        let err = Migration.up(&manager).await.unwrap_err().to_string();
+        assert!(err.contains(&format!("{space} at 0")), "{err}");
+    }
+
+    /// A [`MemoryStore`] whose writes take a while, recording how many were
+    /// in flight at once.
+    #[derive(Debug, Clone, Default)]
+    struct SlowStore {
+        inner: MemoryStore,
+        in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageSetup for SlowStore {
+        type Error = std::io::Error;
+        async fn create(&self, space: &SpaceId) -> Result<(), Self::Error> {
+            self.inner.create(space).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ImmutableReadStore for SlowStore {
+        type Error = std::io::Error;
+        type Readable = <MemoryStore as ImmutableReadStore>::Readable;
+        async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
+            self.inner.contains(space, id).await
+        }
+        async fn read(
+            &self,
+            space: &SpaceId,
+            id: &Hash,
+        ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
+            self.inner.read(space, id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ImmutableWriteStore<crate::storage::memory::MemoryStaging> for SlowStore {
+        type Error = std::io::Error;
+        async fn persist(
+            &self,
+            space: &SpaceId,
+            staged: HashBuffer<Vec<u8>>,
+        ) -> Result<Hash, Self::Error> {
+            use std::sync::atomic::Ordering;
+
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.inner.persist(space, staged).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StoreSize for SlowStore {
+        type Error = std::io::Error;
+        async fn total_size(&self, space: &SpaceId) -> Result<Option<u64>, Self::Error> {
+            self.inner.total_size(space).await
+        }
+    }
+
+    #[tokio::test]
+    async fn staged_puts_persist_concurrently_up_to_the_limit() {
+        use crate::storage::memory::MemoryStaging;
+        use futures::io::AsyncWriteExt;
+
+        let store = SlowStore::default();
+        let db = SpaceDatabase::new(
+            Database::connect(ConnectOptions::new("sqlite::memory:".to_string()))
+                .await
+                .unwrap(),
+            store.clone(),
+            StaticSecret::new([0u8; 32].to_vec()).unwrap(),
+        )
+        .await
+        .unwrap()
+        .with_persist_concurrency(2);
+        let (jwk, space) = owned_test_space(&db, "slow-puts").await;
+
+        let keys = (0..6).map(|i| format!("file-{i}.txt")).collect::<Vec<_>>();
+        let put = |content_hashes: HashMap<(SpaceId, Path), Hash>| {
+            let (db, jwk, space, keys) = (&db, &jwk, &space, &keys);
+            async move {
+                let mut inputs = HashMap::new();
+                for key in keys {
+                    let mut stage = MemoryStaging.stage(space).await.unwrap();
+                    stage.write_all(key.as_bytes()).await.unwrap();
+                    inputs.insert(
+                        (space.clone(), key.parse().unwrap()),
+                        (Metadata(Default::default()), stage),
+                    );
+                }
+                let caps = keys
+                    .iter()
+                    .map(|key| (key.as_str(), "tinycloud.kv/put"))
+                    .collect::<Vec<_>>();
+                db.invoke_with_options::<MemoryStaging>(
+                    owner_invocation(jwk, space, &caps, vec![]),
+                    inputs,
+                    KvInvokeOptions {
+                        content_hashes,
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+        };
+
+        // one failed write fails the whole invocation
+        let wrong = HashMap::from([(
+            (space.clone(), keys[3].parse().unwrap()),
+            crate::hash::hash(b"something else"),
+        )]);
+        assert!(matches!(
+            put(wrong).await,
+            Err(TxStoreError::KvContentHashMismatch(_))
+        ));
+        for key in &keys {
+            assert!(get_kv_entity(&db.conn, &space, &key.parse().unwrap())
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        let (_, outcomes) = put(HashMap::new())
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert_eq!(
+            outcomes
+                .iter()
+                .filter(|outcome| matches!(outcome, InvocationOutcome::KvWrite(_)))
+                .count(),
+            keys.len()
+        );
+        for key in &keys {
+            let hash = get_kv_entity(&db.conn, &space, &key.parse().unwrap())
+                .await
+                .unwrap()
+                .expect("every put is committed")
+                .value;
+            assert_eq!(
+                store.inner.read_to_vec(&space, &hash).await.unwrap(),
+                Some(key.as_bytes().to_vec())
+            );
+        }
+        assert_eq!(
+            store.peak.load(std::sync::atomic::Ordering::SeqCst),
+            2,
+            "writes run concurrently, but no more than the limit at once"
+        );
+    }
+
+    #[tokio::test]
+    async fn sqlite_backend_semantics() {
+        assert_backend_semantics(
+            Database::connect(ConnectOptions::new("sqlite::memory:".to_string()))
+                .await
+                .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn postgres_backend_semantics() {
+        let Ok(database_url) = std::env::var("TINYCLOUD_TEST_POSTGRES_URL") else {
+            eprintln!("skipping PostgreSQL backend test: TINYCLOUD_TEST_POSTGRES_URL is unset");
+            return;
+        };
+
+        let admin = Database::connect(ConnectOptions::new(database_url.clone()))
+            .await
+            .expect("connect to PostgreSQL test database");
+        let schema = format!(
+            "tc_backend_{}_{}",
+            std::process::id(),
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        );
+        admin
+            .execute(Statement::from_string(
+                DbBackend::Postgres,
+                format!("CREATE SCHEMA {schema}"),
+            ))
+            .await
+            .expect("create isolated test schema");
+
+        let mut options = ConnectOptions::new(database_url);
+        options.set_schema_search_path(schema.clone());
+        let conn = Database::connect(options)
+            .await
+            .expect("connect to the test schema");
+        // run in a task so the schema is dropped even if an assertion fails
+        let outcome = tokio::spawn(assert_backend_semantics(conn)).await;
+
+        admin
+            .execute(Statement::from_string(
+                DbBackend::Postgres,
+                format!("DROP SCHEMA {schema} CASCADE"),
+            ))
+            .await
+            .expect("drop isolated test schema");
+        if let Err(panic) = outcome {
+            std::panic::resume_unwind(panic.into_panic());
+        }
+    }
+
     #[tokio::test]
     async fn postgres_concurrent_epoch_appends_do_not_serialize() {
         let Ok(database_url) = std::env::var("TINYCLOUD_TEST_POSTGRES_URL") else {
@@ -3195,6 +7976,227 @@ mod test {
         assert_eq!(db.store_size(&space).await.unwrap(), None);
     }
 
+    #[tokio::test]
+    async fn frozen_spaces_serve_reads_but_reject_writes_and_delegations() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "frozen").await;
+        let written = put_value(&db, &jwk, &space, "a", b"kept").await;
+
+        assert_eq!(db.is_space_frozen(&space).await.unwrap(), Some(false));
+        assert!(db.set_space_frozen(&space, true).await.unwrap());
+        assert_eq!(db.is_space_frozen(&space).await.unwrap(), Some(true));
+        assert!(!db
+            .set_space_frozen(&test_space_id("unhosted"), true)
+            .await
+            .unwrap());
+
+        let mut stage = MemoryStaging.stage(&space).await.unwrap();
+        futures::io::AsyncWriteExt::write_all(&mut stage, b"new")
+            .await
+            .unwrap();
+        let put = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("b", "tinycloud.kv/put")], vec![]),
+                HashMap::from([(
+                    (space.clone(), "b".parse().unwrap()),
+                    (Metadata(Default::default()), stage),
+                )]),
+            )
+            .await;
+        assert!(matches!(
+            put,
+            Err(TxStoreError::Tx(TxError::SpaceFrozen(ref frozen))) if frozen == &space
+        ));
+        let delegation = db
+            .delegate(owner_delegation(
+                &jwk,
+                &space,
+                &test_space_id("delegate").did().to_string(),
+                &[("kv", Some("a"), "tinycloud.kv/get")],
+            ))
+            .await;
+        assert!(matches!(delegation, Err(TxError::SpaceFrozen(_))));
+
+        let (_, outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/get")], vec![]),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [InvocationOutcome::KvRead(Some((_, hash, _)))] if *hash == written
+        ));
+
+        // unfreezing restores writes
+        assert!(db.set_space_frozen(&space, false).await.unwrap());
+        put_value(&db, &jwk, &space, "b", b"new").await;
+    }
+
+    /// Denies invocations of one ability.
+    #[derive(Debug)]
+    struct DenyAbility(&'static str);
+
+    #[async_trait::async_trait]
+    impl Policy for DenyAbility {
+        async fn authorize(&self, ctx: &AuthContext<'_>) -> Result<(), PolicyError> {
+            if ctx
+                .capabilities
+                .iter()
+                .any(|c| c.ability.to_string() == self.0)
+            {
+                Err(PolicyError::Denied(format!("{} is disabled", self.0)))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn policy_denials_roll_back_invocations() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db()
+            .await
+            .unwrap()
+            .with_policy(Arc::new(DenyAbility("tinycloud.kv/del")));
+        let (jwk, space) = owned_test_space(&db, "policy").await;
+        let written = put_value(&db, &jwk, &space, "a", b"kept").await;
+
+        let delete = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/del")], vec![]),
+                HashMap::new(),
+            )
+            .await;
+        assert!(matches!(
+            delete,
+            Err(TxStoreError::Tx(TxError::Policy(PolicyError::Denied(_))))
+        ));
+
+        let (_, outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/get")], vec![]),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [InvocationOutcome::KvRead(Some((_, hash, _)))] if *hash == written
+        ));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_key_keeps_content_shared_with_another() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "shared-content").await;
+        let a = put_value(&db, &jwk, &space, "a", b"same").await;
+        let b = put_value(&db, &jwk, &space, "b", b"same").await;
+        assert_eq!(a, b);
+
+        db.invoke::<MemoryStaging>(
+            owner_invocation(&jwk, &space, &[("a", "tinycloud.kv/del")], vec![]),
+            HashMap::new(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+
+        let (_, outcomes) = db
+            .invoke::<MemoryStaging>(
+                owner_invocation(&jwk, &space, &[("b", "tinycloud.kv/get")], vec![]),
+                HashMap::new(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [InvocationOutcome::KvRead(Some((_, hash, _)))] if *hash == b
+        ));
+        assert_eq!(
+            db.storage.read_to_vec(&space, &b).await.unwrap().as_deref(),
+            Some(&b"same"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_space_removes_rows_and_orphaned_blocks() {
+        use crate::storage::memory::MemoryStaging;
+
+        let db = get_db().await.unwrap();
+        let (jwk, space) = owned_test_space(&db, "deleted").await;
+        let (other_jwk, other) = owned_test_space(&db, "kept").await;
+        put_value(&db, &jwk, &space, "a", b"gone").await;
+        let kept = put_value(&db, &other_jwk, &other, "a", b"kept").await;
+        let mut holder = JWK::generate_ed25519().unwrap();
+        holder.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let caps = [("kv", Some("a"), "tinycloud.kv/get")];
+        let parent = owner_delegation(
+            &jwk,
+            &space,
+            &DID_METHODS.generate(&holder, "key").unwrap().to_string(),
+            &caps,
+        );
+        let parent_hash = parent.content_hash();
+        for delegation in [
+            parent,
+            key_delegation(
+                &holder,
+                &space,
+                &test_space_id("delegate").did().to_string(),
+                &caps,
+                &[parent_hash],
+            ),
+        ] {
+            db.delegate(delegation)
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+        }
+
+        // a block no KV write refers to, as left by an interrupted upload
+        let mut stage = MemoryStaging.stage(&space).await.unwrap();
+        futures::io::AsyncWriteExt::write_all(&mut stage, b"orphan")
+            .await
+            .unwrap();
+        db.storage.persist(&space, stage).await.unwrap();
+
+        assert_eq!(db.delete_space(&space).await.unwrap(), 2);
+        assert!(db
+            .kv_get(&space, &"a".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db.storage.list(&space).await.unwrap().is_empty());
+        assert!(db.event_log(&space, -1, 16).await.unwrap().is_empty());
+        assert_eq!(db.is_space_frozen(&space).await.unwrap(), None);
+        assert_eq!(delegation::Entity::find().count(&db.conn).await.unwrap(), 0);
+        assert_eq!(
+            parent_delegations::Entity::find()
+                .count(&db.conn)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(invocation::Entity::find().count(&db.conn).await.unwrap(), 1);
+
+        // other spaces are untouched, and deleting again is a no-op
+        assert!(matches!(
+            db.kv_get(&other, &"a".parse().unwrap()).await.unwrap(),
+            Some((_, hash, _)) if hash == kept
+        ));
+        assert_eq!(db.delete_space(&space).await.unwrap(), 0);
+    }
+
     #[tokio::test]
     async fn list_space_ids_returns_all_created_spaces() {
         let db = get_db().await.unwrap();
@@ -3206,9 +8208,11 @@ mod test {
         space::Entity::insert_many([
             space::ActiveModel::from(space::Model {
                 id: SpaceIdWrap(a.clone()),
+                frozen: false,
             }),
             space::ActiveModel::from(space::Model {
                 id: SpaceIdWrap(b.clone()),
+                frozen: false,
             }),
         ])
         .exec(&db.conn)
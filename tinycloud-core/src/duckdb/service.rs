@@ -242,6 +242,19 @@ impl DuckDbService {
             .join(format!("{}.duckdb", db_name))
     }
 
+    /// Stop every DuckDB database actor of `space` and remove its cached
+    /// files. The stored artifacts are removed with the space's rows by
+    /// [`SpaceDatabase::delete_space`](crate::SpaceDatabase::delete_space).
+    pub async fn remove_space(&self, space: &SpaceId) -> Result<(), DuckDbError> {
+        let space = space.to_string();
+        self.databases.retain(|(s, _), _| s != &space);
+        match tokio::fs::remove_dir_all(PathBuf::from(&self.base_path).join(&space)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(DuckDbError::Internal(e.to_string())),
+        }
+    }
+
     async fn discard_local_state(&self, key: &(String, String)) -> Result<(), DuckDbError> {
         self.databases.remove(key);
         let cache_path = PathBuf::from(&self.base_path)
@@ -965,7 +965,7 @@ fn native_invocation_cid(invocation: &InvocationInfo) -> Result<String, Encrypti
         .invocation
         .encode()
         .map_err(|err| EncryptionServiceError::InvalidBody(err.to_string()))?;
-    Ok(hash(encoded.as_bytes()).to_cid(0x55).to_string())
+    Ok(hash(encoded.as_bytes()).to_event_cid().to_string())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
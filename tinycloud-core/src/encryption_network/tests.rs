@@ -14,8 +14,7 @@ use time::OffsetDateTime;
 use x25519_dalek::{PublicKey, StaticSecret as X25519StaticSecret};
 
 use tinycloud_auth::{
-    authorization::Cid,
-    multihash_codetable::{Code, MultihashDigest},
+    codec,
     resolver::DID_METHODS,
     ssi::{
         claims::jwt::NumericDate,
@@ -187,7 +186,7 @@ fn build_session_invocation_info(
         resource.to_string().parse().expect("network uri");
     let capability: tinycloud_auth::ucan_capabilities_object::Ability =
         action.to_string().try_into().expect("capability");
-    let delegation_cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"network-session-delegation"));
+    let delegation_cid = codec::to_event_cid(b"network-session-delegation");
 
     let now = OffsetDateTime::now_utc().unix_timestamp();
     let mut attenuation = tinycloud_auth::ucan_capabilities_object::Capabilities::new();
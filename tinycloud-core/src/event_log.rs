@@ -0,0 +1,261 @@
+use crate::{
+    encryption::{maybe_decrypt, ColumnEncryption, EncryptionError},
+    events::{Delegation, Event, Invocation, Operation, Revocation},
+    hash::Hash,
+    models::*,
+    relationships::*,
+    types::{Metadata, SpaceIdWrap},
+};
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use sea_orm::{entity::prelude::*, Condition, ConnectionTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tinycloud_auth::{
+    authorization::{TinyCloudDelegation, TinyCloudInvocation, TinyCloudRevocation},
+    ipld_core::cid::Cid,
+    resource::{Path, SpaceId},
+};
+
+/// One event from a space's log, as served to and ingested from other
+/// nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// The sequence number of the epoch the event was committed in.
+    pub seq: i64,
+    #[serde(flatten)]
+    pub event: LoggedEvent,
+}
+
+/// A committed event in its original header encoding, which decodes to
+/// exactly the bytes its hash was taken over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LoggedEvent {
+    Delegation {
+        event: String,
+    },
+    Invocation {
+        event: String,
+        writes: Vec<LoggedWrite>,
+        deletes: Vec<LoggedDelete>,
+    },
+    Revocation {
+        event: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedWrite {
+    pub space: SpaceId,
+    pub key: Path,
+    /// CID of the written value. Values themselves are not part of the log.
+    pub value: String,
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedDelete {
+    pub space: SpaceId,
+    pub key: Path,
+}
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error(transparent)]
+    Db(#[from] DbErr),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+    #[error("logged event {0} is not a stored delegation, invocation or revocation")]
+    UnknownEvent(Cid),
+    #[error("invalid log entry: {0}")]
+    InvalidEntry(String),
+    #[error("logged write of {0} has no value in this node's block store")]
+    MissingValue(Cid),
+    #[error("block store error: {0}")]
+    Store(String),
+}
+
+/// The events committed to `space` in the `epochs` epochs after sequence
+/// number `since`, in commit order.
+pub(crate) async fn read<C: ConnectionTrait>(
+    db: &C,
+    space: &SpaceId,
+    since: i64,
+    epochs: u32,
+    encryption: Option<&ColumnEncryption>,
+) -> Result<Vec<LogEntry>, LogError> {
+    let order = event_order::Entity::find()
+        .filter(
+            Condition::all()
+                .add(event_order::Column::Space.eq(SpaceIdWrap(space.clone())))
+                .add(event_order::Column::Seq.gt(since))
+                .add(event_order::Column::Seq.lte(since.saturating_add(epochs.into()))),
+        )
+        .order_by_asc(event_order::Column::Seq)
+        .order_by_asc(event_order::Column::Epoch)
+        .order_by_asc(event_order::Column::EpochSeq)
+        .all(db)
+        .await?;
+    let hashes: Vec<Hash> = order.iter().map(|o| o.event).collect();
+
+    let mut delegations: HashMap<Hash, Vec<u8>> = delegation::Entity::find()
+        .filter(delegation::Column::Id.is_in(hashes.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|d| Ok((d.id, maybe_decrypt(encryption, &d.serialization)?)))
+        .collect::<Result<_, EncryptionError>>()?;
+    let mut invocations: HashMap<Hash, Vec<u8>> = invocation::Entity::find()
+        .filter(invocation::Column::Id.is_in(hashes.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|i| Ok((i.id, maybe_decrypt(encryption, &i.serialization)?)))
+        .collect::<Result<_, EncryptionError>>()?;
+    let mut revocations: HashMap<Hash, Vec<u8>> = revocation::Entity::find()
+        .filter(revocation::Column::Id.is_in(hashes))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| (r.id, r.serialization))
+        .collect();
+
+    let invoked: Vec<Hash> = invocations.keys().copied().collect();
+    let mut writes = kv_write::Entity::find()
+        .filter(kv_write::Column::Invocation.is_in(invoked.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .fold(HashMap::<Hash, Vec<LoggedWrite>>::new(), |mut m, w| {
+            m.entry(w.invocation).or_default().push(LoggedWrite {
+                space: w.space.0,
+                key: w.key.0,
+                value: w.value.to_block_cid().to_string(),
+                metadata: w.metadata,
+            });
+            m
+        });
+    let mut deletes = kv_delete::Entity::find()
+        .filter(kv_delete::Column::InvocationId.is_in(invoked))
+        .all(db)
+        .await?
+        .into_iter()
+        .fold(HashMap::<Hash, Vec<LoggedDelete>>::new(), |mut m, d| {
+            m.entry(d.invocation_id).or_default().push(LoggedDelete {
+                space: d.space.0,
+                key: d.key.0,
+            });
+            m
+        });
+
+    order
+        .into_iter()
+        .map(|o| {
+            let event = if let Some(serialization) = delegations.remove(&o.event) {
+                LoggedEvent::Delegation {
+                    event: header(serialization),
+                }
+            } else if let Some(serialization) = invocations.remove(&o.event) {
+                LoggedEvent::Invocation {
+                    event: header(serialization),
+                    writes: writes.remove(&o.event).unwrap_or_default(),
+                    deletes: deletes.remove(&o.event).unwrap_or_default(),
+                }
+            } else if let Some(serialization) = revocations.remove(&o.event) {
+                LoggedEvent::Revocation {
+                    event: header(serialization),
+                }
+            } else {
+                return Err(LogError::UnknownEvent(o.event.to_event_cid()));
+            };
+            Ok(LogEntry { seq: o.seq, event })
+        })
+        .collect()
+}
+
+/// Decode a logged event for [`transact`](crate::db::transact), or `None`
+/// if this node already has it.
+pub(crate) async fn decode<C: ConnectionTrait>(
+    db: &C,
+    event: LoggedEvent,
+) -> Result<Option<Event>, LogError> {
+    Ok(match event {
+        LoggedEvent::Delegation { event } => {
+            let delegation = Delegation::from_header_ser::<TinyCloudDelegation>(&event)
+                .map_err(|e| LogError::InvalidEntry(e.to_string()))?;
+            delegation::Entity::find_by_id(delegation.content_hash())
+                .one(db)
+                .await?
+                .is_none()
+                .then(|| Event::Delegation(Box::new(delegation)))
+        }
+        LoggedEvent::Revocation { event } => {
+            let revocation = Revocation::from_header_ser::<TinyCloudRevocation>(&event)
+                .map_err(|e| LogError::InvalidEntry(e.to_string()))?;
+            revocation::Entity::find_by_id(revocation.content_hash())
+                .one(db)
+                .await?
+                .is_none()
+                .then(|| Event::Revocation(Box::new(revocation)))
+        }
+        LoggedEvent::Invocation {
+            event,
+            writes,
+            deletes,
+        } => {
+            let invocation = Invocation::from_header_ser::<TinyCloudInvocation>(&event)
+                .map_err(|e| LogError::InvalidEntry(e.to_string()))?;
+            if invocation::Entity::find_by_id(invocation.content_hash())
+                .one(db)
+                .await?
+                .is_some()
+            {
+                return Ok(None);
+            }
+            let mut ops = Vec::with_capacity(writes.len() + deletes.len());
+            for write in writes {
+                ops.push(Operation::KvWrite {
+                    space: write.space,
+                    key: write.key,
+                    value: parse_cid(&write.value)?,
+                    metadata: write.metadata,
+                });
+            }
+            // a delete names the version its invocation's facts expected, if
+            // any; one that names none, replayed in order, removes the same
+            // write it did on the node that committed it
+            let versions =
+                crate::db::kv_delete_versions(&invocation.0).map_err(LogError::InvalidEntry)?;
+            ops.extend(deletes.into_iter().map(|delete| {
+                Operation::KvDelete {
+                    version: versions
+                        .get(&(delete.space.clone(), delete.key.clone()))
+                        .copied(),
+                    space: delete.space,
+                    key: delete.key,
+                }
+            }));
+            Some(Event::Invocation(Box::new(invocation), ops))
+        }
+    })
+}
+
+/// The header encoding of a stored event serialization: UCANs are stored as
+/// their JWT, CACAOs as raw DAG-CBOR.
+fn header(serialization: Vec<u8>) -> String {
+    match String::from_utf8(serialization) {
+        Ok(jwt) if jwt.contains('.') => jwt,
+        Ok(other) => URL_SAFE.encode(other),
+        Err(e) => URL_SAFE.encode(e.into_bytes()),
+    }
+}
+
+fn parse_cid(cid: &str) -> Result<Hash, LogError> {
+    cid.parse::<Cid>()
+        .map(Hash::from)
+        .map_err(|_| LogError::InvalidEntry(format!("invalid CID {cid}")))
+}
@@ -5,6 +5,7 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use serde_ipld_dagcbor::EncodeError;
+use tinycloud_auth::codec::{DAG_CBOR, RAW};
 pub use tinycloud_auth::{
     authorization::{
         EncodingError, HeaderEncode, TinyCloudDelegation, TinyCloudInvocation, TinyCloudRevocation,
@@ -14,7 +15,7 @@ pub use tinycloud_auth::{
     resource::{Path, SpaceId},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SerializedEvent<T>(pub T, pub(crate) Vec<u8>);
 
 #[non_exhaustive]
@@ -24,6 +25,16 @@ pub enum FromReqErr<T> {
     Encoding(#[from] EncodingError),
     #[error(transparent)]
     TryFrom(T),
+    #[error("authorization of {size} bytes exceeds the limit of {max} bytes")]
+    TooLarge { size: usize, max: u64 },
+    #[error("invalid authorization body: {0}")]
+    Body(String),
+    #[error("{kind} has {count} capabilities, more than the {max} allowed")]
+    TooManyCapabilities {
+        kind: &'static str,
+        count: usize,
+        max: usize,
+    },
 }
 
 impl<T> SerializedEvent<T> {
@@ -50,7 +61,7 @@ pub type Delegation = SerializedEvent<DelegationInfo>;
 pub type Invocation = SerializedEvent<InvocationInfo>;
 pub type Revocation = SerializedEvent<RevocationInfo>;
 
-#[derive(Debug, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub(crate) enum Operation {
     KvWrite {
         space: SpaceId,
@@ -126,7 +137,7 @@ pub(crate) enum VersionedOperation {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Event {
     Invocation(Box<Invocation>, Vec<Operation>),
     Delegation(Box<Delegation>),
@@ -163,29 +174,31 @@ pub enum HashError {
     EncodeError(#[from] EncodeError<std::collections::TryReserveError>),
 }
 
+/// The hash of an epoch of `space`, which is the same whatever order its
+/// `parents` are given in. `events` are hashed in the order they were
+/// committed, which their positions in the epoch record.
 pub(crate) fn epoch_hash(
     space: &SpaceId,
     events: &[&(Hash, Event)],
     parents: &[Hash],
 ) -> Result<Hash, HashError> {
+    let mut parents = parents.to_vec();
+    parents.sort();
     Ok(hash(&serde_ipld_dagcbor::to_vec(&Epoch {
-        parents: parents.iter().map(|h| h.to_cid(0x71)).collect(),
+        parents: parents.iter().map(|h| h.to_cid(DAG_CBOR)).collect(),
         events: events
             .iter()
             .map(|(h, e)| {
                 Ok(match e {
                     Event::Invocation(_, ops) => hash_inv(h, space, ops)?,
-                    Event::Delegation(_) => OneOrMany::One(h.to_cid(RAW_CODEC)),
-                    Event::Revocation(_) => OneOrMany::One(h.to_cid(RAW_CODEC)),
+                    Event::Delegation(_) => OneOrMany::One(h.to_cid(RAW)),
+                    Event::Revocation(_) => OneOrMany::One(h.to_cid(RAW)),
                 })
             })
             .collect::<Result<Vec<OneOrMany>, HashError>>()?,
     })?))
 }
 
-const CBOR_CODEC: u64 = 0x71;
-const RAW_CODEC: u64 = 0x55;
-
 fn hash_inv(inv_hash: &Hash, sp: &SpaceId, ops: &[Operation]) -> Result<OneOrMany, HashError> {
     #[derive(Debug, Serialize)]
     #[serde(untagged)]
@@ -211,7 +224,7 @@ fn hash_inv(inv_hash: &Hash, sp: &SpaceId, ops: &[Operation]) -> Result<OneOrMan
                 metadata,
             } if space == sp => Some(Op::KvWrite {
                 key,
-                value: value.to_cid(CBOR_CODEC),
+                value: value.to_cid(DAG_CBOR),
                 metadata,
             }),
             Operation::KvDelete {
@@ -221,17 +234,17 @@ fn hash_inv(inv_hash: &Hash, sp: &SpaceId, ops: &[Operation]) -> Result<OneOrMan
                 ..
             } if space == sp => Some(Op::KvDelete {
                 key,
-                version: version.map(|(v, h, s)| (v, h.to_cid(CBOR_CODEC), s)),
+                version: version.map(|(v, h, s)| (v, h.to_cid(DAG_CBOR), s)),
             }),
             _ => None,
         })
-        .map(|op| Ok(hash(&serde_ipld_dagcbor::to_vec(&op)?).to_cid(CBOR_CODEC)))
+        .map(|op| Ok(hash(&serde_ipld_dagcbor::to_vec(&op)?).to_cid(DAG_CBOR)))
         .collect::<Result<Vec<_>, HashError>>()?;
 
     Ok(if ops.is_empty() {
-        OneOrMany::One(inv_hash.to_cid(RAW_CODEC))
+        OneOrMany::One(inv_hash.to_cid(RAW))
     } else {
-        let mut v = vec![inv_hash.to_cid(RAW_CODEC)];
+        let mut v = vec![inv_hash.to_cid(RAW)];
         v.extend(ops);
         OneOrMany::Many(v)
     })
@@ -2,8 +2,9 @@ use multihash_derive::Hasher;
 use sea_orm::entity::prelude::*;
 use sea_orm::DbErr;
 use tinycloud_auth::{
+    codec,
     ipld_core::cid::{multihash::Multihash, Cid},
-    multihash_codetable::{Blake3_256, Code, MultihashDigest},
+    multihash_codetable::{Blake3_256, MultihashDigest},
 };
 
 pub fn hash(data: &[u8]) -> Hash {
@@ -11,20 +12,26 @@ pub fn hash(data: &[u8]) -> Hash {
 }
 
 #[derive(Debug, Default)]
-pub struct Blake3Hasher(Blake3_256);
+pub struct Blake3Hasher(Blake3_256, u64);
 
 impl Blake3Hasher {
     pub fn new() -> Self {
-        Self(Blake3_256::default())
+        Self(Blake3_256::default(), 0)
     }
 
     pub fn update(&mut self, data: &[u8]) -> &mut Self {
         self.0.update(data);
+        self.1 += data.len() as u64;
         self
     }
 
+    /// The number of bytes hashed so far.
+    pub fn count(&self) -> u64 {
+        self.1
+    }
+
     pub fn finalize(&mut self) -> Hash {
-        Hash(Code::Blake3_256.wrap(self.0.finalize()).unwrap())
+        Hash(codec::CONTENT_HASH.wrap(self.0.finalize()).unwrap())
     }
 }
 
@@ -36,6 +43,27 @@ impl Hash {
     pub fn to_cid(self, codec: u64) -> Cid {
         Cid::new_v1(codec, self.0)
     }
+
+    /// The CID of the delegation, invocation or revocation with this hash,
+    /// as clients compute it with [`codec::to_event_cid`].
+    pub fn to_event_cid(self) -> Cid {
+        self.to_cid(codec::RAW)
+    }
+
+    /// The CID of the stored value or epoch with this hash. Epochs are
+    /// DAG-CBOR, but have always been reported with the raw codec.
+    pub fn to_block_cid(self) -> Cid {
+        self.to_cid(codec::RAW)
+    }
+
+    /// The hash of content whose BLAKE3 digest is `digest`.
+    pub fn from_blake3_digest(digest: [u8; 32]) -> Self {
+        Hash(
+            codec::CONTENT_HASH
+                .wrap(&digest)
+                .expect("a 32-byte digest fits a 64-byte multihash"),
+        )
+    }
 }
 
 impl std::cmp::Ord for Hash {
@@ -152,3 +180,30 @@ impl sea_orm::TryFromU64 for Hash {
         Err(DbErr::ConvertFromU64(stringify!($type)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{memory::MemoryStaging, ImmutableStaging};
+    use futures::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn staged_writes_and_cids_share_one_content_hash() {
+        let data = b"some content";
+        let space = "tinycloud:key:test:default".parse().unwrap();
+        let mut stage = MemoryStaging.stage(&space).await.unwrap();
+        stage.write_all(data).await.unwrap();
+        let staged = stage.hash();
+
+        assert_eq!(staged, hash(data));
+        assert_eq!(
+            staged.to_block_cid(),
+            Cid::new_v1(codec::RAW, codec::CONTENT_HASH.digest(data))
+        );
+        assert_eq!(staged.to_event_cid(), codec::to_event_cid(data));
+        assert_eq!(
+            staged,
+            Hash::from_blake3_digest(staged.as_ref().try_into().unwrap())
+        );
+    }
+}
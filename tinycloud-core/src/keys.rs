@@ -39,6 +39,11 @@ pub trait Secrets {
     async fn get_peer_id(&self, space: &SpaceId) -> Result<PeerId, Self::Error> {
         Ok(self.get_pubkey(space).await?.to_peer_id())
     }
+    /// Every keypair `space` may have been hosted under, current key first.
+    /// Backends without key rotation only ever have the current keypair.
+    async fn candidate_keypairs(&self, space: &SpaceId) -> Result<Vec<Keypair>, Self::Error> {
+        Ok(vec![self.get_keypair(space).await?])
+    }
 }
 
 #[async_trait]
@@ -49,9 +54,16 @@ pub trait SecretsSetup {
     async fn setup(&self, input: Self::Input) -> Result<Self::Output, Self::Error>;
 }
 
+/// Derives per-space keypairs from a primary secret.
+///
+/// Secrets retired by a key rotation can be kept as `previous` secrets: new
+/// spaces are always keyed by the primary, while spaces first hosted under a
+/// previous secret keep resolving to their original keypair. Node-level keys
+/// (`derive_key`, `node_keypair`) only ever use the primary.
 #[derive(Clone)]
 pub struct StaticSecret {
     secret: Vec<u8>,
+    previous: Vec<Vec<u8>>,
 }
 
 impl StaticSecret {
@@ -59,10 +71,23 @@ impl StaticSecret {
         if secret.len() < 32 {
             Err(secret)
         } else {
-            Ok(Self { secret })
+            Ok(Self {
+                secret,
+                previous: Vec::new(),
+            })
         }
     }
 
+    /// Keep honouring secrets from earlier rotations, newest first. Fails with
+    /// the first secret shorter than 32 bytes.
+    pub fn with_previous(mut self, previous: Vec<Vec<u8>>) -> Result<Self, Vec<u8>> {
+        if let Some(short) = previous.iter().find(|secret| secret.len() < 32) {
+            return Err(short.clone());
+        }
+        self.previous = previous;
+        Ok(self)
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         &self.secret
     }
@@ -98,15 +123,25 @@ impl StaticSecret {
     }
 }
 
+fn derive_space_keypair(secret: &[u8], space: &SpaceId) -> Result<Keypair, DecodingError> {
+    let mut hasher = Blake3_256::default();
+    hasher.update(secret);
+    hasher.update(space.to_string().as_bytes());
+    let derived = hasher.finalize().to_vec();
+    Ok(EdKP::from(SecretKey::try_from_bytes(derived)?).into())
+}
+
 #[async_trait]
 impl Secrets for StaticSecret {
     type Error = DecodingError;
     async fn get_keypair(&self, space: &SpaceId) -> Result<Keypair, Self::Error> {
-        let mut hasher = Blake3_256::default();
-        hasher.update(&self.secret);
-        hasher.update(space.to_string().as_bytes());
-        let derived = hasher.finalize().to_vec();
-        Ok(EdKP::from(SecretKey::try_from_bytes(derived)?).into())
+        derive_space_keypair(&self.secret, space)
+    }
+    async fn candidate_keypairs(&self, space: &SpaceId) -> Result<Vec<Keypair>, Self::Error> {
+        std::iter::once(&self.secret)
+            .chain(&self.previous)
+            .map(|secret| derive_space_keypair(secret, space))
+            .collect()
     }
     async fn stage_keypair(&self, space: &SpaceId) -> Result<PublicKey, Self::Error> {
         self.get_pubkey(space).await
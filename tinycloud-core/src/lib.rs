@@ -1,18 +1,23 @@
+pub mod allow_list;
+pub mod commit_observer;
 pub mod database_artifacts;
 pub mod db;
 #[cfg(feature = "duckdb")]
 pub mod duckdb;
 pub mod encryption;
 pub mod encryption_network;
+pub mod event_log;
 pub mod events;
 pub mod hash;
 pub mod keys;
 pub mod manifest;
 pub mod migrations;
 pub mod models;
+pub mod policy;
 pub mod policy_authority;
 pub mod policy_capability;
 pub mod relationships;
+pub mod resolver;
 pub mod share_email;
 pub mod sql;
 pub mod sql_sizes;
@@ -23,7 +28,7 @@ pub mod write_hooks;
 
 pub use db::{
     Commit, DelegationStatus, InvocationOutcome, KvInvokeOptions, KvPrecondition, SpaceDatabase,
-    TransactResult, TxError, TxStoreError,
+    SpaceDeleteError, TransactResult, TxError, TxStoreError,
 };
 pub use encryption::ColumnEncryption;
 pub use libp2p;
@@ -10,8 +10,25 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let schema = Schema::new(manager.get_database_backend());
 
+        // Like `revocation` below, `space` keeps its historical shape; later
+        // migrations add its columns.
         manager
-            .create_table(schema.create_table_from_entity(space::Entity))
+            .create_table(
+                Table::create()
+                    .table(space::Entity)
+                    .col(
+                        ColumnDef::new_with_type(
+                            space::Column::Id,
+                            sea_orm::sea_query::ColumnType::String(
+                                sea_orm::sea_query::StringLen::Max,
+                            ),
+                        )
+                        .not_null()
+                        .unique_key()
+                        .primary_key(),
+                    )
+                    .to_owned(),
+            )
             .await?;
         manager
             .create_table(schema.create_table_from_entity(actor::Entity))
@@ -0,0 +1,71 @@
+//! Gives every epoch of a space its own sequence number, so two transactions
+//! racing to append to the same space cannot both commit the same `seq`; the
+//! loser fails on this index and `SpaceDatabase` retries it on fresh tips.
+
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+use crate::models::epoch;
+
+const INDEX: &str = "idx_epoch_space_seq";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Nodes that already raced have duplicate sequence numbers, which are
+        // part of recorded KV versions and cannot be renumbered here. Running
+        // on without the index would let the race recur, so the migration
+        // stops and names the duplicates for an operator to resolve.
+        let db = manager.get_connection();
+        let duplicates = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT space, seq FROM epoch GROUP BY space, seq HAVING COUNT(*) > 1 \
+                 ORDER BY space, seq LIMIT 10",
+            ))
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(format!(
+                    "{} at {}",
+                    row.try_get::<String>("", "space")?,
+                    row.try_get::<i64>("", "seq")?
+                ))
+            })
+            .collect::<Result<Vec<_>, DbErr>>()?;
+        if !duplicates.is_empty() {
+            return Err(DbErr::Migration(format!(
+                "cannot create {INDEX}: epochs share sequence numbers ({}{}); \
+                 remove or renumber the duplicate epochs and restart",
+                duplicates.join(", "),
+                if duplicates.len() == 10 { ", ..." } else { "" }
+            )));
+        }
+        manager
+            .create_index(
+                Index::create()
+                    .name(INDEX)
+                    .table(epoch::Entity)
+                    .col(epoch::Column::Space)
+                    .col(epoch::Column::Seq)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(INDEX)
+                    .table(epoch::Entity)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,39 @@
+//! Lets operators put a space into read-only mode: a frozen space takes no
+//! new delegations or writes, while reads keep working.
+
+use sea_orm_migration::prelude::*;
+
+use crate::models::space;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(space::Entity)
+                    .add_column(
+                        ColumnDef::new(space::Column::Frozen)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(space::Entity)
+                    .drop_column(space::Column::Frozen)
+                    .to_owned(),
+            )
+            .await
+    }
+}
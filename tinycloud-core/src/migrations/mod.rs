@@ -11,6 +11,8 @@ pub mod m20260715_000000_revocation_timestamp;
 pub mod m20260719_000000_share_email_protocol;
 pub mod m20260719_000001_share_policy_presentation_jti;
 pub mod m20260719_000002_policy_status_freshness;
+pub mod m20261016_000000_epoch_seq_unique;
+pub mod m20261016_000001_space_frozen;
 
 pub struct Migrator;
 
@@ -30,6 +32,8 @@ impl MigratorTrait for Migrator {
             Box::new(m20260719_000000_share_email_protocol::Migration),
             Box::new(m20260719_000001_share_policy_presentation_jti::Migration),
             Box::new(m20260719_000002_policy_status_freshness::Migration),
+            Box::new(m20261016_000000_epoch_seq_unique::Migration),
+            Box::new(m20261016_000001_space_frozen::Migration),
         ]
     }
 }
@@ -1,16 +1,19 @@
 use crate::encryption::ColumnEncryption;
 use crate::encryption_network::NetworkId;
 use crate::hash::Hash;
-use crate::models::did_resolution::did_resolution_timeout;
 use crate::policy_capability::sql_caveat;
-use crate::types::{Ability, Caveats, Facts, Resource};
+use crate::resolver::DidResolver;
+use crate::types::{Ability, Caveats, Facts, KvCaveats, Resource};
 use crate::util::DelegationMode;
 use crate::{events::Delegation, models::*, relationships::*, util};
 use sea_orm::{entity::prelude::*, sea_query::OnConflict, ConnectionTrait};
 use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use tinycloud_auth::{
-    authorization::TinyCloudDelegation, identity::did_principal_matches, ssi::dids::AnyDidMethod,
+    authorization::TinyCloudDelegation,
+    cacaos::siwe_cacao::SiweCacao,
+    identity::did_principal_matches,
+    ssi::ucan::{TimeInvalid, Ucan},
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -146,54 +149,217 @@ pub enum DelegationError {
     /// code from `sql-constrained-statement-caveat.md` containment.
     #[error("child-caveats-not-subset-of-parent: {0}")]
     CaveatsNotContained(String),
+    #[error("Delegation chain is deeper than the maximum of {0}")]
+    ChainTooDeep(u32),
+    #[error("Delegation lifetime exceeds the maximum of {0} seconds")]
+    LifetimeTooLong(i64),
+    #[error(transparent)]
+    InvalidKvCaveats(#[from] crate::types::KvCaveatError),
+}
+
+/// Bounds on the delegations a node accepts. Unset bounds are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DelegationLimits {
+    /// Longest chain, counting the delegation itself, that may be registered.
+    pub max_depth: Option<u32>,
+    /// Longest span from `issued_at` (or registration, when the delegation
+    /// carries no issuance time) to `expiry`. Delegations without an expiry
+    /// are rejected while this is set.
+    pub max_ttl: Option<time::Duration>,
+    /// How far outside its validity period a delegation is still accepted,
+    /// to allow for issuers whose clocks are slightly off.
+    pub clock_skew: time::Duration,
+    /// Most capabilities one delegation may grant.
+    pub max_capabilities: Option<usize>,
+    /// Most capabilities one invocation may invoke.
+    pub max_invocation_capabilities: Option<usize>,
 }
 
+/// Register a delegation whose signature and time have already been checked
+/// by [`verify_all`].
 pub(crate) async fn process<C: ConnectionTrait>(
     db: &C,
     delegation: Delegation,
     encryption: Option<&ColumnEncryption>,
+    limits: &DelegationLimits,
 ) -> Result<Hash, Error> {
     let (d, ser) = (delegation.0, delegation.1);
-    verify(&d.delegation).await?;
 
-    validate(db, &d).await?;
+    check_lifetime(&d, limits)?;
+    validate(db, &d, limits).await?;
 
     save(db, d, ser, encryption).await
 }
 
-// verify signatures and time
-async fn verify(delegation: &TinyCloudDelegation) -> Result<(), Error> {
-    match delegation {
-        TinyCloudDelegation::Ucan(ref ucan) => {
-            tokio::time::timeout(
-                did_resolution_timeout(),
-                // TODO go back to static DID_METHODS
-                ucan.verify_signature(&AnyDidMethod::default()),
-            )
+/// Verify a delegation and check it against its persisted parents without
+/// saving it.
+pub(crate) async fn verify_and_authorize<C: ConnectionTrait>(
+    db: &C,
+    delegation: &util::DelegationInfo,
+    resolver: &DidResolver,
+    limits: &DelegationLimits,
+) -> Result<(), Error> {
+    delegation
+        .delegation
+        .verify(resolver, limits.clock_skew)
+        .await?;
+    authorize(db, delegation, limits).await
+}
+
+/// Check a delegation whose signature and time have already been checked by
+/// [`verify_all`] against its persisted parents, without saving it.
+pub(crate) async fn authorize<C: ConnectionTrait>(
+    db: &C,
+    delegation: &util::DelegationInfo,
+    limits: &DelegationLimits,
+) -> Result<(), Error> {
+    check_lifetime(delegation, limits)?;
+    validate(db, delegation, limits).await
+}
+
+/// A delegation format whose signature and validity period can be checked.
+///
+/// Accepting delegations signed some other way means implementing this for
+/// their format, and adding it to [`TinyCloudDelegation`]'s dispatch below.
+#[async_trait::async_trait]
+pub trait VerifiableDelegation: Sync {
+    /// Check the signature, resolving the issuer with `resolver`, and that
+    /// the delegation is valid now, or within `clock_skew` of now.
+    async fn verify(
+        &self,
+        resolver: &DidResolver,
+        clock_skew: time::Duration,
+    ) -> Result<(), DelegationError>;
+}
+
+/// Verify a batch of delegations concurrently, failing if any one fails.
+///
+/// Verification may resolve DIDs over the network but reads nothing else,
+/// so a batch need not wait on each delegation in turn.
+pub(crate) async fn verify_all<'a, D: VerifiableDelegation + 'a>(
+    delegations: impl IntoIterator<Item = &'a D>,
+    resolver: &DidResolver,
+    clock_skew: time::Duration,
+) -> Result<(), DelegationError> {
+    futures::future::try_join_all(
+        delegations
+            .into_iter()
+            .map(|d| d.verify(resolver, clock_skew)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl VerifiableDelegation for TinyCloudDelegation {
+    async fn verify(
+        &self,
+        resolver: &DidResolver,
+        clock_skew: time::Duration,
+    ) -> Result<(), DelegationError> {
+        let delegation: &dyn VerifiableDelegation = match self {
+            TinyCloudDelegation::Ucan(ucan) => ucan.as_ref(),
+            TinyCloudDelegation::Cacao(cacao) => cacao.as_ref(),
+        };
+        delegation.verify(resolver, clock_skew).await
+    }
+}
+
+#[async_trait::async_trait]
+impl VerifiableDelegation for Ucan {
+    async fn verify(
+        &self,
+        resolver: &DidResolver,
+        clock_skew: time::Duration,
+    ) -> Result<(), DelegationError> {
+        tokio::time::timeout(resolver.timeout(), self.verify_signature(resolver))
             .await
             .map_err(|_| DelegationError::InvalidSignature)?
             .map_err(|_| DelegationError::InvalidSignature)?;
-            ucan.payload()
-                .validate_time(None)
-                .map_err(|_| DelegationError::InvalidTime)?;
-        }
-        TinyCloudDelegation::Cacao(ref cacao) => {
-            cacao
-                .verify()
-                .await
-                .map_err(|_| DelegationError::InvalidSignature)?;
-            if !cacao.payload().valid_now() {
-                return Err(DelegationError::InvalidTime.into());
-            }
+        let payload = self.payload();
+        payload
+            .validate_time(None)
+            .or_else(|invalid| {
+                // retry at the edge of the skew window nearest the validity period
+                let now = OffsetDateTime::now_utc();
+                let adjusted = match invalid {
+                    TimeInvalid::TooEarly => now + clock_skew,
+                    TimeInvalid::TooLate => now - clock_skew,
+                };
+                payload.validate_time(Some(adjusted.unix_timestamp_nanos() as f64 / 1e9))
+            })
+            .map_err(|_| DelegationError::InvalidTime)
+    }
+}
+
+#[async_trait::async_trait]
+impl VerifiableDelegation for SiweCacao {
+    async fn verify(
+        &self,
+        _resolver: &DidResolver,
+        clock_skew: time::Duration,
+    ) -> Result<(), DelegationError> {
+        SiweCacao::verify(self)
+            .await
+            .map_err(|_| DelegationError::InvalidSignature)?;
+        if !self
+            .payload()
+            .valid_at_with_tolerance(&OffsetDateTime::now_utc(), clock_skew)
+        {
+            return Err(DelegationError::InvalidTime);
         }
+        Ok(())
+    }
+}
+
+fn check_lifetime(
+    delegation: &util::DelegationInfo,
+    limits: &DelegationLimits,
+) -> Result<(), DelegationError> {
+    let Some(max_ttl) = limits.max_ttl else {
+        return Ok(());
     };
-    Ok(())
+    let start = delegation.issued_at.unwrap_or_else(OffsetDateTime::now_utc);
+    match delegation.expiry {
+        Some(expiry) if expiry - start <= max_ttl => Ok(()),
+        _ => Err(DelegationError::LifetimeTooLong(max_ttl.whole_seconds())),
+    }
+}
+
+/// Whether registering a delegation citing `parents` would make a chain of
+/// more than `max_depth` delegations. Walks `parent_delegations` one
+/// generation at a time, so at most `max_depth` queries are made.
+async fn exceeds_depth<C: ConnectionTrait>(
+    db: &C,
+    parents: Vec<Hash>,
+    max_depth: u32,
+) -> Result<bool, DbErr> {
+    let mut generation = parents;
+    // the delegation being registered is the first link
+    let mut depth = 1;
+    while !generation.is_empty() {
+        depth += 1;
+        if depth > max_depth {
+            return Ok(true);
+        }
+        generation = parent_delegations::Entity::find()
+            .filter(parent_delegations::Column::Child.is_in(generation))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| row.parent)
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .collect();
+    }
+    Ok(false)
 }
 
 // verify parenthood and authorization
 async fn validate<C: ConnectionTrait>(
     db: &C,
     delegation: &util::DelegationInfo,
+    limits: &DelegationLimits,
 ) -> Result<(), Error> {
     // get caps which rely on delegated caps
     let dependant_caps: Vec<_> = delegation
@@ -226,6 +392,12 @@ async fn validate<C: ConnectionTrait>(
                 return Err(DelegationError::MissingParents.into());
             }
 
+            if let Some(max_depth) = limits.max_depth {
+                if exceeds_depth(db, all_parents.iter().map(|p| p.id).collect(), max_depth).await? {
+                    return Err(DelegationError::ChainTooDeep(max_depth).into());
+                }
+            }
+
             // A revoked grant cannot authorize new descendants. Check both
             // the directly cited parents and their transitive ancestors on
             // every registration so revocation takes effect immediately.
@@ -350,6 +522,24 @@ async fn validate<C: ConnectionTrait>(
 /// shapes) and otherwise requires structural equality so a child cannot
 /// silently drop or replace caveats the parent carried.
 fn caveats_contain_child(parent: &Caveats, child: &Caveats) -> Result<(), String> {
+    // a child of a parent with kv caveats must carry caveats at least as
+    // strict as one of the parent's alternatives
+    let parent_kv = KvCaveats::from_caveats(parent).map_err(|e| e.to_string())?;
+    if !parent_kv.is_empty() {
+        let child_kv = KvCaveats::from_caveats(child).map_err(|e| e.to_string())?;
+        if child_kv.is_empty() {
+            return Err("containment-caveat-required".to_string());
+        }
+        return if child_kv
+            .iter()
+            .all(|c| parent_kv.iter().any(|p| p.contains(c)))
+        {
+            Ok(())
+        } else {
+            Err("child-caveats-not-subset-of-parent".to_string())
+        };
+    }
+
     let parent_sql = extract_sql_caveat(parent);
     let child_sql = extract_sql_caveat(child);
 
@@ -410,7 +600,7 @@ fn parent_is_terminal(p: &Model) -> bool {
 
 async fn ensure_parent_active<C: ConnectionTrait>(db: &C, parent_id: &Hash) -> Result<(), Error> {
     if revocation::is_revoked(db, parent_id).await? {
-        return Err(DelegationError::ParentRevoked(parent_id.to_cid(0x55).to_string()).into());
+        return Err(DelegationError::ParentRevoked(parent_id.to_event_cid().to_string()).into());
     }
     let revoked_ancestor = revocation::first_revoked_ancestor(db, parent_id)
         .await
@@ -423,7 +613,7 @@ async fn ensure_parent_active<C: ConnectionTrait>(db: &C, parent_id: &Hash) -> R
     if let Some(ancestor_cid) = revoked_ancestor {
         return Err(DelegationError::AncestorRevoked {
             ancestor_cid,
-            parent_cid: parent_id.to_cid(0x55).to_string(),
+            parent_cid: parent_id.to_event_cid().to_string(),
         }
         .into());
     }
@@ -456,6 +646,12 @@ async fn save<C: ConnectionTrait>(
     serialization: Vec<u8>,
     encryption: Option<&ColumnEncryption>,
 ) -> Result<Hash, Error> {
+    // kv caveats are enforced when the puts they limit are invoked, so one
+    // which could never be checked is refused up front
+    for capability in &delegation.capabilities {
+        KvCaveats::from_caveats(&capability.caveats).map_err(DelegationError::from)?;
+    }
+
     save_actors(&[&delegation.delegator, &delegation.delegate], db).await?;
 
     // Hash is always computed on plaintext (before encryption)
@@ -618,4 +814,230 @@ mod tests {
             Error::InvalidDelegation(DelegationError::ParentRevoked(_))
         ));
     }
+
+    fn ed25519_key() -> (tinycloud_auth::ssi::jwk::JWK, String) {
+        use tinycloud_auth::{resolver::DID_METHODS, ssi::jwk};
+
+        let mut key = jwk::JWK::generate_ed25519().unwrap();
+        key.algorithm = Some(jwk::Algorithm::EdDSA);
+        let did = DID_METHODS.generate(&key, "key").unwrap().to_string();
+        (key, did)
+    }
+
+    /// A UCAN issued by `issuer`, expiring at `expiration` (in seconds since
+    /// the epoch) and signed by `signer`.
+    fn ucan(
+        issuer: &str,
+        signer: &tinycloud_auth::ssi::jwk::JWK,
+        expiration: f64,
+    ) -> TinyCloudDelegation {
+        use tinycloud_auth::{
+            ssi::{
+                claims::jwt::NumericDate,
+                dids::{DIDBuf, DIDURLBuf},
+                ucan::Payload,
+            },
+            ucan_capabilities_object::Capabilities,
+        };
+
+        let fragment = issuer.rsplit_once(':').unwrap().1;
+        let ucan = Payload {
+            issuer: format!("{issuer}#{fragment}").parse::<DIDURLBuf>().unwrap(),
+            audience: "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"
+                .parse::<DIDBuf>()
+                .unwrap(),
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(expiration).unwrap(),
+            nonce: Some("verify".to_string()),
+            facts: None,
+            proof: vec![],
+            attenuation: Capabilities::<serde_json::Value>::new(),
+        }
+        .sign(tinycloud_auth::ssi::jwk::Algorithm::EdDSA, signer)
+        .unwrap();
+        TinyCloudDelegation::Ucan(Box::new(ucan))
+    }
+
+    /// A SIWE CACAO expiring at `expiration` and signed by a fixed Ethereum
+    /// key, with `tamper` applied to its payload after signing.
+    fn cacao(
+        expiration: OffsetDateTime,
+        tamper: impl FnOnce(&mut tinycloud_auth::cacaos::siwe_cacao::Payload),
+    ) -> TinyCloudDelegation {
+        use k256::ecdsa::SigningKey;
+        use sha3::{Digest, Keccak256};
+        use tinycloud_auth::cacaos::{
+            siwe::{Message, Version},
+            siwe_cacao::{Header, Payload},
+        };
+
+        let key = SigningKey::from_bytes(&[0x33u8; 32].into()).unwrap();
+        let public = key.verifying_key().to_encoded_point(false);
+        let message = Message {
+            scheme: None,
+            domain: "example.com".parse().unwrap(),
+            address: Keccak256::digest(&public.as_bytes()[1..])[12..]
+                .try_into()
+                .unwrap(),
+            statement: None,
+            uri: "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"
+                .parse()
+                .unwrap(),
+            version: Version::V1,
+            chain_id: 1,
+            nonce: "verify1234".to_string(),
+            issued_at: (OffsetDateTime::now_utc() - time::Duration::minutes(1)).into(),
+            expiration_time: Some(expiration.into()),
+            not_before: None,
+            request_id: None,
+            resources: vec![],
+        };
+        let (signature, recovery) = key
+            .sign_prehash_recoverable(&message.eip191_hash().unwrap())
+            .unwrap();
+        let mut bytes = [0u8; 65];
+        bytes[..64].copy_from_slice(signature.to_bytes().as_ref());
+        bytes[64] = u8::from(recovery) + 27;
+
+        let mut payload = Payload::from(message);
+        tamper(&mut payload);
+        TinyCloudDelegation::Cacao(Box::new(SiweCacao::new(payload, bytes.into(), Header)))
+    }
+
+    #[tokio::test]
+    async fn ucan_delegations_verify_their_signature_and_lifetime() {
+        let resolver = DidResolver::default();
+        let (key, did) = ed25519_key();
+        let (other, _) = ed25519_key();
+
+        // test delegations expire in 2100
+        ucan(&did, &key, 4_102_444_800.0)
+            .verify(&resolver, time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(matches!(
+            ucan(&did, &other, 4_102_444_800.0)
+                .verify(&resolver, time::Duration::ZERO)
+                .await,
+            Err(DelegationError::InvalidSignature)
+        ));
+        assert!(matches!(
+            ucan(&did, &key, 1_000_000_000.0)
+                .verify(&resolver, time::Duration::ZERO)
+                .await,
+            Err(DelegationError::InvalidTime)
+        ));
+    }
+
+    #[tokio::test]
+    async fn cacao_delegations_verify_their_signature_and_lifetime() {
+        let resolver = DidResolver::default();
+        let hour = time::Duration::hours(1);
+        let expiration = OffsetDateTime::now_utc() + hour;
+        cacao(expiration, |_| ())
+            .verify(&resolver, time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(matches!(
+            cacao(expiration, |p| p.nonce = "tampered1234".to_string())
+                .verify(&resolver, time::Duration::ZERO)
+                .await,
+            Err(DelegationError::InvalidSignature)
+        ));
+        assert!(matches!(
+            cacao(expiration, |p| {
+                p.iss = "did:pkh:solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:6Mq7QmbHkHTdcEpSNgRyLPVu1KGpNWFtvc1fwK5KxF9u"
+                    .parse()
+                    .unwrap()
+            })
+            .verify(&resolver, time::Duration::ZERO)
+            .await,
+            Err(DelegationError::InvalidSignature)
+        ));
+        assert!(matches!(
+            cacao(expiration - 2 * hour, |_| ())
+                .verify(&resolver, time::Duration::ZERO)
+                .await,
+            Err(DelegationError::InvalidTime)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delegations_within_the_clock_skew_are_accepted() {
+        let resolver = DidResolver::default();
+        let skew = time::Duration::seconds(60);
+        let now = OffsetDateTime::now_utc();
+        let (key, did) = ed25519_key();
+
+        // expired 30 seconds ago
+        let recent = now - time::Duration::seconds(30);
+        let ucan_recent = ucan(&did, &key, recent.unix_timestamp() as f64);
+        assert!(matches!(
+            ucan_recent.verify(&resolver, time::Duration::ZERO).await,
+            Err(DelegationError::InvalidTime)
+        ));
+        ucan_recent.verify(&resolver, skew).await.unwrap();
+        let cacao_recent = cacao(recent, |_| ());
+        assert!(matches!(
+            cacao_recent.verify(&resolver, time::Duration::ZERO).await,
+            Err(DelegationError::InvalidTime)
+        ));
+        cacao_recent.verify(&resolver, skew).await.unwrap();
+
+        // expired two minutes ago
+        let stale = now - time::Duration::seconds(120);
+        assert!(matches!(
+            ucan(&did, &key, stale.unix_timestamp() as f64)
+                .verify(&resolver, skew)
+                .await,
+            Err(DelegationError::InvalidTime)
+        ));
+        assert!(matches!(
+            cacao(stale, |_| ()).verify(&resolver, skew).await,
+            Err(DelegationError::InvalidTime)
+        ));
+    }
+
+    /// Verifies after `delay`, as a delegation whose issuer's DID must be
+    /// resolved over the network would.
+    struct SlowDelegation {
+        delay: std::time::Duration,
+        valid: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl VerifiableDelegation for SlowDelegation {
+        async fn verify(&self, _: &DidResolver, _: time::Duration) -> Result<(), DelegationError> {
+            tokio::time::sleep(self.delay).await;
+            self.valid
+                .then_some(())
+                .ok_or(DelegationError::InvalidSignature)
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_verify_concurrently() {
+        let resolver = DidResolver::default();
+        let delay = std::time::Duration::from_millis(100);
+        let batch = |invalid: Option<usize>| {
+            (0..8)
+                .map(|i| SlowDelegation {
+                    delay,
+                    valid: invalid != Some(i),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let started = std::time::Instant::now();
+        verify_all(&batch(None), &resolver, time::Duration::ZERO)
+            .await
+            .unwrap();
+        // verified one after another, the batch would take eight delays
+        assert!(started.elapsed() < delay * 4);
+
+        assert!(matches!(
+            verify_all(&batch(Some(5)), &resolver, time::Duration::ZERO).await,
+            Err(DelegationError::InvalidSignature)
+        ));
+    }
 }
@@ -6,9 +6,9 @@ use super::super::{
     util,
 };
 use crate::encryption::ColumnEncryption;
-use crate::models::did_resolution::did_resolution_timeout;
 use crate::policy_capability::sql_caveat;
-use crate::types::{Caveats, Facts, Resource, SpaceIdWrap};
+use crate::resolver::DidResolver;
+use crate::types::{Caveats, Facts, KvAbility, KvCaveats, Resource, SpaceIdWrap};
 use crate::write_hooks::{hook_delivery_id, subscription_matches_event};
 use crate::{hash::Hash, types::Ability};
 use sea_orm::{
@@ -19,7 +19,6 @@ use std::collections::HashMap;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tinycloud_auth::{
     authorization::TinyCloudInvocation, identity::did_principal_matches, resource::Path,
-    ssi::dids::AnyDidMethod,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -102,36 +101,48 @@ pub enum InvocationError {
     /// invocation boundary).
     #[error("invocation-caveats-not-subset-of-chain: {0}")]
     CaveatsNotContained(String),
+    /// A kv put breaks the kv caveats of every delegated ability which
+    /// would authorize it.
+    #[error(transparent)]
+    KvCaveatViolated(#[from] crate::types::KvCaveatError),
 }
 
 pub(crate) async fn process<C: ConnectionTrait>(
     db: &C,
     invocation: Invocation,
     ops: Vec<VersionedOperation>,
+    resolver: &DidResolver,
     encryption: Option<&ColumnEncryption>,
 ) -> Result<Hash, Error> {
     let (i, serialized) = (invocation.0, invocation.1);
-    verify_invocation(&i.invocation).await?;
-
     let now = OffsetDateTime::now_utc();
+    verify_invocation_at(&i.invocation, resolver, now).await?;
     validate(db, &i, Some(now)).await?;
 
     save(db, i, Some(now), serialized, ops, encryption).await
 }
 
-pub async fn verify_invocation(invocation: &TinyCloudInvocation) -> Result<(), Error> {
-    tokio::time::timeout(
-        did_resolution_timeout(),
-        invocation
-            // TODO go back to static DID_METHODS
-            .verify_signature(&AnyDidMethod::default()),
-    )
-    .await
-    .map_err(|_| InvocationError::InvalidSignature)?
-    .map_err(|_| InvocationError::InvalidSignature)?;
+/// Check an invocation's signature, resolving its invoker with `resolver`,
+/// and that it is valid now.
+pub async fn verify_invocation(
+    invocation: &TinyCloudInvocation,
+    resolver: &DidResolver,
+) -> Result<(), Error> {
+    verify_invocation_at(invocation, resolver, OffsetDateTime::now_utc()).await
+}
+
+async fn verify_invocation_at(
+    invocation: &TinyCloudInvocation,
+    resolver: &DidResolver,
+    at: OffsetDateTime,
+) -> Result<(), Error> {
+    tokio::time::timeout(resolver.timeout(), invocation.verify_signature(resolver))
+        .await
+        .map_err(|_| InvocationError::InvalidSignature)?
+        .map_err(|_| InvocationError::InvalidSignature)?;
     invocation
         .payload()
-        .validate_time(None)
+        .validate_time(Some(at.unix_timestamp_nanos() as f64 / 1e9))
         .map_err(|_| InvocationError::InvalidTime)?;
     Ok(())
 }
@@ -141,9 +152,10 @@ pub async fn verify_invocation(invocation: &TinyCloudInvocation) -> Result<(), E
 pub async fn verify_and_authorize<C: ConnectionTrait>(
     db: &C,
     invocation: &util::InvocationInfo,
+    resolver: &DidResolver,
     now: OffsetDateTime,
 ) -> Result<(), Error> {
-    verify_invocation(&invocation.invocation).await?;
+    verify_invocation_at(&invocation.invocation, resolver, now).await?;
     validate(db, invocation, Some(now)).await
 }
 
@@ -201,9 +213,10 @@ async fn validate<C: ConnectionTrait>(
             // (revocation.md §2.3).
             for (p, _) in &parents {
                 if revocation::is_revoked(db, &p.id).await? {
-                    return Err(
-                        InvocationError::DelegationRevoked(p.id.to_cid(0x55).to_string()).into(),
-                    );
+                    return Err(InvocationError::DelegationRevoked(
+                        p.id.to_event_cid().to_string(),
+                    )
+                    .into());
                 }
                 let revoked_ancestor = revocation::first_revoked_ancestor(db, &p.id)
                     .await
@@ -216,7 +229,7 @@ async fn validate<C: ConnectionTrait>(
                 if let Some(ancestor_cid) = revoked_ancestor {
                     return Err(InvocationError::DelegationAncestorRevoked {
                         ancestor_cid,
-                        invoked_cid: p.id.to_cid(0x55).to_string(),
+                        invoked_cid: p.id.to_event_cid().to_string(),
                     }
                     .into());
                 }
@@ -340,7 +353,11 @@ fn caveats_contain_child(parent: &Caveats, child: &Caveats) -> Result<(), String
         (Some(p), Some(c)) => sql_caveat::contains(&p, &c).map_err(|e| e.as_str().to_string()),
         (Some(_), None) => Err("containment-caveat-required".to_string()),
         (None, _) => {
+            // kv caveats constrain the content of a put rather than the
+            // capability invoked, and are checked against the put itself
+            // (see `check_kv_caveats`)
             if parent.0 == child.0
+                || KvCaveats::from_caveats(parent).is_ok_and(|kv| !kv.is_empty())
                 || (parent.0.is_empty() && child.0.is_empty())
                 || parent.0.is_empty()
             {
@@ -368,6 +385,75 @@ fn extract_sql_caveat(
     None
 }
 
+/// A kv put, as checked against the kv caveats of the abilities which
+/// authorize it.
+#[derive(Debug, Clone)]
+pub struct KvPut<'a> {
+    pub space: &'a tinycloud_auth::resource::SpaceId,
+    pub path: &'a Path,
+    pub size: u64,
+    pub content_type: Option<&'a str>,
+}
+
+/// Check kv puts against the kv caveats of the delegated abilities which
+/// authorize them: each put must satisfy the caveats of at least one.
+/// Puts with no delegated ability to check are left to [`validate`].
+pub(crate) async fn check_kv_caveats<C: ConnectionTrait>(
+    db: &C,
+    invocation: &util::InvocationInfo,
+    puts: &[KvPut<'_>],
+) -> Result<(), Error> {
+    if puts.is_empty() || invocation.parents.is_empty() {
+        return Ok(());
+    }
+    let abilities = abilities::Entity::find()
+        .filter(
+            abilities::Column::Delegation.is_in(invocation.parents.iter().map(|c| Hash::from(*c))),
+        )
+        .all(db)
+        .await?;
+    for put in puts {
+        let Some(capability) = invocation.capabilities.iter().find(|c| {
+            c.ability.tinycloud() == Some(KvAbility::Put.into())
+                && c.resource.tinycloud_resource().is_some_and(|r| {
+                    r.service().as_str() == "kv"
+                        && r.space() == put.space
+                        && r.path() == Some(put.path)
+                })
+        }) else {
+            continue;
+        };
+        if is_root_authority(capability, &invocation.invoker) {
+            continue;
+        }
+        let mut violation = None;
+        for ability in abilities.iter().filter(|a| {
+            capability.resource.extends(&a.resource)
+                && crate::policy_capability::ability_matches(
+                    a.ability.as_ref().as_ref(),
+                    capability.ability.as_ref().as_ref(),
+                )
+        }) {
+            let checked = KvCaveats::from_caveats(&ability.caveats)
+                .map_err(InvocationError::from)?
+                .iter()
+                .map(|kv| kv.check(put.path.as_str(), put.size, put.content_type))
+                .reduce(Result::or);
+            match checked {
+                None | Some(Ok(())) => {
+                    violation = None;
+                    break;
+                }
+                Some(Err(e)) => violation = Some(e),
+            }
+        }
+        if let Some(violation) = violation {
+            return Err(InvocationError::from(violation).into());
+        }
+    }
+    Ok(())
+}
+
 fn is_root_authority(cap: &util::Capability, invoker: &str) -> bool {
     if cap
         .resource
@@ -599,7 +685,7 @@ async fn enqueue_kv_webhook_deliveries<C: ConnectionTrait>(
 
         let event_index = event_indexes.entry(space.to_string()).or_insert(0);
         let current_index = *event_index;
-        let epoch_cid = epoch.to_cid(0x55).to_string();
+        let epoch_cid = epoch.to_block_cid().to_string();
         let event_id = format!("{epoch_cid}:{current_index}");
         let payload_json = serde_json::to_string(&KvWebhookPayload {
             event_type: "write".to_string(),
@@ -717,7 +803,7 @@ mod tests {
             revocation::first_revoked_ancestor(&db, &child_id)
                 .await
                 .unwrap(),
-            Some(parent_id.to_cid(0x55).to_string())
+            Some(parent_id.to_event_cid().to_string())
         );
     }
 
@@ -842,4 +928,73 @@ mod tests {
         caveats_contain_child(&parent, &child_with_constraints)
             .expect("narrowing on an unconstrained parent must be allowed");
     }
+
+    #[tokio::test]
+    async fn invocations_are_verified_with_the_configured_resolver() {
+        use crate::resolver::{ResolverOptions, WebResolverOptions};
+        use tinycloud_auth::{
+            ssi::{
+                claims::jwt::NumericDate,
+                dids::{DIDBuf, DIDURLBuf},
+                jwk::{Algorithm, JWK},
+                ucan::Payload,
+            },
+            ucan_capabilities_object::Capabilities,
+        };
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // the configured did:web client goes through this proxy, which no
+        // built-in resolver knows of
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let proxied = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let read = socket.read(&mut buf).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        });
+        let resolver = DidResolver::new(ResolverOptions {
+            web: Some(WebResolverOptions {
+                timeout: std::time::Duration::from_secs(5),
+                user_agent: None,
+                proxy: Some(proxy),
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut key = JWK::generate_ed25519().unwrap();
+        key.algorithm = Some(Algorithm::EdDSA);
+        let invocation: TinyCloudInvocation = Payload {
+            issuer: "did:web:example.invalid#key-1"
+                .parse::<DIDURLBuf>()
+                .unwrap(),
+            audience: "did:web:example.invalid".parse::<DIDBuf>().unwrap(),
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(4_102_444_800.0).unwrap(),
+            nonce: Some("resolver".to_string()),
+            facts: None,
+            proof: vec![],
+            attenuation: Capabilities::new(),
+        }
+        .sign(Algorithm::EdDSA, &key)
+        .unwrap();
+
+        assert!(verify_invocation(&invocation, &resolver).await.is_err());
+        assert!(proxied
+            .await
+            .unwrap()
+            .starts_with("CONNECT example.invalid:443"));
+    }
 }
@@ -1,6 +1,6 @@
 use super::super::{events::Revocation, models::*, relationships::*};
 use crate::hash::{hash, Hash};
-use crate::models::did_resolution::did_resolution_timeout;
+use crate::resolver::DidResolver;
 use crate::types::Resource;
 use sea_orm::{entity::prelude::*, sea_query::OnConflict, ConnectionTrait, QuerySelect};
 use std::collections::HashSet;
@@ -8,7 +8,6 @@ use time::OffsetDateTime;
 use tinycloud_auth::{
     authorization::{Cid, TinyCloudRevocation},
     identity::did_principal_matches,
-    ssi::dids::AnyDidMethod,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -82,7 +81,7 @@ pub(crate) async fn first_revoked_ancestor<C: ConnectionTrait>(
 ) -> Result<Option<String>, ChainTraversalError> {
     for ancestor in ancestor_chain_ids(db, start).await?.into_iter().skip(1) {
         if is_revoked(db, &ancestor).await? {
-            return Ok(Some(ancestor.to_cid(0x55).to_string()));
+            return Ok(Some(ancestor.to_event_cid().to_string()));
         }
     }
     Ok(None)
@@ -189,7 +188,7 @@ pub(crate) async fn control_proof_decision<C: ConnectionTrait>(
         .filter(abilities::Column::Ability.eq(requested_action))
         .all(db)
         .await?;
-    let target_resource = format!("urn:cid:{}", target.to_cid(0x55));
+    let target_resource = format!("urn:cid:{}", target.to_event_cid());
     let has_control_ability = control_abilities
         .iter()
         .any(|ability| match &ability.resource {
@@ -234,6 +233,7 @@ pub enum RevocationError {
 pub(crate) async fn process<C: ConnectionTrait>(
     db: &C,
     revocation: Revocation,
+    resolver: &DidResolver,
 ) -> Result<Hash, Error> {
     let (r, serialization) = (revocation.0, revocation.1);
 
@@ -253,13 +253,10 @@ pub(crate) async fn process<C: ConnectionTrait>(
             };
         }
         TinyCloudRevocation::Ucan(u) => {
-            tokio::time::timeout(
-                did_resolution_timeout(),
-                u.verify_signature(&AnyDidMethod::default()),
-            )
-            .await
-            .map_err(|_| RevocationError::InvalidSignature)?
-            .map_err(|_| RevocationError::InvalidSignature)?;
+            tokio::time::timeout(resolver.timeout(), u.verify_signature(resolver))
+                .await
+                .map_err(|_| RevocationError::InvalidSignature)?
+                .map_err(|_| RevocationError::InvalidSignature)?;
             u.payload()
                 .validate_time(None)
                 .map_err(|_| RevocationError::InvalidTime)?;
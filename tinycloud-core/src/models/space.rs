@@ -8,6 +8,9 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false, unique)]
     pub id: SpaceIdWrap,
+    /// Whether the space is read-only: it takes no new delegations or
+    /// writes until an operator unfreezes it.
+    pub frozen: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,47 @@
+use crate::util::Capability;
+use async_trait::async_trait;
+use std::{error::Error as StdError, fmt::Debug};
+use tinycloud_auth::resource::SpaceId;
+
+/// An invocation about to be committed, as seen by a [`Policy`]: one
+/// [`AuthContext`] per space its capabilities are invoked on.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthContext<'a> {
+    /// The DID URL that signed the invocation.
+    pub invoker: &'a str,
+    pub space: &'a SpaceId,
+    /// The capabilities invoked on `space`.
+    pub capabilities: &'a [&'a Capability],
+}
+
+/// Operator rules applied to invocations on top of capability checks, such as
+/// refusing writes during maintenance.
+///
+/// Consulted inside the invocation's transaction, after its capabilities are
+/// validated against its delegations and before anything is committed, so a
+/// denial leaves no trace of the invocation.
+#[async_trait]
+pub trait Policy: Debug + Send + Sync {
+    async fn authorize(&self, ctx: &AuthContext<'_>) -> Result<(), PolicyError>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    #[error("denied by policy: {0}")]
+    Denied(String),
+    /// The policy could not be consulted, as opposed to denying the invocation.
+    #[error("policy check failed: {0}")]
+    Failed(#[from] Box<dyn StdError + Send + Sync>),
+}
+
+/// The policy of a node with none configured, allowing every invocation its
+/// capabilities allow.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+#[async_trait]
+impl Policy for AllowAll {
+    async fn authorize(&self, _: &AuthContext<'_>) -> Result<(), PolicyError> {
+        Ok(())
+    }
+}
@@ -11,11 +11,11 @@ use libp2p::identity::ed25519::Keypair;
 use sha2::{Digest, Sha256};
 use sha3::Keccak256;
 use tinycloud_auth::{
+    codec,
     ipld_core::cid::Cid,
     multihash_codetable::{Code, MultihashDigest},
 };
 
-const RAW_CODEC: u64 = 0x55;
 const ROOT_DOMAIN: &[u8] = b"xyz.tinycloud.policy/enforcement-delegation/v1\0";
 const EIP191_SUITE: &str = "eip191-secp256k1-sha256-jcs-v1";
 const ED25519_SUITE: &str = "eddsa-ed25519-sha256-jcs-v1";
@@ -118,7 +118,7 @@ fn canonical_cid(artifact: &PolicyDelegation) -> Result<Vec<u8>, super::Authorit
 
 fn cid_for_artifact(artifact: &PolicyDelegation) -> Result<String, super::AuthorityError> {
     let hash = Code::Blake3_256.digest(&canonical_cid(artifact)?);
-    Ok(Cid::new_v1(RAW_CODEC, hash).to_string())
+    Ok(Cid::new_v1(codec::RAW, hash).to_string())
 }
 
 fn signing_bytes(unsigned: &[u8]) -> Vec<u8> {
@@ -129,7 +129,7 @@ fn signing_bytes(unsigned: &[u8]) -> Vec<u8> {
 fn verify_artifact(artifact: &PolicyDelegation) -> Result<(), super::AuthorityError> {
     let unsigned = canonical_unsigned(artifact)?;
     let hash = Code::Blake3_256.digest(&canonical_cid(artifact)?);
-    let expected_cid = Cid::new_v1(RAW_CODEC, hash);
+    let expected_cid = Cid::new_v1(codec::RAW, hash);
     if artifact.delegation_cid != expected_cid.to_string() {
         return Err(super::AuthorityError::SchemaInvalid);
     }
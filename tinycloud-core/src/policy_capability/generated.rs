@@ -1,26 +1,31 @@
 // @generated by scripts/gen-capabilities.mjs — DO NOT EDIT.
-// Source: capabilities.json (registry version 1, sha256 daecd38d908d05d622684b580501dc38c5945b0499ce7eb3a18d77663b93c73a).
+// Source: capabilities.json (registry version 1, sha256 cc50956d2e6ce23b80f4207b1f0b4dcc8570bdc47102a73735ef4d43d2efbaf2).
 //
 // Canonical single source of truth for TinyCloud capability action URNs (TC-112).
 // Regenerate with: node scripts/gen-capabilities.mjs
 
 pub const REGISTRY_VERSION: u32 = 1;
 pub const REGISTRY_SOURCE_SHA256: &str =
-    "daecd38d908d05d622684b580501dc38c5945b0499ce7eb3a18d77663b93c73a";
+    "cc50956d2e6ce23b80f4207b1f0b4dcc8570bdc47102a73735ef4d43d2efbaf2";
 
 /// GitHub repository the registry lives in (TC-121; js-sdk sync anchor).
 pub const REGISTRY_SOURCE_REPO: &str = "TinyCloudLabs/tinycloud-node";
 /// Git commit the artifact was generated from. Authoritative when generated
 /// in CI (GITHUB_SHA); approximate when generated locally, where it names
 /// the parent of the commit that will contain this artifact.
-pub const REGISTRY_SOURCE_GIT_SHA: &str = "390253aca30628f2ac2be28e64d8e3830da07aaa";
+pub const REGISTRY_SOURCE_GIT_SHA: &str = "e97ea9f325320d54a6f4339e58ef63d7dc509874";
 
 /// Every action URN accepted at the policy boundary for `service`
 /// (active, deprecated-alias, and reserved), sorted. `None` if the
 /// service is unknown to the registry.
 pub fn accepted_actions(service: &str) -> Option<&'static [&'static str]> {
     match service {
-        "tinycloud.capabilities" => Some(&["tinycloud.capabilities/read"]),
+        "tinycloud.capabilities" => Some(&[
+            "tinycloud.capabilities/effective",
+            "tinycloud.capabilities/head",
+            "tinycloud.capabilities/read",
+            "tinycloud.capabilities/usage",
+        ]),
         "tinycloud.delegation" => {
             Some(&["tinycloud.delegation/list", "tinycloud.delegation/status"])
         }
@@ -45,12 +50,16 @@ pub fn accepted_actions(service: &str) -> Option<&'static [&'static str]> {
             "tinycloud.hooks/unregister",
         ]),
         "tinycloud.kv" => Some(&[
+            "tinycloud.kv/copy",
             "tinycloud.kv/del",
             "tinycloud.kv/delete",
+            "tinycloud.kv/exists",
             "tinycloud.kv/get",
             "tinycloud.kv/list",
             "tinycloud.kv/metadata",
+            "tinycloud.kv/move",
             "tinycloud.kv/put",
+            "tinycloud.kv/set-metadata",
         ]),
         "tinycloud.space" => Some(&[
             "tinycloud.space/create",
@@ -99,6 +108,9 @@ pub fn implied_actions(action: &str) -> &'static [&'static str] {
             "tinycloud.duckdb/read",
             "tinycloud.duckdb/write",
         ],
+        "tinycloud.kv/get" => &["tinycloud.kv/exists"],
+        "tinycloud.kv/metadata" => &["tinycloud.kv/exists"],
+        "tinycloud.kv/put" => &["tinycloud.kv/set-metadata"],
         "tinycloud.sql/*" => &[
             "tinycloud.sql/admin",
             "tinycloud.sql/read",
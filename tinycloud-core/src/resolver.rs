@@ -0,0 +1,507 @@
+use crate::models::did_resolution::did_resolution_timeout;
+use serde_json::json;
+use sha3::{Digest, Keccak256};
+use std::{fmt, time::Duration};
+use tinycloud_auth::ssi::dids::{resolution, AnyDidMethod, DIDBuf, DIDKey, DIDResolver, DID};
+
+/// The ENS registry, at the same address on every network it is deployed to.
+const ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+/// `resolver(bytes32)` on the ENS registry.
+const ENS_RESOLVER_SELECTOR: &str = "0178b8bf";
+/// `addr(bytes32)` on an ENS resolver.
+const ENS_ADDR_SELECTOR: &str = "3b3b57de";
+/// `name(bytes32)` on an ENS reverse resolver.
+const ENS_NAME_SELECTOR: &str = "691f3431";
+
+/// Where DIDs whose documents live off the node are resolved from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolverOptions {
+    /// Ethereum JSON-RPC endpoint `did:ens` names are looked up with. The
+    /// built-in resolver is used if unset.
+    pub ens_rpc_url: Option<String>,
+    /// How `did:web` documents are fetched. The built-in resolver is used if
+    /// unset.
+    pub web: Option<WebResolverOptions>,
+    /// Longest a DID resolution may take. Defaults to
+    /// `TINYCLOUD_DID_RESOLUTION_TIMEOUT_MS`, or 3 seconds.
+    pub timeout: Option<Duration>,
+}
+
+/// HTTP client settings for fetching `did:web` documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebResolverOptions {
+    pub timeout: Duration,
+    pub user_agent: Option<String>,
+    /// Proxy every request is sent through, e.g. for nodes behind a firewall.
+    pub proxy: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolverError {
+    #[error("invalid resolver HTTP client settings: {0}")]
+    Client(#[from] reqwest::Error),
+}
+
+/// Resolves DIDs for signature verification, using configured endpoints for
+/// the methods which need the network and the built-in resolvers otherwise.
+#[derive(Clone)]
+pub struct DidResolver {
+    methods: AnyDidMethod,
+    http: reqwest::Client,
+    ens_rpc_url: Option<String>,
+    web: bool,
+    timeout: Option<Duration>,
+}
+
+impl Default for DidResolver {
+    fn default() -> Self {
+        Self {
+            methods: AnyDidMethod::default(),
+            http: reqwest::Client::new(),
+            ens_rpc_url: None,
+            web: false,
+            timeout: None,
+        }
+    }
+}
+
+impl fmt::Debug for DidResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DidResolver")
+            .field("ens_rpc_url", &self.ens_rpc_url)
+            .field("web", &self.web)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl DidResolver {
+    pub fn new(options: ResolverOptions) -> Result<Self, ResolverError> {
+        let mut http = reqwest::Client::builder();
+        if let Some(web) = &options.web {
+            http = http.timeout(web.timeout);
+            if let Some(user_agent) = &web.user_agent {
+                http = http.user_agent(user_agent);
+            }
+            if let Some(proxy) = &web.proxy {
+                http = http.proxy(reqwest::Proxy::all(proxy)?);
+            }
+        }
+        Ok(Self {
+            methods: AnyDidMethod::default(),
+            http: http.build()?,
+            ens_rpc_url: options.ens_rpc_url,
+            web: options.web.is_some(),
+            timeout: options.timeout,
+        })
+    }
+
+    /// Longest a DID resolution may take.
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or_else(did_resolution_timeout)
+    }
+
+    /// The document of `did:web:<id>`, fetched with the configured client.
+    async fn resolve_web(&self, id: &str) -> Result<Vec<u8>, resolution::Error> {
+        let response = self
+            .http
+            .get(web_document_url(id)?)
+            .send()
+            .await
+            .map_err(resolution::Error::internal)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(resolution::Error::NotFound);
+        }
+        Ok(response
+            .error_for_status()
+            .map_err(resolution::Error::internal)?
+            .bytes()
+            .await
+            .map_err(resolution::Error::internal)?
+            .to_vec())
+    }
+
+    /// The Ethereum address `name` resolves to, looked up at `rpc_url`.
+    async fn resolve_ens(&self, rpc_url: &str, name: &str) -> Result<String, resolution::Error> {
+        let node = hex::encode(namehash(name));
+        let resolver = self
+            .eth_call(rpc_url, ENS_REGISTRY, ENS_RESOLVER_SELECTOR, &node)
+            .await?;
+        let resolver = address_word(&resolver).ok_or(resolution::Error::NotFound)?;
+        let address = self
+            .eth_call(rpc_url, &resolver, ENS_ADDR_SELECTOR, &node)
+            .await?;
+        address_word(&address).ok_or(resolution::Error::NotFound)
+    }
+
+    /// The primary ENS name of the Ethereum `address`, if it has one which
+    /// also resolves back to it. Always `None` without an ENS RPC endpoint.
+    pub async fn ens_name(&self, address: &str) -> Result<Option<String>, resolution::Error> {
+        let Some(rpc_url) = &self.ens_rpc_url else {
+            return Ok(None);
+        };
+        let address = address.trim_start_matches("0x").to_lowercase();
+        let node = hex::encode(namehash(&format!("{address}.addr.reverse")));
+        let resolver = self
+            .eth_call(rpc_url, ENS_REGISTRY, ENS_RESOLVER_SELECTOR, &node)
+            .await?;
+        let Some(resolver) = address_word(&resolver) else {
+            return Ok(None);
+        };
+        let name = self
+            .eth_call(rpc_url, &resolver, ENS_NAME_SELECTOR, &node)
+            .await?;
+        let Some(name) = string_word(&name) else {
+            return Ok(None);
+        };
+        // anyone may claim any name in their reverse record, so only trust it
+        // if the name points back at the address
+        match self.resolve_ens(rpc_url, &name).await {
+            Ok(forward)
+                if forward
+                    .trim_start_matches("0x")
+                    .eq_ignore_ascii_case(&address) =>
+            {
+                Ok(Some(name))
+            }
+            Ok(_) | Err(resolution::Error::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn eth_call(
+        &self,
+        rpc_url: &str,
+        to: &str,
+        selector: &str,
+        node: &str,
+    ) -> Result<String, resolution::Error> {
+        let response: serde_json::Value = self
+            .http
+            .post(rpc_url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_call",
+                "params": [{"to": to, "data": format!("0x{selector}{node}")}, "latest"],
+            }))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(resolution::Error::internal)?
+            .json()
+            .await
+            .map_err(resolution::Error::internal)?;
+        response["result"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| resolution::Error::internal(format!("ENS lookup failed: {response}")))
+    }
+}
+
+impl DIDResolver for DidResolver {
+    async fn resolve_representation<'a>(
+        &'a self,
+        did: &'a DID,
+        options: resolution::Options,
+    ) -> Result<resolution::Output<Vec<u8>>, resolution::Error> {
+        match (did.method_name(), &self.ens_rpc_url) {
+            ("key", _) => DIDKey.resolve_representation(did, options).await,
+            ("ens", Some(rpc_url)) => {
+                let name = did.method_specific_id();
+                let address = self.resolve_ens(rpc_url, name).await?;
+                // the name stands for the account it resolves to, so the
+                // account's document is served with the name as its subject
+                let account: DIDBuf = format!("did:pkh:eip155:1:{address}")
+                    .parse()
+                    .map_err(resolution::Error::internal)?;
+                let mut output = self
+                    .methods
+                    .resolve_representation(&account, options)
+                    .await?;
+                output.document = String::from_utf8_lossy(&output.document)
+                    .replace(account.as_str(), did.as_str())
+                    .into_bytes();
+                Ok(output)
+            }
+            ("web", _) if self.web => Ok(resolution::Output {
+                document: self.resolve_web(did.method_specific_id()).await?,
+                document_metadata: Default::default(),
+                metadata: resolution::Metadata::from_content_type(Some(
+                    "application/did+json".to_string(),
+                )),
+            }),
+            _ => self.methods.resolve_representation(did, options).await,
+        }
+    }
+}
+
+/// Resolves `did:key` DIDs locally, and every other DID with the wrapped
+/// resolver.
+///
+/// A `did:key` document is derived from the DID itself, so session keys,
+/// which sign most invocations, never wait on a general purpose resolver.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalDidKey<R>(pub R);
+
+impl<R: DIDResolver> DIDResolver for LocalDidKey<R> {
+    async fn resolve_representation<'a>(
+        &'a self,
+        did: &'a DID,
+        options: resolution::Options,
+    ) -> Result<resolution::Output<Vec<u8>>, resolution::Error> {
+        if did.method_name() == "key" {
+            DIDKey.resolve_representation(did, options).await
+        } else {
+            self.0.resolve_representation(did, options).await
+        }
+    }
+}
+
+/// The URL of the document of `did:web:<id>`: colons separate path segments,
+/// and a port's colon is percent-encoded.
+fn web_document_url(id: &str) -> Result<String, resolution::Error> {
+    let mut segments = id.split(':');
+    let host = segments
+        .next()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| resolution::Error::InvalidMethodSpecificId(id.to_string()))?
+        .replace("%3A", ":")
+        .replace("%3a", ":");
+    let path = segments.collect::<Vec<_>>();
+    Ok(if path.is_empty() {
+        format!("https://{host}/.well-known/did.json")
+    } else {
+        format!("https://{host}/{}/did.json", path.join("/"))
+    })
+}
+
+/// The ENS namehash of `name`.
+fn namehash(name: &str) -> [u8; 32] {
+    name.rsplit('.')
+        .filter(|label| !label.is_empty())
+        .fold([0u8; 32], |node, label| {
+            let mut hasher = Keccak256::new();
+            hasher.update(node);
+            hasher.update(Keccak256::digest(label.as_bytes()));
+            hasher.finalize().into()
+        })
+}
+
+/// The address in an ABI-encoded `address` return value, or `None` if it is
+/// the zero address, which ENS returns for names it has no record of.
+fn address_word(word: &str) -> Option<String> {
+    let word = word.strip_prefix("0x").unwrap_or(word);
+    let address = word.get(word.len().checked_sub(40)?..)?;
+    (!address.bytes().all(|b| b == b'0')).then(|| format!("0x{address}"))
+}
+
+/// The string in an ABI-encoded `string` return value, or `None` if it is
+/// empty or malformed.
+fn string_word(word: &str) -> Option<String> {
+    let bytes = hex::decode(word.strip_prefix("0x").unwrap_or(word)).ok()?;
+    let word_at = |offset: usize| -> Option<usize> {
+        let word = bytes.get(offset..offset.checked_add(32)?)?;
+        word[..24]
+            .iter()
+            .all(|b| *b == 0)
+            .then(|| u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+    };
+    let offset = word_at(0)?;
+    let len = word_at(offset)?;
+    let start = offset.checked_add(32)?;
+    let string = String::from_utf8(bytes.get(start..start.checked_add(len)?)?.to_vec()).ok()?;
+    (!string.is_empty()).then_some(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const RESOLVER: &str = "0x4976fb03c32e5b8cfe2b6ccb31c09ba78ebaba41";
+    const ADDRESS: &str = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045";
+    const NAME: &str = "vitalik.eth";
+
+    /// Answer JSON-RPC `eth_call`s as an Ethereum node holding forward and
+    /// reverse records for one ENS name would, returning the number of calls answered.
+    async fn ens_rpc(listener: tokio::net::TcpListener) -> usize {
+        let mut calls = 0;
+        while let Ok(Ok((mut socket, _))) =
+            tokio::time::timeout(Duration::from_secs(1), listener.accept()).await
+        {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // a request is small enough to arrive before the body is checked
+            while !String::from_utf8_lossy(&request).contains("\"latest\"]") {
+                let read = socket.read(&mut buf).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+            }
+            let request = String::from_utf8_lossy(&request);
+            let result = if request.contains(&format!("0x{ENS_RESOLVER_SELECTOR}")) {
+                format!("0x{:0>64}", RESOLVER.trim_start_matches("0x"))
+            } else if request.contains(&format!("0x{ENS_NAME_SELECTOR}")) {
+                format!(
+                    "0x{:0>64x}{:0>64x}{:0<64}",
+                    32,
+                    NAME.len(),
+                    hex::encode(NAME)
+                )
+            } else {
+                format!("0x{:0>64}", ADDRESS.trim_start_matches("0x"))
+            };
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": result,
+            })
+            .to_string();
+            socket
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            calls += 1;
+        }
+        calls
+    }
+
+    #[tokio::test]
+    async fn ens_names_are_resolved_with_the_configured_rpc() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let rpc = tokio::spawn(ens_rpc(listener));
+
+        let resolver = DidResolver::new(ResolverOptions {
+            ens_rpc_url: Some(rpc_url),
+            ..Default::default()
+        })
+        .unwrap();
+        let did: DIDBuf = "did:ens:vitalik.eth".parse().unwrap();
+        let document = String::from_utf8(
+            resolver
+                .resolve_representation(&did, Default::default())
+                .await
+                .unwrap()
+                .document,
+        )
+        .unwrap();
+
+        assert_eq!(rpc.await.unwrap(), 2);
+        assert!(document.contains("did:ens:vitalik.eth"));
+        assert!(document
+            .to_lowercase()
+            .contains(&format!("eip155:1:{ADDRESS}")));
+    }
+
+    #[tokio::test]
+    async fn primary_ens_names_must_resolve_back_to_the_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let rpc = tokio::spawn(ens_rpc(listener));
+        let resolver = DidResolver::new(ResolverOptions {
+            ens_rpc_url: Some(rpc_url),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            resolver
+                .ens_name(&ADDRESS.trim_start_matches("0x").to_uppercase())
+                .await
+                .unwrap(),
+            Some(NAME.to_string())
+        );
+        // the reverse record claims a name which resolves elsewhere
+        assert_eq!(
+            resolver
+                .ens_name("0x0000000000000000000000000000000000000001")
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(rpc.await.unwrap(), 8);
+
+        assert_eq!(
+            DidResolver::default().ens_name(ADDRESS).await.unwrap(),
+            None
+        );
+    }
+
+    /// Fails any test in which it is asked to resolve a DID.
+    struct Unreachable;
+
+    impl DIDResolver for Unreachable {
+        async fn resolve_representation<'a>(
+            &'a self,
+            did: &'a DID,
+            _: resolution::Options,
+        ) -> Result<resolution::Output<Vec<u8>>, resolution::Error> {
+            panic!("{did} should have been resolved locally")
+        }
+    }
+
+    #[tokio::test]
+    async fn did_key_signatures_verify_without_the_wrapped_resolver() {
+        use tinycloud_auth::{
+            resolver::DID_METHODS,
+            ssi::{
+                claims::jwt::NumericDate,
+                dids::DIDURLBuf,
+                jwk::{Algorithm, JWK},
+                ucan::Payload,
+            },
+            ucan_capabilities_object::Capabilities,
+        };
+
+        let mut key = JWK::generate_ed25519().unwrap();
+        key.algorithm = Some(Algorithm::EdDSA);
+        let did = DID_METHODS.generate(&key, "key").unwrap().to_string();
+        let fragment = did.rsplit_once(':').unwrap().1;
+        let ucan = Payload {
+            issuer: format!("{did}#{fragment}").parse::<DIDURLBuf>().unwrap(),
+            audience: did.parse::<DIDBuf>().unwrap(),
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(4_102_444_800.0).unwrap(),
+            nonce: Some("local".to_string()),
+            facts: None,
+            proof: vec![],
+            attenuation: Capabilities::<serde_json::Value>::new(),
+        }
+        .sign(Algorithm::EdDSA, &key)
+        .unwrap();
+
+        ucan.verify_signature(&LocalDidKey(Unreachable))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn namehash_matches_ens() {
+        assert_eq!(namehash(""), [0u8; 32]);
+        assert_eq!(
+            hex::encode(namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+    }
+
+    #[test]
+    fn web_document_urls() {
+        assert_eq!(
+            web_document_url("example.com").unwrap(),
+            "https://example.com/.well-known/did.json"
+        );
+        assert_eq!(
+            web_document_url("example.com%3A8443:users:alice").unwrap(),
+            "https://example.com:8443/users/alice/did.json"
+        );
+    }
+}
@@ -13,6 +13,7 @@ use sha2::{Digest, Sha256};
 use std::{path::Path, sync::Arc};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tinycloud_auth::{
+    codec,
     ipld_core::cid::Cid,
     multihash_codetable::{Code, MultihashDigest},
 };
@@ -414,7 +415,7 @@ fn load_record(value: Value) -> Result<LoadedMaterial, AuthorityProviderError> {
         return Err(AuthorityProviderError::Artifact);
     }
     let policy_state = jcs::canonicalize(&policy);
-    if Cid::new_v1(0x55, Code::Sha2_256.digest(&policy_state)).to_string()
+    if Cid::new_v1(codec::RAW, Code::Sha2_256.digest(&policy_state)).to_string()
         != share_policy_cid.as_str()
     {
         return Err(AuthorityProviderError::Artifact);
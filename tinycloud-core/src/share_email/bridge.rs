@@ -27,6 +27,7 @@ use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tinycloud_auth::{
+    codec,
     ipld_core::cid::Cid,
     multihash_codetable::{Code, MultihashDigest},
 };
@@ -781,7 +782,7 @@ impl DatabaseAuthorityBridge117 {
         if policy_state_bytes != bundle.policy_state {
             return Err(PortError::Denied);
         }
-        if Cid::new_v1(0x55, Code::Sha2_256.digest(&policy_state_bytes)).to_string()
+        if Cid::new_v1(codec::RAW, Code::Sha2_256.digest(&policy_state_bytes)).to_string()
             != policy.as_str()
             || signed_policy
                 .artifact()
@@ -940,7 +941,7 @@ impl DatabaseAuthorityBridge117 {
         if policy_state_bytes != bundle.policy_state {
             return Err(PortError::Denied);
         }
-        if Cid::new_v1(0x55, Code::Sha2_256.digest(&policy_state_bytes)).to_string()
+        if Cid::new_v1(codec::RAW, Code::Sha2_256.digest(&policy_state_bytes)).to_string()
             != scope.policy_cid.as_str()
             || signed_policy
                 .artifact()
@@ -499,6 +499,32 @@ pub fn create_authorizer(
     }
 }
 
+/// Authorizer for the actor's own `ATTACH` of a vetted sibling database.
+///
+/// Allows attaching exactly `filename`, the server-resolved snapshot path,
+/// and nothing else; [`create_authorizer`] keeps denying every `ATTACH`.
+pub fn attach_authorizer(filename: String) -> impl FnMut(AuthContext<'_>) -> Authorization {
+    move |ctx: AuthContext<'_>| match ctx.action {
+        AuthAction::Attach { filename: attached } if attached == filename => Authorization::Allow,
+        _ => Authorization::Deny,
+    }
+}
+
+/// Wrap `authorizer` to deny everything touching the attached databases
+/// named in `hidden`: siblings attached by an earlier invocation, which the
+/// invoker of this statement holds no read capability on.
+pub fn hide_databases(
+    hidden: Vec<String>,
+    mut authorizer: impl FnMut(AuthContext<'_>) -> Authorization,
+) -> impl FnMut(AuthContext<'_>) -> Authorization {
+    move |ctx: AuthContext<'_>| match ctx.database_name {
+        Some(name) if hidden.iter().any(|alias| alias.eq_ignore_ascii_case(name)) => {
+            Authorization::Deny
+        }
+        _ => authorizer(ctx),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
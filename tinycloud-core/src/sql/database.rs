@@ -1,5 +1,8 @@
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use dashmap::DashMap;
 use rusqlite::hooks::{AuthContext, Authorization};
@@ -16,31 +19,56 @@ use super::{
 const MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024; // 10MB
 const MAX_BOUNDED_QUERY_ROWS: usize = 1_000;
 const MAX_BOUNDED_QUERY_BYTES: usize = 4 * 1024 * 1024;
-const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300); // 5 min
+pub const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300); // 5 min
+pub const CHANNEL_CAPACITY: usize = 32;
+
+static NEXT_ACTOR_ID: AtomicU64 = AtomicU64::new(0);
 
 enum DbMessage {
     Execute {
         request: SqlRequest,
         caveats: Option<SqlCaveats>,
         ability: String,
+        hidden: Vec<String>,
         response_tx: oneshot::Sender<Result<SqlExecutionResult, SqlError>>,
     },
     Export {
         response_tx: oneshot::Sender<Result<Vec<u8>, SqlError>>,
     },
+    Attach {
+        alias: String,
+        snapshot: Vec<u8>,
+        replace: bool,
+        response_tx: oneshot::Sender<Result<SqlExecutionResult, SqlError>>,
+    },
 }
 
 #[derive(Clone)]
 pub struct DatabaseHandle {
     tx: mpsc::Sender<DbMessage>,
+    // distinguishes a respawned actor from the one it replaced
+    id: u64,
 }
 
 impl DatabaseHandle {
+    /// Whether the actor has stopped, e.g. after going idle. A closed handle
+    /// never accepts another request.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Run `request`, denying it any access to the attached siblings whose
+    /// aliases are in `hidden`.
     pub async fn execute(
         &self,
         request: SqlRequest,
         caveats: Option<SqlCaveats>,
         ability: String,
+        hidden: Vec<String>,
     ) -> Result<SqlExecutionResult, SqlError> {
         let (response_tx, response_rx) = oneshot::channel();
         self.tx
@@ -48,6 +76,32 @@ impl DatabaseHandle {
                 request,
                 caveats,
                 ability,
+                hidden,
+                response_tx,
+            })
+            .await
+            .map_err(|_| SqlError::Internal("Database actor not available".to_string()))?;
+        response_rx
+            .await
+            .map_err(|_| SqlError::Internal("Database actor dropped response".to_string()))?
+    }
+
+    /// Attach `snapshot`, the exported contents of a sibling database, read
+    /// only under `alias`, replacing any snapshot already attached under it
+    /// if `replace` is set. The attachment lasts until the actor shuts down,
+    /// but each request names the attachments it may not see.
+    pub async fn attach(
+        &self,
+        alias: String,
+        snapshot: Vec<u8>,
+        replace: bool,
+    ) -> Result<SqlExecutionResult, SqlError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .send(DbMessage::Attach {
+                alias,
+                snapshot,
+                replace,
                 response_tx,
             })
             .await
@@ -74,9 +128,12 @@ pub fn spawn_actor(
     db_name: String,
     base_path: String,
     memory_threshold: u64,
+    idle_timeout: std::time::Duration,
+    channel_capacity: usize,
     databases: Arc<DashMap<(String, String), DatabaseHandle>>,
 ) -> DatabaseHandle {
-    let (tx, mut rx) = mpsc::channel::<DbMessage>(32);
+    let (tx, mut rx) = mpsc::channel::<DbMessage>(channel_capacity);
+    let id = NEXT_ACTOR_ID.fetch_add(1, Ordering::Relaxed);
 
     tokio::task::spawn_blocking(move || {
         let rt = tokio::runtime::Handle::current();
@@ -91,14 +148,20 @@ pub fn spawn_actor(
             StorageMode::InMemory
         };
         let mut conn = storage::open_connection(&mode).expect("Failed to open database");
+        let mut attachments = Attachments::default();
 
         loop {
             // Block on receiving with timeout
             let msg =
-                match rt.block_on(async { tokio::time::timeout(IDLE_TIMEOUT, rx.recv()).await }) {
+                match rt.block_on(async { tokio::time::timeout(idle_timeout, rx.recv()).await }) {
                     Ok(Some(msg)) => msg,
                     Ok(None) => break, // Channel closed
-                    Err(_) => break,   // Idle timeout
+                    Err(_) => {
+                        // Idle timeout: refuse new requests so callers respawn,
+                        // but still serve any that were queued in the meantime
+                        rx.close();
+                        continue;
+                    }
                 };
 
             match msg {
@@ -106,9 +169,10 @@ pub fn spawn_actor(
                     request,
                     caveats,
                     ability,
+                    hidden,
                     response_tx,
                 } => {
-                    let result = handle_message(&conn, &request, &caveats, &ability);
+                    let result = handle_message(&conn, &request, &caveats, &ability, &hidden);
 
                     // Post-write promotion check
                     if result.is_ok() && matches!(mode, StorageMode::InMemory) {
@@ -117,6 +181,9 @@ pub fn spawn_actor(
                                 match storage::promote_to_file(&conn, &file_path) {
                                     Ok(new_conn) => {
                                         conn = new_conn;
+                                        if let Err(e) = attachments.reattach(&conn) {
+                                            tracing::error!(space=%space_id, db=%db_name, error=%e, "Failed to re-attach databases after promotion");
+                                        }
                                         mode = StorageMode::File(file_path.clone());
                                         tracing::info!(space=%space_id, db=%db_name, "Promoted database to file storage");
                                     }
@@ -134,14 +201,110 @@ pub fn spawn_actor(
                     let result = handle_export(&conn, &mode, &file_path);
                     let _ = response_tx.send(result);
                 }
+                DbMessage::Attach {
+                    alias,
+                    snapshot,
+                    replace,
+                    response_tx,
+                } => {
+                    let result = attachments.attach(&conn, alias, &snapshot, replace);
+                    let _ = response_tx.send(result);
+                }
             }
         }
 
-        databases.remove(&(space_id.clone(), db_name.clone()));
+        // a replacement actor may already be registered under this key
+        databases.remove_if(&(space_id.clone(), db_name.clone()), |_, handle| {
+            handle.id == id
+        });
         tracing::debug!(space=%space_id, db=%db_name, "Database actor shutting down");
     });
 
-    DatabaseHandle { tx }
+    DatabaseHandle { tx, id }
+}
+
+/// Sibling databases attached to an actor's connection, each a read-only
+/// snapshot file in a temporary directory removed when the actor exits.
+#[derive(Default)]
+struct Attachments {
+    dir: Option<tempfile::TempDir>,
+    // alias -> `file:` URI of the snapshot
+    attached: Vec<(String, String)>,
+}
+
+impl Attachments {
+    fn attach(
+        &mut self,
+        conn: &rusqlite::Connection,
+        alias: String,
+        snapshot: &[u8],
+        replace: bool,
+    ) -> Result<SqlExecutionResult, SqlError> {
+        if let Some(index) = self
+            .attached
+            .iter()
+            .position(|(attached, _)| *attached == alias)
+        {
+            if !replace {
+                return Err(SqlError::InvalidStatement(format!(
+                    "A database is already attached as {alias}"
+                )));
+            }
+            conn.execute_batch(&format!("DETACH DATABASE \"{alias}\""))
+                .map_err(|e| SqlError::Sqlite(e.to_string()))?;
+            self.attached.remove(index);
+        }
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => self
+                .dir
+                .insert(tempfile::tempdir().map_err(|e| SqlError::Internal(e.to_string()))?),
+        };
+        let path = dir.path().join(format!("{alias}.db"));
+        std::fs::write(&path, snapshot).map_err(|e| SqlError::Internal(e.to_string()))?;
+        // an export of a WAL database keeps the WAL flag, which a read-only
+        // open cannot honour without its shared-memory file
+        rusqlite::Connection::open(&path)
+            .and_then(|snapshot| snapshot.pragma_update(None, "journal_mode", "delete"))
+            .map_err(|e| SqlError::Internal(e.to_string()))?;
+        let uri = snapshot_uri(&path);
+        attach_snapshot(conn, &alias, &uri)?;
+        self.attached.push((alias, uri));
+        Ok(SqlExecutionResult {
+            response: SqlResponse::Execute(ExecuteResponse {
+                changes: 0,
+                last_insert_row_id: None,
+            }),
+            write_targets: Vec::new(),
+        })
+    }
+
+    fn reattach(&self, conn: &rusqlite::Connection) -> Result<(), SqlError> {
+        for (alias, uri) in &self.attached {
+            attach_snapshot(conn, alias, uri)?;
+        }
+        Ok(())
+    }
+}
+
+fn snapshot_uri(path: &std::path::Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{path}?mode=ro")
+}
+
+fn attach_snapshot(conn: &rusqlite::Connection, alias: &str, uri: &str) -> Result<(), SqlError> {
+    // `alias` is a vetted identifier and `uri` is built by the server
+    conn.authorizer(Some(authorizer::attach_authorizer(uri.to_string())));
+    let result = conn.execute_batch(&format!(
+        "ATTACH DATABASE '{}' AS \"{alias}\"",
+        uri.replace('\'', "''")
+    ));
+    conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+    result.map_err(|e| SqlError::Sqlite(e.to_string()))
 }
 
 fn handle_export(
@@ -181,10 +344,17 @@ fn handle_message(
     request: &SqlRequest,
     caveats: &Option<SqlCaveats>,
     ability: &str,
+    hidden: &[String],
 ) -> Result<SqlExecutionResult, SqlError> {
     // TC-119: confers-admin gate (registry-aware). `sql/admin` and `sql/*`
     // (implies admin) pass; identical to the prior `admin | *` match.
     let is_admin = crate::policy_capability::ability_matches(ability, "tinycloud.sql/admin");
+    let create_authorizer = || {
+        authorizer::hide_databases(
+            hidden.to_vec(),
+            authorizer::create_authorizer(caveats.clone(), ability.to_string(), is_admin),
+        )
+    };
 
     match request {
         SqlRequest::Query {
@@ -195,8 +365,7 @@ fn handle_message(
         } => {
             let parsed = parser::validate_sql(sql, caveats, ability)?;
 
-            let auth =
-                authorizer::create_authorizer(caveats.clone(), ability.to_string(), is_admin);
+            let auth = create_authorizer();
             conn.authorizer(Some(auth));
 
             let result = execute_query(conn, sql, params, *max_rows, *max_bytes);
@@ -219,11 +388,7 @@ fn handle_message(
                 for stmt_sql in schema_stmts {
                     let parsed = parser::validate_sql(stmt_sql, caveats, ability)?;
                     write_targets.extend(parsed.write_targets);
-                    let auth = authorizer::create_authorizer(
-                        caveats.clone(),
-                        ability.to_string(),
-                        is_admin,
-                    );
+                    let auth = create_authorizer();
                     conn.authorizer(Some(auth));
                     conn.execute_batch(stmt_sql)
                         .map_err(|e| SqlError::SchemaError(e.to_string()))?;
@@ -232,8 +397,7 @@ fn handle_message(
             }
 
             let parsed = parser::validate_sql(sql, caveats, ability)?;
-            let auth =
-                authorizer::create_authorizer(caveats.clone(), ability.to_string(), is_admin);
+            let auth = create_authorizer();
             conn.authorizer(Some(auth));
 
             let result = execute_statement(conn, sql, params, is_insert_statement(&parsed));
@@ -261,8 +425,7 @@ fn handle_message(
 
             let mut results = Vec::new();
             for (stmt, is_insert) in statements.iter().zip(insert_statements) {
-                let auth =
-                    authorizer::create_authorizer(caveats.clone(), ability.to_string(), is_admin);
+                let auth = create_authorizer();
                 conn.authorizer(Some(auth));
                 let result = execute_statement(conn, &stmt.sql, &stmt.params, is_insert);
                 conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
@@ -284,8 +447,7 @@ fn handle_message(
 
             let parsed = parser::validate_sql(&prepared.sql, caveats, ability)?;
 
-            let auth =
-                authorizer::create_authorizer(caveats.clone(), ability.to_string(), is_admin);
+            let auth = create_authorizer();
             conn.authorizer(Some(auth));
 
             let result = if is_query_statement(&parsed) {
@@ -516,6 +678,7 @@ mod tests {
             },
             &None,
             "tinycloud.sql/write",
+            &[],
         )
         .unwrap();
         let SqlResponse::Execute(insert) = insert.response else {
@@ -536,6 +699,7 @@ mod tests {
                 },
                 &None,
                 "tinycloud.sql/write",
+                &[],
             )
             .unwrap();
             let SqlResponse::Execute(response) = result.response else {
@@ -571,6 +735,7 @@ mod tests {
             },
             &None,
             "tinycloud.sql/write",
+            &[],
         )
         .unwrap();
         let SqlResponse::Batch(batch) = batch.response else {
@@ -594,6 +759,7 @@ mod tests {
             },
             &Some(caveats),
             "tinycloud.sql/write",
+            &[],
         )
         .unwrap();
         let SqlResponse::Execute(prepared) = prepared.response else {
@@ -624,6 +790,7 @@ mod tests {
             },
             &None,
             "tinycloud.sql/schema",
+            &[],
         )
         .expect_err("a prior DDL statement must not authorize a later CTAS source read");
         assert!(
@@ -106,6 +106,8 @@ pub fn validate_sql(
                 }
             }
             Statement::AttachDatabase { .. } => {
+                // the only permitted form is vetted by `parse_attach` and run
+                // by the database actor itself
                 return Err(SqlError::PermissionDenied(
                     "ATTACH is not allowed".to_string(),
                 ));
@@ -187,10 +189,91 @@ pub fn validate_sql(
     })
 }
 
+/// A vetted `ATTACH DATABASE '<name>' AS <alias>` naming another database of
+/// the same space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachTarget {
+    /// Name of the sibling database, as used in its `sql/<name>` resource.
+    pub database: String,
+    pub alias: String,
+}
+
+/// Recognise the one form of `ATTACH` that is allowed.
+///
+/// Returns `Ok(None)` for any other statement, which [`validate_sql`] then
+/// handles (and still rejects any other `ATTACH`). The file name must be the
+/// bare name of a database in the same space; the server resolves it to a
+/// path itself, so arbitrary files can never be attached.
+pub fn parse_attach(sql: &str, ability: &str) -> Result<Option<AttachTarget>, SqlError> {
+    if !is_attach_sql(sql) {
+        return Ok(None);
+    }
+    let statements = Parser::parse_sql(&SQLiteDialect {}, sql)
+        .map_err(|e| SqlError::ParseError(e.to_string()))?;
+    let [Statement::AttachDatabase {
+        schema_name,
+        database_file_name,
+        ..
+    }] = statements.as_slice()
+    else {
+        return Ok(None);
+    };
+    if !ability_matches(ability, "tinycloud.sql/admin") {
+        return Err(SqlError::PermissionDenied(
+            "ATTACH requires admin ability".to_string(),
+        ));
+    }
+    let database = match database_file_name {
+        Expr::Value(Value::SingleQuotedString(name)) if is_plain_name(name) => name.clone(),
+        _ => {
+            return Err(SqlError::PermissionDenied(
+                "ATTACH only accepts the name of another database in this space".to_string(),
+            ))
+        }
+    };
+    let alias = schema_name.value.clone();
+    if !is_plain_name(&alias)
+        || alias.contains('-')
+        || alias.starts_with(|c: char| c.is_ascii_digit())
+        || ["main", "temp"].contains(&alias.to_ascii_lowercase().as_str())
+    {
+        return Err(SqlError::InvalidStatement(format!(
+            "Invalid ATTACH alias: {alias}"
+        )));
+    }
+    Ok(Some(AttachTarget { database, alias }))
+}
+
+/// The database named by a lone `ATTACH DATABASE '<name>'` statement, before
+/// any of the checks of [`parse_attach`].
+pub fn attached_database(sql: &str) -> Option<String> {
+    if !is_attach_sql(sql) {
+        return None;
+    }
+    match Parser::parse_sql(&SQLiteDialect {}, sql).ok()?.as_slice() {
+        [Statement::AttachDatabase {
+            database_file_name: Expr::Value(Value::SingleQuotedString(name)),
+            ..
+        }] => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 pub fn is_pragma_sql(sql: &str) -> bool {
     first_sql_token(sql).as_deref() == Some("pragma")
 }
 
+pub fn is_attach_sql(sql: &str) -> bool {
+    first_sql_token(sql).as_deref() == Some("attach")
+}
+
 fn first_sql_token(sql: &str) -> Option<String> {
     let mut index = 0;
 
@@ -405,6 +488,40 @@ mod tests {
     use super::*;
     use crate::write_hooks::TouchedTables;
 
+    #[test]
+    fn only_admins_may_attach_sibling_databases_by_name() {
+        assert_eq!(
+            parse_attach("ATTACH DATABASE 'users' AS users", "tinycloud.sql/admin").unwrap(),
+            Some(AttachTarget {
+                database: "users".to_string(),
+                alias: "users".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_attach("SELECT 1", "tinycloud.sql/admin").unwrap(),
+            None
+        );
+        assert!(matches!(
+            parse_attach("ATTACH DATABASE 'users' AS users", "tinycloud.sql/write"),
+            Err(SqlError::PermissionDenied(_))
+        ));
+        for sql in [
+            "ATTACH DATABASE '/etc/passwd' AS p",
+            "ATTACH DATABASE '../other/main' AS p",
+            "ATTACH DATABASE 'file:users?mode=rw' AS p",
+            "ATTACH DATABASE 'users' AS main",
+        ] {
+            assert!(parse_attach(sql, "tinycloud.sql/admin").is_err(), "{sql}");
+        }
+        // any ATTACH that bypasses the vetted path is still rejected
+        assert!(validate_sql(
+            "ATTACH DATABASE 'users' AS users",
+            &None,
+            "tinycloud.sql/admin"
+        )
+        .is_err());
+    }
+
     #[test]
     fn update_write_targets_only_include_target_table() {
         let dialect = SQLiteDialect {};
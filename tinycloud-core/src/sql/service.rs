@@ -1,6 +1,8 @@
 use std::{
+    future::Future,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use dashmap::DashMap;
@@ -10,15 +12,21 @@ use crate::database_artifacts::{DatabaseArtifactError, DatabaseArtifactRepositor
 
 use super::{
     caveats::SqlCaveats,
-    database::{spawn_actor, DatabaseHandle},
+    database::{spawn_actor, DatabaseHandle, CHANNEL_CAPACITY, IDLE_TIMEOUT},
+    parser::{self, AttachTarget},
     types::*,
 };
 
 #[derive(Clone)]
 pub struct SqlService {
     databases: Arc<DashMap<(String, String), DatabaseHandle>>,
+    // (space, database) -> the siblings attached to it, restored whenever
+    // its actor is respawned
+    attachments: Arc<DashMap<(String, String), Vec<AttachTarget>>>,
     base_path: String,
     memory_threshold: u64,
+    idle_timeout: Duration,
+    channel_capacity: usize,
     artifact_repository: Arc<dyn DatabaseArtifactRepository>,
 }
 
@@ -30,12 +38,33 @@ impl SqlService {
     ) -> Self {
         Self {
             databases: Arc::new(DashMap::new()),
+            attachments: Arc::new(DashMap::new()),
             base_path,
             memory_threshold,
+            idle_timeout: IDLE_TIMEOUT,
+            channel_capacity: CHANNEL_CAPACITY,
             artifact_repository,
         }
     }
 
+    /// How long a database actor waits for a request before shutting down to
+    /// free its connection. The next request transparently respawns it.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// How many requests may queue for one database before callers wait.
+    /// Panics if zero.
+    pub fn with_channel_capacity(mut self, channel_capacity: usize) -> Self {
+        assert!(
+            channel_capacity > 0,
+            "SQL channel capacity must be non-zero"
+        );
+        self.channel_capacity = channel_capacity;
+        self
+    }
+
     pub async fn execute(
         &self,
         space: &SpaceId,
@@ -44,22 +73,65 @@ impl SqlService {
         caveats: Option<SqlCaveats>,
         ability: String,
     ) -> Result<SqlExecutionResult, SqlError> {
-        let key = (space.to_string(), db_name.to_string());
-        let mut handle = self.handle(space, db_name).await?;
-
-        let result = match handle
-            .execute(request.clone(), caveats.clone(), ability.clone())
+        self.execute_with_siblings(space, db_name, request, caveats, ability, &[])
             .await
+    }
+
+    /// [`execute`](Self::execute), also allowing `ATTACH` of the sibling
+    /// databases named in `readable`, which the caller has verified the
+    /// invoker holds `tinycloud.sql/read` on. Any other `ATTACH` is refused.
+    ///
+    /// Attachments outlive the invocation that made them, so every request
+    /// is checked again: siblings attached to `db_name` but not in `readable`
+    /// cannot be read or written by it.
+    pub async fn execute_with_siblings(
+        &self,
+        space: &SpaceId,
+        db_name: &str,
+        request: SqlRequest,
+        caveats: Option<SqlCaveats>,
+        ability: String,
+        readable: &[String],
+    ) -> Result<SqlExecutionResult, SqlError> {
+        if let SqlRequest::Execute {
+            sql, schema: None, ..
+        } = &request
         {
-            Err(SqlError::Internal(ref msg)) if msg.contains("Database actor not available") => {
-                // Actor is dead — remove stale entry and respawn
-                tracing::warn!(space=%space, db=%db_name, "Dead SQL actor detected, respawning");
-                self.databases.remove(&key);
-                handle = self.handle(space, db_name).await?;
-                handle.execute(request, caveats, ability).await
+            if let Some(target) = parser::parse_attach(sql, &ability)? {
+                if !readable.contains(&target.database) {
+                    return Err(SqlError::PermissionDenied(format!(
+                        "ATTACH requires tinycloud.sql/read on {}",
+                        target.database
+                    )));
+                }
+                return self.attach(space, db_name, target).await;
             }
-            other => other,
-        }?;
+        }
+
+        let key = (space.to_string(), db_name.to_string());
+        let hidden: Vec<String> = self
+            .attachments
+            .get(&key)
+            .map(|targets| {
+                targets
+                    .iter()
+                    .filter(|target| !readable.contains(&target.database))
+                    .map(|target| target.alias.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let handle = self.handle(space, db_name).await?;
+        let (handle, result) = self
+            .on_actor(space, db_name, handle, |handle| {
+                let (request, caveats, ability, hidden) = (
+                    request.clone(),
+                    caveats.clone(),
+                    ability.clone(),
+                    hidden.clone(),
+                );
+                async move { handle.execute(request, caveats, ability, hidden).await }
+            })
+            .await?;
 
         if !result.write_targets.is_empty() {
             let payload = match handle.export().await {
@@ -71,29 +143,121 @@ impl SqlService {
             };
             if let Err(e) = self
                 .artifact_repository
-                .save("sql", &space.to_string(), db_name, payload)
+                .save("sql", &space.to_string(), db_name, payload.clone())
                 .await
             {
                 let _ = self.discard_local_state(&key).await;
                 return Err(artifact_error_to_sql(e));
             }
+            self.refresh_attachments(space, db_name, &payload).await;
         }
 
         Ok(result)
     }
 
+    /// The databases of `space` attached to `db_name`, whose readers must
+    /// also hold `tinycloud.sql/read` on them to see them.
+    pub fn attached_databases(&self, space: &SpaceId, db_name: &str) -> Vec<String> {
+        self.attachments
+            .get(&(space.to_string(), db_name.to_string()))
+            .map(|targets| targets.iter().map(|t| t.database.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Attach a snapshot of another database of the same space, read only.
+    /// The snapshot is replaced after every write to that database, and the
+    /// attachment is restored when the actor is respawned.
+    async fn attach(
+        &self,
+        space: &SpaceId,
+        db_name: &str,
+        target: AttachTarget,
+    ) -> Result<SqlExecutionResult, SqlError> {
+        if target.database == db_name {
+            return Err(SqlError::InvalidStatement(
+                "A database cannot attach itself".to_string(),
+            ));
+        }
+        let snapshot = self.export(space, &target.database).await?;
+        let handle = self.handle(space, db_name).await?;
+        let (_, result) = self
+            .on_actor(space, db_name, handle, |handle| {
+                let (alias, snapshot) = (target.alias.clone(), snapshot.clone());
+                async move { handle.attach(alias, snapshot, false).await }
+            })
+            .await?;
+        self.attachments
+            .entry((space.to_string(), db_name.to_string()))
+            .or_default()
+            .push(target);
+        Ok(result)
+    }
+
+    /// Replace the snapshots of `db_name` attached to the live actors of its
+    /// siblings with `payload`, its contents after a write.
+    async fn refresh_attachments(&self, space: &SpaceId, db_name: &str, payload: &[u8]) {
+        let space = space.to_string();
+        let attached = self
+            .attachments
+            .iter()
+            .filter(|entry| entry.key().0 == space)
+            .flat_map(|entry| {
+                entry
+                    .value()
+                    .iter()
+                    .filter(|target| target.database == db_name)
+                    .map(|target| (entry.key().1.clone(), target.alias.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for (sibling, alias) in attached {
+            // an idle sibling takes a fresh snapshot when it is respawned
+            let Some(handle) = self
+                .databases
+                .get(&(space.clone(), sibling.clone()))
+                .map(|h| h.clone())
+            else {
+                continue;
+            };
+            if let Err(e) = handle.attach(alias, payload.to_vec(), true).await {
+                tracing::warn!(space=%space, db=%sibling, attached=%db_name, error=%e, "Failed to refresh an attached database");
+            }
+        }
+    }
+
+    /// Attach the siblings recorded for `db_name` to its newly spawned actor.
+    async fn restore_attachments(&self, space: &SpaceId, db_name: &str, handle: &DatabaseHandle) {
+        let key = (space.to_string(), db_name.to_string());
+        let targets = match self.attachments.get(&key) {
+            Some(targets) => targets.clone(),
+            None => return,
+        };
+        for target in targets {
+            let restored = match self.export(space, &target.database).await {
+                Ok(snapshot) => handle.attach(target.alias.clone(), snapshot, true).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = restored {
+                // e.g. the sibling was deleted; the alias is gone until it is
+                // attached again
+                tracing::warn!(space=%space, db=%db_name, attached=%target.database, error=%e, "Failed to restore an attached database");
+                if let Some(mut targets) = self.attachments.get_mut(&key) {
+                    targets.retain(|t| t.alias != target.alias);
+                }
+            }
+        }
+    }
+
     pub async fn export(&self, space: &SpaceId, db_name: &str) -> Result<Vec<u8>, SqlError> {
         let key = (space.to_string(), db_name.to_string());
 
         // If there's a live actor, route through it (handles both in-memory and file-backed)
         if let Some(handle) = self.databases.get(&key).map(|h| h.clone()) {
             match handle.export().await {
-                Err(SqlError::Internal(ref msg))
-                    if msg.contains("Database actor not available") =>
-                {
+                Err(e) if is_unavailable(&e) => {
                     // Actor is dead — remove stale entry and fall through to cold read
                     tracing::warn!(space=%space, db=%db_name, "Dead SQL actor detected during export, removing");
-                    self.databases.remove(&key);
+                    self.evict(&key, &handle);
                 }
                 other => return other,
             }
@@ -119,27 +283,73 @@ impl SqlService {
             .unwrap_or_else(|| "default".to_string())
     }
 
+    /// Send `request` to the actor behind `handle`, which was live when it
+    /// was looked up. If the actor has since shut down, as it may when it goes
+    /// idle in between, its handle is dropped and the request is sent once
+    /// more to a respawned actor. The handle which served it is returned.
+    async fn on_actor<T, F, Fut>(
+        &self,
+        space: &SpaceId,
+        db_name: &str,
+        handle: DatabaseHandle,
+        request: F,
+    ) -> Result<(DatabaseHandle, T), SqlError>
+    where
+        F: Fn(DatabaseHandle) -> Fut,
+        Fut: Future<Output = Result<T, SqlError>>,
+    {
+        match request(handle.clone()).await {
+            // the request was never delivered, so it is safe to send again
+            Err(e) if is_unavailable(&e) => {
+                tracing::warn!(space=%space, db=%db_name, "Dead SQL actor detected, respawning");
+                self.evict(&(space.to_string(), db_name.to_string()), &handle);
+                let handle = self.handle(space, db_name).await?;
+                let result = request(handle.clone()).await?;
+                Ok((handle, result))
+            }
+            result => Ok((handle, result?)),
+        }
+    }
+
+    /// Deregister `handle`, unless a respawned actor has already replaced it.
+    fn evict(&self, key: &(String, String), handle: &DatabaseHandle) {
+        self.databases
+            .remove_if(key, |_, stale| stale.id() == handle.id());
+    }
+
     async fn handle(&self, space: &SpaceId, db_name: &str) -> Result<DatabaseHandle, SqlError> {
         let key = (space.to_string(), db_name.to_string());
         if let Some(handle) = self.databases.get(&key).map(|h| h.clone()) {
-            return Ok(handle);
+            if !handle.is_closed() {
+                return Ok(handle);
+            }
+            // evicted while idle but not yet deregistered
+            self.evict(&key, &handle);
         }
 
         self.hydrate_cache(space, db_name).await?;
 
-        Ok(self
+        let mut spawned = false;
+        let handle = self
             .databases
             .entry(key)
             .or_insert_with(|| {
+                spawned = true;
                 spawn_actor(
                     space.to_string(),
                     db_name.to_string(),
                     self.base_path.clone(),
                     self.memory_threshold,
+                    self.idle_timeout,
+                    self.channel_capacity,
                     self.databases.clone(),
                 )
             })
-            .clone())
+            .clone();
+        if spawned {
+            self.restore_attachments(space, db_name, &handle).await;
+        }
+        Ok(handle)
     }
 
     async fn hydrate_cache(&self, space: &SpaceId, db_name: &str) -> Result<(), SqlError> {
@@ -164,6 +374,20 @@ impl SqlService {
             .join(format!("{}.db", db_name))
     }
 
+    /// Stop every SQL database actor of `space` and remove its cached
+    /// files. The stored artifacts are removed with the space's rows by
+    /// [`SpaceDatabase::delete_space`](crate::SpaceDatabase::delete_space).
+    pub async fn remove_space(&self, space: &SpaceId) -> Result<(), SqlError> {
+        let space = space.to_string();
+        self.databases.retain(|(s, _), _| s != &space);
+        self.attachments.retain(|(s, _), _| s != &space);
+        match tokio::fs::remove_dir_all(PathBuf::from(&self.base_path).join(&space)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(SqlError::Internal(e.to_string())),
+        }
+    }
+
     async fn discard_local_state(&self, key: &(String, String)) -> Result<(), SqlError> {
         self.databases.remove(key);
         let cache_path = PathBuf::from(&self.base_path)
@@ -204,6 +428,12 @@ async fn remove_sql_cache_files(path: &Path) -> Result<(), SqlError> {
     Ok(())
 }
 
+/// Whether `error` means a request never reached its actor, which had shut
+/// down.
+fn is_unavailable(error: &SqlError) -> bool {
+    matches!(error, SqlError::Internal(msg) if msg.contains("Database actor not available"))
+}
+
 fn artifact_error_to_sql(err: DatabaseArtifactError) -> SqlError {
     SqlError::Internal(err.to_string())
 }
@@ -409,6 +639,437 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn sibling_databases_can_be_attached_but_arbitrary_files_cannot() {
+        let repo = artifact_repository().await;
+        let cache = TempDir::new().unwrap();
+        let space = test_space_id("sql-attach");
+        let service = SqlService::new(cache.path().to_string_lossy().to_string(), u64::MAX, repo);
+        let readable = vec!["users".to_string(), "missing".to_string()];
+        let execute = |db: &'static str, sql: &str, ability: &str| {
+            service.execute_with_siblings(
+                &space,
+                db,
+                SqlRequest::Execute {
+                    schema: None,
+                    sql: sql.to_string(),
+                    params: Vec::new(),
+                },
+                None,
+                ability.to_string(),
+                &readable,
+            )
+        };
+
+        execute(
+            "users",
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "tinycloud.sql/admin",
+        )
+        .await
+        .unwrap();
+        execute(
+            "users",
+            "INSERT INTO users (id, name) VALUES (1, 'ada')",
+            "tinycloud.sql/write",
+        )
+        .await
+        .unwrap();
+        execute(
+            "main",
+            "CREATE TABLE posts (author INTEGER, title TEXT)",
+            "tinycloud.sql/admin",
+        )
+        .await
+        .unwrap();
+        execute(
+            "main",
+            "INSERT INTO posts (author, title) VALUES (1, 'hello')",
+            "tinycloud.sql/write",
+        )
+        .await
+        .unwrap();
+
+        execute(
+            "main",
+            "ATTACH DATABASE 'users' AS users",
+            "tinycloud.sql/admin",
+        )
+        .await
+        .expect("attaching a sibling database");
+        let result = service
+            .execute_with_siblings(
+                &space,
+                "main",
+                SqlRequest::Query {
+                    sql:
+                        "SELECT u.name, p.title FROM posts p JOIN users.users u ON u.id = p.author"
+                            .to_string(),
+                    params: Vec::new(),
+                    max_rows: None,
+                    max_bytes: None,
+                },
+                None,
+                "tinycloud.sql/read".to_string(),
+                &readable,
+            )
+            .await
+            .unwrap();
+        match result.response {
+            SqlResponse::Query(query) => assert_eq!(
+                query.rows,
+                vec![vec![
+                    SqlValue::Text("ada".to_string()),
+                    SqlValue::Text("hello".to_string())
+                ]]
+            ),
+            other => panic!("expected query response, got {:?}", other),
+        }
+
+        // attached snapshots are read only
+        assert!(execute(
+            "main",
+            "INSERT INTO users.users (id, name) VALUES (2, 'eve')",
+            "tinycloud.sql/admin",
+        )
+        .await
+        .is_err());
+
+        let secret = cache.path().join("secret.db");
+        rusqlite::Connection::open(&secret)
+            .unwrap()
+            .execute_batch("CREATE TABLE secret (value TEXT)")
+            .unwrap();
+        for sql in [
+            format!("ATTACH DATABASE '{}' AS secret", secret.display()),
+            format!("ATTACH DATABASE 'file:{}' AS secret", secret.display()),
+        ] {
+            assert!(matches!(
+                execute("main", &sql, "tinycloud.sql/admin").await,
+                Err(SqlError::PermissionDenied(_))
+            ));
+        }
+        assert!(matches!(
+            execute(
+                "main",
+                "ATTACH DATABASE 'missing' AS missing",
+                "tinycloud.sql/admin"
+            )
+            .await,
+            Err(SqlError::DatabaseNotFound)
+        ));
+        assert!(matches!(
+            execute(
+                "main",
+                "ATTACH DATABASE 'users' AS people",
+                "tinycloud.sql/write"
+            )
+            .await,
+            Err(SqlError::PermissionDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn attachments_need_read_access_and_follow_the_sibling() {
+        let repo = artifact_repository().await;
+        let cache = TempDir::new().unwrap();
+        let space = test_space_id("sql-attach-access");
+        let service = SqlService::new(cache.path().to_string_lossy().to_string(), u64::MAX, repo)
+            .with_idle_timeout(Duration::from_millis(50));
+        let execute = |db: &'static str, sql: &str, ability: &str, readable: &[&str]| {
+            let readable = readable.iter().map(|db| db.to_string()).collect::<Vec<_>>();
+            let service = &service;
+            let space = &space;
+            let sql = sql.to_string();
+            let ability = ability.to_string();
+            async move {
+                service
+                    .execute_with_siblings(
+                        space,
+                        db,
+                        SqlRequest::Execute {
+                            schema: None,
+                            sql,
+                            params: Vec::new(),
+                        },
+                        None,
+                        ability,
+                        &readable,
+                    )
+                    .await
+            }
+        };
+        let query_names = |readable: &[&str]| {
+            let readable = readable.iter().map(|db| db.to_string()).collect::<Vec<_>>();
+            let service = &service;
+            let space = &space;
+            async move {
+                service
+                    .execute_with_siblings(
+                        space,
+                        "main",
+                        SqlRequest::Query {
+                            sql: "SELECT name FROM users.users ORDER BY id".to_string(),
+                            params: Vec::new(),
+                            max_rows: None,
+                            max_bytes: None,
+                        },
+                        None,
+                        "tinycloud.sql/read".to_string(),
+                        &readable,
+                    )
+                    .await
+            }
+        };
+        let names = || async {
+            match query_names(&["users"]).await.unwrap().response {
+                SqlResponse::Query(query) => query.rows,
+                other => panic!("expected query response, got {:?}", other),
+            }
+        };
+
+        execute(
+            "users",
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "tinycloud.sql/admin",
+            &[],
+        )
+        .await
+        .unwrap();
+        execute(
+            "users",
+            "INSERT INTO users (id, name) VALUES (1, 'ada')",
+            "tinycloud.sql/write",
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // admin on `main` alone does not grant reading `users`
+        for readable in [&[][..], &["other"][..]] {
+            assert!(matches!(
+                execute(
+                    "main",
+                    "ATTACH DATABASE 'users' AS users",
+                    "tinycloud.sql/admin",
+                    readable
+                )
+                .await,
+                Err(SqlError::PermissionDenied(_))
+            ));
+        }
+        execute(
+            "main",
+            "ATTACH DATABASE 'users' AS users",
+            "tinycloud.sql/admin",
+            &["users"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(names().await, vec![vec![SqlValue::Text("ada".to_string())]]);
+
+        // the attachment stays on the shared actor, but a later caller
+        // without read access to `users` cannot see it through `main`
+        for readable in [&[][..], &["other"][..]] {
+            let denied = query_names(readable).await.unwrap_err();
+            assert!(
+                denied.to_string().contains("not authorized"),
+                "expected an authorization error, got: {denied}"
+            );
+        }
+
+        // writes to the sibling reach the attached copy
+        execute(
+            "users",
+            "INSERT INTO users (id, name) VALUES (2, 'grace')",
+            "tinycloud.sql/write",
+            &[],
+        )
+        .await
+        .unwrap();
+        let both = vec![
+            vec![SqlValue::Text("ada".to_string())],
+            vec![SqlValue::Text("grace".to_string())],
+        ];
+        assert_eq!(names().await, both);
+
+        // and the attachment outlives the actor going idle
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(service
+            .databases
+            .get(&(space.to_string(), "main".to_string()))
+            .is_none());
+        assert_eq!(names().await, both);
+        assert!(query_names(&[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sql_actor_respawns_after_idle_eviction() {
+        let repo = artifact_repository().await;
+        let cache = TempDir::new().unwrap();
+        let space = test_space_id("sql-idle");
+        let service = SqlService::new(cache.path().to_string_lossy().to_string(), u64::MAX, repo)
+            .with_idle_timeout(Duration::from_millis(50));
+
+        service
+            .execute(
+                &space,
+                "main",
+                SqlRequest::Execute {
+                    schema: Some(vec!["CREATE TABLE items (name TEXT NOT NULL)".to_string()]),
+                    sql: "INSERT INTO items (name) VALUES ('kept')".to_string(),
+                    params: Vec::new(),
+                },
+                None,
+                "tinycloud.sql/write".to_string(),
+            )
+            .await
+            .unwrap();
+        let first = service
+            .databases
+            .get(&(space.to_string(), "main".to_string()))
+            .map(|handle| handle.id())
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        let result = service
+            .execute(
+                &space,
+                "main",
+                SqlRequest::Query {
+                    sql: "SELECT name FROM items".to_string(),
+                    params: Vec::new(),
+                    max_rows: None,
+                    max_bytes: None,
+                },
+                None,
+                "tinycloud.sql/read".to_string(),
+            )
+            .await
+            .expect("an evicted actor is respawned for the next request");
+        match result.response {
+            SqlResponse::Query(query) => {
+                assert_eq!(query.rows, vec![vec![SqlValue::Text("kept".to_string())]]);
+            }
+            other => panic!("expected query response, got {:?}", other),
+        }
+        let second = service
+            .databases
+            .get(&(space.to_string(), "main".to_string()))
+            .map(|handle| handle.id());
+        assert_ne!(second, Some(first));
+    }
+
+    #[tokio::test]
+    async fn requests_to_an_actor_that_shuts_down_after_lookup_are_retried() {
+        let repo = artifact_repository().await;
+        let cache = TempDir::new().unwrap();
+        let space = test_space_id("sql-idle-race");
+        let service = SqlService::new(cache.path().to_string_lossy().to_string(), u64::MAX, repo)
+            .with_idle_timeout(Duration::from_millis(50));
+
+        service
+            .execute(
+                &space,
+                "main",
+                SqlRequest::Execute {
+                    schema: Some(vec!["CREATE TABLE items (name TEXT NOT NULL)".to_string()]),
+                    sql: "INSERT INTO items (name) VALUES ('kept')".to_string(),
+                    params: Vec::new(),
+                },
+                None,
+                "tinycloud.sql/write".to_string(),
+            )
+            .await
+            .unwrap();
+        // looked up while live, then left to go idle before it is sent to
+        let stale = service.handle(&space, "main").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(stale.is_closed());
+
+        let (handle, result) = service
+            .on_actor(&space, "main", stale.clone(), |handle| async move {
+                handle
+                    .execute(
+                        SqlRequest::Query {
+                            sql: "SELECT name FROM items".to_string(),
+                            params: Vec::new(),
+                            max_rows: None,
+                            max_bytes: None,
+                        },
+                        None,
+                        "tinycloud.sql/read".to_string(),
+                    )
+                    .await
+            })
+            .await
+            .expect("the request is retried on a respawned actor");
+        match result.response {
+            SqlResponse::Query(query) => {
+                assert_eq!(query.rows, vec![vec![SqlValue::Text("kept".to_string())]]);
+            }
+            other => panic!("expected query response, got {:?}", other),
+        }
+        assert_ne!(handle.id(), stale.id());
+        assert_eq!(
+            service
+                .databases
+                .get(&(space.to_string(), "main".to_string()))
+                .map(|handle| handle.id()),
+            Some(handle.id())
+        );
+    }
+
+    #[tokio::test]
+    async fn sql_idle_timeout_governs_eviction() {
+        let repo = artifact_repository().await;
+        let cache = TempDir::new().unwrap();
+        let space = test_space_id("sql-idle-config");
+        let key = (space.to_string(), "main".to_string());
+        let query = || SqlRequest::Query {
+            sql: "SELECT 1".to_string(),
+            params: Vec::new(),
+            max_rows: None,
+            max_bytes: None,
+        };
+
+        let patient = SqlService::new(
+            cache.path().to_string_lossy().to_string(),
+            u64::MAX,
+            repo.clone(),
+        )
+        .with_idle_timeout(Duration::from_secs(60))
+        .with_channel_capacity(1);
+        patient
+            .execute(
+                &space,
+                "main",
+                query(),
+                None,
+                "tinycloud.sql/read".to_string(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(patient.databases.get(&key).is_some_and(|h| !h.is_closed()));
+
+        let eager = SqlService::new(cache.path().to_string_lossy().to_string(), u64::MAX, repo)
+            .with_idle_timeout(Duration::from_millis(50));
+        eager
+            .execute(
+                &space,
+                "main",
+                query(),
+                None,
+                "tinycloud.sql/read".to_string(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(eager.databases.get(&key).is_none());
+    }
+
     struct FailingArtifactRepository;
 
     #[async_trait]
@@ -59,6 +59,36 @@ where
                 .map_err(Self::Error::B),
         }
     }
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        match self {
+            Self::A(l) => l
+                .read_range(space, id, start, end)
+                .await
+                .map(|o| {
+                    o.map(|c| {
+                        let (l, r) = c.into_inner();
+                        Content::new(l, r.map(Self::Readable::Left))
+                    })
+                })
+                .map_err(Self::Error::A),
+            Self::B(r) => r
+                .read_range(space, id, start, end)
+                .await
+                .map(|o| {
+                    o.map(|c| {
+                        let (l, r) = c.into_inner();
+                        Content::new(l, r.map(Self::Readable::Right))
+                    })
+                })
+                .map_err(Self::Error::B),
+        }
+    }
 }
 
 #[async_trait]
@@ -151,6 +181,21 @@ where
     }
 }
 
+#[async_trait]
+impl<A, B> ImmutableListStore for Either<A, B>
+where
+    A: ImmutableListStore,
+    B: ImmutableListStore,
+{
+    type Error = EitherError<A::Error, B::Error>;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        match self {
+            Self::A(l) => l.list(space).await.map_err(Self::Error::A),
+            Self::B(r) => r.list(space).await.map_err(Self::Error::B),
+        }
+    }
+}
+
 #[async_trait]
 impl<A, B> StoreSize for Either<A, B>
 where
@@ -165,3 +210,18 @@ where
         }
     }
 }
+
+#[async_trait]
+impl<A, B> StorageHealth for Either<A, B>
+where
+    A: StorageHealth,
+    B: StorageHealth,
+{
+    type Error = EitherError<A::Error, B::Error>;
+    async fn health(&self) -> Result<(), Self::Error> {
+        match self {
+            Either::A(a) => a.health().await.map_err(EitherError::A),
+            Either::B(b) => b.health().await.map_err(EitherError::B),
+        }
+    }
+}
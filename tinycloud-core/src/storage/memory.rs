@@ -1,13 +1,20 @@
 use crate::hash::Hash;
 use crate::storage::{
-    Content, HashBuffer, ImmutableDeleteStore, ImmutableReadStore, ImmutableStaging,
-    ImmutableWriteStore, KeyedWriteError, StorageConfig, StorageSetup, StoreSize, VecReadError,
+    Content, HashBuffer, ImmutableDeleteStore, ImmutableListStore, ImmutableReadStore,
+    ImmutableStaging, ImmutableWriteStore, KeyedWriteError, StorageConfig, StorageHealth,
+    StorageSetup, StoreSize, VecReadError,
 };
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
 use dashmap::DashMap;
 use futures::io::Cursor;
 use sea_orm_migration::async_trait::async_trait;
-use std::{io, sync::Arc};
-use tinycloud_auth::resource::SpaceId;
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tinycloud_auth::{ipld_core::ipld::Ipld, resource::SpaceId};
 
 #[derive(Debug, Default, Clone)]
 pub struct MemoryStore {
@@ -15,6 +22,92 @@ pub struct MemoryStore {
 }
 
 type Blocks = DashMap<Hash, Vec<u8>>;
+type Spaces = DashMap<SpaceId, Arc<Blocks>>;
+
+impl MemoryStore {
+    /// Write every space's blocks to `path`, for [`MemoryStore::load_from`]
+    /// to restore. The snapshot replaces any file at `path` only once it has
+    /// been written in full. Nothing is saved implicitly: a store kept across
+    /// restarts is saved by its owner as it shuts down.
+    pub async fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let snapshot = encode_snapshot(&self.spaces)?;
+        let path = path.as_ref();
+        let partial = partial_path(path);
+        tokio::fs::write(&partial, snapshot).await?;
+        tokio::fs::rename(&partial, path).await
+    }
+
+    /// A store holding the blocks in a snapshot written by
+    /// [`MemoryStore::save_to`].
+    pub async fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let snapshot = tokio::fs::read(path).await?;
+        Ok(Self {
+            spaces: Arc::new(decode_snapshot(&snapshot)?),
+        })
+    }
+}
+
+/// A snapshot is a DAG-CBOR map of space IDs to maps of URL-safe base64
+/// block hashes to block contents.
+fn encode_snapshot(spaces: &Spaces) -> io::Result<Vec<u8>> {
+    let snapshot: BTreeMap<String, Ipld> = spaces
+        .iter()
+        .map(|space| {
+            let blocks = space
+                .value()
+                .iter()
+                .map(|block| {
+                    (
+                        URL_SAFE.encode(block.key().as_ref()),
+                        Ipld::Bytes(block.value().clone()),
+                    )
+                })
+                .collect();
+            (space.key().to_string(), Ipld::Map(blocks))
+        })
+        .collect();
+    serde_ipld_dagcbor::to_vec(&Ipld::Map(snapshot)).map_err(io::Error::other)
+}
+
+fn decode_snapshot(snapshot: &[u8]) -> io::Result<Spaces> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let Ipld::Map(snapshot) =
+        serde_ipld_dagcbor::from_slice(snapshot).map_err(|e| invalid(e.to_string()))?
+    else {
+        return Err(invalid("a snapshot must be a map of spaces".into()));
+    };
+    let spaces = Spaces::new();
+    for (space, blocks) in snapshot {
+        let space: SpaceId = space
+            .parse()
+            .map_err(|_| invalid(format!("invalid space ID {space}")))?;
+        let Ipld::Map(blocks) = blocks else {
+            return Err(invalid(format!("the blocks of {space} must be a map")));
+        };
+        let stored = Blocks::new();
+        for (hash, data) in blocks {
+            let hash = URL_SAFE
+                .decode(&hash)
+                .ok()
+                .and_then(|bytes| Hash::try_from(bytes).ok())
+                .ok_or_else(|| invalid(format!("invalid block hash {hash}")))?;
+            let Ipld::Bytes(data) = data else {
+                return Err(invalid(format!(
+                    "the content of block {hash:?} must be bytes"
+                )));
+            };
+            stored.insert(hash, data);
+        }
+        spaces.insert(space, Arc::new(stored));
+    }
+    Ok(spaces)
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    partial.into()
+}
 
 #[derive(Default, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct MemoryStaging;
@@ -160,6 +253,28 @@ impl ImmutableDeleteStore for MemoryStore {
     }
 }
 
+#[async_trait]
+impl ImmutableListStore for MemoryStore {
+    type Error = io::Error;
+
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        Ok(self
+            .spaces
+            .get(space)
+            .map(|o| o.iter().map(|entry| *entry.key()).collect())
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl StorageHealth for MemoryStore {
+    type Error = io::Error;
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl StoreSize for MemoryStore {
     type Error = io::Error;
@@ -172,3 +287,99 @@ impl StoreSize for MemoryStore {
         }))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn put(store: &MemoryStore, space: &SpaceId, data: &[u8]) -> Hash {
+        let mut stage = MemoryStaging.stage(space).await.unwrap();
+        futures::io::copy(&mut &data[..], &mut stage).await.unwrap();
+        ImmutableWriteStore::<MemoryStaging>::persist(store, space, stage)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn snapshots_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.snapshot");
+        let a: SpaceId = "tinycloud:key:test:a".parse().unwrap();
+        let b: SpaceId = "tinycloud:key:test:b".parse().unwrap();
+
+        let store = MemoryStore::default();
+        let hello = put(&store, &a, b"hello").await;
+        let world = put(&store, &b, b"world").await;
+        store
+            .create(&"tinycloud:key:test:empty".parse().unwrap())
+            .await
+            .unwrap();
+        store.save_to(&path).await.unwrap();
+
+        let loaded = MemoryStore::load_from(&path).await.unwrap();
+        assert_eq!(
+            loaded.read_to_vec(&a, &hello).await.unwrap().unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            loaded.read_to_vec(&b, &world).await.unwrap().unwrap(),
+            b"world"
+        );
+        assert_eq!(loaded.list(&a).await.unwrap(), vec![hello]);
+        assert_eq!(
+            loaded
+                .total_size(&"tinycloud:key:test:empty".parse().unwrap())
+                .await
+                .unwrap(),
+            Some(0)
+        );
+
+        assert_eq!(
+            MemoryStore::load_from(dir.path().join("missing"))
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn verified_reads_check_size_and_hash() {
+        use crate::storage::VerifiedReadError;
+
+        let space: SpaceId = "tinycloud:key:test:verified".parse().unwrap();
+        let store = MemoryStore::default();
+        let hash = put(&store, &space, b"hello").await;
+
+        assert_eq!(
+            store.read_verified(&space, &hash, 5).await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+        assert!(matches!(
+            store.read_verified(&space, &hash, 4).await,
+            Err(VerifiedReadError::TooLarge(4))
+        ));
+        assert_eq!(
+            store
+                .read_verified(&space, &crate::hash::hash(b"absent"), 5)
+                .await
+                .unwrap(),
+            None
+        );
+
+        store
+            .spaces
+            .get(&space)
+            .unwrap()
+            .insert(hash, b"jello".to_vec());
+        assert!(matches!(
+            store.read_verified(&space, &hash, 5).await,
+            Err(VerifiedReadError::HashMismatch)
+        ));
+        // unverified reads serve whatever the store holds
+        assert_eq!(
+            store.read_to_vec(&space, &hash).await.unwrap(),
+            Some(b"jello".to_vec())
+        );
+    }
+}
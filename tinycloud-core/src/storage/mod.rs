@@ -1,4 +1,4 @@
-use crate::hash::Hash;
+use crate::hash::{Blake3Hasher, Hash};
 use sea_orm_migration::async_trait::async_trait;
 use std::error::Error as StdError;
 use tinycloud_auth::resource::SpaceId;
@@ -7,7 +7,7 @@ pub mod either;
 pub mod memory;
 mod util;
 pub use memory::{MemoryStore, MemoryStoreConfig};
-pub use util::{Content, HashBuffer};
+pub use util::{Content, HashBuffer, Ranged};
 
 #[async_trait]
 pub trait StorageConfig<S> {
@@ -29,6 +29,18 @@ pub enum VecReadError<E> {
     Read(futures::io::Error),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum VerifiedReadError<E> {
+    #[error(transparent)]
+    Store(#[from] E),
+    #[error(transparent)]
+    Read(futures::io::Error),
+    #[error("content is larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("content does not match its hash")]
+    HashMismatch,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum KeyedWriteError<E> {
     #[error("Hash Mismatch")]
@@ -70,6 +82,67 @@ pub trait ImmutableReadStore: Send + Sync {
             .map_err(VecReadError::Read)?;
         Ok(Some(v))
     }
+    /// Read the content into memory, failing if it is more than `max_size`
+    /// bytes or does not hash to `id`. The length the store reports is not
+    /// trusted beyond `max_size`, so a corrupt or hostile store can neither
+    /// serve other bytes under `id` nor force a huge allocation.
+    async fn read_verified(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        max_size: u64,
+    ) -> Result<Option<Vec<u8>>, VerifiedReadError<Self::Error>>
+    where
+        Self::Readable: Send,
+    {
+        use futures::io::AsyncReadExt;
+        let (len, reader) = match self.read(space, id).await? {
+            None => return Ok(None),
+            Some(content) => content.into_inner(),
+        };
+        if len > max_size {
+            return Err(VerifiedReadError::TooLarge(max_size));
+        }
+        let mut reader = Box::pin(reader);
+        let mut hasher = Blake3Hasher::new();
+        let mut value = Vec::with_capacity(len as usize);
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let read = reader
+                .read(&mut chunk)
+                .await
+                .map_err(VerifiedReadError::Read)?;
+            if read == 0 {
+                break;
+            }
+            if hasher.count() + read as u64 > max_size {
+                return Err(VerifiedReadError::TooLarge(max_size));
+            }
+            hasher.update(&chunk[..read]);
+            value.extend_from_slice(&chunk[..read]);
+        }
+        if hasher.finalize() != *id {
+            return Err(VerifiedReadError::HashMismatch);
+        }
+        Ok(Some(value))
+    }
+    /// Read bytes `start..end` of the content, or from `start` to the end
+    /// when `end` is `None`. Ranges are clamped to the content's length.
+    ///
+    /// The default reads the whole content and discards what falls outside
+    /// the range; stores that can fetch a range directly should override it.
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        Ok(self.read(space, id).await?.map(|content| {
+            let (total, reader) = content.into_inner();
+            Ranged::skipping(reader, start, end, total)
+        }))
+    }
 }
 
 #[async_trait]
@@ -114,12 +187,28 @@ pub trait ImmutableDeleteStore: Send + Sync {
     async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error>;
 }
 
+/// Enumerates the blocks a store holds for a space, including blocks no KV
+/// write refers to.
+#[async_trait]
+pub trait ImmutableListStore: Send + Sync {
+    type Error: StdError + Send + Sync;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error>;
+}
+
 #[async_trait]
 pub trait StoreSize: Send + Sync {
     type Error: StdError;
     async fn total_size(&self, space: &SpaceId) -> Result<Option<u64>, Self::Error>;
 }
 
+/// A readiness probe for a storage backend.
+#[async_trait]
+pub trait StorageHealth: Send + Sync {
+    type Error: StdError;
+    /// Confirm the backend is reachable and can accept writes.
+    async fn health(&self) -> Result<(), Self::Error>;
+}
+
 #[async_trait]
 impl<S> ImmutableReadStore for Box<S>
 where
@@ -147,6 +236,15 @@ where
     {
         self.read_to_vec(space, id).await
     }
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        (**self).read_range(space, id, start, end).await
+    }
 }
 
 #[async_trait]
@@ -197,6 +295,17 @@ where
     }
 }
 
+#[async_trait]
+impl<S> ImmutableListStore for Box<S>
+where
+    S: ImmutableListStore,
+{
+    type Error = S::Error;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        (**self).list(space).await
+    }
+}
+
 #[async_trait]
 impl<S> StoreSize for Box<S>
 where
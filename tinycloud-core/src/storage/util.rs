@@ -19,6 +19,10 @@ impl<B> HashBuffer<B> {
     pub fn into_inner(self) -> (Blake3Hasher, B) {
         (self.hasher, self.buffer)
     }
+    /// Reassemble a buffer taken apart by [`HashBuffer::into_inner`].
+    pub fn from_parts(hasher: Blake3Hasher, buffer: B) -> Self {
+        Self { buffer, hasher }
+    }
     pub fn hasher(&self) -> &Blake3Hasher {
         &self.hasher
     }
@@ -105,3 +109,104 @@ where
         this.content.poll_read_vectored(cx, bufs)
     }
 }
+
+/// Part of some stored content: bytes `start..start + len` of `total`.
+///
+/// The wrapped reader either yields the whole content, of which the leading
+/// bytes are skipped, or was already positioned at `start` by the store.
+#[pin_project]
+#[derive(Debug)]
+pub struct Ranged<R> {
+    #[pin]
+    inner: R,
+    skip: u64,
+    remaining: u64,
+    start: u64,
+    total: u64,
+}
+
+impl<R> Ranged<R> {
+    /// Range over a reader yielding the whole content, clamping `start..end`
+    /// (`end` exclusive, `None` for the rest of the content) to `total` bytes.
+    pub fn skipping(inner: R, start: u64, end: Option<u64>, total: u64) -> Content<Self> {
+        Self::new(inner, start, end, total, true)
+    }
+
+    /// Range over a reader the store has already positioned at `start`.
+    pub fn positioned(inner: R, start: u64, end: Option<u64>, total: u64) -> Content<Self> {
+        Self::new(inner, start, end, total, false)
+    }
+
+    fn new(inner: R, start: u64, end: Option<u64>, total: u64, skip: bool) -> Content<Self> {
+        let start = start.min(total);
+        let end = end.unwrap_or(total).clamp(start, total);
+        Content::new(
+            end - start,
+            Self {
+                inner,
+                skip: if skip { start } else { 0 },
+                remaining: end - start,
+                start,
+                total,
+            },
+        )
+    }
+
+    /// Offset of the first byte of the range.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Length of the whole content the range was taken from.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn map<T>(self, f: impl FnOnce(R) -> T) -> Ranged<T> {
+        Ranged {
+            inner: f(self.inner),
+            skip: self.skip,
+            remaining: self.remaining,
+            start: self.start,
+            total: self.total,
+        }
+    }
+}
+
+impl<R> futures::io::AsyncRead for Ranged<R>
+where
+    R: futures::io::AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut this = self.project();
+        // discard leading bytes through the caller's buffer
+        while *this.skip > 0 && !buf.is_empty() {
+            let want = buf
+                .len()
+                .min(usize::try_from(*this.skip).unwrap_or(usize::MAX));
+            match this.inner.as_mut().poll_read(cx, &mut buf[..want]) {
+                Poll::Ready(Ok(0)) => {
+                    *this.skip = 0;
+                    *this.remaining = 0;
+                }
+                Poll::Ready(Ok(n)) => *this.skip -= n as u64,
+                other => return other,
+            }
+        }
+        if *this.remaining == 0 || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let want = buf
+            .len()
+            .min(usize::try_from(*this.remaining).unwrap_or(usize::MAX));
+        let read = this.inner.poll_read(cx, &mut buf[..want]);
+        if let Poll::Ready(Ok(n)) = read {
+            *this.remaining -= n as u64;
+        }
+        read
+    }
+}
@@ -1,6 +1,6 @@
 use sea_orm::{entity::prelude::*, sea_query::ValueTypeErr};
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 use ucan_capabilities_object::{ability::AbilityError, Ability as UcanAbility};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -83,3 +83,205 @@ impl sea_orm::TryFromU64 for Ability {
         Err(DbErr::ConvertFromU64(stringify!($type)))
     }
 }
+
+impl Ability {
+    /// The ability as one the node dispatches on, with deprecated aliases
+    /// resolved, or `None` for abilities outside that vocabulary.
+    pub fn tinycloud(&self) -> Option<TinyCloudAbility> {
+        crate::policy_capability::resolve_alias(self.0.as_ref())
+            .parse()
+            .ok()
+    }
+}
+
+impl From<TinyCloudAbility> for Ability {
+    fn from(ability: TinyCloudAbility) -> Self {
+        Ability::try_from(ability.to_string())
+            .expect("TinyCloud abilities are valid ability strings")
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("unknown TinyCloud ability: {0}")]
+pub struct UnknownAbility(pub String);
+
+/// Abilities of the `kv` service.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum KvAbility {
+    Get,
+    Put,
+    Del,
+    List,
+    Exists,
+    Metadata,
+    SetMetadata,
+    Copy,
+    Move,
+}
+
+impl KvAbility {
+    pub const ALL: [KvAbility; 9] = [
+        Self::Get,
+        Self::Put,
+        Self::Del,
+        Self::List,
+        Self::Exists,
+        Self::Metadata,
+        Self::SetMetadata,
+        Self::Copy,
+        Self::Move,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "tinycloud.kv/get",
+            Self::Put => "tinycloud.kv/put",
+            Self::Del => "tinycloud.kv/del",
+            Self::List => "tinycloud.kv/list",
+            Self::Exists => "tinycloud.kv/exists",
+            Self::Metadata => "tinycloud.kv/metadata",
+            Self::SetMetadata => "tinycloud.kv/set-metadata",
+            Self::Copy => "tinycloud.kv/copy",
+            Self::Move => "tinycloud.kv/move",
+        }
+    }
+
+    /// Whether invoking the ability changes the keys of a space.
+    pub fn is_mutation(self) -> bool {
+        matches!(
+            self,
+            Self::Put | Self::Del | Self::SetMetadata | Self::Copy | Self::Move
+        )
+    }
+}
+
+/// Abilities of the `capabilities` service.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum CapabilitiesAbility {
+    Read,
+    Head,
+    Effective,
+    Usage,
+}
+
+impl CapabilitiesAbility {
+    pub const ALL: [CapabilitiesAbility; 4] =
+        [Self::Read, Self::Head, Self::Effective, Self::Usage];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "tinycloud.capabilities/read",
+            Self::Head => "tinycloud.capabilities/head",
+            Self::Effective => "tinycloud.capabilities/effective",
+            Self::Usage => "tinycloud.capabilities/usage",
+        }
+    }
+}
+
+/// The abilities `invoke` dispatches on. Their wire strings are those of
+/// the capability registry; deprecated aliases are resolved by
+/// [`Ability::tinycloud`], not here.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TinyCloudAbility {
+    Kv(KvAbility),
+    Capabilities(CapabilitiesAbility),
+}
+
+impl TinyCloudAbility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Kv(ability) => ability.as_str(),
+            Self::Capabilities(ability) => ability.as_str(),
+        }
+    }
+
+    pub fn kv(self) -> Option<KvAbility> {
+        match self {
+            Self::Kv(ability) => Some(ability),
+            _ => None,
+        }
+    }
+}
+
+impl From<KvAbility> for TinyCloudAbility {
+    fn from(ability: KvAbility) -> Self {
+        Self::Kv(ability)
+    }
+}
+
+impl From<CapabilitiesAbility> for TinyCloudAbility {
+    fn from(ability: CapabilitiesAbility) -> Self {
+        Self::Capabilities(ability)
+    }
+}
+
+impl FromStr for TinyCloudAbility {
+    type Err = UnknownAbility;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KvAbility::ALL
+            .into_iter()
+            .map(Self::Kv)
+            .chain(CapabilitiesAbility::ALL.into_iter().map(Self::Capabilities))
+            .find(|ability| ability.as_str() == s)
+            .ok_or_else(|| UnknownAbility(s.to_string()))
+    }
+}
+
+impl FromStr for KvAbility {
+    type Err = UnknownAbility;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<TinyCloudAbility>()?
+            .kv()
+            .ok_or_else(|| UnknownAbility(s.to_string()))
+    }
+}
+
+impl Display for TinyCloudAbility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Display for KvAbility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Display for CapabilitiesAbility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abilities_round_trip_their_wire_strings() {
+        let all = KvAbility::ALL.into_iter().map(TinyCloudAbility::Kv).chain(
+            CapabilitiesAbility::ALL
+                .into_iter()
+                .map(TinyCloudAbility::Capabilities),
+        );
+        for ability in all {
+            let wire = ability.to_string();
+            assert_eq!(wire.parse::<TinyCloudAbility>().unwrap(), ability);
+            assert_eq!(Ability::from(ability).tinycloud(), Some(ability));
+            if let Some(kv) = ability.kv() {
+                assert_eq!(wire.parse::<KvAbility>().unwrap(), kv);
+                assert_eq!(kv.to_string(), wire);
+            }
+        }
+    }
+
+    #[test]
+    fn aliases_resolve_and_unknown_abilities_do_not_parse() {
+        let alias = Ability::try_from("tinycloud.kv/delete".to_string()).unwrap();
+        assert_eq!(alias.tinycloud(), Some(KvAbility::Del.into()));
+        assert!("tinycloud.kv/delete".parse::<TinyCloudAbility>().is_err());
+        assert!("tinycloud.sql/read".parse::<TinyCloudAbility>().is_err());
+        assert!("tinycloud.capabilities/read".parse::<KvAbility>().is_err());
+    }
+}
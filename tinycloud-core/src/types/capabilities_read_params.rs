@@ -31,4 +31,13 @@ pub struct ListFilters {
     pub path: Option<String>,
     /// Filter by ability (must match one of these)
     pub actions: Option<Vec<String>>,
+    /// Filter by the DID the delegation was issued to
+    pub delegate: Option<String>,
+    /// Filter by resource service, e.g. "kv"
+    pub service: Option<String>,
+    /// Most delegations to return, capped by the node; the listing then
+    /// carries a cursor to continue from
+    pub limit: Option<u64>,
+    /// Cursor returned with the previous page
+    pub cursor: Option<String>,
 }
@@ -41,3 +41,179 @@ impl sea_orm::sea_query::Nullable for Caveats {
         Value::Json(None)
     }
 }
+
+/// Limits a kv capability's nota bene places on the puts it authorizes, e.g.
+/// `{"maxSize": 1048576, "contentTypes": ["image/png"], "pathPattern": "photos/.*"}`.
+///
+/// A capability with several nota bene entries authorizes a put which
+/// satisfies any one of them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct KvCaveats {
+    /// Largest value, in bytes, a put may write.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Content types a put may declare. A put declaring none is refused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_types: Option<Vec<String>>,
+    /// Regular expression the whole path of a put must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum KvCaveatError {
+    #[error("kv-caveat-invalid: {0}")]
+    Invalid(String),
+    #[error("kv-caveat-max-size: {size} bytes exceeds the limit of {max}")]
+    TooLarge { size: u64, max: u64 },
+    #[error("kv-caveat-content-type: {0} is not allowed")]
+    ContentType(String),
+    #[error("kv-caveat-path: {0} does not match the allowed pattern")]
+    Path(String),
+}
+
+impl KvCaveats {
+    const FIELDS: [&'static str; 3] = ["maxSize", "contentTypes", "pathPattern"];
+
+    /// The kv caveats among `caveats`: one per nota bene entry naming any kv
+    /// limit. Entries naming none, such as SQL caveats, are not kv caveats.
+    pub fn from_caveats(caveats: &Caveats) -> Result<Vec<Self>, KvCaveatError> {
+        caveats
+            .0
+            .values()
+            .filter(|nb| {
+                nb.as_object()
+                    .is_some_and(|nb| Self::FIELDS.iter().any(|f| nb.contains_key(*f)))
+            })
+            .map(|nb| {
+                let caveats: Self = serde_json::from_value(nb.clone())
+                    .map_err(|e| KvCaveatError::Invalid(e.to_string()))?;
+                caveats.path_regex()?;
+                Ok(caveats)
+            })
+            .collect()
+    }
+
+    fn path_regex(&self) -> Result<Option<regex::Regex>, KvCaveatError> {
+        self.path_pattern
+            .as_ref()
+            .map(|pattern| {
+                regex::Regex::new(&format!("^(?:{pattern})$"))
+                    .map_err(|e| KvCaveatError::Invalid(e.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Check a put of `size` bytes to `path`, declaring `content_type`.
+    pub fn check(
+        &self,
+        path: &str,
+        size: u64,
+        content_type: Option<&str>,
+    ) -> Result<(), KvCaveatError> {
+        if let Some(max) = self.max_size {
+            if size > max {
+                return Err(KvCaveatError::TooLarge { size, max });
+            }
+        }
+        if let Some(allowed) = &self.content_types {
+            let declared = content_type.map(essence).unwrap_or_default();
+            if !allowed.iter().any(|t| essence(t) == declared) {
+                return Err(KvCaveatError::ContentType(declared));
+            }
+        }
+        if let Some(pattern) = self.path_regex()? {
+            if !pattern.is_match(path) {
+                return Err(KvCaveatError::Path(path.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether every put `narrower` allows is allowed by `self`. Patterns
+    /// are only compared for equality.
+    pub fn contains(&self, narrower: &Self) -> bool {
+        let size = match (self.max_size, narrower.max_size) {
+            (None, _) => true,
+            (Some(max), Some(narrower)) => narrower <= max,
+            (Some(_), None) => false,
+        };
+        let types = match (&self.content_types, &narrower.content_types) {
+            (None, _) => true,
+            (Some(allowed), Some(narrower)) => narrower
+                .iter()
+                .all(|t| allowed.iter().any(|a| essence(a) == essence(t))),
+            (Some(_), None) => false,
+        };
+        let path = self.path_pattern.is_none() || self.path_pattern == narrower.path_pattern;
+        size && types && path
+    }
+}
+
+/// A content type without its parameters, lowercased.
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn caveats(nb: serde_json::Value) -> Caveats {
+        Caveats(BTreeMap::from([("0".to_string(), nb)]))
+    }
+
+    #[test]
+    fn kv_caveats_bound_size_type_and_path() {
+        let kv = KvCaveats::from_caveats(&caveats(json!({
+            "maxSize": 4,
+            "contentTypes": ["text/plain"],
+            "pathPattern": "notes/.*"
+        })))
+        .unwrap()
+        .remove(0);
+        kv.check("notes/a", 4, Some("text/plain; charset=utf-8"))
+            .unwrap();
+        assert_eq!(
+            kv.check("notes/a", 5, Some("text/plain")),
+            Err(KvCaveatError::TooLarge { size: 5, max: 4 })
+        );
+        assert!(kv.check("notes/a", 1, None).is_err());
+        assert!(kv.check("other/notes/a", 1, Some("text/plain")).is_err());
+    }
+
+    #[test]
+    fn only_kv_shaped_nota_bene_are_kv_caveats() {
+        assert!(
+            KvCaveats::from_caveats(&caveats(json!({"mode": "constrained-statements"})))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(KvCaveats::from_caveats(&caveats(json!({"maxSize": "big"}))).is_err());
+        assert!(KvCaveats::from_caveats(&caveats(json!({"pathPattern": "("}))).is_err());
+    }
+
+    #[test]
+    fn narrower_kv_caveats_are_contained() {
+        let parent = KvCaveats {
+            max_size: Some(10),
+            content_types: Some(vec!["text/plain".into(), "image/png".into()]),
+            path_pattern: None,
+        };
+        let child = KvCaveats {
+            max_size: Some(5),
+            content_types: Some(vec!["image/png".into()]),
+            path_pattern: Some("photos/.*".into()),
+        };
+        assert!(parent.contains(&child));
+        assert!(!child.contains(&parent));
+        assert!(!parent.contains(&KvCaveats::default()));
+    }
+}
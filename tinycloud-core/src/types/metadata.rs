@@ -5,6 +5,16 @@ use std::collections::BTreeMap;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Eq, PartialOrd, Ord, Hash)]
 pub struct Metadata(pub BTreeMap<String, String>);
 
+impl Metadata {
+    /// The `content-type` entry, whatever the case of its name.
+    pub fn content_type(&self) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
 impl From<Metadata> for Value {
     fn from(source: Metadata) -> Self {
         Value::Json(serde_json::to_value(source).ok().map(Box::new))
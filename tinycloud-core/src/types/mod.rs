@@ -8,9 +8,9 @@ mod path;
 mod resource;
 mod space_id_wrap;
 
-pub use ability::Ability;
+pub use ability::{Ability, CapabilitiesAbility, KvAbility, TinyCloudAbility, UnknownAbility};
 pub use capabilities_read_params::{CapabilitiesReadParams, ListFilters};
-pub use caveats::Caveats;
+pub use caveats::{Caveats, KvCaveatError, KvCaveats};
 pub use delegation_query::{
     AccountDelegationRecord, DelegationQuery, DelegationQueryDirection, DelegationQueryPage,
     DelegationQueryStatus, DelegationQueryValidationError, DelegationResource,
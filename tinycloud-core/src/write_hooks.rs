@@ -1,5 +1,6 @@
 use crate::hash::Blake3Hasher;
 use crate::models::hook_subscription;
+use tinycloud_auth::codec;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TouchedTables {
@@ -54,7 +55,7 @@ pub fn hook_delivery_id(subscription_id: &str, event_id: &str) -> String {
     hasher.update(subscription_id.as_bytes());
     hasher.update(b":");
     hasher.update(event_id.as_bytes());
-    hasher.finalize().to_cid(0x55).to_string()
+    hasher.finalize().to_cid(codec::RAW).to_string()
 }
 
 fn matches_prefix(prefix: Option<&str>, path: &str) -> bool {
@@ -1,14 +1,14 @@
-use anyhow::Result;
-use reqwest::get;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{get, StatusCode};
 use serde::{Deserialize, Serialize};
-use tinycloud_auth::ipld_core::cid::{multibase::Base, Cid};
 use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::allow_list::{AllowListError, SpaceAllowList};
 
-#[rocket::async_trait]
-pub trait SpaceAllowList {
-    async fn is_allowed(&self, oid: &Cid) -> Result<SpaceId>;
-}
-
+/// An external allowlist queried with `GET <url>/<space id>`, the space id
+/// percent-encoded as a single path segment.
+///
+/// A success status allows the space, `403` or `404` denies it, and any other
+/// response fails the check.
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 #[serde(from = "String", into = "String")]
 pub struct SpaceAllowListService(pub String);
@@ -25,16 +25,50 @@ impl From<SpaceAllowListService> for String {
     }
 }
 
+impl SpaceAllowListService {
+    fn lookup_url(&self, space: &SpaceId) -> String {
+        format!(
+            "{}/{}",
+            self.0.trim_end_matches('/'),
+            utf8_percent_encode(&space.to_string(), NON_ALPHANUMERIC)
+        )
+    }
+}
+
 #[rocket::async_trait]
 impl SpaceAllowList for SpaceAllowListService {
-    async fn is_allowed(&self, oid: &Cid) -> Result<SpaceId> {
-        Ok(
-            get([self.0.as_str(), &oid.to_string_of_base(Base::Base58Btc)?].join("/"))
-                .await?
-                .error_for_status()?
-                .text()
-                .await?
-                .parse()?,
-        )
+    async fn is_allowed(&self, space: &SpaceId) -> Result<bool, AllowListError> {
+        let response = get(self.lookup_url(space))
+            .await
+            .map_err(|e| AllowListError(Box::new(e)))?;
+        match response.status() {
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => Ok(false),
+            _ => response
+                .error_for_status()
+                .map(|_| true)
+                .map_err(|e| AllowListError(Box::new(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn space_ids_are_looked_up_as_one_path_segment() {
+        let service = SpaceAllowListService("https://allow.example/spaces/".into());
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let url = service.lookup_url(&space);
+        let segment = url.strip_prefix("https://allow.example/spaces/").unwrap();
+        assert!(segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '%'));
+        assert_eq!(
+            percent_encoding::percent_decode_str(segment)
+                .decode_utf8()
+                .unwrap(),
+            space.to_string()
+        );
     }
 }
@@ -1,7 +1,8 @@
 use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use rocket::{
     data::{Capped, FromData},
-    futures::io::AsyncRead,
+    futures::io::{AsyncRead, AsyncReadExt, Cursor},
     http::{ContentType, Header, Status},
     request::{FromRequest, Outcome, Request},
     response::{Responder, Response},
@@ -9,14 +10,18 @@ use rocket::{
     Data,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, HashMap},
+    pin::Pin,
+};
 use tinycloud_auth::{
     authorization::{EncodingError, HeaderEncode},
     ipld_core::cid::Cid,
-    resource::SpaceId,
+    resource::{Path, SpaceId},
 };
 use tinycloud_core::{
     hash::Hash,
+    storage::Content,
     types::Metadata,
     util::{Capability, DelegationInfo},
     InvocationOutcome,
@@ -24,6 +29,12 @@ use tinycloud_core::{
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tracing::{info_span, Instrument};
 
+use crate::{
+    compression::{accepts_gzip, compressible, Gzip},
+    ens_names::EnsNames,
+    tracing::record_streamed_body_size,
+};
+
 #[derive(Debug)]
 pub enum DataHolder<O, M = O> {
     None,
@@ -35,7 +46,8 @@ pub enum DataHolder<O, M = O> {
 pub struct InvOut<R>(pub InvocationOutcome<R>);
 
 pub type DataIn<'a> = DataHolder<Data<'a>, (SpaceId, String, Metadata, Capped<&'a [u8]>)>;
-pub type DataOut<R> = DataHolder<InvOut<R>>;
+/// Several outcomes carry the kv key each one was read from, if any.
+pub type DataOut<R> = DataHolder<InvOut<R>, (Option<Path>, InvOut<R>)>;
 
 #[derive(Serialize)]
 struct KvBatchWriteResponse {
@@ -53,12 +65,44 @@ impl<'r> Responder<'r, 'static> for KvListResponse {
     }
 }
 
+#[derive(Serialize)]
+struct KvListDetailedEntry {
+    key: String,
+    metadata: Metadata,
+    hash: String,
+}
+
+struct KvListDetailedResponse(Vec<(tinycloud_auth::resource::Path, Metadata, Hash)>, bool);
+
+impl<'r> Responder<'r, 'static> for KvListDetailedResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let entries = self
+            .0
+            .into_iter()
+            .map(|(path, metadata, hash)| KvListDetailedEntry {
+                key: path.to_string(),
+                metadata,
+                hash: hex::encode(hash.as_ref()),
+            })
+            .collect::<Vec<_>>();
+        let mut response = Json(entries).respond_to(request)?;
+        response.set_header(Header::new("x-tinycloud-truncated", self.1.to_string()));
+        Ok(response)
+    }
+}
+
 struct KvMutationResponse(Option<Hash>);
 
 fn kv_etag(hash: Hash) -> String {
     format!("\"blake3-{}\"", hex::encode(hash.as_ref()))
 }
 
+/// The ETag of a value served gzipped, which is a different representation
+/// from the stored bytes [`kv_etag`] names, so caches never mix the two.
+fn kv_gzip_etag(hash: Hash) -> String {
+    format!("\"blake3-{}-gzip\"", hex::encode(hash.as_ref()))
+}
+
 impl<'r> Responder<'r, 'static> for KvMutationResponse {
     fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
         let mut response = ().respond_to(request)?;
@@ -79,6 +123,248 @@ impl<'r> Responder<'r, 'static> for KvMetadataResponse {
     }
 }
 
+/// One element of the JSON array answering an invocation with several
+/// outcomes of different kinds, in invocation order. Values read by
+/// `tinycloud.kv/get` are referenced by key and ETag rather than inlined;
+/// binary SQL and DuckDB exports are base64-encoded.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum OutcomeJson {
+    KvList {
+        keys: Vec<String>,
+        truncated: bool,
+    },
+    KvListDetailed {
+        entries: Vec<KvListDetailedEntry>,
+        truncated: bool,
+    },
+    KvDelete {
+        etag: Option<String>,
+    },
+    KvMetadata {
+        metadata: Option<Metadata>,
+        etag: Option<String>,
+    },
+    KvExists {
+        exists: bool,
+    },
+    KvWrite {
+        etag: String,
+    },
+    KvBatchWrite {
+        written: Vec<String>,
+        count: usize,
+    },
+    KvRead {
+        key: Option<String>,
+        metadata: Option<Metadata>,
+        etag: Option<String>,
+        size: Option<u64>,
+    },
+    OpenSessions {
+        sessions: HashMap<String, CapJsonRep>,
+        #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
+    DelegationChain {
+        chain: Vec<CapJsonRep>,
+    },
+    EffectivePermissions {
+        capabilities: Vec<Capability>,
+    },
+    EpochHeads {
+        heads: Vec<String>,
+        seq: i64,
+    },
+    Usage {
+        bytes: u64,
+        limit: Option<u64>,
+        objects: u64,
+        delegations: u64,
+    },
+    SqlResult {
+        result: serde_json::Value,
+    },
+    SqlExport {
+        data: String,
+    },
+    DuckDbResult {
+        result: serde_json::Value,
+    },
+    DuckDbExport {
+        data: String,
+    },
+    DuckDbArrow {
+        data: String,
+    },
+    DryRun {
+        authorized: bool,
+        capabilities: Vec<Capability>,
+    },
+}
+
+impl OutcomeJson {
+    fn new<R>(
+        key: Option<Path>,
+        outcome: InvocationOutcome<R>,
+        names: Option<&EnsNames>,
+    ) -> Result<Self, EncodingError> {
+        Ok(match outcome {
+            InvocationOutcome::KvList(keys, truncated) => Self::KvList {
+                keys: keys.iter().map(ToString::to_string).collect(),
+                truncated,
+            },
+            InvocationOutcome::KvListDetailed(entries, truncated) => Self::KvListDetailed {
+                entries: entries
+                    .into_iter()
+                    .map(|(path, metadata, hash)| KvListDetailedEntry {
+                        key: path.to_string(),
+                        metadata,
+                        hash: hex::encode(hash.as_ref()),
+                    })
+                    .collect(),
+                truncated,
+            },
+            InvocationOutcome::KvDelete(hash) => Self::KvDelete {
+                etag: hash.map(kv_etag),
+            },
+            InvocationOutcome::KvMetadata(meta) => {
+                let (metadata, etag) = meta
+                    .map(|(metadata, hash)| (metadata, kv_etag(hash)))
+                    .unzip();
+                Self::KvMetadata { metadata, etag }
+            }
+            InvocationOutcome::KvExists(exists) => Self::KvExists { exists },
+            InvocationOutcome::KvWrite(hash) => Self::KvWrite {
+                etag: kv_etag(hash),
+            },
+            InvocationOutcome::KvBatchWrite(written) => Self::KvBatchWrite {
+                count: written.len(),
+                written: written.iter().map(ToString::to_string).collect(),
+            },
+            InvocationOutcome::KvRead(read) => {
+                let (metadata, etag, size) = match read {
+                    Some((metadata, hash, content)) => {
+                        (Some(metadata), Some(kv_etag(hash)), Some(content.len()))
+                    }
+                    None => (None, None, None),
+                };
+                Self::KvRead {
+                    key: key.map(|key| key.to_string()),
+                    metadata,
+                    etag,
+                    size,
+                }
+            }
+            InvocationOutcome::OpenSessions(sessions, next) => Self::OpenSessions {
+                next_cursor: next.map(|id| id.to_event_cid().to_string()),
+                sessions: sessions
+                    .into_iter()
+                    .map(|(hash, del)| {
+                        Ok((
+                            hash.to_event_cid().to_string(),
+                            CapJsonRep::from_delegation(del)?.with_names(names),
+                        ))
+                    })
+                    .collect::<Result<_, EncodingError>>()?,
+            },
+            InvocationOutcome::DelegationChain(chain) => Self::DelegationChain {
+                chain: chain
+                    .into_iter()
+                    .map(|del| Ok(CapJsonRep::from_delegation(del)?.with_names(names)))
+                    .collect::<Result<_, EncodingError>>()?,
+            },
+            InvocationOutcome::EffectivePermissions(capabilities) => {
+                Self::EffectivePermissions { capabilities }
+            }
+            InvocationOutcome::EpochHeads(heads, seq) => Self::EpochHeads {
+                heads: heads.iter().map(|head| head.to_string()).collect(),
+                seq,
+            },
+            InvocationOutcome::Usage {
+                bytes,
+                limit,
+                objects,
+                delegations,
+            } => Self::Usage {
+                bytes,
+                limit,
+                objects,
+                delegations,
+            },
+            InvocationOutcome::SqlResult(result) => Self::SqlResult { result },
+            InvocationOutcome::SqlExport(data) => Self::SqlExport {
+                data: base64::encode(data),
+            },
+            InvocationOutcome::DuckDbResult(result) => Self::DuckDbResult { result },
+            InvocationOutcome::DuckDbExport(data) => Self::DuckDbExport {
+                data: base64::encode(data),
+            },
+            InvocationOutcome::DuckDbArrow(data) => Self::DuckDbArrow {
+                data: base64::encode(data),
+            },
+            InvocationOutcome::DryRun(capabilities) => Self::DryRun {
+                authorized: true,
+                capabilities,
+            },
+        })
+    }
+}
+
+/// Several kv reads answered as one `multipart/mixed` body. Each part names
+/// its key in `Content-Location` and carries the value's metadata and ETag; a
+/// missing key is an empty part with `X-Tinycloud-Status: 404`.
+struct KvMultiReadResponse<R>(Vec<KvMultiReadPart<R>>);
+
+type KvMultiReadPart<R> = (Option<Path>, Option<(Metadata, Hash, Content<R>)>);
+
+impl<'r, R> Responder<'r, 'static> for KvMultiReadResponse<R>
+where
+    R: 'static + AsyncRead + Send,
+{
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let boundary = format!("tinycloud-{}", uuid::Uuid::new_v4().simple());
+        let mut body: Pin<Box<dyn AsyncRead + Send>> = Box::pin(Cursor::new(Vec::new()));
+        let mut size = 0;
+        for (key, read) in self.0 {
+            let mut head = format!("--{boundary}\r\n");
+            if let Some(key) = key {
+                head.push_str(&format!("Content-Location: {key}\r\n"));
+            }
+            body = match read {
+                Some((metadata, hash, content)) => {
+                    for (name, value) in served_headers(metadata) {
+                        head.push_str(&format!("{name}: {value}\r\n"));
+                    }
+                    head.push_str(&format!(
+                        "ETag: {}\r\nContent-Length: {}\r\n\r\n",
+                        kv_etag(hash),
+                        content.len()
+                    ));
+                    size += head.len() as u64 + content.len() + 2;
+                    Box::pin(
+                        body.chain(Cursor::new(head.into_bytes()))
+                            .chain(content)
+                            .chain(Cursor::new(b"\r\n".to_vec())),
+                    )
+                }
+                None => {
+                    head.push_str("X-Tinycloud-Status: 404\r\nContent-Length: 0\r\n\r\n\r\n");
+                    size += head.len() as u64;
+                    Box::pin(body.chain(Cursor::new(head.into_bytes())))
+                }
+            };
+        }
+        let tail = format!("--{boundary}--\r\n").into_bytes();
+        record_streamed_body_size(request, size + tail.len() as u64);
+        let body = body.chain(Cursor::new(tail));
+        Response::build()
+            .header(ContentType::new("multipart", "mixed").with_params(("boundary", boundary)))
+            .streamed_body(body.compat())
+            .ok()
+    }
+}
+
 #[async_trait]
 impl<'r> FromData<'r> for DataIn<'r> {
     type Error = anyhow::Error;
@@ -121,10 +407,16 @@ where
             InvocationOutcome::KvList(list, truncated) => {
                 KvListResponse(list, truncated).respond_to(request)
             }
+            InvocationOutcome::KvListDetailed(entries, truncated) => {
+                KvListDetailedResponse(entries, truncated).respond_to(request)
+            }
             InvocationOutcome::KvDelete(hash) => KvMutationResponse(hash).respond_to(request),
             InvocationOutcome::KvMetadata(meta) => meta
                 .map(|(metadata, hash)| KvMetadataResponse(metadata, hash))
                 .respond_to(request),
+            InvocationOutcome::KvExists(exists) => Response::build()
+                .status(if exists { Status::Ok } else { Status::NotFound })
+                .ok(),
             InvocationOutcome::KvWrite(hash) => KvMutationResponse(Some(hash)).respond_to(request),
             InvocationOutcome::KvBatchWrite(written) => {
                 let written = written
@@ -137,30 +429,76 @@ where
                 })
                 .respond_to(request)
             }
-            InvocationOutcome::KvRead(data) => data
-                .map(|(md, hash, c)| KVResponse(c, md, hash))
-                .respond_to(request),
-            InvocationOutcome::OpenSessions(sessions) => Json(
-                sessions
-                    .into_iter()
-                    .map(|(hash, del)| {
-                        Ok((
-                            hash.to_cid(0x55).to_string(),
-                            CapJsonRep::from_delegation(del)?,
-                        ))
-                    })
-                    .collect::<Result<HashMap<String, CapJsonRep>>>()
-                    .map_err(|_| Status::InternalServerError)?,
-            )
-            .respond_to(request),
+            InvocationOutcome::KvRead(None) => Err(Status::NotFound),
+            InvocationOutcome::KvRead(Some((md, hash, c))) => {
+                if !compressible(request, &md, c.len()) {
+                    record_streamed_body_size(request, c.len());
+                    return KVResponse(c, md, hash).respond_to(request);
+                }
+                let mut response = if accepts_gzip(request) {
+                    let mut response = KVResponse(Gzip::new(c), md, hash).respond_to(request)?;
+                    response.set_header(Header::new("Content-Encoding", "gzip"));
+                    response.set_header(Header::new("ETag", kv_gzip_etag(hash)));
+                    response
+                } else {
+                    record_streamed_body_size(request, c.len());
+                    KVResponse(c, md, hash).respond_to(request)?
+                };
+                response.set_header(Header::new("Vary", "Accept-Encoding"));
+                Ok(response)
+            }
+            InvocationOutcome::OpenSessions(sessions, next) => {
+                let mut response = Json(
+                    sessions
+                        .into_iter()
+                        .map(|(hash, del)| {
+                            Ok((
+                                hash.to_event_cid().to_string(),
+                                CapJsonRep::from_delegation(del)?
+                                    .with_names(request.rocket().state::<EnsNames>()),
+                            ))
+                        })
+                        .collect::<Result<HashMap<String, CapJsonRep>>>()
+                        .map_err(|_| Status::InternalServerError)?,
+                )
+                .respond_to(request)?;
+                if let Some(next) = next {
+                    response.set_header(Header::new(
+                        "x-tinycloud-next-cursor",
+                        next.to_event_cid().to_string(),
+                    ));
+                }
+                Ok(response)
+            }
             InvocationOutcome::DelegationChain(chain) => Json(
                 chain
                     .into_iter()
-                    .map(|del| Ok(CapJsonRep::from_delegation(del)?))
+                    .map(|del| {
+                        Ok(CapJsonRep::from_delegation(del)?
+                            .with_names(request.rocket().state::<EnsNames>()))
+                    })
                     .collect::<Result<Vec<CapJsonRep>>>()
                     .map_err(|_| Status::InternalServerError)?,
             )
             .respond_to(request),
+            InvocationOutcome::EffectivePermissions(caps) => Json(caps).respond_to(request),
+            InvocationOutcome::EpochHeads(heads, seq) => Json(serde_json::json!({
+                "heads": heads.iter().map(|head| head.to_string()).collect::<Vec<_>>(),
+                "seq": seq,
+            }))
+            .respond_to(request),
+            InvocationOutcome::Usage {
+                bytes,
+                limit,
+                objects,
+                delegations,
+            } => Json(serde_json::json!({
+                "bytes": bytes,
+                "limit": limit,
+                "objects": objects,
+                "delegations": delegations,
+            }))
+            .respond_to(request),
             InvocationOutcome::SqlResult(json) => Json(json).respond_to(request),
             InvocationOutcome::SqlExport(data) => Response::build()
                 .header(ContentType::new("application", "x-sqlite3"))
@@ -175,6 +513,11 @@ where
                 .header(ContentType::new("application", "vnd.apache.arrow.stream"))
                 .sized_body(data.len(), std::io::Cursor::new(data))
                 .ok(),
+            InvocationOutcome::DryRun(capabilities) => Json(serde_json::json!({
+                "authorized": true,
+                "capabilities": capabilities,
+            }))
+            .respond_to(request),
         }
     }
 }
@@ -187,7 +530,32 @@ where
         match self {
             DataHolder::None => ().respond_to(request),
             DataHolder::One(inv) => inv.respond_to(request),
-            DataHolder::Many(_invs) => Err(Status::NotImplemented),
+            DataHolder::Many(outcomes)
+                if outcomes.iter().all(|(_, InvOut(outcome))| {
+                    matches!(outcome, InvocationOutcome::KvRead(_))
+                }) =>
+            {
+                KvMultiReadResponse(
+                    outcomes
+                        .into_iter()
+                        .filter_map(|(key, InvOut(outcome))| match outcome {
+                            InvocationOutcome::KvRead(read) => Some((key, read)),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+                .respond_to(request)
+            }
+            DataHolder::Many(outcomes) => Json(
+                outcomes
+                    .into_iter()
+                    .map(|(key, InvOut(outcome))| {
+                        OutcomeJson::new(key, outcome, request.rocket().state::<EnsNames>())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| Status::InternalServerError)?,
+            )
+            .respond_to(request),
         }
     }
 }
@@ -199,6 +567,12 @@ pub struct CapJsonRep {
     pub delegate: String,
     pub parents: Vec<Cid>,
     raw: String,
+    /// Primary ENS name of the delegator, when the node looks names up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegator_name: Option<String>,
+    /// Primary ENS name of the delegate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegate_name: Option<String>,
 }
 
 impl CapJsonRep {
@@ -209,7 +583,116 @@ impl CapJsonRep {
             delegate: d.delegate,
             parents: d.parents,
             raw: d.delegation.encode()?,
+            delegator_name: None,
+            delegate_name: None,
+        })
+    }
+
+    /// Add the cached ENS names of the delegator and delegate, if `names` is
+    /// configured.
+    pub fn with_names(mut self, names: Option<&EnsNames>) -> Self {
+        if let Some(names) = names {
+            self.delegator_name = names.get(&self.delegator);
+            self.delegate_name = names.get(&self.delegate);
+        }
+        self
+    }
+}
+
+/// Whether `?dryRun=true` asks for an invocation to be authorized without
+/// being applied.
+pub struct DryRun(pub bool);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for DryRun {
+    type Error = anyhow::Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.query_value::<bool>("dryRun") {
+            None => Outcome::Success(DryRun(false)),
+            Some(Ok(dry_run)) => Outcome::Success(DryRun(dry_run)),
+            Some(Err(e)) => Outcome::Error((
+                Status::BadRequest,
+                anyhow::anyhow!("invalid dryRun parameter: {e}"),
+            )),
+        }
+    }
+}
+
+/// The filename `?download=<filename>` asks a kv read to be saved under.
+pub struct Download(pub Option<String>);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Download {
+    type Error = anyhow::Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.query_value::<String>("download") {
+            None => Outcome::Success(Download(None)),
+            Some(Ok(filename)) if !filename.trim().is_empty() => {
+                Outcome::Success(Download(Some(filename)))
+            }
+            Some(Ok(_)) => Outcome::Error((
+                Status::BadRequest,
+                anyhow::anyhow!("download filename must not be empty"),
+            )),
+            Some(Err(e)) => Outcome::Error((
+                Status::BadRequest,
+                anyhow::anyhow!("invalid download parameter: {e}"),
+            )),
+        }
+    }
+}
+
+impl Download {
+    /// Serve a kv read as an attachment with the requested filename, unless
+    /// its metadata already has a `content-disposition`.
+    pub fn apply<R>(&self, out: DataOut<R>) -> DataOut<R> {
+        let Some(filename) = &self.0 else {
+            return out;
+        };
+        match out {
+            DataHolder::One(InvOut(InvocationOutcome::KvRead(Some((
+                mut metadata,
+                hash,
+                content,
+            ))))) => {
+                if !metadata
+                    .0
+                    .keys()
+                    .any(|name| name.eq_ignore_ascii_case("content-disposition"))
+                {
+                    metadata.0.insert(
+                        "content-disposition".to_string(),
+                        attachment_disposition(filename),
+                    );
+                }
+                DataHolder::One(InvOut(InvocationOutcome::KvRead(Some((
+                    metadata, hash, content,
+                )))))
+            }
+            out => out,
+        }
+    }
+}
+
+/// A `Content-Disposition` saving a value as `filename`. The quoted name
+/// keeps only printable ASCII other than quotes and separators; a name which
+/// loses anything to that is also given whole, percent-encoded.
+fn attachment_disposition(filename: &str) -> String {
+    let plain = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '/' | ';' => '_',
+            c if c == ' ' || c.is_ascii_graphic() => c,
+            _ => '_',
         })
+        .collect::<String>();
+    if plain == filename {
+        format!("attachment; filename=\"{plain}\"")
+    } else {
+        format!(
+            "attachment; filename=\"{plain}\"; filename*=UTF-8''{}",
+            utf8_percent_encode(filename, NON_ALPHANUMERIC)
+        )
     }
 }
 
@@ -228,13 +711,86 @@ impl<'r> FromRequest<'r> for ObjectHeaders {
     }
 }
 
+/// Headers never stored with a value nor served from its metadata: those
+/// describing the connection or the request the value was written with,
+/// rather than the value itself.
+const UNSERVED_HEADERS: &[&str] = &[
+    "authorization",
+    "connection",
+    "content-length",
+    "cookie",
+    "host",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "set-cookie",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+    "x-real-ip",
+];
+
+/// The headers of `metadata` to store with a value: those `allowlist` names,
+/// where an entry ending in `*` names every header starting with the rest of
+/// it. Headers describing the request, such as `authorization`, are never
+/// stored.
+pub fn stored_metadata(metadata: Metadata, allowlist: &[String]) -> Metadata {
+    Metadata(
+        metadata
+            .0
+            .into_iter()
+            .filter(|(name, _)| {
+                !UNSERVED_HEADERS
+                    .iter()
+                    .any(|unserved| name.eq_ignore_ascii_case(unserved))
+                    && allowlist
+                        .iter()
+                        .any(|allowed| match allowed.strip_suffix('*') {
+                            Some(prefix) => name
+                                .get(..prefix.len())
+                                .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+                            None => name.eq_ignore_ascii_case(allowed),
+                        })
+            })
+            .collect(),
+    )
+}
+
+/// The headers a value is served with from its stored metadata, such as its
+/// `content-type` and `cache-control`. The content type defaults to
+/// `application/octet-stream` when none was stored.
+fn served_headers(metadata: Metadata) -> Vec<(String, String)> {
+    let mut headers = metadata
+        .0
+        .into_iter()
+        .filter(|(name, _)| {
+            !UNSERVED_HEADERS
+                .iter()
+                .any(|unserved| name.eq_ignore_ascii_case(unserved))
+        })
+        .collect::<Vec<_>>();
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        headers.push((
+            "content-type".to_string(),
+            "application/octet-stream".to_string(),
+        ));
+    }
+    headers
+}
+
 impl<'r> Responder<'r, 'static> for ObjectHeaders {
     fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
         let mut r = Response::build();
-        for (k, v) in self.0 .0 {
-            if k != "content-length" {
-                r.header(Header::new(k, v));
-            }
+        for (k, v) in served_headers(self.0) {
+            r.header(Header::new(k, v));
         }
         Ok(r.finalize())
     }
@@ -261,3 +817,315 @@ where
             .finalize())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::local::asynchronous::Client;
+
+    fn read(key: &str, value: Option<&'static [u8]>) -> (Option<Path>, InvOut<Cursor<Vec<u8>>>) {
+        let read = value.map(|value| {
+            (
+                Metadata(BTreeMap::from([(
+                    "content-type".to_string(),
+                    "text/plain".to_string(),
+                )])),
+                tinycloud_core::hash::hash(value),
+                Content::new(value.len() as u64, Cursor::new(value.to_vec())),
+            )
+        });
+        (
+            Some(key.parse().unwrap()),
+            InvOut(InvocationOutcome::KvRead(read)),
+        )
+    }
+
+    #[get("/many")]
+    fn many() -> DataOut<Cursor<Vec<u8>>> {
+        DataHolder::Many(vec![
+            read("a.txt", Some(b"alpha")),
+            read("missing.txt", None),
+            read("c.txt", Some(b"gamma")),
+        ])
+    }
+
+    #[get("/mixed")]
+    fn mixed() -> DataOut<Cursor<Vec<u8>>> {
+        DataHolder::Many(vec![
+            (
+                None,
+                InvOut(InvocationOutcome::KvList(
+                    vec!["docs/a.txt".parse().unwrap(), "docs/b.txt".parse().unwrap()],
+                    false,
+                )),
+            ),
+            (
+                None,
+                InvOut(InvocationOutcome::KvMetadata(Some((
+                    Metadata(BTreeMap::from([(
+                        "content-type".to_string(),
+                        "text/plain".to_string(),
+                    )])),
+                    tinycloud_core::hash::hash(b"alpha"),
+                )))),
+            ),
+            read("docs/a.txt", Some(b"alpha")),
+        ])
+    }
+
+    #[get("/object/<typed>")]
+    fn object(typed: bool) -> KVResponse<Cursor<Vec<u8>>> {
+        let mut metadata = BTreeMap::from([
+            ("cache-control".to_string(), "max-age=60".to_string()),
+            ("Authorization".to_string(), "secret".to_string()),
+            ("connection".to_string(), "keep-alive".to_string()),
+            ("transfer-encoding".to_string(), "chunked".to_string()),
+            ("content-length".to_string(), "3".to_string()),
+        ]);
+        if typed {
+            metadata.insert("content-type".to_string(), "image/png".to_string());
+        }
+        KVResponse::new(
+            Metadata(metadata),
+            tinycloud_core::hash::hash(b"png"),
+            Cursor::new(b"png".to_vec()),
+        )
+    }
+
+    #[get("/read/<len>?<content_type>")]
+    fn sized_read(len: usize, content_type: &str) -> DataOut<Cursor<Vec<u8>>> {
+        let value = b"tinycloud ".repeat(len / 10);
+        DataHolder::One(InvOut(InvocationOutcome::KvRead(Some((
+            Metadata(BTreeMap::from([(
+                "content-type".to_string(),
+                content_type.to_string(),
+            )])),
+            tinycloud_core::hash::hash(&value),
+            Content::new(value.len() as u64, Cursor::new(value)),
+        )))))
+    }
+
+    #[tokio::test]
+    async fn large_text_reads_are_gzipped_when_accepted() {
+        use std::io::Read;
+
+        let client = Client::tracked(rocket::build().mount("/", routes![sized_read]))
+            .await
+            .unwrap();
+        let value = b"tinycloud ".repeat(10_000);
+        let response = client
+            .get("/read/100000?content_type=text/plain;%20charset=utf-8")
+            .header(Header::new("Accept-Encoding", "br;q=1.0, gzip;q=0.8"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let headers = response.headers();
+        assert_eq!(headers.get_one("content-encoding"), Some("gzip"));
+        assert_eq!(headers.get_one("vary"), Some("Accept-Encoding"));
+        assert_eq!(
+            headers.get_one("etag").map(str::to_string),
+            Some(kv_gzip_etag(tinycloud_core::hash::hash(&value)))
+        );
+        let body = response.into_bytes().await.unwrap();
+        assert!(body.len() < value.len() / 10);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&body[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, value);
+
+        // raw when not accepted, too small or not text
+        for (len, content_type, accept) in [
+            (100_000, "application/json", None),
+            (100_000, "application/json", Some("gzip;q=0, *")),
+            (100, "text/plain", Some("gzip")),
+            (100_000, "image/png", Some("gzip")),
+        ] {
+            let uri = format!("/read/{len}?content_type={content_type}");
+            let mut request = client.get(uri.clone());
+            if let Some(accept) = accept {
+                request = request.header(Header::new("Accept-Encoding", accept));
+            }
+            let response = request.dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(
+                response.headers().get_one("content-encoding"),
+                None,
+                "{uri}"
+            );
+            assert_eq!(
+                response.headers().get_one("etag").map(str::to_string),
+                Some(kv_etag(tinycloud_core::hash::hash(
+                    b"tinycloud ".repeat(len / 10)
+                ))),
+                "{uri}"
+            );
+            let body = response.into_bytes().await.unwrap();
+            assert_eq!(body, b"tinycloud ".repeat(len / 10));
+        }
+    }
+
+    #[get("/download/<stored>")]
+    fn download(stored: bool, download: Download) -> DataOut<Cursor<Vec<u8>>> {
+        let mut metadata = BTreeMap::new();
+        if stored {
+            metadata.insert(
+                "content-disposition".to_string(),
+                "inline; filename=\"stored.txt\"".to_string(),
+            );
+        }
+        download.apply(DataHolder::One(InvOut(InvocationOutcome::KvRead(Some((
+            Metadata(metadata),
+            tinycloud_core::hash::hash(b"alpha"),
+            Content::new(5, Cursor::new(b"alpha".to_vec())),
+        ))))))
+    }
+
+    #[tokio::test]
+    async fn download_names_the_attachment() {
+        let client = Client::tracked(rocket::build().mount("/", routes![download]))
+            .await
+            .unwrap();
+        let disposition = |uri: &'static str| {
+            let client = &client;
+            async move {
+                let response = client.get(uri).dispatch().await;
+                assert_eq!(response.status(), Status::Ok);
+                response
+                    .headers()
+                    .get_one("content-disposition")
+                    .map(str::to_string)
+            }
+        };
+
+        assert_eq!(
+            disposition("/download/false?download=report.txt").await,
+            Some("attachment; filename=\"report.txt\"".to_string())
+        );
+        assert_eq!(disposition("/download/false").await, None);
+        assert_eq!(
+            disposition("/download/true?download=report.txt").await,
+            Some("inline; filename=\"stored.txt\"".to_string())
+        );
+        // quotes and line breaks can't escape the header value
+        assert_eq!(
+            disposition("/download/false?download=a%22%0D%0AX-Evil:%201%C3%A9.txt").await,
+            Some(
+                "attachment; filename=\"a___X-Evil: 1_.txt\"; \
+                 filename*=UTF-8''a%22%0D%0AX%2DEvil%3A%201%C3%A9%2Etxt"
+                    .to_string()
+            )
+        );
+        let response = client.get("/download/false?download=").dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn only_allowlisted_headers_are_stored() {
+        let allowlist = crate::config::Storage::default().metadata_headers;
+        let stored = stored_metadata(
+            Metadata(BTreeMap::from([
+                ("Authorization".to_string(), "secret".to_string()),
+                ("Cookie".to_string(), "session=1".to_string()),
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("cache-control".to_string(), "no-store".to_string()),
+                ("X-Project".to_string(), "apollo".to_string()),
+                ("x-forwarded-for".to_string(), "10.0.0.1".to_string()),
+                ("user-agent".to_string(), "curl".to_string()),
+            ])),
+            &allowlist,
+        );
+        assert_eq!(
+            stored.0.keys().collect::<Vec<_>>(),
+            ["Content-Type", "X-Project", "cache-control"]
+        );
+        // not even an allowlist of everything stores credentials
+        let everything = stored_metadata(
+            Metadata(BTreeMap::from([
+                ("authorization".to_string(), "secret".to_string()),
+                ("user-agent".to_string(), "curl".to_string()),
+            ])),
+            &["*".to_string()],
+        );
+        assert_eq!(everything.0.keys().collect::<Vec<_>>(), ["user-agent"]);
+    }
+
+    #[tokio::test]
+    async fn reads_serve_only_the_headers_describing_the_value() {
+        let client = Client::tracked(rocket::build().mount("/", routes![object]))
+            .await
+            .unwrap();
+        let response = client.get("/object/true").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::PNG));
+        let headers = response.headers();
+        assert_eq!(headers.get_one("cache-control"), Some("max-age=60"));
+        for unserved in ["authorization", "connection", "transfer-encoding"] {
+            assert_eq!(headers.get_one(unserved), None, "{unserved} was served");
+        }
+        assert_eq!(response.into_string().await.unwrap(), "png");
+
+        let response = client.get("/object/false").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::Binary));
+    }
+
+    #[tokio::test]
+    async fn mixed_outcomes_are_served_as_a_json_envelope() {
+        let client = Client::tracked(rocket::build().mount("/", routes![mixed]))
+            .await
+            .unwrap();
+        let response = client.get("/mixed").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+        let etag = kv_etag(tinycloud_core::hash::hash(b"alpha"));
+        assert_eq!(
+            body,
+            serde_json::json!([
+                { "type": "kvList", "keys": ["docs/a.txt", "docs/b.txt"], "truncated": false },
+                {
+                    "type": "kvMetadata",
+                    "metadata": { "content-type": "text/plain" },
+                    "etag": etag,
+                },
+                {
+                    "type": "kvRead",
+                    "key": "docs/a.txt",
+                    "metadata": { "content-type": "text/plain" },
+                    "etag": etag,
+                    "size": 5,
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn multiple_reads_are_served_as_multipart_mixed() {
+        let client = Client::tracked(rocket::build().mount("/", routes![many]))
+            .await
+            .unwrap();
+        let response = client.get("/many").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let content_type = response.content_type().unwrap();
+        assert!(content_type.top() == "multipart" && content_type.sub() == "mixed");
+        let boundary = content_type.param("boundary").unwrap().to_string();
+        let body = response.into_string().await.unwrap();
+
+        let parts = body
+            .strip_suffix(&format!("--{boundary}--\r\n"))
+            .unwrap()
+            .split(&format!("--{boundary}\r\n"))
+            .skip(1)
+            .collect::<Vec<_>>();
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].contains("Content-Location: a.txt\r\n"));
+        assert!(parts[0].contains("content-type: text/plain\r\n"));
+        assert!(parts[0].ends_with("\r\n\r\nalpha\r\n"));
+        assert!(parts[1].contains("Content-Location: missing.txt\r\n"));
+        assert!(parts[1].contains("X-Tinycloud-Status: 404\r\n"));
+        assert!(parts[1].ends_with("Content-Length: 0\r\n\r\n\r\n"));
+        assert!(parts[2].contains("Content-Location: c.txt\r\n"));
+        assert!(parts[2].ends_with("\r\n\r\ngamma\r\n"));
+    }
+}
@@ -1,7 +1,9 @@
 use rocket::{
+    data::{self, Data, FromData, Limits},
     http::Status,
     request::{FromRequest, Outcome, Request},
 };
+use serde::Deserialize;
 use std::convert::TryFrom;
 use tinycloud_auth::authorization::{
     TinyCloudDelegation, TinyCloudInvocation, TinyCloudRevocation,
@@ -11,20 +13,103 @@ use tinycloud_core::{
     util::{DelegationInfo, InvocationInfo, RevocationInfo},
 };
 
+use crate::{
+    config::{Config, HeaderLimits, SpacesConfig},
+    tracing::AccessLogSpaces,
+};
+
 pub struct AuthHeaderGetter<T>(pub SerializedEvent<T>);
 
+/// The configured `headers.max_authorization`, in bytes.
+pub(crate) fn max_authorization(request: &Request<'_>) -> u64 {
+    request
+        .rocket()
+        .state::<Config>()
+        .map(|config| config.headers.max_authorization)
+        .unwrap_or_else(|| HeaderLimits::default().max_authorization)
+        .as_u64()
+}
+
+/// The configured limits on the capabilities of delegations and invocations.
+fn spaces_config(request: &Request<'_>) -> Option<&SpacesConfig> {
+    request
+        .rocket()
+        .state::<Config>()
+        .map(|config| &config.spaces)
+}
+
+/// Refuse an authorization with more capabilities than `max` as soon as it
+/// is parsed, before it is rate limited or its signature is checked.
+fn check_capability_count<E>(
+    kind: &'static str,
+    count: usize,
+    max: Option<usize>,
+) -> Result<(), (Status, FromReqErr<E>)> {
+    match max {
+        Some(max) if count > max => Err((
+            Status::BadRequest,
+            FromReqErr::TooManyCapabilities { kind, count, max },
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn delegation_capabilities<E>(
+    request: &Request<'_>,
+    e: &SerializedEvent<DelegationInfo>,
+) -> Result<(), (Status, FromReqErr<E>)> {
+    check_capability_count(
+        "delegation",
+        e.0.capabilities.len(),
+        spaces_config(request).and_then(|spaces| spaces.max_capabilities_per_delegation),
+    )
+}
+
+fn invocation_capabilities<E>(
+    request: &Request<'_>,
+    e: &SerializedEvent<InvocationInfo>,
+) -> Result<(), (Status, FromReqErr<E>)> {
+    check_capability_count(
+        "invocation",
+        e.0.capabilities.len(),
+        spaces_config(request).and_then(|spaces| spaces.max_capabilities_per_invocation),
+    )
+}
+
+fn revocation_capabilities<E>(
+    _: &Request<'_>,
+    _: &SerializedEvent<RevocationInfo>,
+) -> Result<(), (Status, FromReqErr<E>)> {
+    Ok(())
+}
+
 macro_rules! impl_fromreq {
-    ($type:ident, $inter:ident, $name:tt) => {
+    ($type:ident, $inter:ident, $name:tt, $limit:expr) => {
+        impl_fromreq!($type, $inter, $name, $limit, |_, _| {});
+    };
+    ($type:ident, $inter:ident, $name:tt, $limit:expr, $on_success:expr) => {
         #[rocket::async_trait]
         impl<'r> FromRequest<'r> for AuthHeaderGetter<$type> {
             type Error = FromReqErr<<$type as TryFrom<$inter>>::Error>;
             async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-                match request
-                    .headers()
-                    .get_one($name)
-                    .map(SerializedEvent::<$type>::from_header_ser::<$inter>)
-                {
-                    Some(Ok(e)) => Outcome::Success(AuthHeaderGetter(e)),
+                let header = request.headers().get_one($name);
+                if let Some(size) = header.map(str::len) {
+                    let max = max_authorization(request);
+                    if size as u64 > max {
+                        return Outcome::Error((
+                            Status::RequestHeaderFieldsTooLarge,
+                            FromReqErr::TooLarge { size, max },
+                        ));
+                    }
+                }
+                match header.map(SerializedEvent::<$type>::from_header_ser::<$inter>) {
+                    Some(Ok(e)) => {
+                        if let Err(e) = $limit(request, &e) {
+                            return Outcome::Error(e);
+                        }
+                        $on_success(request, &e);
+                        Outcome::Success(AuthHeaderGetter(e))
+                    }
                     Some(Err(e)) => Outcome::Error((Status::Unauthorized, e)), // Revert back to Failure variant
                     None => Outcome::Forward(Status::Unauthorized),
                 }
@@ -33,9 +118,121 @@ macro_rules! impl_fromreq {
     };
 }
 
-impl_fromreq!(DelegationInfo, TinyCloudDelegation, "Authorization");
-impl_fromreq!(InvocationInfo, TinyCloudInvocation, "Authorization");
-impl_fromreq!(RevocationInfo, TinyCloudRevocation, "Authorization");
+fn record_delegation_spaces(req: &Request<'_>, e: &SerializedEvent<DelegationInfo>) {
+    AccessLogSpaces::record(req, e.0.spaces())
+}
+
+impl_fromreq!(
+    DelegationInfo,
+    TinyCloudDelegation,
+    "Authorization",
+    delegation_capabilities,
+    record_delegation_spaces
+);
+impl_fromreq!(
+    InvocationInfo,
+    TinyCloudInvocation,
+    "Authorization",
+    invocation_capabilities,
+    |req: &Request<'_>, e: &SerializedEvent<InvocationInfo>| AccessLogSpaces::record(
+        req,
+        e.0.spaces()
+    )
+);
+impl_fromreq!(
+    RevocationInfo,
+    TinyCloudRevocation,
+    "Authorization",
+    revocation_capabilities
+);
+
+/// JSON body carrying an encoded delegation or revocation, for authorizations
+/// too large to send in the `Authorization` header.
+#[derive(Deserialize)]
+struct AuthorizationBody {
+    authorization: String,
+}
+
+/// Read the encoded authorization from the body of `request`, forwarding when
+/// the body is empty.
+async fn authorization_body<'r, E>(
+    request: &'r Request<'_>,
+    mut data: Data<'r>,
+) -> data::Outcome<'r, String, FromReqErr<E>> {
+    if data.peek(1).await.is_empty() {
+        return data::Outcome::Forward((data, Status::Unauthorized));
+    }
+    let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+    let body = match data.open(limit).into_string().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(body) => {
+            return data::Outcome::Error((
+                Status::PayloadTooLarge,
+                FromReqErr::TooLarge {
+                    size: body.n.written as usize,
+                    max: limit.as_u64(),
+                },
+            ))
+        }
+        Err(e) => {
+            return data::Outcome::Error((Status::BadRequest, FromReqErr::Body(e.to_string())))
+        }
+    };
+    match serde_json::from_str::<AuthorizationBody>(&body) {
+        Ok(body) => data::Outcome::Success(body.authorization),
+        Err(e) => data::Outcome::Error((Status::BadRequest, FromReqErr::Body(e.to_string()))),
+    }
+}
+
+/// Accept the authorization from the `Authorization` header or, when the
+/// header is absent, from an `{"authorization": "..."}` JSON body. Only for
+/// routes which take no other body.
+macro_rules! impl_fromdata {
+    ($type:ident, $inter:ident, $limit:expr) => {
+        impl_fromdata!($type, $inter, $limit, |_, _| {});
+    };
+    ($type:ident, $inter:ident, $limit:expr, $on_success:expr) => {
+        #[rocket::async_trait]
+        impl<'r> FromData<'r> for AuthHeaderGetter<$type> {
+            type Error = FromReqErr<<$type as TryFrom<$inter>>::Error>;
+            async fn from_data(
+                request: &'r Request<'_>,
+                data: Data<'r>,
+            ) -> data::Outcome<'r, Self> {
+                if request.headers().contains("Authorization") {
+                    return match <Self as FromRequest<'r>>::from_request(request).await {
+                        Outcome::Success(e) => data::Outcome::Success(e),
+                        Outcome::Error(e) => data::Outcome::Error(e),
+                        Outcome::Forward(status) => data::Outcome::Forward((data, status)),
+                    };
+                }
+                let encoded = match authorization_body(request, data).await {
+                    data::Outcome::Success(encoded) => encoded,
+                    data::Outcome::Error(e) => return data::Outcome::Error(e),
+                    data::Outcome::Forward(f) => return data::Outcome::Forward(f),
+                };
+                match SerializedEvent::<$type>::from_header_ser::<$inter>(&encoded) {
+                    Ok(e) => {
+                        if let Err(e) = $limit(request, &e) {
+                            return data::Outcome::Error(e);
+                        }
+                        $on_success(request, &e);
+                        data::Outcome::Success(AuthHeaderGetter(e))
+                    }
+                    Err(e) => data::Outcome::Error((Status::Unauthorized, e)),
+                }
+            }
+        }
+    };
+}
+
+impl_fromdata!(
+    DelegationInfo,
+    TinyCloudDelegation,
+    delegation_capabilities,
+    record_delegation_spaces
+);
+impl_fromdata!(RevocationInfo, TinyCloudRevocation, revocation_capabilities);
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{decode_config, URL_SAFE_NO_PAD};
+use serde::Serialize;
+use std::{env, io::Read};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tinycloud_auth::authorization::{HeaderEncode, TinyCloudDelegation, TinyCloudInvocation};
+use tinycloud_core::util::{Capability, DelegationInfo, InvocationInfo};
+
+/// What a delegation or invocation token says, for debugging authorization.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Decoded {
+    kind: &'static str,
+    issuer: String,
+    audience: String,
+    capabilities: Vec<Capability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiry: Option<String>,
+    parents: Vec<String>,
+    signature_type: String,
+}
+
+/// Decode `token`, telling invocations from delegations by their format: a
+/// SIWE CACAO is always a delegation, and a UCAN is an invocation when it is
+/// addressed to its own issuer.
+fn decode(token: &str) -> Result<Decoded> {
+    let token = token.trim();
+    if !token.contains('.') {
+        return delegation(token);
+    }
+    let (ucan, _) = TinyCloudInvocation::decode(token).context("invalid UCAN")?;
+    let issuer = ucan.payload().issuer.to_string();
+    if issuer.split('#').next() == Some(ucan.payload().audience.as_str()) {
+        invocation(token, ucan)
+    } else {
+        delegation(token)
+    }
+}
+
+fn delegation(token: &str) -> Result<Decoded> {
+    let (delegation, _) = TinyCloudDelegation::decode(token).context("invalid delegation")?;
+    let signature_type = match &delegation {
+        TinyCloudDelegation::Ucan(_) => jwt_algorithm(token)?,
+        TinyCloudDelegation::Cacao(_) => "eip191 (SIWE)".to_string(),
+    };
+    let info = DelegationInfo::try_from(delegation).context("invalid delegation")?;
+    Ok(Decoded {
+        kind: "delegation",
+        issuer: info.delegator,
+        audience: info.delegate,
+        capabilities: info.capabilities,
+        not_before: info.not_before.map(timestamp).transpose()?,
+        expiry: info.expiry.map(timestamp).transpose()?,
+        parents: info.parents.iter().map(ToString::to_string).collect(),
+        signature_type,
+    })
+}
+
+fn invocation(token: &str, ucan: TinyCloudInvocation) -> Result<Decoded> {
+    let audience = ucan.payload().audience.to_string();
+    let expiry = seconds(ucan.payload().expiration.as_seconds())?;
+    let not_before = ucan
+        .payload()
+        .not_before
+        .map(|t| seconds(t.as_seconds()))
+        .transpose()?;
+    let info = InvocationInfo::try_from(ucan).context("invalid invocation")?;
+    Ok(Decoded {
+        kind: "invocation",
+        issuer: info.invoker,
+        audience,
+        capabilities: info.capabilities,
+        not_before,
+        expiry: Some(expiry),
+        parents: info.parents.iter().map(ToString::to_string).collect(),
+        signature_type: jwt_algorithm(token)?,
+    })
+}
+
+/// The `alg` of a JWT's header.
+fn jwt_algorithm(token: &str) -> Result<String> {
+    let header = token.split('.').next().unwrap_or_default();
+    let header: serde_json::Value = serde_json::from_slice(
+        &decode_config(header, URL_SAFE_NO_PAD).context("invalid JWT header")?,
+    )
+    .context("invalid JWT header")?;
+    header["alg"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("JWT header has no alg"))
+}
+
+fn seconds(seconds: f64) -> Result<String> {
+    timestamp(
+        OffsetDateTime::from_unix_timestamp_nanos((seconds * 1_000_000_000.0) as i128)
+            .context("timestamp out of range")?,
+    )
+}
+
+fn timestamp(time: OffsetDateTime) -> Result<String> {
+    Ok(time.format(&Rfc3339)?)
+}
+
+fn main() -> Result<()> {
+    let token = match env::args().nth(1) {
+        Some(token) if token != "-" => token,
+        _ => {
+            let mut token = String::new();
+            std::io::stdin()
+                .read_to_string(&mut token)
+                .context("reading the token from stdin")?;
+            token
+        }
+    };
+    serde_json::to_writer_pretty(std::io::stdout(), &decode(&token)?)?;
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tinycloud_auth::{
+        authorization::{make_invocation, InvocationOptions},
+        resource::SpaceId,
+        ssi::{
+            claims::jwt::NumericDate,
+            dids::{DIDBuf, DIDURLBuf},
+            jwk::{Algorithm, JWK},
+            ucan::Payload,
+        },
+        ucan_capabilities_object::Capabilities,
+    };
+    use tinycloud_core::resolver::DID_METHODS;
+
+    #[test]
+    fn decodes_delegations_and_invocations() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.algorithm = Some(Algorithm::EdDSA);
+        let did = DID_METHODS.generate(&jwk, "key").unwrap();
+        let space = SpaceId::new(did.clone(), "default".parse().unwrap());
+        let did = did.to_string();
+        let vm = format!("{did}#{}", did.rsplit_once(':').unwrap().1);
+        let resource = space.to_resource("kv".parse().unwrap(), None, None, None);
+        let mut attenuation = Capabilities::new();
+        attenuation.with_actions(
+            resource.as_uri(),
+            [("tinycloud.kv/get".parse().unwrap(), [])],
+        );
+        let delegate = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+        let token = TinyCloudDelegation::Ucan(Box::new(
+            Payload {
+                issuer: vm.parse::<DIDURLBuf>().unwrap(),
+                audience: delegate.parse::<DIDBuf>().unwrap(),
+                not_before: None,
+                expiration: NumericDate::try_from_seconds(4_102_444_800.0).unwrap(),
+                nonce: Some("decode-test".into()),
+                facts: None,
+                proof: vec![],
+                attenuation,
+            }
+            .sign(Algorithm::EdDSA, &jwk)
+            .unwrap(),
+        ))
+        .encode()
+        .unwrap();
+
+        let decoded = decode(&format!("{token}\n")).unwrap();
+        assert_eq!(decoded.kind, "delegation");
+        assert_eq!(decoded.issuer, did);
+        assert_eq!(decoded.audience, delegate);
+        assert_eq!(decoded.capabilities.len(), 1);
+        assert_eq!(
+            decoded.capabilities[0].ability.to_string(),
+            "tinycloud.kv/get"
+        );
+        assert_eq!(decoded.expiry.as_deref(), Some("2100-01-01T00:00:00Z"));
+        assert!(decoded.parents.is_empty());
+        assert_eq!(decoded.signature_type, "EdDSA");
+
+        let parent = TinyCloudDelegation::decode(&token).unwrap();
+        let parent = tinycloud_core::hash::hash(&parent.1).to_event_cid();
+        let invocation = make_invocation(
+            [(resource, ["tinycloud.kv/get".parse().unwrap()])],
+            &parent,
+            &jwk,
+            &vm,
+            4_102_444_800.0,
+            InvocationOptions::default(),
+        )
+        .unwrap()
+        .encode()
+        .unwrap();
+
+        let decoded = decode(&invocation).unwrap();
+        assert_eq!(decoded.kind, "invocation");
+        assert_eq!(decoded.issuer, did);
+        assert_eq!(decoded.audience, did);
+        assert_eq!(decoded.parents, vec![parent.to_string()]);
+        assert_eq!(decoded.signature_type, "EdDSA");
+
+        assert!(decode("not a token").is_err());
+    }
+}
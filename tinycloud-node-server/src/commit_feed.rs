@@ -0,0 +1,94 @@
+use crate::commit_webhook::CommitEvent;
+use dashmap::DashMap;
+use std::sync::Arc;
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{
+    commit_observer::{CommitKind, CommitObserver},
+    Commit,
+};
+use tokio::sync::broadcast;
+
+/// Live commits for each space with at least one subscriber.
+///
+/// Each space gets its own broadcast channel when first subscribed to, and
+/// loses it at the first commit after its last subscriber is gone. A
+/// subscriber that falls more than `capacity` commits behind misses the
+/// oldest ones.
+#[derive(Debug, Clone)]
+pub struct CommitFeed {
+    capacity: usize,
+    spaces: Arc<DashMap<SpaceId, broadcast::Sender<CommitEvent>>>,
+}
+
+impl CommitFeed {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            spaces: Arc::default(),
+        }
+    }
+
+    pub fn subscribe(&self, space: &SpaceId) -> broadcast::Receiver<CommitEvent> {
+        self.spaces
+            .entry(space.clone())
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .subscribe()
+    }
+}
+
+impl CommitObserver for CommitFeed {
+    fn committed(&self, kind: CommitKind, space: &SpaceId, commit: &Commit) {
+        let unheard = match self.spaces.get(space) {
+            Some(sender) => sender.send(CommitEvent::new(kind, space, commit)).is_err(),
+            None => return,
+        };
+        if unheard {
+            self.spaces
+                .remove_if(space, |_, sender| sender.receiver_count() == 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tinycloud_core::hash::hash;
+
+    fn commit(seq: i64) -> Commit {
+        Commit {
+            rev: hash(&seq.to_be_bytes()),
+            seq,
+            committed_events: vec![hash(b"event")],
+            consumed_epochs: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn commits_reach_subscribers_of_their_space_only() {
+        let feed = CommitFeed::new(2);
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let other: SpaceId = "tinycloud:key:test:other".parse().unwrap();
+        let mut receiver = feed.subscribe(&space);
+
+        feed.committed(CommitKind::Write, &other, &commit(0));
+        feed.committed(CommitKind::Delegation, &space, &commit(1));
+        let event = receiver.recv().await.unwrap();
+        assert_eq!((event.kind, event.seq), (CommitKind::Delegation, 1));
+
+        // a lagging subscriber loses the oldest commits, not the newest
+        for seq in 2..5 {
+            feed.committed(CommitKind::Write, &space, &commit(seq));
+        }
+        assert!(matches!(
+            receiver.recv().await,
+            Err(broadcast::error::RecvError::Lagged(1))
+        ));
+        assert_eq!(receiver.recv().await.unwrap().seq, 3);
+        assert_eq!(receiver.recv().await.unwrap().seq, 4);
+
+        // the channel goes away once nobody is listening
+        drop(receiver);
+        feed.committed(CommitKind::Write, &space, &commit(5));
+        assert!(feed.spaces.is_empty());
+    }
+}
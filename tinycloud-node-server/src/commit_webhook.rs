@@ -0,0 +1,203 @@
+use crate::config::HooksConfig;
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{
+    commit_observer::{CommitKind, CommitObserver},
+    Commit,
+};
+use tokio::sync::mpsc;
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The JSON body posted to the commit webhook for one commit to one space.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitEvent {
+    #[serde(rename = "type")]
+    pub kind: CommitKind,
+    pub space: String,
+    /// The space's new epoch.
+    pub rev: String,
+    pub seq: i64,
+    pub committed_events: Vec<String>,
+}
+
+impl CommitEvent {
+    pub(crate) fn new(kind: CommitKind, space: &SpaceId, commit: &Commit) -> Self {
+        Self {
+            kind,
+            space: space.to_string(),
+            rev: commit.rev.to_block_cid().to_string(),
+            seq: commit.seq,
+            committed_events: commit
+                .committed_events
+                .iter()
+                .map(|event| event.to_event_cid().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Posts every commit on the node to `hooks.commit_webhook_url`.
+///
+/// Commits are queued and posted in order by a single background task, which
+/// retries a failed post with exponential backoff up to
+/// `hooks.webhook_max_attempts` times. When the queue is full the commit is
+/// dropped, so a slow endpoint never holds up writes.
+#[derive(Debug)]
+pub struct CommitWebhook {
+    queue: mpsc::Sender<CommitEvent>,
+}
+
+impl CommitWebhook {
+    /// Start posting commits, if a commit webhook is configured.
+    pub fn spawn(config: &HooksConfig) -> Result<Option<Self>> {
+        let Some(url) = config.commit_webhook_url.clone() else {
+            return Ok(None);
+        };
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.webhook_timeout_seconds.max(1)))
+            .build()
+            .context("building commit webhook HTTP client")?;
+        let (queue, events) = mpsc::channel(config.commit_webhook_queue_capacity.max(1));
+        tokio::spawn(deliver(
+            client,
+            url,
+            config.webhook_max_attempts.max(1),
+            events,
+        ));
+        Ok(Some(Self { queue }))
+    }
+}
+
+impl CommitObserver for CommitWebhook {
+    fn committed(&self, kind: CommitKind, space: &SpaceId, commit: &Commit) {
+        if let Err(mpsc::error::TrySendError::Full(event)) =
+            self.queue.try_send(CommitEvent::new(kind, space, commit))
+        {
+            tracing::warn!(
+                space = %event.space,
+                rev = %event.rev,
+                "commit webhook queue is full, dropping event"
+            );
+            observe("dropped");
+        }
+    }
+}
+
+async fn deliver(
+    client: Client,
+    url: String,
+    max_attempts: usize,
+    mut events: mpsc::Receiver<CommitEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        let mut delay = FIRST_RETRY_DELAY;
+        for attempt in 1..=max_attempts {
+            match client
+                .post(&url)
+                .json(&event)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => {
+                    observe("delivered");
+                    break;
+                }
+                Err(error) if attempt < max_attempts => {
+                    tracing::debug!(%error, attempt, "commit webhook post failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        %error,
+                        space = %event.space,
+                        rev = %event.rev,
+                        "commit webhook post failed, dropping event"
+                    );
+                    observe("failed");
+                }
+            }
+        }
+    }
+}
+
+fn observe(outcome: &'static str) {
+    if crate::prometheus::enabled() {
+        crate::prometheus::COMMIT_WEBHOOK_EVENTS
+            .with_label_values(&[outcome])
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hyper::{
+        body::to_bytes,
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use std::{convert::Infallible, net::TcpListener};
+    use tinycloud_core::hash::hash;
+
+    #[tokio::test]
+    async fn commits_are_posted_as_json_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (tx, mut bodies) = mpsc::unbounded_channel();
+        let make_service = make_service_fn(move |_| {
+            let tx = tx.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let tx = tx.clone();
+                    async move {
+                        let body = to_bytes(request.into_body()).await.unwrap();
+                        let _ = tx.send(serde_json::from_slice::<serde_json::Value>(&body));
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_service));
+
+        let webhook = CommitWebhook::spawn(&HooksConfig {
+            commit_webhook_url: Some(format!("http://{address}/commits")),
+            ..HooksConfig::default()
+        })
+        .unwrap()
+        .expect("a commit webhook is configured");
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let commit = Commit {
+            rev: hash(b"epoch"),
+            seq: 7,
+            committed_events: vec![hash(b"invocation")],
+            consumed_epochs: vec![hash(b"parent")],
+        };
+        webhook.committed(CommitKind::Write, &space, &commit);
+
+        let body = tokio::time::timeout(Duration::from_secs(5), bodies.recv())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "write",
+                "space": "tinycloud:key:test:default",
+                "rev": hash(b"epoch").to_block_cid().to_string(),
+                "seq": 7,
+                "committedEvents": [hash(b"invocation").to_event_cid().to_string()],
+            })
+        );
+        assert!(CommitWebhook::spawn(&HooksConfig::default())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use flate2::{write::GzEncoder, Compression};
+use rocket::{futures::io::AsyncRead, Request};
+use std::{
+    io::{self, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tinycloud_core::types::Metadata;
+
+use crate::config::Config;
+
+/// Bytes read from the value per compressed block.
+const CHUNK: usize = 16 * 1024;
+
+/// Whether a `len` byte value with `metadata` is served differently to clients
+/// accepting gzip: compression is enabled, the value is at least
+/// `compression.min_size` and its content type is textual.
+pub(crate) fn compressible(request: &Request<'_>, metadata: &Metadata, len: u64) -> bool {
+    let config = request
+        .rocket()
+        .state::<Config>()
+        .map(|config| config.compression.clone())
+        .unwrap_or_default();
+    let header = |name: &str| {
+        metadata
+            .0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    config.enabled
+        && len >= config.min_size.as_u64()
+        // already encoded by the writer
+        && header("content-encoding").is_none()
+        && header("content-type").is_some_and(textual)
+}
+
+fn textual(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/x-ndjson"
+                | "application/xml"
+                | "application/javascript"
+                | "application/ecmascript"
+        )
+}
+
+/// Whether the request's `Accept-Encoding` allows gzip, explicitly or through
+/// `*`, with a non-zero quality.
+pub(crate) fn accepts_gzip(request: &Request<'_>) -> bool {
+    let mut gzip = None;
+    let mut any = None;
+    for coding in request
+        .headers()
+        .get("Accept-Encoding")
+        .flat_map(|header| header.split(','))
+    {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let accepted = !params.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                == Some(0.0)
+        });
+        if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            gzip = Some(accepted);
+        } else if name == "*" {
+            any = Some(accepted);
+        }
+    }
+    gzip.or(any).unwrap_or(false)
+}
+
+/// Gzips a value as it is read, so compressed reads stream like plain ones.
+pub(crate) struct Gzip {
+    inner: Pin<Box<dyn AsyncRead + Send>>,
+    /// `None` once the value is read and the gzip trailer written.
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    chunk: Box<[u8]>,
+    compressed: Vec<u8>,
+    served: usize,
+}
+
+impl Gzip {
+    pub fn new(inner: impl AsyncRead + Send + 'static) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            encoder: Some(GzEncoder::new(Vec::new(), Compression::default())),
+            chunk: vec![0; CHUNK].into_boxed_slice(),
+            compressed: Vec::new(),
+            served: 0,
+        }
+    }
+}
+
+impl AsyncRead for Gzip {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let pending = &this.compressed[this.served..];
+            if !pending.is_empty() {
+                let n = pending.len().min(buf.len());
+                buf[..n].copy_from_slice(&pending[..n]);
+                this.served += n;
+                return Poll::Ready(Ok(n));
+            }
+            let Some(encoder) = this.encoder.as_mut() else {
+                return Poll::Ready(Ok(0));
+            };
+            let read = ready!(this.inner.as_mut().poll_read(cx, &mut this.chunk))?;
+            if read == 0 {
+                encoder.try_finish()?;
+            } else {
+                encoder.write_all(&this.chunk[..read])?;
+            }
+            this.compressed.clear();
+            this.served = 0;
+            std::mem::swap(&mut this.compressed, encoder.get_mut());
+            if read == 0 {
+                this.encoder = None;
+            }
+        }
+    }
+}
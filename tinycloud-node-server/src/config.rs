@@ -11,8 +11,18 @@ use serde_with::{
     formats::Unpadded,
     serde_as, FromInto,
 };
-use std::{fs, path::PathBuf};
-use tinycloud_core::keys::StaticSecret;
+use std::{
+    fs,
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+use tinycloud_core::{
+    keys::StaticSecret,
+    models::delegation::DelegationLimits,
+    resolver::{ResolverOptions, WebResolverOptions},
+    sea_orm::ConnectOptions,
+};
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Hash, PartialEq, Eq)]
 pub struct Config {
@@ -35,7 +45,19 @@ pub struct Config {
     #[serde(default)]
     pub public_spaces: PublicSpacesConfig,
     #[serde(default)]
+    pub rate_limit: SpaceRateLimitConfig,
+    #[serde(default)]
     pub share_email: ShareEmailConfig,
+    #[serde(default)]
+    pub timeouts: RequestTimeouts,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub resolver: ResolverConfig,
+    #[serde(default)]
+    pub headers: HeaderLimits,
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 /// Production exact-email composition.  The capability remains unavailable
@@ -616,6 +638,213 @@ pub struct PublicSpacesConfig {
     pub storage_limit: ByteUnit,
 }
 
+/// Per-space token bucket for authenticated `/invoke`, `/sql` and
+/// `/delegate` requests, and a per-client one charged before those requests
+/// are verified. Off unless `enabled` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct SpaceRateLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_space_requests_per_second")]
+    pub requests_per_second: u32,
+    #[serde(default = "default_space_rate_limit_burst")]
+    pub burst: u32,
+    #[serde(default = "default_client_requests_per_second")]
+    pub client_requests_per_second: u32,
+    #[serde(default = "default_client_rate_limit_burst")]
+    pub client_burst: u32,
+}
+
+fn default_space_requests_per_second() -> u32 {
+    50
+}
+
+fn default_space_rate_limit_burst() -> u32 {
+    100
+}
+
+fn default_client_requests_per_second() -> u32 {
+    100
+}
+
+fn default_client_rate_limit_burst() -> u32 {
+    200
+}
+
+impl Default for SpaceRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_second: default_space_requests_per_second(),
+            burst: default_space_rate_limit_burst(),
+            client_requests_per_second: default_client_requests_per_second(),
+            client_burst: default_client_rate_limit_burst(),
+        }
+    }
+}
+
+/// How long `/delegate` and `/invoke` wait on the node before failing with
+/// `504 Gateway Timeout`. Work that has started committing is never given up.
+/// `0` waits indefinitely.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    /// Seconds to verify and store a delegation, which may resolve DIDs over
+    /// the network.
+    #[serde(default = "default_delegate_timeout_secs")]
+    pub delegate_secs: u64,
+    /// Seconds to apply an invocation once its body has been received,
+    /// including writes to the block store.
+    #[serde(default = "default_invoke_timeout_secs")]
+    pub invoke_secs: u64,
+}
+
+fn default_delegate_timeout_secs() -> u64 {
+    30
+}
+
+fn default_invoke_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        Self {
+            delegate_secs: default_delegate_timeout_secs(),
+            invoke_secs: default_invoke_timeout_secs(),
+        }
+    }
+}
+
+/// Size limits on request headers. Delegations and invocations travel in the
+/// `Authorization` header, which grows with the ReCap of a SIWE session and
+/// the facts and caveats of a UCAN.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct HeaderLimits {
+    /// Largest `Authorization` header accepted. Longer headers are rejected
+    /// with `431 Request Header Fields Too Large`.
+    #[serde(default = "default_max_authorization")]
+    pub max_authorization: ByteUnit,
+}
+
+fn default_max_authorization() -> ByteUnit {
+    ByteUnit::Kibibyte(64)
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        Self {
+            max_authorization: default_max_authorization(),
+        }
+    }
+}
+
+/// Compression of kv reads for clients sending `Accept-Encoding: gzip`.
+/// Only textual content types are compressed; values are stored as written.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct CompressionConfig {
+    #[serde(default = "default_compression_enabled")]
+    pub enabled: bool,
+    /// Smallest value compressed. Smaller values are served as stored.
+    #[serde(default = "default_compression_min_size")]
+    pub min_size: ByteUnit,
+}
+
+fn default_compression_enabled() -> bool {
+    true
+}
+
+fn default_compression_min_size() -> ByteUnit {
+    ByteUnit::Kibibyte(1)
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_compression_enabled(),
+            min_size: default_compression_min_size(),
+        }
+    }
+}
+
+/// Retention of the responses to `/invoke` requests sent with an
+/// `Idempotency-Key`.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct IdempotencyConfig {
+    /// Seconds a key's response is replayed to retries.
+    #[serde(default = "default_idempotency_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Keys held at once.
+    #[serde(default = "default_idempotency_capacity")]
+    pub capacity: usize,
+}
+
+fn default_idempotency_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_idempotency_capacity() -> usize {
+    10_000
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_idempotency_ttl_secs(),
+            capacity: default_idempotency_capacity(),
+        }
+    }
+}
+
+/// Endpoints DIDs are resolved with, for nodes which cannot reach the
+/// built-in ones or should use their own.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Hash, PartialEq, Eq)]
+pub struct ResolverConfig {
+    /// Ethereum JSON-RPC endpoint `did:ens` names are looked up with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ens_rpc_url: Option<String>,
+    /// Milliseconds a DID resolution may take. Overrides
+    /// `TINYCLOUD_DID_RESOLUTION_TIMEOUT_MS` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// HTTP client used for `did:web`. The built-in client is used if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebResolverConfig>,
+    /// Show the primary ENS names of `did:pkh:eip155` accounts in delegation
+    /// listings. Needs `ens_rpc_url`.
+    #[serde(default)]
+    pub ens_names: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct WebResolverConfig {
+    /// Seconds a `did:web` document request may take.
+    #[serde(default = "default_web_resolver_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Proxy `did:web` documents are fetched through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+fn default_web_resolver_timeout_secs() -> u64 {
+    10
+}
+
+impl ResolverConfig {
+    pub fn options(&self) -> ResolverOptions {
+        ResolverOptions {
+            ens_rpc_url: self.ens_rpc_url.clone(),
+            web: self.web.as_ref().map(|web| WebResolverOptions {
+                timeout: Duration::from_secs(web.timeout_secs),
+                user_agent: web.user_agent.clone(),
+                proxy: web.proxy.clone(),
+            }),
+            timeout: self.timeout_ms.map(Duration::from_millis),
+        }
+    }
+}
+
 fn default_rate_limit_per_minute() -> u32 {
     60
 }
@@ -678,6 +907,14 @@ pub struct HooksConfig {
     pub webhook_timeout_seconds: u64,
     #[serde(default = "default_hooks_webhook_max_attempts")]
     pub webhook_max_attempts: usize,
+    /// Receives a POST for every write, delegation and revocation committed
+    /// to any space on this node.
+    #[serde(default)]
+    pub commit_webhook_url: Option<String>,
+    /// Commits waiting to be posted to `commit_webhook_url`; commits made
+    /// while the queue is full are dropped.
+    #[serde(default = "default_hooks_commit_webhook_queue_capacity")]
+    pub commit_webhook_queue_capacity: usize,
 }
 
 fn default_hooks_max_ticket_ttl_seconds() -> u64 {
@@ -708,6 +945,10 @@ fn default_hooks_webhook_max_attempts() -> usize {
     5
 }
 
+fn default_hooks_commit_webhook_queue_capacity() -> usize {
+    1024
+}
+
 impl Default for HooksConfig {
     fn default() -> Self {
         Self {
@@ -719,6 +960,8 @@ impl Default for HooksConfig {
             ),
             webhook_timeout_seconds: default_hooks_webhook_timeout_seconds(),
             webhook_max_attempts: default_hooks_webhook_max_attempts(),
+            commit_webhook_url: None,
+            commit_webhook_queue_capacity: default_hooks_commit_webhook_queue_capacity(),
         }
     }
 }
@@ -772,6 +1015,11 @@ impl Default for TeeConfig {
 pub struct Static {
     #[serde_as(as = "Option<Base64<UrlSafe, Unpadded>>")]
     secret: Option<Vec<u8>>,
+    /// Secrets retired by earlier rotations, newest first. Spaces already
+    /// hosted under one of these keep their key; new spaces use `secret`.
+    #[serde(default)]
+    #[serde_as(as = "Vec<Base64<UrlSafe, Unpadded>>")]
+    previous: Vec<Vec<u8>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -786,7 +1034,9 @@ impl TryFrom<Static> for StaticSecret {
     type Error = SecretInitError;
     fn try_from(s: Static) -> Result<Self, Self::Error> {
         let secret = s.secret.ok_or(SecretInitError::MissingSecret)?;
-        StaticSecret::new(secret).map_err(|v| SecretInitError::NotEnoughEntropy(v.len()))
+        StaticSecret::new(secret)
+            .and_then(|secret| secret.with_previous(s.previous))
+            .map_err(|v| SecretInitError::NotEnoughEntropy(v.len()))
     }
 }
 
@@ -806,13 +1056,123 @@ pub enum LoggingFormat {
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Tracing {
     pub traceheader: String,
+    /// Export spans over OTLP. Implied by `endpoint`; without one, the
+    /// exporter is configured from the standard `OTEL_EXPORTER_OTLP_*`
+    /// environment variables.
     pub enabled: bool,
+    /// The collector spans are exported to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub protocol: OtlpProtocol,
+    /// The fraction of traces started by this node which are sampled.
+    /// Requests which arrive in a trace follow its sampling decision.
+    #[serde(default)]
+    pub sampling_ratio: SamplingRatio,
+}
+
+impl Tracing {
+    pub fn exports(&self) -> bool {
+        self.enabled || self.endpoint.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum OtlpProtocol {
+    #[default]
+    Grpc,
+    Http,
+}
+
+/// A sampling probability, between 0 and 1.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct SamplingRatio(f64);
+
+impl SamplingRatio {
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for SamplingRatio {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl TryFrom<f64> for SamplingRatio {
+    type Error = String;
+    fn try_from(ratio: f64) -> Result<Self, Self::Error> {
+        if (0.0..=1.0).contains(&ratio) {
+            Ok(Self(ratio))
+        } else {
+            Err(format!("sampling ratio {ratio} is not between 0 and 1"))
+        }
+    }
+}
+
+impl From<SamplingRatio> for f64 {
+    fn from(ratio: SamplingRatio) -> Self {
+        ratio.0
+    }
+}
+
+// the ratio is never NaN
+impl Eq for SamplingRatio {}
+
+impl std::hash::Hash for SamplingRatio {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Hash, PartialEq, Eq)]
 pub struct SpacesConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allowlist: Option<SpaceAllowListService>,
+    /// Prune expired delegations, past the clock skew, every this many
+    /// seconds. Disabled if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_delegations_interval_secs: Option<u64>,
+    /// Reject delegations whose parent chain, counting the delegation itself,
+    /// is longer than this. Unbounded if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delegation_depth: Option<u32>,
+    /// Reject delegations valid for longer than this many seconds after
+    /// issuance. Unbounded if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delegation_ttl_secs: Option<u64>,
+    /// Accept delegations up to this many seconds before they become valid
+    /// or after they expire, for clients whose clocks are slightly off.
+    /// No tolerance if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegation_clock_skew_secs: Option<u64>,
+    /// Reject delegations granting more than this many capabilities.
+    /// Unbounded if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_capabilities_per_delegation: Option<usize>,
+    /// Reject invocations of more than this many capabilities. Unbounded if
+    /// unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_capabilities_per_invocation: Option<usize>,
+}
+
+impl SpacesConfig {
+    pub fn delegation_limits(&self) -> DelegationLimits {
+        DelegationLimits {
+            max_depth: self.max_delegation_depth,
+            max_ttl: self
+                .max_delegation_ttl_secs
+                .map(|secs| time::Duration::seconds(secs.min(i64::MAX as u64) as i64)),
+            clock_skew: self
+                .delegation_clock_skew_secs
+                .map(|secs| time::Duration::seconds(secs.min(i64::MAX as u64) as i64))
+                .unwrap_or_default(),
+            max_capabilities: self.max_capabilities_per_delegation,
+            max_invocation_capabilities: self.max_capabilities_per_invocation,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
@@ -821,17 +1181,33 @@ pub struct SqlStorageConfig {
     pub path: Option<String>,
     #[serde(default = "default_sql_memory_threshold")]
     pub memory_threshold: ByteUnit,
+    /// Seconds a database actor waits for a request before shutting down.
+    #[serde(default = "default_sql_idle_timeout")]
+    pub idle_timeout_secs: NonZeroU64,
+    /// Requests that may queue for one database before callers wait.
+    #[serde(default = "default_sql_channel_capacity")]
+    pub channel_capacity: NonZeroUsize,
 }
 
 fn default_sql_memory_threshold() -> ByteUnit {
     ByteUnit::Mebibyte(10)
 }
 
+fn default_sql_idle_timeout() -> NonZeroU64 {
+    NonZeroU64::new(300).expect("non-zero")
+}
+
+fn default_sql_channel_capacity() -> NonZeroUsize {
+    NonZeroUsize::new(32).expect("non-zero")
+}
+
 impl Default for SqlStorageConfig {
     fn default() -> Self {
         Self {
             path: None,
             memory_threshold: default_sql_memory_threshold(),
+            idle_timeout_secs: default_sql_idle_timeout(),
+            channel_capacity: default_sql_channel_capacity(),
         }
     }
 }
@@ -886,11 +1262,127 @@ pub struct Storage {
     pub staging: BlockStage,
     #[serde(default)]
     pub database: Option<String>,
+    /// Read replica of `database`, serving KV reads and lists made outside
+    /// an invocation. All reads use `database` when unset.
+    #[serde(default)]
+    pub replica: Option<String>,
+    /// Connection pool bound. Defaults to 1 for SQLite, which cannot run
+    /// concurrent write transactions, and 100 otherwise.
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    #[serde(default)]
+    pub min_connections: u32,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// Close pooled connections idle for this long.
+    #[serde(default = "default_connection_idle_timeout")]
+    pub idle_timeout_secs: u64,
     pub limit: Option<ByteUnit>,
     #[serde(default)]
     pub sql: SqlStorageConfig,
     #[serde(default)]
     pub duckdb: DuckDbStorageConfig,
+    /// Infer a `content-type` from the leading bytes of a put value when the
+    /// client does not send one.
+    #[serde(default)]
+    pub sniff_content_type: bool,
+    #[serde(default)]
+    pub cache: BlockCacheConfig,
+    /// Largest request body accepted by `/invoke`, for a single put or a
+    /// whole multipart batch. Longer bodies are rejected with 413.
+    #[serde(default = "default_max_upload")]
+    pub max_upload: ByteUnit,
+    /// Largest chunk of a resumable upload, sent in one `PUT /upload/<id>`.
+    /// The whole upload is still bounded by `max_upload`.
+    #[serde(default = "default_max_upload_chunk")]
+    pub max_upload_chunk: ByteUnit,
+    /// Seconds a resumable upload is kept without a request before it and
+    /// its staged content are dropped.
+    #[serde(default = "default_upload_expiry")]
+    pub upload_expiry_secs: u64,
+    /// Resumable uploads one space may have open at once.
+    #[serde(default = "default_max_open_uploads")]
+    pub max_open_uploads: usize,
+    /// Request headers kept as the metadata of a kv put; an entry ending in
+    /// `*` keeps every header starting with the rest of it. Credentials and
+    /// other headers describing the request are never kept.
+    #[serde(default = "default_metadata_headers")]
+    pub metadata_headers: Vec<String>,
+    /// Values of one invocation written to the block store at once, so a
+    /// multipart batch is not bound by the latency of each write.
+    #[serde(default = "default_persist_concurrency")]
+    pub persist_concurrency: usize,
+}
+
+fn default_connect_timeout() -> u64 {
+    30
+}
+
+fn default_connection_idle_timeout() -> u64 {
+    600
+}
+
+fn default_max_upload() -> ByteUnit {
+    ByteUnit::Gibibyte(1)
+}
+
+fn default_max_upload_chunk() -> ByteUnit {
+    ByteUnit::Mebibyte(16)
+}
+
+fn default_upload_expiry() -> u64 {
+    3600
+}
+
+fn default_max_open_uploads() -> usize {
+    16
+}
+
+fn default_persist_concurrency() -> usize {
+    tinycloud_core::db::DEFAULT_PERSIST_CONCURRENCY
+}
+
+fn default_metadata_headers() -> Vec<String> {
+    [
+        "content-type",
+        "content-disposition",
+        "cache-control",
+        "x-*",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// In-memory LRU cache of small blocks in front of the block store. Off
+/// unless `enabled` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct BlockCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Total size of the cached blocks.
+    #[serde(default = "default_block_cache_capacity")]
+    pub capacity: ByteUnit,
+    /// Blocks larger than this are always read from the block store.
+    #[serde(default = "default_block_cache_max_entry_size")]
+    pub max_entry_size: ByteUnit,
+}
+
+fn default_block_cache_capacity() -> ByteUnit {
+    ByteUnit::Mebibyte(64)
+}
+
+fn default_block_cache_max_entry_size() -> ByteUnit {
+    ByteUnit::Mebibyte(1)
+}
+
+impl Default for BlockCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: default_block_cache_capacity(),
+            max_entry_size: default_block_cache_max_entry_size(),
+        }
+    }
 }
 
 fn default_datadir() -> PathBuf {
@@ -919,7 +1411,9 @@ impl Storage {
         // Resolve blocks path if it's the Local variant with the empty default
         if let BlockConfig::B(ref fs) = self.blocks {
             if fs.path().as_os_str().is_empty() {
-                self.blocks = BlockConfig::B(FileSystemConfig::new(dir.join("blocks")));
+                self.blocks = BlockConfig::B(
+                    FileSystemConfig::new(dir.join("blocks")).with_shard_depth(fs.shard_depth()),
+                );
             }
         }
 
@@ -934,6 +1428,43 @@ impl Storage {
             .as_deref()
             .expect("Storage::resolve() must be called before accessing database")
     }
+
+    /// Check the connection pool settings. Panics if called before resolve().
+    pub fn validate_pool(&self) -> Result<(), &'static str> {
+        let max_connections = self.max_connections_for(self.database());
+        if max_connections == 0 {
+            return Err("storage.max_connections must be at least 1");
+        }
+        if self.database().starts_with("sqlite") && max_connections > 1 {
+            return Err("storage.max_connections must be 1 for a SQLite database");
+        }
+        if self.min_connections > max_connections {
+            return Err("storage.min_connections must not exceed storage.max_connections");
+        }
+        if self.connect_timeout_secs == 0 {
+            return Err("storage.connect_timeout_secs must be at least 1");
+        }
+        if self.idle_timeout_secs == 0 {
+            return Err("storage.idle_timeout_secs must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Connection options for `url` with the configured pool settings.
+    pub fn connect_options(&self, url: &str) -> ConnectOptions {
+        let mut options = ConnectOptions::from(url);
+        options
+            .max_connections(self.max_connections_for(url))
+            .min_connections(self.min_connections)
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .idle_timeout(Duration::from_secs(self.idle_timeout_secs));
+        options
+    }
+
+    fn max_connections_for(&self, url: &str) -> u32 {
+        self.max_connections
+            .unwrap_or(if url.starts_with("sqlite") { 1 } else { 100 })
+    }
 }
 
 impl Default for Storage {
@@ -943,9 +1474,22 @@ impl Default for Storage {
             blocks: BlockStorage::default().into(),
             staging: StagingStorage::default().into(),
             database: None,
+            replica: None,
+            max_connections: None,
+            min_connections: 0,
+            connect_timeout_secs: default_connect_timeout(),
+            idle_timeout_secs: default_connection_idle_timeout(),
             limit: None,
             sql: SqlStorageConfig::default(),
             duckdb: DuckDbStorageConfig::default(),
+            sniff_content_type: false,
+            cache: BlockCacheConfig::default(),
+            max_upload: default_max_upload(),
+            max_upload_chunk: default_max_upload_chunk(),
+            upload_expiry_secs: default_upload_expiry(),
+            max_open_uploads: default_max_open_uploads(),
+            metadata_headers: default_metadata_headers(),
+            persist_concurrency: default_persist_concurrency(),
         }
     }
 }
@@ -963,6 +1507,8 @@ fn fs_store() -> BlockConfig {
 pub enum BlockStorage {
     Local(FileSystemConfig),
     S3(S3BlockConfig),
+    #[cfg(feature = "azure")]
+    Azure(crate::storage::azure::AzureBlockConfig),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, Default)]
@@ -970,6 +1516,29 @@ pub enum StagingStorage {
     FileSystem,
     #[default]
     Memory,
+    /// Stage uploads in memory until they are larger than `threshold`, then
+    /// in temporary files.
+    Adaptive {
+        #[serde(default = "default_spill_threshold")]
+        threshold: ByteUnit,
+    },
+    /// Stage uploads in Redis, under keys which expire `ttl_secs` after
+    /// they were last written to.
+    #[cfg(feature = "redis")]
+    Redis {
+        url: String,
+        #[serde(default = "default_redis_staging_ttl")]
+        ttl_secs: u64,
+    },
+}
+
+fn default_spill_threshold() -> ByteUnit {
+    ByteUnit::Mebibyte(1)
+}
+
+#[cfg(feature = "redis")]
+fn default_redis_staging_ttl() -> u64 {
+    3600
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
@@ -986,6 +1555,14 @@ pub struct Telemetry {
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Prometheus {
     pub port: u16,
+    /// Also serve metrics at `/metrics` on the main server, alongside the
+    /// separate server on `port`.
+    #[serde(default)]
+    pub on_main_port: bool,
+    /// Bearer token a scrape of `/metrics` on the main server must present.
+    /// Required with `on_main_port`, as the metrics are labelled by space.
+    #[serde(default)]
+    pub token: Option<String>,
 }
 
 impl Default for Tracing {
@@ -993,6 +1570,9 @@ impl Default for Tracing {
         Tracing {
             enabled: false,
             traceheader: "TinyCloud-Trace-Id".to_string(),
+            endpoint: None,
+            protocol: OtlpProtocol::default(),
+            sampling_ratio: SamplingRatio::default(),
         }
     }
 }
@@ -1014,7 +1594,11 @@ impl Default for Relay {
 
 impl Default for Prometheus {
     fn default() -> Self {
-        Self { port: 8001 }
+        Self {
+            port: 8001,
+            on_main_port: false,
+            token: None,
+        }
     }
 }
 
@@ -1024,6 +1608,62 @@ mod tests {
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn pool_settings_are_applied_and_bounded() {
+        let mut storage = Storage {
+            database: Some("postgres://localhost/tinycloud".to_string()),
+            max_connections: Some(20),
+            min_connections: 2,
+            connect_timeout_secs: 5,
+            idle_timeout_secs: 60,
+            ..Default::default()
+        };
+        storage.validate_pool().unwrap();
+        let options = storage.connect_options(storage.database());
+        assert_eq!(options.get_max_connections(), Some(20));
+        assert_eq!(options.get_min_connections(), Some(2));
+        assert_eq!(options.get_connect_timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(60)));
+
+        storage.min_connections = 21;
+        assert!(storage.validate_pool().is_err());
+        storage.min_connections = 0;
+        storage.max_connections = Some(0);
+        assert!(storage.validate_pool().is_err());
+
+        // SQLite serializes writes through a single connection
+        storage.database = Some("sqlite::memory:".to_string());
+        storage.max_connections = None;
+        storage.validate_pool().unwrap();
+        assert_eq!(
+            storage
+                .connect_options("sqlite::memory:")
+                .get_max_connections(),
+            Some(1)
+        );
+        storage.max_connections = Some(4);
+        assert!(storage.validate_pool().is_err());
+    }
+
+    #[test]
+    fn sql_actor_settings_must_be_non_zero() {
+        let config: SqlStorageConfig = serde_json::from_value(serde_json::json!({
+            "idle_timeout_secs": 30,
+            "channel_capacity": 64,
+        }))
+        .unwrap();
+        assert_eq!(config.idle_timeout_secs.get(), 30);
+        assert_eq!(config.channel_capacity.get(), 64);
+
+        for field in ["idle_timeout_secs", "channel_capacity"] {
+            assert!(
+                serde_json::from_value::<SqlStorageConfig>(serde_json::json!({ field: 0 }))
+                    .is_err(),
+                "{field} = 0 must be rejected"
+            );
+        }
+    }
+
     fn enabled_config() -> ShareEmailConfig {
         ShareEmailConfig {
             enabled: true,
@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tinycloud_core::resolver::DidResolver;
+use tokio::sync::Semaphore;
+
+/// How long a looked-up name, or the lack of one, is reused for.
+const NAME_TTL: Duration = Duration::from_secs(60 * 60);
+/// Most accounts whose names are kept at once.
+const MAX_NAMES: usize = 10_000;
+/// Most lookups running in the background at once.
+const MAX_LOOKUPS: usize = 16;
+
+/// Looks up the primary ENS name of an Ethereum address.
+#[async_trait]
+pub trait ReverseResolver: Send + Sync {
+    async fn primary_name(&self, address: &str) -> Option<String>;
+}
+
+#[async_trait]
+impl ReverseResolver for DidResolver {
+    async fn primary_name(&self, address: &str) -> Option<String> {
+        match tokio::time::timeout(self.timeout(), self.ens_name(address)).await {
+            Ok(Ok(name)) => name,
+            Ok(Err(error)) => {
+                ::tracing::debug!(%address, %error, "ENS reverse lookup failed");
+                None
+            }
+            Err(_) => {
+                ::tracing::debug!(%address, "ENS reverse lookup timed out");
+                None
+            }
+        }
+    }
+}
+
+/// Primary ENS names of the `did:pkh:eip155` accounts in capability listings.
+///
+/// Listings never wait on a lookup: an unknown account starts one in the
+/// background and is listed without a name until it finishes. Once
+/// [`MAX_LOOKUPS`] are running, further accounts are looked up on a later
+/// listing, and once [`MAX_NAMES`] are kept the oldest make way for new ones.
+#[derive(Clone)]
+pub struct EnsNames {
+    resolver: Arc<dyn ReverseResolver>,
+    names: Arc<DashMap<String, (Instant, Option<String>)>>,
+    max_names: usize,
+    lookups: Arc<Semaphore>,
+}
+
+impl EnsNames {
+    pub fn new(resolver: impl ReverseResolver + 'static) -> Self {
+        Self::with_limits(resolver, MAX_NAMES, MAX_LOOKUPS)
+    }
+
+    fn with_limits(
+        resolver: impl ReverseResolver + 'static,
+        max_names: usize,
+        max_lookups: usize,
+    ) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            names: Arc::new(DashMap::new()),
+            max_names,
+            lookups: Arc::new(Semaphore::new(max_lookups)),
+        }
+    }
+
+    /// The cached name of the account `did` identifies, looking it up in the
+    /// background if it is not cached.
+    pub fn get(&self, did: &str) -> Option<String> {
+        let address = eip155_address(did)?;
+        if let Some(entry) = self.names.get(&address) {
+            if entry.0.elapsed() < NAME_TTL {
+                return entry.1.clone();
+            }
+        }
+        let permit = self.lookups.clone().try_acquire_owned().ok()?;
+        // hold the slot so concurrent listings don't repeat the lookup
+        self.insert(address.clone(), None);
+        let names = self.clone();
+        tokio::spawn(async move {
+            names.lookup_address(address).await;
+            drop(permit);
+        });
+        None
+    }
+
+    /// Look up and cache the name of the account `did` identifies.
+    pub async fn lookup(&self, did: &str) -> Option<String> {
+        self.lookup_address(eip155_address(did)?).await
+    }
+
+    async fn lookup_address(&self, address: String) -> Option<String> {
+        let name = self.resolver.primary_name(&address).await;
+        self.insert(address, name.clone());
+        name
+    }
+
+    /// Keep `name` for `address`, first dropping expired names, then the
+    /// oldest, if no more can be kept.
+    fn insert(&self, address: String, name: Option<String>) {
+        if self.names.len() >= self.max_names && !self.names.contains_key(&address) {
+            self.names.retain(|_, (at, _)| at.elapsed() < NAME_TTL);
+            if self.names.len() >= self.max_names {
+                // found before removing, as the iterator holds the map's locks
+                let oldest = self
+                    .names
+                    .iter()
+                    .min_by_key(|entry| entry.0)
+                    .map(|entry| entry.key().clone());
+                if let Some(oldest) = oldest {
+                    self.names.remove(&oldest);
+                }
+            }
+        }
+        self.names.insert(address, (Instant::now(), name));
+    }
+}
+
+/// The lowercased address of a `did:pkh:eip155` DID or DID URL.
+fn eip155_address(did: &str) -> Option<String> {
+    let did = did.split('#').next()?;
+    let (_chain, address) = did.strip_prefix("did:pkh:eip155:")?.split_once(':')?;
+    let hex = address.strip_prefix("0x")?;
+    (hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit())).then(|| address.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ADDRESS: &str = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+
+    /// Knows the name of one address, counting the lookups made.
+    #[derive(Default)]
+    struct MockReverse(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl ReverseResolver for MockReverse {
+        async fn primary_name(&self, address: &str) -> Option<String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            address
+                .eq_ignore_ascii_case(ADDRESS)
+                .then(|| "vitalik.eth".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn names_are_looked_up_once_and_only_for_eip155_accounts() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let names = EnsNames::new(MockReverse(lookups.clone()));
+        let did = format!("did:pkh:eip155:1:{ADDRESS}");
+
+        assert_eq!(
+            names.lookup(&format!("{did}#blockchainAccountId")).await,
+            Some("vitalik.eth".into())
+        );
+        assert_eq!(names.get(&did), Some("vitalik.eth".into()));
+        assert_eq!(
+            names.get("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"),
+            None
+        );
+        assert_eq!(names.get("did:pkh:eip155:1:0xnotanaddress"), None);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        let unnamed = "did:pkh:eip155:1:0x0000000000000000000000000000000000000001";
+        assert_eq!(names.get(unnamed), None);
+        while lookups.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(names.get(unnamed), None);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    /// Never answers, counting the lookups started.
+    struct StalledReverse(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl ReverseResolver for StalledReverse {
+        async fn primary_name(&self, _: &str) -> Option<String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::future::pending().await
+        }
+    }
+
+    fn account(n: usize) -> String {
+        format!("did:pkh:eip155:1:0x{n:040x}")
+    }
+
+    #[tokio::test]
+    async fn lookups_and_kept_names_are_bounded() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let names = EnsNames::with_limits(StalledReverse(started.clone()), 100, 2);
+        for n in 0..10 {
+            assert_eq!(names.get(&account(n)), None);
+        }
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        // the rest wait for a later listing rather than queueing lookups
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(names.names.len(), 2);
+
+        let names = EnsNames::with_limits(MockReverse::default(), 3, 2);
+        for n in 0..10 {
+            names.lookup(&account(n)).await;
+        }
+        assert_eq!(names.names.len(), 3);
+        assert!(names.names.contains_key(&account(9)[17..]));
+        assert!(!names.names.contains_key(&account(0)[17..]));
+    }
+}
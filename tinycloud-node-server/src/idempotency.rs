@@ -0,0 +1,318 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    response::{self, Responder, Response},
+};
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tinycloud_auth::authorization::TinyCloudInvocation;
+use tinycloud_core::{
+    events::Invocation,
+    hash::{hash, Hash},
+};
+
+use crate::config::IdempotencyConfig;
+
+/// Header a client names a request with, so that a retry of it is answered
+/// with the first attempt's response rather than being applied again.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// Header set on a response replayed for a retried request.
+const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+
+/// Largest response body recorded for replay. A retry of a request whose
+/// response was larger, or streamed, is handled as a new request.
+const MAX_RECORDED_BODY: usize = 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdempotencyError {
+    #[error("a request with this Idempotency-Key is still being processed")]
+    InFlight,
+    #[error("this Idempotency-Key was used for a different request")]
+    Mismatch,
+    #[error("too many requests with an Idempotency-Key are being processed")]
+    Full,
+}
+
+impl IdempotencyError {
+    fn status(&self) -> Status {
+        match self {
+            Self::InFlight => Status::Conflict,
+            Self::Mismatch => Status::UnprocessableEntity,
+            Self::Full => Status::ServiceUnavailable,
+        }
+    }
+}
+
+/// Responses to requests carrying an `Idempotency-Key`, kept for a TTL.
+///
+/// Keys are scoped to the invoker and spaces the request's invocation claims,
+/// and a key is only answered for a request with the very same signed
+/// invocation, so one client can neither see nor reuse another's responses.
+/// The invocation is verified by the route, once; a request which fails
+/// authentication releases its key rather than having its response recorded.
+/// At most `capacity` keys are held; when full, the recorded response closest
+/// to expiry is forgotten to make room.
+pub struct IdempotencyCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+struct Entry {
+    /// Digest of the request the key was first used with.
+    request: Hash,
+    expires_at: Instant,
+    /// `None` while the first request is still being handled.
+    response: Option<RecordedResponse>,
+}
+
+/// A response as it was first sent.
+#[derive(Debug, Clone)]
+pub struct RecordedResponse {
+    status: Status,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl IdempotencyCache {
+    pub fn new(config: &IdempotencyConfig) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(config.ttl_secs),
+            capacity: config.capacity.max(1),
+        }
+    }
+
+    /// Claim `key` for `request`, or get the response recorded for it.
+    fn begin(
+        &self,
+        key: &str,
+        request: Hash,
+        now: Instant,
+    ) -> Result<Option<RecordedResponse>, IdempotencyError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| entry.expires_at > now);
+
+        if let Some(entry) = entries.get(key) {
+            if entry.request != request {
+                return Err(IdempotencyError::Mismatch);
+            }
+            return entry
+                .response
+                .clone()
+                .map(Some)
+                .ok_or(IdempotencyError::InFlight);
+        }
+
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .filter(|(_, entry)| entry.response.is_some())
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone())
+                .ok_or(IdempotencyError::Full)?;
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                request,
+                expires_at: now + self.ttl,
+                response: None,
+            },
+        );
+        Ok(None)
+    }
+
+    /// Record the response to the request which claimed `key`, or release
+    /// the key so that a retry is handled as a new request.
+    fn finish(&self, key: &str, response: Option<RecordedResponse>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match response {
+            Some(response) => {
+                if let Some(entry) = entries.get_mut(key) {
+                    entry.response = Some(response);
+                }
+            }
+            None => {
+                entries.remove(key);
+            }
+        }
+    }
+}
+
+/// How a request is handled with respect to its `Idempotency-Key`.
+pub enum Idempotency {
+    /// The request carries no key, or no invocation, and is handled as usual.
+    Untracked,
+    /// The request is the first with its key; its response will be recorded.
+    Recorded,
+    /// The request retries one which was already answered.
+    Replay(RecordedResponse),
+}
+
+/// The key a request claimed, for [`IdempotencyFairing`] to record its
+/// response under.
+struct Claimed(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Idempotency {
+    type Error = IdempotencyError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let (Some(key), Some(authorization), Some(cache)) = (
+            req.headers().get_one(IDEMPOTENCY_KEY),
+            req.headers().get_one("Authorization"),
+            req.rocket().state::<IdempotencyCache>(),
+        ) else {
+            return Outcome::Success(Idempotency::Untracked);
+        };
+        // an invocation which does not parse is left for the route to reject
+        let Ok(invocation) = Invocation::from_header_ser::<TinyCloudInvocation>(authorization)
+        else {
+            return Outcome::Success(Idempotency::Untracked);
+        };
+        let mut spaces: Vec<String> = invocation.0.spaces().map(ToString::to_string).collect();
+        spaces.sort();
+        spaces.dedup();
+        let key = format!("{}\n{}\n{key}", invocation.0.invoker, spaces.join(" "));
+        // a retry is the same invocation sent to the same route
+        let request =
+            hash(format!("{} {}\n{authorization}", req.method(), req.uri().path()).as_bytes());
+        match cache.begin(&key, request, Instant::now()) {
+            Ok(Some(response)) => Outcome::Success(Idempotency::Replay(response)),
+            Ok(None) => {
+                req.local_cache(|| Claimed(Some(key)));
+                Outcome::Success(Idempotency::Recorded)
+            }
+            Err(e) => Outcome::Error((e.status(), e)),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for RecordedResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .sized_body(self.body.len(), Cursor::new(self.body));
+        for (name, value) in self.headers {
+            response.raw_header_adjoin(name, value);
+        }
+        response.raw_header(IDEMPOTENT_REPLAYED, "true");
+        Ok(response.finalize())
+    }
+}
+
+/// Records the responses of requests which claimed an `Idempotency-Key`.
+///
+/// Responses which a retry should not see again — server errors, timeouts,
+/// rate limiting and failed authentication — release the key instead.
+pub struct IdempotencyFairing;
+
+fn is_recordable(status: Status) -> bool {
+    !(status.code >= 500
+        || status == Status::TooManyRequests
+        || status == Status::RequestTimeout
+        || status == Status::Unauthorized)
+}
+
+#[rocket::async_trait]
+impl Fairing for IdempotencyFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Idempotency Keys",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Claimed(Some(key)) = req.local_cache(|| Claimed(None)) else {
+            return;
+        };
+        let Some(cache) = req.rocket().state::<IdempotencyCache>() else {
+            return;
+        };
+        let sized = res
+            .body()
+            .preset_size()
+            .is_some_and(|size| size <= MAX_RECORDED_BODY);
+        if !is_recordable(res.status()) || !sized {
+            cache.finish(key, None);
+            return;
+        }
+        let body = match res.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(_) => {
+                cache.finish(key, None);
+                return;
+            }
+        };
+        let recorded = RecordedResponse {
+            status: res.status(),
+            headers: res
+                .headers()
+                .iter()
+                .map(|header| (header.name().to_string(), header.value().to_string()))
+                .collect(),
+            body: body.clone(),
+        };
+        res.set_sized_body(body.len(), Cursor::new(body));
+        cache.finish(key, Some(recorded));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(body: &[u8]) -> RecordedResponse {
+        RecordedResponse {
+            status: Status::Ok,
+            headers: Vec::new(),
+            body: body.to_vec(),
+        }
+    }
+
+    #[test]
+    fn keys_replay_their_response_until_they_expire() {
+        let cache = IdempotencyCache::new(&IdempotencyConfig {
+            ttl_secs: 60,
+            capacity: 1,
+        });
+        let request = hash(b"request");
+        let now = Instant::now();
+
+        assert!(cache.begin("key", request, now).unwrap().is_none());
+        assert!(matches!(
+            cache.begin("key", request, now),
+            Err(IdempotencyError::InFlight)
+        ));
+        cache.finish("key", Some(recorded(b"done")));
+        assert_eq!(
+            cache.begin("key", request, now).unwrap().unwrap().body,
+            b"done"
+        );
+        assert!(matches!(
+            cache.begin("key", hash(b"other"), now),
+            Err(IdempotencyError::Mismatch)
+        ));
+
+        // a new key takes the place of the recorded one
+        assert!(cache.begin("next", request, now).unwrap().is_none());
+        assert!(matches!(
+            cache.begin("third", request, now),
+            Err(IdempotencyError::Full)
+        ));
+        assert!(cache
+            .begin("next", request, now + Duration::from_secs(61))
+            .unwrap()
+            .is_none());
+    }
+}
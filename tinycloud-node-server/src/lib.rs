@@ -15,15 +15,21 @@ use std::{path::Path, sync::Arc};
 pub mod allow_list;
 pub mod auth_guards;
 pub mod authorization;
+pub mod commit_feed;
+pub mod commit_webhook;
+mod compression;
 pub mod config;
 #[cfg(feature = "dstack")]
 pub mod dstack;
+pub mod ens_names;
 pub mod hooks;
+pub mod idempotency;
 pub mod invocation_replay;
 pub mod link;
 pub mod node_control;
 pub mod prometheus;
 pub mod quota;
+pub mod rate_limit;
 pub mod routes;
 pub mod runtime;
 pub mod share_email;
@@ -47,39 +53,65 @@ pub(crate) mod test_support {
     }
 }
 
+use commit_feed::CommitFeed;
+use commit_webhook::CommitWebhook;
 use config::{BlockStorage, Config, Keys, StagingStorage};
+use ens_names::EnsNames;
 use hooks::HookRuntime;
+use idempotency::{IdempotencyCache, IdempotencyFairing};
 use invocation_replay::InvocationReplayCache;
 use node_control::control::ControlPlaneHandle;
 use quota::QuotaCache;
+use rate_limit::SpaceRateLimiter;
 use routes::{
-    admin::{delete_quota, get_quota, get_usage, list_quotas, set_quota},
+    admin::{
+        create_space, delete_quota, delete_space, freeze_space, get_quota, get_usage, list_quotas,
+        list_spaces, prune_delegations, set_quota, unfreeze_space,
+    },
     attestation::attestation,
-    create_signed_kv_url, delegate, delegation_query, delegation_status,
+    create_signed_kv_url, delegate, delegate_preview, delegation_query, delegation_status,
     encryption::{
         create_network as create_encryption_network, decrypt as encryption_decrypt,
         get_network as get_encryption_network, revoke_network as revoke_encryption_network,
         well_known_network as encryption_well_known,
     },
+    events::space_events,
     hooks::{create_hook_ticket, create_webhook, delete_webhook, hook_events, list_webhooks},
-    info, invoke, open_host_key,
+    info, invoke,
+    log::space_log,
+    manifest,
+    metrics::metrics,
+    open_host_key,
     public::{public_kv_get, public_kv_head, public_kv_list, public_kv_options, RateLimiter},
-    revoke, signed_kv_get,
+    revoke, signed_kv_get, sql_invoke,
+    uploads::{upload_chunk, upload_complete, upload_init, upload_status, Uploads},
     util_routes::*,
-    version,
+    version, well_known_node,
 };
+#[cfg(feature = "azure")]
+use storage::azure::{AzureBlockConfig, AzureBlockStore};
+#[cfg(feature = "redis")]
+use storage::redis::RedisStaging;
 use storage::{
+    adaptive::AdaptiveStaging,
+    caching::CachingStore,
     file_system::{FileSystemConfig, FileSystemStore, TempFileSystemStage},
+    metered::MeteredStore,
     s3::{S3BlockConfig, S3BlockStore},
+    LocalStaging,
 };
 use tee::TeeContext;
 #[cfg(feature = "duckdb")]
 use tinycloud_core::duckdb::DuckDbService;
 use tinycloud_core::{
+    allow_list::SpaceAllowList,
+    commit_observer::CommitObserver,
     database_artifacts::{DatabaseArtifactRepository, SeaOrmDatabaseArtifactRepository},
     encryption_network::{EncryptionService, LocalOneOfOneBackend},
     keys::{SecretsSetup, StaticSecret},
-    sea_orm::{ConnectOptions, Database, DatabaseConnection},
+    policy::{AllowAll, Policy},
+    resolver::DidResolver,
+    sea_orm::{Database, DatabaseConnection},
     sql::SqlService,
     sql_sizes::{SizeTrackingArtifactRepository, SqlSizes},
     storage::{either::Either, memory::MemoryStaging, StorageConfig},
@@ -87,10 +119,21 @@ use tinycloud_core::{
 };
 use webhook_dispatcher::{spawn_webhook_dispatcher, WebhookDispatcher};
 
-pub type BlockStores = Either<S3BlockStore, FileSystemStore>;
+#[cfg(not(feature = "azure"))]
+pub type BlockStores = CachingStore<MeteredStore<Either<S3BlockStore, FileSystemStore>>>;
+#[cfg(not(feature = "azure"))]
 pub type BlockConfig = Either<S3BlockConfig, FileSystemConfig>;
-pub type BlockStage = Either<TempFileSystemStage, MemoryStaging>;
-
+#[cfg(feature = "azure")]
+pub type BlockStores =
+    CachingStore<MeteredStore<Either<Either<S3BlockStore, AzureBlockStore>, FileSystemStore>>>;
+#[cfg(feature = "azure")]
+pub type BlockConfig = Either<Either<S3BlockConfig, AzureBlockConfig>, FileSystemConfig>;
+#[cfg(not(feature = "redis"))]
+pub type BlockStage = LocalStaging;
+#[cfg(feature = "redis")]
+pub type BlockStage = Either<LocalStaging, RedisStaging>;
+
+#[cfg(not(feature = "azure"))]
 impl From<BlockStorage> for BlockConfig {
     fn from(c: BlockStorage) -> BlockConfig {
         match c {
@@ -100,29 +143,89 @@ impl From<BlockStorage> for BlockConfig {
     }
 }
 
+#[cfg(not(feature = "azure"))]
+impl From<BlockConfig> for BlockStorage {
+    fn from(c: BlockConfig) -> Self {
+        match c {
+            BlockConfig::A(s) => Self::S3(s),
+            BlockConfig::B(b) => Self::Local(b),
+        }
+    }
+}
+
+#[cfg(feature = "azure")]
+impl From<BlockStorage> for BlockConfig {
+    fn from(c: BlockStorage) -> BlockConfig {
+        match c {
+            BlockStorage::S3(s) => Self::A(Either::A(s)),
+            BlockStorage::Azure(a) => Self::A(Either::B(a)),
+            BlockStorage::Local(l) => Self::B(l),
+        }
+    }
+}
+
+#[cfg(feature = "azure")]
 impl From<BlockConfig> for BlockStorage {
     fn from(c: BlockConfig) -> Self {
         match c {
-            BlockConfig::A(a) => Self::S3(a),
+            BlockConfig::A(Either::A(s)) => Self::S3(s),
+            BlockConfig::A(Either::B(a)) => Self::Azure(a),
             BlockConfig::B(b) => Self::Local(b),
         }
     }
 }
 
+#[cfg(not(feature = "redis"))]
+impl From<StagingStorage> for BlockStage {
+    fn from(c: StagingStorage) -> Self {
+        match c {
+            StagingStorage::Memory => Self::A(Either::B(MemoryStaging)),
+            StagingStorage::FileSystem => Self::A(Either::A(TempFileSystemStage)),
+            StagingStorage::Adaptive { threshold } => Self::B(AdaptiveStaging::new(threshold)),
+        }
+    }
+}
+
+#[cfg(not(feature = "redis"))]
+impl From<BlockStage> for StagingStorage {
+    fn from(c: BlockStage) -> Self {
+        match c {
+            BlockStage::A(Either::B(_)) => Self::Memory,
+            BlockStage::A(Either::A(_)) => Self::FileSystem,
+            BlockStage::B(a) => Self::Adaptive {
+                threshold: a.threshold(),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
 impl From<StagingStorage> for BlockStage {
     fn from(c: StagingStorage) -> Self {
         match c {
-            StagingStorage::Memory => Self::B(MemoryStaging),
-            StagingStorage::FileSystem => Self::A(TempFileSystemStage),
+            StagingStorage::Memory => Self::A(Either::A(Either::B(MemoryStaging))),
+            StagingStorage::FileSystem => Self::A(Either::A(Either::A(TempFileSystemStage))),
+            StagingStorage::Adaptive { threshold } => {
+                Self::A(Either::B(AdaptiveStaging::new(threshold)))
+            }
+            StagingStorage::Redis { url, ttl_secs } => Self::B(RedisStaging::new(url, ttl_secs)),
         }
     }
 }
 
+#[cfg(feature = "redis")]
 impl From<BlockStage> for StagingStorage {
     fn from(c: BlockStage) -> Self {
         match c {
-            BlockStage::B(_) => Self::Memory,
-            BlockStage::A(_) => Self::FileSystem,
+            BlockStage::A(Either::A(Either::B(_))) => Self::Memory,
+            BlockStage::A(Either::A(Either::A(_))) => Self::FileSystem,
+            BlockStage::A(Either::B(a)) => Self::Adaptive {
+                threshold: a.threshold(),
+            },
+            BlockStage::B(r) => Self::Redis {
+                url: r.url().to_string(),
+                ttl_secs: r.ttl_secs(),
+            },
         }
     }
 }
@@ -134,10 +237,26 @@ pub async fn app(config: &Figment) -> Result<Rocket<Build>> {
     app_with_control(config, &tinycloud_config, None).await
 }
 
+/// [`app`], consulting `policy` before committing any invocation. The policy
+/// is also managed as `Arc<dyn Policy>`.
+pub async fn app_with_policy(config: &Figment, policy: Arc<dyn Policy>) -> Result<Rocket<Build>> {
+    let tinycloud_config = config.extract::<Config>()?;
+    build_app(config, &tinycloud_config, None, policy).await
+}
+
 pub async fn app_with_control(
     config: &Figment,
     tinycloud_config: &Config,
     control: Option<ControlPlaneHandle>,
+) -> Result<Rocket<Build>> {
+    build_app(config, tinycloud_config, control, Arc::new(AllowAll)).await
+}
+
+async fn build_app(
+    config: &Figment,
+    tinycloud_config: &Config,
+    control: Option<ControlPlaneHandle>,
+    policy: Arc<dyn Policy>,
 ) -> Result<Rocket<Build>> {
     let mut tinycloud_config = tinycloud_config.clone();
     tinycloud_config.storage.resolve();
@@ -161,12 +280,21 @@ pub async fn app_with_control(
 
     let mut routes = rocket::routes![
         healthcheck,
+        readiness,
         cors,
         info,
         version,
+        well_known_node,
+        manifest,
         open_host_key,
         invoke,
+        upload_init,
+        upload_chunk,
+        upload_status,
+        upload_complete,
+        sql_invoke,
         delegate,
+        delegate_preview,
         delegation_query,
         delegation_status,
         revoke,
@@ -174,6 +302,8 @@ pub async fn app_with_control(
         signed_kv_get,
         create_hook_ticket,
         hook_events,
+        space_events,
+        space_log,
         create_webhook,
         list_webhooks,
         delete_webhook,
@@ -187,6 +317,12 @@ pub async fn app_with_control(
         get_quota,
         list_quotas,
         get_usage,
+        list_spaces,
+        create_space,
+        prune_delegations,
+        freeze_space,
+        unfreeze_space,
+        delete_space,
         create_encryption_network,
         get_encryption_network,
         encryption_well_known,
@@ -194,6 +330,12 @@ pub async fn app_with_control(
         revoke_encryption_network,
     ];
     routes.extend(share_email::public_routes());
+    if tinycloud_config.prometheus.on_main_port {
+        if tinycloud_config.prometheus.token.is_none() {
+            anyhow::bail!("prometheus.on_main_port requires prometheus.token to be set");
+        }
+        routes.extend(rocket::routes![metrics]);
+    }
 
     let key_setup: StaticSecret = resolve_keys(&tinycloud_config.keys).await?;
     let webhook_encryption =
@@ -202,6 +344,7 @@ pub async fn app_with_control(
         tinycloud_config.hooks.clone(),
         key_setup.derive_key(b"tinycloud/hooks/tickets"),
     );
+    let commit_feed = CommitFeed::new(tinycloud_config.hooks.sse_broadcast_capacity);
     let signed_url_runtime =
         signed_urls::SignedUrlRuntime::new(key_setup.derive_key(b"tinycloud/kv/signed-urls"));
 
@@ -238,36 +381,55 @@ pub async fn app_with_control(
         }
     };
 
+    tinycloud_config
+        .storage
+        .validate_pool()
+        .map_err(|error| anyhow::anyhow!(error))?;
     let database = tinycloud_config.storage.database();
-    let mut connect_opts = ConnectOptions::from(database);
+    let mut connect_opts = tinycloud_config.storage.connect_options(database);
     let is_sqlite = database.starts_with("sqlite");
     if is_sqlite {
         // SQLite cannot handle concurrent write transactions — two DEFERRED
-        // transactions deadlock when both try to upgrade to writers.  Use a
-        // single connection to serialize writes, and enable WAL mode so reads
-        // outside transactions remain concurrent.
-        connect_opts.max_connections(1);
+        // transactions deadlock when both try to upgrade to writers. The pool
+        // is held to a single connection to serialize writes; enable WAL mode
+        // so reads outside transactions remain concurrent.
         connect_opts.map_sqlx_sqlite_opts(|opts| {
             opts.create_if_missing(true)
                 .pragma("journal_mode", "WAL")
                 .busy_timeout(std::time::Duration::from_secs(5))
         });
-    } else {
-        connect_opts.max_connections(100);
-        if let Some(root_cert_path) = tinycloud_config
-            .share_email
-            .postgres_tls
-            .root_cert_path
-            .as_deref()
-        {
-            let root_cert_path = root_cert_path.to_owned();
-            connect_opts.map_sqlx_postgres_opts(move |options| {
-                options.ssl_root_cert(root_cert_path.clone())
-            });
-        }
+    } else if let Some(root_cert_path) = tinycloud_config
+        .share_email
+        .postgres_tls
+        .root_cert_path
+        .as_deref()
+    {
+        let root_cert_path = root_cert_path.to_owned();
+        connect_opts
+            .map_sqlx_postgres_opts(move |options| options.ssl_root_cert(root_cert_path.clone()));
     }
 
     let database_connection = Database::connect(connect_opts).await?;
+    let replica_connection = match tinycloud_config.storage.replica.as_deref() {
+        Some(replica) => {
+            let mut replica_opts = tinycloud_config.storage.connect_options(replica);
+            if replica.starts_with("sqlite") {
+                replica_opts.map_sqlx_sqlite_opts(|opts| opts.read_only(true));
+            } else if let Some(root_cert_path) = tinycloud_config
+                .share_email
+                .postgres_tls
+                .root_cert_path
+                .as_deref()
+            {
+                let root_cert_path = root_cert_path.to_owned();
+                replica_opts.map_sqlx_postgres_opts(move |options| {
+                    options.ssl_root_cert(root_cert_path.clone())
+                });
+            }
+            Some(Database::connect(replica_opts).await?)
+        }
+        None => None,
+    };
     // SQL/DuckDB artifact-size mirror folded into `store_size`. Empty here;
     // wired into the decorator + SpaceDatabase BEFORE migrations, then seeded
     // from DB truth AFTER `TinyCloud::new` runs migrations (see below).
@@ -296,11 +458,31 @@ pub async fn app_with_control(
 
     let tinycloud = TinyCloud::new(
         database_connection,
-        tinycloud_config.storage.blocks.open().await?,
+        CachingStore::new(
+            MeteredStore::from(tinycloud_config.storage.blocks.open().await?),
+            &tinycloud_config.storage.cache,
+        ),
         key_setup.setup(()).await?,
     )
     .await?
+    .with_replica(replica_connection)
     .with_encryption(Some(webhook_encryption.clone()))
+    .with_allow_list(
+        tinycloud_config
+            .spaces
+            .allowlist
+            .clone()
+            .map(|allow_list| Arc::new(allow_list) as Arc<dyn SpaceAllowList>),
+    )
+    .with_commit_observer(
+        CommitWebhook::spawn(&tinycloud_config.hooks)?
+            .map(|webhook| Arc::new(webhook) as Arc<dyn CommitObserver>),
+    )
+    .with_commit_observer(Some(Arc::new(commit_feed.clone())))
+    .with_policy(policy.clone())
+    .with_delegation_limits(tinycloud_config.spaces.delegation_limits())
+    .with_persist_concurrency(tinycloud_config.storage.persist_concurrency)
+    .with_resolver(DidResolver::new(tinycloud_config.resolver.options())?)
     .with_sql_sizes(sql_sizes.clone());
 
     // Seed the SQL-size mirror AFTER `TinyCloud::new` ran migrations — the
@@ -312,7 +494,11 @@ pub async fn app_with_control(
         tinycloud_config.storage.sql.path.clone().expect("resolved"),
         tinycloud_config.storage.sql.memory_threshold.as_u64(),
         database_artifact_repository.clone(),
-    );
+    )
+    .with_idle_timeout(std::time::Duration::from_secs(
+        tinycloud_config.storage.sql.idle_timeout_secs.get(),
+    ))
+    .with_channel_capacity(tinycloud_config.storage.sql.channel_capacity.get());
 
     let share_email_runtime = share_email::compose(
         tinycloud_config.share_email.clone(),
@@ -367,8 +553,23 @@ pub async fn app_with_control(
         std::env::var("TINYCLOUD_QUOTA_URL").ok(),
     );
     let invocation_replay_cache = InvocationReplayCache::new();
+    let idempotency_cache = IdempotencyCache::new(&tinycloud_config.idempotency);
 
     let rate_limiter = RateLimiter::new(&tinycloud_config.public_spaces);
+    let space_rate_limiter = SpaceRateLimiter::new(&tinycloud_config.rate_limit);
+    {
+        let space_rate_limiter = space_rate_limiter.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let purged = space_rate_limiter.purge_full();
+                if purged > 0 {
+                    ::tracing::debug!(purged, "dropped full rate limit buckets");
+                }
+            }
+        });
+    }
     let webhook_dispatcher = WebhookDispatcher::new(
         tinycloud.clone(),
         tinycloud_config.hooks.clone(),
@@ -376,12 +577,55 @@ pub async fn app_with_control(
     )?;
     spawn_webhook_dispatcher(webhook_dispatcher);
 
+    if let Some(secs) = tinycloud_config.spaces.prune_delegations_interval_secs {
+        let tinycloud = tinycloud.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(secs.max(1)));
+            loop {
+                interval.tick().await;
+                match tinycloud
+                    .prune_expired_delegations(time::OffsetDateTime::now_utc())
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(pruned) => ::tracing::info!(pruned, "pruned expired delegations"),
+                    Err(error) => ::tracing::warn!(?error, "expired delegation pruning failed"),
+                }
+            }
+        });
+    }
+
+    let uploads = Uploads::new(
+        std::time::Duration::from_secs(tinycloud_config.storage.upload_expiry_secs),
+        tinycloud_config.storage.max_open_uploads,
+    );
+    {
+        let uploads = uploads.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let expired = uploads.purge_expired();
+                if expired > 0 {
+                    ::tracing::debug!(expired, "dropped expired uploads");
+                }
+            }
+        });
+    }
+
+    let ens_names = (tinycloud_config.resolver.ens_names
+        && tinycloud_config.resolver.ens_rpc_url.is_some())
+    .then(|| EnsNames::new(tinycloud.resolver().clone()));
+
     let rocket = rocket::custom(config)
         .mount("/", routes)
+        .register("/", rocket::catchers![header_too_large])
         .attach(AdHoc::config::<Config>())
         .attach(tracing::TracingFairing {
             header_name: tinycloud_config.log.tracing.traceheader,
         })
+        .attach(tracing::AccessLogFairing)
+        .attach(IdempotencyFairing)
         .manage(tinycloud)
         .manage(sql_service);
     #[cfg(feature = "duckdb")]
@@ -389,14 +633,23 @@ pub async fn app_with_control(
     let rocket = rocket
         .manage(quota_cache)
         .manage(invocation_replay_cache)
+        .manage(idempotency_cache)
         .manage(hook_runtime)
+        .manage(commit_feed)
         .manage(signed_url_runtime)
         .manage(webhook_encryption)
         .manage(rate_limiter)
+        .manage(space_rate_limiter)
         .manage(share_email_runtime)
         .manage(tee_context)
         .manage(encryption_service)
+        .manage(uploads)
+        .manage(policy)
         .manage(tinycloud_config.storage.staging.open().await?);
+    let rocket = match ens_names {
+        Some(ens_names) => rocket.manage(ens_names),
+        None => rocket,
+    };
 
     let rocket = if let Some(control) = control {
         let control_running = control.clone();
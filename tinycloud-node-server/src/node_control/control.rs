@@ -23,6 +23,7 @@ use std::{
 };
 use subtle::ConstantTimeEq;
 use time::OffsetDateTime;
+use tinycloud_core::storage::either::Either;
 use tokio::{
     net::TcpListener,
     sync::{Mutex, Notify, RwLock},
@@ -264,6 +265,9 @@ pub struct PublicStorageSnapshot {
 pub enum PublicStagingSnapshot {
     FileSystem,
     Memory,
+    Adaptive,
+    #[cfg(feature = "redis")]
+    Redis,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -1208,20 +1212,48 @@ fn effective_public_config(base_config_path: &Path) -> ControlResult<ControlConf
     .map_err(|err| ControlError::internal_error(err.to_string()))
 }
 
+#[cfg(not(feature = "azure"))]
 fn public_block_config(blocks: &BlockConfig) -> PublicBlocksSnapshot {
     match blocks {
-        BlockConfig::A(s3) => PublicBlocksSnapshot {
-            kind: "s3".to_string(),
+        BlockConfig::A(s3) => public_s3_blocks(s3),
+        BlockConfig::B(local) => public_local_blocks(local),
+    }
+}
+
+#[cfg(feature = "azure")]
+fn public_block_config(blocks: &BlockConfig) -> PublicBlocksSnapshot {
+    match blocks {
+        BlockConfig::A(Either::A(s3)) => public_s3_blocks(s3),
+        BlockConfig::A(Either::B(azure)) => PublicBlocksSnapshot {
+            kind: "azure".to_string(),
             path: None,
-            bucket: Some(s3.bucket.clone()),
-            endpoint: s3.endpoint.as_ref().map(|uri| uri.to_string()),
-        },
-        BlockConfig::B(local) => PublicBlocksSnapshot {
-            kind: "local".to_string(),
-            path: Some(local.path().display().to_string()),
-            bucket: None,
-            endpoint: None,
+            bucket: Some(azure.container.clone()),
+            endpoint: azure
+                .account
+                .as_ref()
+                .map(|account| format!("https://{account}.blob.core.windows.net")),
         },
+        BlockConfig::B(local) => public_local_blocks(local),
+    }
+}
+
+fn public_s3_blocks(s3: &crate::storage::s3::S3BlockConfig) -> PublicBlocksSnapshot {
+    PublicBlocksSnapshot {
+        kind: "s3".to_string(),
+        path: None,
+        bucket: Some(s3.bucket.clone()),
+        endpoint: s3.endpoint.as_ref().map(|uri| uri.to_string()),
+    }
+}
+
+fn public_local_blocks(
+    local: &crate::storage::file_system::FileSystemConfig,
+) -> PublicBlocksSnapshot {
+    PublicBlocksSnapshot {
+        kind: "local".to_string(),
+        path: Some(local.path().display().to_string()),
+        bucket: None,
+        endpoint: None,
     }
 }
 
@@ -1248,10 +1280,22 @@ fn log_source_name(log_mode: LogMode) -> &'static str {
     }
 }
 
+#[cfg(not(feature = "redis"))]
+fn public_staging_snapshot(staging: &crate::BlockStage) -> PublicStagingSnapshot {
+    match staging {
+        crate::BlockStage::A(Either::A(_)) => PublicStagingSnapshot::FileSystem,
+        crate::BlockStage::A(Either::B(_)) => PublicStagingSnapshot::Memory,
+        crate::BlockStage::B(_) => PublicStagingSnapshot::Adaptive,
+    }
+}
+
+#[cfg(feature = "redis")]
 fn public_staging_snapshot(staging: &crate::BlockStage) -> PublicStagingSnapshot {
     match staging {
-        crate::BlockStage::A(_) => PublicStagingSnapshot::FileSystem,
-        crate::BlockStage::B(_) => PublicStagingSnapshot::Memory,
+        crate::BlockStage::A(Either::A(Either::A(_))) => PublicStagingSnapshot::FileSystem,
+        crate::BlockStage::A(Either::A(Either::B(_))) => PublicStagingSnapshot::Memory,
+        crate::BlockStage::A(Either::B(_)) => PublicStagingSnapshot::Adaptive,
+        crate::BlockStage::B(_) => PublicStagingSnapshot::Redis,
     }
 }
 
@@ -1,6 +1,9 @@
 use hyper::{header::CONTENT_TYPE, Body, Request, Response};
 use lazy_static::lazy_static;
-use prometheus::{register_histogram_vec, Encoder, HistogramVec, TextEncoder};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder, TEXT_FORMAT,
+};
 use std::{
     sync::atomic::{AtomicBool, Ordering},
     time::Duration,
@@ -33,6 +36,42 @@ lazy_static! {
         &["span", "outcome"]
     )
     .unwrap();
+    pub static ref STORAGE_OPERATIONS: IntCounterVec = register_int_counter_vec!(
+        "tinycloud_storage_operations_total",
+        "Block storage reads, writes and deletes by outcome (ok, not_found or error).",
+        &["backend", "operation", "outcome"]
+    )
+    .unwrap();
+    pub static ref STORAGE_BYTES: IntCounterVec = register_int_counter_vec!(
+        "tinycloud_storage_bytes_total",
+        "Bytes read from and written to block storage.",
+        &["backend", "operation"]
+    )
+    .unwrap();
+    pub static ref STORAGE_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "tinycloud_storage_operation_duration_seconds",
+        "Block storage operation latencies in seconds.",
+        &["backend", "operation", "outcome"]
+    )
+    .unwrap();
+    pub static ref BLOCK_CACHE_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "tinycloud_block_cache_requests_total",
+        "Block cache lookups by result (hit or miss).",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref COMMIT_WEBHOOK_EVENTS: IntCounterVec = register_int_counter_vec!(
+        "tinycloud_commit_webhook_events_total",
+        "Commit webhook events by outcome (delivered, failed or dropped).",
+        &["outcome"]
+    )
+    .unwrap();
+    pub static ref SPACE_SIZE_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "tinycloud_space_size_bytes",
+        "Total stored bytes per space.",
+        &["space"]
+    )
+    .unwrap();
 }
 
 pub fn set_enabled(enabled: bool) {
@@ -51,17 +90,50 @@ pub fn observe_span(span: &'static str, outcome: &'static str, duration: Duratio
     }
 }
 
-pub async fn serve_req(_req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
-    let encoder = TextEncoder::new();
+/// Record one block storage operation on `backend` (`s3`, `azure` or `fs`).
+pub fn observe_storage(
+    backend: &'static str,
+    operation: &'static str,
+    outcome: &'static str,
+    bytes: u64,
+    duration: Duration,
+) {
+    if enabled() {
+        STORAGE_OPERATIONS
+            .with_label_values(&[backend, operation, outcome])
+            .inc();
+        STORAGE_BYTES
+            .with_label_values(&[backend, operation])
+            .inc_by(bytes);
+        STORAGE_HISTOGRAM
+            .with_label_values(&[backend, operation, outcome])
+            .observe(duration.as_secs_f64());
+    }
+}
+
+pub fn set_space_size(space: &str, size: u64) {
+    if enabled() {
+        SPACE_SIZE_GAUGE
+            .with_label_values(&[space])
+            .set(i64::try_from(size).unwrap_or(i64::MAX));
+    }
+}
 
+/// Every registered metric in the text exposition format.
+pub fn encode_text() -> Vec<u8> {
     let metric_families = prometheus::gather();
     let mut buffer = vec![];
-    encoder.encode(&metric_families, &mut buffer).unwrap();
+    TextEncoder::new()
+        .encode(&metric_families, &mut buffer)
+        .unwrap();
+    buffer
+}
 
+pub async fn serve_req(_req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
     let response = Response::builder()
         .status(200)
-        .header(CONTENT_TYPE, encoder.format_type())
-        .body(Body::from(buffer))
+        .header(CONTENT_TYPE, TEXT_FORMAT)
+        .body(Body::from(encode_text()))
         .unwrap();
     Ok(response)
 }
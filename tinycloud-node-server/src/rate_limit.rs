@@ -0,0 +1,353 @@
+use dashmap::DashMap;
+use rocket::{
+    http::{Header, Status},
+    request::Request,
+    response::{self, Responder, Response},
+};
+use std::{
+    collections::HashSet,
+    fmt,
+    hash::Hash,
+    io::Cursor,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tinycloud_auth::resource::SpaceId;
+
+use crate::config::SpaceRateLimitConfig;
+
+/// Token-bucket limiter for authenticated requests, keyed by space, with a
+/// second set of buckets keyed by client address.
+///
+/// A request is first charged to its client, before anything about it is
+/// verified, which bounds how much verification work one client can cause.
+/// Its spaces are only checked against their buckets, and are charged once
+/// the route has verified the request, so a forged header can neither spend
+/// another space's budget nor add buckets. Disabled limiters admit every
+/// request without touching their state.
+#[derive(Clone)]
+pub struct SpaceRateLimiter {
+    buckets: Arc<DashMap<SpaceId, TokenBucket>>,
+    clients: Arc<DashMap<IpAddr, TokenBucket>>,
+    enabled: bool,
+    space_rate: Rate,
+    client_rate: Rate,
+}
+
+#[derive(Clone, Copy)]
+struct Rate {
+    tokens_per_second: f64,
+    burst: f64,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// What a rate-limited request ran out of tokens for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Limited {
+    Space(SpaceId),
+    Client(IpAddr),
+}
+
+/// A request rejected because its space or client has no tokens left.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub limited: Limited,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for Limited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Space(space) => write!(f, "space {space}"),
+            Self::Client(ip) => write!(f, "client {ip}"),
+        }
+    }
+}
+
+impl Rate {
+    fn new(requests_per_second: u32, burst: u32) -> Self {
+        Self {
+            tokens_per_second: f64::from(requests_per_second.max(1)),
+            burst: f64::from(burst.max(1)),
+        }
+    }
+
+    fn is_full(&self, bucket: &TokenBucket, now: Instant) -> bool {
+        self.refilled(bucket, now) >= self.burst
+    }
+
+    fn refilled(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        (bucket.tokens + elapsed * self.tokens_per_second).min(self.burst)
+    }
+
+    fn retry_after(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1.0 - tokens) / self.tokens_per_second)
+    }
+}
+
+impl SpaceRateLimiter {
+    pub fn new(config: &SpaceRateLimitConfig) -> Self {
+        Self {
+            buckets: Arc::new(DashMap::new()),
+            clients: Arc::new(DashMap::new()),
+            enabled: config.enabled,
+            space_rate: Rate::new(config.requests_per_second, config.burst),
+            client_rate: Rate::new(config.client_requests_per_second, config.client_burst),
+        }
+    }
+
+    /// Admit a request from `client` which claims `spaces`, before it is
+    /// verified. One token is taken from the client's bucket; the spaces'
+    /// buckets are only checked, and must be charged with
+    /// [`SpaceRateLimiter::charge`] once the request is verified.
+    pub fn admit<'a>(
+        &self,
+        client: IpAddr,
+        spaces: impl IntoIterator<Item = &'a SpaceId>,
+    ) -> Result<(), RateLimited> {
+        if !self.enabled {
+            return Ok(());
+        }
+        take(&self.clients, &client, self.client_rate, true).map_err(|retry_after| {
+            RateLimited {
+                limited: Limited::Client(client),
+                retry_after,
+            }
+        })?;
+        let now = Instant::now();
+        distinct(spaces).try_for_each(|space| match self.buckets.get(space) {
+            Some(bucket) => {
+                let tokens = self.space_rate.refilled(&bucket, now);
+                if tokens >= 1.0 {
+                    Ok(())
+                } else {
+                    Err(RateLimited {
+                        limited: Limited::Space(space.clone()),
+                        retry_after: self.space_rate.retry_after(tokens),
+                    })
+                }
+            }
+            None => Ok(()),
+        })
+    }
+
+    /// Charge one token to each space of a request the route has verified,
+    /// whether or not the spaces have a token left; an admitted request is
+    /// never turned away after the fact.
+    pub fn charge<'a>(&self, spaces: impl IntoIterator<Item = &'a SpaceId>) {
+        if !self.enabled {
+            return;
+        }
+        for space in distinct(spaces) {
+            let _ = take(&self.buckets, space, self.space_rate, false);
+        }
+    }
+
+    /// Charge the spaces of a request from the route's outcome. Requests
+    /// refused before or by verification — malformed (`400`), not
+    /// authenticated (`401`), replayed (`409`) or timed out (`504`) — are not
+    /// charged.
+    pub fn charge_outcome<'a, T>(
+        &self,
+        spaces: impl IntoIterator<Item = &'a SpaceId>,
+        outcome: &Result<T, (Status, String)>,
+    ) {
+        match outcome {
+            Err((
+                Status::BadRequest
+                | Status::Unauthorized
+                | Status::Conflict
+                | Status::GatewayTimeout,
+                _,
+            )) => {}
+            _ => self.charge(spaces),
+        }
+    }
+
+    /// Take one token from the bucket of each distinct space. The spaces
+    /// must be those of a verified request.
+    pub fn check<'a>(
+        &self,
+        spaces: impl IntoIterator<Item = &'a SpaceId>,
+    ) -> Result<(), RateLimited> {
+        if !self.enabled {
+            return Ok(());
+        }
+        distinct(spaces).try_for_each(|space| {
+            take(&self.buckets, space, self.space_rate, true).map_err(|retry_after| RateLimited {
+                limited: Limited::Space(space.clone()),
+                retry_after,
+            })
+        })
+    }
+
+    /// Drop the buckets that have refilled completely, which admit exactly
+    /// what a fresh bucket would. Returns how many were dropped.
+    pub fn purge_full(&self) -> usize {
+        let now = Instant::now();
+        let before = self.buckets.len() + self.clients.len();
+        self.buckets
+            .retain(|_, bucket| !self.space_rate.is_full(bucket, now));
+        self.clients
+            .retain(|_, bucket| !self.client_rate.is_full(bucket, now));
+        before.saturating_sub(self.buckets.len() + self.clients.len())
+    }
+}
+
+fn distinct<'a>(
+    spaces: impl IntoIterator<Item = &'a SpaceId>,
+) -> impl Iterator<Item = &'a SpaceId> {
+    spaces.into_iter().collect::<HashSet<_>>().into_iter()
+}
+
+/// Take a token from the bucket of `key`. Without `strict`, the token is
+/// taken even when the bucket is empty, which never drops below zero.
+fn take<K: Hash + Eq + Clone>(
+    buckets: &DashMap<K, TokenBucket>,
+    key: &K,
+    rate: Rate,
+    strict: bool,
+) -> Result<(), Duration> {
+    let now = Instant::now();
+    let mut bucket = buckets.entry(key.clone()).or_insert_with(|| TokenBucket {
+        tokens: rate.burst,
+        last_refill: now,
+    });
+
+    bucket.tokens = rate.refilled(&bucket, now);
+    bucket.last_refill = now;
+
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        Ok(())
+    } else if strict {
+        Err(rate.retry_after(bucket.tokens))
+    } else {
+        bucket.tokens = 0.0;
+        Ok(())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for RateLimited {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        // Retry-After is whole seconds; never advertise 0 for a rejected request.
+        let retry_after = self.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let body = format!("Rate limit exceeded for {}", self.limited);
+        Response::build()
+            .status(Status::TooManyRequests)
+            .header(Header::new("Retry-After", retry_after.to_string()))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limiter(enabled: bool, requests_per_second: u32, burst: u32) -> SpaceRateLimiter {
+        SpaceRateLimiter::new(&SpaceRateLimitConfig {
+            enabled,
+            requests_per_second,
+            burst,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn buckets_are_per_space_and_bounded_by_burst() {
+        let limiter = limiter(true, 1, 2);
+        let a: SpaceId = "tinycloud:key:a:default".parse().unwrap();
+        let b: SpaceId = "tinycloud:key:b:default".parse().unwrap();
+
+        assert!(limiter.check([&a]).is_ok());
+        assert!(limiter.check([&a]).is_ok());
+        let limited = limiter.check([&a]).unwrap_err();
+        assert_eq!(limited.limited, Limited::Space(a));
+        assert!(limited.retry_after > Duration::ZERO);
+        assert!(limited.retry_after <= Duration::from_secs(1));
+
+        // another space has its own bucket, and one request naming a space
+        // twice is charged once
+        assert!(limiter.check([&b, &b]).is_ok());
+        assert!(limiter.check([&b]).is_ok());
+        assert!(limiter.check([&b]).is_err());
+    }
+
+    #[test]
+    fn spaces_are_only_charged_for_verified_requests() {
+        let limiter = limiter(true, 1, 1);
+        let client = IpAddr::from([127, 0, 0, 1]);
+        let a: SpaceId = "tinycloud:key:a:default".parse().unwrap();
+
+        // admitting a request neither spends nor creates a space's bucket
+        for _ in 0..5 {
+            assert!(limiter.admit(client, [&a]).is_ok());
+        }
+        assert!(limiter.buckets.is_empty());
+
+        // a request which failed authentication is not charged
+        limiter.charge_outcome([&a], &Err::<(), _>((Status::Unauthorized, String::new())));
+        assert!(limiter.admit(client, [&a]).is_ok());
+
+        limiter.charge_outcome([&a], &Ok::<_, (Status, String)>(()));
+        let limited = limiter.admit(client, [&a]).unwrap_err();
+        assert_eq!(limited.limited, Limited::Space(a));
+    }
+
+    #[test]
+    fn clients_are_charged_before_verification() {
+        let limiter = SpaceRateLimiter::new(&SpaceRateLimitConfig {
+            enabled: true,
+            client_requests_per_second: 1,
+            client_burst: 2,
+            ..Default::default()
+        });
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let other = IpAddr::from([192, 0, 2, 2]);
+
+        assert!(limiter.admit(client, []).is_ok());
+        assert!(limiter.admit(client, []).is_ok());
+        let limited = limiter.admit(client, []).unwrap_err();
+        assert_eq!(limited.limited, Limited::Client(client));
+        assert!(limiter.admit(other, []).is_ok());
+    }
+
+    #[test]
+    fn full_buckets_are_purged() {
+        let fast = limiter(true, 1000, 1);
+        let a: SpaceId = "tinycloud:key:a:default".parse().unwrap();
+        let b: SpaceId = "tinycloud:key:b:default".parse().unwrap();
+
+        assert!(fast.check([&a]).is_ok());
+        assert!(fast.check([&b]).is_ok());
+        assert_eq!(fast.buckets.len(), 2);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(fast.purge_full(), 2);
+        assert!(fast.buckets.is_empty());
+
+        // a drained bucket is kept until it refills
+        let slow = limiter(true, 1, 1);
+        assert!(slow.check([&a]).is_ok());
+        assert_eq!(slow.purge_full(), 0);
+        assert!(slow.check([&a]).is_err());
+    }
+
+    #[test]
+    fn disabled_limiter_admits_everything() {
+        let limiter = limiter(false, 1, 1);
+        let client = IpAddr::from([127, 0, 0, 1]);
+        let space: SpaceId = "tinycloud:key:a:default".parse().unwrap();
+        for _ in 0..10 {
+            assert!(limiter.admit(client, [&space]).is_ok());
+            limiter.charge([&space]);
+            assert!(limiter.check([&space]).is_ok());
+        }
+    }
+}
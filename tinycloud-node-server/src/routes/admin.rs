@@ -1,6 +1,7 @@
 use rocket::{
     http::Status,
     request::{FromRequest, Outcome, Request},
+    response::status::Custom,
     serde::json::Json,
     State,
 };
@@ -10,6 +11,7 @@ use subtle::ConstantTimeEq;
 
 use crate::quota::QuotaCache;
 use crate::TinyCloud;
+use tinycloud_core::sql::SqlService;
 
 /// Request guard that validates `Authorization: Bearer <TINYCLOUD_ADMIN_SECRET>`.
 pub struct AdminAuth;
@@ -201,6 +203,196 @@ pub async fn get_usage(
     Ok(Json(UsageResponse { spaces, count }))
 }
 
+#[derive(Serialize)]
+pub struct SpaceSummary {
+    pub space_id: String,
+    /// Sequence number of the space's latest event, `null` if it has none.
+    pub latest_seq: Option<i64>,
+    pub usage_bytes: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SpaceListResponse {
+    pub spaces: Vec<SpaceSummary>,
+    pub count: usize,
+}
+
+/// Enumerate every space hosted on the node, sorted by space id, with its
+/// latest event sequence number and metered usage.
+#[get("/admin/spaces")]
+pub async fn list_spaces(
+    _auth: AdminAuth,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<SpaceListResponse>, (Status, String)> {
+    let spaces: Vec<SpaceSummary> = tinycloud
+        .list_spaces()
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?
+        .into_iter()
+        .map(|(space, latest_seq, usage_bytes)| SpaceSummary {
+            space_id: space.to_string(),
+            latest_seq,
+            usage_bytes,
+        })
+        .collect();
+    let count = spaces.len();
+    Ok(Json(SpaceListResponse { spaces, count }))
+}
+
+#[derive(Serialize)]
+pub struct PruneDelegationsResponse {
+    pub pruned: u64,
+}
+
+/// Prune delegations that have already expired, removing their abilities
+/// while the event log keeps them. Expired delegations that are still parents
+/// of live delegations, or that were revoked, are kept.
+#[post("/admin/delegations/prune")]
+pub async fn prune_delegations(
+    _auth: AdminAuth,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<PruneDelegationsResponse>, (Status, String)> {
+    let pruned = tinycloud
+        .prune_expired_delegations(time::OffsetDateTime::now_utc())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    Ok(Json(PruneDelegationsResponse { pruned }))
+}
+
+#[derive(Serialize)]
+pub struct SpaceCreateResponse {
+    pub space_id: String,
+    pub created: bool,
+}
+
+/// Provision a space ahead of any host delegation. Answers `201` when the
+/// space was created and `200` when it already existed; a later
+/// `tinycloud.space/host` delegation for it only records the delegation.
+#[post("/admin/spaces/<space_id>")]
+pub async fn create_space(
+    _auth: AdminAuth,
+    space_id: &str,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Custom<Json<SpaceCreateResponse>>, (Status, String)> {
+    let sid: tinycloud_auth::resource::SpaceId = space_id
+        .parse()
+        .map_err(|_| (Status::BadRequest, "Invalid space ID".into()))?;
+    let created = tinycloud
+        .create_space(&sid)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    if created {
+        ::tracing::info!(space = %sid, "space created by admin");
+    }
+    Ok(Custom(
+        if created { Status::Created } else { Status::Ok },
+        Json(SpaceCreateResponse {
+            space_id: space_id.to_string(),
+            created,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct SpaceFreezeResponse {
+    pub space_id: String,
+    pub frozen: bool,
+}
+
+/// Put a space into read-only mode. It keeps serving reads, but new
+/// delegations and writes are rejected with `403` until it is unfrozen.
+#[put("/admin/spaces/<space_id>/frozen")]
+pub async fn freeze_space(
+    _auth: AdminAuth,
+    space_id: &str,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<SpaceFreezeResponse>, (Status, String)> {
+    set_frozen(space_id, true, tinycloud).await
+}
+
+/// Return a frozen space to normal operation.
+#[delete("/admin/spaces/<space_id>/frozen")]
+pub async fn unfreeze_space(
+    _auth: AdminAuth,
+    space_id: &str,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<SpaceFreezeResponse>, (Status, String)> {
+    set_frozen(space_id, false, tinycloud).await
+}
+
+async fn set_frozen(
+    space_id: &str,
+    frozen: bool,
+    tinycloud: &TinyCloud,
+) -> Result<Json<SpaceFreezeResponse>, (Status, String)> {
+    let sid: tinycloud_auth::resource::SpaceId = space_id
+        .parse()
+        .map_err(|_| (Status::BadRequest, "Invalid space ID".into()))?;
+    if !tinycloud
+        .set_space_frozen(&sid, frozen)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?
+    {
+        return Err((Status::NotFound, "Space not found".into()));
+    }
+    Ok(Json(SpaceFreezeResponse {
+        space_id: space_id.to_string(),
+        frozen,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct SpaceDeleteResponse {
+    pub space_id: String,
+    pub removed_blocks: u64,
+}
+
+/// Permanently delete a space: its KV data and blocks, event log,
+/// delegations recorded only in it, hooks and SQL/DuckDB databases. The
+/// `confirm` query parameter must repeat the space ID. Deleting a space that
+/// is already gone succeeds, so a deletion that failed part way can be
+/// retried.
+#[delete("/admin/spaces/<space_id>?<confirm>")]
+pub async fn delete_space(
+    _auth: AdminAuth,
+    space_id: &str,
+    confirm: Option<&str>,
+    tinycloud: &State<TinyCloud>,
+    sql_service: &State<SqlService>,
+    #[cfg_attr(not(feature = "duckdb"), allow(unused_variables))]
+    duckdb_service: super::DuckDbInvokeState<'_>,
+) -> Result<Json<SpaceDeleteResponse>, (Status, String)> {
+    let sid: tinycloud_auth::resource::SpaceId = space_id
+        .parse()
+        .map_err(|_| (Status::BadRequest, "Invalid space ID".into()))?;
+    if confirm != Some(space_id) {
+        return Err((
+            Status::BadRequest,
+            "Deleting a space requires ?confirm=<space_id>".into(),
+        ));
+    }
+
+    let removed_blocks = tinycloud
+        .delete_space(&sid)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    sql_service
+        .remove_space(&sid)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    #[cfg(feature = "duckdb")]
+    duckdb_service
+        .remove_space(&sid)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+
+    ::tracing::warn!(space = %sid, removed_blocks, "space deleted by admin");
+    Ok(Json(SpaceDeleteResponse {
+        space_id: space_id.to_string(),
+        removed_blocks,
+    }))
+}
+
 /// Sort spaces by usage descending, with unknown (`None`) usage last.
 fn sort_usage_desc_nulls_last(spaces: &mut [SpaceUsage]) {
     spaces.sort_by(|a, b| match (a.usage_bytes, b.usage_bytes) {
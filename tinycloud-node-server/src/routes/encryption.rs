@@ -250,7 +250,7 @@ async fn verify_auth(
         .invoke::<BlockStage>(invocation, HashMap::new())
         .await
         .map(|_| ())
-        .map_err(|err| (Status::Unauthorized, err.to_string()));
+        .map_err(|err| (super::verify_error_status(&err), err.to_string()));
     crate::prometheus::observe_span(
         span,
         if result.is_ok() { "ok" } else { "error" },
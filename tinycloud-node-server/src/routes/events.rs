@@ -0,0 +1,146 @@
+use super::hooks::{find_parent_expiry, invocation_expiry};
+use crate::{authorization::AuthHeaderGetter, commit_feed::CommitFeed, TinyCloud};
+use rocket::{
+    get,
+    http::Status,
+    response::stream::{Event, EventStream},
+    tokio::sync::broadcast::error::RecvError,
+    State,
+};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{
+    commit_observer::CommitKind,
+    hash::Hash,
+    util::{Capability, InvocationInfo},
+};
+
+/// Stream the commits to `space` as server-sent events, as they happen.
+///
+/// The request carries an invocation of `tinycloud.capabilities/read` in the
+/// space, which is verified like any other before the stream opens. Each
+/// message is named after the commit's type and carries the commit as JSON.
+/// A client too slow to keep up is sent a `lagged` message with the number
+/// of commits it missed and continues from the oldest one still buffered.
+///
+/// The stream ends when the invocation or a delegation it was made under
+/// expires, and at the first revocation committed to the space after which
+/// its delegations, or any they derive from, are revoked.
+#[get("/events/<space>")]
+pub async fn space_events<'r>(
+    space: &str,
+    invocation: AuthHeaderGetter<InvocationInfo>,
+    tinycloud: &'r State<TinyCloud>,
+    feed: &'r State<CommitFeed>,
+) -> Result<EventStream![Event + 'r], (Status, String)> {
+    let space: SpaceId = space
+        .parse()
+        .map_err(|_| (Status::BadRequest, "invalid space id".to_string()))?;
+    if !reads_capabilities_of(&invocation.0 .0.capabilities, &space) {
+        return Err((
+            Status::Forbidden,
+            format!("subscribing to {space} requires tinycloud.capabilities/read"),
+        ));
+    }
+    let info = &invocation.0 .0;
+    let invocation_exp = invocation_expiry(info)?;
+    let expiry = find_parent_expiry(info, tinycloud)
+        .await?
+        .map_or(invocation_exp, |parent_exp| parent_exp.min(invocation_exp));
+    let parents: Vec<Hash> = info.parents.iter().map(|cid| Hash::from(*cid)).collect();
+    super::verify_auth("server.space_events", invocation.0, tinycloud).await?;
+
+    let mut receiver = feed.subscribe(&space);
+    let remaining = (expiry - OffsetDateTime::now_utc().unix_timestamp()).max(0) as u64;
+    Ok(EventStream! {
+        let expired = rocket::tokio::time::sleep(Duration::from_secs(remaining));
+        rocket::tokio::pin!(expired);
+        loop {
+            // a revocation may have been among the commits a lagging client
+            // missed
+            let maybe_revoked = rocket::tokio::select! {
+                _ = &mut expired => break,
+                message = receiver.recv() => match message {
+                    Ok(event) => {
+                        yield Event::json(&event)
+                            .id(event.seq.to_string())
+                            .event(event.kind.as_str());
+                        event.kind == CommitKind::Revocation
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        yield Event::data(missed.to_string()).event("lagged");
+                        true
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            // a chain that cannot be checked is treated as revoked
+            if maybe_revoked
+                && !parents.is_empty()
+                && !matches!(tinycloud.chain_revoked(&parents).await, Ok(false))
+            {
+                break;
+            }
+        }
+    }
+    .heartbeat(Duration::from_secs(30)))
+}
+
+pub(super) fn reads_capabilities_of(capabilities: &[Capability], space: &SpaceId) -> bool {
+    capabilities.iter().any(|capability| {
+        capability
+            .resource
+            .tinycloud_resource()
+            .is_some_and(|resource| {
+                resource.space() == space && resource.service().as_str() == "capabilities"
+            })
+            && capability.ability.as_ref().as_ref() == "tinycloud.capabilities/read"
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tinycloud_core::types::{Ability, Resource};
+
+    fn capability(space: &SpaceId, service: &str, ability: &str) -> Capability {
+        Capability {
+            resource: Resource::TinyCloud(space.clone().to_resource(
+                service.parse().unwrap(),
+                Some("all".parse().unwrap()),
+                None,
+                None,
+            )),
+            ability: Ability::try_from(ability.to_string()).unwrap(),
+            caveats: Default::default(),
+        }
+    }
+
+    #[test]
+    fn subscribing_requires_reading_the_spaces_capabilities() {
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let other: SpaceId = "tinycloud:key:test:other".parse().unwrap();
+
+        assert!(reads_capabilities_of(
+            &[capability(
+                &space,
+                "capabilities",
+                "tinycloud.capabilities/read"
+            )],
+            &space
+        ));
+        assert!(!reads_capabilities_of(
+            &[capability(
+                &other,
+                "capabilities",
+                "tinycloud.capabilities/read"
+            )],
+            &space
+        ));
+        assert!(!reads_capabilities_of(
+            &[capability(&space, "kv", "tinycloud.kv/get")],
+            &space
+        ));
+    }
+}
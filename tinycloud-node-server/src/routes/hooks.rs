@@ -19,6 +19,7 @@ use rocket::{
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tinycloud_auth::codec;
 use tinycloud_core::{
     hash::Blake3Hasher,
     hash::Hash,
@@ -412,7 +413,7 @@ fn scope_extends(requested_scope: &str, authorized_scope: &str) -> bool {
         || requested_scope.starts_with(&format!("{authorized_scope}/"))
 }
 
-fn invocation_expiry(invocation: &InvocationInfo) -> Result<i64, (Status, String)> {
+pub(super) fn invocation_expiry(invocation: &InvocationInfo) -> Result<i64, (Status, String)> {
     Ok(invocation
         .invocation
         .payload()
@@ -492,10 +493,10 @@ fn hook_subscription_id(
     hasher.update(callback_url.as_bytes());
     hasher.update(b":");
     hasher.update(created_at.as_bytes());
-    hasher.finalize().to_cid(0x55).to_string()
+    hasher.finalize().to_cid(codec::RAW).to_string()
 }
 
-async fn find_parent_expiry(
+pub(super) async fn find_parent_expiry(
     invocation: &InvocationInfo,
     tinycloud: &TinyCloud,
 ) -> Result<Option<i64>, (Status, String)> {
@@ -538,8 +539,6 @@ mod tests {
     use tempfile::TempDir;
     use tinycloud_auth::{
         authorization::{make_invocation, InvocationOptions},
-        ipld_core::cid::Cid,
-        multihash_codetable::{Code, MultihashDigest},
         resolver::DID_METHODS,
         resource::{Path, ResourceId, Service, SpaceId},
         siwe_recap::Ability,
@@ -564,7 +563,7 @@ mod tests {
         let _persisted = tempdir.keep();
         Ok(TinyCloud::new(
             db,
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?)
@@ -595,7 +594,7 @@ mod tests {
             None,
         );
 
-        let delegation = Cid::new_v1(0x55, Code::Blake3_256.digest(b"delegation"));
+        let delegation = codec::to_event_cid(b"delegation");
         let invocation = make_invocation(
             vec![(
                 hook_resource,
@@ -675,7 +674,7 @@ mod tests {
             None,
             None,
         );
-        let delegation = Cid::new_v1(0x55, Code::Blake3_256.digest(b"delegation"));
+        let delegation = codec::to_event_cid(b"delegation");
         let invocation = make_invocation(
             vec![(
                 hook_resource,
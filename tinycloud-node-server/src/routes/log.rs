@@ -0,0 +1,45 @@
+use crate::{authorization::AuthHeaderGetter, TinyCloud};
+use rocket::{get, http::Status, serde::json::Json, State};
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{event_log::LogEntry, util::InvocationInfo};
+
+/// Most epochs returned by one request for a space's log.
+const LOG_PAGE_EPOCHS: u32 = 256;
+
+/// The events committed to `space` after sequence number `since`, or from
+/// the start of the log without it, for another node to ingest.
+///
+/// Like [`super::events::space_events`], the request carries an invocation
+/// of `tinycloud.capabilities/read` in the space, though it is checked
+/// without being recorded, so one invocation can page through the whole log.
+/// A response covers at most [`LOG_PAGE_EPOCHS`] epochs; the caller asks
+/// again from the last entry's `seq` until the log comes back empty.
+#[get("/log/<space>?<since>")]
+pub async fn space_log(
+    space: &str,
+    since: Option<i64>,
+    invocation: AuthHeaderGetter<InvocationInfo>,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<Vec<LogEntry>>, (Status, String)> {
+    let space: SpaceId = space
+        .parse()
+        .map_err(|_| (Status::BadRequest, "invalid space id".to_string()))?;
+    if !super::events::reads_capabilities_of(&invocation.0 .0.capabilities, &space) {
+        return Err((
+            Status::Forbidden,
+            format!("reading the log of {space} requires tinycloud.capabilities/read"),
+        ));
+    }
+    // recording the invocation would add an event for the next page to
+    // return, and the caller would never reach the end
+    tinycloud
+        .authorize_invocation(&invocation.0 .0)
+        .await
+        .map_err(|e| (Status::Unauthorized, e.to_string()))?;
+
+    tinycloud
+        .event_log(&space, since.unwrap_or(-1), LOG_PAGE_EPOCHS)
+        .await
+        .map(Json)
+        .map_err(|e| (Status::InternalServerError, e.to_string()))
+}
@@ -0,0 +1,136 @@
+use crate::config::Config;
+use rocket::{
+    get,
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome, Request},
+};
+use subtle::ConstantTimeEq;
+
+/// Admits a scrape of [`metrics`] which presents `prometheus.token`. The node
+/// refuses to start with `prometheus.on_main_port` and no token, but a scrape
+/// is refused all the same if one is missing, since the metrics are labelled
+/// by space.
+pub struct MetricsAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAuth {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(config) = request.rocket().state::<Config>() else {
+            return Outcome::Error((Status::InternalServerError, "Config not managed"));
+        };
+        let Some(expected) = config.prometheus.token.as_deref() else {
+            return Outcome::Error((Status::Unauthorized, "No metrics token configured"));
+        };
+
+        match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+        {
+            Some(token) if token.as_bytes().ct_eq(expected.as_bytes()).into() => {
+                Outcome::Success(MetricsAuth)
+            }
+            _ => Outcome::Error((Status::Unauthorized, "Invalid metrics token")),
+        }
+    }
+}
+
+/// The node's Prometheus metrics, as served by the separate metrics server,
+/// for deployments which scrape the main port instead. Mounted when
+/// `prometheus.on_main_port` is set.
+#[get("/metrics")]
+pub fn metrics(_auth: MetricsAuth) -> (ContentType, Vec<u8>) {
+    (
+        ContentType::parse_flexible(prometheus::TEXT_FORMAT).unwrap_or(ContentType::Plain),
+        crate::prometheus::encode_text(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Prometheus;
+    use rocket::{http::Header, local::asynchronous::Client};
+
+    async fn client(token: Option<&str>) -> Client {
+        let config = Config {
+            prometheus: Prometheus {
+                on_main_port: true,
+                token: token.map(str::to_string),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rocket = rocket::build()
+            .mount("/", rocket::routes![metrics])
+            .manage(config);
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn metrics_are_served_in_the_text_format() {
+        crate::prometheus::STORAGE_OPERATIONS
+            .with_label_values(&["test", "read"])
+            .inc();
+        let client = client(Some("scrape")).await;
+        let response = client
+            .get("/metrics")
+            .header(Header::new("Authorization", "Bearer scrape"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type().unwrap().to_string(),
+            prometheus::TEXT_FORMAT
+        );
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains("# TYPE tinycloud_storage_operations_total counter"));
+    }
+
+    #[tokio::test]
+    async fn metrics_require_the_configured_token() {
+        let client = client(Some("scrape")).await;
+        assert_eq!(
+            client.get("/metrics").dispatch().await.status(),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            client
+                .get("/metrics")
+                .header(Header::new("Authorization", "Bearer other"))
+                .dispatch()
+                .await
+                .status(),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            client
+                .get("/metrics")
+                .header(Header::new("Authorization", "Bearer scrape"))
+                .dispatch()
+                .await
+                .status(),
+            Status::Ok
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_are_refused_without_a_configured_token() {
+        let client = client(None).await;
+        assert_eq!(
+            client.get("/metrics").dispatch().await.status(),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            client
+                .get("/metrics")
+                .header(Header::new("Authorization", "Bearer "))
+                .dispatch()
+                .await
+                .status(),
+            Status::Unauthorized
+        );
+    }
+}
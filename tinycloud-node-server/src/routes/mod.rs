@@ -5,22 +5,26 @@ use rocket::{data::ToByteUnit, http::Status, response::status::Custom, serde::js
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
-    time::Instant,
+    time::{Duration, Instant},
 };
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tinycloud_auth::resource::{Path, SpaceId};
 use tokio::io::AsyncReadExt;
-use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 use tracing::{info_span, Instrument};
 
 use crate::{
-    auth_guards::{DataIn, DataOut, InvOut, KVResponse, ObjectHeaders},
+    auth_guards::{
+        stored_metadata, DataIn, DataOut, Download, DryRun, InvOut, KVResponse, ObjectHeaders,
+    },
     authorization::AuthHeaderGetter,
     config::Config,
     hooks::{HookRuntime, WriteEvent},
+    idempotency::{Idempotency, RecordedResponse},
     invocation_replay::InvocationReplayCache,
     quota::QuotaCache,
-    routes::public::is_public_space,
+    rate_limit::{RateLimited, SpaceRateLimiter},
+    routes::public::{is_public_space, ClientIp},
     signed_urls::{
         load_signed_kv_ticket, mint_signed_kv_url, validate_signed_kv_hash_binding,
         validate_signed_kv_ticket, SignedKvUrlRequest, SignedKvUrlResponse, SignedUrlRuntime,
@@ -33,18 +37,27 @@ use tinycloud_core::duckdb::{
     DuckDbCaveats, DuckDbError, DuckDbRequest, DuckDbResponse, DuckDbService,
 };
 use tinycloud_core::{
+    db::kv_read_keys,
     encryption_network::EncryptionService,
     events::Invocation,
+    hash::Hash,
+    keys::StaticSecret,
+    manifest::{Manifest, ResolutionError},
     models::{
         hook_delivery, hook_subscription, invocation as invocation_model, kv_delete, kv_write,
     },
+    policy::PolicyError,
+    policy_capability::generated::accepted_actions,
     sea_orm::{
         error::{RuntimeErr, SqlxError},
         ColumnTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
     },
     sql::{SqlCaveats, SqlError, SqlRequest, SqlService},
     storage::{HashBuffer, ImmutableReadStore, ImmutableStaging},
-    types::{Ability, DelegationQuery, DelegationQueryPage, Metadata, Resource},
+    types::{
+        Ability, CapabilitiesAbility, DelegationQuery, DelegationQueryPage, KvAbility, Metadata,
+        Resource, TinyCloudAbility,
+    },
     util::{Capability, DelegationInfo, InvocationInfo, RevocationInfo},
     write_hooks::{db_table_path, hook_delivery_id, subscription_matches_event, TouchedTables},
     DelegationStatus, InvocationOutcome, KvInvokeOptions, KvPrecondition, TransactResult, TxError,
@@ -54,12 +67,16 @@ use tinycloud_core::{
 pub mod admin;
 pub mod attestation;
 pub mod encryption;
+pub mod events;
 pub mod hooks;
+pub mod log;
+pub mod metrics;
 pub mod public;
 #[cfg(feature = "tc-bench-v1")]
 pub mod tc_bench;
+pub mod uploads;
 pub mod util;
-use util::LimitedReader;
+use util::{LimitedReader, UploadLimit};
 
 fn retryable_sqlstate(code: &str) -> bool {
     matches!(code, "40001" | "40P01")
@@ -152,6 +169,76 @@ pub fn version(
     Json(build_info(tee, quota_cache, encryption, share_email))
 }
 
+/// Services whose abilities this node accepts, in the order they are
+/// advertised by `/.well-known/tinycloud-node`.
+const SERVED_SERVICES: &[&str] = &[
+    "tinycloud.kv",
+    "tinycloud.capabilities",
+    "tinycloud.delegation",
+    "tinycloud.hooks",
+    "tinycloud.sql",
+    #[cfg(feature = "duckdb")]
+    "tinycloud.duckdb",
+    "tinycloud.encryption",
+    "tinycloud.space",
+];
+
+/// Discovery document for clients choosing or configuring against a node.
+/// Everything here is public: keys are the public halves only, and limits
+/// are the ones a client would otherwise learn by hitting them.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WellKnownNode {
+    pub protocol: u32,
+    pub version: String,
+    /// did:key identifiers of the node's host keys.
+    pub host_keys: Vec<String>,
+    /// Accepted ability URNs, keyed by service.
+    pub abilities: BTreeMap<&'static str, &'static [&'static str]>,
+    pub limits: WellKnownLimits,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WellKnownLimits {
+    pub max_upload_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_storage_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delegation_depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delegation_ttl_secs: Option<u64>,
+    pub delegation_clock_skew_secs: u64,
+}
+
+fn well_known_document(config: &Config, node_did: &str) -> WellKnownNode {
+    let spaces = &config.spaces;
+    WellKnownNode {
+        protocol: tinycloud_auth::protocol::PROTOCOL_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        host_keys: vec![node_did.to_string()],
+        abilities: SERVED_SERVICES
+            .iter()
+            .filter_map(|service| Some((*service, accepted_actions(service)?)))
+            .collect(),
+        limits: WellKnownLimits {
+            max_upload_bytes: config.storage.max_upload.as_u64(),
+            default_storage_bytes: config.storage.limit.map(|limit| limit.as_u64()),
+            max_delegation_depth: spaces.max_delegation_depth,
+            max_delegation_ttl_secs: spaces.max_delegation_ttl_secs,
+            delegation_clock_skew_secs: spaces.delegation_clock_skew_secs.unwrap_or(0),
+        },
+    }
+}
+
+#[get("/.well-known/tinycloud-node")]
+pub fn well_known_node(
+    config: &State<Config>,
+    encryption: &State<EncryptionService>,
+) -> Json<WellKnownNode> {
+    Json(well_known_document(config, encryption.node_did()))
+}
+
 #[allow(clippy::let_unit_value)]
 pub mod util_routes {
     use super::*;
@@ -159,6 +246,18 @@ pub mod util_routes {
     #[options("/<_s..>")]
     pub async fn cors(_s: std::path::PathBuf) {}
 
+    /// Explains an `Authorization` header rejected for its size, rather than
+    /// leaving clients with a bare status.
+    #[catch(431)]
+    pub fn header_too_large(request: &rocket::Request<'_>) -> String {
+        format!(
+            "The Authorization header exceeds this node's limit of {} bytes. \
+             Split large capability sets across several delegations, or ask \
+             the node operator to raise headers.max_authorization.",
+            crate::authorization::max_authorization(request)
+        )
+    }
+
     #[get("/healthz")]
     pub async fn healthcheck(s: &State<TinyCloud>) -> Status {
         if s.check_db_connection().await.is_ok() {
@@ -167,6 +266,40 @@ pub mod util_routes {
             Status::InternalServerError
         }
     }
+
+    #[derive(Serialize)]
+    pub struct Readiness {
+        pub ready: bool,
+        /// Components whose check failed: `database` and/or `storage`.
+        pub failing: Vec<&'static str>,
+    }
+
+    /// Readiness probe. Unlike `/healthz` this also checks that the block
+    /// store is reachable, answering 503 when any component is down.
+    #[get("/readyz")]
+    pub async fn readiness(s: &State<TinyCloud>) -> Custom<Json<Readiness>> {
+        let mut failing = Vec::new();
+        if let Err(e) = s.check_db_connection().await {
+            tracing::warn!(error = %e, "database readiness check failed");
+            failing.push("database");
+        }
+        if let Err(e) = s.check_storage().await {
+            tracing::warn!(error = %e, "block storage readiness check failed");
+            failing.push("storage");
+        }
+        let status = if failing.is_empty() {
+            Status::Ok
+        } else {
+            Status::ServiceUnavailable
+        };
+        Custom(
+            status,
+            Json(Readiness {
+                ready: failing.is_empty(),
+                failing,
+            }),
+        )
+    }
 }
 
 #[get("/peer/generate/<space>")]
@@ -188,6 +321,63 @@ pub async fn open_host_key(
     })
 }
 
+/// A space's manifest: who may delegate and invoke its capabilities, and the
+/// peers hosting it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestResponse {
+    pub id: String,
+    pub delegators: Vec<String>,
+    pub invokers: Vec<String>,
+    pub bootstrap_peers: Vec<BootstrapPeerResponse>,
+}
+
+#[derive(Serialize)]
+pub struct BootstrapPeerResponse {
+    pub id: String,
+    pub addrs: Vec<String>,
+}
+
+impl From<Manifest> for ManifestResponse {
+    fn from(manifest: Manifest) -> Self {
+        Self {
+            id: manifest.id().to_string(),
+            delegators: manifest
+                .delegators()
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            invokers: manifest.invokers().iter().map(|i| i.to_string()).collect(),
+            bootstrap_peers: manifest
+                .bootstrap_peers()
+                .peers
+                .iter()
+                .map(|peer| BootstrapPeerResponse {
+                    id: peer.id.to_string(),
+                    addrs: peer.addrs.iter().map(|a| a.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Resolve a space's DID with the node's resolver into its manifest.
+#[get("/manifest/<space>")]
+pub async fn manifest(
+    space: &str,
+    tinycloud: &State<TinyCloud>,
+) -> Result<Json<ManifestResponse>, (Status, String)> {
+    let space: SpaceId = space
+        .parse()
+        .map_err(|_| (Status::BadRequest, "Invalid space ID".to_string()))?;
+    match Manifest::resolve(&space, tinycloud.resolver()).await {
+        Ok(Some(manifest)) => Ok(Json(manifest.into())),
+        Ok(None) => Err((Status::NotFound, format!("no manifest for {space}"))),
+        Err(e @ ResolutionError::Deactivated) => Err((Status::Gone, e.to_string())),
+        Err(e @ ResolutionError::Resolver(_)) => Err((Status::BadGateway, e.to_string())),
+    }
+}
+
 #[post("/signed/kv", format = "json", data = "<request>")]
 pub async fn create_signed_kv_url(
     invocation: AuthHeaderGetter<InvocationInfo>,
@@ -255,6 +445,19 @@ pub struct DelegateResponse {
     pub skipped: Vec<String>,
 }
 
+/// What a delegation would grant if submitted to `/delegate`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegationPreview {
+    pub cid: String,
+    pub delegator: String,
+    pub delegate: String,
+    pub capabilities: Vec<Capability>,
+    pub expiry: Option<String>,
+    pub not_before: Option<String>,
+    pub parents: Vec<String>,
+}
+
 #[derive(Deserialize)]
 pub struct DelegationStatusRequest {
     pub cid: String,
@@ -308,7 +511,7 @@ pub async fn delegation_status(
 ) -> Result<Custom<Json<DelegationStatusResponse>>, (Status, String)> {
     let request = request.into_inner();
     let auth = &invocation.0 .0;
-    invocation_model::verify_invocation(&auth.invocation)
+    invocation_model::verify_invocation(&auth.invocation, tinycloud.resolver())
         .await
         .map_err(|_| {
             (
@@ -391,12 +594,28 @@ pub async fn delegation_query(
         })
 }
 
-#[post("/delegate")]
+/// The deadline for work allowed `secs` seconds, after which it is given up
+/// with `504 Gateway Timeout` unless it has started committing. `0` waits
+/// indefinitely.
+fn deadline_after(secs: u64) -> Option<tokio::time::Instant> {
+    (secs != 0).then(|| tokio::time::Instant::now() + Duration::from_secs(secs))
+}
+
+/// Store a delegation, sent in the `Authorization` header or, for one too
+/// large for a header, as `{"authorization": "<encoded delegation>"}`.
+#[post("/delegate", data = "<d>")]
 pub async fn delegate(
     d: AuthHeaderGetter<DelegationInfo>,
+    client: ClientIp,
     req_span: TracingSpan,
     tinycloud: &State<TinyCloud>,
-) -> Result<Json<DelegateResponse>, (Status, String)> {
+    config: &State<Config>,
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<Json<DelegateResponse>, rocket::Either<RateLimited, (Status, String)>> {
+    let spaces: Vec<SpaceId> = d.0 .0.spaces().cloned().collect();
+    space_rate_limiter
+        .admit(client.0, &spaces)
+        .map_err(rocket::Either::Left)?;
     let action_label = "delegation";
     let span = info_span!(parent: &req_span.0, "delegate", action = %action_label);
     // Instrumenting async block to handle yielding properly
@@ -407,20 +626,9 @@ pub async fn delegate(
                 .start_timer()
         });
         let res = tinycloud
-            .delegate(d.0)
+            .delegate_before(d.0, deadline_after(config.timeouts.delegate_secs))
             .await
-            .map_err(|e| {
-                (
-                    match &e {
-                        TxError::SpaceNotFound => Status::NotFound,
-                        TxError::Db(error) | TxError::EpochInsert(error) => {
-                            database_error_status(error)
-                        }
-                        _ => Status::Unauthorized,
-                    },
-                    e.to_string(),
-                )
-            })
+            .map_err(|e| (tx_error_status(&e), e.to_string()))
             .and_then(|result: TransactResult| {
                 let activated: Vec<String> = result.commits.keys().map(|s| s.to_string()).collect();
                 let skipped: Vec<String> = result
@@ -437,7 +645,7 @@ pub async fn delegate(
                     .next()
                     .and_then(|c| c.committed_events.into_iter().next())
                     .or_else(|| result.delegation_cids.into_iter().next())
-                    .map(|h| h.to_cid(0x55).to_string())
+                    .map(|h| h.to_event_cid().to_string())
                     .ok_or_else(|| {
                         (Status::Unauthorized, "Delegation not committed".to_string())
                     })?;
@@ -451,10 +659,31 @@ pub async fn delegate(
         if let Some(timer) = timer {
             timer.observe_duration();
         }
+        space_rate_limiter.charge_outcome(&spaces, &res);
         res
     }
     .instrument(span)
     .await
+    .map_err(rocket::Either::Right)
+}
+
+/// Expand a delegation the way `/delegate` would, without verifying or
+/// storing it, so a client can see exactly which resources and abilities it
+/// grants before submitting it.
+#[post("/delegate/preview")]
+pub fn delegate_preview(d: AuthHeaderGetter<DelegationInfo>) -> Json<DelegationPreview> {
+    let cid = d.0.content_hash().to_event_cid().to_string();
+    let info = d.0 .0;
+    let format = |t: OffsetDateTime| t.format(&Rfc3339).ok();
+    Json(DelegationPreview {
+        cid,
+        delegator: info.delegator,
+        delegate: info.delegate,
+        capabilities: info.capabilities,
+        expiry: info.expiry.and_then(format),
+        not_before: info.not_before.and_then(format),
+        parents: info.parents.iter().map(ToString::to_string).collect(),
+    })
 }
 
 /// W1 (C): node-confirmed revocation surface.
@@ -465,8 +694,9 @@ pub async fn delegate(
 /// signature suite is staged on top of the existing pipeline as a followup
 /// (it requires a new variant in `tinycloud-auth::TinyCloudRevocation`); the
 /// route itself is mounted so the Policy Engine `active_cutoff` loop can
-/// rely on a confirmation response.
-#[post("/revoke")]
+/// rely on a confirmation response. Like `/delegate`, the revocation may be
+/// sent in a JSON body instead of the `Authorization` header.
+#[post("/revoke", data = "<r>")]
 pub async fn revoke(
     r: AuthHeaderGetter<RevocationInfo>,
     req_span: TracingSpan,
@@ -508,8 +738,12 @@ pub struct RevokeResponse {
 #[allow(clippy::too_many_arguments)]
 pub async fn invoke(
     i: AuthHeaderGetter<InvocationInfo>,
+    client: ClientIp,
+    idempotency: Idempotency,
     req_span: TracingSpan,
     headers: ObjectHeaders,
+    dry_run: DryRun,
+    download: Download,
     data: DataIn<'_>,
     staging: &State<BlockStage>,
     tinycloud: &State<TinyCloud>,
@@ -519,11 +753,25 @@ pub async fn invoke(
     sql_service: &State<SqlService>,
     duckdb_service: &State<DuckDbService>,
     hook_runtime: &State<HookRuntime>,
-) -> Result<DataOut<<BlockStores as ImmutableReadStore>::Readable>, (Status, String)> {
-    invoke_impl(
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<
+    rocket::Either<DataOut<<BlockStores as ImmutableReadStore>::Readable>, RecordedResponse>,
+    rocket::Either<RateLimited, (Status, String)>,
+> {
+    let spaces: Vec<SpaceId> = i.0 .0.spaces().cloned().collect();
+    space_rate_limiter
+        .admit(client.0, &spaces)
+        .map_err(rocket::Either::Left)?;
+    if let Idempotency::Replay(response) = idempotency {
+        // only responses to verified invocations are recorded
+        space_rate_limiter.charge(&spaces);
+        return Ok(rocket::Either::Right(response));
+    }
+    let res = invoke_impl(
         i,
         req_span,
         headers,
+        dry_run,
         data,
         staging,
         tinycloud,
@@ -534,7 +782,10 @@ pub async fn invoke(
         duckdb_service,
         hook_runtime,
     )
-    .await
+    .await;
+    space_rate_limiter.charge_outcome(&spaces, &res);
+    res.map(|out| rocket::Either::Left(download.apply(out)))
+        .map_err(rocket::Either::Right)
 }
 
 #[post("/invoke", data = "<data>")]
@@ -542,8 +793,12 @@ pub async fn invoke(
 #[allow(clippy::too_many_arguments)]
 pub async fn invoke(
     i: AuthHeaderGetter<InvocationInfo>,
+    client: ClientIp,
+    idempotency: Idempotency,
     req_span: TracingSpan,
     headers: ObjectHeaders,
+    dry_run: DryRun,
+    download: Download,
     data: DataIn<'_>,
     staging: &State<BlockStage>,
     tinycloud: &State<TinyCloud>,
@@ -552,11 +807,25 @@ pub async fn invoke(
     invocation_replay_cache: &State<InvocationReplayCache>,
     sql_service: &State<SqlService>,
     hook_runtime: &State<HookRuntime>,
-) -> Result<DataOut<<BlockStores as ImmutableReadStore>::Readable>, (Status, String)> {
-    invoke_impl(
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<
+    rocket::Either<DataOut<<BlockStores as ImmutableReadStore>::Readable>, RecordedResponse>,
+    rocket::Either<RateLimited, (Status, String)>,
+> {
+    let spaces: Vec<SpaceId> = i.0 .0.spaces().cloned().collect();
+    space_rate_limiter
+        .admit(client.0, &spaces)
+        .map_err(rocket::Either::Left)?;
+    if let Idempotency::Replay(response) = idempotency {
+        // only responses to verified invocations are recorded
+        space_rate_limiter.charge(&spaces);
+        return Ok(rocket::Either::Right(response));
+    }
+    let res = invoke_impl(
         i,
         req_span,
         headers,
+        dry_run,
         data,
         staging,
         tinycloud,
@@ -567,13 +836,16 @@ pub async fn invoke(
         (),
         hook_runtime,
     )
-    .await
+    .await;
+    space_rate_limiter.charge_outcome(&spaces, &res);
+    res.map(|out| rocket::Either::Left(download.apply(out)))
+        .map_err(rocket::Either::Right)
 }
 
 #[cfg(feature = "duckdb")]
-type DuckDbInvokeState<'a> = &'a State<DuckDbService>;
+pub(crate) type DuckDbInvokeState<'a> = &'a State<DuckDbService>;
 #[cfg(not(feature = "duckdb"))]
-type DuckDbInvokeState<'a> = ();
+pub(crate) type DuckDbInvokeState<'a> = ();
 
 type KvInputMap = HashMap<
     (SpaceId, Path),
@@ -601,11 +873,14 @@ fn take_metadata_header(metadata: &mut Metadata, name: &str) -> Option<String> {
     metadata.0.remove(&key)
 }
 
+/// The digest of a strong TinyCloud ETag, of the value either as stored or as
+/// served gzipped.
 fn parse_strong_blake3_etag(value: &str) -> Result<[u8; 32], (Status, String)> {
     let value = value.trim();
     let digest = value
         .strip_prefix("\"blake3-")
         .and_then(|value| value.strip_suffix('"'))
+        .map(|digest| digest.strip_suffix("-gzip").unwrap_or(digest))
         .ok_or_else(|| {
             (
                 Status::BadRequest,
@@ -633,6 +908,25 @@ fn parse_strong_blake3_etag(value: &str) -> Result<[u8; 32], (Status, String)> {
     })
 }
 
+/// An `X-Content-Hash` value: the hex BLAKE3 digest of the put value, bare or
+/// in the strong ETag form the node returns.
+fn parse_content_hash(value: &str) -> Result<[u8; 32], (Status, String)> {
+    let value = value.trim();
+    let digest = value
+        .strip_prefix("\"blake3-")
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    hex::decode(digest)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            (
+                Status::BadRequest,
+                "X-Content-Hash must be a hex BLAKE3 digest of 64 characters".to_string(),
+            )
+        })
+}
+
 fn parse_positive_u64_header(
     metadata: &mut Metadata,
     name: &str,
@@ -681,20 +975,11 @@ fn kv_invoke_options_for_capabilities(
     let mutation_targets = capabilities
         .iter()
         .filter_map(|capability| {
-            match (&capability.resource, capability.ability.as_ref().as_ref()) {
-                (Resource::TinyCloud(resource), ability)
-                    if resource.service().as_str() == "kv"
-                        && matches!(
-                            ability,
-                            "tinycloud.kv/put" | "tinycloud.kv/del" | "tinycloud.kv/delete"
-                        )
-                        && resource.path().is_some() =>
+            match (&capability.resource, capability.ability.tinycloud()?.kv()?) {
+                (Resource::TinyCloud(resource), ability @ (KvAbility::Put | KvAbility::Del))
+                    if resource.service().as_str() == "kv" && resource.path().is_some() =>
                 {
-                    Some((
-                        resource.space().clone(),
-                        resource.path()?.clone(),
-                        ability.to_string(),
-                    ))
+                    Some((resource.space().clone(), resource.path()?.clone(), ability))
                 }
                 _ => None,
             }
@@ -709,7 +994,7 @@ fn kv_invoke_options_for_capabilities(
                 "If-None-Match only supports * for KV create".to_string(),
             ));
         }
-        if multipart || mutation_targets.len() != 1 || mutation_targets[0].2 != "tinycloud.kv/put" {
+        if multipart || mutation_targets.len() != 1 || mutation_targets[0].2 != KvAbility::Put {
             return Err((
                 Status::BadRequest,
                 "If-None-Match: * requires exactly one non-multipart KV put".to_string(),
@@ -731,6 +1016,21 @@ fn kv_invoke_options_for_capabilities(
         );
     }
 
+    let mut content_hashes = HashMap::new();
+    if let Some(value) = take_metadata_header(&mut headers.0, "x-content-hash") {
+        if multipart || mutation_targets.len() != 1 || mutation_targets[0].2 != KvAbility::Put {
+            return Err((
+                Status::BadRequest,
+                "X-Content-Hash requires exactly one non-multipart KV put".to_string(),
+            ));
+        }
+        let (space, path, _) = &mutation_targets[0];
+        content_hashes.insert(
+            (space.clone(), path.clone()),
+            tinycloud_core::hash::Hash::from_blake3_digest(parse_content_hash(&value)?),
+        );
+    }
+
     let max_response_bytes =
         parse_positive_u64_header(&mut headers.0, "x-tinycloud-max-response-bytes")?;
     let list_limit = parse_positive_u64_header(&mut headers.0, "x-tinycloud-limit")?
@@ -745,11 +1045,43 @@ fn kv_invoke_options_for_capabilities(
             }
         })
         .transpose()?;
+    let list_detailed = take_metadata_header(&mut headers.0, "x-tinycloud-list-detailed")
+        .map(|value| match value.trim() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err((
+                Status::BadRequest,
+                "x-tinycloud-list-detailed must be true or false".to_string(),
+            )),
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    // the remaining headers are the metadata of a put, and likewise the
+    // metadata a set-metadata merges in; its empty body's length is not the
+    // stored value's
+    let mut metadata_updates = HashMap::new();
+    for capability in capabilities {
+        if let (Resource::TinyCloud(resource), Some(TinyCloudAbility::Kv(KvAbility::SetMetadata))) =
+            (&capability.resource, capability.ability.tinycloud())
+        {
+            if let (Some(path), "kv") = (resource.path(), resource.service().as_str()) {
+                let mut update = headers.0.clone();
+                take_metadata_header(&mut update, "content-length");
+                metadata_updates.insert((resource.space().clone(), path.clone()), update);
+            }
+        }
+    }
 
     Ok(KvInvokeOptions {
         preconditions,
         max_response_bytes,
         list_limit,
+        list_detailed,
+        dry_run: false,
+        content_hashes,
+        metadata_updates,
+        quota_limits: HashMap::new(),
     })
 }
 
@@ -767,8 +1099,8 @@ fn kv_put_capabilities(invocation: &InvocationInfo) -> Vec<(SpaceId, Path)> {
     invocation
         .capabilities
         .iter()
-        .filter_map(|c| match (&c.resource, c.ability.as_ref().as_ref()) {
-            (Resource::TinyCloud(r), "tinycloud.kv/put")
+        .filter_map(|c| match (&c.resource, c.ability.tinycloud()?) {
+            (Resource::TinyCloud(r), TinyCloudAbility::Kv(KvAbility::Put))
                 if r.service().as_str() == "kv" && r.path().is_some() =>
             {
                 Some((r.space().clone(), r.path()?.clone()))
@@ -780,8 +1112,8 @@ fn kv_put_capabilities(invocation: &InvocationInfo) -> Vec<(SpaceId, Path)> {
 
 fn is_tight_kv_put_capability(capability: &Capability) -> bool {
     matches!(
-        (&capability.resource, capability.ability.as_ref().as_ref()),
-        (Resource::TinyCloud(resource), "tinycloud.kv/put")
+        (&capability.resource, capability.ability.tinycloud()),
+        (Resource::TinyCloud(resource), Some(TinyCloudAbility::Kv(KvAbility::Put)))
             if resource.service().as_str() == "kv" && resource.path().is_some()
     )
 }
@@ -908,15 +1240,42 @@ async fn staged_batch_remaining(
     Ok(Some((remaining, current_size, limit_bytes)))
 }
 
+fn upload_too_large(config: &Config) -> (Status, String) {
+    (
+        Status::PayloadTooLarge,
+        format!(
+            "Request body exceeds the maximum upload size of {} bytes",
+            config.storage.max_upload.as_u64()
+        ),
+    )
+}
+
+fn multipart_error(error: multer::Error, config: &Config) -> (Status, String) {
+    if util::is_upload_too_large(&error) {
+        upload_too_large(config)
+    } else {
+        (Status::BadRequest, error.to_string())
+    }
+}
+
+fn staging_error(error: std::io::Error, config: &Config) -> (Status, String) {
+    if util::is_upload_too_large(&error) {
+        upload_too_large(config)
+    } else {
+        (Status::InternalServerError, error.to_string())
+    }
+}
+
 async fn copy_multipart_field_to_stage(
     mut field: multer::Field<'_>,
     stage: &mut HashBuffer<<BlockStage as ImmutableStaging>::Writable>,
     remaining: &mut Option<(u64, u64, u64)>,
+    config: &Config,
 ) -> Result<(), (Status, String)> {
     while let Some(chunk) = field
         .chunk()
         .await
-        .map_err(|e| (Status::BadRequest, e.to_string()))?
+        .map_err(|e| multipart_error(e, config))?
     {
         if let Some((remaining_bytes, current_size, limit_bytes)) = remaining.as_mut() {
             let chunk_len = u64::try_from(chunk.len())
@@ -963,7 +1322,15 @@ async fn build_batch_kv_inputs(
     })?;
     let boundary =
         multer::parse_boundary(content_type).map_err(|e| (Status::BadRequest, e.to_string()))?;
-    let mut multipart = multer::Multipart::with_reader(data.open(1u8.gigabytes()), boundary);
+    let max_upload = config.storage.max_upload.as_u64();
+    let mut multipart = multer::Multipart::with_reader(
+        UploadLimit::new(
+            data.open(max_upload.saturating_add(1).bytes()).compat(),
+            max_upload,
+        )
+        .compat(),
+        boundary,
+    );
     let mut inputs = HashMap::new();
     let (space, _) = expected
         .values()
@@ -974,7 +1341,7 @@ async fn build_batch_kv_inputs(
     while let Some(field) = multipart
         .next_field()
         .await
-        .map_err(|e| (Status::BadRequest, e.to_string()))?
+        .map_err(|e| multipart_error(e, config))?
     {
         let encoded_path = field
             .name()
@@ -999,12 +1366,12 @@ async fn build_batch_kv_inputs(
             ));
         }
 
-        let metadata = field_metadata(&field);
+        let metadata = stored_metadata(field_metadata(&field), &config.storage.metadata_headers);
         let mut stage = staging
             .stage(space)
             .await
             .map_err(|e| (Status::InternalServerError, e.to_string()))?;
-        copy_multipart_field_to_stage(field, &mut stage, &mut remaining).await?;
+        copy_multipart_field_to_stage(field, &mut stage, &mut remaining, config).await?;
         inputs.insert((space.clone(), typed_path.clone()), (metadata, stage));
     }
 
@@ -1028,11 +1395,125 @@ async fn build_batch_kv_inputs(
     Ok(inputs)
 }
 
+/// SQL-only entry point. Handles a `tinycloud.sql/*` invocation exactly as
+/// `/invoke` does, but rejects invocations that carry no SQL capability
+/// rather than dispatching them to another service.
+#[post("/sql", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn sql_invoke(
+    i: AuthHeaderGetter<InvocationInfo>,
+    client: ClientIp,
+    req_span: TracingSpan,
+    data: DataIn<'_>,
+    tinycloud: &State<TinyCloud>,
+    config: &State<Config>,
+    quota_cache: &State<QuotaCache>,
+    invocation_replay_cache: &State<InvocationReplayCache>,
+    sql_service: &State<SqlService>,
+    hook_runtime: &State<HookRuntime>,
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<
+    DataOut<<BlockStores as ImmutableReadStore>::Readable>,
+    rocket::Either<RateLimited, (Status, String)>,
+> {
+    let spaces: Vec<SpaceId> = i.0 .0.spaces().cloned().collect();
+    space_rate_limiter
+        .admit(client.0, &spaces)
+        .map_err(rocket::Either::Left)?;
+    let span = info_span!(parent: &req_span.0, "invoke", action = "sql");
+    let res = async move {
+        let timer = crate::prometheus::enabled().then(|| {
+            crate::prometheus::AUTHORIZED_INVOKE_HISTOGRAM
+                .with_label_values(&["sql"])
+                .start_timer()
+        });
+
+        let sql_caps = sql_capabilities(&i.0 .0);
+        if sql_caps.is_empty() {
+            return Err((
+                Status::BadRequest,
+                "Invocation carries no tinycloud.sql capability".to_string(),
+            ));
+        }
+        invocation_replay_cache.check_and_insert(&i.0).await?;
+
+        let result = handle_sql_invoke(
+            i,
+            data,
+            tinycloud,
+            sql_service,
+            hook_runtime,
+            quota_cache,
+            config,
+            &sql_caps,
+        )
+        .await;
+        if let Some(timer) = timer {
+            timer.observe_duration();
+        }
+        result
+    }
+    .instrument(span)
+    .await;
+    space_rate_limiter.charge_outcome(&spaces, &res);
+    res.map_err(rocket::Either::Right)
+}
+
+/// `(space, path, ability)` for each `tinycloud.sql/*` capability of the
+/// invocation.
+fn sql_capabilities(invocation: &InvocationInfo) -> Vec<(SpaceId, Option<String>, String)> {
+    invocation
+        .capabilities
+        .iter()
+        .filter_map(|c| match (&c.resource, c.ability.as_ref().as_ref()) {
+            (Resource::TinyCloud(r), ability)
+                if r.service().as_str() == "sql" && ability.starts_with("tinycloud.sql/") =>
+            {
+                Some((
+                    r.space().clone(),
+                    r.path().map(|p| p.to_string()),
+                    ability.to_string(),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// SQL and DuckDB writes do not pass through the frozen-space check of
+/// `transact`, so any that touch a frozen space are refused here.
+async fn reject_frozen_database_writes(
+    tinycloud: &TinyCloud,
+    caps: &[(SpaceId, Option<String>, String)],
+) -> Result<(), (Status, String)> {
+    const READS: [&str; 5] = [
+        "tinycloud.sql/read",
+        "tinycloud.sql/select",
+        "tinycloud.duckdb/read",
+        "tinycloud.duckdb/select",
+        "tinycloud.duckdb/export",
+    ];
+    for (space, _, _) in caps
+        .iter()
+        .filter(|(_, _, ability)| !READS.contains(&ability.as_str()))
+    {
+        let frozen = tinycloud
+            .is_space_frozen(space)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+        if frozen == Some(true) {
+            return Err((Status::Forbidden, format!("Space is frozen: {space}")));
+        }
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn invoke_impl(
     i: AuthHeaderGetter<InvocationInfo>,
     req_span: TracingSpan,
     mut headers: ObjectHeaders,
+    DryRun(dry_run): DryRun,
     data: DataIn<'_>,
     staging: &State<BlockStage>,
     tinycloud: &State<TinyCloud>,
@@ -1055,29 +1536,31 @@ async fn invoke_impl(
                 .start_timer()
         });
 
-        invocation_replay_cache.check_and_insert(&i.0).await?;
+        // a dry run has no effect, so it does not spend the invocation
+        if !dry_run {
+            invocation_replay_cache.check_and_insert(&i.0).await?;
+        }
 
         // Check for SQL capabilities
-        let sql_caps: Vec<_> = i
-            .0
-             .0
-            .capabilities
-            .iter()
-            .filter_map(|c| match (&c.resource, c.ability.as_ref().as_ref()) {
-                (Resource::TinyCloud(r), ability)
-                    if r.service().as_str() == "sql" && ability.starts_with("tinycloud.sql/") =>
-                {
-                    Some((
-                        r.space().clone(),
-                        r.path().map(|p| p.to_string()),
-                        ability.to_string(),
-                    ))
-                }
-                _ => None,
-            })
-            .collect();
+        let sql_caps = sql_capabilities(&i.0 .0);
+
+        if dry_run
+            && (!sql_caps.is_empty()
+                || i.0 .0.capabilities.iter().any(|c| {
+                    matches!(
+                        &c.resource,
+                        Resource::TinyCloud(r) if r.service().as_str() == "duckdb"
+                    )
+                }))
+        {
+            return Err((
+                Status::BadRequest,
+                "Dry runs are only supported for kv and capability invocations".to_string(),
+            ));
+        }
 
         if !sql_caps.is_empty() {
+            reject_frozen_database_writes(tinycloud, &sql_caps).await?;
             let result = handle_sql_invoke(
                 i,
                 data,
@@ -1118,6 +1601,7 @@ async fn invoke_impl(
                     .collect();
 
             if !duckdb_caps.is_empty() {
+                reject_frozen_database_writes(tinycloud, &duckdb_caps).await?;
                 let arrow_format = headers.0 .0.iter().any(|(k, v)| {
                     k.eq_ignore_ascii_case("accept")
                         && v.contains("application/vnd.apache.arrow.stream")
@@ -1161,8 +1645,32 @@ async fn invoke_impl(
 
         let put_caps = kv_put_capabilities(&i.0 .0);
         let is_multipart_request = is_multipart(&headers);
-        let kv_options = kv_invoke_options(&i.0 .0, &mut headers, is_multipart_request)?;
-        let expected_batch_inputs = if is_multipart_request && !put_caps.is_empty() {
+        let mut kv_options = kv_invoke_options(&i.0 .0, &mut headers, is_multipart_request)?;
+        kv_options.dry_run = dry_run;
+        kv_options.metadata_updates = std::mem::take(&mut kv_options.metadata_updates)
+            .into_iter()
+            .map(|(key, update)| (key, stored_metadata(update, &config.storage.metadata_headers)))
+            .collect();
+        for capability in &i.0 .0.capabilities {
+            let (Resource::TinyCloud(resource), Some(TinyCloudAbility::Capabilities(CapabilitiesAbility::Usage))) =
+                (&capability.resource, capability.ability.tinycloud())
+            else {
+                continue;
+            };
+            let space = resource.space();
+            let limit = if is_public_space(space) {
+                Some(config.public_spaces.storage_limit)
+            } else {
+                quota_cache.get_limit(space).await
+            };
+            if let Some(limit) = limit {
+                kv_options.quota_limits.insert(space.clone(), limit.as_u64());
+            }
+        }
+        let expected_batch_inputs = if dry_run {
+            // content is neither staged nor checked against the capabilities
+            None
+        } else if is_multipart_request && !put_caps.is_empty() {
             Some(validate_kv_batch_capabilities(&i.0 .0, &put_caps)?)
         } else {
             None
@@ -1177,13 +1685,28 @@ async fn invoke_impl(
         let staging_start = Instant::now();
         let inputs_result: Result<KvInputMap, (Status, String)> =
             match (data, put_caps.as_slice(), is_multipart_request) {
+                _ if dry_run => Ok(HashMap::new()),
                 (DataIn::None | DataIn::One(_), [], _) => Ok(HashMap::new()),
             (DataIn::One(d), [(space, path)], false) => {
                 let mut stage = staging
                     .stage(space)
                     .await
                     .map_err(|e| (Status::InternalServerError, e.to_string()))?;
-                let open_data = d.open(1u8.gigabytes()).compat();
+                // Never override a client-supplied content type.
+                let max_upload = config.storage.max_upload.as_u64();
+                let (sniffed, open_data) = util::sniff_content_type(
+                    UploadLimit::new(d.open(max_upload.saturating_add(1).bytes()).compat(), max_upload),
+                    config.storage.sniff_content_type
+                        && metadata_header(&headers.0, "content-type").is_none(),
+                )
+                .await
+                .map_err(|e| staging_error(e, config))?;
+                if let Some(content_type) = sniffed {
+                    headers
+                        .0
+                         .0
+                        .insert("content-type".to_string(), content_type.to_string());
+                }
 
                 // Use public space storage limit if applicable, otherwise per-space quota
                 let effective_limit = if is_public_space(space) {
@@ -1215,7 +1738,9 @@ async fn invoke_impl(
                             futures::io::copy(LimitedReader::new(open_data, remaining), &mut stage)
                                 .await
                                 .map_err(|e| {
-                                    if e.to_string().contains("storage limit") {
+                                    if util::is_upload_too_large(&e) {
+                                        upload_too_large(config)
+                                    } else if e.to_string().contains("storage limit") {
                                         (
                                             Status::PayloadTooLarge,
                                             format!(
@@ -1234,11 +1759,12 @@ async fn invoke_impl(
                     // no limit on storage, just use the data as is
                     futures::io::copy(open_data, &mut stage)
                         .await
-                        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+                        .map_err(|e| staging_error(e, config))?;
                 };
 
                 let mut inputs = HashMap::new();
-                inputs.insert((space.clone(), path.clone()), (headers.0, stage));
+                let metadata = stored_metadata(headers.0, &config.storage.metadata_headers);
+                inputs.insert((space.clone(), path.clone()), (metadata, stage));
                 Ok(inputs)
             }
                 (DataIn::One(d), [_, ..], true) => build_batch_kv_inputs(
@@ -1267,6 +1793,7 @@ async fn invoke_impl(
         let inputs = inputs_result?;
         let invocation_info = i.0 .0.clone();
         let invoke_start = Instant::now();
+        kv_options.deadline = deadline_after(config.timeouts.invoke_secs);
         let invoke_result = tinycloud
             .invoke_with_options::<BlockStage>(i.0, inputs, kv_options)
             .await;
@@ -1276,7 +1803,12 @@ async fn invoke_impl(
             invoke_start.elapsed(),
         );
         let res = match invoke_result {
+            Ok((_, outcomes)) if dry_run => Ok(outcomes
+                .into_iter()
+                .next()
+                .map_or(DataOut::None, |outcome| DataOut::One(InvOut(outcome)))),
             Ok((tx_result, mut outcomes)) => {
+                observe_space_sizes(tinycloud, &invocation_info).await;
                 emit_kv_hook_events(hook_runtime, tinycloud, &invocation_info, &tx_result).await;
                 if let Some(written_paths) = batch_written_paths {
                     if outcomes.len() != written_paths.len()
@@ -1294,34 +1826,27 @@ async fn invoke_impl(
                         ))))
                     }
                 } else {
-                    Ok(match (outcomes.pop(), outcomes.pop(), outcomes.drain(..)) {
-                        (None, None, _) => DataOut::None,
-                        (Some(o), None, _) => DataOut::One(InvOut(o)),
-                        (Some(o), Some(next), rest) => {
-                            let mut v = vec![InvOut(o), InvOut(next)];
-                            v.extend(rest.map(InvOut));
-                            DataOut::Many(v)
+                    Ok(match outcomes.len() {
+                        0 => DataOut::None,
+                        1 => DataOut::One(InvOut(outcomes.remove(0))),
+                        _ => {
+                            let mut read_keys = kv_read_keys(&invocation_info).into_iter();
+                            DataOut::Many(
+                                outcomes
+                                    .into_iter()
+                                    .map(|outcome| {
+                                        let key = matches!(outcome, InvocationOutcome::KvRead(_))
+                                            .then(|| read_keys.next())
+                                            .flatten();
+                                        (key, InvOut(outcome))
+                                    })
+                                    .collect(),
+                            )
                         }
-                        _ => unreachable!(),
                     })
                 }
             }
-            Err(e) => Err((
-                match &e {
-                    TxStoreError::Tx(TxError::SpaceNotFound) => Status::NotFound,
-                    TxStoreError::KvPreconditionFailed => Status::PreconditionFailed,
-                    TxStoreError::KvSerializationConflict => Status::ServiceUnavailable,
-                    TxStoreError::KvResponseTooLarge { .. } => Status::PayloadTooLarge,
-                    TxStoreError::Tx(TxError::InvalidInvocation(
-                        invocation_model::InvocationError::MissingKvWrite(_),
-                    )) => Status::NotFound,
-                    TxStoreError::Tx(TxError::Db(error) | TxError::EpochInsert(error)) => {
-                        database_error_status(error)
-                    }
-                    _ => Status::Unauthorized,
-                },
-                e.to_string(),
-            )),
+            Err(e) => Err((kv_invoke_status(&e), e.to_string())),
         };
 
         if let Some(timer) = timer {
@@ -1333,6 +1858,74 @@ async fn invoke_impl(
     .await
 }
 
+/// The status a delegation or invocation the node refused is answered with,
+/// on every route: `401` unless it was authenticated and then refused.
+fn tx_error_status(error: &TxError<BlockStores, StaticSecret>) -> Status {
+    match error {
+        TxError::SpaceNotFound => Status::NotFound,
+        TxError::SpaceNotAllowed(_) | TxError::SpaceFrozen(_) => Status::Forbidden,
+        TxError::AllowList(_) => Status::ServiceUnavailable,
+        TxError::Policy(PolicyError::Denied(_)) => Status::Forbidden,
+        TxError::Policy(PolicyError::Failed(_)) => Status::ServiceUnavailable,
+        TxError::TooManyCapabilities { .. } => Status::BadRequest,
+        TxError::TimedOut => Status::GatewayTimeout,
+        TxError::Db(error) | TxError::EpochInsert(error) => database_error_status(error),
+        _ => Status::Unauthorized,
+    }
+}
+
+/// The status a failed kv or capability invocation is answered with.
+fn kv_invoke_status(error: &TxStoreError<BlockStores, BlockStage, StaticSecret>) -> Status {
+    match error {
+        TxStoreError::KvPreconditionFailed => Status::PreconditionFailed,
+        TxStoreError::KvSerializationConflict => Status::ServiceUnavailable,
+        TxStoreError::KvResponseTooLarge { .. } => Status::PayloadTooLarge,
+        TxStoreError::KvSourceNotFound(_) | TxStoreError::KvKeyNotFound(_) => Status::NotFound,
+        TxStoreError::KvContentHashMismatch(_)
+        | TxStoreError::KvInvalidVersions(_)
+        | TxStoreError::KvCopyDeletesSource(_) => Status::BadRequest,
+        TxStoreError::Tx(TxError::InvalidInvocation(
+            invocation_model::InvocationError::MissingKvWrite(_),
+        )) => Status::NotFound,
+        TxStoreError::Tx(TxError::InvalidInvocation(
+            invocation_model::InvocationError::KvCaveatViolated(_),
+        )) => Status::Forbidden,
+        TxStoreError::Tx(error) => tx_error_status(error),
+        _ => Status::Unauthorized,
+    }
+}
+
+/// The status an invocation which failed to verify is answered with, for
+/// routes which apply it before doing their own work.
+fn verify_error_status(error: &TxStoreError<BlockStores, BlockStage, StaticSecret>) -> Status {
+    match error {
+        TxStoreError::Tx(error) => tx_error_status(error),
+        _ => Status::Unauthorized,
+    }
+}
+
+/// Refresh the size gauge of every space a committed kv invocation mutated.
+/// Block storage operations themselves are recorded by the store.
+async fn observe_space_sizes(tinycloud: &TinyCloud, invocation: &InvocationInfo) {
+    if !crate::prometheus::enabled() {
+        return;
+    }
+    let mutated = invocation
+        .capabilities
+        .iter()
+        .filter_map(|c| {
+            let resource = c.resource.tinycloud_resource()?;
+            (resource.service().as_str() == "kv" && c.ability.tinycloud()?.kv()?.is_mutation())
+                .then(|| resource.space().clone())
+        })
+        .collect::<HashSet<_>>();
+    for space in mutated {
+        if let Ok(Some(size)) = tinycloud.store_size(&space).await {
+            crate::prometheus::set_space_size(&space.to_string(), size);
+        }
+    }
+}
+
 async fn emit_kv_hook_events(
     hook_runtime: &HookRuntime,
     tinycloud: &State<TinyCloud>,
@@ -1408,12 +2001,10 @@ async fn emit_kv_hook_events(
                 Some((
                     resource.space(),
                     resource.service().as_str(),
-                    // Resolve deprecated aliases (e.g. kv/delete -> kv/del) so
-                    // alias-invoked operations emit the same live-bus events as
-                    // canonical ones — mirrors the db.rs dispatch resolution.
-                    tinycloud_core::policy_capability::resolve_alias(
-                        capability.ability.as_ref().as_ref(),
-                    ),
+                    // `kv()` resolves deprecated aliases (e.g. kv/delete ->
+                    // kv/del) so alias-invoked operations emit the same
+                    // live-bus events as canonical ones, as db.rs dispatches.
+                    capability.ability.tinycloud()?.kv()?,
                     resource.path()?,
                 ))
             })
@@ -1421,7 +2012,7 @@ async fn emit_kv_hook_events(
             continue;
         };
 
-        if service != "kv" || !matches!(ability, "tinycloud.kv/put" | "tinycloud.kv/del") {
+        if service != "kv" || !ability.is_mutation() {
             continue;
         }
 
@@ -1435,27 +2026,31 @@ async fn emit_kv_hook_events(
 
         let key = (space_id.clone(), path.to_string());
         let event = match ability {
-            "tinycloud.kv/put" => writes.get(&key).map(|row| WriteEvent {
-                event_type: "write".to_string(),
-                id: format!("{}:{current_index}", commit.rev.to_cid(0x55)),
-                space: space_id.clone(),
-                service: "kv".to_string(),
-                ability: "tinycloud.kv/put".to_string(),
-                path: Some(row.key.to_string()),
-                actor: invocation.invoker.clone(),
-                epoch: commit.rev.to_cid(0x55).to_string(),
-                event_index: current_index,
-                timestamp: timestamp.clone(),
-            }),
-            "tinycloud.kv/del" => deletes.get(&key).map(|row| WriteEvent {
+            // copies, moves and metadata updates land as ordinary writes on
+            // their destination
+            KvAbility::Put | KvAbility::SetMetadata | KvAbility::Copy | KvAbility::Move => {
+                writes.get(&key).map(|row| WriteEvent {
+                    event_type: "write".to_string(),
+                    id: format!("{}:{current_index}", commit.rev.to_block_cid()),
+                    space: space_id.clone(),
+                    service: "kv".to_string(),
+                    ability: KvAbility::Put.to_string(),
+                    path: Some(row.key.to_string()),
+                    actor: invocation.invoker.clone(),
+                    epoch: commit.rev.to_block_cid().to_string(),
+                    event_index: current_index,
+                    timestamp: timestamp.clone(),
+                })
+            }
+            KvAbility::Del => deletes.get(&key).map(|row| WriteEvent {
                 event_type: "write".to_string(),
-                id: format!("{}:{current_index}", commit.rev.to_cid(0x55)),
+                id: format!("{}:{current_index}", commit.rev.to_block_cid()),
                 space: space_id.clone(),
                 service: "kv".to_string(),
-                ability: "tinycloud.kv/del".to_string(),
+                ability: KvAbility::Del.to_string(),
                 path: Some(row.key.to_string()),
                 actor: invocation.invoker.clone(),
-                epoch: commit.rev.to_cid(0x55).to_string(),
+                epoch: commit.rev.to_block_cid().to_string(),
                 event_index: current_index,
                 timestamp: timestamp.clone(),
             }),
@@ -1574,14 +2169,31 @@ async fn handle_sql_invoke(
     );
     let body_str = body_result?;
 
-    let (space, path, ability) = select_database_scope(sql_caps, "sql")?;
-    let db_name = SqlService::db_name_from_path(path);
-    let space_id = space.to_string();
-
     let sql_request: SqlRequest =
         serde_json::from_str(&body_str).map_err(|e| (Status::BadRequest, e.to_string()))?;
 
-    require_sql_admin_for_request(&sql_request, space, path, &db_name, sql_caps)?;
+    // an ATTACH cites a read capability on the sibling it names, which is
+    // not a database the statement runs on
+    let (sibling_caps, sql_caps) =
+        split_attached_capabilities(&sql_request, sql_caps, |space, db| {
+            sql_service.attached_databases(space, db)
+        });
+    let (space, path, ability) = select_database_scope(&sql_caps, "sql")?;
+    let db_name = SqlService::db_name_from_path(path);
+    let space_id = space.to_string();
+    let readable_siblings = sibling_caps
+        .iter()
+        .filter(|(sibling_space, _, sibling_ability)| {
+            sibling_space == space
+                && tinycloud_core::policy_capability::ability_matches(
+                    sibling_ability,
+                    "tinycloud.sql/read",
+                )
+        })
+        .map(|(_, sibling_path, _)| SqlService::db_name_from_path(sibling_path.as_deref()))
+        .collect::<Vec<_>>();
+
+    require_sql_admin_for_request(&sql_request, space, path, &db_name, &sql_caps)?;
 
     // W1 (D): under a constrained-statements profile (carried by the
     // validated transitive delegation chain), raw paths are blocked
@@ -1630,12 +2242,13 @@ async fn handle_sql_invoke(
 
     let execute_start = Instant::now();
     let execute_result = sql_service
-        .execute(
+        .execute_with_siblings(
             space,
             &db_name,
             sql_request,
             exec_caveats,
             ability.to_string(),
+            &readable_siblings,
         )
         .await;
     crate::prometheus::observe_span(
@@ -1652,7 +2265,7 @@ async fn handle_sql_invoke(
     if let Some(epoch) = auth_result
         .commits
         .get(space)
-        .map(|commit| commit.rev.to_cid(0x55).to_string())
+        .map(|commit| commit.rev.to_block_cid().to_string())
     {
         if let Ok(timestamp) = OffsetDateTime::now_utc().format(&Rfc3339) {
             let events = database_write_events(
@@ -1680,6 +2293,51 @@ async fn handle_sql_invoke(
     Ok(DataOut::One(InvOut(InvocationOutcome::SqlResult(json))))
 }
 
+/// Split the capabilities on sibling databases from those on the database
+/// the request runs on: the sibling named by an `ATTACH`, or, for any other
+/// request, the reads of siblings already attached to a database it names,
+/// as `attached` lists them. Every capability of the invocation, including
+/// those on siblings, has been verified against its delegations by then.
+#[allow(clippy::type_complexity)]
+fn split_attached_capabilities(
+    request: &SqlRequest,
+    caps: &[(SpaceId, Option<String>, String)],
+    attached: impl Fn(&SpaceId, &str) -> Vec<String>,
+) -> (
+    Vec<(SpaceId, Option<String>, String)>,
+    Vec<(SpaceId, Option<String>, String)>,
+) {
+    let db_name = |path: &Option<String>| SqlService::db_name_from_path(path.as_deref());
+    let attaching = match request {
+        SqlRequest::Execute {
+            sql, schema: None, ..
+        } => tinycloud_core::sql::parser::attached_database(sql),
+        _ => None,
+    };
+    if let Some(attaching) = attaching {
+        return caps
+            .iter()
+            .cloned()
+            .partition(|(_, path, _)| path.is_some() && db_name(path) == attaching);
+    }
+    let named: HashSet<(&SpaceId, String)> = caps
+        .iter()
+        .filter(|(_, path, _)| path.is_some())
+        .map(|(space, path, _)| (space, db_name(path)))
+        .collect();
+    let siblings: HashSet<(&SpaceId, String)> = named
+        .iter()
+        .flat_map(|(space, db)| attached(space, db).into_iter().map(move |s| (*space, s)))
+        .filter(|sibling| named.contains(sibling))
+        .collect();
+    caps.iter().cloned().partition(|(space, path, ability)| {
+        path.is_some()
+            && siblings.contains(&(space, db_name(path)))
+            && tinycloud_core::policy_capability::ability_matches(ability, "tinycloud.sql/read")
+            && !tinycloud_core::policy_capability::ability_matches(ability, "tinycloud.sql/write")
+    })
+}
+
 fn require_sql_admin_for_request(
     request: &SqlRequest,
     space: &SpaceId,
@@ -1697,19 +2355,19 @@ fn require_sql_admin_for_request(
 }
 
 fn sql_request_requires_admin(request: &SqlRequest) -> bool {
+    let requires_admin = |sql: &str| {
+        tinycloud_core::sql::parser::is_pragma_sql(sql)
+            || tinycloud_core::sql::parser::is_attach_sql(sql)
+    };
     match request {
-        SqlRequest::Query { sql, .. } => tinycloud_core::sql::parser::is_pragma_sql(sql),
+        SqlRequest::Query { sql, .. } => requires_admin(sql),
         SqlRequest::Execute { sql, schema, .. } => {
-            tinycloud_core::sql::parser::is_pragma_sql(sql)
-                || schema.as_ref().is_some_and(|statements| {
-                    statements
-                        .iter()
-                        .any(|statement| tinycloud_core::sql::parser::is_pragma_sql(statement))
-                })
+            requires_admin(sql)
+                || schema
+                    .as_ref()
+                    .is_some_and(|statements| statements.iter().any(|s| requires_admin(s)))
         }
-        SqlRequest::Batch { statements } => statements
-            .iter()
-            .any(|statement| tinycloud_core::sql::parser::is_pragma_sql(&statement.sql)),
+        SqlRequest::Batch { statements } => statements.iter().any(|s| requires_admin(&s.sql)),
         SqlRequest::ExecuteStatement { .. } | SqlRequest::Export => false,
     }
 }
@@ -1750,7 +2408,6 @@ async fn derive_chain_constrained_caveat_with_conn<C: tinycloud_core::sea_orm::C
     (Status, String),
 > {
     use std::collections::HashSet;
-    use tinycloud_core::hash::Hash;
     use tinycloud_core::models::abilities;
     use tinycloud_core::policy_capability::sql_caveat;
     use tinycloud_core::relationships::parent_delegations;
@@ -2195,7 +2852,7 @@ async fn handle_duckdb_invoke(
     if let Some(epoch) = auth_result
         .commits
         .get(space)
-        .map(|commit| commit.rev.to_cid(0x55).to_string())
+        .map(|commit| commit.rev.to_block_cid().to_string())
     {
         if let Ok(timestamp) = OffsetDateTime::now_utc().format(&Rfc3339) {
             let events = database_write_events(
@@ -2556,18 +3213,7 @@ async fn verify_auth(
     let result = tinycloud
         .invoke::<BlockStage>(invocation, HashMap::new())
         .await
-        .map_err(|e| {
-            (
-                match &e {
-                    TxStoreError::Tx(TxError::SpaceNotFound) => Status::NotFound,
-                    TxStoreError::Tx(TxError::Db(error) | TxError::EpochInsert(error)) => {
-                        database_error_status(error)
-                    }
-                    _ => Status::Unauthorized,
-                },
-                e.to_string(),
-            )
-        })
+        .map_err(|e| (verify_error_status(&e), e.to_string()))
         .map(|(tx_result, _)| tx_result);
     crate::prometheus::observe_span(
         span,
@@ -2648,6 +3294,617 @@ mod tests {
         ))))
     }
 
+    #[test]
+    fn requests_time_out_unless_waiting_indefinitely() {
+        assert!(deadline_after(0).is_none());
+        assert_eq!(tx_error_status(&TxError::TimedOut), Status::GatewayTimeout);
+    }
+
+    #[tokio::test]
+    async fn delegations_stalled_on_did_resolution_fail_with_504() -> Result<()> {
+        use crate::config::{RequestTimeouts, SpaceRateLimitConfig};
+        use rocket::http::{Header, Status};
+        use rocket::local::asynchronous::Client;
+        use tinycloud_auth::ssi::{
+            claims::jwt::NumericDate, dids::DIDURLBuf, jwk::Algorithm, ucan::Payload,
+        };
+        use tinycloud_auth::ucan_capabilities_object::Capabilities;
+        use tinycloud_core::resolver::{DidResolver, ResolverOptions, WebResolverOptions};
+
+        // the issuer's did:web document is fetched through a proxy which
+        // accepts every connection and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let proxy = format!("http://{}", listener.local_addr()?);
+        let stalled = tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let mut jwk = JWK::generate_ed25519()?;
+        jwk.algorithm = Some(Algorithm::EdDSA);
+        let issuer: DIDBuf = "did:web:example.invalid".parse()?;
+        let space = SpaceId::new(issuer.clone(), "default".parse()?);
+        let mut capabilities = Capabilities::new();
+        capabilities.with_action(
+            space
+                .to_resource("kv".parse()?, Some("notes/".parse()?), None, None)
+                .to_string()
+                .parse()?,
+            "tinycloud.kv/get".parse::<UcanAbility>()?,
+            [std::collections::BTreeMap::<String, serde_json::Value>::new()],
+        );
+        let header = Payload {
+            issuer: format!("{issuer}#key-1").parse::<DIDURLBuf>()?,
+            audience: test_space_id("stalled")
+                .did()
+                .to_string()
+                .parse::<DIDBuf>()?,
+            not_before: None,
+            expiration: NumericDate::try_from_seconds(4_102_444_800.0)?,
+            nonce: Some("stalled-resolution".to_string()),
+            facts: Some(Vec::<serde_json::Value>::new()),
+            proof: vec![],
+            attenuation: capabilities,
+        }
+        .sign(Algorithm::EdDSA, &jwk)?
+        .encode()?;
+
+        // stalled in storing the delegation, then, with rate limiting, in
+        // verifying it for the rate limit before that
+        for rate_limited in [false, true] {
+            let tinycloud =
+                test_tinycloud()
+                    .await?
+                    .with_resolver(DidResolver::new(ResolverOptions {
+                        web: Some(WebResolverOptions {
+                            timeout: Duration::from_secs(60),
+                            user_agent: None,
+                            proxy: Some(proxy.clone()),
+                        }),
+                        timeout: Some(Duration::from_secs(60)),
+                        ..Default::default()
+                    })?);
+            let config = Config {
+                timeouts: RequestTimeouts {
+                    delegate_secs: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let client = Client::tracked(
+                rocket::build()
+                    .mount("/", rocket::routes![delegate])
+                    .attach(crate::tracing::TracingFairing {
+                        header_name: Config::default().log.tracing.traceheader,
+                    })
+                    .manage(tinycloud)
+                    .manage(config)
+                    .manage(SpaceRateLimiter::new(&SpaceRateLimitConfig {
+                        enabled: rate_limited,
+                        ..Default::default()
+                    })),
+            )
+            .await?;
+
+            let started = std::time::Instant::now();
+            let response = client
+                .post("/delegate")
+                .header(Header::new("Authorization", header.clone()))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::GatewayTimeout);
+            assert!(started.elapsed() < Duration::from_secs(10));
+        }
+        stalled.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn well_known_document_lists_limits_and_abilities() {
+        use rocket::data::ByteUnit;
+
+        let mut config = Config::default();
+        config.storage.max_upload = ByteUnit::Mebibyte(8);
+        config.storage.limit = Some(ByteUnit::Gibibyte(1));
+        config.spaces.max_delegation_depth = Some(4);
+        config.spaces.max_delegation_ttl_secs = Some(86_400);
+        config.spaces.delegation_clock_skew_secs = Some(30);
+
+        let node_did = StaticSecret::new(vec![7u8; 32]).unwrap().node_did();
+        let document = serde_json::to_value(well_known_document(&config, &node_did)).unwrap();
+
+        assert_eq!(document["hostKeys"], serde_json::json!([node_did]));
+        assert_eq!(
+            document["protocol"],
+            tinycloud_auth::protocol::PROTOCOL_VERSION
+        );
+        assert_eq!(
+            document["limits"],
+            serde_json::json!({
+                "maxUploadBytes": 8 * 1024 * 1024,
+                "defaultStorageBytes": 1024 * 1024 * 1024,
+                "maxDelegationDepth": 4,
+                "maxDelegationTtlSecs": 86_400,
+                "delegationClockSkewSecs": 30,
+            })
+        );
+        let kv = document["abilities"]["tinycloud.kv"].as_array().unwrap();
+        for ability in KvAbility::ALL {
+            assert!(kv.contains(&ability.as_str().into()), "{ability} missing");
+        }
+        let capabilities = document["abilities"]["tinycloud.capabilities"]
+            .as_array()
+            .unwrap();
+        for ability in CapabilitiesAbility::ALL {
+            assert!(capabilities.contains(&ability.as_str().into()));
+        }
+        assert!(document["abilities"]["tinycloud.sql"].is_array());
+    }
+
+    /// Sign a SIWE session for the EIP-155 account of `seed`, returning the
+    /// account address, the session and its encoded delegation header.
+    fn signed_session(
+        seed: u8,
+        abilities: serde_json::Value,
+    ) -> Result<(String, tinycloud_sdk_wasm::session::Session, String)> {
+        use k256::ecdsa::SigningKey;
+        use sha3::{Digest, Keccak256};
+        use tinycloud_auth::cacaos::siwe::encode_eip55;
+        use tinycloud_sdk_wasm::session::{complete_session_setup, prepare_session};
+
+        let signing_key = SigningKey::from_bytes(&[seed; 32].into())?;
+        let public_key = signing_key.verifying_key().to_encoded_point(false);
+        let address: [u8; 20] = Keccak256::digest(&public_key.as_bytes()[1..])[12..].try_into()?;
+        let address = format!("0x{}", encode_eip55(&address));
+        let prepared = prepare_session(serde_json::from_value(serde_json::json!({
+            "abilities": abilities,
+            "address": address,
+            "chainId": 1,
+            "domain": "example.com",
+            "issuedAt": "2024-01-01T00:00:00Z",
+            "expirationTime": "2100-01-01T00:00:00Z",
+            "spaceId": format!("tinycloud:pkh:eip155:1:{address}:default"),
+        }))?)?;
+        let (signature, recovery_id) =
+            signing_key.sign_prehash_recoverable(&prepared.siwe.eip191_hash()?)?;
+        let mut signature_bytes = [0u8; 65];
+        signature_bytes[..64].copy_from_slice(signature.to_bytes().as_ref());
+        signature_bytes[64] = u8::from(recovery_id) + 27;
+        let mut signed = serde_json::to_value(prepared)?;
+        signed.as_object_mut().unwrap().insert(
+            "signature".into(),
+            format!("0x{}", hex::encode(signature_bytes)).into(),
+        );
+        let session = complete_session_setup(serde_json::from_value(signed)?)?;
+        let header = serde_json::to_value(&session.delegation_header)?["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        Ok((address, session, header))
+    }
+
+    #[tokio::test]
+    async fn delegation_preview_expands_siwe_recap_capabilities() -> Result<()> {
+        use rocket::http::{Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let (address, session, header) = signed_session(
+            0x44,
+            serde_json::json!({
+                "kv": { "notes/": ["tinycloud.kv/get", "tinycloud.kv/put"] },
+                "sql": { "main.db": ["tinycloud.sql/read"] },
+            }),
+        )?;
+
+        let client =
+            Client::tracked(rocket::build().mount("/", rocket::routes![delegate_preview])).await?;
+        let response = client
+            .post("/delegate/preview")
+            .header(Header::new("Authorization", header))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let preview: serde_json::Value = response.into_json().await.unwrap();
+
+        assert_eq!(preview["cid"], session.delegation_cid.to_string());
+        assert!(preview["delegator"]
+            .as_str()
+            .unwrap()
+            .eq_ignore_ascii_case(&format!("did:pkh:eip155:1:{address}")));
+        assert!(session
+            .verification_method
+            .starts_with(preview["delegate"].as_str().unwrap()));
+        assert_eq!(preview["expiry"], "2100-01-01T00:00:00Z");
+        let mut granted: Vec<(String, String)> = preview["capabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|c| c["resource"].as_str().unwrap().contains(":default/"))
+            .map(|c| {
+                let resource = c["resource"].as_str().unwrap();
+                (
+                    resource[resource.find(":default/").unwrap()..].to_string(),
+                    c["ability"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        granted.sort();
+        assert_eq!(
+            granted,
+            [
+                (":default/kv/notes/", "tinycloud.kv/get"),
+                (":default/kv/notes/", "tinycloud.kv/put"),
+                (":default/sql/main.db", "tinycloud.sql/read"),
+            ]
+            .map(|(r, a)| (r.to_string(), a.to_string()))
+        );
+
+        let unsigned = client
+            .post("/delegate/preview")
+            .header(Header::new("Authorization", "not a delegation"))
+            .dispatch()
+            .await;
+        assert_eq!(unsigned.status(), Status::Unauthorized);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delegations_in_the_body_match_delegations_in_the_header() -> Result<()> {
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let (_, session, header) = signed_session(
+            0x45,
+            serde_json::json!({ "kv": { "notes/": ["tinycloud.kv/get"] } }),
+        )?;
+        let space: SpaceId = session.space_id.to_string().parse()?;
+        let space = &space;
+        let node = move || async move {
+            let tinycloud = test_tinycloud().await?;
+            tinycloud.create_space(space).await?;
+            Client::tracked(
+                rocket::build()
+                    .mount("/", rocket::routes![delegate])
+                    .attach(crate::tracing::TracingFairing {
+                        header_name: Config::default().log.tracing.traceheader,
+                    })
+                    .manage(tinycloud)
+                    .manage(Config::default())
+                    .manage(SpaceRateLimiter::new(&Default::default())),
+            )
+            .await
+            .map_err(anyhow::Error::from)
+        };
+
+        let via_header = node()
+            .await?
+            .post("/delegate")
+            .header(Header::new("Authorization", header.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(via_header.status(), Status::Ok);
+        let via_header: serde_json::Value = via_header.into_json().await.unwrap();
+
+        let body_node = node().await?;
+        let via_body = body_node
+            .post("/delegate")
+            .header(ContentType::JSON)
+            .body(serde_json::json!({ "authorization": header }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(via_body.status(), Status::Ok);
+        let via_body: serde_json::Value = via_body.into_json().await.unwrap();
+
+        assert_eq!(via_body, via_header);
+        assert_eq!(via_body["cid"], session.delegation_cid.to_string());
+
+        let garbled = body_node
+            .post("/delegate")
+            .header(ContentType::JSON)
+            .body(r#"{"authorization": "not a delegation"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(garbled.status(), Status::Unauthorized);
+        let missing = body_node.post("/delegate").dispatch().await;
+        assert_eq!(missing.status(), Status::Unauthorized);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn manifests_resolve_pkh_spaces() -> Result<()> {
+        use rocket::http::Status;
+        use rocket::local::asynchronous::Client;
+
+        let jwk = JWK::generate_secp256k1();
+        let did: DIDBuf = DID_METHODS.generate(&jwk, "pkh:eth")?;
+        let space = SpaceId::new(did.clone(), "default".parse()?);
+        let client = Client::tracked(
+            rocket::build()
+                .mount("/", rocket::routes![manifest])
+                .manage(test_tinycloud().await?),
+        )
+        .await?;
+
+        let response = client.get(format!("/manifest/{space}")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let manifest: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(manifest["id"], space.to_string());
+        let delegators = manifest["delegators"].as_array().unwrap();
+        assert!(delegators
+            .iter()
+            .any(|d| d == &format!("{did}#blockchainAccountId")));
+        assert!(delegators
+            .iter()
+            .all(|d| d.as_str().unwrap().starts_with(did.as_str())));
+        assert_eq!(manifest["invokers"], manifest["delegators"]);
+        assert_eq!(manifest["bootstrapPeers"], serde_json::json!([]));
+
+        let invalid = client.get("/manifest/not-a-space").dispatch().await;
+        assert_eq!(invalid.status(), Status::BadRequest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn root_self_delegations_authorize_sessions() -> Result<()> {
+        use tinycloud_auth::authorization::{
+            HeaderEncode, TinyCloudDelegation, TinyCloudInvocation,
+        };
+        use tinycloud_core::events::Delegation;
+        use tinycloud_sdk_wasm::session::root_session;
+
+        let mut root = JWK::generate_ed25519()?;
+        root.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let space = SpaceId::new(DID_METHODS.generate(&root, "key")?, "default".parse()?);
+        let tinycloud = test_tinycloud().await?;
+        tinycloud.create_space(&space).await?;
+
+        let session = root_session(serde_json::from_value(serde_json::json!({
+            "abilities": { "kv": { "notes/": ["tinycloud.kv/get"] } },
+            "spaceId": space.to_string(),
+            "rootJwk": root,
+            "expirationSecs": 4_102_444_800.0,
+        }))?)?;
+        let header = serde_json::to_value(&session.delegation_header)?["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        tinycloud
+            .delegate(Delegation::from_header_ser::<TinyCloudDelegation>(&header)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("root delegation rejected: {e}"))?;
+
+        let invoke = |path: &str| -> Result<Invocation> {
+            let invocation = session.invoke(
+                [(
+                    "kv".parse()?,
+                    path.parse()?,
+                    None,
+                    None,
+                    ["tinycloud.kv/get".parse::<UcanAbility>()?],
+                )],
+                None,
+            )?;
+            Ok(Invocation::from_header_ser::<TinyCloudInvocation>(
+                &invocation.encode()?,
+            )?)
+        };
+        tinycloud
+            .invoke::<BlockStage>(invoke("notes/today")?, HashMap::new())
+            .await
+            .map_err(|e| anyhow::anyhow!("root session invocation rejected: {e}"))?;
+        assert!(tinycloud
+            .invoke::<BlockStage>(invoke("secrets/today")?, HashMap::new())
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn space_logs_page_to_the_end() -> Result<()> {
+        use rocket::http::{Header, Status};
+        use rocket::local::asynchronous::Client;
+        use tinycloud_auth::authorization::{HeaderEncode, TinyCloudDelegation};
+        use tinycloud_core::{event_log::LogEntry, events::Delegation};
+        use tinycloud_sdk_wasm::session::root_session;
+
+        let mut root = JWK::generate_ed25519()?;
+        root.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let space = SpaceId::new(DID_METHODS.generate(&root, "key")?, "default".parse()?);
+        let tinycloud = test_tinycloud().await?;
+        tinycloud.create_space(&space).await?;
+
+        let session = root_session(serde_json::from_value(serde_json::json!({
+            "abilities": { "capabilities": { "all": ["tinycloud.capabilities/read"] } },
+            "spaceId": space.to_string(),
+            "rootJwk": root,
+            "expirationSecs": 4_102_444_800.0,
+        }))?)?;
+        let header = serde_json::to_value(&session.delegation_header)?["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        tinycloud
+            .delegate(Delegation::from_header_ser::<TinyCloudDelegation>(&header)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("root delegation rejected: {e}"))?;
+        let invocation = session
+            .invoke(
+                [(
+                    "capabilities".parse()?,
+                    "all".parse()?,
+                    None,
+                    None,
+                    ["tinycloud.capabilities/read".parse::<UcanAbility>()?],
+                )],
+                None,
+            )?
+            .encode()?;
+
+        let client = Client::tracked(
+            rocket::build()
+                .mount("/", rocket::routes![log::space_log])
+                .manage(tinycloud),
+        )
+        .await?;
+        let mut since = None;
+        let mut pulled = Vec::new();
+        for _ in 0..10 {
+            let uri = match since {
+                Some(seq) => format!("/log/{space}?since={seq}"),
+                None => format!("/log/{space}"),
+            };
+            let response = client
+                .get(uri)
+                .header(Header::new("Authorization", invocation.clone()))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            let page: Vec<LogEntry> = response.into_json().await.unwrap();
+            let Some(last) = page.last() else {
+                // only the delegation was ever committed
+                assert_eq!(pulled.len(), 1);
+                return Ok(());
+            };
+            since = Some(last.seq);
+            pulled.extend(page);
+        }
+        panic!("the log never came back empty: {} entries", pulled.len());
+    }
+
+    #[tokio::test]
+    async fn puts_without_a_content_type_are_sniffed() -> Result<()> {
+        use rocket::http::{Header, Status};
+        use rocket::local::asynchronous::Client;
+        use tinycloud_auth::authorization::{HeaderEncode, TinyCloudDelegation};
+        use tinycloud_core::events::Delegation;
+        use tinycloud_sdk_wasm::session::root_session;
+
+        let mut root = JWK::generate_ed25519()?;
+        root.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let space = SpaceId::new(DID_METHODS.generate(&root, "key")?, "default".parse()?);
+        let tinycloud = test_tinycloud().await?;
+        tinycloud.create_space(&space).await?;
+        let session = root_session(serde_json::from_value(serde_json::json!({
+            "abilities": { "kv": { "images/": ["tinycloud.kv/put"] } },
+            "spaceId": space.to_string(),
+            "rootJwk": root,
+            "expirationSecs": 4_102_444_800.0,
+        }))?)?;
+        let header = serde_json::to_value(&session.delegation_header)?["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        tinycloud
+            .delegate(Delegation::from_header_ser::<TinyCloudDelegation>(&header)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("root delegation rejected: {e}"))?;
+
+        let mut config = Config::default();
+        config.storage.sniff_content_type = true;
+        let client = Client::tracked(
+            rocket::build()
+                .mount("/", rocket::routes![invoke])
+                .attach(crate::tracing::TracingFairing {
+                    header_name: Config::default().log.tracing.traceheader,
+                })
+                .attach(crate::idempotency::IdempotencyFairing)
+                .manage(tinycloud)
+                .manage(fresh_sql_service().await)
+                .manage(QuotaCache::new(config.storage.limit, None))
+                .manage(InvocationReplayCache::new())
+                .manage(crate::idempotency::IdempotencyCache::new(
+                    &Default::default(),
+                ))
+                .manage(SpaceRateLimiter::new(&Default::default()))
+                .manage(HookRuntime::new(HooksConfig::default(), [9u8; 32]))
+                .manage(BlockStage::from(crate::config::StagingStorage::Memory))
+                .manage(config),
+        )
+        .await?;
+
+        let png = [
+            0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 0x0d, b'I', b'H', b'D', b'R',
+        ];
+        for (path, content_type, stored) in [
+            ("images/pixel", None, "image/png"),
+            (
+                "images/labelled",
+                Some("application/octet-stream"),
+                "application/octet-stream",
+            ),
+        ] {
+            let invocation = session
+                .invoke(
+                    [(
+                        "kv".parse()?,
+                        path.parse()?,
+                        None,
+                        None,
+                        ["tinycloud.kv/put".parse::<UcanAbility>()?],
+                    )],
+                    None,
+                )?
+                .encode()?;
+            let mut request = client
+                .post("/invoke")
+                .header(Header::new("Authorization", invocation))
+                .body(png);
+            if let Some(content_type) = content_type {
+                request = request.header(Header::new("Content-Type", content_type));
+            }
+            let response = request.dispatch().await;
+            assert_eq!(response.status(), Status::Ok, "{path}");
+
+            let (metadata, _, _) = client
+                .rocket()
+                .state::<TinyCloud>()
+                .unwrap()
+                .kv_get(&space, &path.parse()?)
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))?
+                .ok_or_else(|| anyhow::anyhow!("{path} was not written"))?;
+            // a content type the client sends is never overridden
+            assert_eq!(metadata_header(&metadata, "content-type"), Some(stored));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oversized_authorization_headers_get_a_clear_431() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let mut config = Config::default();
+        config.headers.max_authorization = ByteUnit::Kibibyte(1);
+        let client = Client::tracked(
+            rocket::build()
+                .mount("/", rocket::routes![delegate_preview])
+                .register("/", rocket::catchers![util_routes::header_too_large])
+                .manage(config),
+        )
+        .await?;
+
+        let response = client
+            .post("/delegate/preview")
+            .header(Header::new("Authorization", "a".repeat(1025)))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::RequestHeaderFieldsTooLarge);
+        let body = response.into_string().await.unwrap_or_default();
+        assert!(body.contains("limit of 1024 bytes"), "{body}");
+
+        // at the limit the header is parsed as usual
+        let response = client
+            .post("/delegate/preview")
+            .header(Header::new("Authorization", "a".repeat(1024)))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn conditional_kv_etags_are_strong_blake3_etags() {
         let digest = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
@@ -2655,6 +3912,11 @@ mod tests {
             parse_strong_blake3_etag(&format!("\"blake3-{digest}\"")).unwrap(),
             hex::decode(digest).unwrap().as_slice()
         );
+        // as served gzipped
+        assert_eq!(
+            parse_strong_blake3_etag(&format!("\"blake3-{digest}-gzip\"")).unwrap(),
+            hex::decode(digest).unwrap().as_slice()
+        );
 
         for invalid in [
             "*",
@@ -2669,6 +3931,45 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn content_hash_header_expects_the_put_value_hash() {
+        let space = test_space_id("content-hash");
+        let capability = kv_put_capability(&space, "files/report.txt");
+        let expected = tinycloud_core::hash::hash(b"report");
+        let digest = hex::encode(expected.as_ref());
+
+        for value in [digest.clone(), format!("\"blake3-{digest}\"")] {
+            let mut headers = ObjectHeaders(Metadata(BTreeMap::from([(
+                "X-Content-Hash".to_string(),
+                value,
+            )])));
+            let options = kv_invoke_options_for_capabilities(
+                std::slice::from_ref(&capability),
+                &mut headers,
+                false,
+            )
+            .unwrap();
+            assert_eq!(
+                options
+                    .content_hashes
+                    .get(&(space.clone(), "files/report.txt".parse().unwrap())),
+                Some(&expected)
+            );
+            assert!(metadata_header(&headers.0, "x-content-hash").is_none());
+        }
+
+        let mut headers = ObjectHeaders(Metadata(BTreeMap::from([(
+            "X-Content-Hash".to_string(),
+            "not-a-digest".to_string(),
+        )])));
+        assert_eq!(
+            kv_invoke_options_for_capabilities(&[capability], &mut headers, false)
+                .unwrap_err()
+                .0,
+            Status::BadRequest
+        );
+    }
+
     #[tokio::test]
     async fn bounded_kv_headers_are_positive_and_removed_from_object_metadata() {
         let mut metadata = Metadata(BTreeMap::from([
@@ -2702,6 +4003,47 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn kv_list_detailed_header_is_a_boolean_flag() {
+        let space = test_space_id("kv-list-detailed");
+        let capability = kv_put_capability(&space, "files/report.txt");
+
+        let mut headers = ObjectHeaders(Metadata(BTreeMap::from([(
+            "X-TinyCloud-List-Detailed".to_string(),
+            "true".to_string(),
+        )])));
+        let options = kv_invoke_options_for_capabilities(
+            std::slice::from_ref(&capability),
+            &mut headers,
+            false,
+        )
+        .unwrap();
+        assert!(options.list_detailed);
+        assert!(metadata_header(&headers.0, "x-tinycloud-list-detailed").is_none());
+
+        let mut headers = ObjectHeaders(Metadata(BTreeMap::new()));
+        assert!(
+            !kv_invoke_options_for_capabilities(
+                std::slice::from_ref(&capability),
+                &mut headers,
+                false
+            )
+            .unwrap()
+            .list_detailed
+        );
+
+        let mut headers = ObjectHeaders(Metadata(BTreeMap::from([(
+            "x-tinycloud-list-detailed".to_string(),
+            "yes".to_string(),
+        )])));
+        assert_eq!(
+            kv_invoke_options_for_capabilities(&[capability], &mut headers, false)
+                .unwrap_err()
+                .0,
+            Status::BadRequest
+        );
+    }
+
     #[tokio::test]
     async fn kv_create_and_replace_headers_build_exact_key_preconditions() {
         let space = test_space_id("conditional-kv-options");
@@ -2800,7 +4142,7 @@ mod tests {
         let _persisted = tempdir.keep();
         Ok(TinyCloud::new(
             db,
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?)
@@ -2890,6 +4232,39 @@ mod tests {
             .expect("admin PRAGMA should be accepted");
     }
 
+    #[tokio::test]
+    async fn uploads_past_max_upload_are_rejected_not_truncated() {
+        async fn read_body(body: &'static [u8], config: &Config) -> Result<(), (Status, String)> {
+            let max_upload = config.storage.max_upload.as_u64();
+            let mut multipart =
+                multer::Multipart::with_reader(UploadLimit::new(body, max_upload).compat(), "X");
+            while let Some(mut field) = multipart
+                .next_field()
+                .await
+                .map_err(|e| multipart_error(e, config))?
+            {
+                while field
+                    .chunk()
+                    .await
+                    .map_err(|e| multipart_error(e, config))?
+                    .is_some()
+                {}
+            }
+            Ok(())
+        }
+
+        let body: &'static [u8] =
+            b"--X\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhello\r\n--X--\r\n";
+        let mut config = Config::default();
+        config.storage.max_upload = rocket::data::ByteUnit::Byte(body.len() as u64);
+        read_body(body, &config).await.unwrap();
+
+        config.storage.max_upload = rocket::data::ByteUnit::Byte(body.len() as u64 - 1);
+        let (status, message) = read_body(body, &config).await.unwrap_err();
+        assert_eq!(status, Status::PayloadTooLarge);
+        assert!(message.contains("maximum upload size"), "{message}");
+    }
+
     #[tokio::test]
     async fn multipart_batch_path_names_are_percent_decoded() {
         assert_eq!(
@@ -3064,6 +4439,58 @@ mod tests {
         assert_eq!(ability, "tinycloud.sql/write");
     }
 
+    #[tokio::test]
+    async fn attach_reads_of_the_sibling_do_not_widen_the_database_scope() {
+        let space = test_space_id("alpha");
+        let caps = vec![
+            (
+                space.clone(),
+                Some("main".to_string()),
+                "tinycloud.sql/admin".to_string(),
+            ),
+            (
+                space.clone(),
+                Some("users".to_string()),
+                "tinycloud.sql/read".to_string(),
+            ),
+        ];
+        let attach = SqlRequest::Execute {
+            schema: None,
+            sql: "ATTACH DATABASE 'users' AS users".to_string(),
+            params: Vec::new(),
+        };
+
+        let nothing_attached = |_: &SpaceId, _: &str| Vec::new();
+        let (siblings, caps) = split_attached_capabilities(&attach, &caps, nothing_attached);
+        assert_eq!(siblings.len(), 1);
+        assert_eq!(siblings[0].1.as_deref(), Some("users"));
+        let (_, path, ability) = select_database_scope(&caps, "sql").unwrap();
+        assert_eq!(path, Some("main"));
+        assert_eq!(ability, "tinycloud.sql/admin");
+
+        // any other statement keeps both, which name two databases, unless
+        // the sibling is attached to the other
+        let query = SqlRequest::Execute {
+            schema: None,
+            sql: "SELECT 1".to_string(),
+            params: Vec::new(),
+        };
+        let caps = [caps, siblings].concat();
+        let (siblings, scoped) = split_attached_capabilities(&query, &caps, nothing_attached);
+        assert!(siblings.is_empty());
+        assert!(select_database_scope(&scoped, "sql").is_err());
+
+        let users_attached_to_main = |_: &SpaceId, db: &str| match db {
+            "main" => vec!["users".to_string()],
+            _ => Vec::new(),
+        };
+        let (siblings, scoped) = split_attached_capabilities(&query, &caps, users_attached_to_main);
+        assert_eq!(siblings.len(), 1);
+        assert_eq!(siblings[0].1.as_deref(), Some("users"));
+        let (_, path, _) = select_database_scope(&scoped, "sql").unwrap();
+        assert_eq!(path, Some("main"));
+    }
+
     #[tokio::test]
     async fn select_database_scope_accepts_sql_schema_ability() {
         let space = test_space_id("alpha");
@@ -3490,7 +4917,7 @@ mod tests {
         let _persisted = tempdir.keep();
         let tinycloud = TinyCloud::new(
             db.clone(),
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?;
@@ -3597,7 +5024,7 @@ mod tests {
         }
         .insert(&conn)
         .await?;
-        let parent_cid: AuthCid = parent_hash.to_cid(0x55);
+        let parent_cid: AuthCid = parent_hash.to_event_cid();
         let mut invocation_nb = std::collections::BTreeMap::new();
         for (key, value) in constrained_caveat
             .as_object()
@@ -3652,6 +5079,7 @@ mod tests {
             .manage(Config::default())
             .manage(QuotaCache::new(None, None))
             .manage(InvocationReplayCache::new())
+            .manage(SpaceRateLimiter::new(&Default::default()))
             .manage(hook_runtime)
             .manage(BlockStage::from(crate::config::StagingStorage::Memory));
 
@@ -3763,7 +5191,7 @@ mod tests {
         let _persisted = tempdir.keep();
         let tinycloud = TinyCloud::new(
             db.clone(),
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?;
@@ -3874,7 +5302,7 @@ mod tests {
         {
             invocation_nb.insert(key.clone(), value.clone());
         }
-        let parent_cid: AuthCid = delegation_hash.to_cid(0x55);
+        let parent_cid: AuthCid = delegation_hash.to_event_cid();
         let make_auth_header = |nonce: &str| -> Result<String> {
             let mut invocation_caps = Capabilities::new();
             invocation_caps.with_action(
@@ -3907,6 +5335,7 @@ mod tests {
             .manage(Config::default())
             .manage(QuotaCache::new(None, None))
             .manage(InvocationReplayCache::new())
+            .manage(SpaceRateLimiter::new(&Default::default()))
             .manage(hook_runtime)
             .manage(BlockStage::from(crate::config::StagingStorage::Memory));
 
@@ -4234,7 +5663,7 @@ mod tests {
         let parent_id = tinycloud_core::hash::hash(b"status-parent");
         let mut child_capabilities = Capabilities::new();
         child_capabilities.with_action(
-            format!("urn:cid:{}", parent_id.to_cid(0x55)).parse()?,
+            format!("urn:cid:{}", parent_id.to_event_cid()).parse()?,
             "tinycloud.delegation/status".parse::<UcanAbility>()?,
             [std::collections::BTreeMap::<String, serde_json::Value>::new()],
         );
@@ -4245,7 +5674,7 @@ mod tests {
             expiration: NumericDate::try_from_seconds(4_102_444_800.0)?,
             nonce: Some("signed-status-child".to_string()),
             facts: Some(Vec::<serde_json::Value>::new()),
-            proof: vec![parent_id.to_cid(0x55)],
+            proof: vec![parent_id.to_event_cid()],
             attenuation: child_capabilities,
         }
         .sign(holder_jwk.get_algorithm().unwrap_or_default(), &holder_jwk)?
@@ -4416,7 +5845,7 @@ mod tests {
         for (proof_id, resource) in [
             (
                 wrong_target_session_id,
-                Resource::Other(format!("urn:cid:{}", direct_id.to_cid(0x55)).parse()?),
+                Resource::Other(format!("urn:cid:{}", direct_id.to_event_cid()).parse()?),
             ),
             (
                 foreign_scope_session_id,
@@ -4504,7 +5933,7 @@ mod tests {
             abilities::ActiveModel {
                 delegation: Set(id),
                 resource: Set(Resource::Other(
-                    format!("urn:cid:{}", wallet_child_id.to_cid(0x55)).parse()?,
+                    format!("urn:cid:{}", wallet_child_id.to_event_cid()).parse()?,
                 )),
                 ability: Set(Ability::try_from(
                     "tinycloud.delegation/status".to_string(),
@@ -4562,7 +5991,7 @@ mod tests {
             })
             .manage(tinycloud);
         let client = Client::tracked(rocket).await?;
-        let child_cid = child_id.to_cid(0x55).to_string();
+        let child_cid = child_id.to_event_cid().to_string();
         let request = |cid: &str, header: String| {
             client
                 .post("/delegation/status")
@@ -4596,14 +6025,14 @@ mod tests {
             &holder_did,
             &query_control_resource,
             "authorized-account-query",
-            vec![wallet_session_id.to_cid(0x55)],
+            vec![wallet_session_id.to_event_cid()],
         )?)
         .dispatch()
         .await;
         assert_eq!(response.status(), Status::Ok);
         let query_body = response.into_string().await.unwrap_or_default();
-        assert!(query_body.contains(&wallet_child_id.to_cid(0x55).to_string()));
-        assert!(!query_body.contains(&foreign_record_id.to_cid(0x55).to_string()));
+        assert!(query_body.contains(&wallet_child_id.to_event_cid().to_string()));
+        assert!(!query_body.contains(&foreign_record_id.to_event_cid().to_string()));
 
         let foreign_query_resource = Resource::TinyCloud(foreign_control_space.to_resource(
             "delegation".parse()?,
@@ -4617,7 +6046,7 @@ mod tests {
             &holder_did,
             &foreign_query_resource,
             "mismatched-query-principal",
-            vec![wallet_session_id.to_cid(0x55)],
+            vec![wallet_session_id.to_event_cid()],
         )?)
         .dispatch()
         .await;
@@ -4638,14 +6067,14 @@ mod tests {
                 &holder_did,
                 &query_control_resource,
                 label,
-                vec![proof_id.to_cid(0x55)],
+                vec![proof_id.to_event_cid()],
             )?)
             .dispatch()
             .await;
             assert_eq!(response.status(), Status::Forbidden, "{label}");
         }
 
-        let mismatched_cid = direct_id.to_cid(0x55).to_string();
+        let mismatched_cid = direct_id.to_event_cid().to_string();
         let response = request(
             &child_cid,
             status_header(
@@ -4671,7 +6100,7 @@ mod tests {
         assert_eq!(body["status"], "active");
         assert_eq!(body["active"], true);
 
-        let wallet_child_cid = wallet_child_id.to_cid(0x55).to_string();
+        let wallet_child_cid = wallet_child_id.to_event_cid().to_string();
         let response = request(
             &wallet_child_cid,
             status_header_with_proofs(
@@ -4680,7 +6109,7 @@ mod tests {
                 &holder_did,
                 &wallet_child_cid,
                 "wallet-session-status",
-                vec![wallet_session_id.to_cid(0x55)],
+                vec![wallet_session_id.to_event_cid()],
             )?,
         )
         .dispatch()
@@ -4693,49 +6122,49 @@ mod tests {
         for (label, proofs) in [
             (
                 "missing-session-status",
-                vec![missing_session_id.to_cid(0x55)],
+                vec![missing_session_id.to_event_cid()],
             ),
             (
                 "expired-session-status",
-                vec![expired_session_id.to_cid(0x55)],
+                vec![expired_session_id.to_event_cid()],
             ),
             (
                 "future-session-status",
-                vec![future_session_id.to_cid(0x55)],
+                vec![future_session_id.to_event_cid()],
             ),
             (
                 "revoked-session-status",
-                vec![revoked_session_id.to_cid(0x55)],
+                vec![revoked_session_id.to_event_cid()],
             ),
             (
                 "same-wallet-unrelated-capability",
-                vec![unrelated_session_id.to_cid(0x55)],
+                vec![unrelated_session_id.to_event_cid()],
             ),
             (
                 "correct-action-wrong-target-resource",
-                vec![wrong_target_session_id.to_cid(0x55)],
+                vec![wrong_target_session_id.to_event_cid()],
             ),
             (
                 "correct-action-foreign-root-scope",
-                vec![foreign_scope_session_id.to_cid(0x55)],
+                vec![foreign_scope_session_id.to_event_cid()],
             ),
             (
                 "correct-action-narrow-scope",
-                vec![narrow_scope_session_id.to_cid(0x55)],
+                vec![narrow_scope_session_id.to_event_cid()],
             ),
             (
                 "correct-action-query-scope",
-                vec![query_scope_session_id.to_cid(0x55)],
+                vec![query_scope_session_id.to_event_cid()],
             ),
             (
                 "correct-action-fragment-scope",
-                vec![fragment_scope_session_id.to_cid(0x55)],
+                vec![fragment_scope_session_id.to_event_cid()],
             ),
             (
                 "multiple-mixed-session-status",
                 vec![
-                    wallet_session_id.to_cid(0x55),
-                    unrelated_session_id.to_cid(0x55),
+                    wallet_session_id.to_event_cid(),
+                    unrelated_session_id.to_event_cid(),
                 ],
             ),
         ] {
@@ -4772,7 +6201,7 @@ mod tests {
                         &holder_vm,
                         &holder_did,
                         &wallet_child_cid,
-                        proof_id.to_cid(0x55),
+                        proof_id.to_event_cid(),
                     )?,
                 ))
                 .dispatch()
@@ -4789,7 +6218,7 @@ mod tests {
                     &holder_vm,
                     &holder_did,
                     &wallet_child_cid,
-                    wallet_session_id.to_cid(0x55),
+                    wallet_session_id.to_event_cid(),
                 )?,
             ))
             .dispatch()
@@ -4804,7 +6233,7 @@ mod tests {
                 &holder_did,
                 &wallet_child_cid,
                 "wallet-session-post-revoke",
-                vec![wallet_session_id.to_cid(0x55)],
+                vec![wallet_session_id.to_event_cid()],
             )?,
         )
         .dispatch()
@@ -4848,7 +6277,7 @@ mod tests {
         assert_eq!(body["status"], "revoked");
         assert_eq!(body["revoked"], true);
 
-        let expired_cid = expired_id.to_cid(0x55).to_string();
+        let expired_cid = expired_id.to_event_cid().to_string();
         let response = request(
             &expired_cid,
             status_header(
@@ -4865,7 +6294,7 @@ mod tests {
         assert_eq!(body["status"], "expired");
         assert_eq!(body["expired"], true);
 
-        let direct_cid = direct_id.to_cid(0x55).to_string();
+        let direct_cid = direct_id.to_event_cid().to_string();
         let response = request(
             &direct_cid,
             status_header(
@@ -4900,7 +6329,7 @@ mod tests {
         assert_eq!(response.status(), Status::NotFound);
 
         let missing_cid = tinycloud_core::hash::hash(b"missing-status")
-            .to_cid(0x55)
+            .to_event_cid()
             .to_string();
         let response = request(
             &missing_cid,
@@ -5018,7 +6447,7 @@ mod tests {
         .await?;
 
         // Cite C; the helper must walk to A and find the caveat.
-        let cid_c: AuthCid = hash_c.to_cid(0x55);
+        let cid_c: AuthCid = hash_c.to_event_cid();
         let caveat = derive_chain_constrained_caveat_with_conn(&conn, &[cid_c])
             .await
             .expect("helper must succeed")
@@ -5135,7 +6564,7 @@ mod tests {
         let _persisted = tempdir.keep();
         let tinycloud = TinyCloud::new(
             auth_db.clone(),
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?
@@ -5239,7 +6668,7 @@ mod tests {
             .await?;
         }
 
-        let parent_cid = parent_hash.to_cid(0x55);
+        let parent_cid = parent_hash.to_event_cid();
         let used = tinycloud
             .store_size(&space)
             .await
@@ -5306,17 +6735,39 @@ mod tests {
     fn metered_sql_rocket(
         setup: MeteredSqlHttp,
         limit: rocket::data::ByteUnit,
+    ) -> rocket::Rocket<rocket::Build> {
+        metered_sql_rocket_with_limiter(setup, limit, SpaceRateLimiter::new(&Default::default()))
+    }
+
+    fn metered_sql_rocket_with_limiter(
+        setup: MeteredSqlHttp,
+        limit: rocket::data::ByteUnit,
+        space_rate_limiter: SpaceRateLimiter,
+    ) -> rocket::Rocket<rocket::Build> {
+        metered_sql_rocket_with_config(setup, limit, space_rate_limiter, Config::default())
+    }
+
+    fn metered_sql_rocket_with_config(
+        setup: MeteredSqlHttp,
+        limit: rocket::data::ByteUnit,
+        space_rate_limiter: SpaceRateLimiter,
+        config: Config,
     ) -> rocket::Rocket<rocket::Build> {
         rocket::build()
-            .mount("/", rocket::routes![invoke])
+            .mount("/", rocket::routes![invoke, sql_invoke])
             .attach(crate::tracing::TracingFairing {
                 header_name: Config::default().log.tracing.traceheader,
             })
+            .attach(crate::idempotency::IdempotencyFairing)
             .manage(setup.tinycloud)
             .manage(setup.sql_service)
-            .manage(Config::default())
+            .manage(config)
             .manage(QuotaCache::new(Some(limit), None))
             .manage(InvocationReplayCache::new())
+            .manage(crate::idempotency::IdempotencyCache::new(
+                &Default::default(),
+            ))
+            .manage(space_rate_limiter)
             .manage(HookRuntime::new(HooksConfig::default(), [9u8; 32]))
             .manage(BlockStage::from(crate::config::StagingStorage::Memory))
     }
@@ -5399,6 +6850,108 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn sql_route_executes_sql_invocations() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let setup = metered_sql_http_setup("sql-route").await?;
+        let auth_header = sql_invocation_header(
+            &setup,
+            "tinycloud.sql/read",
+            "urn:uuid:00000000-0000-4000-8000-0000000000s1",
+        )?;
+
+        let client =
+            Client::tracked(metered_sql_rocket(setup, ByteUnit::Byte(1_000_000_000))).await?;
+        let response = client
+            .post("/sql")
+            .header(Header::new("Authorization", auth_header))
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(&SqlRequest::Query {
+                sql: "SELECT val FROM labels WHERE label = 'alpha'".to_string(),
+                params: vec![],
+                max_rows: None,
+                max_bytes: None,
+            })?)
+            .dispatch()
+            .await;
+
+        let status = response.status();
+        let body = response.into_string().await.unwrap_or_default();
+        assert_eq!(
+            status,
+            Status::Ok,
+            "sql route must execute the query: {body}"
+        );
+        assert!(serde_json::from_str::<serde_json::Value>(&body)?.is_object());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_verified_invocations_are_charged_to_the_rate_limit() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let mut setup = metered_sql_http_setup("sql-rate-limit").await?;
+        let headers = |setup: &MeteredSqlHttp, nonces: &[&str]| {
+            nonces
+                .iter()
+                .map(|n| {
+                    sql_invocation_header(
+                        setup,
+                        "tinycloud.sql/read",
+                        &format!("urn:uuid:00000000-0000-4000-8000-0000000000{n}"),
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        let signer = std::mem::replace(&mut setup.jwk, JWK::generate_ed25519()?);
+        let forged = headers(&setup, &["f0", "f1", "f2"])?;
+        setup.jwk = signer;
+        let signed = headers(&setup, &["s0", "s1"])?;
+        let limiter = SpaceRateLimiter::new(&crate::config::SpaceRateLimitConfig {
+            enabled: true,
+            requests_per_second: 1,
+            burst: 1,
+            ..Default::default()
+        });
+
+        let client = Client::tracked(metered_sql_rocket_with_limiter(
+            setup,
+            ByteUnit::Byte(1_000_000_000),
+            limiter,
+        ))
+        .await?;
+        let query = serde_json::to_string(&SqlRequest::Query {
+            sql: "SELECT val FROM labels WHERE label = 'alpha'".to_string(),
+            params: vec![],
+            max_rows: None,
+            max_bytes: None,
+        })?;
+        let send = |header: String| {
+            client
+                .post("/invoke")
+                .header(Header::new("Authorization", header))
+                .header(ContentType::JSON)
+                .body(query.clone())
+                .dispatch()
+        };
+
+        // forged invocations are rejected without spending the space's burst
+        for header in forged {
+            assert_eq!(send(header).await.status(), Status::Unauthorized);
+        }
+        assert_eq!(send(signed[0].clone()).await.status(), Status::Ok);
+        assert_eq!(
+            send(signed[1].clone()).await.status(),
+            Status::TooManyRequests
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn sql_write_under_limit_returns_200() -> Result<()> {
         use rocket::data::ByteUnit;
@@ -5437,6 +6990,231 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn retried_invocations_with_an_idempotency_key_apply_once() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let setup = metered_sql_http_setup("sql-idempotent").await?;
+        let write = sql_invocation_header(
+            &setup,
+            "tinycloud.sql/write",
+            "urn:uuid:00000000-0000-4000-8000-0000000000i1",
+        )?;
+        let read = sql_invocation_header(
+            &setup,
+            "tinycloud.sql/read",
+            "urn:uuid:00000000-0000-4000-8000-0000000000i2",
+        )?;
+
+        let client =
+            Client::tracked(metered_sql_rocket(setup, ByteUnit::Byte(1_000_000_000))).await?;
+        let mut responses = Vec::new();
+        for _ in 0..2 {
+            let response = client
+                .post("/invoke")
+                .header(Header::new("Authorization", write.clone()))
+                .header(Header::new("Idempotency-Key", "insert-gamma"))
+                .header(ContentType::JSON)
+                .body(serde_json::to_string(&SqlRequest::Execute {
+                    schema: None,
+                    sql: "INSERT INTO labels (label, val) VALUES ('gamma', 333)".to_string(),
+                    params: vec![],
+                })?)
+                .dispatch()
+                .await;
+            let status = response.status();
+            let replayed = response.headers().get_one("Idempotent-Replayed").is_some();
+            responses.push((status, replayed, response.into_string().await));
+        }
+        assert_eq!(responses[0].0, Status::Ok, "{:?}", responses[0].2);
+        assert!(!responses[0].1);
+        assert!(responses[1].1, "the retry must be answered from the record");
+        assert_eq!(responses[0].0, responses[1].0);
+        assert_eq!(responses[0].2, responses[1].2);
+
+        let response = client
+            .post("/invoke")
+            .header(Header::new("Authorization", read))
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(&SqlRequest::Query {
+                sql: "SELECT COUNT(*) FROM labels WHERE label = 'gamma'".to_string(),
+                params: vec![],
+                max_rows: None,
+                max_bytes: None,
+            })?)
+            .dispatch()
+            .await;
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().await.unwrap_or_default())?;
+        assert_eq!(body["rows"][0][0], 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn forged_invocations_do_not_claim_idempotency_keys() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let mut setup = metered_sql_http_setup("sql-idempotent-forged").await?;
+        let nonce = "urn:uuid:00000000-0000-4000-8000-0000000000i3";
+        let signer = std::mem::replace(&mut setup.jwk, JWK::generate_ed25519()?);
+        let forged = sql_invocation_header(&setup, "tinycloud.sql/read", nonce)?;
+        setup.jwk = signer;
+        let signed = sql_invocation_header(&setup, "tinycloud.sql/read", nonce)?;
+
+        let client =
+            Client::tracked(metered_sql_rocket(setup, ByteUnit::Byte(1_000_000_000))).await?;
+        let query = serde_json::to_string(&SqlRequest::Query {
+            sql: "SELECT val FROM labels WHERE label = 'alpha'".to_string(),
+            params: vec![],
+            max_rows: None,
+            max_bytes: None,
+        })?;
+        let send = |header: String| {
+            client
+                .post("/invoke")
+                .header(Header::new("Authorization", header))
+                .header(Header::new("Idempotency-Key", "read-alpha"))
+                .header(ContentType::JSON)
+                .body(query.clone())
+                .dispatch()
+        };
+
+        // the forged request is answered but its response is not recorded
+        // under the key, which stays free for its owner
+        assert_eq!(send(forged).await.status(), Status::Unauthorized);
+        let response = send(signed.clone()).await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.headers().get_one("Idempotent-Replayed").is_none());
+        let response = send(signed).await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.headers().get_one("Idempotent-Replayed").is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invocations_over_the_capability_limit_are_refused_before_verification() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        let mut setup = metered_sql_http_setup("sql-too-many-capabilities").await?;
+        let signer = std::mem::replace(&mut setup.jwk, JWK::generate_ed25519()?);
+        let forged = sql_invocation_header(
+            &setup,
+            "tinycloud.sql/read",
+            "urn:uuid:00000000-0000-4000-8000-0000000000c0",
+        )?;
+        setup.jwk = signer;
+        let signed = sql_invocation_header(
+            &setup,
+            "tinycloud.sql/read",
+            "urn:uuid:00000000-0000-4000-8000-0000000000c1",
+        )?;
+        let mut config = Config::default();
+        config.spaces.max_capabilities_per_invocation = Some(0);
+        let limiter = SpaceRateLimiter::new(&crate::config::SpaceRateLimitConfig {
+            enabled: true,
+            requests_per_second: 1,
+            burst: 1,
+            ..Default::default()
+        });
+
+        let client = Client::tracked(metered_sql_rocket_with_config(
+            setup,
+            ByteUnit::Byte(1_000_000_000),
+            limiter,
+            config,
+        ))
+        .await?;
+        let query = serde_json::to_string(&SqlRequest::Query {
+            sql: "SELECT val FROM labels WHERE label = 'alpha'".to_string(),
+            params: vec![],
+            max_rows: None,
+            max_bytes: None,
+        })?;
+
+        // refused with 400 on every route, whether or not the signature
+        // holds, and without charging the space
+        for route in ["/sql", "/invoke"] {
+            for header in [&forged, &signed, &signed] {
+                let response = client
+                    .post(route)
+                    .header(Header::new("Authorization", header.clone()))
+                    .header(ContentType::JSON)
+                    .body(query.clone())
+                    .dispatch()
+                    .await;
+                assert_eq!(response.status(), Status::BadRequest, "{route}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses every invocation, either outright or as if its backend were
+    /// unreachable.
+    #[derive(Debug)]
+    struct RefuseAll {
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl tinycloud_core::policy::Policy for RefuseAll {
+        async fn authorize(
+            &self,
+            _: &tinycloud_core::policy::AuthContext<'_>,
+        ) -> Result<(), PolicyError> {
+            Err(if self.fail {
+                PolicyError::Failed("policy backend unreachable".into())
+            } else {
+                PolicyError::Denied("maintenance".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn policy_refusals_are_answered_alike_on_every_route() -> Result<()> {
+        use rocket::data::ByteUnit;
+        use rocket::http::{ContentType, Header, Status};
+        use rocket::local::asynchronous::Client;
+
+        for (fail, expected) in [
+            (false, Status::Forbidden),
+            (true, Status::ServiceUnavailable),
+        ] {
+            let mut setup = metered_sql_http_setup("sql-policy-refusal").await?;
+            let header = sql_invocation_header(
+                &setup,
+                "tinycloud.sql/read",
+                "urn:uuid:00000000-0000-4000-8000-0000000000p1",
+            )?;
+            setup.tinycloud = setup.tinycloud.with_policy(Arc::new(RefuseAll { fail }));
+            let client =
+                Client::tracked(metered_sql_rocket(setup, ByteUnit::Byte(1_000_000_000))).await?;
+            let query = serde_json::to_string(&SqlRequest::Query {
+                sql: "SELECT val FROM labels WHERE label = 'alpha'".to_string(),
+                params: vec![],
+                max_rows: None,
+                max_bytes: None,
+            })?;
+
+            for route in ["/sql", "/invoke"] {
+                let response = client
+                    .post(route)
+                    .header(Header::new("Authorization", header.clone()))
+                    .header(ContentType::JSON)
+                    .body(query.clone())
+                    .dispatch()
+                    .await;
+                assert_eq!(response.status(), expected, "{route}");
+            }
+        }
+        Ok(())
+    }
+
     /// Regression: a write wrapped in `ExecuteStatement` whose SQL is pinned
     /// by the invoker's OWN invocation facts (the facts-caveats fallback, no
     /// chain constrained caveat) must hit the 402 gate like any other write.
@@ -8,7 +8,7 @@ use rocket::{
 };
 use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Instant};
 use tinycloud_auth::resource::{Path, SpaceId};
-use tinycloud_core::storage::{Content, ImmutableReadStore};
+use tinycloud_core::storage::{Content, ImmutableReadStore, Ranged};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 
 use crate::{config::PublicSpacesConfig, BlockStores, TinyCloud};
@@ -112,13 +112,53 @@ impl<'r> FromRequest<'r> for IfNoneMatch {
     }
 }
 
+/// A single `Range: bytes=start-[end]` request. Suffix and multi-part ranges
+/// are not supported; as RFC 9110 allows, they are ignored and the whole
+/// value is served.
+pub struct ByteRange {
+    pub start: u64,
+    /// Exclusive end of the range, or `None` for the rest of the value.
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    fn parse(header: &str) -> Option<Self> {
+        let (start, end) = header.trim().strip_prefix("bytes=")?.split_once('-')?;
+        let start = start.trim().parse().ok()?;
+        let end = match end.trim() {
+            "" => None,
+            end => Some(end.parse::<u64>().ok()?.checked_add(1)?),
+        };
+        match end {
+            Some(end) if end <= start => None,
+            end => Some(Self { start, end }),
+        }
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for ByteRange {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request
+            .headers()
+            .get_one("Range")
+            .and_then(ByteRange::parse)
+        {
+            Some(range) => Outcome::Success(range),
+            None => Outcome::Forward(Status::Ok),
+        }
+    }
+}
+
 // --- Response Types ---
 
 const CACHE_CONTROL: &str = "public, max-age=60";
 const CORS_ORIGIN: &str = "*";
 const CORS_METHODS: &str = "GET, HEAD, OPTIONS";
-const CORS_ALLOW_HEADERS: &str = "If-None-Match";
-const CORS_EXPOSE_HEADERS: &str = "ETag, Content-Type, Content-Length";
+const CORS_ALLOW_HEADERS: &str = "If-None-Match, Range";
+const CORS_EXPOSE_HEADERS: &str =
+    "ETag, Content-Type, Content-Length, Content-Range, Accept-Ranges";
 
 /// Headers safe to expose on unauthenticated public endpoints.
 /// Everything else (authorization, host, user-agent, etc.) is stripped.
@@ -146,14 +186,48 @@ fn add_public_headers(response: &mut Response<'_>, etag: Option<&str>) {
     }
 }
 
-pub struct PublicKVResponse<R>(Content<R>, Metadata, String);
+pub enum PublicKVBody<R> {
+    Full(Content<R>),
+    Range(Content<Ranged<R>>),
+}
+
+pub struct PublicKVResponse<R>(PublicKVBody<R>, Metadata, String);
 
 impl<'r, R> Responder<'r, 'static> for PublicKVResponse<R>
 where
     R: 'static + AsyncRead + Send,
 {
-    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
-        let mut response = Response::build().streamed_body(self.0.compat()).finalize();
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = match self.0 {
+            PublicKVBody::Full(content) => {
+                crate::tracing::record_streamed_body_size(request, content.len());
+                Response::build()
+                    .header(Header::new("Accept-Ranges", "bytes"))
+                    .streamed_body(content.compat())
+                    .finalize()
+            }
+            PublicKVBody::Range(content) => {
+                let (len, ranged) = content.into_inner();
+                let (start, total) = (ranged.start(), ranged.total());
+                if len == 0 {
+                    Response::build()
+                        .status(Status::RangeNotSatisfiable)
+                        .header(Header::new("Content-Range", format!("bytes */{total}")))
+                        .finalize()
+                } else {
+                    crate::tracing::record_streamed_body_size(request, len);
+                    Response::build()
+                        .status(Status::PartialContent)
+                        .header(Header::new("Accept-Ranges", "bytes"))
+                        .header(Header::new(
+                            "Content-Range",
+                            format!("bytes {start}-{}/{total}", start + len - 1),
+                        ))
+                        .streamed_body(Content::new(len, ranged).compat())
+                        .finalize()
+                }
+            }
+        };
         for (k, v) in sanitized_metadata(&self.1) {
             response.set_header(Header::new(k.clone(), v.clone()));
         }
@@ -202,6 +276,7 @@ pub async fn public_kv_get(
     space_id: &str,
     key: RawKeyPath,
     if_none_match: Option<IfNoneMatch>,
+    range: Option<ByteRange>,
     client_ip: ClientIp,
     rate_limiter: &State<RateLimiter>,
     tinycloud: &State<TinyCloud>,
@@ -226,10 +301,17 @@ pub async fn public_kv_get(
         .parse()
         .map_err(|_| (Status::BadRequest, "Invalid key".to_string()))?;
 
-    let result = tinycloud
-        .public_kv_get(&space_id, &key)
-        .await
-        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    let result = match range {
+        Some(ByteRange { start, end }) => tinycloud
+            .public_kv_get_range(&space_id, &key, start, end)
+            .await
+            .map(|read| read.map(|(md, hash, c)| (md, hash, PublicKVBody::Range(c)))),
+        None => tinycloud
+            .public_kv_get(&space_id, &key)
+            .await
+            .map(|read| read.map(|(md, hash, c)| (md, hash, PublicKVBody::Full(c)))),
+    }
+    .map_err(|e| (Status::InternalServerError, e.to_string()))?;
 
     match result {
         Some((md, hash, content)) => {
@@ -0,0 +1,752 @@
+use dashmap::{mapref::entry::Entry as MapEntry, DashMap};
+use futures::io::AsyncWriteExt;
+use rocket::{
+    data::{Data, ToByteUnit},
+    get,
+    http::Status,
+    post, put,
+    serde::json::Json,
+    Either, State,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tinycloud_auth::resource::{Path, SpaceId};
+use tinycloud_core::{
+    events::Invocation,
+    hash::Hash,
+    storage::{HashBuffer, ImmutableReadStore, ImmutableStaging},
+    types::Metadata,
+    util::InvocationInfo,
+    InvocationOutcome,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{
+    deadline_after, emit_kv_hook_events, kv_invoke_options, kv_invoke_status, kv_put_capabilities,
+    observe_space_sizes, staging_error, upload_too_large,
+};
+use crate::{
+    auth_guards::{stored_metadata, DataOut, InvOut, ObjectHeaders},
+    authorization::AuthHeaderGetter,
+    config::Config,
+    hooks::HookRuntime,
+    invocation_replay::InvocationReplayCache,
+    quota::QuotaCache,
+    rate_limit::{RateLimited, SpaceRateLimiter},
+    routes::public::{is_public_space, ClientIp},
+    BlockStage, BlockStores, TinyCloud,
+};
+
+type Stage = HashBuffer<<BlockStage as ImmutableStaging>::Writable>;
+type Slot = Arc<Mutex<Option<Upload>>>;
+
+/// A kv put whose value is being uploaded in chunks.
+struct Upload {
+    invocation: Invocation,
+    metadata: Metadata,
+    space: SpaceId,
+    path: Path,
+    stage: Stage,
+    offset: u64,
+}
+
+struct Entry {
+    upload: Slot,
+    touched: Instant,
+    space: SpaceId,
+    invocation: Hash,
+}
+
+/// Resumable uploads in progress, by id.
+///
+/// An invocation has at most one upload open, and a space at most
+/// `max_per_space`. An upload is dropped, along with its staged content,
+/// once it has gone `expiry` without a request.
+#[derive(Clone)]
+pub struct Uploads {
+    uploads: Arc<DashMap<String, Entry>>,
+    by_invocation: Arc<DashMap<Hash, String>>,
+    per_space: Arc<DashMap<SpaceId, usize>>,
+    expiry: Duration,
+    max_per_space: usize,
+}
+
+impl Uploads {
+    pub fn new(expiry: Duration, max_per_space: usize) -> Self {
+        Self {
+            uploads: Arc::new(DashMap::new()),
+            by_invocation: Arc::new(DashMap::new()),
+            per_space: Arc::new(DashMap::new()),
+            expiry,
+            max_per_space,
+        }
+    }
+
+    fn start(&self, upload: Upload) -> Result<String, (Status, String)> {
+        self.purge_expired();
+        let invocation = upload.invocation.content_hash();
+        let space = upload.space.clone();
+        let id = Uuid::new_v4().simple().to_string();
+        match self.by_invocation.entry(invocation) {
+            MapEntry::Occupied(_) => {
+                return Err((
+                    Status::Conflict,
+                    "An upload is already open for this invocation".to_string(),
+                ))
+            }
+            MapEntry::Vacant(vacant) => {
+                let mut open = self.per_space.entry(space.clone()).or_default();
+                if *open >= self.max_per_space {
+                    return Err((
+                        Status::TooManyRequests,
+                        format!("{space} already has {} uploads open", *open),
+                    ));
+                }
+                *open += 1;
+                vacant.insert(id.clone());
+            }
+        }
+        self.uploads.insert(
+            id.clone(),
+            Entry {
+                upload: Arc::new(Mutex::new(Some(upload))),
+                touched: Instant::now(),
+                space,
+                invocation,
+            },
+        );
+        Ok(id)
+    }
+
+    fn get(&self, id: &str) -> Option<Slot> {
+        let mut entry = self.uploads.get_mut(id)?;
+        if entry.touched.elapsed() >= self.expiry {
+            drop(entry);
+            self.remove(id);
+            return None;
+        }
+        entry.touched = Instant::now();
+        Some(entry.upload.clone())
+    }
+
+    fn remove(&self, id: &str) {
+        if let Some((_, entry)) = self.uploads.remove(id) {
+            self.release(&entry.space, &entry.invocation);
+        }
+    }
+
+    /// Drop the uploads which have expired, returning how many there were.
+    pub fn purge_expired(&self) -> usize {
+        let mut expired = Vec::new();
+        self.uploads.retain(|_, entry| {
+            let live = entry.touched.elapsed() < self.expiry;
+            if !live {
+                expired.push((entry.space.clone(), entry.invocation));
+            }
+            live
+        });
+        for (space, invocation) in &expired {
+            self.release(space, invocation);
+        }
+        expired.len()
+    }
+
+    /// Free the invocation and the space slot of an upload that has gone.
+    fn release(&self, space: &SpaceId, invocation: &Hash) {
+        self.by_invocation.remove(invocation);
+        self.per_space.remove_if_mut(space, |_, open| {
+            *open = open.saturating_sub(1);
+            *open == 0
+        });
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadStatus {
+    pub id: String,
+    /// Bytes uploaded so far, where the next chunk goes.
+    pub offset: u64,
+}
+
+fn unknown_upload() -> (Status, String) {
+    (
+        Status::NotFound,
+        "Upload not found; it may have expired or been completed".to_string(),
+    )
+}
+
+/// Start a resumable upload of the value of a kv put.
+///
+/// The invocation must hold exactly one kv put. It is authorized now but only
+/// spent when the upload is completed, and the request's other headers are
+/// the value's metadata, as for `/invoke`. The returned id authorizes the
+/// rest of the upload. An invocation may have only one upload open, and a
+/// space at most `storage.max_open_uploads`.
+#[post("/upload/init")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_init(
+    i: AuthHeaderGetter<InvocationInfo>,
+    client: ClientIp,
+    headers: ObjectHeaders,
+    staging: &State<BlockStage>,
+    tinycloud: &State<TinyCloud>,
+    config: &State<Config>,
+    uploads: &State<Uploads>,
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<(Status, Json<UploadStatus>), Either<RateLimited, (Status, String)>> {
+    let spaces: Vec<SpaceId> = i.0 .0.spaces().cloned().collect();
+    space_rate_limiter
+        .admit(client.0, &spaces)
+        .map_err(Either::Left)?;
+    let invocation = i.0;
+    let (space, path) = match kv_put_capabilities(&invocation.0).as_slice() {
+        [(space, path)] => (space.clone(), path.clone()),
+        _ => {
+            return Err(Either::Right((
+                Status::BadRequest,
+                "A resumable upload needs an invocation of exactly one kv put".to_string(),
+            )))
+        }
+    };
+
+    let mut options =
+        kv_invoke_options(&invocation.0, &mut ObjectHeaders(headers.0.clone()), false)
+            .map_err(Either::Right)?;
+    options.dry_run = true;
+    options.deadline = deadline_after(config.timeouts.invoke_secs);
+    let verified = tinycloud
+        .invoke_with_options::<BlockStage>(invocation.clone(), HashMap::new(), options)
+        .await
+        .map_err(|e| (kv_invoke_status(&e), e.to_string()));
+    space_rate_limiter.charge_outcome(&spaces, &verified);
+    verified.map_err(Either::Right)?;
+
+    let stage = staging
+        .stage(&space)
+        .await
+        .map_err(|e| Either::Right((Status::InternalServerError, e.to_string())))?;
+    let id = uploads
+        .start(Upload {
+            invocation,
+            metadata: headers.0,
+            space,
+            path,
+            stage,
+            offset: 0,
+        })
+        .map_err(Either::Right)?;
+    Ok((Status::Created, Json(UploadStatus { id, offset: 0 })))
+}
+
+/// Append a chunk to an upload at `offset`, the number of bytes uploaded so
+/// far. A chunk is appended whole or not at all, so an upload interrupted
+/// mid-chunk resumes from the offset [`upload_status`] reports. A chunk
+/// that would take the upload past the space's remaining quota is refused.
+#[put("/upload/<id>?<offset>", data = "<data>")]
+pub async fn upload_chunk(
+    id: &str,
+    offset: u64,
+    data: Data<'_>,
+    tinycloud: &State<TinyCloud>,
+    config: &State<Config>,
+    quota_cache: &State<QuotaCache>,
+    uploads: &State<Uploads>,
+) -> Result<Json<UploadStatus>, (Status, String)> {
+    let slot = uploads.get(id).ok_or_else(unknown_upload)?;
+    let mut slot = slot.lock().await;
+    let upload = slot.as_mut().ok_or_else(unknown_upload)?;
+    if offset != upload.offset {
+        return Err((
+            Status::Conflict,
+            format!("Upload is at offset {}", upload.offset),
+        ));
+    }
+
+    let max_chunk = config.storage.max_upload_chunk.as_u64();
+    let chunk = data
+        .open(max_chunk.bytes())
+        .into_bytes()
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+    if !chunk.is_complete() {
+        return Err((
+            Status::PayloadTooLarge,
+            format!("Upload chunks are limited to {max_chunk} bytes"),
+        ));
+    }
+    let end = upload.offset + chunk.len() as u64;
+    if end > config.storage.max_upload.as_u64() {
+        return Err(upload_too_large(config));
+    }
+    check_remaining_quota(tinycloud, config, quota_cache, &upload.space, end).await?;
+    if let Err(e) = upload.stage.write_all(&chunk).await {
+        // the stage may hold part of the chunk, so the upload can't go on
+        *slot = None;
+        uploads.remove(id);
+        return Err(staging_error(e, config));
+    }
+    upload.offset = end;
+    Ok(Json(UploadStatus {
+        id: id.to_string(),
+        offset: end,
+    }))
+}
+
+/// How much of an upload has been received.
+#[get("/upload/<id>")]
+pub async fn upload_status(
+    id: &str,
+    uploads: &State<Uploads>,
+) -> Result<Json<UploadStatus>, (Status, String)> {
+    let slot = uploads.get(id).ok_or_else(unknown_upload)?;
+    let slot = slot.lock().await;
+    let upload = slot.as_ref().ok_or_else(unknown_upload)?;
+    Ok(Json(UploadStatus {
+        id: id.to_string(),
+        offset: upload.offset,
+    }))
+}
+
+/// Commit the kv put of an upload with the value uploaded, spending its
+/// invocation. The response is that of the put sent to `/invoke`.
+#[post("/upload/<id>/complete")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_complete(
+    id: &str,
+    tinycloud: &State<TinyCloud>,
+    config: &State<Config>,
+    quota_cache: &State<QuotaCache>,
+    invocation_replay_cache: &State<InvocationReplayCache>,
+    hook_runtime: &State<HookRuntime>,
+    uploads: &State<Uploads>,
+    space_rate_limiter: &State<SpaceRateLimiter>,
+) -> Result<
+    DataOut<<BlockStores as ImmutableReadStore>::Readable>,
+    Either<RateLimited, (Status, String)>,
+> {
+    let slot = uploads
+        .get(id)
+        .ok_or_else(|| Either::Right(unknown_upload()))?;
+    let mut slot = slot.lock().await;
+    let spaces = slot
+        .as_ref()
+        .ok_or_else(|| Either::Right(unknown_upload()))?
+        .invocation
+        .0
+        .spaces();
+    // verified when the upload began
+    space_rate_limiter.check(spaces).map_err(Either::Left)?;
+    let upload = slot.take().ok_or_else(|| Either::Right(unknown_upload()))?;
+    uploads.remove(id);
+    drop(slot);
+
+    complete(
+        upload,
+        tinycloud,
+        config,
+        quota_cache,
+        invocation_replay_cache,
+        hook_runtime,
+    )
+    .await
+    .map_err(Either::Right)
+}
+
+/// Refuse `size` more bytes for `space` if they would not fit in its quota.
+async fn check_remaining_quota(
+    tinycloud: &TinyCloud,
+    config: &Config,
+    quota_cache: &QuotaCache,
+    space: &SpaceId,
+    size: u64,
+) -> Result<(), (Status, String)> {
+    let limit = if is_public_space(space) {
+        Some(config.public_spaces.storage_limit)
+    } else {
+        quota_cache.get_limit(space).await
+    };
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let used = tinycloud
+        .store_size(space)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?
+        .ok_or_else(|| (Status::NotFound, "space not found".to_string()))?;
+    if used >= limit.as_u64() {
+        return Err((
+            Status::new(402),
+            format!(
+                "Storage quota exceeded. Used: {} bytes, Limit: {} bytes",
+                used,
+                limit.as_u64()
+            ),
+        ));
+    }
+    if used.saturating_add(size) > limit.as_u64() {
+        return Err((
+            Status::PayloadTooLarge,
+            format!(
+                "Write exceeds remaining storage. Used: {} bytes, Limit: {} bytes",
+                used,
+                limit.as_u64()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+async fn complete(
+    upload: Upload,
+    tinycloud: &State<TinyCloud>,
+    config: &Config,
+    quota_cache: &QuotaCache,
+    invocation_replay_cache: &InvocationReplayCache,
+    hook_runtime: &HookRuntime,
+) -> Result<DataOut<<BlockStores as ImmutableReadStore>::Readable>, (Status, String)> {
+    let Upload {
+        invocation,
+        metadata,
+        space,
+        path,
+        stage,
+        offset,
+    } = upload;
+    invocation_replay_cache
+        .check_and_insert(&invocation)
+        .await?;
+
+    let mut headers = ObjectHeaders(metadata);
+    let mut options = kv_invoke_options(&invocation.0, &mut headers, false)?;
+    options.metadata_updates = std::mem::take(&mut options.metadata_updates)
+        .into_iter()
+        .map(|(key, update)| {
+            (
+                key,
+                stored_metadata(update, &config.storage.metadata_headers),
+            )
+        })
+        .collect();
+    let metadata = stored_metadata(headers.0, &config.storage.metadata_headers);
+
+    check_remaining_quota(tinycloud, config, quota_cache, &space, offset).await?;
+
+    let info = invocation.0.clone();
+    let mut inputs = HashMap::new();
+    inputs.insert((space, path), (metadata, stage));
+    options.deadline = deadline_after(config.timeouts.invoke_secs);
+    let (tx_result, outcomes) = tinycloud
+        .invoke_with_options::<BlockStage>(invocation, inputs, options)
+        .await
+        .map_err(|e| (kv_invoke_status(&e), e.to_string()))?;
+    observe_space_sizes(tinycloud, &info).await;
+    emit_kv_hook_events(hook_runtime, tinycloud, &info, &tx_result).await;
+    Ok(outcomes
+        .into_iter()
+        .find(|outcome| matches!(outcome, InvocationOutcome::KvWrite(_)))
+        .map_or(DataOut::None, |outcome| DataOut::One(InvOut(outcome))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::HooksConfig, storage::file_system::FileSystemConfig as NodeFileSystemConfig,
+    };
+    use anyhow::Result;
+    use rocket::{
+        http::Header,
+        local::asynchronous::{Client, LocalResponse},
+    };
+    use tempfile::TempDir;
+    use tinycloud_auth::{
+        authorization::{HeaderEncode, TinyCloudDelegation},
+        resolver::DID_METHODS,
+        siwe_recap::Ability as UcanAbility,
+        ssi::jwk::{Algorithm, JWK},
+    };
+    use tinycloud_core::{
+        events::Delegation,
+        hash::{hash, Hash},
+        keys::StaticSecret,
+        sea_orm::{ConnectOptions, Database},
+        storage::{either::Either as StoreEither, StorageConfig as _},
+    };
+    use tinycloud_sdk_wasm::session::{root_session, Session};
+
+    async fn test_tinycloud() -> Result<TinyCloud> {
+        let tempdir = TempDir::new()?;
+        let db = Database::connect(ConnectOptions::new("sqlite::memory:".to_string())).await?;
+        let storage = NodeFileSystemConfig::new(tempdir.path()).open().await?;
+        let _persisted = tempdir.keep();
+        Ok(TinyCloud::new(
+            db,
+            crate::storage::metered::MeteredStore::from(StoreEither::B(storage)).into(),
+            StaticSecret::new(vec![0u8; 32]).unwrap(),
+        )
+        .await?)
+    }
+
+    /// A client of the upload routes, and a root session of a space on its
+    /// node allowed to put under `files/`.
+    async fn upload_client(config: Config) -> Result<(Client, Session)> {
+        let mut root = JWK::generate_ed25519()?;
+        root.algorithm = Some(Algorithm::EdDSA);
+        let space = SpaceId::new(DID_METHODS.generate(&root, "key")?, "default".parse()?);
+        let tinycloud = test_tinycloud().await?;
+        tinycloud.create_space(&space).await?;
+        let session = root_session(serde_json::from_value(serde_json::json!({
+            "abilities": { "kv": { "files/": ["tinycloud.kv/put"] } },
+            "spaceId": space.to_string(),
+            "rootJwk": root,
+            "expirationSecs": 4_102_444_800.0,
+        }))?)?;
+        let header = serde_json::to_value(&session.delegation_header)?["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        tinycloud
+            .delegate(Delegation::from_header_ser::<TinyCloudDelegation>(&header)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("root delegation rejected: {e}"))?;
+
+        let rocket = rocket::build()
+            .mount(
+                "/",
+                rocket::routes![upload_init, upload_chunk, upload_status, upload_complete],
+            )
+            .manage(tinycloud)
+            .manage(QuotaCache::new(config.storage.limit, None))
+            .manage(InvocationReplayCache::new())
+            .manage(SpaceRateLimiter::new(&Default::default()))
+            .manage(HookRuntime::new(HooksConfig::default(), [9u8; 32]))
+            .manage(BlockStage::from(crate::config::StagingStorage::Memory))
+            .manage(Uploads::new(
+                Duration::from_secs(config.storage.upload_expiry_secs),
+                config.storage.max_open_uploads,
+            ))
+            .manage(config);
+        Ok((Client::tracked(rocket).await?, session))
+    }
+
+    async fn init(client: &Client, session: &Session, path: &str) -> Result<String> {
+        let response = init_with(client, &put_invocation(session, path)?).await;
+        assert_eq!(response.status(), Status::Created);
+        Ok(json(response).await?["id"].as_str().unwrap().to_string())
+    }
+
+    fn put_invocation(session: &Session, path: &str) -> Result<String> {
+        let invocation = session.invoke(
+            [(
+                "kv".parse()?,
+                path.parse()?,
+                None,
+                None,
+                ["tinycloud.kv/put".parse::<UcanAbility>()?],
+            )],
+            None,
+        )?;
+        Ok(invocation.encode()?)
+    }
+
+    async fn init_with<'c>(client: &'c Client, invocation: &str) -> LocalResponse<'c> {
+        client
+            .post("/upload/init")
+            .header(Header::new("Authorization", invocation.to_string()))
+            .header(Header::new("Content-Type", "text/plain"))
+            .dispatch()
+            .await
+    }
+
+    async fn json(response: LocalResponse<'_>) -> Result<serde_json::Value> {
+        Ok(serde_json::from_str(
+            &response.into_string().await.unwrap_or_default(),
+        )?)
+    }
+
+    async fn stored_hash(client: &Client, session: &Session, path: &str) -> Result<Hash> {
+        let tinycloud = client.rocket().state::<TinyCloud>().unwrap();
+        let (metadata, hash, _) = tinycloud
+            .kv_get(&session.space_id, &path.parse()?)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?
+            .ok_or_else(|| anyhow::anyhow!("{path} was not written"))?;
+        assert_eq!(
+            crate::routes::metadata_header(&metadata, "content-type"),
+            Some("text/plain")
+        );
+        Ok(hash)
+    }
+
+    #[tokio::test]
+    async fn chunks_are_staged_until_the_upload_completes() -> Result<()> {
+        let (client, session) = upload_client(Config::default()).await?;
+        let id = init(&client, &session, "files/report.txt").await?;
+
+        for (offset, chunk) in [(0, "hello, "), (7, "world")] {
+            let response = client
+                .put(format!("/upload/{id}?offset={offset}"))
+                .body(chunk)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(json(response).await?["offset"], offset + chunk.len() as u64);
+        }
+        let tinycloud = client.rocket().state::<TinyCloud>().unwrap();
+        assert!(tinycloud
+            .kv_get(&session.space_id, &"files/report.txt".parse()?)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?
+            .is_none());
+
+        let response = client
+            .post(format!("/upload/{id}/complete"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            stored_hash(&client, &session, "files/report.txt").await?,
+            hash(b"hello, world")
+        );
+
+        // the upload is gone once completed
+        let response = client.get(format!("/upload/{id}")).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uploads_resume_from_the_last_whole_chunk() -> Result<()> {
+        use rocket::data::ByteUnit;
+
+        let mut config = Config::default();
+        config.storage.max_upload_chunk = ByteUnit::Byte(8);
+        let (client, session) = upload_client(config).await?;
+        let id = init(&client, &session, "files/resumed.txt").await?;
+
+        let response = client
+            .put(format!("/upload/{id}?offset=0"))
+            .body("first ")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        // a chunk cut off by the limit is dropped whole
+        let response = client
+            .put(format!("/upload/{id}?offset=6"))
+            .body("second chunk")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+        // and a retry of an already received chunk is refused
+        let response = client
+            .put(format!("/upload/{id}?offset=0"))
+            .body("first ")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Conflict);
+
+        let response = client.get(format!("/upload/{id}")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(json(response).await?["offset"], 6);
+        for (offset, chunk) in [(6, "second"), (12, " chunk")] {
+            let response = client
+                .put(format!("/upload/{id}?offset={offset}"))
+                .body(chunk)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        let response = client
+            .post(format!("/upload/{id}/complete"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            stored_hash(&client, &session, "files/resumed.txt").await?,
+            hash(b"first second chunk")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_uploads_are_limited_per_invocation_and_space() -> Result<()> {
+        let mut config = Config::default();
+        config.storage.max_open_uploads = 2;
+        let (client, session) = upload_client(config).await?;
+
+        let first = put_invocation(&session, "files/one.txt")?;
+        let response = init_with(&client, &first).await;
+        assert_eq!(response.status(), Status::Created);
+        let id = json(response).await?["id"].as_str().unwrap().to_string();
+        assert_eq!(init_with(&client, &first).await.status(), Status::Conflict);
+        init(&client, &session, "files/two.txt").await?;
+        let third = put_invocation(&session, "files/three.txt")?;
+        assert_eq!(
+            init_with(&client, &third).await.status(),
+            Status::TooManyRequests
+        );
+
+        // completing an upload frees its slot
+        let response = client
+            .post(format!("/upload/{id}/complete"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(init_with(&client, &third).await.status(), Status::Created);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chunks_past_the_remaining_quota_are_refused() -> Result<()> {
+        use rocket::data::ByteUnit;
+
+        let mut config = Config::default();
+        config.storage.limit = Some(ByteUnit::Kibibyte(1));
+        let (client, session) = upload_client(config).await?;
+        let id = init(&client, &session, "files/big.bin").await?;
+
+        let response = client
+            .put(format!("/upload/{id}?offset=0"))
+            .body("small")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(format!("/upload/{id}?offset=5"))
+            .body(vec![0u8; 2048])
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+
+        // the refused chunk was not staged
+        let response = client.get(format!("/upload/{id}")).dispatch().await;
+        assert_eq!(json(response).await?["offset"], 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_uploads_expire() -> Result<()> {
+        let mut config = Config::default();
+        config.storage.upload_expiry_secs = 0;
+        let (client, session) = upload_client(config).await?;
+        let id = init(&client, &session, "files/abandoned.txt").await?;
+
+        let response = client
+            .put(format!("/upload/{id}?offset=0"))
+            .body("never finished")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            client.rocket().state::<Uploads>().unwrap().purge_expired(),
+            0
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
-use futures::io::AsyncRead;
+use futures::io::{AsyncRead, AsyncReadExt, Chain, Cursor};
 use pin_project::pin_project;
-use std::{io::Error as IoError, task::Poll};
+use std::{error::Error as StdError, io::Error as IoError, task::Poll};
+
+/// Number of leading bytes inspected when sniffing a value's content type.
+const CONTENT_SNIFF_LEN: u64 = 8192;
 
 /// LimitedRead wraps an AsyncRead and limits the number of bytes that can be read.
 ///
@@ -57,6 +60,96 @@ where
     }
 }
 
+/// Caps a request body at the configured maximum upload size.
+///
+/// Unlike opening the body with a plain limit, which silently truncates, a
+/// body longer than `limit` fails the read with [`UploadTooLarge`]. The inner
+/// reader should allow at least one byte past `limit` so overflow is seen.
+#[pin_project]
+#[derive(Debug)]
+pub struct UploadLimit<R> {
+    #[pin]
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> UploadLimit<R> {
+    pub fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("request body exceeds the maximum upload size")]
+pub struct UploadTooLarge;
+
+/// Whether `error`, or any error it wraps, is an [`UploadTooLarge`].
+pub fn is_upload_too_large(error: &(dyn StdError + 'static)) -> bool {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        if error.is::<UploadTooLarge>() {
+            return true;
+        }
+        // `io::Error::source` skips over the error it wraps
+        next = match error.downcast_ref::<IoError>() {
+            Some(io) => io.get_ref().map(|inner| inner as &(dyn StdError + 'static)),
+            None => error.source(),
+        };
+    }
+    false
+}
+
+impl<R> AsyncRead for UploadLimit<R>
+where
+    R: AsyncRead,
+{
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.project();
+
+        match this.inner.poll_read(cx, buf) {
+            Poll::Ready(Ok(n)) if n as u64 > *this.remaining => {
+                Poll::Ready(Err(IoError::other(UploadTooLarge)))
+            }
+            Poll::Ready(Ok(n)) => {
+                *this.remaining -= n as u64;
+                Poll::Ready(Ok(n))
+            }
+            r => r,
+        }
+    }
+}
+
+/// Reads the head of `reader` and infers a MIME type from its magic bytes.
+///
+/// The returned reader yields the full value, including the bytes consumed
+/// for sniffing. When `sniff` is false nothing is read ahead.
+pub async fn sniff_content_type<R>(
+    mut reader: R,
+    sniff: bool,
+) -> Result<(Option<&'static str>, Chain<Cursor<Vec<u8>>, R>), IoError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut head = Vec::new();
+    let content_type = if sniff {
+        (&mut reader)
+            .take(CONTENT_SNIFF_LEN)
+            .read_to_end(&mut head)
+            .await?;
+        infer::get(&head).map(|kind| kind.mime_type())
+    } else {
+        None
+    };
+    Ok((content_type, Cursor::new(head).chain(reader)))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -83,4 +176,39 @@ mod test {
         let r = reader.read_to_end(&mut buf).await;
         assert!(r.is_err());
     }
+
+    #[tokio::test]
+    async fn upload_limit_rejects_bodies_past_the_cap() {
+        let data = b"hello world";
+
+        let mut buf = Vec::new();
+        let mut reader = UploadLimit::new(&data[..], data.len() as u64);
+        assert_eq!(reader.read_to_end(&mut buf).await.unwrap(), data.len());
+
+        let mut reader = UploadLimit::new(&data[..], data.len() as u64 - 1);
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert!(is_upload_too_large(&err));
+
+        // still recognised once wrapped, as multer does with stream errors
+        let wrapped: Box<dyn StdError + Send + Sync> = Box::new(IoError::other(err));
+        assert!(is_upload_too_large(wrapped.as_ref()));
+        assert!(!is_upload_too_large(&IoError::other(LimitExceeded)));
+    }
+
+    #[tokio::test]
+    async fn sniffs_png_without_losing_bytes() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x01";
+
+        let (content_type, mut reader) = sniff_content_type(&png[..], true).await.unwrap();
+        assert_eq!(content_type, Some("image/png"));
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, png);
+
+        let (content_type, mut reader) = sniff_content_type(&png[..], false).await.unwrap();
+        assert_eq!(content_type, None);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, png);
+    }
 }
@@ -364,8 +364,7 @@ mod tests {
     use tempfile::TempDir;
     use tinycloud_auth::{
         authorization::{make_invocation, InvocationOptions},
-        ipld_core::cid::Cid,
-        multihash_codetable::{Code, MultihashDigest},
+        codec,
         resolver::DID_METHODS,
         resource::{ResourceId, Service},
         siwe_recap::Ability,
@@ -390,7 +389,7 @@ mod tests {
         let _persisted = tempdir.keep();
         Ok(TinyCloud::new(
             db,
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?)
@@ -420,7 +419,7 @@ mod tests {
             None,
         );
 
-        let delegation = Cid::new_v1(0x55, Code::Blake3_256.digest(b"delegation"));
+        let delegation = codec::to_event_cid(b"delegation");
         let invocation = make_invocation(
             vec![(resource, vec!["tinycloud.kv/get".parse::<Ability>()?])],
             &delegation,
@@ -467,7 +466,7 @@ mod tests {
             })
             .collect::<Result<Vec<(ResourceId, Vec<Ability>)>>>()?;
 
-        let delegation = Cid::new_v1(0x55, Code::Blake3_256.digest(b"delegation"));
+        let delegation = codec::to_event_cid(b"delegation");
         let invocation = make_invocation(
             resources,
             &delegation,
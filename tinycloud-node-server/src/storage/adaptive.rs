@@ -0,0 +1,174 @@
+use super::file_system::TempFileStage;
+use core::pin::Pin;
+use futures::{
+    future::{poll_fn, Either as AsyncEither},
+    io::AsyncWrite,
+    ready,
+    task::{Context, Poll},
+    Future,
+};
+use rocket::data::ByteUnit;
+use std::io::{Error as IoError, Write};
+use tempfile::NamedTempFile;
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::storage::*;
+use tokio::task::JoinHandle;
+
+/// Stages uploads in memory until they grow past `threshold`, then moves
+/// them to a temporary file, so small values are staged as quickly as with
+/// `MemoryStaging` while large ones take no more than `threshold` of memory.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct AdaptiveStaging {
+    threshold: ByteUnit,
+}
+
+impl AdaptiveStaging {
+    pub fn new(threshold: ByteUnit) -> Self {
+        Self { threshold }
+    }
+
+    pub fn threshold(&self) -> ByteUnit {
+        self.threshold
+    }
+}
+
+#[async_trait]
+impl ImmutableStaging for AdaptiveStaging {
+    type Error = std::convert::Infallible;
+    type Writable = AdaptiveStage;
+    async fn get_staging_buffer(&self, _: &SpaceId) -> Result<Self::Writable, Self::Error> {
+        Ok(AdaptiveStage::Memory {
+            buffer: Vec::new(),
+            threshold: self.threshold.as_u64(),
+        })
+    }
+}
+
+#[async_trait]
+impl StorageConfig<AdaptiveStaging> for AdaptiveStaging {
+    type Error = std::convert::Infallible;
+    async fn open(&self) -> Result<AdaptiveStaging, Self::Error> {
+        Ok(*self)
+    }
+}
+
+/// A staging buffer of [`AdaptiveStaging`], in memory until the write that
+/// would take it past its threshold. The temporary file is created and the
+/// buffer written to it off the async runtime, while the stage is spilling.
+#[derive(Debug)]
+pub enum AdaptiveStage {
+    Memory { buffer: Vec<u8>, threshold: u64 },
+    Spilling(JoinHandle<Result<NamedTempFile, IoError>>),
+    Spilled(TempFileStage),
+}
+
+impl AdaptiveStage {
+    /// The staged bytes as the buffer of a file system or memory stage, for
+    /// the stores to persist as they would either.
+    pub async fn into_local(mut self) -> Result<AsyncEither<TempFileStage, Vec<u8>>, IoError> {
+        poll_fn(|cx| self.poll_spilled(cx)).await?;
+        Ok(match self {
+            Self::Memory { buffer, .. } => AsyncEither::Right(buffer),
+            Self::Spilled(file) => AsyncEither::Left(file),
+            Self::Spilling(_) => unreachable!("the stage finished spilling above"),
+        })
+    }
+
+    /// Wait for a spilling stage to have spilled.
+    fn poll_spilled(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        if let Self::Spilling(spill) = self {
+            let file = match Pin::new(spill).poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(spilled) => spilled.map_err(IoError::other)??,
+            };
+            *self = Self::Spilled(TempFileStage::new(file));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for AdaptiveStage {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.get_mut();
+        if let Self::Memory { buffer, threshold } = this {
+            if (buffer.len() + buf.len()) as u64 <= *threshold {
+                buffer.extend_from_slice(buf);
+                return Poll::Ready(Ok(buf.len()));
+            }
+            // at most `threshold` bytes, written once
+            let buffer = std::mem::take(buffer);
+            *this = Self::Spilling(tokio::task::spawn_blocking(move || {
+                let mut file = NamedTempFile::new()?;
+                file.write_all(&buffer)?;
+                Ok(file)
+            }));
+        }
+        ready!(this.poll_spilled(cx))?;
+        let Self::Spilled(file) = this else {
+            unreachable!("the stage spilled above")
+        };
+        Pin::new(file).poll_write(cx, buf)
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.get_mut();
+        ready!(this.poll_spilled(cx))?;
+        match this {
+            Self::Spilled(file) => Pin::new(file).poll_flush(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.get_mut();
+        ready!(this.poll_spilled(cx))?;
+        match this {
+            Self::Spilled(file) => Pin::new(file).poll_close(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::{
+        file_system::{FileSystemConfig, FileSystemStore},
+        LocalStaging,
+    };
+    use futures::io::AsyncWriteExt;
+    use tinycloud_core::storage::memory::MemoryStaging;
+
+    #[tokio::test]
+    async fn uploads_spill_to_disk_past_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: FileSystemStore = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        store.create(&space).await.unwrap();
+        let staging = LocalStaging::B(AdaptiveStaging::new(ByteUnit::Byte(64)));
+
+        for (data, spilled) in [(vec![1u8; 64], false), (vec![2u8; 200], true)] {
+            let mut stage = staging.stage(&space).await.unwrap();
+            // in several writes, so the spill happens part way through
+            for chunk in data.chunks(48) {
+                stage.write_all(chunk).await.unwrap();
+            }
+            let (hasher, buffer) = stage.into_inner();
+            let AsyncEither::Right(buffer) = buffer else {
+                panic!("expected an adaptive stage");
+            };
+            assert_eq!(matches!(buffer, AdaptiveStage::Spilled(_)), spilled);
+            let stage = HashBuffer::from_parts(hasher, AsyncEither::Right(buffer));
+            let hash = ImmutableWriteStore::<LocalStaging>::persist(&store, &space, stage)
+                .await
+                .unwrap();
+
+            let mut expected = MemoryStaging.stage(&space).await.unwrap();
+            expected.write_all(&data).await.unwrap();
+            assert_eq!(hash, expected.hash());
+            assert_eq!(store.read_to_vec(&space, &hash).await.unwrap(), Some(data));
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use azure_core::{error::Error as AzureError, StatusCode};
+use azure_storage::{ConnectionString, StorageCredentials};
+use azure_storage_blobs::prelude::{
+    BlobBlockType, BlobClient, BlockId, BlockList, ClientBuilder, ContainerClient,
+};
+use futures::{
+    future::Either as AsyncEither,
+    io::{AsyncRead, AsyncReadExt, Cursor},
+    stream::{IntoAsyncRead, StreamExt, TryStreamExt},
+};
+use rocket::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Error as IoError, ops::AddAssign};
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{hash::Hash, storage::*};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use super::{file_system, size::SpaceSizes};
+
+/// Connection string used to authenticate to the storage account. Without
+/// it, the node authenticates with the identity Azure assigns it.
+const CONNECTION_STRING_VAR: &str = "AZURE_STORAGE_CONNECTION_STRING";
+
+/// Blobs are read in ranges, and uploaded in blocks, of at most this many
+/// bytes.
+const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The content of a blob, fetched one range at a time as it is read.
+pub type BlobReader = IntoAsyncRead<ReceiverStream<Result<Vec<u8>, IoError>>>;
+
+#[derive(Clone)]
+pub struct AzureBlockStore {
+    pub container: ContainerClient,
+    sizes: SpaceSizes,
+}
+
+impl std::fmt::Debug for AzureBlockStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzureBlockStore")
+            .field("container", &self.container.container_name())
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct AzureBlockConfig {
+    pub container: String,
+    /// The storage account holding `container`. Required when authenticating
+    /// with a managed identity; a connection string names its own account.
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AzureStoreError {
+    #[error(transparent)]
+    Azure(#[from] AzureError),
+    #[error(transparent)]
+    Io(#[from] IoError),
+    #[error("no storage account is configured for the Azure block store")]
+    MissingAccount,
+}
+
+#[async_trait]
+impl StorageConfig<AzureBlockStore> for AzureBlockConfig {
+    type Error = AzureStoreError;
+    async fn open(&self) -> Result<AzureBlockStore, Self::Error> {
+        AzureBlockStore::new_(self).await
+    }
+}
+
+#[async_trait]
+impl StorageSetup for AzureBlockStore {
+    type Error = std::convert::Infallible;
+    async fn create(&self, space: &SpaceId) -> Result<(), Self::Error> {
+        self.sizes.init_size(space.clone()).await;
+        Ok(())
+    }
+}
+
+fn new_client(config: &AzureBlockConfig) -> Result<ContainerClient, AzureStoreError> {
+    let (account, credentials) = match std::env::var(CONNECTION_STRING_VAR) {
+        Ok(connection_string) => {
+            let connection_string = ConnectionString::new(&connection_string)?;
+            let account = config
+                .account
+                .clone()
+                .or_else(|| connection_string.account_name.map(str::to_string))
+                .ok_or(AzureStoreError::MissingAccount)?;
+            (account, connection_string.storage_credentials()?)
+        }
+        Err(_) => (
+            config
+                .account
+                .clone()
+                .ok_or(AzureStoreError::MissingAccount)?,
+            StorageCredentials::token_credential(azure_identity::create_credential()?),
+        ),
+    };
+    Ok(ClientBuilder::new(account, credentials).container_client(&config.container))
+}
+
+impl AzureBlockStore {
+    async fn new_(config: &AzureBlockConfig) -> Result<Self, AzureStoreError> {
+        let container = new_client(config)?;
+        let sizes = container
+            .list_blobs()
+            .into_stream()
+            // get the sum of all blobs in each page
+            .try_fold(HashMap::new(), |mut acc, page| async move {
+                // get the sum of all blobs per space in this particular page
+                for (space, blob_size) in page.blobs.blobs().filter_map(|blob| {
+                    let (o, _) = blob.name.rsplit_once('/')?;
+                    let space: SpaceId = o.parse().ok()?;
+                    if blob.properties.content_length > 0 {
+                        Some((space, blob.properties.content_length))
+                    } else {
+                        None
+                    }
+                }) {
+                    acc.entry(space).or_insert(0).add_assign(blob_size);
+                }
+                Ok(acc)
+            })
+            .await?
+            .into();
+        Ok(AzureBlockStore { container, sizes })
+    }
+
+    fn key(&self, space: &SpaceId, id: &Hash) -> String {
+        format!(
+            "{}/{}",
+            space,
+            base64::encode_config(id.as_ref(), base64::URL_SAFE)
+        )
+    }
+
+    fn blob(&self, space: &SpaceId, id: &Hash) -> BlobClient {
+        self.container.blob_client(self.key(space, id))
+    }
+
+    /// Upload the `size` bytes of `body` as the block `hash`, unless it is
+    /// already stored. Bodies larger than one chunk are uploaded as staged
+    /// blocks, committed together once all of them are.
+    async fn put<R>(
+        &self,
+        space: &SpaceId,
+        hash: &Hash,
+        size: u64,
+        mut body: R,
+    ) -> Result<(), AzureStoreError>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        if self.contains(space, hash).await? {
+            return Ok(());
+        }
+        let blob = self.blob(space, hash);
+        if size <= CHUNK_SIZE {
+            let mut bytes = Vec::with_capacity(size as usize);
+            body.read_to_end(&mut bytes).await?;
+            blob.put_block_blob(bytes).await?;
+        } else {
+            let mut blocks = Vec::new();
+            loop {
+                let mut chunk = Vec::with_capacity(CHUNK_SIZE as usize);
+                (&mut body).take(CHUNK_SIZE).read_to_end(&mut chunk).await?;
+                if chunk.is_empty() {
+                    break;
+                }
+                // block ids of a blob must all be the same length
+                let id = BlockId::new(format!("{:016x}", blocks.len()));
+                blob.put_block(id.clone(), chunk).await?;
+                blocks.push(BlobBlockType::new_uncommitted(id));
+            }
+            blob.put_block_list(BlockList { blocks }).await?;
+        }
+        self.increment_size(space, size).await;
+        Ok(())
+    }
+
+    /// Upload the staged temporary file at `path` as the block `hash`.
+    async fn put_file(
+        &self,
+        space: &SpaceId,
+        hash: &Hash,
+        path: &std::path::Path,
+    ) -> Result<(), AzureStoreError> {
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        self.put(space, hash, size, file.compat()).await
+    }
+
+    async fn increment_size(&self, space: &SpaceId, size: u64) {
+        self.sizes.increment_size(space, size).await;
+    }
+    async fn decrement_size(&self, space: &SpaceId, size: u64) {
+        self.sizes.decrement_size(space, size).await;
+    }
+}
+
+fn is_not_found(e: &AzureError) -> bool {
+    e.as_http_error()
+        .is_some_and(|e| e.status() == StatusCode::NotFound)
+}
+
+/// Read bytes `start..end` of `blob`, one ranged request of at most
+/// [`CHUNK_SIZE`] at a time. The next range is only requested once the
+/// previous one has been taken by the reader, and none are once the reader
+/// is dropped.
+fn ranged_reader(blob: BlobClient, start: u64, end: u64) -> BlobReader {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut offset = start;
+        while offset < end {
+            let next = end.min(offset + CHUNK_SIZE);
+            let chunk = get_range(&blob, offset, next).await.map_err(IoError::other);
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                break;
+            }
+            offset = next;
+        }
+    });
+    ReceiverStream::new(rx).into_async_read()
+}
+
+async fn get_range(blob: &BlobClient, start: u64, end: u64) -> Result<Vec<u8>, AzureError> {
+    let mut bytes = Vec::with_capacity((end - start) as usize);
+    let mut pages = blob
+        .get()
+        .range(start..end)
+        .chunk_size(end - start)
+        .into_stream();
+    while let Some(page) = pages.next().await {
+        bytes.extend_from_slice(&page?.data.collect().await?);
+    }
+    Ok(bytes)
+}
+
+#[async_trait]
+impl ImmutableReadStore for AzureBlockStore {
+    type Error = AzureError;
+    type Readable = BlobReader;
+    async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
+        match self.blob(space, id).get_properties().await {
+            Ok(_) => Ok(true),
+            Err(e) if is_not_found(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+    ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
+        let blob = self.blob(space, id);
+        let length = match blob.get_properties().await {
+            Ok(p) => p.blob.properties.content_length,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(Content::new(length, ranged_reader(blob, 0, length))))
+    }
+
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        let blob = self.blob(space, id);
+        let total = match blob.get_properties().await {
+            Ok(p) => p.blob.properties.content_length,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let start = start.min(total);
+        let end = end.unwrap_or(total).clamp(start, total);
+        Ok(Some(Ranged::positioned(
+            ranged_reader(blob, start, end),
+            start,
+            Some(end),
+            total,
+        )))
+    }
+}
+
+#[async_trait]
+impl StorageHealth for AzureBlockStore {
+    type Error = AzureError;
+    async fn health(&self) -> Result<(), Self::Error> {
+        self.container.get_properties().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ImmutableWriteStore<memory::MemoryStaging> for AzureBlockStore {
+    type Error = AzureStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<memory::MemoryStaging as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (mut h, f) = staged.into_inner();
+        let hash = h.finalize();
+        self.put(space, &hash, f.len() as u64, Cursor::new(f))
+            .await?;
+        Ok(hash)
+    }
+}
+
+#[async_trait]
+impl ImmutableWriteStore<file_system::TempFileSystemStage> for AzureBlockStore {
+    type Error = AzureStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<file_system::TempFileSystemStage as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (mut h, f) = staged.into_inner();
+        let hash = h.finalize();
+        let (_file, path) = f.into_inner();
+        self.put_file(space, &hash, &path).await?;
+        Ok(hash)
+    }
+}
+
+#[async_trait]
+impl ImmutableWriteStore<either::Either<file_system::TempFileSystemStage, memory::MemoryStaging>>
+    for AzureBlockStore
+{
+    type Error = AzureStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<either::Either<file_system::TempFileSystemStage, memory::MemoryStaging> as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (mut h, f) = staged.into_inner();
+        let hash = h.finalize();
+        match f {
+            AsyncEither::Left(t_file) => {
+                let (_file, path) = t_file.into_inner();
+                self.put_file(space, &hash, &path).await?;
+            }
+            AsyncEither::Right(b) => {
+                self.put(space, &hash, b.len() as u64, Cursor::new(b))
+                    .await?
+            }
+        };
+        Ok(hash)
+    }
+}
+
+#[async_trait]
+impl ImmutableWriteStore<super::LocalStaging> for AzureBlockStore {
+    type Error = AzureStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<super::LocalStaging as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        let local = match f {
+            AsyncEither::Left(local) => local,
+            AsyncEither::Right(adaptive) => adaptive.into_local().await?,
+        };
+        ImmutableWriteStore::<either::Either<file_system::TempFileSystemStage, memory::MemoryStaging>>::persist(
+            self,
+            space,
+            HashBuffer::from_parts(h, local),
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl ImmutableWriteStore<either::Either<super::LocalStaging, super::redis::RedisStaging>>
+    for AzureBlockStore
+{
+    type Error = AzureStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<
+            <either::Either<
+                super::LocalStaging,
+                super::redis::RedisStaging,
+            > as ImmutableStaging>::Writable,
+        >,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        match f {
+            AsyncEither::Left(local) => {
+                ImmutableWriteStore::<super::LocalStaging>::persist(
+                    self,
+                    space,
+                    HashBuffer::from_parts(h, local),
+                )
+                .await
+            }
+            AsyncEither::Right(stage) => {
+                let local = stage.into_local().await?;
+                ImmutableWriteStore::<
+                    either::Either<file_system::TempFileSystemStage, memory::MemoryStaging>,
+                >::persist(self, space, HashBuffer::from_parts(h, local))
+                .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ImmutableListStore for AzureBlockStore {
+    type Error = AzureError;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        let prefix = format!("{space}/");
+        self.container
+            .list_blobs()
+            .prefix(prefix.clone())
+            .into_stream()
+            .try_fold(Vec::new(), |mut acc, page| {
+                let prefix = &prefix;
+                async move {
+                    // blobs deeper than the space prefix are not blocks of it
+                    acc.extend(page.blobs.blobs().filter_map(|blob| {
+                        let name = blob.name.strip_prefix(prefix.as_str())?;
+                        let bytes = base64::decode_config(name, base64::URL_SAFE).ok()?;
+                        Hash::try_from(bytes).ok()
+                    }));
+                    Ok(acc)
+                }
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl ImmutableDeleteStore for AzureBlockStore {
+    type Error = AzureError;
+    async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error> {
+        let blob = self.blob(space, id);
+        let size = match blob.get_properties().await {
+            Ok(p) => p.blob.properties.content_length,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match blob.delete().await {
+            Ok(_) => {
+                self.decrement_size(space, size).await;
+                Ok(Some(()))
+            }
+            // deleted since its properties were read
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreSize for AzureBlockStore {
+    type Error = AzureError;
+    async fn total_size(&self, space: &SpaceId) -> Result<Option<u64>, Self::Error> {
+        Ok(self.sizes.get_size(space).await)
+    }
+}
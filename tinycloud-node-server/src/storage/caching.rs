@@ -0,0 +1,464 @@
+use futures::{
+    future::Either as AsyncEither,
+    io::{AsyncReadExt, Cursor},
+};
+use rocket::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{hash::Hash, storage::*};
+
+use crate::config::BlockCacheConfig;
+
+/// An in-memory LRU cache of small blocks in front of another store.
+///
+/// Blocks are addressed by content hash, so a cached block can only go stale
+/// by being deleted, which goes through [`CachingStore::remove`]. Without a
+/// cache every operation passes straight through to the inner store.
+#[derive(Debug, Clone)]
+pub struct CachingStore<S> {
+    inner: S,
+    cache: Option<Arc<BlockCache>>,
+}
+
+#[derive(Debug)]
+struct BlockCache {
+    max_entry_size: u64,
+    lru: Mutex<Lru>,
+}
+
+#[derive(Debug)]
+struct Lru {
+    entries: HashMap<(SpaceId, Hash), (Arc<[u8]>, u64)>,
+    // last use tick -> key, oldest first
+    order: BTreeMap<u64, (SpaceId, Hash)>,
+    tick: u64,
+    size: u64,
+    capacity: u64,
+    // bumped by every removal, so reads racing one do not cache its block
+    removals: u64,
+}
+
+impl Lru {
+    fn new(capacity: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            size: 0,
+            capacity,
+            removals: 0,
+        }
+    }
+
+    fn get(&mut self, key: &(SpaceId, Hash)) -> Option<Arc<[u8]>> {
+        self.tick += 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        *last_used = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: (SpaceId, Hash), value: Arc<[u8]>) {
+        if value.len() as u64 > self.capacity {
+            return;
+        }
+        self.remove(&key);
+        self.tick += 1;
+        self.size += value.len() as u64;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+        while self.size > self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    if let Some((value, _)) = self.entries.remove(&oldest) {
+                        self.size -= value.len() as u64;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Cache a block read when there had been `removals`, unless a block has
+    /// been removed since.
+    fn populate(&mut self, key: (SpaceId, Hash), value: Arc<[u8]>, removals: u64) {
+        if self.removals == removals {
+            self.insert(key, value);
+        }
+    }
+
+    fn remove(&mut self, key: &(SpaceId, Hash)) {
+        self.removals += 1;
+        if let Some((value, last_used)) = self.entries.remove(key) {
+            self.order.remove(&last_used);
+            self.size -= value.len() as u64;
+        }
+    }
+}
+
+impl<S> CachingStore<S> {
+    pub fn new(inner: S, config: &BlockCacheConfig) -> Self {
+        Self {
+            inner,
+            cache: config.enabled.then(|| {
+                Arc::new(BlockCache {
+                    max_entry_size: config.max_entry_size.as_u64(),
+                    lru: Mutex::new(Lru::new(config.capacity.as_u64())),
+                })
+            }),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn lru(&self) -> Option<std::sync::MutexGuard<'_, Lru>> {
+        self.cache.as_ref().map(|cache| {
+            cache
+                .lru
+                .lock()
+                .expect("block cache should not be poisoned")
+        })
+    }
+}
+
+impl<S> From<S> for CachingStore<S> {
+    /// Wrap `inner` without a cache.
+    fn from(inner: S) -> Self {
+        Self { inner, cache: None }
+    }
+}
+
+fn observe(result: &'static str) {
+    if crate::prometheus::enabled() {
+        crate::prometheus::BLOCK_CACHE_REQUESTS
+            .with_label_values(&[result])
+            .inc();
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableReadStore for CachingStore<S>
+where
+    S: ImmutableReadStore,
+{
+    type Error = VecReadError<S::Error>;
+    type Readable = AsyncEither<Cursor<Arc<[u8]>>, S::Readable>;
+    async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
+        if let Some(mut lru) = self.lru() {
+            if lru.get(&(space.clone(), *id)).is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(self.inner.contains(space, id).await?)
+    }
+
+    async fn read(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+    ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
+        let Some(cache) = &self.cache else {
+            return Ok(self.inner.read(space, id).await?.map(|content| {
+                let (len, reader) = content.into_inner();
+                Content::new(len, AsyncEither::Right(reader))
+            }));
+        };
+        let key = (space.clone(), *id);
+        if let Some(value) = self.lru().and_then(|mut lru| lru.get(&key)) {
+            observe("hit");
+            return Ok(Some(Content::new(
+                value.len() as u64,
+                AsyncEither::Left(Cursor::new(value)),
+            )));
+        }
+        observe("miss");
+        let removals = self.lru().map(|lru| lru.removals);
+        let Some(content) = self.inner.read(space, id).await? else {
+            return Ok(None);
+        };
+        let (len, reader) = content.into_inner();
+        if len > cache.max_entry_size {
+            return Ok(Some(Content::new(len, AsyncEither::Right(reader))));
+        }
+        let mut value = Vec::with_capacity(len as usize);
+        Box::pin(reader)
+            .read_to_end(&mut value)
+            .await
+            .map_err(VecReadError::Read)?;
+        let value: Arc<[u8]> = value.into();
+        if let (Some(mut lru), Some(removals)) = (self.lru(), removals) {
+            lru.populate(key, value.clone(), removals);
+        }
+        Ok(Some(Content::new(
+            value.len() as u64,
+            AsyncEither::Left(Cursor::new(value)),
+        )))
+    }
+
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        // ranges are served from the cache but never populate it
+        if let Some(value) = self
+            .lru()
+            .and_then(|mut lru| lru.get(&(space.clone(), *id)))
+        {
+            observe("hit");
+            let total = value.len() as u64;
+            return Ok(Some(Ranged::skipping(
+                AsyncEither::Left(Cursor::new(value)),
+                start,
+                end,
+                total,
+            )));
+        }
+        Ok(self
+            .inner
+            .read_range(space, id, start, end)
+            .await?
+            .map(|content| {
+                let (len, ranged) = content.into_inner();
+                Content::new(len, ranged.map(AsyncEither::Right))
+            }))
+    }
+}
+
+#[async_trait]
+impl<S, W> ImmutableWriteStore<W> for CachingStore<S>
+where
+    S: ImmutableWriteStore<W>,
+    W: ImmutableStaging,
+    W::Writable: 'static,
+{
+    type Error = S::Error;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<W::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        self.inner.persist(space, staged).await
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableDeleteStore for CachingStore<S>
+where
+    S: ImmutableDeleteStore,
+{
+    type Error = S::Error;
+    async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error> {
+        let key = (space.clone(), *id);
+        if let Some(mut lru) = self.lru() {
+            lru.remove(&key);
+        }
+        let removed = self.inner.remove(space, id).await;
+        // again, for reads which found the block before it was removed
+        if let Some(mut lru) = self.lru() {
+            lru.remove(&key);
+        }
+        removed
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableListStore for CachingStore<S>
+where
+    S: ImmutableListStore,
+{
+    type Error = S::Error;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        self.inner.list(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StorageSetup for CachingStore<S>
+where
+    S: StorageSetup + Sync,
+{
+    type Error = S::Error;
+    async fn create(&self, space: &SpaceId) -> Result<(), Self::Error> {
+        self.inner.create(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StoreSize for CachingStore<S>
+where
+    S: StoreSize,
+{
+    type Error = S::Error;
+    async fn total_size(&self, space: &SpaceId) -> Result<Option<u64>, Self::Error> {
+        self.inner.total_size(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StorageHealth for CachingStore<S>
+where
+    S: StorageHealth,
+{
+    type Error = S::Error;
+    async fn health(&self) -> Result<(), Self::Error> {
+        self.inner.health().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::file_system::{
+        FileSystemConfig, FileSystemStore, FileSystemStoreError, TempFileSystemStage,
+    };
+    use rocket::data::ByteUnit;
+    use tokio::sync::Semaphore;
+    use tokio_util::compat::Compat;
+
+    async fn put(store: &FileSystemStore, space: &SpaceId, data: &[u8]) -> Hash {
+        let mut stage = TempFileSystemStage.stage(space).await.unwrap();
+        futures::io::copy(&mut &data[..], &mut stage).await.unwrap();
+        ImmutableWriteStore::<TempFileSystemStage>::persist(store, space, stage)
+            .await
+            .unwrap()
+    }
+
+    async fn read<S: ImmutableReadStore>(
+        store: &CachingStore<S>,
+        space: &SpaceId,
+        id: &Hash,
+    ) -> Option<Vec<u8>> {
+        store.read_to_vec(space, id).await.unwrap()
+    }
+
+    /// A file system store whose reads, once they have looked for their
+    /// block, add a permit to `found` and wait for a permit of `resume`.
+    struct PausedReads {
+        inner: FileSystemStore,
+        found: Arc<Semaphore>,
+        resume: Arc<Semaphore>,
+    }
+
+    #[async_trait]
+    impl ImmutableReadStore for PausedReads {
+        type Error = FileSystemStoreError;
+        type Readable = Compat<tokio::fs::File>;
+        async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
+            self.inner.contains(space, id).await
+        }
+
+        async fn read(
+            &self,
+            space: &SpaceId,
+            id: &Hash,
+        ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
+            let content = self.inner.read(space, id).await?;
+            self.found.add_permits(1);
+            self.resume.acquire().await.unwrap().forget();
+            Ok(content)
+        }
+
+        async fn read_range(
+            &self,
+            space: &SpaceId,
+            id: &Hash,
+            start: u64,
+            end: Option<u64>,
+        ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+            self.inner.read_range(space, id, start, end).await
+        }
+    }
+
+    #[async_trait]
+    impl ImmutableDeleteStore for PausedReads {
+        type Error = FileSystemStoreError;
+        async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error> {
+            self.inner.remove(space, id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_racing_a_delete_do_not_cache_the_deleted_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let inner = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        inner.create(&space).await.unwrap();
+        let a = put(&inner, &space, b"hello!").await;
+        let (found, resume) = (Arc::new(Semaphore::new(0)), Arc::new(Semaphore::new(0)));
+        let store = CachingStore::new(
+            PausedReads {
+                inner,
+                found: found.clone(),
+                resume: resume.clone(),
+            },
+            &BlockCacheConfig {
+                enabled: true,
+                capacity: ByteUnit::Byte(10),
+                max_entry_size: ByteUnit::Byte(10),
+            },
+        );
+
+        // the read finds the block, then the block is deleted before the
+        // read caches it
+        let (read_a, removed) = tokio::join!(read(&store, &space, &a), async {
+            found.acquire().await.unwrap().forget();
+            let removed = store.remove(&space, &a).await.unwrap();
+            resume.add_permits(1);
+            removed
+        });
+        assert_eq!(read_a.as_deref(), Some(&b"hello!"[..]));
+        assert_eq!(removed, Some(()));
+
+        resume.add_permits(1);
+        assert_eq!(read(&store, &space, &a).await, None);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_blocks_are_evicted_and_deletes_invalidate() {
+        let dir = tempfile::tempdir().unwrap();
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let inner = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        inner.create(&space).await.unwrap();
+        let a = put(&inner, &space, b"hello!").await;
+        let b = put(&inner, &space, b"world!").await;
+        let store = CachingStore::new(
+            inner,
+            &BlockCacheConfig {
+                enabled: true,
+                capacity: ByteUnit::Byte(10),
+                max_entry_size: ByteUnit::Byte(10),
+            },
+        );
+
+        // a cached block is served even once the backend has lost it
+        assert_eq!(
+            read(&store, &space, &a).await.as_deref(),
+            Some(&b"hello!"[..])
+        );
+        store.inner().remove(&space, &a).await.unwrap();
+        assert_eq!(
+            read(&store, &space, &a).await.as_deref(),
+            Some(&b"hello!"[..])
+        );
+
+        // caching b does not leave room for a
+        assert_eq!(
+            read(&store, &space, &b).await.as_deref(),
+            Some(&b"world!"[..])
+        );
+        assert_eq!(read(&store, &space, &a).await, None);
+
+        // deleting through the cache drops the cached copy
+        assert_eq!(store.remove(&space, &b).await.unwrap(), Some(()));
+        assert_eq!(read(&store, &space, &b).await, None);
+    }
+}
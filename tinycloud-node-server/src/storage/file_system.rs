@@ -1,7 +1,7 @@
 use super::size::SpaceSizes;
 use core::pin::Pin;
 use futures::{
-    future::{Either as AsyncEither, TryFutureExt},
+    future::Either as AsyncEither,
     io::{AsyncWrite, AsyncWriteExt},
     stream::TryStreamExt,
     task::{Context, Poll},
@@ -10,35 +10,96 @@ use pin_project::pin_project;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    io::{Error as IoError, ErrorKind},
-    path::{Path, PathBuf},
+    io::{Error as IoError, ErrorKind, SeekFrom},
+    path::{Component, Path, PathBuf},
 };
 use tempfile::{NamedTempFile, PathPersistError};
 use tinycloud_auth::{resource::SpaceId, ssi::dids::DIDBuf};
 use tinycloud_core::{hash::Hash, storage::*};
-use tokio::fs::{create_dir_all, metadata, remove_file, File};
+use tokio::{
+    fs::{create_dir_all, metadata, remove_file, File},
+    io::AsyncSeekExt,
+};
 use tokio_stream::wrappers::ReadDirStream;
 
 use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
 
+/// Characters of a block's name used to name each level of shard
+/// directories.
+const SHARD_WIDTH: usize = 2;
+
+/// File in the store's directory recording the shard depth its blocks are
+/// laid out at.
+const SHARD_DEPTH_FILE: &str = ".shard_depth";
+
 #[derive(Debug, Clone)]
 pub struct FileSystemStore {
     path: PathBuf,
+    shard_depth: u8,
     sizes: SpaceSizes,
 }
 
 impl FileSystemStore {
-    async fn new(path: PathBuf) -> Result<Self, IoError> {
+    async fn new(path: PathBuf, shard_depth: u8) -> Result<Self, IoError> {
         // get the size of the directory
         let sizes = store_sizes(&path).await?.into();
-        Ok(Self { path, sizes })
+        Ok(Self {
+            path,
+            shard_depth,
+            sizes,
+        })
+    }
+
+    fn space_path(&self, space: &SpaceId) -> Result<PathBuf, IoError> {
+        self.space_dir(space.suffix(), space.name().as_str())
+    }
+
+    /// The directory of the space with DID `suffix` and `name`, one directory
+    /// level each. Parsing a `SpaceId` already rules out separators and `..`,
+    /// but a part that is not exactly one plain path component is refused
+    /// here too rather than resolved outside the store.
+    fn space_dir(&self, suffix: &str, name: &str) -> Result<PathBuf, IoError> {
+        let mut path = self.path.clone();
+        for part in [suffix, name] {
+            let mut components = Path::new(part).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(component)), None) if component == part => {
+                    path.push(component)
+                }
+                _ => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidInput,
+                        format!("{part:?} is not a valid space directory"),
+                    ))
+                }
+            }
+        }
+        Ok(path)
     }
 
-    fn get_path(&self, space: &SpaceId, mh: &Hash) -> PathBuf {
-        self.path
-            .join(space.suffix())
-            .join(space.name().as_str())
-            .join(base64::encode_config(mh.as_ref(), base64::URL_SAFE))
+    fn get_path(&self, space: &SpaceId, mh: &Hash) -> Result<PathBuf, IoError> {
+        let name = base64::encode_config(mh.as_ref(), base64::URL_SAFE);
+        let mut path = self.space_path(space)?;
+        for shard in name
+            .as_bytes()
+            .chunks(SHARD_WIDTH)
+            .take(self.shard_depth.into())
+        {
+            // base64 names are ASCII, so every chunk is a whole string
+            path.push(std::str::from_utf8(shard).unwrap_or_default());
+        }
+        Ok(path.join(name))
+    }
+
+    /// The path a new block is written to, creating its shard directories.
+    async fn create_path(&self, space: &SpaceId, mh: &Hash) -> Result<PathBuf, IoError> {
+        let path = self.get_path(space, mh)?;
+        if self.shard_depth > 0 {
+            if let Some(dir) = path.parent() {
+                create_dir_all(dir).await?;
+            }
+        }
+        Ok(path)
     }
 
     async fn increment_size(&self, space: &SpaceId, size: u64) {
@@ -52,17 +113,32 @@ impl FileSystemStore {
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct FileSystemConfig {
     path: PathBuf,
+    /// Levels of subdirectories blocks are sharded into, each named by the
+    /// next two characters of the block's name. With the default of 0 every
+    /// block of a space is in one directory. The depth is recorded in the
+    /// store, which then refuses to open at any other, as the blocks already
+    /// in it are not moved.
+    #[serde(default)]
+    shard_depth: u8,
 }
 
 impl FileSystemConfig {
     pub fn new<P: AsRef<Path>>(p: P) -> Self {
         Self {
             path: p.as_ref().into(),
+            shard_depth: 0,
         }
     }
+    pub fn with_shard_depth(mut self, shard_depth: u8) -> Self {
+        self.shard_depth = shard_depth;
+        self
+    }
     pub fn path(&self) -> &Path {
         &self.path
     }
+    pub fn shard_depth(&self) -> u8 {
+        self.shard_depth
+    }
 }
 
 #[async_trait]
@@ -72,7 +148,8 @@ impl StorageConfig<FileSystemStore> for FileSystemConfig {
         if !self.path.is_dir() {
             create_dir_all(&self.path).await?;
         }
-        Ok(FileSystemStore::new(self.path.clone()).await?)
+        check_shard_depth(&self.path, self.shard_depth).await?;
+        Ok(FileSystemStore::new(self.path.clone(), self.shard_depth).await?)
     }
 }
 
@@ -80,7 +157,7 @@ impl StorageConfig<FileSystemStore> for FileSystemConfig {
 impl StorageSetup for FileSystemStore {
     type Error = IoError;
     async fn create(&self, space: &SpaceId) -> Result<(), Self::Error> {
-        let path = self.path.join(space.suffix()).join(space.name().as_str());
+        let path = self.space_path(space)?;
         if !path.is_dir() {
             create_dir_all(&path).await?;
         }
@@ -93,6 +170,7 @@ impl Default for FileSystemConfig {
     fn default() -> Self {
         Self {
             path: PathBuf::new(),
+            shard_depth: 0,
         }
     }
 }
@@ -110,19 +188,35 @@ impl ImmutableReadStore for FileSystemStore {
     type Error = FileSystemStoreError;
     type Readable = Compat<File>;
     async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
-        Ok(self.get_path(space, id).exists())
+        Ok(self.get_path(space, id)?.exists())
     }
     async fn read(
         &self,
         space: &SpaceId,
         id: &Hash,
     ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
-        match File::open(self.get_path(space, id)).await {
+        match File::open(self.get_path(space, id)?).await {
             Ok(f) => Ok(Some(Content::new(f.metadata().await?.len(), f.compat()))),
             Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        let mut f = match File::open(self.get_path(space, id)?).await {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let total = f.metadata().await?.len();
+        f.seek(SeekFrom::Start(start.min(total))).await?;
+        Ok(Some(Ranged::positioned(f.compat(), start, end, total)))
+    }
 }
 
 #[async_trait]
@@ -133,6 +227,63 @@ impl StoreSize for FileSystemStore {
     }
 }
 
+#[async_trait]
+impl StorageHealth for FileSystemStore {
+    type Error = FileSystemStoreError;
+    async fn health(&self) -> Result<(), Self::Error> {
+        // creating (and dropping) a temp file proves the base path is writable
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || NamedTempFile::new_in(path))
+            .await
+            .map_err(IoError::other)??;
+        Ok(())
+    }
+}
+
+/// Check the store at `path` lays its blocks out at `shard_depth`, as none
+/// could be found at any other, and record it. Only stores holding spaces
+/// are checked, and those from before the depth was recorded are unsharded.
+async fn check_shard_depth(path: &Path, shard_depth: u8) -> Result<(), IoError> {
+    let file = path.join(SHARD_DEPTH_FILE);
+    let recorded = match tokio::fs::read_to_string(&file).await {
+        Ok(recorded) => Some(recorded.trim().parse::<u8>().map_err(|_| {
+            IoError::new(
+                ErrorKind::InvalidData,
+                format!("{} does not hold a shard depth", file.display()),
+            )
+        })?),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if has_directories(path).await? {
+        let depth = recorded.unwrap_or(0);
+        if depth != shard_depth {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "the block store at {} holds its blocks at shard depth {depth}, \
+                     but is configured with a shard depth of {shard_depth}",
+                    path.display()
+                ),
+            ));
+        }
+    }
+    if recorded != Some(shard_depth) {
+        tokio::fs::write(&file, shard_depth.to_string()).await?;
+    }
+    Ok(())
+}
+
+async fn has_directories(path: &Path) -> Result<bool, IoError> {
+    let mut entries = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 // get the sum size of all files in this directory (recurse into subdirectories with space ID names)
 async fn store_sizes<P: AsRef<Path>>(path: &P) -> Result<HashMap<SpaceId, u64>, IoError> {
     ReadDirStream::new(tokio::fs::read_dir(path).await?)
@@ -168,16 +319,49 @@ async fn store_sizes<P: AsRef<Path>>(path: &P) -> Result<HashMap<SpaceId, u64>,
         .await
 }
 
+// get the sum size of every regular file under this directory, at any depth.
+// Counting more than the blocks at the configured shard depth can only
+// over-report a space's usage, where counting less would let it exceed its
+// quota.
 async fn space_size<P: AsRef<Path>>(path: &P) -> Result<u64, IoError> {
-    // get the sum size of all files in this directory (do not recurse into subdirectories)
-    ReadDirStream::new(tokio::fs::read_dir(path).await?)
-        .try_fold(0, |acc, entry| async move {
-            entry
-                .metadata()
-                .map_ok(|m| if m.is_dir() { acc } else { acc + m.len() })
-                .await
-        })
-        .await
+    let mut size = 0;
+    let mut dirs = vec![path.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            // symlinks are not followed, so a link cannot count a file twice
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                size += entry.metadata().await?.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+/// The names of the files `shard_depth` levels of directories below `dir`,
+/// which is where a space's blocks are.
+async fn block_files(dir: PathBuf, shard_depth: u8) -> Result<Vec<String>, IoError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(dir, shard_depth)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            match (entry.file_type().await?.is_dir(), depth) {
+                (true, 0) => {}
+                (true, depth) => dirs.push((entry.path(), depth - 1)),
+                (false, 0) => {
+                    if let Ok(name) = entry.file_name().into_string() {
+                        files.push(name);
+                    }
+                }
+                (false, _) => {}
+            }
+        }
+    }
+    Ok(files)
 }
 
 #[derive(Default, Debug, Clone, Hash, PartialEq, Eq)]
@@ -222,7 +406,10 @@ impl ImmutableStaging for TempFileSystemStage {
     type Error = FileSystemStoreError;
     type Writable = TempFileStage;
     async fn get_staging_buffer(&self, _: &SpaceId) -> Result<Self::Writable, Self::Error> {
-        Ok(TempFileStage::new(NamedTempFile::new()?))
+        let file = tokio::task::spawn_blocking(NamedTempFile::new)
+            .await
+            .map_err(IoError::other)??;
+        Ok(TempFileStage::new(file))
     }
 }
 
@@ -240,7 +427,7 @@ impl ImmutableWriteStore<TempFileSystemStage> for FileSystemStore {
         if !self.contains(space, &hash).await? {
             let size = f.size().await?;
             let (_, path) = f.into_inner();
-            path.persist(self.get_path(space, &hash))?;
+            path.persist(self.create_path(space, &hash).await?)?;
             self.increment_size(space, size).await;
         }
         Ok(hash)
@@ -258,7 +445,7 @@ impl ImmutableWriteStore<memory::MemoryStaging> for FileSystemStore {
         let (mut h, v) = staged.into_inner();
         let hash = h.finalize();
         if !self.contains(space, &hash).await? {
-            let file = File::create(self.get_path(space, &hash)).await?;
+            let file = File::create(self.create_path(space, &hash).await?).await?;
             let size = v.len() as u64;
             let mut writer = futures::io::BufWriter::new(file.compat());
             writer.write_all(&v).await?;
@@ -287,11 +474,11 @@ impl ImmutableWriteStore<either::Either<TempFileSystemStage, memory::MemoryStagi
                 AsyncEither::Left(t_file) => {
                     let size = t_file.size().await?;
                     let (_, path) = t_file.into_inner();
-                    path.persist(self.get_path(space, &hash))?;
+                    path.persist(self.create_path(space, &hash).await?)?;
                     self.increment_size(space, size).await;
                 }
                 AsyncEither::Right(v) => {
-                    let file = File::create(self.get_path(space, &hash)).await?;
+                    let file = File::create(self.create_path(space, &hash).await?).await?;
                     let size = v.len() as u64;
                     let mut writer = futures::io::BufWriter::new(file.compat());
                     writer.write_all(&v).await?;
@@ -304,11 +491,72 @@ impl ImmutableWriteStore<either::Either<TempFileSystemStage, memory::MemoryStagi
     }
 }
 
+#[async_trait]
+impl ImmutableWriteStore<super::LocalStaging> for FileSystemStore {
+    type Error = FileSystemStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<super::LocalStaging as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        let local = match f {
+            AsyncEither::Left(local) => local,
+            AsyncEither::Right(adaptive) => adaptive.into_local().await?,
+        };
+        ImmutableWriteStore::<either::Either<TempFileSystemStage, memory::MemoryStaging>>::persist(
+            self,
+            space,
+            HashBuffer::from_parts(h, local),
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl ImmutableWriteStore<either::Either<super::LocalStaging, super::redis::RedisStaging>>
+    for FileSystemStore
+{
+    type Error = FileSystemStoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<
+            <either::Either<
+                super::LocalStaging,
+                super::redis::RedisStaging,
+            > as ImmutableStaging>::Writable,
+        >,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        match f {
+            AsyncEither::Left(local) => {
+                ImmutableWriteStore::<super::LocalStaging>::persist(
+                    self,
+                    space,
+                    HashBuffer::from_parts(h, local),
+                )
+                .await
+            }
+            AsyncEither::Right(stage) => {
+                let local = stage.into_local().await?;
+                ImmutableWriteStore::<either::Either<TempFileSystemStage, memory::MemoryStaging>>::persist(
+                    self,
+                    space,
+                    HashBuffer::from_parts(h, local),
+                )
+                .await
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl ImmutableDeleteStore for FileSystemStore {
     type Error = FileSystemStoreError;
     async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error> {
-        let path = self.get_path(space, id);
+        let path = self.get_path(space, id)?;
         let size = match metadata(&path).await {
             Ok(m) => m.len(),
             Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
@@ -325,6 +573,27 @@ impl ImmutableDeleteStore for FileSystemStore {
     }
 }
 
+#[async_trait]
+impl ImmutableListStore for FileSystemStore {
+    type Error = FileSystemStoreError;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        let files = match block_files(self.space_path(space)?, self.shard_depth).await {
+            Ok(files) => files,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        // blocks are named by their URL-safe base64 hash; skip anything else
+        Ok(files
+            .into_iter()
+            .filter_map(|name| {
+                base64::decode_config(name, base64::URL_SAFE)
+                    .ok()
+                    .and_then(|bytes| Hash::try_from(bytes).ok())
+            })
+            .collect())
+    }
+}
+
 #[async_trait]
 impl StorageConfig<TempFileSystemStage> for TempFileSystemStage {
     type Error = std::convert::Infallible;
@@ -377,6 +646,24 @@ mod test {
             store.read_to_vec(&space_id, &hash).await.unwrap().unwrap(),
             data
         );
+
+        for (start, end, expected) in [
+            (6, Some(9), &b"wor"[..]),
+            (6, None, &b"world"[..]),
+            (6, Some(100), &b"world"[..]),
+            (100, None, &b""[..]),
+        ] {
+            let mut range = store
+                .read_range(&space_id, &hash, start, end)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(range.len(), expected.len() as u64);
+            let mut buf = Vec::new();
+            range.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, expected);
+        }
+
         assert_eq!(store.remove(&space_id, &hash).await.unwrap(), Some(()));
         assert_eq!(store.remove(&space_id, &hash).await.unwrap(), None);
         assert!(!store.contains(&space_id, &hash).await.unwrap());
@@ -386,4 +673,150 @@ mod test {
             None
         );
     }
+
+    #[tokio::test]
+    async fn sharded_blocks_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = FileSystemConfig::new(dir.path()).with_shard_depth(2);
+        let store = cfg.open().await.unwrap();
+        let space_id: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        store.create(&space_id).await.unwrap();
+
+        let mut hashes = Vec::new();
+        for data in [&b"hello world"[..], &b"goodbye world"[..]] {
+            let mut stage = memory::MemoryStaging.stage(&space_id).await.unwrap();
+            futures::io::copy(&mut &data[..], &mut stage).await.unwrap();
+            let hash =
+                ImmutableWriteStore::<memory::MemoryStaging>::persist(&store, &space_id, stage)
+                    .await
+                    .unwrap();
+            assert_eq!(
+                store.read_to_vec(&space_id, &hash).await.unwrap().unwrap(),
+                data
+            );
+            hashes.push(hash);
+        }
+
+        // blocks are two levels of shard directories below the space
+        let name = base64::encode_config(hashes[0].as_ref(), base64::URL_SAFE);
+        assert!(store
+            .space_path(&space_id)
+            .join(&name[..2])
+            .join(&name[2..4])
+            .join(&name)
+            .is_file());
+        let mut listed = store.list(&space_id).await.unwrap();
+        listed.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        hashes.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        assert_eq!(listed, hashes);
+
+        // sizes are recovered from the sharded layout when reopened
+        let reopened = cfg.open().await.unwrap();
+        assert_eq!(reopened.total_size(&space_id).await.unwrap(), Some(24));
+
+        assert_eq!(store.remove(&space_id, &hashes[0]).await.unwrap(), Some(()));
+        assert!(!store.contains(&space_id, &hashes[0]).await.unwrap());
+        assert!(store.contains(&space_id, &hashes[1]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stores_refuse_to_open_at_another_shard_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let space_id: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        let store = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        store.create(&space_id).await.unwrap();
+        let mut stage = memory::MemoryStaging.stage(&space_id).await.unwrap();
+        futures::io::copy(&mut &b"hello world"[..], &mut stage)
+            .await
+            .unwrap();
+        let hash = ImmutableWriteStore::<memory::MemoryStaging>::persist(&store, &space_id, stage)
+            .await
+            .unwrap();
+
+        let err = FileSystemConfig::new(dir.path())
+            .with_shard_depth(2)
+            .open()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // the depth it was created at still finds its blocks
+        let reopened = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        assert!(reopened.contains(&space_id, &hash).await.unwrap());
+
+        // as do stores from before the depth was recorded, which are unsharded
+        std::fs::remove_file(dir.path().join(SHARD_DEPTH_FILE)).unwrap();
+        assert!(FileSystemConfig::new(dir.path())
+            .with_shard_depth(1)
+            .open()
+            .await
+            .is_err());
+        let reopened = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        assert!(reopened.contains(&space_id, &hash).await.unwrap());
+
+        // an empty store takes the depth it is opened at, until it holds spaces
+        let sharded = tempfile::tempdir().unwrap();
+        FileSystemConfig::new(sharded.path()).open().await.unwrap();
+        let cfg = FileSystemConfig::new(sharded.path()).with_shard_depth(2);
+        cfg.open().await.unwrap().create(&space_id).await.unwrap();
+        cfg.open().await.unwrap();
+        assert!(FileSystemConfig::new(sharded.path()).open().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn space_sizes_count_nested_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = FileSystemConfig::new(dir.path());
+        let space_id: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        cfg.open().await.unwrap().create(&space_id).await.unwrap();
+
+        let space_dir = dir
+            .path()
+            .join(space_id.suffix())
+            .join(space_id.name().as_str());
+        std::fs::write(space_dir.join("flat"), b"12345").unwrap();
+        std::fs::create_dir_all(space_dir.join("a").join("b")).unwrap();
+        std::fs::write(space_dir.join("a").join("b").join("nested"), b"123").unwrap();
+
+        let store = cfg.open().await.unwrap();
+        assert_eq!(store.total_size(&space_id).await.unwrap(), Some(8));
+    }
+
+    #[tokio::test]
+    async fn space_directories_stay_within_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileSystemConfig::new(dir.path().join("store"))
+            .open()
+            .await
+            .unwrap();
+        let space_id: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        assert_eq!(
+            store.space_path(&space_id).unwrap(),
+            dir.path().join("store").join("key:test").join("default")
+        );
+
+        for (suffix, name) in [
+            ("..", "store"),
+            ("key:test", ".."),
+            ("../outside", "default"),
+            ("key:test", "../../outside"),
+            ("/tmp", "default"),
+            ("key:test", "a/b"),
+            ("key:test", "."),
+            ("", "default"),
+        ] {
+            let err = store.space_dir(suffix, name).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{suffix:?} {name:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn health_requires_a_writable_base_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        assert!(store.health().await.is_ok());
+
+        std::fs::remove_dir_all(dir.path()).unwrap();
+        assert!(store.health().await.is_err());
+    }
 }
@@ -0,0 +1,259 @@
+use rocket::async_trait;
+use std::time::Instant;
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::{
+    hash::Hash,
+    storage::{either::Either, *},
+};
+
+#[cfg(feature = "azure")]
+use super::azure::AzureBlockStore;
+use super::{file_system::FileSystemStore, s3::S3BlockStore};
+
+/// The backend a block store's operations are labelled with in the storage
+/// metrics.
+pub trait StorageBackend {
+    fn backend(&self) -> &'static str;
+}
+
+impl StorageBackend for S3BlockStore {
+    fn backend(&self) -> &'static str {
+        "s3"
+    }
+}
+
+#[cfg(feature = "azure")]
+impl StorageBackend for AzureBlockStore {
+    fn backend(&self) -> &'static str {
+        "azure"
+    }
+}
+
+impl StorageBackend for FileSystemStore {
+    fn backend(&self) -> &'static str {
+        "fs"
+    }
+}
+
+impl<A, B> StorageBackend for Either<A, B>
+where
+    A: StorageBackend,
+    B: StorageBackend,
+{
+    fn backend(&self) -> &'static str {
+        match self {
+            Either::A(a) => a.backend(),
+            Either::B(b) => b.backend(),
+        }
+    }
+}
+
+/// Records every block read, write and delete of the store it wraps: how
+/// long it took, whether it found, stored or removed the block, and the bytes
+/// it moved, labelled with the backend that served it.
+///
+/// Reads are timed until their content can be read, not until it has been.
+#[derive(Debug, Clone)]
+pub struct MeteredStore<S> {
+    inner: S,
+}
+
+impl<S> MeteredStore<S> {
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S> From<S> for MeteredStore<S> {
+    fn from(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: StorageBackend> MeteredStore<S> {
+    /// Record an operation started at `started`, which moved `bytes` if it
+    /// found the block.
+    fn observe<T, E>(
+        &self,
+        operation: &'static str,
+        started: Instant,
+        result: &Result<Option<T>, E>,
+        bytes: impl FnOnce(&T) -> u64,
+    ) {
+        let (outcome, bytes) = match result {
+            Ok(Some(value)) => ("ok", bytes(value)),
+            Ok(None) => ("not_found", 0),
+            Err(_) => ("error", 0),
+        };
+        crate::prometheus::observe_storage(
+            self.inner.backend(),
+            operation,
+            outcome,
+            bytes,
+            started.elapsed(),
+        );
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableReadStore for MeteredStore<S>
+where
+    S: ImmutableReadStore + StorageBackend,
+{
+    type Error = S::Error;
+    type Readable = S::Readable;
+    async fn contains(&self, space: &SpaceId, id: &Hash) -> Result<bool, Self::Error> {
+        self.inner.contains(space, id).await
+    }
+
+    async fn read(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+    ) -> Result<Option<Content<Self::Readable>>, Self::Error> {
+        let started = Instant::now();
+        let result = self.inner.read(space, id).await;
+        self.observe("read", started, &result, Content::len);
+        result
+    }
+
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        let started = Instant::now();
+        let result = self.inner.read_range(space, id, start, end).await;
+        self.observe("read", started, &result, Content::len);
+        result
+    }
+}
+
+#[async_trait]
+impl<S, W> ImmutableWriteStore<W> for MeteredStore<S>
+where
+    S: ImmutableWriteStore<W> + StorageBackend,
+    W: ImmutableStaging,
+    W::Writable: 'static,
+{
+    type Error = S::Error;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<W::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let bytes = staged.hasher().count();
+        let started = Instant::now();
+        let result = self.inner.persist(space, staged).await;
+        self.observe("write", started, &result.as_ref().map(Some), |_| bytes);
+        result
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableDeleteStore for MeteredStore<S>
+where
+    S: ImmutableDeleteStore + StorageBackend,
+{
+    type Error = S::Error;
+    async fn remove(&self, space: &SpaceId, id: &Hash) -> Result<Option<()>, Self::Error> {
+        let started = Instant::now();
+        let result = self.inner.remove(space, id).await;
+        self.observe("delete", started, &result, |_| 0);
+        result
+    }
+}
+
+#[async_trait]
+impl<S> ImmutableListStore for MeteredStore<S>
+where
+    S: ImmutableListStore,
+{
+    type Error = S::Error;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        self.inner.list(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StorageSetup for MeteredStore<S>
+where
+    S: StorageSetup + Sync,
+{
+    type Error = S::Error;
+    async fn create(&self, space: &SpaceId) -> Result<(), Self::Error> {
+        self.inner.create(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StoreSize for MeteredStore<S>
+where
+    S: StoreSize,
+{
+    type Error = S::Error;
+    async fn total_size(&self, space: &SpaceId) -> Result<Option<u64>, Self::Error> {
+        self.inner.total_size(space).await
+    }
+}
+
+#[async_trait]
+impl<S> StorageHealth for MeteredStore<S>
+where
+    S: StorageHealth,
+{
+    type Error = S::Error;
+    async fn health(&self) -> Result<(), Self::Error> {
+        self.inner.health().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::file_system::FileSystemConfig;
+
+    fn operations(operation: &str, outcome: &str) -> u64 {
+        crate::prometheus::STORAGE_OPERATIONS
+            .with_label_values(&["fs", operation, outcome])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn operations_are_recorded_with_their_backend_and_outcome() {
+        crate::prometheus::set_enabled(true);
+        let dir = tempfile::tempdir().unwrap();
+        let store = MeteredStore::from(FileSystemConfig::new(dir.path()).open().await.unwrap());
+        let space: SpaceId = "tinycloud:key:test:metered".parse().unwrap();
+        store.create(&space).await.unwrap();
+
+        let (writes, reads, missing, deletes, nothing_deleted) = (
+            operations("write", "ok"),
+            operations("read", "ok"),
+            operations("read", "not_found"),
+            operations("delete", "ok"),
+            operations("delete", "not_found"),
+        );
+
+        let mut stage = memory::MemoryStaging.stage(&space).await.unwrap();
+        futures::io::copy(&mut &b"hello world"[..], &mut stage)
+            .await
+            .unwrap();
+        let hash = ImmutableWriteStore::<memory::MemoryStaging>::persist(&store, &space, stage)
+            .await
+            .unwrap();
+        store.read(&space, &hash).await.unwrap().unwrap();
+        store.remove(&space, &hash).await.unwrap().unwrap();
+        assert!(store.read(&space, &hash).await.unwrap().is_none());
+        assert!(store.remove(&space, &hash).await.unwrap().is_none());
+
+        // other tests may record operations concurrently, never fewer
+        assert!(operations("write", "ok") > writes);
+        assert!(operations("read", "ok") > reads);
+        assert!(operations("read", "not_found") > missing);
+        assert!(operations("delete", "ok") > deletes);
+        assert!(operations("delete", "not_found") > nothing_deleted);
+    }
+}
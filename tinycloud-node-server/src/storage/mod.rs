@@ -1,3 +1,16 @@
+use tinycloud_core::storage::{either::Either, memory::MemoryStaging};
+
+pub mod adaptive;
+#[cfg(feature = "azure")]
+pub mod azure;
+pub mod caching;
 pub mod file_system;
+pub mod metered;
+#[cfg(feature = "redis")]
+pub mod redis;
 pub mod s3;
 pub mod size;
+
+/// Staging on the node itself, in memory, temporary files or both.
+pub type LocalStaging =
+    Either<Either<file_system::TempFileSystemStage, MemoryStaging>, adaptive::AdaptiveStaging>;
@@ -0,0 +1,304 @@
+use super::file_system::TempFileStage;
+use core::pin::Pin;
+use futures::{
+    future::{poll_fn, Either as AsyncEither},
+    io::{AsyncWrite, AsyncWriteExt},
+    ready,
+    task::{Context, Poll},
+    Future,
+};
+use redis::{aio::ConnectionManager, Client, RedisError};
+use std::{fmt, io::Error as IoError, sync::Arc};
+use tinycloud_auth::resource::SpaceId;
+use tinycloud_core::storage::*;
+use tokio::{sync::OnceCell, task::JoinHandle};
+
+/// Staged bytes are sent to Redis in chunks of at least this many bytes.
+const APPEND_CHUNK: usize = 64 * 1024;
+
+/// Staged chunks are read back from Redis this many at a time.
+const READ_BATCH: isize = 16;
+
+/// Stages uploads in Redis, so that a node's request handlers need neither
+/// local disk nor memory for the bodies they are still receiving.
+///
+/// Each staging buffer is a Redis list of chunks under an ephemeral key which
+/// expires `ttl_secs` after its last append, so an upload which is abandoned
+/// before being persisted is cleaned up by Redis itself, and no single Redis
+/// value has to hold a whole upload. Blocks are still hashed locally as they
+/// are staged.
+#[derive(Clone)]
+pub struct RedisStaging {
+    url: String,
+    ttl_secs: u64,
+    conn: Arc<OnceCell<ConnectionManager>>,
+}
+
+impl RedisStaging {
+    pub fn new(url: String, ttl_secs: u64) -> Self {
+        Self {
+            url,
+            ttl_secs,
+            conn: Arc::new(OnceCell::new()),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.conn
+            .get_or_try_init(|| async {
+                Client::open(self.url.as_str())?
+                    .get_connection_manager()
+                    .await
+            })
+            .await
+            .cloned()
+    }
+}
+
+impl fmt::Debug for RedisStaging {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStaging")
+            .field("url", &self.url)
+            .field("ttl_secs", &self.ttl_secs)
+            .finish()
+    }
+}
+
+impl PartialEq for RedisStaging {
+    fn eq(&self, other: &Self) -> bool {
+        self.url == other.url && self.ttl_secs == other.ttl_secs
+    }
+}
+
+impl Eq for RedisStaging {}
+
+impl core::hash::Hash for RedisStaging {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.url.hash(state);
+        self.ttl_secs.hash(state);
+    }
+}
+
+#[async_trait]
+impl ImmutableStaging for RedisStaging {
+    type Error = RedisError;
+    type Writable = RedisStage;
+    async fn get_staging_buffer(&self, space: &SpaceId) -> Result<Self::Writable, Self::Error> {
+        Ok(RedisStage {
+            conn: self.connection().await?,
+            key: format!(
+                "tinycloud:staging:{}:{:032x}",
+                space.suffix(),
+                rand::random::<u128>()
+            ),
+            ttl_secs: i64::try_from(self.ttl_secs).unwrap_or(i64::MAX),
+            buffer: Vec::new(),
+            len: 0,
+            pending: None,
+        })
+    }
+}
+
+/// A staging buffer held in Redis. Writes are buffered locally and pushed
+/// to the buffer's list in chunks; [`RedisStage::into_local`] reads the
+/// staged chunks back a batch at a time and removes the list.
+pub struct RedisStage {
+    conn: ConnectionManager,
+    key: String,
+    ttl_secs: i64,
+    buffer: Vec<u8>,
+    len: usize,
+    pending: Option<JoinHandle<Result<(), RedisError>>>,
+}
+
+impl RedisStage {
+    /// Every byte written to this buffer, in order, as the buffer of a file
+    /// system or memory stage for the stores to persist as they would either.
+    ///
+    /// Bytes which fit in one batch of chunks are read into memory, larger
+    /// ones are copied to a temporary file so that no more than one batch is
+    /// held in memory at once.
+    pub async fn into_local(mut self) -> Result<AsyncEither<TempFileStage, Vec<u8>>, IoError> {
+        poll_fn(|cx| Pin::new(&mut self).poll_flush(cx)).await?;
+        let mut local = if self.len <= APPEND_CHUNK * READ_BATCH as usize {
+            AsyncEither::Right(Vec::with_capacity(self.len))
+        } else {
+            let file = tokio::task::spawn_blocking(tempfile::NamedTempFile::new)
+                .await
+                .map_err(IoError::other)??;
+            AsyncEither::Left(TempFileStage::new(file))
+        };
+        let copied = self.copy_chunks(&mut local).await;
+        let removed: Result<(), IoError> = redis::cmd("DEL")
+            .arg(&self.key)
+            .query_async(&mut self.conn)
+            .await
+            .map_err(IoError::other);
+        copied.and(removed)?;
+        Ok(local)
+    }
+
+    async fn copy_chunks(
+        &mut self,
+        local: &mut AsyncEither<TempFileStage, Vec<u8>>,
+    ) -> Result<(), IoError> {
+        let mut start = 0;
+        loop {
+            let chunks: Vec<Vec<u8>> = redis::cmd("LRANGE")
+                .arg(&self.key)
+                .arg(start)
+                .arg(start + READ_BATCH - 1)
+                .query_async(&mut self.conn)
+                .await
+                .map_err(IoError::other)?;
+            for chunk in &chunks {
+                local.write_all(chunk).await?;
+            }
+            if chunks.len() < READ_BATCH as usize {
+                return local.flush().await;
+            }
+            start += READ_BATCH;
+        }
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        if let Some(pending) = self.pending.as_mut() {
+            let appended = ready!(Pin::new(pending).poll(cx));
+            self.pending = None;
+            appended.map_err(IoError::other)?.map_err(IoError::other)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn append(&mut self) {
+        let bytes = std::mem::take(&mut self.buffer);
+        let mut conn = self.conn.clone();
+        let key = self.key.clone();
+        let ttl_secs = self.ttl_secs;
+        self.pending = Some(tokio::spawn(async move {
+            redis::pipe()
+                .rpush(&key, bytes)
+                .ignore()
+                .expire(&key, ttl_secs)
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        }));
+    }
+}
+
+impl fmt::Debug for RedisStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStage")
+            .field("key", &self.key)
+            .field("buffered", &self.buffer.len())
+            .finish()
+    }
+}
+
+impl AsyncWrite for RedisStage {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if this.buffer.len() >= APPEND_CHUNK {
+            this.append();
+            ready!(this.poll_pending(cx))?;
+        }
+        this.buffer.extend_from_slice(buf);
+        this.len += buf.len();
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if !this.buffer.is_empty() {
+            this.append();
+            ready!(this.poll_pending(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::{
+        file_system::{FileSystemConfig, FileSystemStore},
+        LocalStaging,
+    };
+    use tinycloud_core::storage::{either::Either, memory::MemoryStaging};
+
+    type Stage = Either<LocalStaging, RedisStaging>;
+
+    #[tokio::test]
+    async fn redis_staged_blocks_persist_with_their_local_hash() {
+        let Ok(url) = std::env::var("TINYCLOUD_TEST_REDIS_URL") else {
+            eprintln!("skipping Redis staging test: TINYCLOUD_TEST_REDIS_URL is unset");
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let store: FileSystemStore = FileSystemConfig::new(dir.path()).open().await.unwrap();
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+        store.create(&space).await.unwrap();
+
+        // more than one batch of chunks, so read back a batch at a time
+        let data: Vec<u8> = (0..APPEND_CHUNK * READ_BATCH as usize * 2 + 5)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let staging = Stage::B(RedisStaging::new(url.clone(), 60));
+        let mut stage = staging.stage(&space).await.unwrap();
+        stage.write_all(&data).await.unwrap();
+        stage.flush().await.unwrap();
+
+        // staged as a list of chunks rather than one value
+        let mut conn = Client::open(url.as_str())
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let keys: Vec<String> = redis::cmd("KEYS")
+            .arg(format!("tinycloud:staging:{}:*", space.suffix()))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(keys.len(), 1);
+        let chunks: usize = redis::cmd("LLEN")
+            .arg(&keys[0])
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(chunks > READ_BATCH as usize);
+
+        let hash = ImmutableWriteStore::<Stage>::persist(&store, &space, stage)
+            .await
+            .unwrap();
+
+        let mut local = MemoryStaging.stage(&space).await.unwrap();
+        local.write_all(&data).await.unwrap();
+        assert_eq!(hash, local.hash());
+        assert_eq!(store.read_to_vec(&space, &hash).await.unwrap(), Some(data));
+
+        // the staged bytes are removed from Redis once persisted
+        let keys: Vec<String> = redis::cmd("KEYS")
+            .arg(format!("tinycloud:staging:{}:*", space.suffix()))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(keys.is_empty());
+    }
+}
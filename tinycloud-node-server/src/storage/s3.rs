@@ -113,6 +113,47 @@ impl S3BlockStore {
         )
     }
 
+    /// The length of the object `id` from the `content_length` of a
+    /// `GetObject` response. S3 reports a length of 0 when it sends no
+    /// `Content-Length`, as with a chunked response, so the length of any block
+    /// but the empty one is then taken from a `HeadObject` rather than by
+    /// reading the body.
+    async fn body_length(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        content_length: i64,
+    ) -> Result<u64, S3StoreError> {
+        let length = match content_length {
+            0 if *id != tinycloud_core::hash::hash(&[]) => self
+                .client
+                .head_object()
+                .bucket(&self.bucket)
+                .key(self.key(space, id))
+                .send()
+                .await
+                .map_err(S3Error::from)?
+                .content_length(),
+            length => length,
+        };
+        u64::try_from(length).map_err(|_| S3StoreError::NegativeLength(length))
+    }
+
+    /// Warn about an object said to be larger than everything tracked for
+    /// its space, which suggests the tracked size or the object is wrong.
+    async fn check_length(&self, space: &SpaceId, id: &Hash, length: u64) {
+        match self.sizes.get_size(space).await {
+            Some(total) if length > total => ::tracing::warn!(
+                %space,
+                key = %self.key(space, id),
+                length,
+                total,
+                "S3 object is larger than its space's tracked size"
+            ),
+            _ => {}
+        }
+    }
+
     async fn increment_size(&self, space: &SpaceId, size: u64) {
         self.sizes.increment_size(space, size).await;
     }
@@ -135,6 +176,8 @@ pub enum S3StoreError {
     Bytestream(#[from] ByteStreamError),
     #[error(transparent)]
     Length(#[from] std::num::TryFromIntError),
+    #[error("S3 reported a negative content length of {0}")]
+    NegativeLength(i64),
 }
 
 #[async_trait]
@@ -176,12 +219,11 @@ impl ImmutableReadStore for S3BlockStore {
             .send()
             .await;
         match res {
-            Ok(o) => Ok(Some(Content::new(
-                o.content_length().try_into()?,
-                o.body
-                    .map_err(convert as fn(ByteStreamError) -> IoError)
-                    .into_async_read(),
-            ))),
+            Ok(o) => {
+                let length = self.body_length(space, id, o.content_length()).await?;
+                self.check_length(space, id, length).await;
+                Ok(Some(Content::new(length, readable(o.body))))
+            }
             Err(SdkError::ServiceError {
                 err:
                     GetObjectError {
@@ -193,6 +235,98 @@ impl ImmutableReadStore for S3BlockStore {
             Err(e) => Err(S3Error::from(e).into()),
         }
     }
+
+    async fn read_range(
+        &self,
+        space: &SpaceId,
+        id: &Hash,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<Option<Content<Ranged<Self::Readable>>>, Self::Error> {
+        let res = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key(space, id))
+            .range(range_header(start, end))
+            .send()
+            .await;
+        match res {
+            Ok(o) => {
+                let length: u64 = o.content_length().try_into()?;
+                let total = o
+                    .content_range()
+                    .and_then(content_range_total)
+                    .unwrap_or(start + length);
+                Ok(Some(Ranged::positioned(
+                    readable(o.body),
+                    start,
+                    end,
+                    total,
+                )))
+            }
+            Err(SdkError::ServiceError {
+                err:
+                    GetObjectError {
+                        kind: GetObjectErrorKind::NoSuchKey(_),
+                        ..
+                    },
+                ..
+            }) => Ok(None),
+            // the range starts at or past the end of the object
+            Err(SdkError::ServiceError { raw, .. }) if raw.http().status().as_u16() == 416 => {
+                let total = self
+                    .client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(self.key(space, id))
+                    .send()
+                    .await
+                    .map_err(S3Error::from)?
+                    .content_length()
+                    .try_into()?;
+                Ok(Some(Ranged::positioned(
+                    readable(ByteStream::from(Vec::new())),
+                    start,
+                    end,
+                    total,
+                )))
+            }
+            Err(e) => Err(S3Error::from(e).into()),
+        }
+    }
+}
+
+fn readable(body: ByteStream) -> <S3BlockStore as ImmutableReadStore>::Readable {
+    body.map_err(convert as fn(ByteStreamError) -> IoError)
+        .into_async_read()
+}
+
+/// `Range` header for bytes `start..end`; S3 ranges include their last byte.
+fn range_header(start: u64, end: Option<u64>) -> String {
+    match end {
+        Some(end) => format!("bytes={start}-{}", end.saturating_sub(1).max(start)),
+        None => format!("bytes={start}-"),
+    }
+}
+
+/// The object length from a `Content-Range: bytes 0-99/1234` header.
+fn content_range_total(content_range: &str) -> Option<u64> {
+    content_range.rsplit_once('/')?.1.parse().ok()
+}
+
+#[async_trait]
+impl StorageHealth for S3BlockStore {
+    type Error = S3StoreError;
+    async fn health(&self) -> Result<(), Self::Error> {
+        self.client
+            .head_bucket()
+            .bucket(&self.bucket)
+            .send()
+            .await
+            .map_err(S3Error::from)?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -297,6 +431,94 @@ impl ImmutableWriteStore<either::Either<file_system::TempFileSystemStage, memory
     }
 }
 
+#[async_trait]
+impl ImmutableWriteStore<super::LocalStaging> for S3BlockStore {
+    type Error = S3StoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<<super::LocalStaging as ImmutableStaging>::Writable>,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        let local = match f {
+            AsyncEither::Left(local) => local,
+            AsyncEither::Right(adaptive) => adaptive.into_local().await?,
+        };
+        ImmutableWriteStore::<either::Either<file_system::TempFileSystemStage, memory::MemoryStaging>>::persist(
+            self,
+            space,
+            HashBuffer::from_parts(h, local),
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl ImmutableWriteStore<either::Either<super::LocalStaging, super::redis::RedisStaging>>
+    for S3BlockStore
+{
+    type Error = S3StoreError;
+    async fn persist(
+        &self,
+        space: &SpaceId,
+        staged: HashBuffer<
+            <either::Either<
+                super::LocalStaging,
+                super::redis::RedisStaging,
+            > as ImmutableStaging>::Writable,
+        >,
+    ) -> Result<Hash, Self::Error> {
+        let (h, f) = staged.into_inner();
+        match f {
+            AsyncEither::Left(local) => {
+                ImmutableWriteStore::<super::LocalStaging>::persist(
+                    self,
+                    space,
+                    HashBuffer::from_parts(h, local),
+                )
+                .await
+            }
+            AsyncEither::Right(stage) => {
+                let local = stage.into_local().await?;
+                ImmutableWriteStore::<
+                    either::Either<file_system::TempFileSystemStage, memory::MemoryStaging>,
+                >::persist(self, space, HashBuffer::from_parts(h, local))
+                .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ImmutableListStore for S3BlockStore {
+    type Error = S3StoreError;
+    async fn list(&self, space: &SpaceId) -> Result<Vec<Hash>, Self::Error> {
+        let prefix = format!("{space}/");
+        Ok(self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(&prefix)
+            .into_paginator()
+            .send()
+            .try_fold(Vec::new(), |mut acc, page| {
+                let prefix = &prefix;
+                async move {
+                    // keys deeper than the space prefix are not blocks of it
+                    acc.extend(page.contents.into_iter().flatten().filter_map(|content| {
+                        let name = content.key()?.strip_prefix(prefix.as_str())?;
+                        let bytes = base64::decode_config(name, base64::URL_SAFE).ok()?;
+                        Hash::try_from(bytes).ok()
+                    }));
+                    Ok(acc)
+                }
+            })
+            .await
+            .map_err(S3Error::from)?)
+    }
+}
+
 #[async_trait]
 impl ImmutableDeleteStore for S3BlockStore {
     type Error = S3StoreError;
@@ -346,3 +568,93 @@ impl StoreSize for S3BlockStore {
         Ok(self.sizes.get_size(space).await)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_smithy_client::test_connection::capture_request;
+    use aws_types::{region::Region, Credentials};
+
+    #[tokio::test]
+    async fn ranged_reads_send_the_range_to_s3() {
+        let (conn, request) = capture_request(None);
+        let config = aws_sdk_s3::Config::builder()
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .build();
+        let store = S3BlockStore {
+            client: Client::from_conf_conn(config, conn),
+            bucket: "bucket".to_string(),
+            sizes: SpaceSizes::new(),
+        };
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+
+        // the canned response is irrelevant; only the request is checked
+        let _ = store
+            .read_range(&space, &tinycloud_core::hash::hash(b"value"), 6, Some(9))
+            .await;
+        let sent = request.expect_request();
+        assert_eq!(sent.headers().get("range").unwrap(), "bytes=6-8");
+
+        assert_eq!(range_header(6, None), "bytes=6-");
+        assert_eq!(content_range_total("bytes 6-8/11"), Some(11));
+    }
+
+    #[tokio::test]
+    async fn reads_without_a_content_length_are_measured() {
+        use aws_smithy_client::test_connection::TestConnection;
+        use aws_smithy_http::body::SdkBody;
+        use futures::io::AsyncReadExt;
+
+        let request = || hyper::Request::builder().body(SdkBody::empty()).unwrap();
+        // a chunked GetObject, then the HeadObject asking for its length
+        let conn = TestConnection::new(vec![
+            (
+                request(),
+                hyper::Response::builder()
+                    .status(200)
+                    .body(SdkBody::from("value"))
+                    .unwrap(),
+            ),
+            (
+                request(),
+                hyper::Response::builder()
+                    .status(200)
+                    .header("Content-Length", "5")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+        ]);
+        let config = aws_sdk_s3::Config::builder()
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .build();
+        let store = S3BlockStore {
+            client: Client::from_conf_conn(config, conn.clone()),
+            bucket: "bucket".to_string(),
+            sizes: SpaceSizes::new(),
+        };
+        let space: SpaceId = "tinycloud:key:test:default".parse().unwrap();
+
+        let content = store
+            .read(&space, &tinycloud_core::hash::hash(b"value"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(content.len(), 5);
+        let mut value = String::new();
+        Box::pin(content).read_to_string(&mut value).await.unwrap();
+        assert_eq!(value, "value");
+        let sent = conn.requests();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].actual.method(), "HEAD");
+
+        // the empty block needs no HeadObject
+        let empty = tinycloud_core::hash::hash(&[]);
+        assert_eq!(store.body_length(&space, &empty, 0).await.unwrap(), 0);
+        assert!(matches!(
+            store.body_length(&space, &empty, -1).await,
+            Err(S3StoreError::NegativeLength(-1))
+        ));
+    }
+}
@@ -1,8 +1,17 @@
-use opentelemetry::trace::{TraceContextExt, TracerProvider};
-use opentelemetry_otlp::ExporterBuildError;
+use opentelemetry::{
+    propagation::{Extractor, Injector, TextMapPropagator},
+    trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider,
+    },
+};
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+};
 use rocket::{
     fairing::{Fairing, Info, Kind},
-    http::Status,
+    http::{HeaderMap, Status},
     request::{FromRequest, Outcome},
     Data, Request, Response,
 };
@@ -14,6 +23,7 @@ use std::{
     time::Instant,
 };
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tinycloud_auth::resource::SpaceId;
 use tracing::{
     field::{Field, Visit},
     info_span,
@@ -34,10 +44,103 @@ struct RequestTelemetry {
     start: Instant,
 }
 
+/// Starts a span for every request, joined to the caller's trace when the
+/// request carries one, and reports the trace on the response.
+///
+/// The W3C `traceparent` and `tracestate` headers are read and emitted; the
+/// `header_name` header carries the bare trace ID, and is only read when no
+/// `traceparent` is present.
 pub struct TracingFairing {
     pub header_name: String,
 }
 
+struct HeaderExtractor<'a>(&'a HeaderMap<'a>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.names().map(|name| name.as_str()).collect()
+    }
+}
+
+#[derive(Default)]
+struct HeaderInjector(Vec<(String, String)>);
+
+impl Injector for HeaderInjector {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.push((key.to_string(), value));
+    }
+}
+
+/// The trace context a request was sent in, if any.
+fn remote_parent(headers: &HeaderMap<'_>, trace_header: &str) -> Option<opentelemetry::Context> {
+    let cx = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    if cx.span().span_context().is_valid() {
+        return Some(cx);
+    }
+    // the custom header names only a trace, so the request's span is given a
+    // remote parent of its own within it
+    let trace_id = headers
+        .get_one(trace_header)
+        .and_then(|id| TraceId::from_hex(id).ok())
+        .filter(|id| *id != TraceId::INVALID)?;
+    Some(
+        opentelemetry::Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from_bytes(rand::random()),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        )),
+    )
+}
+
+fn request_span(headers: &HeaderMap<'_>, trace_header: &str) -> Span {
+    let span = info_span!(parent: None, "request", trace_id = tracing::field::Empty);
+    if let Some(parent) = remote_parent(headers, trace_header) {
+        span.set_parent(parent);
+    }
+    span.record(
+        "trace_id",
+        tracing::field::display(&span.context().span().span_context().trace_id()),
+    );
+    span
+}
+
+/// Emits one `tinycloud::access` event per request with its method, path,
+/// status, body sizes, space and duration.
+pub struct AccessLogFairing;
+
+#[derive(Clone)]
+struct AccessLogStart(Instant);
+
+/// Spaces named by the request's authorization, recorded by the auth guard.
+#[derive(Clone, Default)]
+pub struct AccessLogSpaces(Vec<SpaceId>);
+
+impl AccessLogSpaces {
+    pub fn record<'a>(req: &Request<'_>, spaces: impl IntoIterator<Item = &'a SpaceId>) {
+        req.local_cache(|| {
+            let mut spaces: Vec<SpaceId> = spaces.into_iter().cloned().collect();
+            spaces.sort_by_key(|space| space.to_string());
+            spaces.dedup();
+            AccessLogSpaces(spaces)
+        });
+    }
+}
+
+/// Length of a streamed response body, which Rocket cannot report itself.
+#[derive(Clone, Copy)]
+struct StreamedBodySize(Option<u64>);
+
+/// Record the length of a streamed response body for the access log.
+pub fn record_streamed_body_size(req: &Request<'_>, size: u64) {
+    req.local_cache(|| StreamedBodySize(Some(size)));
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LogEntry {
@@ -288,6 +391,39 @@ where
     }
 }
 
+fn span_exporter(config: &config::Tracing) -> Result<SpanExporter, ExporterBuildError> {
+    match (config.protocol, &config.endpoint) {
+        (config::OtlpProtocol::Grpc, None) => SpanExporter::builder().with_tonic().build(),
+        (config::OtlpProtocol::Grpc, Some(endpoint)) => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build(),
+        (config::OtlpProtocol::Http, None) => SpanExporter::builder().with_http().build(),
+        (config::OtlpProtocol::Http, Some(endpoint)) => SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build(),
+    }
+}
+
+/// The provider of the tracer spans are exported with, if export is
+/// configured.
+fn tracer_provider(
+    config: &config::Tracing,
+) -> Result<Option<SdkTracerProvider>, ExporterBuildError> {
+    if !config.exports() {
+        return Ok(None);
+    }
+    Ok(Some(
+        SdkTracerProvider::builder()
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                config.sampling_ratio.get(),
+            ))))
+            .with_batch_exporter(span_exporter(config)?)
+            .build(),
+    ))
+}
+
 pub fn tracing_try_init(config: &config::Logging) -> Result<(), ExporterBuildError> {
     let _ = LogTracer::init();
     let env_filter = tracing_subscriber::EnvFilter::builder()
@@ -298,21 +434,8 @@ pub fn tracing_try_init(config: &config::Logging) -> Result<(), ExporterBuildErr
         config::LoggingFormat::Text => subscriber.boxed(),
         config::LoggingFormat::Json => subscriber.json().boxed(),
     };
-    let telemetry = if config.tracing.enabled {
-        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-            .with_batch_exporter(
-                opentelemetry_otlp::SpanExporter::builder()
-                    .with_tonic()
-                    .build()?,
-            )
-            .build();
-        let tracer = provider.tracer("tinycloud");
-
-        let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
-        Some(telemetry)
-    } else {
-        None
-    };
+    let telemetry = tracer_provider(&config.tracing)?
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("tinycloud")));
     let collector = Registry::default()
         .with(env_filter)
         .with(log)
@@ -331,11 +454,7 @@ impl Fairing for TracingFairing {
         }
     }
     async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
-        let span = info_span!(parent: None, "request", trace_id = tracing::field::Empty);
-        span.record(
-            "trace_id",
-            tracing::field::display(&span.context().span().span_context().trace_id()),
-        );
+        let span = request_span(req.headers(), &self.header_name);
         req.local_cache(|| Some(TracingSpan(span)));
         if crate::prometheus::enabled() {
             req.local_cache(|| {
@@ -349,8 +468,14 @@ impl Fairing for TracingFairing {
     async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
         if let Some(TracingSpan(span)) = req.local_cache(|| Option::<TracingSpan>::None).to_owned()
         {
-            let trace_id = span.context().span().span_context().trace_id();
+            let cx = span.context();
+            let trace_id = cx.span().span_context().trace_id();
             res.set_raw_header(self.header_name.clone(), format!("{trace_id}"));
+            let mut injector = HeaderInjector::default();
+            TraceContextPropagator::new().inject_context(&cx, &mut injector);
+            for (name, value) in injector.0 {
+                res.set_raw_header(name, value);
+            }
         }
         if crate::prometheus::enabled() {
             if let Some(telemetry) = req
@@ -373,6 +498,62 @@ impl Fairing for TracingFairing {
     }
 }
 
+/// Bytes sent in the response body. A sized body reports its own length;
+/// streamed bodies (kv reads) only count what their responder recorded, so a
+/// body is never counted twice.
+fn response_body_size(req: &Request<'_>, res: &mut Response<'_>) -> Option<u64> {
+    res.body()
+        .preset_size()
+        .map(|size| size as u64)
+        .or(req.local_cache(|| StreamedBodySize(None)).0)
+}
+
+#[rocket::async_trait]
+impl Fairing for AccessLogFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access Log Fairing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| Some(AccessLogStart(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let duration_ms = req
+            .local_cache(|| Option::<AccessLogStart>::None)
+            .as_ref()
+            .map(|AccessLogStart(start)| start.elapsed().as_secs_f64() * 1000.0);
+        let bytes_in = req
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok());
+        let bytes_out = response_body_size(req, res);
+        let spaces = req.local_cache(AccessLogSpaces::default);
+        let space = (!spaces.0.is_empty()).then(|| {
+            spaces
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        tracing::info!(
+            target: "tinycloud::access",
+            method = req.method().as_str(),
+            path = req.uri().path().as_str(),
+            status = res.status().code,
+            bytes_in,
+            bytes_out,
+            space,
+            duration_ms,
+            "request"
+        );
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for TracingSpan {
     type Error = ();
@@ -384,3 +565,130 @@ impl<'r> FromRequest<'r> for TracingSpan {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::{get, local::asynchronous::Client, response::Responder, routes};
+
+    struct Streamed;
+
+    impl<'r> Responder<'r, 'static> for Streamed {
+        fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'static> {
+            record_streamed_body_size(req, 5);
+            Ok(Response::build()
+                .streamed_body(std::io::Cursor::new(b"hello".to_vec()))
+                .finalize())
+        }
+    }
+
+    #[get("/sized")]
+    fn sized() -> &'static str {
+        "hello"
+    }
+
+    #[get("/streamed")]
+    fn streamed() -> Streamed {
+        Streamed
+    }
+
+    struct Sizes;
+
+    #[rocket::async_trait]
+    impl Fairing for Sizes {
+        fn info(&self) -> Info {
+            Info {
+                name: "Sizes",
+                kind: Kind::Response,
+            }
+        }
+
+        async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+            let size = response_body_size(req, res);
+            res.set_raw_header("X-Logged-Size", format!("{size:?}"));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn body_sizes_are_counted_once() {
+        let client = Client::tracked(
+            rocket::build()
+                .mount("/", routes![sized, streamed])
+                .attach(Sizes),
+        )
+        .await
+        .unwrap();
+        for route in ["/sized", "/streamed"] {
+            let res = client.get(route).dispatch().await;
+            assert_eq!(res.headers().get_one("X-Logged-Size"), Some("Some(5)"));
+        }
+    }
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn with_telemetry<T>(f: impl FnOnce() -> T) -> T {
+        let tracer = SdkTracerProvider::builder().build().tracer("test");
+        let subscriber =
+            Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::with_default(subscriber, f)
+    }
+
+    #[test]
+    fn incoming_traceparent_is_the_parent_span_context() {
+        let mut headers = HeaderMap::new();
+        headers.add_raw("traceparent", TRACEPARENT);
+        headers.add_raw("TinyCloud-Trace-Id", "0af7651916cd43dd8448eb211c80319c");
+        let (trace_id, parent) = with_telemetry(|| {
+            let span = request_span(&headers, "TinyCloud-Trace-Id");
+            let trace_id = span.context().span().span_context().trace_id();
+            (
+                trace_id,
+                remote_parent(&headers, "TinyCloud-Trace-Id").unwrap(),
+            )
+        });
+        let parent = parent.span().span_context().clone();
+        assert!(parent.is_remote());
+        assert_eq!(
+            parent.span_id(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+        // the standard header wins over the custom one
+        assert_eq!(
+            trace_id,
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+    }
+
+    #[test]
+    fn custom_trace_header_is_honoured_without_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.add_raw("TinyCloud-Trace-Id", "0af7651916cd43dd8448eb211c80319c");
+        let trace_id = with_telemetry(|| {
+            request_span(&headers, "TinyCloud-Trace-Id")
+                .context()
+                .span()
+                .span_context()
+                .trace_id()
+        });
+        assert_eq!(
+            trace_id,
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn otlp_export_is_added_when_configured() {
+        assert!(tracer_provider(&config::Tracing::default())
+            .unwrap()
+            .is_none());
+        for protocol in [config::OtlpProtocol::Grpc, config::OtlpProtocol::Http] {
+            let config = config::Tracing {
+                endpoint: Some("http://localhost:4317".into()),
+                protocol,
+                sampling_ratio: 0.5.try_into().unwrap(),
+                ..Default::default()
+            };
+            assert!(tracer_provider(&config).unwrap().is_some());
+        }
+    }
+}
@@ -240,7 +240,7 @@ mod tests {
         let encryption = ColumnEncryption::new([9u8; 32]);
         let tinycloud = TinyCloud::new(
             db.clone(),
-            Either::B(storage),
+            crate::storage::metered::MeteredStore::from(Either::B(storage)).into(),
             StaticSecret::new(vec![0u8; 32]).unwrap(),
         )
         .await?
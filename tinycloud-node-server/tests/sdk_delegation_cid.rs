@@ -0,0 +1,83 @@
+// The SDK names a session's delegation by a CID it computes itself, and uses
+// that CID as the proof of every invocation the session signs. These tests
+// set a session up the way the SDK does, store its delegation the way the
+// node does, and check that both sides arrive at the same CID.
+
+use k256::ecdsa::SigningKey;
+use serde_json::json;
+use sha3::{Digest, Keccak256};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tinycloud_auth::{authorization::TinyCloudDelegation, cacaos::siwe::encode_eip55};
+use tinycloud_core::{
+    events::Delegation,
+    keys::StaticSecret,
+    sea_orm::{ConnectOptions, Database},
+    storage::memory::MemoryStore,
+    SpaceDatabase,
+};
+use tinycloud_sdk_wasm::session::{complete_session_setup, prepare_session, Session};
+
+fn signed_session(signing_key: &SigningKey) -> Session {
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let address: [u8; 20] = Keccak256::digest(&public_key.as_bytes()[1..])[12..]
+        .try_into()
+        .unwrap();
+    let address = format!("0x{}", encode_eip55(&address));
+    let now = OffsetDateTime::now_utc();
+    let prepared = prepare_session(
+        serde_json::from_value(json!({
+            "abilities": { "kv": { "notes/": ["tinycloud.kv/get", "tinycloud.kv/put"] } },
+            "address": address,
+            "chainId": 1,
+            "domain": "example.com",
+            "issuedAt": now.format(&Rfc3339).unwrap(),
+            "expirationTime": (now + Duration::hours(1)).format(&Rfc3339).unwrap(),
+            "spaceId": format!("tinycloud:pkh:eip155:1:{address}:default"),
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(&prepared.siwe.eip191_hash().unwrap())
+        .unwrap();
+    let mut signature_bytes = [0u8; 65];
+    signature_bytes[..64].copy_from_slice(signature.to_bytes().as_ref());
+    signature_bytes[64] = u8::from(recovery_id) + 27;
+
+    let mut signed = serde_json::to_value(prepared).unwrap();
+    signed.as_object_mut().unwrap().insert(
+        "signature".into(),
+        format!("0x{}", hex::encode(signature_bytes)).into(),
+    );
+    complete_session_setup(serde_json::from_value(signed).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn sdk_session_cid_names_the_delegation_the_node_stores() {
+    let session = signed_session(&SigningKey::from_bytes(&[0x22u8; 32].into()).unwrap());
+    let header = serde_json::to_value(&session.delegation_header).unwrap()["Authorization"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let db = SpaceDatabase::new(
+        Database::connect(ConnectOptions::new("sqlite::memory:".to_string()))
+            .await
+            .unwrap(),
+        MemoryStore::default(),
+        StaticSecret::new([0u8; 32].to_vec()).unwrap(),
+    )
+    .await
+    .unwrap();
+    let delegation = Delegation::from_header_ser::<TinyCloudDelegation>(&header).unwrap();
+    let stored = db
+        .delegate(delegation)
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap()
+        .delegation_cids;
+
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].to_event_cid(), session.delegation_cid);
+}
@@ -0,0 +1,85 @@
+// The SDK picks the `tinycloud.sql/*` ability a SQL request is invoked with
+// before the node sees it. These tests run the shared cases through both: the
+// SDK must pick the ability each case names, and the node must accept every
+// statement of the request with that ability and refuse it with any narrower
+// one, so the SDK never asks for less or more than the node requires.
+
+use serde::Deserialize;
+use serde_json::Value;
+use tinycloud_core::sql::{parser::validate_sql, SqlRequest};
+
+const CASES: &str = include_str!("fixtures/sql-abilities.json");
+
+#[derive(Deserialize)]
+struct Case {
+    case: String,
+    request: Value,
+    ability: String,
+}
+
+/// The statements the node validates for `request`; named statements live in
+/// caveats and exports run no SQL.
+fn statements(request: &SqlRequest) -> Vec<&str> {
+    match request {
+        SqlRequest::Query { sql, .. } => vec![sql],
+        SqlRequest::Execute { sql, schema, .. } => schema
+            .iter()
+            .flatten()
+            .chain(std::iter::once(sql))
+            .map(String::as_str)
+            .collect(),
+        SqlRequest::Batch { statements } => statements.iter().map(|s| s.sql.as_str()).collect(),
+        SqlRequest::ExecuteStatement { .. } | SqlRequest::Export => Vec::new(),
+    }
+}
+
+/// The abilities narrower than `ability`, each of which must fail.
+fn narrower(ability: &str) -> &'static [&'static str] {
+    match ability {
+        "tinycloud.sql/read" => &[],
+        "tinycloud.sql/schema" => &["tinycloud.sql/read"],
+        "tinycloud.sql/write" => &["tinycloud.sql/read", "tinycloud.sql/schema"],
+        "tinycloud.sql/admin" => &[
+            "tinycloud.sql/read",
+            "tinycloud.sql/schema",
+            "tinycloud.sql/write",
+        ],
+        other => panic!("no case should need {other}"),
+    }
+}
+
+#[test]
+fn sdk_sql_abilities_match_what_the_node_requires() {
+    let cases: Vec<Case> = serde_json::from_str(CASES).unwrap();
+    for case in cases {
+        let picked = tinycloud_sdk_wasm::sql::sql_ability(
+            &serde_json::from_value(case.request.clone()).unwrap(),
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(picked, case.ability, "{}", case.case);
+
+        let request: SqlRequest = serde_json::from_value(case.request).unwrap();
+        let statements = statements(&request);
+        for sql in &statements {
+            assert!(
+                validate_sql(sql, &None, &case.ability).is_ok(),
+                "{}: {sql} refused with {}",
+                case.case,
+                case.ability
+            );
+        }
+        if statements.is_empty() {
+            continue;
+        }
+        for ability in narrower(&case.ability) {
+            assert!(
+                statements
+                    .iter()
+                    .any(|sql| validate_sql(sql, &None, ability).is_err()),
+                "{}: accepted with the narrower {ability}",
+                case.case
+            );
+        }
+    }
+}
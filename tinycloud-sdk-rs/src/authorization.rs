@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use tinycloud_auth::authorization::{TinyCloudDelegation, TinyCloudInvocation};
+use tinycloud_auth::authorization::{
+    TinyCloudDelegation, TinyCloudInvocation, TinyCloudRevocation,
+};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DelegationHeaders {
@@ -13,6 +15,12 @@ pub struct InvocationHeaders {
     invocation: TinyCloudInvocation,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RevocationHeaders {
+    #[serde(with = "header_enc", rename = "Authorization")]
+    revocation: TinyCloudRevocation,
+}
+
 impl InvocationHeaders {
     pub fn new(invocation: TinyCloudInvocation) -> Self {
         Self { invocation }
@@ -25,6 +33,12 @@ impl DelegationHeaders {
     }
 }
 
+impl RevocationHeaders {
+    pub fn new(revocation: TinyCloudRevocation) -> Self {
+        Self { revocation }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("failed to generate proof for invocation: {0}")]
@@ -2,11 +2,41 @@ use std::str::FromStr;
 pub use tinycloud_auth::cacaos::siwe::{decode_eip55, encode_eip55};
 use tinycloud_auth::resource::{KRIParseError, SpaceId};
 
+/// Build the id of a space owned by a `did:pkh` account on any chain.
+///
+/// `namespace` and `chain_id` are the two halves of the chain's CAIP-2 id
+/// (e.g. `solana` and `4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ`), and `address` is
+/// the account address in the chain's own encoding, giving
+/// `tinycloud:pkh:<namespace>:<chain_id>:<address>:<name>`.
+pub fn make_space_id_pkh(
+    namespace: &str,
+    chain_id: &str,
+    address: &str,
+    name: String,
+) -> Result<SpaceId, KRIParseError> {
+    // CAIP-2 and CAIP-10 character sets
+    let valid = |s: &str, len: std::ops::RangeInclusive<usize>, extra: &[char]| {
+        len.contains(&s.len())
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || extra.contains(&c))
+    };
+    if !valid(namespace, 3..=8, &['-'])
+        || namespace.chars().any(|c| c.is_ascii_uppercase())
+        || !valid(chain_id, 1..=32, &['-', '_'])
+        || !valid(address, 1..=128, &['-', '.', '%'])
+    {
+        return Err(KRIParseError::IncorrectForm);
+    }
+    SpaceId::from_str(&format!(
+        "tinycloud:pkh:{namespace}:{chain_id}:{address}:{name}"
+    ))
+}
+
 pub fn make_space_id_pkh_eip155(
     address: &[u8; 20],
     chain_id: u32,
     name: String,
 ) -> Result<SpaceId, KRIParseError> {
     let addr = encode_eip55(address);
-    SpaceId::from_str(&format!("tinycloud:pkh:eip155:{chain_id}:0x{addr}:{name}"))
+    make_space_id_pkh("eip155", &chain_id.to_string(), &format!("0x{addr}"), name)
 }
@@ -70,3 +70,59 @@ export type HostConfig = {
   peerId: string,
 }
 "#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_DEF: &'static str = r#"
+/**
+ * Configuration object for generating a TinyCloud Revocation SIWE message.
+ */
+export type RevocationConfig = {
+  /** Ethereum address that issued the delegation. */
+  address: string,
+  /** Chain ID. */
+  chainId: number,
+  /** Domain of the webpage. */
+  domain: string,
+  /** Current time for SIWE message. */
+  issuedAt: string,
+  /** CID of the delegation being revoked. */
+  delegationCid: string,
+}
+"#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_DEF: &'static str = r#"
+/**
+ * A request to the TinyCloud SQL service, passed to `invokeSql` and sent as
+ * the body of the invocation.
+ */
+export type SqlRequest =
+  | { action: "query", sql: string, params?: unknown[], maxRows?: number, maxBytes?: number }
+  | { action: "execute", sql: string, params?: unknown[], schema?: string[] }
+  | { action: "batch", statements: { sql: string, params?: unknown[] }[] }
+  | { action: "executeStatement", name: string, params?: unknown[] }
+  | { action: "export" }
+"#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_DEF: &'static str = r#"
+/**
+ * A delegation decoded by `parseDelegation`. Times are seconds since the epoch.
+ */
+export type ParsedDelegation = {
+  /** CID of the delegation, usable as a parent. */
+  cid: string,
+  /** DID of the party granting the capabilities. */
+  delegator: string,
+  /** DID (or DID URL) of the party receiving them. */
+  delegate: string,
+  /** The granted abilities, one entry per resource and ability. */
+  capabilities: { resource: string, ability: string }[],
+  /** When the delegation stops being valid. */
+  expiry?: number,
+  /** When the delegation starts being valid. */
+  notBefore?: number,
+  /** CIDs of the delegations this one builds on. */
+  parents: string[],
+}
+"#;
@@ -0,0 +1,284 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tinycloud_auth::{
+    authorization::{EncodingError, HeaderEncode, TinyCloudDelegation},
+    cacaos::{siwe::Message, siwe_cacao::SIWEPayloadConversionError},
+    codec,
+    identity::principal_did,
+    ipld_core::cid::Cid,
+    siwe_recap::{Capability, VerificationError as RecapError},
+    ssi::dids::AnyDidMethod,
+};
+
+/// A delegation received from elsewhere, decoded for inspection before it is
+/// used as a parent.
+///
+/// Mirrors the node's own view of a delegation: who granted it, to whom, what
+/// it grants, when it is valid and which delegations it builds on. Times are
+/// seconds since the epoch.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedDelegation {
+    /// CID of the delegation, as the node will record it.
+    pub cid: String,
+    pub delegator: String,
+    pub delegate: String,
+    pub capabilities: Vec<DelegatedCapability>,
+    pub expiry: Option<f64>,
+    pub not_before: Option<f64>,
+    pub parents: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatedCapability {
+    /// Full resource URI, e.g. "tinycloud:pkh:eip155:1:0x...:default/kv/path".
+    pub resource: String,
+    /// Full-URN ability, e.g. "tinycloud.kv/get".
+    pub ability: String,
+}
+
+/// A delegation's grants exactly as the node will authorize them, the same
+/// view its `/delegate/preview` route returns.
+///
+/// Unlike [`ParsedDelegation`], the delegator and delegate are reduced to
+/// their principal DIDs and each capability keeps its caveats, keyed by
+/// position as the node stores them.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegationCapabilities {
+    pub delegator: String,
+    pub delegate: String,
+    pub capabilities: Vec<ExpandedCapability>,
+    pub expiry: Option<f64>,
+    pub not_before: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedCapability {
+    pub resource: String,
+    pub ability: String,
+    /// Caveats of the capability; always empty for SIWE ReCap delegations.
+    pub caveats: BTreeMap<String, serde_json::Value>,
+}
+
+fn principal(did: &str) -> String {
+    principal_did(did).unwrap_or_else(|_| did.split('#').next().unwrap_or(did).to_string())
+}
+
+fn decode(encoded: &str) -> Result<(TinyCloudDelegation, Cid), Error> {
+    let (delegation, bytes) = TinyCloudDelegation::decode(encoded)?;
+    Ok((delegation, codec::to_event_cid(&bytes)))
+}
+
+fn delegation_info(delegation: &TinyCloudDelegation, cid: Cid) -> Result<ParsedDelegation, Error> {
+    Ok(match delegation {
+        TinyCloudDelegation::Ucan(ucan) => {
+            let payload = ucan.payload();
+            ParsedDelegation {
+                cid: cid.to_string(),
+                delegator: payload.issuer.to_string(),
+                delegate: payload.audience.to_string(),
+                capabilities: payload
+                    .attenuation
+                    .abilities()
+                    .iter()
+                    .flat_map(|(resource, abilities)| {
+                        abilities.keys().map(move |ability| DelegatedCapability {
+                            resource: resource.to_string(),
+                            ability: ability.to_string(),
+                        })
+                    })
+                    .collect(),
+                expiry: Some(payload.expiration.as_seconds()),
+                not_before: payload.not_before.map(|nbf| nbf.as_seconds()),
+                parents: payload.proof.iter().map(Cid::to_string).collect(),
+            }
+        }
+        TinyCloudDelegation::Cacao(cacao) => {
+            let payload = cacao.payload();
+            let message: Message = payload.clone().try_into()?;
+            let (capabilities, parents) =
+                match Capability::<serde_json::Value>::extract_and_verify(&message)? {
+                    Some(recap) => {
+                        let (caps, proofs) = recap.into_inner();
+                        let capabilities = caps
+                            .into_inner()
+                            .into_iter()
+                            .flat_map(|(resource, abilities)| {
+                                abilities
+                                    .into_keys()
+                                    .map(move |ability| DelegatedCapability {
+                                        resource: resource.to_string(),
+                                        ability: ability.to_string(),
+                                    })
+                            })
+                            .collect();
+                        (capabilities, proofs)
+                    }
+                    None => (Vec::new(), Vec::new()),
+                };
+            ParsedDelegation {
+                cid: cid.to_string(),
+                delegator: payload.iss.to_string(),
+                delegate: payload.aud.to_string(),
+                capabilities,
+                expiry: payload
+                    .exp
+                    .as_ref()
+                    .map(|exp| exp.as_ref().unix_timestamp() as f64),
+                not_before: payload
+                    .nbf
+                    .as_ref()
+                    .map(|nbf| nbf.as_ref().unix_timestamp() as f64),
+                parents: parents.iter().map(Cid::to_string).collect(),
+            }
+        }
+    })
+}
+
+/// Decode a delegation header value (base64url CACAO or UCAN JWT) without
+/// checking its signature.
+pub fn parse_delegation(encoded: &str) -> Result<ParsedDelegation, Error> {
+    let (delegation, cid) = decode(encoded)?;
+    delegation_info(&delegation, cid)
+}
+
+/// Decode a delegation header value and expand it into the capabilities the
+/// node will grant, without checking its signature.
+pub fn parse_delegation_capabilities(encoded: &str) -> Result<DelegationCapabilities, Error> {
+    let (delegation, cid) = decode(encoded)?;
+    let info = delegation_info(&delegation, cid)?;
+    let capabilities = match &delegation {
+        TinyCloudDelegation::Ucan(ucan) => ucan
+            .payload()
+            .attenuation
+            .abilities()
+            .iter()
+            .flat_map(|(resource, abilities)| {
+                abilities
+                    .iter()
+                    .map(move |(ability, caveats)| ExpandedCapability {
+                        resource: resource.to_string(),
+                        ability: ability.to_string(),
+                        caveats: caveats
+                            .as_ref()
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(i, nb)| {
+                                Some((i.to_string(), serde_json::to_value(nb).ok()?))
+                            })
+                            .collect(),
+                    })
+            })
+            .collect(),
+        TinyCloudDelegation::Cacao(_) => info
+            .capabilities
+            .into_iter()
+            .map(|c| ExpandedCapability {
+                resource: c.resource,
+                ability: c.ability,
+                caveats: BTreeMap::new(),
+            })
+            .collect(),
+    };
+    Ok(DelegationCapabilities {
+        delegator: principal(&info.delegator),
+        delegate: principal(&info.delegate),
+        capabilities,
+        expiry: info.expiry,
+        not_before: info.not_before,
+    })
+}
+
+/// Run the signature and validity-window checks the node applies before
+/// accepting a delegation, at `now` (seconds since the epoch).
+///
+/// Returns `Ok(false)` when the delegation decodes but fails either check;
+/// whether its parents exist and authorize it can only be decided by the node.
+pub async fn verify_delegation(encoded: &str, now: f64) -> Result<bool, Error> {
+    let (delegation, cid) = decode(encoded)?;
+    let info = delegation_info(&delegation, cid)?;
+    let signature_valid = match &delegation {
+        TinyCloudDelegation::Ucan(ucan) => ucan
+            .verify_signature(&AnyDidMethod::default())
+            .await
+            .is_ok(),
+        TinyCloudDelegation::Cacao(cacao) => cacao.verify().await.is_ok(),
+    };
+    Ok(signature_valid
+        && info.not_before.map(|nbf| nbf <= now).unwrap_or(true)
+        && info.expiry.map(|exp| exp >= now).unwrap_or(true))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to decode the delegation: {0}")]
+    Decoding(#[from] EncodingError),
+    #[error("invalid SIWE delegation: {0}")]
+    InvalidSiwe(#[from] SIWEPayloadConversionError),
+    #[error("invalid recap capabilities: {0}")]
+    InvalidRecap(#[from] RecapError),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::session::test::test_session;
+
+    #[test]
+    fn session_delegation_parses_to_its_grants() {
+        let session = test_session();
+        let encoded = serde_json::to_value(&session.delegation_header).unwrap()["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let parsed = parse_delegation(&encoded).unwrap();
+        assert_eq!(parsed.cid, session.delegation_cid.to_string());
+        assert!(parsed
+            .delegator
+            .eq_ignore_ascii_case("did:pkh:eip155:1:0x7BD63AA37326a64d458559F44432103e3d6eEDE9"));
+        assert_eq!(parsed.delegate, session.verification_method);
+        assert!(parsed
+            .capabilities
+            .iter()
+            .any(|c| c.resource.ends_with("/kv/path") && c.ability == "tinycloud.kv/get"));
+        assert!(parsed.parents.is_empty());
+        assert!(parse_delegation("not a delegation").is_err());
+    }
+
+    #[test]
+    fn recap_delegation_expands_to_one_capability_per_ability() {
+        let session = test_session();
+        let encoded = serde_json::to_value(&session.delegation_header).unwrap()["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let expanded = parse_delegation_capabilities(&encoded).unwrap();
+        assert!(expanded
+            .delegator
+            .eq_ignore_ascii_case("did:pkh:eip155:1:0x7BD63AA37326a64d458559F44432103e3d6eEDE9"));
+        assert!(!expanded.delegate.contains('#'));
+        assert!(session.verification_method.starts_with(&expanded.delegate));
+        let kv: Vec<&str> = expanded
+            .capabilities
+            .iter()
+            .filter(|c| c.resource.ends_with(":default/kv/path"))
+            .map(|c| c.ability.as_str())
+            .collect();
+        for ability in [
+            "tinycloud.kv/put",
+            "tinycloud.kv/get",
+            "tinycloud.kv/list",
+            "tinycloud.kv/del",
+            "tinycloud.kv/metadata",
+        ] {
+            assert!(kv.contains(&ability), "{ability} missing from {kv:?}");
+        }
+        assert!(expanded.capabilities.iter().all(|c| c.caveats.is_empty()));
+        assert_eq!(expanded.expiry, Some(32503680000.0));
+    }
+}
@@ -1,13 +1,13 @@
 mod definitions;
+pub mod delegation;
 pub mod host;
+pub mod revocation;
 pub mod session;
+pub mod sql;
 pub mod vault;
 
 use hex::FromHex;
-use tinycloud_auth::{
-    ipld_core::cid::Cid,
-    multihash_codetable::{Code, MultihashDigest},
-};
+use tinycloud_auth::{ipld_core::cid::Cid, multihash_codetable::MultihashDigest};
 use tinycloud_sdk_rs::{authorization::InvocationHeaders, util};
 use wasm_bindgen::prelude::*;
 
@@ -55,6 +55,21 @@ pub fn makeSpaceId(address: String, chainId: u32, name: String) -> Result<String
     .to_string())
 }
 
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn makeSpaceIdPkh(
+    namespace: String,
+    chainId: String,
+    address: String,
+    name: String,
+) -> Result<String, JsValue> {
+    Ok(
+        tinycloud_sdk_rs::util::make_space_id_pkh(&namespace, &chainId, &address, name)
+            .map_err(map_jserr)?
+            .to_string(),
+    )
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn prepareSession(config: JsValue) -> Result<JsValue, JsValue> {
@@ -72,6 +87,14 @@ pub fn completeSessionSetup(config: JsValue) -> Result<JsValue, JsValue> {
     )?)
 }
 
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn rootSession(config: JsValue) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(
+        &session::root_session(serde_wasm_bindgen::from_value(config)?).map_err(map_jserr)?,
+    )?)
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn invoke(
@@ -105,6 +128,33 @@ pub fn invoke(
     ))?)
 }
 
+/// Build the invocation headers for a SQL request against `database`
+/// (`"default"` when omitted). `sqlRequest` is the same JSON object sent as
+/// the request body, e.g. `{ action: "query", sql: "SELECT 1", params: [] }`.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn invokeSql(
+    session: JsValue,
+    sqlRequest: JsValue,
+    database: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let session: session::Session = serde_wasm_bindgen::from_value(session)?;
+    let request: sql::SqlRequest = serde_wasm_bindgen::from_value(sqlRequest)?;
+    let authz = session
+        .invoke_sql(
+            database
+                .as_deref()
+                .unwrap_or("default")
+                .parse()
+                .map_err(map_jserr)?,
+            request,
+        )
+        .map_err(map_jserr)?;
+    Ok(serde_wasm_bindgen::to_value(&InvocationHeaders::new(
+        authz,
+    ))?)
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn generateHostSIWEMessage(config: JsValue) -> Result<String, JsValue> {
@@ -123,6 +173,27 @@ pub fn siweToDelegationHeaders(signedSIWEMessage: JsValue) -> Result<JsValue, Js
     )?)
 }
 
+/// Generate the SIWE message revoking a delegation previously granted by
+/// `address`. The message's URI is `ucan:<delegationCid>`, which the server
+/// reads as the revocation target.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn generateRevocationSIWEMessage(config: JsValue) -> Result<String, JsValue> {
+    Ok(
+        revocation::generate_revocation_siwe_message(serde_wasm_bindgen::from_value(config)?)
+            .map_err(map_jserr)?
+            .to_string(),
+    )
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn siweToRevocationHeaders(signedSIWEMessage: JsValue) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(
+        &revocation::siwe_to_revocation_headers(serde_wasm_bindgen::from_value(signedSIWEMessage)?),
+    )?)
+}
+
 /// Create a multi-resource delegation UCAN from a session to another DID.
 ///
 /// Produces a **single** UCAN JWT that encodes every `(service, path, actions)`
@@ -223,6 +294,53 @@ pub fn parseRecapFromSiwe(siweString: &str) -> Result<JsValue, JsValue> {
     Ok(serde_wasm_bindgen::to_value(&entries)?)
 }
 
+/// Decode a delegation received from another party (the value of its
+/// `Authorization` header) so it can be shown before being used as a parent.
+///
+/// # Returns
+/// A [`delegation::ParsedDelegation`]: `{ cid, delegator, delegate,
+/// capabilities: [{ resource, ability }], expiry?, notBefore?, parents }`.
+/// The signature is not checked; use `verifyDelegation` for that.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn parseDelegation(encoded: &str) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(
+        &delegation::parse_delegation(encoded).map_err(map_jserr)?,
+    )?)
+}
+
+/// Expand a delegation into exactly the capabilities the node will grant for
+/// it, matching the node's `/delegate/preview` route.
+///
+/// # Returns
+/// A [`delegation::DelegationCapabilities`]: `{ delegator, delegate,
+/// capabilities: [{ resource, ability, caveats }], expiry?, notBefore? }`,
+/// with principal DIDs and one entry per resource and ability. The signature
+/// is not checked.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn parseDelegationCapabilities(encoded: &str) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(
+        &delegation::parse_delegation_capabilities(encoded).map_err(map_jserr)?,
+    )?)
+}
+
+/// Check a delegation's signature and that it is valid right now.
+///
+/// Resolves to `false` when either check fails and rejects when the input is
+/// not a delegation at all. Whether its parents authorize it is only known to
+/// the node it is submitted to.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub async fn verifyDelegation(encoded: String) -> Result<bool, JsValue> {
+    use tinycloud_auth::ssi::claims::chrono;
+    // chrono rather than time, which has no clock on wasm32-unknown-unknown
+    let now = chrono::Utc::now().timestamp_millis() as f64 / 1000.0;
+    delegation::verify_delegation(&encoded, now)
+        .await
+        .map_err(map_jserr)
+}
+
 /// Compute a CID from data bytes using Blake3_256 hash.
 ///
 /// This uses the same hashing algorithm as the TinyCloud server,
@@ -237,7 +355,7 @@ pub fn parseRecapFromSiwe(siweString: &str) -> Result<JsValue, JsValue> {
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn computeCid(data: &[u8], codec: u64) -> String {
-    let hash = Code::Blake3_256.digest(data);
+    let hash = tinycloud_auth::codec::CONTENT_HASH.digest(data);
     let cid = Cid::new_v1(codec, hash);
     cid.to_string()
 }
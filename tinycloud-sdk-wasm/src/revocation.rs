@@ -0,0 +1,89 @@
+use http::uri::Authority;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use tinycloud_auth::{
+    authorization::TinyCloudRevocation,
+    cacaos::{
+        siwe::{generate_nonce, Message, TimeStamp, Version},
+        siwe_cacao::{Header as SiweHeader, SiweCacao},
+    },
+    ipld_core::cid::Cid,
+};
+
+use tinycloud_sdk_rs::authorization::RevocationHeaders;
+
+use crate::host::{Error, SignedMessage};
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationConfig {
+    #[serde(with = "tinycloud_sdk_rs::serde_siwe::address")]
+    pub address: [u8; 20],
+    pub chain_id: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub domain: Authority,
+    #[serde_as(as = "DisplayFromStr")]
+    pub issued_at: TimeStamp,
+    /// CID of the delegation being revoked.
+    #[serde_as(as = "DisplayFromStr")]
+    pub delegation_cid: Cid,
+}
+
+impl TryFrom<RevocationConfig> for Message {
+    type Error = String;
+    fn try_from(c: RevocationConfig) -> Result<Self, String> {
+        // the server resolves the revoked delegation from the `ucan:<cid>` audience
+        Ok(Self {
+            scheme: None,
+            address: c.address,
+            chain_id: c.chain_id,
+            domain: c.domain,
+            issued_at: c.issued_at,
+            uri: format!("ucan:{}", c.delegation_cid)
+                .try_into()
+                .map_err(|e| format!("error building revocation target URI: {e}"))?,
+            nonce: generate_nonce(),
+            statement: None,
+            resources: vec![],
+            version: Version::V1,
+            not_before: None,
+            expiration_time: None,
+            request_id: None,
+        })
+    }
+}
+
+pub fn generate_revocation_siwe_message(config: RevocationConfig) -> Result<Message, Error> {
+    Message::try_from(config).map_err(Error::UnableToGenerateSIWEMessage)
+}
+
+pub fn siwe_to_revocation_headers(signed_message: SignedMessage) -> RevocationHeaders {
+    RevocationHeaders::new(TinyCloudRevocation::Cacao(Box::new(SiweCacao::new(
+        signed_message.siwe.into(),
+        signed_message.signature,
+        SiweHeader,
+    ))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn revocation_message_targets_the_delegation() {
+        let cid = "bafyreib5v4o2r6xs3gkz2ojpfkrmxvfqjw6u4ilx6gxmd2jydyl3rtyxse";
+        let config = json!({
+            "address": "0x7BD63AA37326a64d458559F44432103e3d6eEDE9",
+            "chainId": 1u8,
+            "domain": "example.com",
+            "issuedAt": "2022-01-01T00:00:00.000Z",
+            "delegationCid": cid,
+        });
+        let message =
+            generate_revocation_siwe_message(serde_json::from_value(config).unwrap()).unwrap();
+        assert_eq!(message.uri.as_str(), format!("ucan:{cid}"));
+        assert!(message.resources.is_empty());
+    }
+}
@@ -5,15 +5,15 @@ use serde_with::{serde_as, DisplayFromStr};
 use std::collections::HashMap;
 use tinycloud_auth::{
     authorization::{
-        make_invocation, make_invocation_from_uris, InvocationError, InvocationOptions,
-        TinyCloudDelegation, TinyCloudInvocation,
+        make_invocation, make_invocation_from_uris, HeaderEncode, InvocationError,
+        InvocationOptions, TinyCloudDelegation, TinyCloudInvocation,
     },
     cacaos::{
         siwe::{generate_nonce, Message, TimeStamp, Version as SIWEVersion},
         siwe_cacao::{Header as SiweHeader, Signature, SiweCacao},
     },
+    codec,
     ipld_core::cid::Cid,
-    multihash_codetable::{Code, MultihashDigest},
     resolver::DID_METHODS,
     resource::{
         iri_string::types::{UriFragmentString, UriQueryString, UriString},
@@ -30,6 +30,8 @@ use tinycloud_auth::{
 };
 use tinycloud_sdk_rs::authorization::DelegationHeaders;
 
+use crate::sql::{sql_ability, SqlInvocationError, SqlRequest};
+
 type AbilitiesMap = HashMap<Service, HashMap<Path, Vec<Ability>>>;
 
 #[serde_as]
@@ -276,6 +278,48 @@ impl Session {
         )
     }
 
+    /// Build one invocation covering every op in `ops`.
+    ///
+    /// Each op targets its own resource in this session's space; ops naming
+    /// the same resource have their abilities merged. Per-op facts are
+    /// appended to the invocation's facts in op order.
+    pub fn invoke_batch(
+        &self,
+        ops: Vec<InvocationOp>,
+    ) -> Result<TinyCloudInvocation, InvocationError> {
+        let mut facts = Vec::new();
+        let mut targets = Vec::with_capacity(ops.len());
+        for op in ops {
+            facts.extend(op.facts.into_iter().flatten());
+            targets.push((op.service, op.path, op.query, op.fragment, op.abilities));
+        }
+        self.invoke(targets, (!facts.is_empty()).then_some(facts))
+    }
+
+    /// Invoke the `sql` service on `database` for `request`.
+    ///
+    /// The ability is picked from the request's statements via
+    /// [`sql_ability`], and the request itself is attached as a
+    /// `{ "sqlRequest": ... }` fact so the invocation records what it
+    /// authorized. The same request must still be sent as the body.
+    pub fn invoke_sql(
+        &self,
+        database: Path,
+        request: SqlRequest,
+    ) -> Result<TinyCloudInvocation, SqlInvocationError> {
+        let ability = sql_ability(&request)?;
+        Ok(self.invoke(
+            std::iter::once((
+                "sql".parse().expect("sql is a valid service name"),
+                database,
+                None,
+                None,
+                std::iter::once(ability),
+            )),
+            Some(vec![serde_json::json!({ "sqlRequest": request })]),
+        )?)
+    }
+
     /// Create a multi-resource delegation UCAN from this session to another DID.
     ///
     /// Unlike invocations (which are self-issued for immediate use), delegations
@@ -315,127 +359,172 @@ impl Session {
         expiration_secs: f64,
         not_before_secs: Option<f64>,
     ) -> Result<DelegationResult, DelegationError> {
-        use std::str::FromStr;
+        sign_delegation(
+            &self.jwk,
+            &self.verification_method,
+            vec![self.delegation_cid],
+            delegate_did,
+            space_id,
+            abilities,
+            expiration_secs,
+            not_before_secs,
+        )
+    }
+}
 
-        if abilities.is_empty() {
-            return Err(DelegationError::EmptyAbilities);
+/// Sign a delegation UCAN of `abilities` in `space_id` from the key `issuer`,
+/// as its verification method `issuer_vm`, citing `proof`.
+#[allow(clippy::too_many_arguments)]
+fn sign_delegation(
+    issuer: &JWK,
+    issuer_vm: &str,
+    proof: Vec<Cid>,
+    delegate_did: &str,
+    space_id: &SpaceId,
+    abilities: AbilitiesMap,
+    expiration_secs: f64,
+    not_before_secs: Option<f64>,
+) -> Result<DelegationResult, DelegationError> {
+    use std::str::FromStr;
+
+    if abilities.is_empty() {
+        return Err(DelegationError::EmptyAbilities);
+    }
+
+    // Build capabilities (type parameter is the caveats type, using empty
+    // array to match invocation / session capability encoding).
+    //
+    // We walk the full (service, path, actions) tree so that a single UCAN
+    // encodes every entry. `Capabilities::with_actions` is keyed by the
+    // resource URI, which already embeds (space, service, path), so
+    // distinct (service, path) tuples naturally end up as distinct entries
+    // in the underlying `att:` map. This is the same encoding path used
+    // by `SessionConfig::into_message`.
+    let mut caps = tinycloud_auth::ucan_capabilities_object::Capabilities::<[(); 0]>::new();
+    let mut resources: Vec<DelegatedResource> = Vec::new();
+
+    // Sort services and paths so the resulting `resources` vector is
+    // deterministic regardless of HashMap iteration order. This keeps
+    // test assertions stable and makes the JS side's read-back predictable.
+    let mut services: Vec<(Service, HashMap<Path, Vec<Ability>>)> = abilities.into_iter().collect();
+    services.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+    for (service, paths_map) in services {
+        if paths_map.is_empty() {
+            return Err(DelegationError::EmptyPathsForService(service.to_string()));
         }
 
-        // Build capabilities (type parameter is the caveats type, using empty
-        // array to match invocation / session capability encoding).
-        //
-        // We walk the full (service, path, actions) tree so that a single UCAN
-        // encodes every entry. `Capabilities::with_actions` is keyed by the
-        // resource URI, which already embeds (space, service, path), so
-        // distinct (service, path) tuples naturally end up as distinct entries
-        // in the underlying `att:` map. This is the same encoding path used
-        // by `SessionConfig::into_message`.
-        let mut caps = tinycloud_auth::ucan_capabilities_object::Capabilities::<[(); 0]>::new();
-        let mut resources: Vec<DelegatedResource> = Vec::new();
-
-        // Sort services and paths so the resulting `resources` vector is
-        // deterministic regardless of HashMap iteration order. This keeps
-        // test assertions stable and makes the JS side's read-back predictable.
-        let mut services: Vec<(Service, HashMap<Path, Vec<Ability>>)> =
-            abilities.into_iter().collect();
-        services.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
-
-        for (service, paths_map) in services {
-            if paths_map.is_empty() {
-                return Err(DelegationError::EmptyPathsForService(service.to_string()));
+        let mut paths: Vec<(Path, Vec<Ability>)> = paths_map.into_iter().collect();
+        paths.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+        for (path, path_actions) in paths {
+            if path_actions.is_empty() {
+                return Err(DelegationError::EmptyActionsForPath {
+                    service: service.to_string(),
+                    path: path.to_string(),
+                });
             }
 
-            let mut paths: Vec<(Path, Vec<Ability>)> = paths_map.into_iter().collect();
-            paths.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            // Follow the same empty-path convention as `into_message`:
+            // an empty-string path means "no path segment" (the resource
+            // URI has no path component), which matches how the session's
+            // own recap encodes space-wide grants.
+            let path_opt = if path.as_str().is_empty() {
+                None
+            } else {
+                Some(path.clone())
+            };
+
+            let is_raw_encryption_resource = service.as_str() == "encryption"
+                && path.as_str().starts_with("urn:tinycloud:encryption:");
+            let resource_uri: UriString = if is_raw_encryption_resource {
+                path.as_str()
+                    .parse()
+                    .map_err(|err| DelegationError::InvalidRawResource(format!("{err}")))?
+            } else {
+                space_id
+                    .clone()
+                    .to_resource(service.clone(), path_opt, None, None)
+                    .as_uri()
+            };
+
+            let action_strings: Vec<String> = path_actions.iter().map(|a| a.to_string()).collect();
+
+            // Extend the capability object with this (resource, actions)
+            // pair. The ucan-capabilities-object crate keys internally by
+            // resource URI, so each iteration adds a distinct entry.
+            caps.with_actions(resource_uri, path_actions.into_iter().map(|a| (a, [])));
+
+            resources.push(DelegatedResource {
+                service: service.to_string(),
+                space: if is_raw_encryption_resource {
+                    "encryption".to_string()
+                } else {
+                    space_id.to_string()
+                },
+                path: path.to_string(),
+                actions: action_strings,
+            });
+        }
+    }
 
-            for (path, path_actions) in paths {
-                if path_actions.is_empty() {
-                    return Err(DelegationError::EmptyActionsForPath {
-                        service: service.to_string(),
-                        path: path.to_string(),
-                    });
-                }
+    // Build UCAN payload (F=serde_json::Value for facts, C=[();0] for caveats)
+    let payload: Payload<serde_json::Value, [(); 0]> = Payload {
+        issuer: DIDURLBuf::from_str(issuer_vm).map_err(DelegationError::InvalidIssuer)?,
+        audience: DIDBuf::from_str(delegate_did).map_err(DelegationError::InvalidAudience)?,
+        not_before: not_before_secs
+            .map(NumericDate::try_from_seconds)
+            .transpose()
+            .map_err(DelegationError::InvalidNotBefore)?,
+        expiration: NumericDate::try_from_seconds(expiration_secs)
+            .map_err(DelegationError::InvalidExpiration)?,
+        nonce: Some(format!("urn:uuid:{}", uuid::Uuid::new_v4())),
+        facts: None,
+        proof,
+        attenuation: caps,
+    };
 
-                // Follow the same empty-path convention as `into_message`:
-                // an empty-string path means "no path segment" (the resource
-                // URI has no path component), which matches how the session's
-                // own recap encodes space-wide grants.
-                let path_opt = if path.as_str().is_empty() {
-                    None
-                } else {
-                    Some(path.clone())
-                };
-
-                let is_raw_encryption_resource = service.as_str() == "encryption"
-                    && path.as_str().starts_with("urn:tinycloud:encryption:");
-                let resource_uri: UriString = if is_raw_encryption_resource {
-                    path.as_str()
-                        .parse()
-                        .map_err(|err| DelegationError::InvalidRawResource(format!("{err}")))?
-                } else {
-                    space_id
-                        .clone()
-                        .to_resource(service.clone(), path_opt, None, None)
-                        .as_uri()
-                };
+    // Sign the UCAN
+    let ucan = payload
+        .sign(issuer.get_algorithm().unwrap_or_default(), issuer)
+        .map_err(DelegationError::SigningError)?;
 
-                let action_strings: Vec<String> =
-                    path_actions.iter().map(|a| a.to_string()).collect();
+    // Encode the UCAN to JWT string
+    let delegation_str = ucan.encode().map_err(DelegationError::EncodingError)?;
 
-                // Extend the capability object with this (resource, actions)
-                // pair. The ucan-capabilities-object crate keys internally by
-                // resource URI, so each iteration adds a distinct entry.
-                caps.with_actions(resource_uri, path_actions.into_iter().map(|a| (a, [])));
+    let cid = codec::to_event_cid(delegation_str.as_bytes());
 
-                resources.push(DelegatedResource {
-                    service: service.to_string(),
-                    space: if is_raw_encryption_resource {
-                        "encryption".to_string()
-                    } else {
-                        space_id.to_string()
-                    },
-                    path: path.to_string(),
-                    actions: action_strings,
-                });
-            }
-        }
+    Ok(DelegationResult {
+        delegation: delegation_str,
+        cid: cid.to_string(),
+        delegate_did: delegate_did.to_string(),
+        expiry: expiration_secs,
+        resources,
+    })
+}
 
-        // Build UCAN payload (F=serde_json::Value for facts, C=[();0] for caveats)
-        let payload: Payload<serde_json::Value, [(); 0]> = Payload {
-            issuer: DIDURLBuf::from_str(&self.verification_method)
-                .map_err(DelegationError::InvalidIssuer)?,
-            audience: DIDBuf::from_str(delegate_did).map_err(DelegationError::InvalidAudience)?,
-            not_before: not_before_secs
-                .map(NumericDate::try_from_seconds)
-                .transpose()
-                .map_err(DelegationError::InvalidNotBefore)?,
-            expiration: NumericDate::try_from_seconds(expiration_secs)
-                .map_err(DelegationError::InvalidExpiration)?,
-            nonce: Some(format!("urn:uuid:{}", uuid::Uuid::new_v4())),
-            facts: None,
-            proof: vec![self.delegation_cid],
-            attenuation: caps,
-        };
+/// One resource and the abilities invoked on it, as part of
+/// [`Session::invoke_batch`].
+#[derive(Debug, Clone)]
+pub struct InvocationOp {
+    pub service: Service,
+    pub path: Path,
+    pub query: Option<UriQueryString>,
+    pub fragment: Option<UriFragmentString>,
+    pub abilities: Vec<Ability>,
+    pub facts: Option<Vec<serde_json::Value>>,
+}
 
-        // Sign the UCAN
-        let ucan = payload
-            .sign(self.jwk.get_algorithm().unwrap_or_default(), &self.jwk)
-            .map_err(DelegationError::SigningError)?;
-
-        // Encode the UCAN to JWT string
-        let delegation_str = ucan.encode().map_err(DelegationError::EncodingError)?;
-
-        // Calculate CID (using raw codec for JWT bytes, like invocations)
-        let hash = Code::Blake3_256.digest(delegation_str.as_bytes());
-        let cid = Cid::new_v1(0x55, hash); // 0x55 = raw codec
-
-        Ok(DelegationResult {
-            delegation: delegation_str,
-            cid: cid.to_string(),
-            delegate_did: delegate_did.to_string(),
-            expiry: expiration_secs,
-            resources,
-        })
+impl InvocationOp {
+    pub fn new(service: Service, path: Path, abilities: Vec<Ability>) -> Self {
+        Self {
+            service,
+            path,
+            query: None,
+            fragment: None,
+            abilities,
+            facts: None,
+        }
     }
 }
 
@@ -628,19 +717,7 @@ pub fn prepare_session(config: SessionConfig) -> Result<PreparedSession, Error>
         delegate_uri.clone()
     } else {
         // For session key delegation: derive from the JWK
-        // HACK bit of a hack here, because we know exactly how did:key works
-        // ideally we should use the did resolver to resolve the DID and find the
-        // right verification method, to support any arbitrary method.
-        let mut vm = DID_METHODS.generate(&jwk, "key")?.to_string();
-        let fragment = vm
-            .rsplit_once(':')
-            .ok_or_else(|| Error::UnableToGenerateSIWEMessage("Failed to calculate DID VM".into()))?
-            .1
-            .to_string();
-        // Create a proper DID URL with fragment: did:key:z6Mk...#z6Mk...
-        vm.push('#');
-        vm.push_str(&fragment);
-        vm
+        did_key_vm(&jwk)?
     };
 
     let space_id = config.space_id.clone();
@@ -666,10 +743,7 @@ pub fn complete_session_setup(signed_session: SignedSession) -> Result<Session,
         SiweHeader,
     );
     let serialised = serde_ipld_dagcbor::to_vec(&delegation)?;
-    let hash = Code::Blake3_256.digest(&serialised);
-    // Use raw codec 0x55 to match server behavior
-    // Server always returns CIDs with raw codec for consistency
-    let delegation_cid = Cid::new_v1(0x55, hash);
+    let delegation_cid = codec::to_event_cid(&serialised);
     let delegation_header =
         DelegationHeaders::new(TinyCloudDelegation::Cacao(Box::new(delegation)));
 
@@ -683,6 +757,95 @@ pub fn complete_session_setup(signed_session: SignedSession) -> Result<Session,
     })
 }
 
+/// The `did:key` verification method of `jwk`.
+fn did_key_vm(jwk: &JWK) -> Result<String, Error> {
+    // HACK bit of a hack here, because we know exactly how did:key works
+    // ideally we should use the did resolver to resolve the DID and find the
+    // right verification method, to support any arbitrary method.
+    let mut vm = DID_METHODS.generate(jwk, "key")?.to_string();
+    let fragment = vm
+        .rsplit_once(':')
+        .ok_or_else(|| Error::UnableToGenerateSIWEMessage("Failed to calculate DID VM".into()))?
+        .1
+        .to_string();
+    // Create a proper DID URL with fragment: did:key:z6Mk...#z6Mk...
+    vm.push('#');
+    vm.push_str(&fragment);
+    Ok(vm)
+}
+
+/// Settings for [`root_session`].
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootSessionConfig {
+    pub abilities: AbilitiesMap,
+    pub space_id: SpaceId,
+    /// The key of the `did:key` the space belongs to.
+    pub root_jwk: JWK,
+    /// When the session expires, in seconds since the epoch.
+    pub expiration_secs: f64,
+    #[serde(default)]
+    pub not_before_secs: Option<f64>,
+    /// The session key; one is generated if unset.
+    #[serde(default)]
+    pub jwk: Option<JWK>,
+}
+
+/// Start a session in a space owned by a `did:key` without a SIWE round-trip:
+/// the root key delegates `abilities` straight to the session key. Nodes
+/// accept the delegation like any other from the space's root, checking its
+/// issuer against the space's DID.
+pub fn root_session(config: RootSessionConfig) -> Result<Session, RootSessionError> {
+    let root_vm = did_key_vm(&config.root_jwk)?;
+    let root_did = root_vm
+        .split_once('#')
+        .map_or(root_vm.as_str(), |(did, _)| did);
+    if root_did != config.space_id.did().as_str() {
+        return Err(RootSessionError::NotSpaceRoot(config.space_id.to_string()));
+    }
+    let mut jwk = match config.jwk {
+        Some(jwk) => jwk,
+        None => JWK::generate_ed25519().map_err(Error::from)?,
+    };
+    jwk.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+    let verification_method = did_key_vm(&jwk)?;
+    let session_did = verification_method
+        .split_once('#')
+        .map_or(verification_method.as_str(), |(did, _)| did);
+
+    let delegation = sign_delegation(
+        &config.root_jwk,
+        &root_vm,
+        vec![],
+        session_did,
+        &config.space_id,
+        config.abilities,
+        config.expiration_secs,
+        config.not_before_secs,
+    )?;
+    let (ucan, _) = TinyCloudDelegation::decode(&delegation.delegation)?;
+    Ok(Session {
+        delegation_header: DelegationHeaders::new(ucan),
+        delegation_cid: codec::to_event_cid(delegation.delegation.as_bytes()),
+        jwk,
+        space_id: config.space_id,
+        additional_spaces: None,
+        verification_method,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RootSessionError {
+    #[error("the root key is not the DID of space {0}")]
+    NotSpaceRoot(String),
+    #[error(transparent)]
+    Session(#[from] Error),
+    #[error(transparent)]
+    Delegation(#[from] DelegationError),
+    #[error("unable to decode the root delegation: {0}")]
+    Encoding(#[from] tinycloud_auth::authorization::EncodingError),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("unable to generate session key: {0}")]
@@ -740,6 +903,79 @@ pub mod test {
             .expect("failed to create invocation");
     }
 
+    #[test]
+    fn batch_invocation_carries_every_op() {
+        let session = test_session();
+        let op = |path: &str, ability: &str| {
+            InvocationOp::new(
+                "kv".parse().unwrap(),
+                path.parse().unwrap(),
+                vec![ability.parse().unwrap()],
+            )
+        };
+        let mut del = op("path/b", "tinycloud.kv/del");
+        del.facts = Some(vec![json!({ "reason": "cleanup" })]);
+
+        let invocation = session
+            .invoke_batch(vec![
+                op("path/a", "tinycloud.kv/put"),
+                del,
+                op("path/c", "tinycloud.kv/get"),
+            ])
+            .expect("failed to create batch invocation");
+
+        let mut pairs: Vec<(String, String)> = invocation
+            .payload()
+            .attenuation
+            .abilities()
+            .iter()
+            .flat_map(|(resource, abilities)| {
+                abilities
+                    .keys()
+                    .map(move |a| (resource.to_string(), a.to_string()))
+            })
+            .collect();
+        pairs.sort();
+        let resource = |path: &str| {
+            session
+                .space_id
+                .clone()
+                .to_resource(
+                    "kv".parse().unwrap(),
+                    Some(path.parse().unwrap()),
+                    None,
+                    None,
+                )
+                .as_uri()
+                .to_string()
+        };
+        assert_eq!(
+            pairs,
+            vec![
+                (resource("path/a"), "tinycloud.kv/put".to_string()),
+                (resource("path/b"), "tinycloud.kv/del".to_string()),
+                (resource("path/c"), "tinycloud.kv/get".to_string()),
+            ]
+        );
+        assert_eq!(
+            invocation.payload().facts,
+            Some(vec![json!({ "reason": "cleanup" })])
+        );
+    }
+
+    #[test]
+    fn sql_invocations_carry_their_request() {
+        let invocation = test_session()
+            .invoke_sql(
+                "mydb".parse().unwrap(),
+                serde_json::from_value(json!({ "action": "query", "sql": "SELECT 1" })).unwrap(),
+            )
+            .expect("failed to create SQL invocation");
+        let facts = invocation.payload().facts.clone().unwrap();
+        assert_eq!(facts[0]["sqlRequest"]["sql"], "SELECT 1");
+        assert_eq!(facts[0]["sqlRequest"]["action"], "query");
+    }
+
     #[test]
     fn parse_recap_roundtrip() {
         // Build a SIWE with a known set of capabilities, stringify it, then
@@ -1531,4 +1767,52 @@ pub mod test {
             }
         }
     }
+
+    #[test]
+    fn root_sessions_are_delegated_by_the_space_did() {
+        let mut root = JWK::generate_ed25519().unwrap();
+        root.algorithm = Some(tinycloud_auth::ssi::jwk::Algorithm::EdDSA);
+        let root_did = DID_METHODS.generate(&root, "key").unwrap();
+        let space = SpaceId::new(root_did.clone(), "default".parse().unwrap());
+        let config = |space: &SpaceId| {
+            serde_json::from_value::<RootSessionConfig>(json!({
+                "abilities": { "kv": { "notes/": ["tinycloud.kv/get"] } },
+                "spaceId": space.to_string(),
+                "rootJwk": root,
+                "expirationSecs": 4_000_000_000.0,
+            }))
+            .unwrap()
+        };
+
+        let session = root_session(config(&space)).unwrap();
+        let header = serde_json::to_value(&session.delegation_header).unwrap()["Authorization"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let (TinyCloudDelegation::Ucan(ucan), _) = TinyCloudDelegation::decode(&header).unwrap()
+        else {
+            panic!("root sessions are delegated with a UCAN");
+        };
+        let payload = ucan.payload();
+        assert!(payload.issuer.as_str().starts_with(root_did.as_str()));
+        assert!(session
+            .verification_method
+            .starts_with(payload.audience.as_str()));
+        assert!(payload.proof.is_empty());
+        assert_eq!(
+            session.delegation_cid,
+            codec::to_event_cid(header.as_bytes())
+        );
+
+        let other = SpaceId::new(
+            DID_METHODS
+                .generate(&JWK::generate_ed25519().unwrap(), "key")
+                .unwrap(),
+            "default".parse().unwrap(),
+        );
+        assert!(matches!(
+            root_session(config(&other)),
+            Err(RootSessionError::NotSpaceRoot(_))
+        ));
+    }
 }
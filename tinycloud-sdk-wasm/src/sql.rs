@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::{ast::Statement, dialect::SQLiteDialect, parser::Parser};
+use tinycloud_auth::{authorization::InvocationError, siwe_recap::Ability};
+
+/// A request to the TinyCloud SQL service, shaped like the server's
+/// `SqlRequest` and sent as the body of a `sql` invocation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action")]
+pub enum SqlRequest {
+    #[serde(rename = "query")]
+    Query {
+        sql: String,
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+        #[serde(default, rename = "maxRows", skip_serializing_if = "Option::is_none")]
+        max_rows: Option<usize>,
+        #[serde(default, rename = "maxBytes", skip_serializing_if = "Option::is_none")]
+        max_bytes: Option<usize>,
+    },
+    #[serde(rename = "execute")]
+    Execute {
+        sql: String,
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema: Option<Vec<String>>,
+    },
+    #[serde(rename = "batch")]
+    Batch { statements: Vec<SqlStatement> },
+    #[serde(rename = "executeStatement")]
+    ExecuteStatement {
+        name: String,
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+    },
+    #[serde(rename = "export")]
+    Export,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SqlStatement {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<serde_json::Value>,
+}
+
+/// What a single statement asks of the database, in the terms the server
+/// checks a `tinycloud.sql/*` ability against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatementKind {
+    Read,
+    Write,
+    Ddl,
+    Admin,
+}
+
+/// The `tinycloud.sql/*` ability a SQL request needs, classifying its
+/// statements as the server does: `PRAGMA` and `ATTACH` need `admin`, requests
+/// only defining schema need `schema` (which allows nothing else), any other
+/// change needs `write`, and queries and exports need `read`. Named statements
+/// are kept in the delegation's caveats, out of the SDK's sight, so they ask
+/// for `write`.
+pub fn sql_ability(request: &SqlRequest) -> Result<Ability, SqlInvocationError> {
+    let statements: Vec<&str> = match request {
+        SqlRequest::Query { sql, .. } => vec![sql],
+        SqlRequest::Execute { sql, schema, .. } => schema
+            .iter()
+            .flatten()
+            .chain(std::iter::once(sql))
+            .map(String::as_str)
+            .collect(),
+        SqlRequest::Batch { statements } => statements.iter().map(|s| s.sql.as_str()).collect(),
+        SqlRequest::ExecuteStatement { .. } => return Ok(sql_ability_named("write")),
+        SqlRequest::Export => return Ok(sql_ability_named("read")),
+    };
+    let kinds = statements
+        .into_iter()
+        .map(statement_kind)
+        .collect::<Result<Vec<_>, _>>()?;
+    let ability = if kinds.contains(&StatementKind::Admin) {
+        "admin"
+    } else if kinds.iter().all(|kind| *kind == StatementKind::Read) {
+        "read"
+    } else if kinds.iter().all(|kind| *kind == StatementKind::Ddl) {
+        "schema"
+    } else {
+        "write"
+    };
+    Ok(sql_ability_named(ability))
+}
+
+fn sql_ability_named(name: &str) -> Ability {
+    format!("tinycloud.sql/{name}")
+        .parse()
+        .expect("sql abilities are valid")
+}
+
+/// Classify `sql`, which like on the server must be exactly one statement of
+/// a kind the SQL service runs.
+fn statement_kind(sql: &str) -> Result<StatementKind, SqlInvocationError> {
+    let statements = Parser::parse_sql(&SQLiteDialect {}, sql)
+        .map_err(|e| SqlInvocationError::InvalidSql(e.to_string()))?;
+    let [statement] = statements.as_slice() else {
+        return Err(SqlInvocationError::InvalidSql(
+            "exactly one SQL statement is required".to_string(),
+        ));
+    };
+    Ok(match statement {
+        Statement::Query(_) => StatementKind::Read,
+        Statement::Insert { .. } | Statement::Update { .. } | Statement::Delete { .. } => {
+            StatementKind::Write
+        }
+        Statement::CreateTable { .. }
+        | Statement::CreateView { .. }
+        | Statement::AlterTable { .. }
+        | Statement::Drop { .. }
+        | Statement::CreateIndex { .. } => StatementKind::Ddl,
+        Statement::Pragma { .. } | Statement::AttachDatabase { .. } => StatementKind::Admin,
+        other => {
+            return Err(SqlInvocationError::InvalidSql(format!(
+                "statement type not allowed: {other}"
+            )))
+        }
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SqlInvocationError {
+    #[error("invalid SQL request: {0}")]
+    InvalidSql(String),
+    #[error(transparent)]
+    Invocation(#[from] InvocationError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the abilities of valid requests are checked against the node's own
+    // classification in tinycloud-node-server/tests/sdk_sql_ability.rs
+
+    #[test]
+    fn requests_the_node_would_refuse_need_no_ability() {
+        for invalid in ["SELECT 1; SELECT 2", "NOT SQL", ""] {
+            let request = SqlRequest::Query {
+                sql: invalid.to_string(),
+                params: Vec::new(),
+                max_rows: None,
+                max_bytes: None,
+            };
+            assert!(
+                matches!(
+                    sql_ability(&request),
+                    Err(SqlInvocationError::InvalidSql(_))
+                ),
+                "{invalid}"
+            );
+        }
+    }
+}